@@ -4,12 +4,11 @@
 
 use criterion::{BenchmarkId, Criterion, Throughput, black_box, criterion_group, criterion_main};
 use std::io::{Cursor, Write};
-use std::path::PathBuf;
 use tempfile::TempDir;
 use zip::CompressionMethod;
 use zip::write::{SimpleFileOptions, ZipWriter};
 
-use unzip::{Args, extract_archive};
+use unzip::{ExtractOptions, extract_archive};
 
 /// Create a test ZIP archive with the specified number of files and size per file
 fn create_test_archive(num_files: usize, bytes_per_file: usize) -> Vec<u8> {
@@ -49,14 +48,9 @@ fn bench_extract_small(c: &mut Criterion) {
             let cursor = Cursor::new(&zip_data);
             let mut archive = zip::ZipArchive::new(cursor).unwrap();
 
-            let args = Args {
-                zipfile: PathBuf::from("test.zip"),
-                output_dir: Some(temp_dir.path().to_path_buf()),
-                quiet: 2, // Suppress all output
-                ..Default::default()
-            };
+            let options = ExtractOptions::new().output_dir(temp_dir.path()).quiet(2); // Suppress all output
 
-            extract_archive(&mut archive, black_box(&args)).unwrap();
+            extract_archive(&mut archive, black_box(&options)).unwrap();
         });
     });
 
@@ -83,14 +77,9 @@ fn bench_extract_medium(c: &mut Criterion) {
             let cursor = Cursor::new(&zip_data);
             let mut archive = zip::ZipArchive::new(cursor).unwrap();
 
-            let args = Args {
-                zipfile: PathBuf::from("test.zip"),
-                output_dir: Some(temp_dir.path().to_path_buf()),
-                quiet: 2,
-                ..Default::default()
-            };
+            let options = ExtractOptions::new().output_dir(temp_dir.path()).quiet(2);
 
-            extract_archive(&mut archive, black_box(&args)).unwrap();
+            extract_archive(&mut archive, black_box(&options)).unwrap();
         });
     });
 
@@ -116,14 +105,9 @@ fn bench_extract_many_small(c: &mut Criterion) {
             let cursor = Cursor::new(&zip_data);
             let mut archive = zip::ZipArchive::new(cursor).unwrap();
 
-            let args = Args {
-                zipfile: PathBuf::from("test.zip"),
-                output_dir: Some(temp_dir.path().to_path_buf()),
-                quiet: 2,
-                ..Default::default()
-            };
+            let options = ExtractOptions::new().output_dir(temp_dir.path()).quiet(2);
 
-            extract_archive(&mut archive, black_box(&args)).unwrap();
+            extract_archive(&mut archive, black_box(&options)).unwrap();
         });
     });
 
@@ -159,15 +143,10 @@ fn bench_glob_filtering(c: &mut Criterion) {
             let cursor = Cursor::new(&zip_data);
             let mut archive = zip::ZipArchive::new(cursor).unwrap();
 
-            let args = Args {
-                zipfile: PathBuf::from("test.zip"),
-                output_dir: Some(temp_dir.path().to_path_buf()),
-                patterns: vec!["*.rs".to_string()],
-                quiet: 2,
-                ..Default::default()
-            };
+            let options =
+                ExtractOptions::new().output_dir(temp_dir.path()).patterns(["*.rs"]).quiet(2);
 
-            extract_archive(&mut archive, black_box(&args)).unwrap();
+            extract_archive(&mut archive, black_box(&options)).unwrap();
         });
     });
 
@@ -177,15 +156,44 @@ fn bench_glob_filtering(c: &mut Criterion) {
             let cursor = Cursor::new(&zip_data);
             let mut archive = zip::ZipArchive::new(cursor).unwrap();
 
-            let args = Args {
-                zipfile: PathBuf::from("test.zip"),
-                output_dir: Some(temp_dir.path().to_path_buf()),
-                patterns: vec!["*.rs".to_string(), "*.md".to_string()],
-                quiet: 2,
-                ..Default::default()
-            };
+            let options = ExtractOptions::new()
+                .output_dir(temp_dir.path())
+                .patterns(["*.rs", "*.md"])
+                .quiet(2);
 
-            extract_archive(&mut archive, black_box(&args)).unwrap();
+            extract_archive(&mut archive, black_box(&options)).unwrap();
+        });
+    });
+
+    // 10^5 entries against 100 patterns with -C, selecting one entry in 1000:
+    // matching rather than writing dominates
+    let many_entries = {
+        let mut buffer = Vec::new();
+        let mut zip = ZipWriter::new(Cursor::new(&mut buffer));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        for i in 0..100_000 {
+            let filename = format!("Data/Part_{:03}/file_{:06}.bin", i % 1000, i);
+            zip.start_file(filename, options).unwrap();
+        }
+        zip.finish().unwrap();
+        buffer
+    };
+    let patterns: Vec<String> =
+        (0..100).map(|i| format!("data/part_{:03}/*_0000??.BIN", i)).collect();
+    group.sample_size(10);
+    group.bench_function("filter_case_insensitive_100k_entries_100_patterns", |b| {
+        b.iter(|| {
+            let temp_dir = TempDir::new().unwrap();
+            let cursor = Cursor::new(&many_entries);
+            let mut archive = zip::ZipArchive::new(cursor).unwrap();
+
+            let options = ExtractOptions::new()
+                .output_dir(temp_dir.path())
+                .patterns(&patterns)
+                .case_insensitive(true)
+                .quiet(2);
+
+            extract_archive(&mut archive, black_box(&options)).unwrap();
         });
     });
 
@@ -7,6 +7,8 @@
 //!
 //! Aims for command-line compatibility with Info-ZIP unzip, supporting common
 //! options like `-l`, `-v`, `-t`, `-d`, `-o`, `-n`, `-f`, `-u`, `-j`, and `-x`.
+//! [`Args::parse_info_zip`] also accepts Info-ZIP's argument order: options
+//! anywhere, several patterns after one `-x`, and `--` to end option parsing.
 //!
 //! # Examples
 //!
@@ -16,38 +18,201 @@
 //!
 //! // Parse from command line
 //! let args = Args::parse();
+//!
+//! // Info-ZIP order: exclusions after the archive, `-d` last
+//! let args = Args::parse_info_zip_from(["unzip", "a.zip", "-x", "*.log", "*.tmp", "-d", "out"]);
+//! assert_eq!(args.exclude, ["*.log", "*.tmp"]);
 //! ```
 
-use clap::Parser;
+use clap::{Arg, Command, CommandFactory, Parser};
+use std::ffi::OsString;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::checkpoint::CheckpointInterval;
+use crate::compat::Compat;
+use crate::dirstamp::StampSource;
+use crate::duplicates::DuplicatePolicy;
+use crate::expect::Expected;
+use crate::glob::Wildcards;
+use crate::implied_dirs::ImpliedDirTimes;
+use crate::invalid_names::InvalidNames;
+use crate::linux::CachePolicy;
+use crate::list::{ListFormat, TotalsFormat};
+use crate::list_filter::{ListDate, SortKey};
+use crate::owner::Owner;
+use crate::recompress::OutputCompression;
+use crate::report::ReportFormat;
+use crate::size_check::{ExpectedSizes, SizeCheck};
+use crate::template::NameTemplate;
+use crate::transform::Transform;
+use crate::utils::PatternMatcher;
+use crate::zipinfo::is_zipinfo_mode;
 
 /// A fast, reliable unzip utility written in Rust - Info-ZIP compatible
 #[derive(Parser, Debug, Clone, Default)]
 #[command(author, version, about, long_about = None)]
 #[command(after_help = "Examples:
   unzip archive.zip                    Extract all files to current directory
-  unzip -l archive.zip                 List contents without extracting
+  unzip -l archive.zip                 List contents without extracting, as Info-ZIP does
+  unzip -l --format pretty a.zip       List with human-readable sizes and ISO dates
+  unzip -l --format csv a.zip > a.csv  List as CSV for a spreadsheet
+  unzip -l --format ls-lR a.zip > ls-lR
+                                       List as ls -lR would in the unpacked archive
+  unzip --print0 a.zip '*.log' | xargs -0 ...
+                                       List names ended by NUL bytes, safe with any file name
+  unzip -l --sort size --reverse a.zip List the largest entries first
+  unzip -l --min-size 1G --newer 2024-01-01 a.zip
+                                       List entries of 1 GiB or more modified since 2024
+  unzip --totals-only=json a.zip       Print only the file count, sizes and ratio, as JSON
+  unzip --tree --sort size a.zip       Draw the entries as a tree with each directory's size
+  unzip --du --depth 2 a.zip           Show the size of each directory two levels deep, like du
   unzip -t archive.zip                 Test archive integrity
+  unzip -t --report=tap archive.zip    Test, writing one TAP result per entry to stdout
+  unzip -t --report=json archive.zip   Test, writing a JSON status per entry and a summary
+  unzip --test-quick huge.zip          Check headers and sizes without decompressing
+  unzip --lint archive.zip             Deep-check data and legacy reader compatibility
+  unzip --diff /srv/app release.zip    Check a deployment still matches its archive
+  unzip --comment-json - build.zip | jq .commit
+                                       Print a field of the JSON metadata kept in the comment
+  unzip --stats archive.zip            Show path depth, bytes per extension and the largest directories
   unzip -d /tmp archive.zip            Extract to /tmp directory
+  unzip --no-create-dir -d /srv/app a.zip
+                                       Fail if /srv/app does not exist instead of creating it
+  unzip '*.zip' --subdir-per-archive  Extract every archive here, each into its own directory
+  unzip --smart-dir download.zip       Extract into download/ unless the archive has one top directory
+  unzip --stamp-output-dir -d out a.zip
+                                       Give out/ the archive's modification time once extracted
+  unzip --implied-dir-times=source-date-epoch src.zip
+                                       Date directories without an entry by $SOURCE_DATE_EPOCH
+  unzip -l backup.zip                  List a split set, reading backup.z01, backup.z02, ...
   unzip archive.zip '*.txt'            Extract only .txt files
   unzip archive.zip -x '*.log'         Extract all except .log files
+  unzip -l archive.zip '*.txt'         List only .txt files, exiting with 11 if there are none
+  unzip --exclude-from skip.txt a.zip  Extract all except what the globs in skip.txt match
+  unzip --members-from files.txt build.zip
+                                       Extract exactly the listed entries, failing if one is missing
+  unzip --member-index 3 archive.zip   Extract only the fourth entry in listing order
+  unzip --duplicates=rename a.zip      Keep every entry of a repeated name, later ones renamed
   unzip -p archive.zip file.txt        Extract file.txt to stdout
+  unzip -c archive.zip '*.txt'         Show the text files, each after a line with its name
+  curl -s URL | unzip --filter > out   Decompress the first member of a downloaded stream, like funzip
+  unzip --to-tar - x.zip | zstd > x.tar.zst
+                                       Repack as a tar stream without extracting to disk
   unzip -j archive.zip                 Extract without directory structure
+  unzip --strip-components=1 src.zip   Drop the top directory (project-1.2.3/) from all paths
+  unzip --transform 's,^docs/,manual/,' site.zip
+                                       Extract docs/ into manual/
+  unzip -j --name-template '{stem}-{crc}{ext}' a.zip
+                                       Flatten without collisions, as readme-3610a686.txt
+  unzip --merge-case-dirs archive.zip  Extract Docs/ and docs/ entries into one directory
   unzip -o archive.zip                 Overwrite files without prompting
   unzip -n archive.zip                 Never overwrite existing files
+  unzip -o --force-readonly app.zip    Also replace files whose mode is read-only (0444)
+  unzip --auto-rename -d inbox a.zip   Extract a.txt as a (1).txt if inbox/a.txt exists
+  unzip -o --allow-self-overwrite -d .. ../dist.zip
+                                       Extract even entries that land on the archive itself
+  unzip --compat=infozip archive.zip  Behave like Info-ZIP for scripts that parse its output
+  unzip --compat=infozip -W a.zip 'bin/*'
+                                       Extract only the files directly in bin/, as Info-ZIP -W does
   unzip -f archive.zip                 Freshen (update only existing files)
+  unzip --dry-run -u archive.zip       Show what an update would extract, skip or overwrite
+  unzip --dirs-only -d /srv tree.zip   Recreate the directory layout without any files
+  unzip --recurse-archives=2 drop.zip  Also extract the zips inside, and the zips inside those
+  unzip --transactional app.zip        Undo all changes if extraction fails
+  unzip --chown www-data:www-data -d /srv/www site.zip
+                                       Extract as root, owned by the web server account
+  unzip --priority 'manifest.json,*.index' data.zip
+                                       Extract manifests and indexes before the bulk data
+  unzip --shared-dest -d /scratch a.zip
+                                       Extract alongside other processes writing to /scratch
+  unzip --checkpoint-every 30s/1G --resume huge.zip
+                                       Continue where an evicted worker stopped
+  unzip --wait-complete=30min -d /srv/ingest /drop/upload.zip
+                                       Wait for an upload still in progress to finish first
+  unzip --size-check=strict -t vendor.zip
+                                       Fail if any entry's size fields disagree
+  unzip --metrics-file /var/lib/node_exporter/unzip.prom nightly.zip
+                                       Export the run's counters to Prometheus
+  unzip --verify backup.zip            Read files back from disk to catch corruption
+  unzip --opendoc-check --dry-run book.epub
+                                       Check the EPUB container conventions without extracting
+  unzip --compress-output=zst:19 logs.zip
+                                       Store every extracted file as NAME.zst
+  unzip --scan-socket /run/clamav/clamd.ctl upload.zip
+                                       Scan each file with clamd while writing it, removing flagged ones
+  unzip --cache=keep archive.zip       Leave extracted files in the page cache
   unzip -u archive.zip                 Update (freshen + create new files)
+  unzip --if-changed -d /srv app.zip   Rewrite only files whose content differs
+  unzip -o --patch-in-place vm.zip     Rewrite only the changed blocks of disk.img
+  unzip -a archive.zip                 Convert text file line endings to the local convention
+  unzip -O CP936 archive.zip           Decode Windows entry names as Chinese GBK
+  unzip --auto-encoding archive.zip    Guess the encoding of legacy entry names
+  unzip --invalid-names=escape a.zip   Extract names that are not valid UTF-8 with %XX escapes
+  unzip --raw-names evidence.zip       Write names as their exact bytes, shown with %XX escapes
+  unzip -t --heuristic-decode broken.zip
+                                       Recover entries with a mislabelled Stored/Deflate method
+  unzip --salvage truncated.zip        Recover entries from an archive cut short or damaged
+  unzip --max-output-size 1G --max-ratio 100 untrusted.zip
+                                       Refuse to expand beyond 1 GiB or 100:1 per entry
+  unzip --expect-files 120 --expect-bytes 2G+-5% release.zip
+                                       Refuse artifacts that are not what a deploy expects
   unzip -Z archive.zip                 Zipinfo mode: detailed archive information
-  unzip -Z -v archive.zip              Verbose zipinfo output")]
+  unzip -Z -v archive.zip              Verbose zipinfo output
+  unzip -Z l --extended-times archive.zip
+                                       Compare DOS and UT modification times")]
 pub struct Args {
-    /// Path to the ZIP file to extract
+    /// Path to the ZIP file to extract; `*` and `?` in its name select several
     #[arg(value_name = "FILE")]
     pub zipfile: PathBuf,
 
-    /// Directory to extract files to (default: current directory)
+    /// Directory to extract files to (default: current directory), created
+    /// along with its missing parents unless --no-create-dir is given
     #[arg(short = 'd', long = "directory", value_name = "EXDIR")]
     pub output_dir: Option<PathBuf>,
 
+    /// Fail if EXDIR does not exist instead of creating it
+    #[arg(long = "no-create-dir")]
+    pub no_create_dir: bool,
+
+    /// Extract each archive into a directory named after it, inside EXDIR
+    #[arg(long = "subdir-per-archive")]
+    pub subdir_per_archive: bool,
+
+    /// Treat the words after the archive that name existing .zip files as more
+    /// archives rather than member names (not with --compat=infozip)
+    #[arg(long = "archives")]
+    pub archives: bool,
+
+    /// Extract into a directory named after the archive, unless everything in it
+    /// already lies in one top-level directory
+    #[arg(long = "smart-dir")]
+    pub smart_dir: bool,
+
+    /// Once extracted, set the output directory's modification time to the
+    /// archive's (archive, the default) or to the newest extracted file's (newest)
+    #[arg(
+        long = "stamp-output-dir",
+        value_name = "SOURCE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "archive",
+        value_parser = crate::dirstamp::parse_stamp_source
+    )]
+    pub stamp_output_dir: Option<StampSource>,
+
+    /// Modification time of directories created for files without an entry of
+    /// their own: now, newest (of what they contain), source-date-epoch or
+    /// SECONDS since the epoch
+    #[arg(
+        long = "implied-dir-times",
+        value_name = "POLICY",
+        default_value = "now",
+        value_parser = crate::implied_dirs::parse_implied_dir_times
+    )]
+    pub implied_dir_times: ImpliedDirTimes,
+
     /// List contents only (short format)
     #[arg(short = 'l', long = "list")]
     pub list_only: bool,
@@ -56,24 +221,151 @@ pub struct Args {
     #[arg(short = 'v', long = "verbose")]
     pub verbose: bool,
 
+    /// Layout of -l and -v listings: infozip (Info-ZIP's columns and totals,
+    /// the default), pretty (human-readable sizes and ISO dates), csv, or find
+    /// and ls-lR (the output of `find . -ls` and `ls -lR` in the unpacked
+    /// archive)
+    #[arg(
+        long = "format",
+        value_name = "LAYOUT",
+        default_value = "infozip",
+        value_parser = crate::list::parse_list_format
+    )]
+    pub list_format: ListFormat,
+
+    /// Print only the totals of the listing (file count, sizes, ratio) on one
+    /// line, as `text` (default) or `json`
+    #[arg(
+        long = "totals-only",
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "text",
+        value_parser = crate::list::parse_totals_format
+    )]
+    pub totals_only: Option<TotalsFormat>,
+
+    /// Sort the listing by KEY: name, size, ratio or mtime (smallest or oldest
+    /// first)
+    #[arg(long = "sort", value_name = "KEY", value_parser = crate::list_filter::parse_sort_key)]
+    pub sort: Option<SortKey>,
+
+    /// With --sort, list largest, newest or last first
+    #[arg(long = "reverse", requires = "sort")]
+    pub reverse: bool,
+
+    /// List the entries as a tree, like the tree command, with the cumulative
+    /// size of each directory
+    #[arg(long = "tree", conflicts_with = "totals_only")]
+    pub tree: bool,
+
+    /// List only the entry names, each ended by a NUL byte, for xargs -0
+    #[arg(long = "print0", conflicts_with_all = ["totals_only", "tree"])]
+    pub print0: bool,
+
+    /// Summarize the uncompressed and compressed sizes of each directory, like
+    /// du, down to --depth levels
+    #[arg(long = "du", conflicts_with_all = ["totals_only", "tree", "print0"])]
+    pub du: bool,
+
+    /// With --du, list directories down to N levels (default 1, the top-level
+    /// directories; 0 prints only the total)
+    #[arg(long = "depth", value_name = "N", requires = "du")]
+    pub depth: Option<usize>,
+
+    /// List only entries of at least SIZE uncompressed bytes (K, M, G, T suffixes)
+    #[arg(long = "min-size", value_name = "SIZE", value_parser = crate::limits::parse_size)]
+    pub min_size: Option<u64>,
+
+    /// List only entries of at most SIZE uncompressed bytes
+    #[arg(long = "max-size", value_name = "SIZE", value_parser = crate::limits::parse_size)]
+    pub max_size: Option<u64>,
+
+    /// List only entries modified after DATE (YYYY-MM-DD [HH:MM[:SS]])
+    #[arg(long = "newer", value_name = "DATE", value_parser = crate::list_filter::parse_list_date)]
+    pub newer: Option<ListDate>,
+
+    /// List only entries modified before DATE
+    #[arg(long = "older", value_name = "DATE", value_parser = crate::list_filter::parse_list_date)]
+    pub older: Option<ListDate>,
+
     /// Test archive integrity
     #[arg(short = 't', long = "test")]
     pub test: bool,
 
+    /// With -t, write the results in a machine-readable FORMAT (tap, json) to stdout
+    /// instead of the console output
+    #[arg(
+        long = "report",
+        value_name = "FORMAT",
+        requires = "test",
+        value_parser = crate::report::parse_report_format
+    )]
+    pub report: Option<ReportFormat>,
+
+    /// Check the local headers against the central directory, sizes and methods
+    /// without decompressing anything: a quick sanity check before a full -t
+    #[arg(long = "test-quick", conflicts_with_all = ["test", "salvage"])]
+    pub test_quick: bool,
+
+    /// Deep check before re-serving: re-verify CRCs through a re-compression round trip
+    /// and warn about methods legacy readers lack (e.g. Deflate64)
+    #[arg(long = "lint")]
+    pub lint: bool,
+
+    /// Compare the archive with the tree under DIR and report files that are
+    /// missing, extra, or differ in size, CRC32 or modification time
+    #[arg(long = "diff", value_name = "DIR")]
+    pub diff: Option<PathBuf>,
+
+    /// Show the archive's shape and content: path depth, files per directory,
+    /// files and bytes per extension and the largest directories
+    #[arg(long = "stats")]
+    pub stats: bool,
+
     /// Extract files to stdout/pipe (no messages)
     #[arg(short = 'p', long = "pipe")]
     pub pipe: bool,
 
+    /// Extract files to stdout like -p, with each preceded by its name and text
+    /// converted as -a asks
+    #[arg(short = 'c', long = "crt", conflicts_with = "pipe")]
+    pub crt: bool,
+
+    /// Write the first member of a zip or gzip stream to stdout, like funzip;
+    /// reads stdin unless FILE is given
+    #[arg(long = "filter", conflicts_with_all = ["pipe", "crt"])]
+    pub filter: bool,
+
+    /// Convert the selected entries to an uncompressed tar stream written to
+    /// FILE, or stdout for `-`, keeping paths, modes, times and symlinks
+    #[arg(long = "to-tar", value_name = "FILE", conflicts_with_all = ["pipe", "crt", "filter"])]
+    pub to_tar: Option<PathBuf>,
+
     /// Display archive comment only
     #[arg(short = 'z', long = "comment")]
     pub comment_only: bool,
 
+    /// Write the archive comment to PATH (- for stdout) after checking it is
+    /// JSON; exits with 13 if there is no comment and 14 if it is not JSON
+    #[arg(
+        long = "comment-json",
+        value_name = "PATH",
+        conflicts_with = "comment_only"
+    )]
+    pub comment_json: Option<PathBuf>,
+
     /// Zipinfo mode: detailed archive information (-Z or -Z MODE)
     /// Modes: -1 (filenames), -2 (filenames+headers), -s (short, default),
     /// -m (medium with %), -l (long with size), -v (verbose), -h (header), -t (trailer)
     #[arg(short = 'Z', long = "zipinfo", value_name = "MODE")]
     pub zipinfo: Option<Option<String>>,
 
+    /// In zipinfo long/verbose output, show the extended (UT) time next to the DOS
+    /// time and flag entries where they differ by more than 2 seconds
+    #[arg(long = "extended-times")]
+    pub extended_times: bool,
+
     /// Overwrite existing files without prompting
     #[arg(short = 'o', long = "overwrite")]
     pub overwrite: bool,
@@ -82,6 +374,31 @@ pub struct Args {
     #[arg(short = 'n', long = "never-overwrite")]
     pub never_overwrite: bool,
 
+    /// Extract next to existing files under a free name, NAME (1).EXT,
+    /// NAME (2).EXT, ..., instead of skipping or replacing them
+    #[arg(long = "auto-rename", conflicts_with_all = ["overwrite", "freshen", "update"])]
+    pub auto_rename: bool,
+
+    /// Extract even when an entry would be written over the archive being read,
+    /// or over a directory holding it, which is refused by default
+    #[arg(long = "allow-self-overwrite")]
+    pub allow_self_overwrite: bool,
+
+    /// Replace existing files only if their size or CRC32 differs from the
+    /// entry's, leaving identical files untouched
+    #[arg(
+        long = "if-changed",
+        conflicts_with_all = [
+            "never_overwrite",
+            "freshen",
+            "update",
+            "auto_rename",
+            "compress_output",
+            "text_mode"
+        ]
+    )]
+    pub if_changed: bool,
+
     /// Freshen existing files only (don't create new)
     #[arg(short = 'f', long = "freshen")]
     pub freshen: bool,
@@ -94,23 +411,292 @@ pub struct Args {
     #[arg(short = 'j', long = "junk-paths")]
     pub junk_paths: bool,
 
+    /// Remove the first N components from entry paths, skipping entries with
+    /// nothing left, like tar --strip-components
+    #[arg(long = "strip-components", value_name = "N", default_value_t = 0)]
+    pub strip_components: usize,
+
+    /// Rewrite output paths with a sed expression, s/REGEX/REPLACEMENT/FLAGS
+    /// (repeatable, applied in order)
+    #[arg(long = "transform", value_name = "EXPR", value_parser = crate::transform::parse_transform)]
+    pub transform: Vec<Transform>,
+
+    /// Name extracted files after TEMPLATE, with placeholders {path}, {dir},
+    /// {name}, {stem}, {ext}, {crc}, {index}, {date} and {mtime}
+    #[arg(
+        long = "name-template",
+        value_name = "TEMPLATE",
+        value_parser = crate::template::parse_name_template
+    )]
+    pub name_template: Option<NameTemplate>,
+
     /// Match filenames case-insensitively
     #[arg(short = 'C', long = "case-insensitive")]
     pub case_insensitive: bool,
 
+    /// Keep `*` and `?` in patterns from matching `/`, leaving that to `**`
+    /// (already the default outside --compat=infozip)
+    #[arg(short = 'W', long = "wild-stop-at-dir")]
+    pub wild_stop_at_dir: bool,
+
     /// Make filenames lowercase
     #[arg(short = 'L', long = "lowercase")]
     pub lowercase: bool,
 
-    /// Skip restoring file timestamps
-    #[arg(short = 'D', long = "no-timestamps")]
-    pub no_timestamps: bool,
+    /// Extract directories whose names differ only in case across entries under
+    /// the spelling that comes first in the archive
+    #[arg(long = "merge-case-dirs")]
+    pub merge_case_dirs: bool,
+
+    /// Skip restoring directory timestamps (-D), or all timestamps (-DD)
+    #[arg(short = 'D', long = "no-timestamps", action = clap::ArgAction::Count)]
+    pub no_timestamps: u8,
 
     /// Quiet mode (-q quieter, -qq quietest)
     #[arg(short = 'q', long = "quiet", action = clap::ArgAction::Count)]
     pub quiet: u8,
 
-    /// Number of parallel extraction threads (default: auto)
+    /// Character set of entry names in DOS/Windows archives (e.g. CP437, CP936, SJIS)
+    #[arg(short = 'O', long = "oem-charset", value_name = "CHARSET")]
+    pub oem_charset: Option<String>,
+
+    /// Character set of entry names in Unix and other archives
+    #[arg(short = 'I', long = "iso-charset", value_name = "CHARSET")]
+    pub iso_charset: Option<String>,
+
+    /// Convert line endings of text files (-a), or of all files (-aa)
+    #[arg(short = 'a', long = "text", action = clap::ArgAction::Count)]
+    pub text_mode: u8,
+
+    /// Store files whose paths exceed filesystem limits under short names plus a manifest
+    #[arg(long = "flatten-long-paths")]
+    pub flatten_long_paths: bool,
+
+    /// Report the I/O hints and preallocations applied and what the destination supports
+    #[arg(long = "verbose-io")]
+    pub verbose_io: bool,
+
+    /// Page cache use: keep extracted files and the archive cached, drop them, or
+    /// drop only beyond a quarter of memory (auto)
+    #[arg(
+        long = "cache",
+        value_name = "MODE",
+        default_value = "auto",
+        value_parser = crate::linux::parse_cache_policy
+    )]
+    pub cache: CachePolicy,
+
+    /// Follow Info-ZIP's output formats, timestamps, exit codes and overwrite
+    /// prompts (infozip), or use progress bars and human-readable sizes (native)
+    #[arg(
+        long = "compat",
+        value_name = "MODE",
+        default_value = "native",
+        value_parser = crate::compat::parse_compat
+    )]
+    pub compat: Compat,
+
+    /// Guess the character set of non-UTF-8 entry names (UTF-8, locale charset, CP437)
+    #[arg(long = "auto-encoding")]
+    pub auto_encoding: bool,
+
+    /// Entry names that do not decode: replace the bad bytes with U+FFFD (lossy),
+    /// write them as %XX (escape), or refuse the archive (reject)
+    #[arg(
+        long = "invalid-names",
+        value_name = "POLICY",
+        default_value = "lossy",
+        value_parser = crate::invalid_names::parse_invalid_names
+    )]
+    pub invalid_names: InvalidNames,
+
+    /// Write entry names to disk as their exact bytes, shown with %XX escapes,
+    /// decoding none of them
+    #[arg(
+        long = "raw-names",
+        conflicts_with_all = ["oem_charset", "iso_charset", "auto_encoding", "invalid_names"]
+    )]
+    pub raw_names: bool,
+
+    /// Write through symlinks already in the destination, even ones leaving it
+    #[arg(long = "follow-symlinks")]
+    pub follow_symlinks: bool,
+
+    /// Retry entries that fail their CRC check as the other of Stored and Deflate,
+    /// for archives whose writer mislabelled the compression method
+    #[arg(long = "heuristic-decode")]
+    pub heuristic_decode: bool,
+
+    /// Ignore the central directory and recover what entries the local file
+    /// headers still describe, reporting those that cannot be recovered
+    #[arg(long = "salvage")]
+    pub salvage: bool,
+
+    /// Show what extraction would do with each entry without writing anything
+    #[arg(long = "dry-run")]
+    pub dry_run: bool,
+
+    /// Create the directories of the archive, including those holding files, but
+    /// no files
+    #[arg(long = "dirs-only")]
+    pub dirs_only: bool,
+
+    /// Also extract the zip files found inside the archive into directories named
+    /// after them, up to N levels deep (default 3)
+    #[arg(
+        long = "recurse-archives",
+        value_name = "N",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "3",
+        value_parser = crate::nested::parse_recursion_depth
+    )]
+    pub recurse_archives: Option<u32>,
+
+    /// Extract into a staging directory and move the files into place only if
+    /// every entry succeeds, leaving the destination untouched otherwise
+    #[arg(long = "transactional", conflicts_with_all = ["checkpoint_every", "resume"])]
+    pub transactional: bool,
+
+    /// Write each file under a temporary name and rename it into place, so other
+    /// processes extracting into the same directory never see partial files;
+    /// files they already placed with the same content are skipped
+    #[arg(long = "shared-dest", conflicts_with_all = ["transactional", "auto_rename"])]
+    pub shared_dest: bool,
+
+    /// Write over existing files of the entry's size in place, rewriting only
+    /// the blocks that differ, to keep copy-on-write extents shared
+    #[arg(
+        long = "patch-in-place",
+        conflicts_with_all = ["transactional", "shared_dest", "compress_output"]
+    )]
+    pub patch_in_place: bool,
+
+    /// Replace existing read-only files (make them writable, write them and restore
+    /// their mode) instead of skipping them with a warning
+    #[arg(long = "force-readonly")]
+    pub force_readonly: bool,
+
+    /// Give extracted files and created directories this owner, as USER:GROUP,
+    /// USER, :GROUP or USER: (names or numeric IDs; needs CAP_CHOWN)
+    #[arg(
+        long = "chown",
+        value_name = "USER:GROUP",
+        value_parser = crate::owner::parse_owner,
+        conflicts_with = "transactional"
+    )]
+    pub chown: Option<Owner>,
+
+    /// Extract the entries matching these comma-separated patterns before all
+    /// others; a pattern without / also matches the file name in any directory
+    #[arg(long = "priority", value_name = "PATTERNS", value_delimiter = ',')]
+    pub priority: Vec<String>,
+
+    /// Before reading the archive, wait while it is missing, still growing or
+    /// without a central directory, for up to TIMEOUT (seconds, or 30s, 5min,
+    /// 1h; default 10min)
+    #[arg(
+        long = "wait-complete",
+        value_name = "TIMEOUT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "10min",
+        value_parser = crate::wait_complete::parse_wait_timeout,
+        conflicts_with = "filter"
+    )]
+    pub wait_complete: Option<Duration>,
+
+    /// Before extracting or testing, compare the 32-bit, Zip64 and data
+    /// descriptor sizes of every entry: strict fails on any difference, lenient
+    /// warns and goes by the most plausible value; entries must then decompress
+    /// to that size
+    #[arg(
+        long = "size-check",
+        value_name = "MODE",
+        value_parser = crate::size_check::parse_size_check,
+        conflicts_with_all = ["filter", "salvage"]
+    )]
+    pub size_check: Option<SizeCheck>,
+
+    /// Sizes `--size-check` settled on, filled in before the archive is
+    /// extracted or tested
+    #[arg(skip)]
+    pub expected_sizes: Option<Arc<ExpectedSizes>>,
+
+    /// Record the extracted entries for --resume every TIME and/or SIZE (30s,
+    /// 5min, 1G, 30s/1G), and finish the current entries before exiting on SIGTERM
+    #[arg(
+        long = "checkpoint-every",
+        value_name = "INTERVAL",
+        value_parser = crate::checkpoint::parse_checkpoint_interval
+    )]
+    pub checkpoint_every: Option<CheckpointInterval>,
+
+    /// Skip the entries an earlier checkpointed run already extracted
+    #[arg(long = "resume")]
+    pub resume: bool,
+
+    /// When the run completes, write its counters (bytes and entries extracted,
+    /// errors, duration, throughput) to PATH in Prometheus textfile format
+    #[arg(long = "metrics-file", value_name = "PATH")]
+    pub metrics_file: Option<PathBuf>,
+
+    /// Read every file back from disk after writing it and fail if its CRC32
+    /// differs from the data written
+    #[arg(long = "verify")]
+    pub verify: bool,
+
+    /// Compress each extracted file with zstd into NAME.zst (zst or zst:LEVEL,
+    /// LEVEL 1-22, default 3)
+    #[arg(
+        long = "compress-output",
+        value_name = "FORMAT",
+        value_parser = crate::recompress::parse_output_compression,
+        conflicts_with_all = ["verify", "heuristic_decode"]
+    )]
+    pub compress_output: Option<OutputCompression>,
+
+    /// Scan each file as it is written through the ClamAV-compatible daemon
+    /// listening on the Unix socket PATH (INSTREAM); flagged files are not kept
+    #[arg(long = "scan-socket", value_name = "PATH")]
+    pub scan_socket: Option<PathBuf>,
+
+    /// Move files the scanner flags into DIR instead of removing them
+    #[arg(long = "quarantine", value_name = "DIR", requires = "scan_socket")]
+    pub quarantine: Option<PathBuf>,
+
+    /// Abort once extracted data exceeds SIZE bytes (suffixes K, M, G, T)
+    #[arg(long = "max-output-size", value_name = "SIZE", value_parser = crate::limits::parse_size)]
+    pub max_output_size: Option<u64>,
+
+    /// Abort when an entry expands more than RATIO times its compressed size
+    #[arg(long = "max-ratio", value_name = "RATIO")]
+    pub max_ratio: Option<u64>,
+
+    /// Refuse archives with more than NUM entries
+    #[arg(long = "max-entries", value_name = "NUM")]
+    pub max_entries: Option<usize>,
+
+    /// Refuse to extract unless the selected files number N (N+-T or N+-P% for a
+    /// tolerance)
+    #[arg(long = "expect-files", value_name = "N", value_parser = crate::expect::parse_expected_count)]
+    pub expect_files: Option<Expected>,
+
+    /// Refuse to extract unless the selected files add up to SIZE bytes (suffixes
+    /// K, M, G, T; SIZE+-T or SIZE+-P% for a tolerance)
+    #[arg(long = "expect-bytes", value_name = "SIZE", value_parser = crate::expect::parse_expected_size)]
+    pub expect_bytes: Option<Expected>,
+
+    /// Refuse to extract an EPUB, OpenDocument or Office Open XML file that breaks
+    /// its container conventions (mimetype first and stored, required parts)
+    #[arg(
+        long = "opendoc-check",
+        conflicts_with_all = ["junk_paths", "lowercase", "strip_components", "transform", "name_template"]
+    )]
+    pub opendoc_check: bool,
+
+    /// Most parallel extraction threads (default: auto); how many run follows
+    /// the measured throughput
     #[arg(short = 'T', long = "threads", value_name = "NUM")]
     pub threads: Option<usize>,
 
@@ -125,4 +711,377 @@ pub struct Args {
     /// Exclude files matching these patterns
     #[arg(short = 'x', long = "exclude", value_name = "PATTERN")]
     pub exclude: Vec<String>,
+
+    /// Also select the patterns in FILE, one glob per line (`#` starts a comment,
+    /// `-` reads stdin)
+    #[arg(long = "include-from", value_name = "FILE")]
+    pub include_from: Vec<PathBuf>,
+
+    /// Also exclude the patterns in FILE, one glob per line (`#` starts a comment,
+    /// `-` reads stdin)
+    #[arg(long = "exclude-from", value_name = "FILE")]
+    pub exclude_from: Vec<PathBuf>,
+
+    /// Also extract the entries named in FILE, one exact name per line (`-` for
+    /// stdin), and fail if any of them is not in the archive
+    #[arg(long = "members-from", value_name = "FILE")]
+    pub members_from: Option<PathBuf>,
+
+    /// Extract only the entry at position N of the central directory, counting
+    /// from 0 as listed, even if other entries share its name
+    #[arg(
+        long = "member-index",
+        value_name = "N",
+        conflicts_with = "members_from"
+    )]
+    pub member_index: Option<usize>,
+
+    /// Which of the entries sharing a name to extract: the first, the last, all
+    /// with the later ones renamed NAME (1).EXT, or none and fail (error)
+    #[arg(
+        long = "duplicates",
+        value_name = "POLICY",
+        default_value = "last",
+        value_parser = crate::duplicates::parse_duplicate_policy
+    )]
+    pub duplicates: DuplicatePolicy,
+}
+
+impl Args {
+    /// Parse the process arguments, accepting Info-ZIP's argument order
+    ///
+    /// See [`normalize_args`] for what is accepted beyond [`Parser::parse`].
+    pub fn parse_info_zip() -> Self {
+        Self::parse_info_zip_from(std::env::args_os())
+    }
+
+    /// Parse `args`, starting with the program name, accepting Info-ZIP's
+    /// argument order
+    pub fn parse_info_zip_from<I, T>(args: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString>,
+    {
+        Self::parse_from(normalize_args(args.into_iter().map(Into::into).collect()))
+    }
+
+    /// How `*` and `?` in member patterns treat `/`, given `--compat` and `-W`
+    pub fn wildcards(&self) -> Wildcards {
+        self.compat.wildcards(self.wild_stop_at_dir)
+    }
+
+    /// Matcher selecting the entries the member patterns and `-x` ask for
+    pub(crate) fn matcher(&self) -> PatternMatcher<'_> {
+        PatternMatcher::new(&self.patterns, &self.exclude, self.case_insensitive)
+            .with_wildcards(self.wildcards())
+    }
+}
+
+/// What an option on the command line does with the words after it
+enum Arity<'a> {
+    /// Takes no value, or has it attached (`-dout`, `--directory=out`)
+    Flag,
+    /// Takes the next word as its value
+    Value,
+    /// `-x`: the following words are exclusion patterns, up to the next option.
+    /// Holds the options clustered before it (`-o` of `-ox`), if any
+    Exclude(Option<&'a str>),
+    /// `-Z`: takes the next word only if it is a zipinfo mode. Holds the options
+    /// clustered before it, if any
+    Zipinfo(Option<&'a str>),
+}
+
+/// Rewrite an Info-ZIP style command line into one clap parses the same way
+///
+/// Info-ZIP's hand-rolled parser accepts options anywhere: `-d dir` before or
+/// after the archive and member list, and `-x` followed by any number of
+/// exclusion patterns, ended by the next option. `--` ends option parsing, so the
+/// archive and members may start with `-`. A word after `-Z` is its mode only if
+/// it names one (`-Z l`, `-Z -v`); otherwise it is the archive.
+///
+/// The result lists the options first, then `--` and the archive and members.
+pub fn normalize_args(args: Vec<OsString>) -> Vec<OsString> {
+    let mut command = Args::command();
+    command.build();
+
+    let mut args = args.into_iter().peekable();
+    let mut options: Vec<OsString> = args.next().into_iter().collect();
+    let mut operands = Vec::new();
+    let mut excluding = false;
+
+    while let Some(arg) = args.next() {
+        let Some(text) = arg.to_str().filter(|text| text.len() > 1 && text.starts_with('-')) else {
+            if excluding {
+                let mut exclude = OsString::from("--exclude=");
+                exclude.push(&arg);
+                options.push(exclude);
+            } else {
+                operands.push(arg);
+            }
+            continue;
+        };
+        excluding = false;
+        if text == "--" {
+            operands.extend(args.by_ref());
+            break;
+        }
+
+        match option_arity(&command, text) {
+            Arity::Flag => options.push(arg),
+            Arity::Value => {
+                options.push(arg);
+                options.extend(args.next());
+            },
+            Arity::Exclude(cluster) => {
+                options.extend(cluster.map(OsString::from));
+                excluding = true;
+            },
+            Arity::Zipinfo(cluster) => {
+                let mode = args.next_if(|next| next.to_str().is_some_and(is_zipinfo_mode));
+                match mode {
+                    Some(mode) => {
+                        options.extend(cluster.map(OsString::from));
+                        let mut zipinfo = OsString::from("--zipinfo=");
+                        zipinfo.push(mode);
+                        options.push(zipinfo);
+                    },
+                    None => options.push(arg),
+                }
+            },
+        }
+    }
+
+    // funzip reads stdin when given no file
+    if operands.is_empty() && options.iter().any(|option| option == "--filter") {
+        operands.push("-".into());
+    }
+    options.push("--".into());
+    options.extend(operands);
+    options
+}
+
+/// How the option word `text` (starting with `-`) uses the words after it
+fn option_arity<'a>(command: &Command, text: &'a str) -> Arity<'a> {
+    // Options whose value must be attached (`--recurse-archives=2`) never take the
+    // next word
+    let takes_value = |arg: &Arg| {
+        arg.get_num_args().is_some_and(|range| range.takes_values()) && !arg.is_require_equals_set()
+    };
+
+    if let Some(long) = text.strip_prefix("--") {
+        let arg = command.get_arguments().find(|arg| arg.get_long() == Some(long));
+        return match arg {
+            Some(arg) if arg.get_id() == "exclude" => Arity::Exclude(None),
+            Some(arg) if arg.get_id() == "zipinfo" => Arity::Zipinfo(None),
+            Some(arg) if takes_value(arg) => Arity::Value,
+            _ => Arity::Flag,
+        };
+    }
+
+    // A cluster of short options; the first one taking a value ends it
+    for (i, c) in text.char_indices().skip(1) {
+        let Some(arg) = command.get_arguments().find(|arg| arg.get_short() == Some(c)) else {
+            return Arity::Flag;
+        };
+        if !takes_value(arg) {
+            continue;
+        }
+        if i + c.len_utf8() < text.len() {
+            return Arity::Flag;
+        }
+        let cluster = (i > 1).then(|| &text[..i]);
+        return if arg.get_id() == "exclude" {
+            Arity::Exclude(cluster)
+        } else if arg.get_id() == "zipinfo" {
+            Arity::Zipinfo(cluster)
+        } else {
+            Arity::Value
+        };
+    }
+    Arity::Flag
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What one command line should parse to
+    struct Expected<'a> {
+        zipfile: &'a str,
+        patterns: &'a [&'a str],
+        exclude: &'a [&'a str],
+        output_dir: Option<&'a str>,
+    }
+
+    fn parse(line: &[&str]) -> Args {
+        Args::parse_info_zip_from(std::iter::once("unzip").chain(line.iter().copied()))
+    }
+
+    #[test]
+    fn test_invocation_styles() {
+        let none: &[&str] = &[];
+        let matrix: &[(&[&str], Expected)] = &[
+            (
+                &["app.zip"],
+                Expected { zipfile: "app.zip", patterns: none, exclude: none, output_dir: None },
+            ),
+            // Options first, as in most documentation
+            (
+                &["-o", "-d", "/opt/app", "app.zip"],
+                Expected {
+                    zipfile: "app.zip",
+                    patterns: none,
+                    exclude: none,
+                    output_dir: Some("/opt/app"),
+                },
+            ),
+            // `-d` last, as in install scripts
+            (
+                &["-q", "app.zip", "-d", "/opt/app"],
+                Expected {
+                    zipfile: "app.zip",
+                    patterns: none,
+                    exclude: none,
+                    output_dir: Some("/opt/app"),
+                },
+            ),
+            // Members before `-d`, options after them
+            (
+                &["fonts.zip", "*.ttf", "*.otf", "-d", "fonts", "-o"],
+                Expected {
+                    zipfile: "fonts.zip",
+                    patterns: &["*.ttf", "*.otf"],
+                    exclude: none,
+                    output_dir: Some("fonts"),
+                },
+            ),
+            // An exclusion list of several patterns
+            (
+                &["src.zip", "*.rs", "-x", "*.log", "target/*"],
+                Expected {
+                    zipfile: "src.zip",
+                    patterns: &["*.rs"],
+                    exclude: &["*.log", "target/*"],
+                    output_dir: None,
+                },
+            ),
+            // `-d` ends the exclusion list
+            (
+                &["-o", "dist.zip", "-x", "__MACOSX/*", "*.DS_Store", "-d", "out"],
+                Expected {
+                    zipfile: "dist.zip",
+                    patterns: none,
+                    exclude: &["__MACOSX/*", "*.DS_Store"],
+                    output_dir: Some("out"),
+                },
+            ),
+            // `-d` before the archive, `-x` after it
+            (
+                &["-d", "out", "backup.zip", "-x", "*.bak"],
+                Expected {
+                    zipfile: "backup.zip",
+                    patterns: none,
+                    exclude: &["*.bak"],
+                    output_dir: Some("out"),
+                },
+            ),
+            // Clustered options with an attached directory
+            (
+                &["-qqo", "app.zip", "-dout"],
+                Expected {
+                    zipfile: "app.zip",
+                    patterns: none,
+                    exclude: none,
+                    output_dir: Some("out"),
+                },
+            ),
+            // `-x` ending a cluster
+            (
+                &["app.zip", "-ox", "*.conf"],
+                Expected {
+                    zipfile: "app.zip",
+                    patterns: none,
+                    exclude: &["*.conf"],
+                    output_dir: None,
+                },
+            ),
+            // `--` ends option parsing, and the exclusion list
+            (
+                &["--", "-release.zip"],
+                Expected {
+                    zipfile: "-release.zip",
+                    patterns: none,
+                    exclude: none,
+                    output_dir: None,
+                },
+            ),
+            (
+                &["docs.zip", "-x", "*.tmp", "--", "-notes.txt"],
+                Expected {
+                    zipfile: "docs.zip",
+                    patterns: &["-notes.txt"],
+                    exclude: &["*.tmp"],
+                    output_dir: None,
+                },
+            ),
+            // Long options work anywhere too
+            (
+                &["app.zip", "--directory", "out", "--exclude", "*.log", "*.tmp"],
+                Expected {
+                    zipfile: "app.zip",
+                    patterns: none,
+                    exclude: &["*.log", "*.tmp"],
+                    output_dir: Some("out"),
+                },
+            ),
+        ];
+
+        for (line, expected) in matrix {
+            let args = parse(line);
+            assert_eq!(args.zipfile, PathBuf::from(expected.zipfile), "{:?}", line);
+            assert_eq!(args.patterns, expected.patterns, "{:?}", line);
+            assert_eq!(args.exclude, expected.exclude, "{:?}", line);
+            assert_eq!(args.output_dir, expected.output_dir.map(PathBuf::from), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn test_zipinfo_mode_words() {
+        let args = parse(&["-Z", "app.zip"]);
+        assert_eq!((args.zipinfo, args.zipfile), (Some(None), PathBuf::from("app.zip")));
+
+        let args = parse(&["-Z", "l", "app.zip"]);
+        assert_eq!(args.zipinfo, Some(Some("l".to_string())));
+
+        let args = parse(&["-Z", "-v", "app.zip", "*.txt"]);
+        assert_eq!(args.zipinfo, Some(Some("-v".to_string())));
+        assert!(!args.verbose);
+        assert_eq!(args.patterns, ["*.txt"]);
+    }
+
+    #[test]
+    fn test_filter_reads_stdin_without_file() {
+        assert_eq!(parse(&["--filter"]).zipfile, PathBuf::from("-"));
+        assert_eq!(parse(&["--filter", "-P", "pw"]).zipfile, PathBuf::from("-"));
+        assert_eq!(parse(&["--filter", "in.zip"]).zipfile, PathBuf::from("in.zip"));
+    }
+
+    #[test]
+    fn test_option_values_are_kept() {
+        let args = parse(&["app.zip", "-P", "secret", "-T", "4", "--max-ratio", "50", "-x", "a"]);
+        assert_eq!(args.password.as_deref(), Some("secret"));
+        assert_eq!(args.threads, Some(4));
+        assert_eq!(args.max_ratio, Some(50));
+        assert_eq!(args.exclude, ["a"]);
+    }
+
+    #[test]
+    fn test_optional_values_must_be_attached() {
+        let args = parse(&["--recurse-archives", "app.zip", "--max-ratio", "50"]);
+        assert_eq!((args.recurse_archives, args.zipfile), (Some(3), PathBuf::from("app.zip")));
+        assert_eq!(args.max_ratio, Some(50));
+
+        assert_eq!(parse(&["--recurse-archives=1", "app.zip"]).recurse_archives, Some(1));
+        assert_eq!(parse(&["app.zip"]).recurse_archives, None);
+    }
 }
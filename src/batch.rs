@@ -0,0 +1,229 @@
+//! Several archives in one run (`unzip '*.zip'`, `unzip a.zip b.zip`)
+//!
+//! Like Info-ZIP, the archive argument may hold the wildcards `*` and `?` in its
+//! file name, quoted so the shell leaves them alone; [`expand_archives`] matches
+//! them against the files in the named directory. The words after the archive are
+//! member patterns, applied to every archive. With `--archives`, the words right
+//! after the archive that name existing `.zip` files are archives too, so a
+//! wildcard the shell already expanded works as well; without it, or with
+//! `--compat=infozip`, `unzip nest.zip t.zip` extracts the member `t.zip` as
+//! Info-ZIP does, whatever files the current directory holds.
+//!
+//! Each archive is processed in turn, by default into the same output directory,
+//! or with `--subdir-per-archive` into a directory named after it ([`subdir_for`]).
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::path::{Path, PathBuf};
+//! use unzip::Args;
+//! use unzip::batch::{Batch, subdir_for};
+//!
+//! let args = Args::parse_info_zip_from(["unzip", "drops/*.zip", "*.txt"]);
+//! let batch = Batch::from_args(&args)?;
+//! for archive in &batch.archives {
+//!     println!("{} -> {}", archive.display(), subdir_for(None, archive).display());
+//! }
+//! assert_eq!(batch.patterns, ["*.txt"]);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::args::Args;
+use crate::compat::Compat;
+use crate::error::{Result, UnzipError};
+use crate::glob::glob_match;
+use crate::nested::is_archive_path;
+use crate::pattern_files::read_patterns;
+
+/// Archives named on the command line and the member patterns for all of them
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Batch {
+    /// Archives to process, in order
+    pub archives: Vec<PathBuf>,
+    /// Member patterns, the words after the archives and those of `--include-from`
+    pub patterns: Vec<String>,
+    /// Exclusion patterns, those of `-x` and `--exclude-from`
+    pub exclude: Vec<String>,
+}
+
+impl Batch {
+    /// Expand the archive argument, split the archives that follow it from the
+    /// member patterns when `--archives` asks for it and add the patterns of the
+    /// pattern files
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a wildcard matches no file, its directory cannot be
+    /// read, or a pattern file cannot be read (see [`crate::pattern_files`])
+    pub fn from_args(args: &Args) -> Result<Self> {
+        let mut archives = expand_archives(&args.zipfile)?;
+        let more = if args.archives && args.compat != Compat::InfoZip {
+            args.patterns
+                .iter()
+                .take_while(|word| is_archive_path(Path::new(word)) && Path::new(word).is_file())
+                .count()
+        } else {
+            0
+        };
+        archives.extend(args.patterns[..more].iter().map(PathBuf::from));
+        let mut patterns = args.patterns[more..].to_vec();
+        patterns.extend(read_patterns(&args.include_from)?);
+        let mut exclude = args.exclude.clone();
+        exclude.extend(read_patterns(&args.exclude_from)?);
+        Ok(Self { archives, patterns, exclude })
+    }
+
+    /// Whether more than one archive is processed
+    pub fn is_batch(&self) -> bool {
+        self.archives.len() > 1
+    }
+
+    /// Arguments to process `archive` of the batch with
+    pub fn args_for(&self, args: &Args, archive: &Path) -> Args {
+        let mut args = args.clone();
+        if args.subdir_per_archive {
+            args.output_dir = Some(subdir_for(args.output_dir.as_deref(), archive));
+        }
+        args.zipfile = archive.to_path_buf();
+        args.patterns = self.patterns.clone();
+        args.exclude = self.exclude.clone();
+        args
+    }
+}
+
+/// Whether an archive argument holds wildcards for this tool to expand
+pub fn has_wildcards(word: &str) -> bool {
+    word.contains(['*', '?'])
+}
+
+/// Files matching the wildcards in the file name of `pattern`, sorted by name
+///
+/// A `pattern` without wildcards, or naming an existing file, is returned as is.
+///
+/// # Errors
+///
+/// Returns an error if nothing matches, the directory part holds wildcards, or the
+/// directory cannot be read
+pub fn expand_archives(pattern: &Path) -> Result<Vec<PathBuf>> {
+    let name = pattern.file_name().and_then(|name| name.to_str());
+    let Some(name) = name.filter(|name| has_wildcards(name) && !pattern.exists()) else {
+        return Ok(vec![pattern.to_path_buf()]);
+    };
+    let parent = pattern.parent().filter(|parent| !parent.as_os_str().is_empty());
+    let dir = parent.unwrap_or(Path::new("."));
+    if dir.to_str().is_some_and(has_wildcards) {
+        let message = "wildcards are only supported in the archive's file name";
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, message).into());
+    }
+
+    let entries = fs::read_dir(dir).map_err(UnzipError::file("read directory", dir))?;
+    let mut matches = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(UnzipError::file("read directory", dir))?;
+        let file_name = entry.file_name();
+        if let Some(file_name) = file_name.to_str()
+            && glob_match(name, file_name)
+            && entry.path().is_file()
+        {
+            matches
+                .push(parent.map_or_else(|| PathBuf::from(file_name), |dir| dir.join(file_name)));
+        }
+    }
+    if matches.is_empty() {
+        let message = format!("cannot find any matches for wildcard specification {}", name);
+        return Err(io::Error::new(io::ErrorKind::NotFound, message).into());
+    }
+    matches.sort();
+    Ok(matches)
+}
+
+/// Directory `--subdir-per-archive` extracts `archive` into: its name without the
+/// extension, inside `output_dir` if given
+pub fn subdir_for(output_dir: Option<&Path>, archive: &Path) -> PathBuf {
+    let stem = archive.file_stem().unwrap_or(archive.as_os_str());
+    output_dir.map_or_else(|| PathBuf::from(stem), |dir| dir.join(stem))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_archives() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        for name in ["b.zip", "a.zip", "notes.txt", "c.ZIP"] {
+            fs::write(temp_dir.path().join(name), b"").unwrap();
+        }
+        fs::create_dir(temp_dir.path().join("dir.zip")).unwrap();
+
+        let found = expand_archives(&temp_dir.path().join("*.zip")).unwrap();
+        assert_eq!(found, [temp_dir.path().join("a.zip"), temp_dir.path().join("b.zip")]);
+        let found = expand_archives(&temp_dir.path().join("?.*")).unwrap();
+        assert_eq!(found.len(), 3);
+
+        let literal = temp_dir.path().join("missing.zip");
+        assert_eq!(expand_archives(&literal).unwrap(), [literal]);
+        assert!(expand_archives(&temp_dir.path().join("*.tar")).is_err());
+        assert!(expand_archives(&temp_dir.path().join("*/?.zip")).is_err());
+    }
+
+    #[test]
+    fn test_batch_splits_archives_from_patterns() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let (a, b) = (temp_dir.path().join("a.zip"), temp_dir.path().join("b.zip"));
+        fs::write(&a, b"").unwrap();
+        fs::write(&b, b"").unwrap();
+        let args = Args {
+            zipfile: a.clone(),
+            patterns: vec![b.display().to_string(), "*.txt".to_string(), "other.zip".to_string()],
+            subdir_per_archive: true,
+            ..Default::default()
+        };
+
+        // Without --archives, an existing .zip after the archive is a member name
+        let batch = Batch::from_args(&args).unwrap();
+        assert_eq!(batch.archives.len(), 1);
+        assert_eq!(batch.patterns.len(), 3);
+        let infozip = Args { archives: true, compat: Compat::InfoZip, ..args.clone() };
+        assert!(!Batch::from_args(&infozip).unwrap().is_batch());
+
+        let args = Args { archives: true, ..args };
+        let batch = Batch::from_args(&args).unwrap();
+        assert_eq!(batch.archives, [a, b.clone()]);
+        assert_eq!(batch.patterns, ["*.txt", "other.zip"]);
+        assert!(batch.is_batch());
+
+        let args = batch.args_for(&args, &b);
+        assert_eq!(args.zipfile, b);
+        assert_eq!(args.output_dir, Some(PathBuf::from("b")));
+        assert_eq!(subdir_for(Some(Path::new("out")), &b), Path::new("out/b"));
+    }
+
+    #[test]
+    fn test_batch_reads_pattern_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let (include, exclude) = (temp_dir.path().join("in"), temp_dir.path().join("ex"));
+        fs::write(&include, "# sources\nsrc/**/*.rs\n\n*.toml\n").unwrap();
+        fs::write(&exclude, "  target/**\r\n#*.lock\n").unwrap();
+        let args = Args {
+            zipfile: PathBuf::from("a.zip"),
+            patterns: vec!["README*".to_string()],
+            exclude: vec!["*.log".to_string()],
+            include_from: vec![include],
+            exclude_from: vec![exclude],
+            ..Default::default()
+        };
+
+        let batch = Batch::from_args(&args).unwrap();
+        assert_eq!(batch.patterns, ["README*", "src/**/*.rs", "*.toml"]);
+        let args = batch.args_for(&args, Path::new("a.zip"));
+        assert_eq!(args.exclude, ["*.log", "target/**"]);
+
+        let missing = Args { include_from: vec![temp_dir.path().join("nope")], ..args };
+        assert!(Batch::from_args(&missing).is_err());
+    }
+}
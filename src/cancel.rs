@@ -0,0 +1,125 @@
+//! Cooperative cancellation of long-running operations
+//!
+//! A [`CancelToken`] is shared between the code running an extraction or test and
+//! whoever may want to stop it: a GUI's cancel button, or the Ctrl-C handler of the
+//! binary. Extraction checks the token before every entry and between blocks of
+//! data; once it is cancelled, the file being written is removed and the operation
+//! returns [`UnzipError::Cancelled`].
+//!
+//! [`CancelToken::stop`] is the gentler request: the entries being written are
+//! finished first, and the operation stops before the next one. Checkpointed runs
+//! (`--checkpoint-every`) stop this way on SIGTERM, so nothing is left half written.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use std::thread;
+//! use zip::ZipArchive;
+//! use unzip::{CancelToken, ExtractOptions, UnzipError, extract_archive};
+//!
+//! let cancel = CancelToken::new();
+//! let options = ExtractOptions::new().output_dir("out").cancel_token(cancel.clone());
+//! let worker = thread::spawn(move || {
+//!     let mut archive = ZipArchive::new(File::open("big.zip")?)?;
+//!     extract_archive(&mut archive, &options)
+//! });
+//!
+//! cancel.cancel();
+//! match worker.join().unwrap() {
+//!     Err(UnzipError::Cancelled) => println!("stopped"),
+//!     other => println!("{:?}", other),
+//! }
+//! ```
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::error::{Result, UnzipError};
+
+/// Token state: running
+const RUNNING: u8 = 0;
+/// Token state: stop before the next entry
+const STOPPING: u8 = 1;
+/// Token state: stop now
+const CANCELLED: u8 = 2;
+
+/// Shared flag that asks a running operation to stop
+///
+/// Clones refer to the same flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicU8>);
+
+impl CancelToken {
+    /// Create a token that is not cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask every operation holding a clone of this token to stop
+    pub fn cancel(&self) {
+        self.0.store(CANCELLED, Ordering::Relaxed);
+    }
+
+    /// Ask every operation holding a clone of this token to stop once the entries
+    /// it is writing are complete
+    ///
+    /// Has no effect after [`CancelToken::cancel`].
+    pub fn stop(&self) {
+        let _ = self.0.compare_exchange(RUNNING, STOPPING, Ordering::Relaxed, Ordering::Relaxed);
+    }
+
+    /// Whether [`CancelToken::cancel`] or [`CancelToken::stop`] was called
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed) != RUNNING
+    }
+
+    /// Fail with [`UnzipError::Cancelled`] once the token is cancelled or stopped;
+    /// checked before every entry
+    pub(crate) fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            Err(UnzipError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Fail with [`UnzipError::Cancelled`] once the token is cancelled, but not
+    /// when it is only stopped; checked while an entry is written
+    pub(crate) fn check_within_entry(&self) -> Result<()> {
+        if self.0.load(Ordering::Relaxed) == CANCELLED {
+            Err(UnzipError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clones_share_the_flag() {
+        let token = CancelToken::new();
+        let clone = token.clone();
+        assert!(token.check().is_ok());
+
+        clone.cancel();
+        assert!(token.is_cancelled());
+        assert!(matches!(token.check(), Err(UnzipError::Cancelled)));
+    }
+
+    #[test]
+    fn test_stop_finishes_the_current_entry() {
+        let token = CancelToken::new();
+        token.stop();
+        assert!(token.is_cancelled());
+        assert!(matches!(token.check(), Err(UnzipError::Cancelled)));
+        assert!(token.check_within_entry().is_ok());
+
+        token.cancel();
+        token.stop();
+        assert!(token.check_within_entry().is_err());
+    }
+}
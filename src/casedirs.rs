@@ -0,0 +1,178 @@
+//! Directories spelled in more than one case (`--merge-case-dirs`)
+//!
+//! Archives built on case-insensitive systems sometimes hold both `Docs/a.txt` and
+//! `docs/b.txt`. Extracted on a case-insensitive filesystem the two land in one
+//! directory; on a case-sensitive one they end up in two, and which of the two a
+//! tool reading the tree finds depends on the platform. [`CaseDirs`] records every
+//! directory of the selected entries by its lowercased path, so extraction can warn
+//! about directories spelled more than one way, and with `--merge-case-dirs` write
+//! them all under the spelling that comes first in the archive, on any filesystem.
+//!
+//! # Examples
+//!
+//! ```
+//! use unzip::casedirs::CaseDirs;
+//!
+//! let dirs = CaseDirs::from_names(["Docs/a.txt", "docs/b.txt", "docs/Img/c.png"]);
+//! let conflicts: Vec<_> = dirs.conflicts().collect();
+//! assert_eq!(conflicts, [["Docs", "docs"]]);
+//! assert_eq!(dirs.merge("docs/img/d.png"), "Docs/Img/d.png");
+//! ```
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io::{Read, Seek};
+use zip::ZipArchive;
+
+use crate::charset::NameDecoder;
+use crate::error::Result;
+use crate::utils::{PatternMatcher, lowercase};
+
+/// The ways one directory is spelled
+#[derive(Debug, Clone)]
+struct Spellings {
+    /// Spelling of the directory's own name that comes first in the archive
+    first: String,
+    /// Full paths of the directory, one per distinct spelling of its own name, in
+    /// archive order
+    paths: Vec<String>,
+}
+
+/// Spellings of the directories in an archive, by lowercased path
+#[derive(Debug, Clone, Default)]
+pub struct CaseDirs {
+    dirs: BTreeMap<String, Spellings>,
+}
+
+impl CaseDirs {
+    /// Record the directories of entry names, where names ending in `/` are
+    /// directories themselves
+    pub fn from_names<I, S>(names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut dirs = Self::default();
+        for name in names {
+            dirs.add(name.as_ref());
+        }
+        dirs
+    }
+
+    /// Record the directories of the entries `matcher` selects
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the central directory cannot be read
+    pub(crate) fn scan<R: Read + Seek>(
+        archive: &mut ZipArchive<R>,
+        names: &NameDecoder,
+        matcher: &PatternMatcher,
+    ) -> Result<Self> {
+        let mut dirs = Self::default();
+        for i in 0..archive.len() {
+            let file = archive.by_index_raw(i)?;
+            let name = names.decode(&file);
+            let selected = if file.is_dir() {
+                matcher.should_extract_dir(&name)
+            } else {
+                matcher.should_extract(&name)
+            };
+            if selected {
+                dirs.add(&name);
+            }
+        }
+        Ok(dirs)
+    }
+
+    /// Record the directories leading to entry `name`
+    pub fn add(&mut self, name: &str) {
+        let Some(end) = name.rfind('/') else {
+            return;
+        };
+        let mut key = String::new();
+        let mut path_end = 0;
+        for component in name[..end].split('/') {
+            if !key.is_empty() {
+                key.push('/');
+                path_end += 1;
+            }
+            key.push_str(&lowercase(component));
+            path_end += component.len();
+            let path = &name[..path_end];
+            match self.dirs.get_mut(&key) {
+                None => {
+                    let spellings =
+                        Spellings { first: component.to_string(), paths: vec![path.to_string()] };
+                    self.dirs.insert(key.clone(), spellings);
+                },
+                Some(spellings) => {
+                    let own_name = |path: &str| path.rsplit('/').next().map(str::to_string);
+                    if !spellings.paths.iter().any(|seen| own_name(seen) == own_name(path)) {
+                        spellings.paths.push(path.to_string());
+                    }
+                },
+            }
+        }
+    }
+
+    /// Directories spelled more than one way, each as its full paths in archive
+    /// order, the spelling [`merge`](Self::merge) keeps first
+    pub fn conflicts(&self) -> impl Iterator<Item = &[String]> {
+        self.dirs
+            .values()
+            .filter(|dir| dir.paths.len() > 1)
+            .map(|dir| dir.paths.as_slice())
+    }
+
+    /// Whether any directory is spelled more than one way
+    pub fn has_conflicts(&self) -> bool {
+        self.conflicts().next().is_some()
+    }
+
+    /// Entry `name` with each directory spelled the way it first appears
+    pub fn merge<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        let Some(end) = name.rfind('/') else {
+            return Cow::Borrowed(name);
+        };
+        let mut merged = String::with_capacity(name.len());
+        let mut key = String::new();
+        for component in name[..end].split('/') {
+            if !key.is_empty() {
+                key.push('/');
+                merged.push('/');
+            }
+            key.push_str(&lowercase(component));
+            merged.push_str(self.dirs.get(&key).map_or(component, |dir| &dir.first));
+        }
+        merged.push_str(&name[end..]);
+        if merged == name {
+            Cow::Borrowed(name)
+        } else {
+            Cow::Owned(merged)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_keeps_first_spelling_of_each_level() {
+        let dirs = CaseDirs::from_names(["Docs/", "docs/sub/a.txt", "DOCS/Sub/b.txt", "top.txt"]);
+        let conflicts: Vec<_> = dirs.conflicts().collect();
+        assert_eq!(conflicts, [vec!["Docs", "docs", "DOCS"], vec!["docs/sub", "DOCS/Sub"]]);
+        assert_eq!(dirs.merge("DOCS/Sub/b.txt"), "Docs/sub/b.txt");
+        assert_eq!(dirs.merge("docs/SUB/"), "Docs/sub/");
+        assert_eq!(dirs.merge("top.txt"), "top.txt");
+        assert!(matches!(dirs.merge("Docs/sub/a.txt"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_no_conflicts() {
+        let dirs = CaseDirs::from_names(["a/b/c.txt", "a/B.txt", "a/d/", "b/c.txt"]);
+        assert!(!dirs.has_conflicts());
+        assert_eq!(dirs.merge("A/b/x.txt"), "a/b/x.txt");
+    }
+}
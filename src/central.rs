@@ -0,0 +1,503 @@
+//! Streaming central directory reader for listings of huge archives
+//!
+//! `ZipArchive::new` reads every central directory record, and indexes every name,
+//! before the first entry can be looked at. For an archive with a million entries
+//! that is seconds of silence and hundreds of megabytes before `unzip -l` prints a
+//! line. A [`CentralDirectory`] only locates the end of central directory record
+//! (through [`locate_archive`], so self-extractors and cut-off comments work as
+//! they do elsewhere) and then yields one [`CentralRecord`] at a time as it reads
+//! the directory through a buffer: output starts at once and memory stays flat.
+//!
+//! Records carry what the listings show, with the Zip64 sizes, the Info-ZIP
+//! Unicode Path name, the real method of AES entries and the encryption scheme
+//! already applied.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use unzip::central::CentralDirectory;
+//!
+//! for record in CentralDirectory::open(File::open("huge.zip")?)? {
+//!     let record = record?;
+//!     println!("{:>10}  {}", record.size, String::from_utf8_lossy(&record.name));
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use zip::CompressionMethod;
+use zip::result::ZipError;
+
+use crate::encryption::{EncryptionScheme, parse_scheme};
+use crate::error::Result;
+use crate::records::{
+    CENTRAL_HEADER_LEN, CENTRAL_SIGNATURE, DESCRIPTOR_SIGNATURE, EOCD_LEN, LOCAL_HEADER_LEN,
+    LOCAL_SIGNATURE, ZIP64_EOCD_LEN, ZIP64_EOCD_SIGNATURE, ZIP64_LOCATOR_LEN, u16_at, u32_at,
+    u64_at,
+};
+use crate::sfx::locate_archive;
+
+/// Read buffer for the central directory
+const BUFFER_SIZE: usize = 256 * 1024;
+
+/// One central directory file header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CentralRecord {
+    /// "Version made by": host system in the high byte, specification version low
+    pub version_made_by: u16,
+    /// General purpose bit flags
+    pub flags: u16,
+    /// Compression method code, the real one for AES entries
+    pub method: u16,
+    /// DOS modification time, `None` if the fields do not form a valid date
+    pub modified: Option<zip::DateTime>,
+    /// CRC32 of the uncompressed data
+    pub crc32: u32,
+    /// Size of the stored data, from the Zip64 extra field when it does not fit
+    pub compressed_size: u64,
+    /// Uncompressed size, from the Zip64 extra field when it does not fit
+    pub size: u64,
+    /// Host-dependent attributes, the Unix mode in the high 16 bits for Unix hosts
+    pub external_attributes: u32,
+    /// Raw name bytes, from the Unicode Path extra field when it matches the name
+    pub name: Vec<u8>,
+    /// Whether `name` is UTF-8, by the EFS flag or a Unicode Path extra field
+    pub is_utf8: bool,
+    /// Offset of the local file header in the reader, from the Zip64 extra field
+    /// when it does not fit, and moved by any data in front of the archive the
+    /// writer did not account for
+    pub header_offset: u64,
+    /// How the entry is encrypted, from the flags and the AES extra field
+    pub encryption: EncryptionScheme,
+}
+
+impl CentralRecord {
+    /// Host system byte of "version made by" (3 for Unix)
+    pub fn host(&self) -> u8 {
+        (self.version_made_by >> 8) as u8
+    }
+
+    /// Whether the entry is encrypted
+    pub fn is_encrypted(&self) -> bool {
+        self.flags & 1 != 0
+    }
+
+    /// Compression method, as the zip crate names it
+    pub fn compression(&self) -> CompressionMethod {
+        match self.method {
+            0 => CompressionMethod::STORE,
+            8 => CompressionMethod::DEFLATE,
+            9 => CompressionMethod::DEFLATE64,
+            12 => CompressionMethod::BZIP2,
+            14 => CompressionMethod::LZMA,
+            93 => CompressionMethod::ZSTD,
+            95 => CompressionMethod::XZ,
+            #[allow(deprecated)]
+            code => CompressionMethod::Unsupported(code),
+        }
+    }
+}
+
+/// The size fields of a header as written, before its Zip64 extra field is
+/// applied, for `--size-check` (see [`crate::size_check`])
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct HeaderSizes {
+    /// The 32-bit compressed size
+    pub(crate) compressed: u32,
+    /// The 32-bit uncompressed size
+    pub(crate) size: u32,
+    /// Whether the 32-bit header offset is 0xFFFFFFFF, so the Zip64 field holds
+    /// the offset after any sizes; always false for local headers
+    pub(crate) offset_in_zip64: bool,
+    /// The values of the Zip64 extra field in order, empty without one
+    pub(crate) zip64: Vec<u64>,
+}
+
+impl HeaderSizes {
+    /// The size fields of a header with 32-bit sizes `compressed` and `size` and
+    /// the extra field `extra`
+    pub(crate) fn new(compressed: u32, size: u32, offset_in_zip64: bool, mut extra: &[u8]) -> Self {
+        let mut zip64 = Vec::new();
+        while let (Some(id), Some(len)) = (u16_at(extra, 0), u16_at(extra, 2)) {
+            let len = usize::from(len);
+            let Some(data) = extra.get(4..4 + len) else {
+                break;
+            };
+            if id == 0x0001 {
+                zip64 = data.chunks_exact(8).filter_map(|value| u64_at(value, 0)).collect();
+                break;
+            }
+            extra = &extra[4 + len..];
+        }
+        Self { compressed, size, offset_in_zip64, zip64 }
+    }
+}
+
+/// The central directory of an archive, read one record at a time
+pub struct CentralDirectory<R> {
+    reader: BufReader<R>,
+    remaining: u64,
+    layout: Layout,
+    /// How far the directory lies past where the end record says it starts
+    shift: u64,
+}
+
+/// Where the central directory and the end records that follow it lie
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Layout {
+    /// Offset of the first record
+    pub(crate) start: u64,
+    /// Offset just past the last record: the Zip64 end record if there is one,
+    /// otherwise the end of central directory record
+    pub(crate) end: u64,
+    /// Offset of the end of central directory record
+    pub(crate) eocd: u64,
+}
+
+impl<R: Read + Seek> CentralDirectory<R> {
+    /// Locate the central directory of the archive in `reader`
+    ///
+    /// Nothing past the end records is read until the records are iterated.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `reader` cannot be read or holds no archive
+    pub fn open(mut reader: R) -> Result<Self> {
+        let found = locate_archive(&mut reader)?
+            .ok_or(ZipError::InvalidArchive("Could not find central directory end"))?;
+        let mut eocd = [0u8; EOCD_LEN];
+        reader.seek(SeekFrom::Start(found.eocd))?;
+        reader.read_exact(&mut eocd)?;
+        let mut entries = u64::from(u16_at(&eocd, 10).unwrap_or_default());
+        let mut cd_size = u64::from(u32_at(&eocd, 12).unwrap_or_default());
+        let mut cd_start = u64::from(u32_at(&eocd, 16).unwrap_or_default());
+        let mut cd_end = found.eocd;
+        let zip64 = entries == u64::from(u16::MAX)
+            || cd_size == u64::from(u32::MAX)
+            || cd_start == u64::from(u32::MAX);
+        if zip64 {
+            let record = read_zip64_record(&mut reader, found.eocd)?;
+            let field64 = |at| u64_at(&record, at).unwrap_or_default();
+            entries = field64(32);
+            cd_size = field64(40);
+            cd_start = field64(48);
+            cd_end = found.eocd - (ZIP64_LOCATOR_LEN + ZIP64_EOCD_LEN) as u64;
+        }
+
+        // Measured back from the end records, so data in front of the archive
+        // does not matter, whether or not the writer adjusted the offsets
+        let start = cd_end
+            .checked_sub(cd_size)
+            .ok_or(ZipError::InvalidArchive("Central directory size exceeds archive"))?;
+        reader.seek(SeekFrom::Start(start))?;
+        let layout = Layout { start, end: cd_end, eocd: found.eocd };
+        Ok(Self {
+            reader: BufReader::with_capacity(BUFFER_SIZE, reader),
+            remaining: entries,
+            layout,
+            shift: start.wrapping_sub(cd_start),
+        })
+    }
+
+    /// Where the directory and its end records lie in the reader
+    pub(crate) fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// Run `f` on the underlying reader, which it may seek anywhere; records are
+    /// then read on from where they stopped
+    ///
+    /// The read buffer is dropped, so this is for between batches of records.
+    pub(crate) fn with_reader<T>(&mut self, f: impl FnOnce(&mut R) -> Result<T>) -> Result<T> {
+        let position = self.reader.stream_position()?;
+        let result = f(self.reader.get_mut());
+        self.reader.seek(SeekFrom::Start(position))?;
+        result
+    }
+
+    /// Number of records not yet read, as the end record declares them
+    pub fn len(&self) -> u64 {
+        self.remaining
+    }
+
+    /// Whether all records have been read
+    pub fn is_empty(&self) -> bool {
+        self.remaining == 0
+    }
+
+    /// The next record, with its size fields as written
+    pub(crate) fn next_with_sizes(&mut self) -> Option<Result<(CentralRecord, HeaderSizes)>> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let record = self.read_record();
+        if record.is_err() {
+            // Nothing after a damaged record can be trusted to line up
+            self.remaining = 0;
+        }
+        Some(record)
+    }
+
+    fn read_record(&mut self) -> Result<(CentralRecord, HeaderSizes)> {
+        let mut header = [0u8; CENTRAL_HEADER_LEN];
+        self.reader.read_exact(&mut header)?;
+        let field16 = |at| u16_at(&header, at).unwrap_or_default();
+        let field32 = |at| u32_at(&header, at).unwrap_or_default();
+        if field32(0) != CENTRAL_SIGNATURE {
+            return Err(ZipError::InvalidArchive("Invalid Central Directory header").into());
+        }
+        let mut record = CentralRecord {
+            version_made_by: field16(4),
+            flags: field16(8),
+            method: field16(10),
+            modified: zip::DateTime::try_from_msdos(field16(14), field16(12)).ok(),
+            crc32: field32(16),
+            compressed_size: u64::from(field32(20)),
+            size: u64::from(field32(24)),
+            external_attributes: field32(38),
+            name: vec![0u8; usize::from(field16(28))],
+            is_utf8: field16(8) & (1 << 11) != 0,
+            header_offset: u64::from(field32(42)),
+            encryption: EncryptionScheme::None,
+        };
+        let mut extra = vec![0u8; usize::from(field16(30))];
+        self.reader.read_exact(&mut record.name)?;
+        self.reader.read_exact(&mut extra)?;
+        self.reader.seek_relative(i64::from(field16(32)))?;
+        let (compressed, size) = (field32(20), field32(24));
+        apply_extra(&mut record, &extra, compressed, size);
+        record.header_offset = record.header_offset.wrapping_add(self.shift);
+        let offset_in_zip64 = field32(42) == u32::MAX;
+        Ok((record, HeaderSizes::new(compressed, size, offset_in_zip64, &extra)))
+    }
+}
+
+impl<R: Read + Seek> Iterator for CentralDirectory<R> {
+    type Item = Result<CentralRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_sizes().map(|record| record.map(|(record, _)| record))
+    }
+}
+
+/// Read the ZIP64 end of central directory record in front of the locator
+/// ahead of the end record at `eocd`
+///
+/// Writers put it right there, without extensible data; the locator's offset
+/// is not used since data in front of the archive may have moved it.
+fn read_zip64_record<R: Read + Seek>(reader: &mut R, eocd: u64) -> Result<[u8; ZIP64_EOCD_LEN]> {
+    let at = eocd
+        .checked_sub((ZIP64_LOCATOR_LEN + ZIP64_EOCD_LEN) as u64)
+        .ok_or(ZipError::InvalidArchive("Missing ZIP64 end of central directory"))?;
+    let mut record = [0u8; ZIP64_EOCD_LEN];
+    reader.seek(SeekFrom::Start(at))?;
+    reader.read_exact(&mut record)?;
+    if u32_at(&record, 0) != Some(ZIP64_EOCD_SIGNATURE) {
+        return Err(ZipError::InvalidArchive("Invalid ZIP64 end of central directory").into());
+    }
+    Ok(record)
+}
+
+/// Apply the Zip64, Unicode Path and AES extra fields to `record`
+///
+/// `compressed` and `size` are the header's 32-bit fields: the Zip64 field holds
+/// exactly the values those and the header offset mark as too large, in that
+/// order. Local file headers go through here too, to be compared field by field.
+pub(crate) fn apply_extra(
+    record: &mut CentralRecord,
+    mut extra: &[u8],
+    compressed: u32,
+    size: u32,
+) {
+    // Before the AES field replaces the method that tells it apart
+    record.encryption = parse_scheme(record.flags, record.method, extra);
+    while let (Some(id), Some(len)) = (u16_at(extra, 0), u16_at(extra, 2)) {
+        let len = usize::from(len);
+        let Some(data) = extra.get(4..4 + len) else {
+            break;
+        };
+        match id {
+            0x0001 => {
+                let mut values = data.chunks_exact(8).filter_map(|value| u64_at(value, 0));
+                if size == u32::MAX {
+                    record.size = values.next().unwrap_or(record.size);
+                }
+                if compressed == u32::MAX {
+                    record.compressed_size = values.next().unwrap_or(record.compressed_size);
+                }
+                if record.header_offset == u64::from(u32::MAX) {
+                    record.header_offset = values.next().unwrap_or(record.header_offset);
+                }
+            },
+            // Only trusted while it still describes the name it was written for
+            0x7075 if len > 5 && u32_at(data, 1) == Some(crc32fast::hash(&record.name)) => {
+                record.name = data[5..].to_vec();
+                record.is_utf8 = true;
+            },
+            0x9901 if len == 7 => record.method = u16_at(data, 5).unwrap_or(record.method),
+            _ => {},
+        }
+        extra = &extra[4 + len..];
+    }
+}
+
+/// A local file header, read to be checked against its central record
+pub(crate) struct LocalHeader {
+    /// The header in the shape of a central record, its extra fields applied
+    pub(crate) record: CentralRecord,
+    /// Its size fields as written
+    pub(crate) sizes: HeaderSizes,
+    /// Where the entry's data starts
+    pub(crate) data_start: u64,
+}
+
+/// The local file header at `offset`, `None` if there is none there
+pub(crate) fn read_local_header<R: Read + Seek>(
+    reader: &mut R,
+    offset: u64,
+) -> io::Result<Option<LocalHeader>> {
+    let mut header = [0u8; LOCAL_HEADER_LEN];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut header)?;
+    if u32_at(&header, 0) != Some(LOCAL_SIGNATURE) {
+        return Ok(None);
+    }
+    let field16 = |at| u16_at(&header, at).unwrap_or_default();
+    let field32 = |at| u32_at(&header, at).unwrap_or_default();
+    let flags = field16(6);
+    let mut record = CentralRecord {
+        version_made_by: 0,
+        flags,
+        method: field16(8),
+        modified: None,
+        crc32: field32(14),
+        compressed_size: u64::from(field32(18)),
+        size: u64::from(field32(22)),
+        external_attributes: 0,
+        name: vec![0u8; usize::from(field16(26))],
+        is_utf8: flags & (1 << 11) != 0,
+        header_offset: offset,
+        encryption: EncryptionScheme::None,
+    };
+    let mut extra = vec![0u8; usize::from(field16(28))];
+    reader.read_exact(&mut record.name)?;
+    reader.read_exact(&mut extra)?;
+    apply_extra(&mut record, &extra, field32(18), field32(22));
+    let sizes = HeaderSizes::new(field32(18), field32(22), false, &extra);
+    let data_start =
+        offset + LOCAL_HEADER_LEN as u64 + record.name.len() as u64 + extra.len() as u64;
+    Ok(Some(LocalHeader { record, sizes, data_start }))
+}
+
+/// The fields of a data descriptor
+///
+/// Its sizes take 8 bytes each in Zip64 archives and 4 otherwise, with nothing
+/// to tell which, so both readings are kept.
+pub(crate) struct Descriptor {
+    /// The CRC32
+    pub(crate) crc32: u32,
+    /// The compressed size and size read as 32-bit fields
+    pub(crate) narrow: (u64, u64),
+    /// The compressed size and size read as 64-bit fields, `None` if the
+    /// descriptor is too short for them
+    pub(crate) wide: Option<(u64, u64)>,
+}
+
+/// The data descriptor at `offset`, with or without its optional signature
+pub(crate) fn read_descriptor<R: Read + Seek>(
+    reader: &mut R,
+    offset: u64,
+) -> io::Result<Descriptor> {
+    let mut bytes = Vec::with_capacity(24);
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.by_ref().take(24).read_to_end(&mut bytes)?;
+    let fields = match &bytes[..] {
+        fields if u32_at(fields, 0) == Some(DESCRIPTOR_SIGNATURE) => &fields[4..],
+        fields => fields,
+    };
+    let (Some(crc32), Some(compressed), Some(size)) =
+        (u32_at(fields, 0), u32_at(fields, 4), u32_at(fields, 8))
+    else {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    };
+    Ok(Descriptor {
+        crc32,
+        narrow: (u64::from(compressed), u64::from(size)),
+        wide: u64_at(fields, 4).zip(u64_at(fields, 12)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::ZipWriter;
+    use zip::write::SimpleFileOptions;
+
+    #[test]
+    fn test_records_match_zip_archive() {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().large_file(true);
+        zip.add_directory("dir/", SimpleFileOptions::default()).unwrap();
+        zip.start_file("dir/big.txt", options).unwrap();
+        zip.write_all(&b"zip64 sized\n".repeat(100)).unwrap();
+        zip.start_file("stored.bin", options.compression_method(CompressionMethod::STORE))
+            .unwrap();
+        zip.write_all(b"raw").unwrap();
+        let mut data = b"stub in front".to_vec();
+        data.extend(zip.finish().unwrap().into_inner());
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(&data)).unwrap();
+        let directory = CentralDirectory::open(Cursor::new(&data)).unwrap();
+        assert_eq!(directory.len(), 3);
+        let records: Vec<_> = directory.map(|record| record.unwrap()).collect();
+        for (i, record) in records.iter().enumerate() {
+            let file = archive.by_index_raw(i).unwrap();
+            assert_eq!(record.name, file.name_raw());
+            assert_eq!(
+                (record.size, record.compressed_size),
+                (file.size(), file.compressed_size())
+            );
+            assert_eq!(record.crc32, file.crc32());
+            assert_eq!(record.compression(), file.compression());
+            assert_eq!(record.modified, file.last_modified());
+            assert_eq!(record.header_offset, file.header_start());
+        }
+    }
+
+    #[test]
+    fn test_unicode_path_applies_only_to_its_name() {
+        let name = b"caf\x82.txt".to_vec();
+        let unicode = |crc: u32| {
+            let mut field = vec![0x75, 0x70, 14, 0, 1];
+            field.extend(crc.to_le_bytes());
+            field.extend("café.txt".as_bytes());
+            field
+        };
+        let mut record = CentralRecord {
+            version_made_by: 0,
+            flags: 0,
+            method: 8,
+            modified: None,
+            crc32: 0,
+            compressed_size: 0,
+            size: 0,
+            external_attributes: 0,
+            name: name.clone(),
+            is_utf8: false,
+            header_offset: 0,
+            encryption: EncryptionScheme::None,
+        };
+        apply_extra(&mut record, &unicode(0), 0, 0);
+        assert_eq!((record.name.as_slice(), record.is_utf8), (name.as_slice(), false));
+        apply_extra(&mut record, &unicode(crc32fast::hash(&name)), 0, 0);
+        assert_eq!((record.name.as_slice(), record.is_utf8), ("café.txt".as_bytes(), true));
+    }
+
+    #[test]
+    fn test_open_rejects_non_archive() {
+        assert!(CentralDirectory::open(Cursor::new(b"not a zip file".repeat(10))).is_err());
+    }
+}
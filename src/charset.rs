@@ -0,0 +1,345 @@
+//! Entry name character set conversion (`-O` / `-I`)
+//!
+//! ZIP entry names are only guaranteed to be UTF-8 when the language encoding flag
+//! (EFS, general purpose bit 11) is set. Archives written on Windows without that flag
+//! store names in the creating machine's OEM code page, which shows up as mojibake when
+//! read as CP437. Like Info-ZIP, `-O CHARSET` selects the code page for archives made
+//! on DOS/Windows hosts and `-I CHARSET` selects it for archives from other hosts.
+//!
+//! CP437 is decoded with a built-in table; every other code page is resolved through
+//! `encoding_rs` using its WHATWG label (plus common `CPnnn` aliases).
+//!
+//! With `--auto-encoding`, names without an explicit charset are guessed per entry:
+//! bytes that form valid UTF-8 are taken as UTF-8 (many tools forget to set EFS),
+//! then the charset of the current locale is tried, and CP437 is the last resort.
+//!
+//! Names that do not decode in their charset are handled as `--invalid-names`
+//! says (see [`crate::invalid_names`]). `--raw-names` decodes no name at all
+//! (see [`crate::raw_names`]).
+//!
+//! # Examples
+//!
+//! ```
+//! use unzip::charset::Charset;
+//!
+//! let sjis = Charset::from_label("SJIS").unwrap();
+//! assert_eq!(sjis.decode(&[0x83, 0x65, 0x83, 0x58, 0x83, 0x67]), "テスト");
+//!
+//! let cp437 = Charset::from_label("CP437").unwrap();
+//! assert_eq!(cp437.decode(&[0x81, b'b', b'e', b'r']), "über");
+//! ```
+
+use encoding_rs::Encoding;
+use std::borrow::Cow;
+use zip::HasZipMetadata;
+use zip::read::ZipFile;
+
+use crate::args::Args;
+use crate::error::{Result, UnzipError};
+use crate::invalid_names::{InvalidNames, escape_name};
+
+/// "Version made by" host byte for Unix
+const HOST_UNIX: u8 = 3;
+
+/// Upper half (0x80-0xFF) of the IBM PC code page 437
+const CP437_HIGH: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å', //
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ', //
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»', //
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐', //
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧', //
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀', //
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩', //
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+/// A character set used to decode non-UTF-8 entry names
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    /// IBM PC code page 437 (the ZIP specification's default)
+    Cp437,
+    /// Any encoding known to `encoding_rs`
+    Other(&'static Encoding),
+}
+
+impl Charset {
+    /// Resolve a user-supplied charset label
+    ///
+    /// Accepts WHATWG labels (`shift_jis`, `gbk`, `windows-1252`, ...) as well as
+    /// the code page spellings Info-ZIP users are used to (`CP437`, `CP936`, `SJIS`).
+    /// Returns `None` for unknown labels.
+    pub fn from_label(label: &str) -> Option<Self> {
+        let normalized = label.trim().to_ascii_lowercase();
+        let alias = match normalized.as_str() {
+            "cp437" | "ibm437" | "437" | "ibmpc" => return Some(Self::Cp437),
+            "cp936" | "936" => "gbk",
+            "cp932" | "932" => "shift_jis",
+            "cp949" | "949" => "euc-kr",
+            "cp950" | "950" => "big5",
+            other => other,
+        };
+        Encoding::for_label(alias.as_bytes()).map(Self::Other)
+    }
+
+    /// Decode raw name bytes using this character set
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        match self {
+            Self::Cp437 => bytes
+                .iter()
+                .map(|&b| {
+                    if b < 0x80 {
+                        b as char
+                    } else {
+                        CP437_HIGH[(b - 0x80) as usize]
+                    }
+                })
+                .collect(),
+            Self::Other(encoding) => encoding.decode_without_bom_handling(bytes).0.into_owned(),
+        }
+    }
+}
+
+/// Determine the character set of the current locale
+///
+/// Looks at `LC_ALL`, `LC_CTYPE` and `LANG` in that order and resolves the codeset
+/// part of the first one that is set (e.g. `ja_JP.SJIS` yields Shift_JIS).
+/// Returns `None` for the C/POSIX locale or an unknown codeset.
+pub fn locale_charset() -> Option<Charset> {
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())?;
+    let codeset = locale.split_once('.')?.1;
+    let codeset = codeset.split('@').next().unwrap_or(codeset);
+    Charset::from_label(codeset)
+}
+
+/// Guess the character set of a single non-UTF-8-flagged entry name
+///
+/// Prefers UTF-8 when the bytes are valid UTF-8, then `locale` when it decodes the
+/// bytes without errors, and finally CP437, which accepts any byte sequence.
+pub fn detect(bytes: &[u8], locale: Option<Charset>) -> Charset {
+    if std::str::from_utf8(bytes).is_ok() {
+        return Charset::Other(encoding_rs::UTF_8);
+    }
+    if let Some(Charset::Other(encoding)) = locale
+        && encoding.decode_without_bom_handling_and_without_replacement(bytes).is_some()
+    {
+        return Charset::Other(encoding);
+    }
+    Charset::Cp437
+}
+
+/// Decodes entry names according to the `-O` / `-I` / `--auto-encoding` options
+///
+/// Entries with the EFS flag set are always treated as UTF-8. Other entries use the
+/// `-I` charset when the archive was made on Unix and the `-O` charset otherwise.
+/// Without an explicit charset, names are guessed with [`detect`] in auto mode and
+/// otherwise fall back to the zip crate's CP437 interpretation.
+#[derive(Debug, Clone, Default)]
+pub struct NameDecoder {
+    oem: Option<Charset>,
+    iso: Option<Charset>,
+    auto: bool,
+    locale: Option<Charset>,
+    invalid: InvalidNames,
+    raw: bool,
+}
+
+impl NameDecoder {
+    /// Create a decoder from explicit charsets
+    pub fn new(oem: Option<Charset>, iso: Option<Charset>) -> Self {
+        Self { oem, iso, auto: false, locale: None, invalid: InvalidNames::Lossy, raw: false }
+    }
+
+    /// Enable encoding detection for names without an explicit charset
+    ///
+    /// # Arguments
+    ///
+    /// * `locale` - Locale charset to try before CP437 (see [`locale_charset`])
+    pub fn with_detection(mut self, locale: Option<Charset>) -> Self {
+        self.auto = true;
+        self.locale = locale;
+        self
+    }
+
+    /// Handle names that do not decode as `policy` says (`--invalid-names`)
+    pub fn with_invalid_names(mut self, policy: InvalidNames) -> Self {
+        self.invalid = policy;
+        self
+    }
+
+    /// What names that do not decode become
+    pub fn invalid_names(&self) -> InvalidNames {
+        self.invalid
+    }
+
+    /// Decode no name, escaping every one of them instead (`--raw-names`)
+    pub fn with_raw_names(mut self, raw: bool) -> Self {
+        self.raw = raw;
+        self
+    }
+
+    /// Whether names are escaped bytes, to be written to disk as those bytes
+    pub fn raw_names(&self) -> bool {
+        self.raw
+    }
+
+    /// Build a decoder from the `-O`, `-I`, `--auto-encoding`, `--invalid-names`
+    /// and `--raw-names` command-line options
+    ///
+    /// # Errors
+    ///
+    /// Returns [`UnzipError::UnknownCharset`] if either charset label is not recognized
+    pub fn from_args(args: &Args) -> Result<Self> {
+        let resolve = |label: &Option<String>, flag: &'static str| -> Result<Option<Charset>> {
+            match label {
+                Some(label) => match Charset::from_label(label) {
+                    Some(charset) => Ok(Some(charset)),
+                    None => Err(UnzipError::UnknownCharset { flag, label: label.clone() }),
+                },
+                None => Ok(None),
+            }
+        };
+        let decoder =
+            Self::new(resolve(&args.oem_charset, "-O")?, resolve(&args.iso_charset, "-I")?)
+                .with_invalid_names(args.invalid_names)
+                .with_raw_names(args.raw_names);
+        if args.auto_encoding {
+            return Ok(decoder.with_detection(locale_charset()));
+        }
+        Ok(decoder)
+    }
+
+    /// Return the display/extraction name of an entry
+    pub fn decode<'a>(&self, file: &'a ZipFile<'_>) -> Cow<'a, str> {
+        let meta = file.get_metadata();
+        if self.escapes(file.name_raw(), meta.is_utf8, meta.system as u8) {
+            return Cow::Owned(escape_name(file.name_raw()));
+        }
+        if meta.is_utf8 {
+            return Cow::Borrowed(file.name());
+        }
+        match self.charset(file.name_raw(), meta.system as u8) {
+            Some(charset) => Cow::Owned(charset.decode(file.name_raw())),
+            None => Cow::Borrowed(file.name()),
+        }
+    }
+
+    /// Return the display name of raw name bytes from an archive made on `host`,
+    /// for readers that do not go through the zip crate
+    ///
+    /// Without an explicit charset, non-UTF-8 names are CP437, as the zip crate
+    /// reads them.
+    pub fn decode_raw<'a>(&self, raw: &'a [u8], is_utf8: bool, host: u8) -> Cow<'a, str> {
+        if self.escapes(raw, is_utf8, host) {
+            return Cow::Owned(escape_name(raw));
+        }
+        if is_utf8 {
+            return String::from_utf8_lossy(raw);
+        }
+        Cow::Owned(self.charset(raw, host).unwrap_or(Charset::Cp437).decode(raw))
+    }
+
+    /// Whether the name `raw` from `host` does not decode: it is flagged as UTF-8
+    /// and is not, or its `-O` / `-I` charset finds malformed bytes in it
+    ///
+    /// Names in the default CP437 never are.
+    pub fn is_invalid(&self, raw: &[u8], is_utf8: bool, host: u8) -> bool {
+        if is_utf8 {
+            return std::str::from_utf8(raw).is_err();
+        }
+        match self.charset(raw, host) {
+            Some(Charset::Other(encoding)) => {
+                encoding.decode_without_bom_handling_and_without_replacement(raw).is_none()
+            },
+            Some(Charset::Cp437) | None => false,
+        }
+    }
+
+    /// Whether the name `raw` is escaped: all are with `--raw-names`, and the
+    /// invalid ones with `--invalid-names=escape`
+    fn escapes(&self, raw: &[u8], is_utf8: bool, host: u8) -> bool {
+        self.raw || (self.invalid == InvalidNames::Escape && self.is_invalid(raw, is_utf8, host))
+    }
+
+    /// Charset for the non-UTF-8 name `raw` from `host`, `None` for the default
+    fn charset(&self, raw: &[u8], host: u8) -> Option<Charset> {
+        // `zip` only distinguishes DOS, Unix and "unknown"; the unknown hosts seen in
+        // practice (NTFS, VFAT) are Windows, so only Unix archives use `-I`
+        let charset = if host == HOST_UNIX {
+            self.iso
+        } else {
+            self.oem
+        };
+        charset.or_else(|| self.auto.then(|| detect(raw, self.locale)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_charset_from_label_aliases() {
+        assert_eq!(Charset::from_label("CP437"), Some(Charset::Cp437));
+        assert_eq!(Charset::from_label("ibm437"), Some(Charset::Cp437));
+        assert_eq!(Charset::from_label("CP936"), Some(Charset::Other(encoding_rs::GBK)));
+        assert_eq!(Charset::from_label("SJIS"), Some(Charset::Other(encoding_rs::SHIFT_JIS)));
+        assert_eq!(Charset::from_label("cp1252"), Some(Charset::Other(encoding_rs::WINDOWS_1252)));
+        assert_eq!(Charset::from_label("utf-8"), Some(Charset::Other(encoding_rs::UTF_8)));
+        assert_eq!(Charset::from_label("klingon"), None);
+    }
+
+    #[test]
+    fn test_charset_decode_cp437() {
+        let cp437 = Charset::Cp437;
+        assert_eq!(cp437.decode(b"plain.txt"), "plain.txt");
+        assert_eq!(cp437.decode(&[0x84, b'.', b't']), "ä.t");
+        assert_eq!(cp437.decode(&[0xE1, 0xFF]), "ß\u{a0}");
+    }
+
+    #[test]
+    fn test_charset_decode_gbk() {
+        let gbk = Charset::from_label("CP936").unwrap();
+        // "测试" in GBK
+        assert_eq!(gbk.decode(&[0xB2, 0xE2, 0xCA, 0xD4]), "测试");
+    }
+
+    #[test]
+    fn test_detect_prefers_utf8_then_locale() {
+        let gbk = Charset::from_label("GBK");
+        assert_eq!(detect("测试".as_bytes(), gbk), Charset::Other(encoding_rs::UTF_8));
+        assert_eq!(detect(&[0xB2, 0xE2, 0xCA, 0xD4], gbk), gbk.unwrap());
+        // 0x81 0x20 is not a valid GBK sequence
+        assert_eq!(detect(&[0x81, 0x20], gbk), Charset::Cp437);
+        assert_eq!(detect(&[0x81, b'b', b'e', b'r'], None), Charset::Cp437);
+    }
+
+    #[test]
+    fn test_name_decoder_escapes_invalid_names() {
+        let gbk = Charset::from_label("GBK");
+        let escape = NameDecoder::new(gbk, None).with_invalid_names(InvalidNames::Escape);
+        assert_eq!(escape.decode_raw(b"a\xff.txt", true, HOST_UNIX), "a%FF.txt");
+        assert_eq!(escape.decode_raw("100%.txt".as_bytes(), true, HOST_UNIX), "100%.txt");
+        // 0x81 0x20 is not a valid GBK sequence, and -O applies to DOS archives
+        assert!(escape.is_invalid(&[0x81, 0x20], false, 0));
+        assert_eq!(escape.decode_raw(&[0x81, 0x20], false, 0), "%81 ");
+        assert_eq!(escape.decode_raw(&[0xB2, 0xE2], false, 0), "测");
+        assert!(!escape.is_invalid(&[0x81, 0x20], false, HOST_UNIX));
+
+        let lossy = NameDecoder::default();
+        assert_eq!(lossy.decode_raw(b"a\xff.txt", true, HOST_UNIX), "a\u{fffd}.txt");
+
+        // Raw names are never decoded, not even as CP437
+        let raw = NameDecoder::default().with_raw_names(true);
+        assert_eq!(raw.decode_raw(&[0x84, b'%'], false, 0), "%84%25");
+        assert_eq!(raw.decode_raw("ä.txt".as_bytes(), true, HOST_UNIX), "ä.txt");
+    }
+
+    #[test]
+    fn test_name_decoder_from_args_rejects_unknown() {
+        let args = Args { oem_charset: Some("bogus".to_string()), ..Default::default() };
+        assert!(NameDecoder::from_args(&args).is_err());
+    }
+}
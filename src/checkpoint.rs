@@ -0,0 +1,311 @@
+//! Resumable extraction for preemptible workers (`--checkpoint-every`, `--resume`)
+//!
+//! Spot and preemptible cloud instances are evicted with a SIGTERM and a short
+//! grace period, which is not enough to finish extracting a huge archive. With
+//! `--checkpoint-every 30s/1GB`, the entries extracted so far are recorded in a
+//! checkpoint file in the output directory ([`CHECKPOINT_FILE`]) every 30 seconds
+//! or every GiB written, whichever comes first. On SIGTERM (or Ctrl-C) the entries
+//! being written are finished, the checkpoint is saved and the run exits.
+//!
+//! Running the same command again with `--resume` skips the entries the
+//! checkpoint records and extracts the rest. A missing checkpoint starts from the
+//! beginning, so a worker can always pass `--resume`. Once a run completes, the
+//! checkpoint is removed.
+//!
+//! Entries are recorded by their index, CRC32 and size, so a checkpoint of a
+//! different archive matches nothing rather than skipping the wrong entries. A
+//! file cut short by a hard kill (SIGKILL) is not in the checkpoint and is
+//! extracted again, which needs `-o` to replace it.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::time::Duration;
+//! use unzip::checkpoint::parse_checkpoint_interval;
+//!
+//! let interval = parse_checkpoint_interval("30s/1GB")?;
+//! assert_eq!(interval.time, Some(Duration::from_secs(30)));
+//! assert_eq!(interval.bytes, Some(1 << 30));
+//! # Ok::<(), String>(())
+//! ```
+
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::error::{Result, UnzipError};
+use crate::events::ExtractEvent;
+use crate::limits::parse_size;
+use crate::options::ExtractOptions;
+
+/// Name of the checkpoint file in the output directory
+pub const CHECKPOINT_FILE: &str = ".unzip-checkpoint";
+
+/// First line of a checkpoint file, naming its format version
+const HEADER: &str = "unzip-checkpoint 1";
+
+/// How often `--checkpoint-every` saves a checkpoint
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CheckpointInterval {
+    /// Save once this much time passed since the last checkpoint
+    pub time: Option<Duration>,
+    /// Save once this many bytes were extracted since the last checkpoint
+    pub bytes: Option<u64>,
+}
+
+/// Parse a `--checkpoint-every` interval: a time (`30s`, `5min`, `1h`), a size
+/// (`512M`, `1GB`), or both separated by `/`
+///
+/// # Errors
+///
+/// Returns a message suitable for clap if a part is neither a time nor a size, is
+/// zero, or is given twice
+pub fn parse_checkpoint_interval(value: &str) -> std::result::Result<CheckpointInterval, String> {
+    let mut interval = CheckpointInterval::default();
+    for part in value.split('/').map(str::trim) {
+        let replaced = if let Some(time) = parse_duration(part) {
+            interval.time.replace(time).is_some()
+        } else {
+            let bytes = parse_size(part).map_err(|_| {
+                format!(
+                    "invalid checkpoint interval: {} (expected a time like 30s or a size like 1G)",
+                    part
+                )
+            })?;
+            interval.bytes.replace(bytes).is_some()
+        };
+        if replaced {
+            return Err(format!("checkpoint interval given twice: {}", value));
+        }
+    }
+    if interval.time == Some(Duration::ZERO) || interval.bytes == Some(0) {
+        return Err(format!("checkpoint interval must not be zero: {}", value));
+    }
+    Ok(interval)
+}
+
+/// Parse a number of seconds (`s`), minutes (`min`) or hours (`h`)
+///
+/// Plain `m` is left to [`parse_size`], where it means MiB.
+pub(crate) fn parse_duration(value: &str) -> Option<Duration> {
+    let split = value.find(|c: char| !c.is_ascii_digit())?;
+    let (digits, unit) = value.split_at(split);
+    let seconds = match unit.to_ascii_lowercase().as_str() {
+        "s" | "sec" => 1,
+        "min" => 60,
+        "h" => 3600,
+        _ => return None,
+    };
+    let number: u64 = digits.parse().ok()?;
+    number.checked_mul(seconds).map(Duration::from_secs)
+}
+
+/// An extracted entry, identified well enough not to match another archive's
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct EntryId {
+    index: usize,
+    crc32: u32,
+    size: u64,
+}
+
+/// Entries recorded so far and when they were last saved
+#[derive(Debug)]
+struct State {
+    done: Vec<EntryId>,
+    saved_at: Instant,
+    unsaved_bytes: u64,
+}
+
+#[derive(Debug)]
+struct Inner {
+    path: PathBuf,
+    interval: CheckpointInterval,
+    resumed: HashSet<EntryId>,
+    state: Mutex<State>,
+}
+
+/// Checkpoint of one extraction run, shared by its worker threads
+///
+/// Clones refer to the same checkpoint.
+#[derive(Debug, Clone)]
+pub(crate) struct Checkpoint(Arc<Inner>);
+
+impl Checkpoint {
+    /// Checkpoint for the run `options` describe, reading the previous one for
+    /// `--resume`; `None` unless checkpointing or resuming
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an existing checkpoint cannot be read or is not one
+    pub(crate) fn open(options: &ExtractOptions) -> Result<Option<Self>> {
+        if options.dry_run || (options.checkpoint_every.is_none() && !options.resume) {
+            return Ok(None);
+        }
+        let path = options.output_dir.join(CHECKPOINT_FILE);
+        let done = if options.resume {
+            read_checkpoint(&path)?
+        } else {
+            Vec::new()
+        };
+        Ok(Some(Self(Arc::new(Inner {
+            resumed: done.iter().copied().collect(),
+            interval: options.checkpoint_every.unwrap_or_default(),
+            state: Mutex::new(State { done, saved_at: Instant::now(), unsaved_bytes: 0 }),
+            path,
+        }))))
+    }
+
+    /// Whether a previous run already extracted the entry at `index`
+    pub(crate) fn is_done(&self, index: usize, crc32: u32, size: u64) -> bool {
+        self.0.resumed.contains(&EntryId { index, crc32, size })
+    }
+
+    /// Record that the entry at `index` is completely extracted, saving the
+    /// checkpoint if the interval is up
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the checkpoint cannot be written
+    pub(crate) fn record(&self, index: usize, crc32: u32, size: u64) -> Result<()> {
+        let mut state = self.0.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.done.push(EntryId { index, crc32, size });
+        state.unsaved_bytes = state.unsaved_bytes.saturating_add(size);
+        let interval = self.0.interval;
+        if interval.time.is_some_and(|time| state.saved_at.elapsed() >= time)
+            || interval.bytes.is_some_and(|bytes| state.unsaved_bytes >= bytes)
+        {
+            self.save(&mut state)?;
+        }
+        Ok(())
+    }
+
+    /// Write the checkpoint next to its final place and rename it there, so an
+    /// eviction while saving leaves the previous checkpoint intact
+    fn save(&self, state: &mut State) -> Result<()> {
+        let mut text = format!("{}\n", HEADER);
+        for entry in &state.done {
+            let _ = writeln!(text, "{} {:08x} {}", entry.index, entry.crc32, entry.size);
+        }
+        let path = &self.0.path;
+        let temp = path.with_extension("tmp");
+        fs::write(&temp, text).map_err(UnzipError::file("write checkpoint", &temp))?;
+        fs::rename(&temp, path).map_err(UnzipError::file("write checkpoint", path))?;
+        state.saved_at = Instant::now();
+        state.unsaved_bytes = 0;
+        Ok(())
+    }
+
+    /// End the run: remove the checkpoint if `result` is a success, otherwise save
+    /// it and tell how to continue
+    ///
+    /// # Errors
+    ///
+    /// Returns the error in `result`, or one removing the checkpoint
+    pub(crate) fn finish<T>(
+        &self,
+        result: Result<T>,
+        on_event: &mut dyn FnMut(ExtractEvent),
+    ) -> Result<T> {
+        let mut state = self.0.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let path = &self.0.path;
+        if result.is_ok() {
+            match fs::remove_file(path) {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => {
+                    return Err(UnzipError::file("remove checkpoint", path)(error));
+                },
+                _ => {},
+            }
+        } else if !state.done.is_empty() && self.save(&mut state).is_ok() {
+            on_event(ExtractEvent::Warning {
+                message: format!(
+                    "checkpoint: {} entries extracted, recorded in {}; run again with --resume to continue",
+                    state.done.len(),
+                    path.display()
+                ),
+            });
+        }
+        result
+    }
+}
+
+/// Entries recorded in the checkpoint at `path`, none if it does not exist
+fn read_checkpoint(path: &Path) -> Result<Vec<EntryId>> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(UnzipError::file("read checkpoint", path)(error)),
+    };
+    let invalid = || {
+        let error = io::Error::new(io::ErrorKind::InvalidData, "not an unzip checkpoint");
+        UnzipError::file("read checkpoint", path)(error)
+    };
+    let mut lines = text.lines();
+    if lines.next() != Some(HEADER) {
+        return Err(invalid());
+    }
+    lines
+        .map(|line| {
+            let mut fields = line.split(' ');
+            let index = fields.next()?.parse().ok()?;
+            let crc32 = u32::from_str_radix(fields.next()?, 16).ok()?;
+            let size = fields.next()?.parse().ok()?;
+            fields.next().is_none().then_some(EntryId { index, crc32, size })
+        })
+        .collect::<Option<_>>()
+        .ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_checkpoint_interval() {
+        let interval = parse_checkpoint_interval("5min").unwrap();
+        assert_eq!(
+            interval,
+            CheckpointInterval { time: Some(Duration::from_secs(300)), bytes: None }
+        );
+        let interval = parse_checkpoint_interval("512M / 1h").unwrap();
+        assert_eq!(interval.bytes, Some(512 << 20));
+        assert_eq!(interval.time, Some(Duration::from_secs(3600)));
+
+        assert!(parse_checkpoint_interval("30s/10s").is_err());
+        assert!(parse_checkpoint_interval("0s").is_err());
+        assert!(parse_checkpoint_interval("soon").is_err());
+    }
+
+    #[test]
+    fn test_checkpoint_round_trip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let options = ExtractOptions::new()
+            .output_dir(temp_dir.path())
+            .checkpoint_every(Some(CheckpointInterval { time: None, bytes: Some(100) }));
+        let checkpoint = Checkpoint::open(&options).unwrap().unwrap();
+        let path = temp_dir.path().join(CHECKPOINT_FILE);
+
+        checkpoint.record(0, 0xdead_beef, 60).unwrap();
+        assert!(!path.exists());
+        checkpoint.record(2, 0x1234, 60).unwrap();
+        assert!(path.exists());
+        checkpoint.record(3, 0x5678, 1).unwrap();
+        let mut events = Vec::new();
+        let result: Result<()> = Err(UnzipError::Cancelled);
+        assert!(checkpoint.finish(result, &mut |event| events.push(event)).is_err());
+        assert_eq!(events.len(), 1);
+
+        let resumed = Checkpoint::open(&options.clone().resume(true)).unwrap().unwrap();
+        assert!(resumed.is_done(3, 0x5678, 1));
+        assert!(!resumed.is_done(3, 0x5678, 2));
+        assert!(!resumed.is_done(1, 0x5678, 1));
+        resumed.finish(Ok(()), &mut |_| {}).unwrap();
+        assert!(!path.exists());
+
+        fs::write(&path, "something else\n").unwrap();
+        assert!(Checkpoint::open(&options.resume(true)).is_err());
+    }
+}
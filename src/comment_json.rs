@@ -0,0 +1,321 @@
+//! JSON metadata in the archive comment (`--comment-json PATH`)
+//!
+//! Build systems often stamp an archive with a JSON blob in its comment: the
+//! commit it was built from, a manifest version, a signature. `-z` prints the
+//! comment whatever it holds; `--comment-json PATH` checks that it is one JSON
+//! value and writes it to PATH, or to stdout with `-`, so a pipeline gets either
+//! a JSON document it can hand to `jq` or a failure it can branch on:
+//!
+//! - exit status 13: the archive has no comment ([`CommentJsonError::Missing`])
+//! - exit status 14: the comment is not valid JSON, with the byte offset of the
+//!   problem ([`CommentJsonError::Invalid`])
+//!
+//! The comment is checked against RFC 8259, without a JSON library. Whitespace
+//! around the value is dropped and a newline ends the output.
+//!
+//! # Examples
+//!
+//! ```
+//! use unzip::comment_json::{CommentJsonError, comment_json};
+//!
+//! assert_eq!(comment_json(b" {\"commit\": \"3f2a\"}\n"), Ok("{\"commit\": \"3f2a\"}"));
+//! assert_eq!(comment_json(b""), Err(CommentJsonError::Missing));
+//! assert!(matches!(comment_json(b"{\"commit\": }"), Err(CommentJsonError::Invalid { offset: 11, .. })));
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Seek, Write};
+use std::path::Path;
+use zip::ZipArchive;
+
+use crate::error::{Result, UnzipError};
+
+/// Deepest nesting of arrays and objects accepted, so a hostile comment cannot
+/// exhaust the stack
+const MAX_DEPTH: usize = 512;
+
+/// Why the archive comment is not the JSON `--comment-json` expects
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommentJsonError {
+    /// The archive has no comment, or only whitespace
+    Missing,
+    /// The comment is not one valid JSON value
+    Invalid {
+        /// Byte offset in the comment where it stops being valid
+        offset: usize,
+        /// What is wrong there
+        reason: &'static str,
+    },
+}
+
+impl CommentJsonError {
+    /// Exit status of a run failing with this error
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::Missing => 13,
+            Self::Invalid { .. } => 14,
+        }
+    }
+}
+
+impl fmt::Display for CommentJsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing => write!(f, "archive has no comment (--comment-json)"),
+            Self::Invalid { offset, reason } => write!(
+                f,
+                "archive comment is not valid JSON: {} at byte {} (--comment-json)",
+                reason, offset
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CommentJsonError {}
+
+/// The JSON value in `comment`, without the whitespace around it
+///
+/// # Errors
+///
+/// Returns [`CommentJsonError::Missing`] if the comment is empty, or
+/// [`CommentJsonError::Invalid`] if it is not UTF-8 or not one JSON value
+pub fn comment_json(comment: &[u8]) -> std::result::Result<&str, CommentJsonError> {
+    let text = std::str::from_utf8(comment).map_err(|e| CommentJsonError::Invalid {
+        offset: e.valid_up_to(),
+        reason: "invalid UTF-8",
+    })?;
+    let start = text.len() - text.trim_start_matches(is_space).len();
+    let json = text.trim_matches(is_space);
+    if json.is_empty() {
+        return Err(CommentJsonError::Missing);
+    }
+    let mut parser = Parser { bytes: json.as_bytes(), pos: 0, depth: 0 };
+    let checked = parser.value().and_then(|()| match parser.peek_token() {
+        None => Ok(()),
+        Some(_) => Err(parser.error("trailing characters")),
+    });
+    checked
+        .map(|()| json)
+        .map_err(|(offset, reason)| CommentJsonError::Invalid { offset: start + offset, reason })
+}
+
+/// Write the archive comment to `path`, or stdout for `-`, once checked to be
+/// JSON (`--comment-json`)
+///
+/// # Errors
+///
+/// Returns a [`CommentJsonError`] if the comment is missing or not JSON, or an
+/// error if the output cannot be written
+pub fn write_comment_json<R: Read + Seek>(archive: &ZipArchive<R>, path: &Path) -> Result<()> {
+    let json = comment_json(archive.comment())?;
+    if path == Path::new("-") {
+        let mut out = io::stdout().lock();
+        writeln!(out, "{}", json)?;
+        return Ok(out.flush()?);
+    }
+    fs::write(path, format!("{}\n", json)).map_err(UnzipError::file("write comment", path))
+}
+
+/// JSON whitespace; other Unicode spaces are not allowed between tokens
+fn is_space(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\r')
+}
+
+/// Recursive descent syntax check of RFC 8259 JSON
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+/// Offset and reason of a syntax error
+type Syntax = std::result::Result<(), (usize, &'static str)>;
+
+impl Parser<'_> {
+    fn error(&self, reason: &'static str) -> (usize, &'static str) {
+        (self.pos, reason)
+    }
+
+    /// The next byte after whitespace, without consuming it
+    fn peek_token(&mut self) -> Option<u8> {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.bytes.get(self.pos) {
+            self.pos += 1;
+        }
+        self.bytes.get(self.pos).copied()
+    }
+
+    /// Consume `byte` after whitespace, failing with `reason` if it is not next
+    fn expect(&mut self, byte: u8, reason: &'static str) -> Syntax {
+        if self.peek_token() != Some(byte) {
+            return Err(self.error(reason));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn value(&mut self) -> Syntax {
+        match self.peek_token() {
+            Some(b'{') => self.nested(b'}', Self::member),
+            Some(b'[') => self.nested(b']', Self::value),
+            Some(b'"') => self.string(),
+            Some(b't') => self.literal(b"true"),
+            Some(b'f') => self.literal(b"false"),
+            Some(b'n') => self.literal(b"null"),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("expected a value")),
+            None => Err(self.error("unexpected end")),
+        }
+    }
+
+    /// An object or array: `item`s separated by commas up to `close`
+    fn nested(&mut self, close: u8, item: fn(&mut Self) -> Syntax) -> Syntax {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(self.error("nested too deeply"));
+        }
+        self.pos += 1;
+        if self.peek_token() == Some(close) {
+            self.pos += 1;
+        } else {
+            loop {
+                item(self)?;
+                match self.peek_token() {
+                    Some(b',') => self.pos += 1,
+                    Some(byte) if byte == close => {
+                        self.pos += 1;
+                        break;
+                    },
+                    Some(_) => return Err(self.error("expected ',' or a closing bracket")),
+                    None => return Err(self.error("unexpected end")),
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(())
+    }
+
+    /// A `"name": value` pair of an object
+    fn member(&mut self) -> Syntax {
+        if self.peek_token() != Some(b'"') {
+            return Err(self.error("expected a string key"));
+        }
+        self.string()?;
+        self.expect(b':', "expected ':'")?;
+        self.value()
+    }
+
+    fn literal(&mut self, word: &[u8]) -> Syntax {
+        if !self.bytes[self.pos..].starts_with(word) {
+            return Err(self.error("invalid literal"));
+        }
+        self.pos += word.len();
+        Ok(())
+    }
+
+    fn string(&mut self) -> Syntax {
+        self.pos += 1;
+        loop {
+            match self.bytes.get(self.pos) {
+                None => return Err(self.error("unterminated string")),
+                Some(b'"') => break,
+                Some(0..0x20) => return Err(self.error("control character in string")),
+                Some(b'\\') => {
+                    self.pos += 1;
+                    match self.bytes.get(self.pos) {
+                        Some(b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't') => {},
+                        Some(b'u') => {
+                            let hex = self.bytes.get(self.pos + 1..self.pos + 5);
+                            if !hex.is_some_and(|hex| hex.iter().all(u8::is_ascii_hexdigit)) {
+                                return Err(self.error("invalid \\u escape"));
+                            }
+                            self.pos += 4;
+                        },
+                        _ => return Err(self.error("invalid escape")),
+                    }
+                },
+                Some(_) => {},
+            }
+            self.pos += 1;
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn number(&mut self) -> Syntax {
+        if self.bytes[self.pos] == b'-' {
+            self.pos += 1;
+        }
+        match self.bytes.get(self.pos) {
+            Some(b'0') => self.pos += 1,
+            Some(b'1'..=b'9') => self.digits(),
+            _ => return Err(self.error("invalid number")),
+        }
+        if self.bytes.get(self.pos) == Some(&b'.') {
+            self.pos += 1;
+            self.required_digits()?;
+        }
+        if let Some(b'e' | b'E') = self.bytes.get(self.pos) {
+            self.pos += 1;
+            if let Some(b'+' | b'-') = self.bytes.get(self.pos) {
+                self.pos += 1;
+            }
+            self.required_digits()?;
+        }
+        Ok(())
+    }
+
+    fn digits(&mut self) {
+        while self.bytes.get(self.pos).is_some_and(u8::is_ascii_digit) {
+            self.pos += 1;
+        }
+    }
+
+    fn required_digits(&mut self) -> Syntax {
+        if !self.bytes.get(self.pos).is_some_and(u8::is_ascii_digit) {
+            return Err(self.error("invalid number"));
+        }
+        self.digits();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_comment_json_accepts_json() {
+        for valid in [
+            "{}",
+            "[]",
+            "null",
+            "-0.5e+10",
+            "\"caf\u{e9} \\u00e9 \\n\"",
+            "{\"a\": [1, 2.0, {\"b\": null}], \"c\": true, \"d\": \"\\\"\"}",
+        ] {
+            assert_eq!(comment_json(valid.as_bytes()), Ok(valid), "{}", valid);
+        }
+    }
+
+    #[test]
+    fn test_comment_json_rejects_invalid() {
+        let invalid = |comment: &str| match comment_json(comment.as_bytes()) {
+            Err(CommentJsonError::Invalid { offset, reason }) => (offset, reason),
+            other => panic!("{:?} for {}", other, comment),
+        };
+        assert_eq!(invalid("{\"a\": 1,}"), (8, "expected a string key"));
+        assert_eq!(invalid("  [1 2]"), (5, "expected ',' or a closing bracket"));
+        assert_eq!(invalid("{} {}"), (3, "trailing characters"));
+        assert_eq!(invalid("01"), (1, "trailing characters"));
+        assert_eq!(invalid("\"tab\there\""), (4, "control character in string"));
+        assert_eq!(invalid("\"\\x\""), (2, "invalid escape"));
+        assert_eq!(invalid("[1."), (3, "invalid number"));
+        assert_eq!(invalid("nul"), (0, "invalid literal"));
+        assert_eq!(invalid("{\"a\""), (4, "expected ':'"));
+        assert_eq!(invalid("build 42"), (0, "expected a value"));
+        assert_eq!(invalid(&"[".repeat(MAX_DEPTH + 1)), (MAX_DEPTH, "nested too deeply"));
+        assert_eq!(comment_json(b"{\xff}").unwrap_err().exit_code(), 14);
+        assert_eq!(comment_json(b" \r\n").unwrap_err(), CommentJsonError::Missing);
+    }
+}
@@ -0,0 +1,329 @@
+//! Info-ZIP compatibility mode (`--compat`)
+//!
+//! Scripts written against Info-ZIP's UnZip parse its output, check its exit codes
+//! and answer its overwrite prompts; people at a terminal want a progress bar and
+//! human-readable sizes. `--compat=native` (the default) gives the latter.
+//! `--compat=infozip` makes this tool behave the way Info-ZIP would:
+//!
+//! - Output: an `Archive:` header, one `extracting:` / `creating:` / `testing:`
+//!   line per entry on stdout, and no progress bar or closing summary (`-l` and
+//!   `-v` use Info-ZIP's layouts in both modes unless `--format pretty` is given)
+//! - Timestamps: the extended timestamp (UT) field or the DOS time, never NTFS
+//!   times, with the access time set to the modification time when none is stored
+//! - Exit codes: Info-ZIP's numbers ([`exit_code`]) instead of 1 for every error
+//! - Prompting: an existing file without `-o`, `-n` or `--auto-rename` asks
+//!   `replace NAME? [y]es, [n]o, [A]ll, [N]one, [r]ename:` on stdin; an empty
+//!   new name after `r` picks a free `NAME (N).EXT`
+//! - Wildcards: `*` and `?` in member patterns match `/` too, unless `-W` is given
+//!   ([`Compat::wildcards`])
+//!
+//! Unsafe entry names are rejected and writes stay inside the output directory in
+//! both modes.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::io;
+//! use unzip::compat::{Compat, exit_code, parse_compat};
+//!
+//! assert_eq!(parse_compat("infozip"), Ok(Compat::InfoZip));
+//! let missing = io::Error::from(io::ErrorKind::NotFound);
+//! assert_eq!(exit_code([&missing as &(dyn std::error::Error + 'static)]), 9);
+//! ```
+
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use zip::result::ZipError;
+
+use crate::error::{Result, UnzipError};
+use crate::glob::Wildcards;
+use crate::rename::free_path;
+
+/// Which tool's conventions the CLI follows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compat {
+    /// Behave like Info-ZIP's UnZip, for scripts that expect it
+    InfoZip,
+    /// Progress bar, human-readable sizes and summaries (default)
+    #[default]
+    Native,
+}
+
+impl fmt::Display for Compat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InfoZip => write!(f, "infozip"),
+            Self::Native => write!(f, "native"),
+        }
+    }
+}
+
+impl Compat {
+    /// How `*` and `?` treat `/` in member patterns: as Info-ZIP does, which
+    /// `-W` (`stop_at_dir`) turns into this tool's own behavior of stopping there
+    pub fn wildcards(self, stop_at_dir: bool) -> Wildcards {
+        match self {
+            Self::InfoZip if !stop_at_dir => Wildcards::MatchDir,
+            _ => Wildcards::StopAtDir,
+        }
+    }
+}
+
+/// Parse a `--compat` mode: `infozip` or `native`
+///
+/// # Errors
+///
+/// Returns a message suitable for clap if the mode is unknown
+pub fn parse_compat(value: &str) -> std::result::Result<Compat, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "infozip" | "info-zip" => Ok(Compat::InfoZip),
+        "native" => Ok(Compat::Native),
+        _ => Err(format!("invalid compat mode: {} (expected infozip or native)", value)),
+    }
+}
+
+/// Info-ZIP's exit code for an error, given the error and its causes outermost first
+///
+/// The first cause that identifies the failure decides:
+///
+/// | Code | Meaning |
+/// |------|---------|
+/// | 2 | corrupt entry data (CRC mismatch, failed test or read-back) |
+/// | 3 | severe error in the archive format, or any other failure |
+/// | 9 | archive not found, or no zipfile directory in it |
+/// | 11 | no matching entry, or a `--members-from` name missing |
+/// | 12 | overlapping entries, a likely zip bomb |
+/// | 50 | disk full |
+/// | 80 | interrupted by the user |
+/// | 81 | unsupported compression or encryption |
+/// | 82 | missing or wrong password |
+pub fn exit_code<'a, I>(causes: I) -> u8
+where
+    I: IntoIterator<Item = &'a (dyn Error + 'static)>,
+{
+    for cause in causes {
+        let code = if let Some(error) = cause.downcast_ref::<UnzipError>() {
+            unzip_error_code(error)
+        } else if let Some(error) = cause.downcast_ref::<ZipError>() {
+            zip_error_code(error)
+        } else if let Some(error) = cause.downcast_ref::<io::Error>() {
+            io_error_code(error)
+        } else {
+            None
+        };
+        if let Some(code) = code {
+            return code;
+        }
+    }
+    3
+}
+
+fn unzip_error_code(error: &UnzipError) -> Option<u8> {
+    match error {
+        UnzipError::Cancelled => Some(80),
+        UnzipError::PasswordRequired { .. }
+        | UnzipError::InvalidPassword
+        | UnzipError::PasswordPrompt(_) => Some(82),
+        UnzipError::Unsupported(_) => Some(81),
+        UnzipError::MissingMembers(_)
+        | UnzipError::Unmatched(_)
+        | UnzipError::NoSuchEntry { .. }
+        | UnzipError::NoSuchName { .. } => Some(11),
+        UnzipError::DiffFound { .. } => Some(1),
+        UnzipError::OverlappingEntry { .. } => Some(12),
+        UnzipError::CommentJson(error) => Some(error.exit_code()),
+        UnzipError::Incomplete(_) => Some(9),
+        UnzipError::CrcMismatch { .. }
+        | UnzipError::VerifyFailed { .. }
+        | UnzipError::TestFailed { .. }
+        | UnzipError::SizeCheck(_) => Some(2),
+        UnzipError::Zip(error) => zip_error_code(error),
+        UnzipError::Io(error) | UnzipError::File { source: error, .. } => io_error_code(error),
+        UnzipError::Read { source, .. } => io_error_code(source).or(Some(2)),
+        _ => None,
+    }
+}
+
+fn zip_error_code(error: &ZipError) -> Option<u8> {
+    match error {
+        ZipError::InvalidArchive(message) if message.contains("EOCD") => Some(9),
+        ZipError::InvalidArchive(_) => Some(3),
+        ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED) | ZipError::InvalidPassword => {
+            Some(82)
+        },
+        ZipError::UnsupportedArchive(_) => Some(81),
+        ZipError::FileNotFound => Some(11),
+        ZipError::Io(error) => io_error_code(error),
+        _ => None,
+    }
+}
+
+fn io_error_code(error: &io::Error) -> Option<u8> {
+    match error.kind() {
+        io::ErrorKind::NotFound => Some(9),
+        io::ErrorKind::StorageFull => Some(50),
+        io::ErrorKind::InvalidData => Some(2),
+        _ => None,
+    }
+}
+
+/// Answer to an overwrite prompt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Reply {
+    /// Replace the existing file
+    Overwrite,
+    /// Keep the existing file and skip the entry
+    Skip,
+    /// Extract the entry to this path instead
+    Rename(PathBuf),
+}
+
+/// Info-ZIP's `replace NAME?` prompt, remembering `[A]ll` and `[N]one`
+#[derive(Debug, Default)]
+pub(crate) struct OverwritePrompt {
+    /// Answer given for all remaining files
+    always: Option<bool>,
+}
+
+impl OverwritePrompt {
+    /// Ask on stdout whether to replace `path`, reading the answer from stdin
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the prompt cannot be written
+    pub(crate) fn ask(&mut self, path: &Path) -> Result<Reply> {
+        let stdout = io::stdout();
+        self.ask_with(path, &mut io::stdin().lock(), &mut stdout.lock())
+    }
+
+    /// Ask whether to replace `path`, reading answers from `input`
+    ///
+    /// End of input answers `[N]one`, as in Info-ZIP. A new name from `[r]ename`
+    /// stays in the directory of `path` and must not exist yet; an empty one picks
+    /// the first free name `NAME (N).EXT`, as `--auto-rename` does.
+    fn ask_with(
+        &mut self,
+        path: &Path,
+        input: &mut impl BufRead,
+        output: &mut impl Write,
+    ) -> Result<Reply> {
+        match self.always {
+            Some(true) => return Ok(Reply::Overwrite),
+            Some(false) => return Ok(Reply::Skip),
+            None => {},
+        }
+        let original = path;
+        let mut path = path.to_path_buf();
+        loop {
+            write!(output, "replace {}? [y]es, [n]o, [A]ll, [N]one, [r]ename: ", path.display())?;
+            output.flush()?;
+            let Some(answer) = read_answer(input)? else {
+                writeln!(output, "(EOF or read error, treating as \"[N]one\" ...)")?;
+                self.always = Some(false);
+                return Ok(Reply::Skip);
+            };
+            match answer.chars().next() {
+                Some('y' | 'Y') => return Ok(Reply::Overwrite),
+                Some('n') => return Ok(Reply::Skip),
+                Some('A') => {
+                    self.always = Some(true);
+                    return Ok(Reply::Overwrite);
+                },
+                Some('N') => {
+                    self.always = Some(false);
+                    return Ok(Reply::Skip);
+                },
+                Some('r' | 'R') => {
+                    write!(output, "new name: ")?;
+                    output.flush()?;
+                    let Some(name) = read_answer(input)? else {
+                        return Ok(Reply::Skip);
+                    };
+                    if name.is_empty() {
+                        return Ok(Reply::Rename(free_path(original, Path::exists)));
+                    }
+                    let renamed = Path::new(&name);
+                    if name == ".." || renamed.components().count() != 1 {
+                        writeln!(output, "error:  invalid new name [ {} ]", name)?;
+                        continue;
+                    }
+                    path.set_file_name(renamed);
+                    if !path.exists() {
+                        return Ok(Reply::Rename(path));
+                    }
+                },
+                _ => writeln!(output, "error:  invalid response [{}]", answer)?,
+            }
+        }
+    }
+}
+
+/// Read one line of input without its line ending, `None` at end of input
+fn read_answer(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn ask(prompt: &mut OverwritePrompt, path: &Path, input: &str) -> (Reply, String) {
+        let mut output = Vec::new();
+        let reply = prompt.ask_with(path, &mut input.as_bytes(), &mut output).unwrap();
+        (reply, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        let code = |error: &(dyn Error + 'static)| exit_code([error]);
+        assert_eq!(code(&UnzipError::Cancelled), 80);
+        assert_eq!(code(&UnzipError::InvalidPassword), 82);
+        assert_eq!(code(&UnzipError::TestFailed { errors: 1 }), 2);
+        let overlap = UnzipError::OverlappingEntry { name: "a".into(), other: "b".into() };
+        assert_eq!(code(&overlap), 12);
+        assert_eq!(code(&ZipError::InvalidArchive("Could not find EOCD")), 9);
+        assert_eq!(code(&ZipError::InvalidArchive("Invalid file path")), 3);
+        assert_eq!(code(&ZipError::UnsupportedArchive("Compression method not supported")), 81);
+        assert_eq!(code(&UnzipError::Io(io::ErrorKind::StorageFull.into())), 50);
+        assert_eq!(code(&io::Error::other("something else")), 3);
+    }
+
+    #[test]
+    fn test_prompt_answers() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("a.txt");
+        fs::write(&path, "old").unwrap();
+        fs::write(temp_dir.path().join("taken.txt"), "old").unwrap();
+
+        let mut prompt = OverwritePrompt::default();
+        assert_eq!(ask(&mut prompt, &path, "n\n").0, Reply::Skip);
+        assert_eq!(ask(&mut prompt, &path, "y\n").0, Reply::Overwrite);
+
+        let (reply, output) = ask(&mut prompt, &path, "x\nr\n../up\nr\ntaken.txt\nr\nnew.txt\n");
+        assert_eq!(reply, Reply::Rename(temp_dir.path().join("new.txt")));
+        let (reply, _) = ask(&mut prompt, &path, "r\ntaken.txt\nr\n\n");
+        assert_eq!(reply, Reply::Rename(temp_dir.path().join("a (1).txt")));
+        assert!(output.contains("error:  invalid response [x]"));
+        assert!(output.contains("error:  invalid new name [ ../up ]"));
+        assert!(output.contains("replace "));
+
+        assert_eq!(ask(&mut prompt, &path, "A\n").0, Reply::Overwrite);
+        assert_eq!(ask(&mut prompt, &path, "").0, Reply::Overwrite);
+    }
+
+    #[test]
+    fn test_prompt_end_of_input_means_none() {
+        let mut prompt = OverwritePrompt::default();
+        let (reply, output) = ask(&mut prompt, Path::new("a.txt"), "");
+        assert_eq!(reply, Reply::Skip);
+        assert!(output.ends_with("(EOF or read error, treating as \"[N]one\" ...)\n"));
+        assert_eq!(ask(&mut prompt, Path::new("b.txt"), "y\n").0, Reply::Skip);
+    }
+}
@@ -0,0 +1,393 @@
+//! Confined writes below the output directory
+//!
+//! Sanitized entry names ([`crate::path_safety`]) cannot climb out of the output
+//! directory on their own, but a symlink already present in the destination can:
+//! extracting `link/passwd` where `link -> /etc` would overwrite `/etc/passwd`.
+//! [`Destination`] creates every directory and file relative to a handle on the
+//! output directory and refuses to resolve through symlinks that leave it.
+//!
+//! - On Linux, paths are resolved with `openat2(RESOLVE_BENEATH)`, so symlinks that
+//!   stay inside the output directory keep working. Kernels without `openat2`
+//!   (before 5.6) fall back to walking the path with `O_NOFOLLOW`, which refuses
+//!   every symlinked directory.
+//! - The final component is opened with `O_NOFOLLOW`; a symlink in its place is
+//!   replaced by the extracted file rather than written through.
+//! - Elsewhere, existing path components are checked with `symlink_metadata`
+//!   before writing, which is best effort.
+//!
+//! `--follow-symlinks` restores plain path-based writes for destinations that are
+//! meant to be reached through symlinks.
+//!
+//! The directories a [`Destination`] creates are recorded, so their metadata can
+//! be set once extraction is done ([`Destination::created_dirs`]).
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::io::Write;
+//! use std::path::Path;
+//! use unzip::destination::Destination;
+//!
+//! let destination = Destination::open(Path::new("out"), false)?;
+//! destination.create_dir_all(Path::new("out/docs"))?;
+//! destination.create_file(Path::new("out/docs/readme.txt"))?.write_all(b"hello")?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+/// Handle on the output directory that all extracted paths must stay under
+#[derive(Debug)]
+pub struct Destination {
+    root: PathBuf,
+    follow_symlinks: bool,
+    #[cfg(target_os = "linux")]
+    dir: rustix::fd::OwnedFd,
+    /// Directories created so far, parents first
+    created: Mutex<Vec<PathBuf>>,
+}
+
+impl Destination {
+    /// Open the output directory, which must already exist
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - Output directory; extracted paths are joined onto it
+    /// * `follow_symlinks` - Write through existing symlinks (`--follow-symlinks`)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be opened
+    pub fn open(root: &Path, follow_symlinks: bool) -> io::Result<Self> {
+        #[cfg(target_os = "linux")]
+        let dir = {
+            use rustix::fs::{Mode, OFlags, open};
+            open(root, OFlags::PATH | OFlags::DIRECTORY | OFlags::CLOEXEC, Mode::empty())?
+        };
+        Ok(Self {
+            root: root.to_path_buf(),
+            follow_symlinks,
+            #[cfg(target_os = "linux")]
+            dir,
+            created: Mutex::default(),
+        })
+    }
+
+    /// The directories created through this destination, parents first
+    pub fn created_dirs(&self) -> Vec<PathBuf> {
+        self.created.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    fn record_created(&self, dirs: impl IntoIterator<Item = PathBuf>) {
+        self.created.lock().unwrap_or_else(PoisonError::into_inner).extend(dirs);
+    }
+
+    /// Create a directory and its missing parents below the output directory
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a directory cannot be created, or if `path` is outside
+    /// the output directory or reached through a symlink leaving it
+    pub fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        if self.follow_symlinks {
+            let missing = missing_dirs(path);
+            fs::create_dir_all(path)?;
+            self.record_created(missing);
+            return Ok(());
+        }
+        let relative = self.relative(path)?;
+        self.create_dirs(&relative)
+    }
+
+    /// Create (or truncate) a file below the output directory for writing
+    ///
+    /// A symlink at `path` itself is removed and replaced by a regular file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be created, or if `path` is outside the
+    /// output directory or reached through a symlink leaving it
+    pub fn create_file(&self, path: &Path) -> io::Result<File> {
+        if self.follow_symlinks {
+            return File::create(path);
+        }
+        let relative = self.relative(path)?;
+        let Some(name) = relative.file_name() else {
+            return Err(escape_error());
+        };
+        let parent = relative.parent().unwrap_or(Path::new(""));
+        self.open_file(parent, Path::new(name))
+    }
+
+    /// Create a file below the output directory that must not exist yet, as
+    /// `O_EXCL` does
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::ErrorKind::AlreadyExists`] error if something, even a
+    /// symlink, is at `path`, or an error as [`Destination::create_file`] does
+    pub fn create_new_file(&self, path: &Path) -> io::Result<File> {
+        if self.follow_symlinks {
+            return File::create_new(path);
+        }
+        let relative = self.relative(path)?;
+        let Some(name) = relative.file_name() else {
+            return Err(escape_error());
+        };
+        let parent = relative.parent().unwrap_or(Path::new(""));
+        self.open_new_file(parent, Path::new(name))
+    }
+
+    /// Open the file at `path` below the output directory for reading and
+    /// writing, without creating or truncating it (`--patch-in-place`)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if nothing is at `path`, if a symlink is (unless
+    /// symlinks are followed), or as [`Destination::create_file`] does
+    pub fn open_existing_file(&self, path: &Path) -> io::Result<File> {
+        if self.follow_symlinks {
+            return OpenOptions::new().read(true).write(true).open(path);
+        }
+        let relative = self.relative(path)?;
+        let Some(name) = relative.file_name() else {
+            return Err(escape_error());
+        };
+        let parent = relative.parent().unwrap_or(Path::new(""));
+        self.open_existing(parent, Path::new(name))
+    }
+
+    /// `path` relative to the output directory, with only normal components
+    fn relative(&self, path: &Path) -> io::Result<PathBuf> {
+        let relative = path.strip_prefix(&self.root).map_err(|_| escape_error())?;
+        if relative.components().all(|c| matches!(c, Component::Normal(_))) {
+            Ok(relative.to_path_buf())
+        } else {
+            Err(escape_error())
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn create_dirs(&self, relative: &Path) -> io::Result<()> {
+        use rustix::fs::{Mode, mkdirat};
+
+        let mut prefix = PathBuf::new();
+        for component in relative.components() {
+            let parent = self.open_dir(&prefix)?;
+            prefix.push(component);
+            match mkdirat(&parent, component.as_os_str(), Mode::from_raw_mode(0o777)) {
+                Ok(()) => self.record_created([self.root.join(&prefix)]),
+                Err(rustix::io::Errno::EXIST) => {},
+                Err(err) => return Err(err.into()),
+            }
+        }
+        // Checks that the last component, if it already existed, stays beneath
+        self.open_dir(&prefix).map(drop)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn open_file(&self, parent: &Path, name: &Path) -> io::Result<File> {
+        use rustix::fs::{AtFlags, Mode, OFlags, openat, unlinkat};
+
+        let parent = self.open_dir(parent)?;
+        let flags =
+            OFlags::WRONLY | OFlags::CREATE | OFlags::TRUNC | OFlags::NOFOLLOW | OFlags::CLOEXEC;
+        let mode = Mode::from_raw_mode(0o666);
+        match openat(&parent, name, flags, mode) {
+            Err(rustix::io::Errno::LOOP) => {
+                unlinkat(&parent, name, AtFlags::empty())?;
+                Ok(openat(&parent, name, flags | OFlags::EXCL, mode)?.into())
+            },
+            result => Ok(result?.into()),
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn open_new_file(&self, parent: &Path, name: &Path) -> io::Result<File> {
+        use rustix::fs::{Mode, OFlags, openat};
+
+        let parent = self.open_dir(parent)?;
+        let flags = OFlags::WRONLY | OFlags::CREATE | OFlags::EXCL | OFlags::CLOEXEC;
+        Ok(openat(&parent, name, flags, Mode::from_raw_mode(0o666))?.into())
+    }
+
+    #[cfg(target_os = "linux")]
+    fn open_existing(&self, parent: &Path, name: &Path) -> io::Result<File> {
+        use rustix::fs::{Mode, OFlags, openat};
+
+        let parent = self.open_dir(parent)?;
+        let flags = OFlags::RDWR | OFlags::NOFOLLOW | OFlags::CLOEXEC;
+        Ok(openat(&parent, name, flags, Mode::empty())?.into())
+    }
+
+    /// Open a directory below the root without resolving outside of it
+    #[cfg(target_os = "linux")]
+    fn open_dir(&self, relative: &Path) -> io::Result<rustix::fd::OwnedFd> {
+        use rustix::fs::{Mode, OFlags, ResolveFlags, openat, openat2};
+        use rustix::io::Errno;
+
+        let flags = OFlags::PATH | OFlags::DIRECTORY | OFlags::CLOEXEC;
+        let path = if relative.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            relative
+        };
+        let resolve = ResolveFlags::BENEATH | ResolveFlags::NO_MAGICLINKS;
+        match openat2(&self.dir, path, flags, Mode::empty(), resolve) {
+            Ok(fd) => return Ok(fd),
+            Err(Errno::XDEV) => return Err(escape_error()),
+            // Kernels before 5.6, or seccomp filters that do not know the call
+            Err(Errno::NOSYS) | Err(Errno::PERM) => {},
+            Err(err) => return Err(err.into()),
+        }
+
+        // No openat2: walk one component at a time without following symlinks
+        let mut dir = openat(&self.dir, ".", flags, Mode::empty())?;
+        for component in relative.components() {
+            dir = match openat(&dir, component.as_os_str(), flags | OFlags::NOFOLLOW, Mode::empty())
+            {
+                Ok(fd) => fd,
+                // O_PATH | O_NOFOLLOW opens the link itself, which is not a directory
+                Err(Errno::NOTDIR) | Err(Errno::LOOP) => return Err(escape_error()),
+                Err(err) => return Err(err.into()),
+            };
+        }
+        Ok(dir)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn create_dirs(&self, relative: &Path) -> io::Result<()> {
+        self.check_no_symlinks(relative)?;
+        let path = self.root.join(relative);
+        let missing = missing_dirs(&path);
+        fs::create_dir_all(&path)?;
+        self.record_created(missing);
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn open_file(&self, parent: &Path, name: &Path) -> io::Result<File> {
+        self.check_no_symlinks(parent)?;
+        let path = self.root.join(parent).join(name);
+        if fs::symlink_metadata(&path).is_ok_and(|meta| meta.file_type().is_symlink()) {
+            fs::remove_file(&path)?;
+        }
+        File::create(path)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn open_new_file(&self, parent: &Path, name: &Path) -> io::Result<File> {
+        self.check_no_symlinks(parent)?;
+        File::create_new(self.root.join(parent).join(name))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn open_existing(&self, parent: &Path, name: &Path) -> io::Result<File> {
+        let relative = parent.join(name);
+        self.check_no_symlinks(&relative)?;
+        OpenOptions::new().read(true).write(true).open(self.root.join(relative))
+    }
+
+    /// Refuse existing symlinks among the components of `relative`
+    #[cfg(not(target_os = "linux"))]
+    fn check_no_symlinks(&self, relative: &Path) -> io::Result<()> {
+        let mut path = self.root.clone();
+        for component in relative.components() {
+            path.push(component);
+            match fs::symlink_metadata(&path) {
+                Ok(meta) if meta.file_type().is_symlink() => return Err(escape_error()),
+                Ok(_) => {},
+                Err(_) => break,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The directories `fs::create_dir_all(path)` would create, parents first
+pub(crate) fn missing_dirs(path: &Path) -> Vec<PathBuf> {
+    let mut missing: Vec<PathBuf> = path
+        .ancestors()
+        .take_while(|dir| !dir.as_os_str().is_empty() && fs::symlink_metadata(dir).is_err())
+        .map(Path::to_path_buf)
+        .collect();
+    missing.reverse();
+    missing
+}
+
+pub(crate) fn escape_error() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        "path leaves the output directory through a symlink",
+    )
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn test_writes_stay_below_root() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path().join("out");
+        let outside = temp_dir.path().join("outside");
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        symlink(&outside, root.join("link")).unwrap();
+
+        let destination = Destination::open(&root, false).unwrap();
+        destination.create_dir_all(&root.join("a/b")).unwrap();
+        destination
+            .create_file(&root.join("a/b/c.txt"))
+            .unwrap()
+            .write_all(b"ok")
+            .unwrap();
+        assert_eq!(fs::read(root.join("a/b/c.txt")).unwrap(), b"ok");
+
+        let err = destination.create_file(&root.join("link/evil.txt")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert!(destination.create_dir_all(&root.join("link/sub")).is_err());
+        assert!(!outside.join("evil.txt").exists());
+        assert!(!outside.join("sub").exists());
+        assert!(destination.create_file(&outside.join("x.txt")).is_err());
+    }
+
+    #[test]
+    fn test_symlink_in_place_of_file_is_replaced() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path().join("out");
+        fs::create_dir_all(&root).unwrap();
+        let target = temp_dir.path().join("target.txt");
+        fs::write(&target, b"keep").unwrap();
+        symlink(&target, root.join("file.txt")).unwrap();
+
+        let destination = Destination::open(&root, false).unwrap();
+        destination
+            .create_file(&root.join("file.txt"))
+            .unwrap()
+            .write_all(b"new")
+            .unwrap();
+
+        assert_eq!(fs::read(&target).unwrap(), b"keep");
+        assert!(!fs::symlink_metadata(root.join("file.txt")).unwrap().file_type().is_symlink());
+    }
+
+    #[test]
+    fn test_follow_symlinks_writes_through() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path().join("out");
+        let outside = temp_dir.path().join("outside");
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        symlink(&outside, root.join("link")).unwrap();
+
+        let destination = Destination::open(&root, true).unwrap();
+        destination.create_file(&root.join("link/through.txt")).unwrap();
+        assert!(outside.join("through.txt").exists());
+    }
+}
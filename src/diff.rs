@@ -0,0 +1,379 @@
+//! Comparison of an archive with a directory tree (`--diff DIR`)
+//!
+//! Checks that a deployment still matches the archive it came from. Every entry
+//! is looked up under DIR by its sanitized path and reported if it is missing,
+//! has the wrong type, or a file differs in size, CRC32 or modification time.
+//! Files and directories under DIR that no entry accounts for are reported as
+//! extra; an extra directory is reported once, not with everything inside it.
+//!
+//! Sizes and CRC32s are taken from the central directory, so no entry data is
+//! decompressed and encrypted entries are compared without a password; only the
+//! files on disk are read, and only when their size matches. Modification times
+//! are compared like `-f`/`-u` do, with the best time the entry records, allowing
+//! for the two-second resolution of DOS times.
+//!
+//! Patterns (`-x` included) limit both sides of the comparison. Exit codes follow
+//! Info-ZIP: 0 when the tree matches, 1 when differences were found, and the usual
+//! codes for archives that cannot be read.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use std::path::Path;
+//! use zip::ZipArchive;
+//! use unzip::Args;
+//! use unzip::diff::diff_entries;
+//! use clap::Parser;
+//!
+//! let mut archive = ZipArchive::new(File::open("release.zip")?)?;
+//! for finding in diff_entries(&mut archive, &Args::parse(), Path::new("/srv/app"))? {
+//!     println!("{}: {}", finding.path, finding.difference);
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use filetime::FileTime;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+use crate::args::Args;
+use crate::charset::NameDecoder;
+use crate::error::{Result, UnzipError};
+use crate::invalid_names::check_names;
+use crate::path_safety::sanitize_entry_name;
+use crate::timestamps::{DOS_TIME_RESOLUTION, EntryTimes};
+use crate::utils::PatternMatcher;
+use crate::verify::crc32_of;
+
+/// How a path in the tree differs from the archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Difference {
+    /// The entry has no counterpart in the tree
+    Missing,
+    /// The tree has a file or directory no entry accounts for
+    Extra,
+    /// The entry is a file and the tree has a directory, or the other way round
+    WrongType {
+        /// Whether the entry is a directory
+        directory: bool,
+    },
+    /// The file has another size
+    Size { archive: u64, disk: u64 },
+    /// The file has the same size but other content
+    Crc { archive: u32, disk: u32 },
+    /// The file has the same content but another modification time
+    Modified { archive: FileTime, disk: FileTime },
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing => write!(f, "missing"),
+            Self::Extra => write!(f, "not in archive"),
+            Self::WrongType { directory: true } => write!(f, "directory in archive, file on disk"),
+            Self::WrongType { directory: false } => {
+                write!(f, "file in archive, directory on disk")
+            },
+            Self::Size { archive, disk } => {
+                write!(f, "size differs (archive: {}, disk: {})", archive, disk)
+            },
+            Self::Crc { archive, disk } => {
+                write!(f, "content differs (CRC archive: {:08x}, disk: {:08x})", archive, disk)
+            },
+            Self::Modified { archive, disk } => write!(
+                f,
+                "modification time differs by {}s",
+                disk.unix_seconds() - archive.unix_seconds()
+            ),
+        }
+    }
+}
+
+/// A difference found for one path, relative to the compared directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffFinding {
+    /// Path with `/` separators, ending in `/` for directories
+    pub path: String,
+    /// What differs
+    pub difference: Difference,
+}
+
+/// Compare the entries selected by `args` with the tree under `dir`
+///
+/// Entries come first, in archive order, then extra paths in sorted order.
+///
+/// # Errors
+///
+/// Returns an error if the archive cannot be read or `dir` cannot be listed
+pub fn diff_entries<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    args: &Args,
+    dir: &Path,
+) -> Result<Vec<DiffFinding>> {
+    let matcher = args.matcher();
+    let names = NameDecoder::from_args(args)?;
+    check_names(archive, &names)?;
+    let mut findings = Vec::new();
+    // Paths the archive accounts for, including the parents of its entries
+    let mut known = HashSet::new();
+
+    for i in 0..archive.len() {
+        let file = archive.by_index_raw(i)?;
+        let name = names.decode(&file).into_owned();
+        if !matcher.should_extract(&name) {
+            continue;
+        }
+        // Extraction skips unsafe names, so they have nothing to match
+        let Ok(relative) = sanitize_entry_name(&name) else {
+            continue;
+        };
+        known.extend(relative.ancestors().map(Path::to_path_buf));
+
+        let directory = file.is_dir();
+        let path = display_path(&relative, directory);
+        let difference = match fs::metadata(dir.join(&relative)) {
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Some(Difference::Missing),
+            Err(error) => {
+                return Err(UnzipError::file("read metadata of", &dir.join(&relative))(error));
+            },
+            Ok(meta) if meta.is_dir() != directory => Some(Difference::WrongType { directory }),
+            Ok(_) if directory => None,
+            Ok(meta) if meta.len() != file.size() => {
+                Some(Difference::Size { archive: file.size(), disk: meta.len() })
+            },
+            Ok(meta) => {
+                let outpath = dir.join(&relative);
+                let disk = File::open(&outpath)
+                    .and_then(|mut disk| crc32_of(&mut disk))
+                    .map_err(UnzipError::file("read", &outpath))?;
+                let modified = EntryTimes::from_entry(&file).modified;
+                if disk != file.crc32() {
+                    Some(Difference::Crc { archive: file.crc32(), disk })
+                } else if let (Some(archive), Ok(disk)) = (modified, meta.modified()) {
+                    let disk = FileTime::from_system_time(disk);
+                    let skew = (disk.unix_seconds() - archive.unix_seconds()).abs();
+                    (skew >= DOS_TIME_RESOLUTION).then_some(Difference::Modified { archive, disk })
+                } else {
+                    None
+                }
+            },
+        };
+        if let Some(difference) = difference {
+            findings.push(DiffFinding { path, difference });
+        }
+    }
+
+    let mut extra = Vec::new();
+    find_extra(dir, Path::new(""), &known, &matcher, &mut extra)?;
+    extra.sort();
+    findings.extend(
+        extra
+            .into_iter()
+            .map(|path| DiffFinding { path, difference: Difference::Extra }),
+    );
+    Ok(findings)
+}
+
+/// Collect the paths under `dir.join(relative)` that are not `known`, without
+/// descending into extra directories
+fn find_extra(
+    dir: &Path,
+    relative: &Path,
+    known: &HashSet<PathBuf>,
+    matcher: &PatternMatcher,
+    extra: &mut Vec<String>,
+) -> Result<()> {
+    let listed = dir.join(relative);
+    let entries = fs::read_dir(&listed).map_err(UnzipError::file("list", &listed))?;
+    for entry in entries {
+        let entry = entry.map_err(UnzipError::file("list", &listed))?;
+        let path = relative.join(entry.file_name());
+        let directory = entry.file_type().map_err(UnzipError::file("list", &listed))?.is_dir();
+        if known.contains(&path) {
+            if directory {
+                find_extra(dir, &path, known, matcher, extra)?;
+            }
+            continue;
+        }
+        let shown = display_path(&path, directory);
+        if matcher.should_extract(&shown) {
+            extra.push(shown);
+        }
+    }
+    Ok(())
+}
+
+/// `path` with `/` separators, and a trailing `/` if it is a directory
+fn display_path(path: &Path, directory: bool) -> String {
+    let mut shown = path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    if directory {
+        shown.push('/');
+    }
+    shown
+}
+
+/// Run the `--diff` comparison with `dir` and print its findings
+///
+/// # Errors
+///
+/// Returns [`UnzipError::DiffFound`] if the tree differs from the archive, an
+/// error if the findings cannot be written to stdout, plus the errors of
+/// [`diff_entries`]
+pub fn diff_archive<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    args: &Args,
+    dir: &Path,
+) -> Result<()> {
+    let findings = diff_entries(archive, args, dir)?;
+
+    if args.quiet < 2 {
+        let stdout = io::stdout();
+        let mut out = BufWriter::new(stdout.lock());
+        for finding in &findings {
+            writeln!(out, "{}: {}", finding.path, finding.difference)?;
+        }
+        if findings.is_empty() {
+            let (archive, dir) = (args.zipfile.display(), dir.display());
+            writeln!(out, "No differences between {} and {}.", archive, dir)?;
+        } else {
+            writeln!(
+                out,
+                "{} difference(s) between {} and {}.",
+                findings.len(),
+                args.zipfile.display(),
+                dir.display()
+            )?;
+        }
+        out.flush()?;
+    }
+
+    if !findings.is_empty() {
+        return Err(UnzipError::DiffFound { differences: findings.len() });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::io::{Cursor, Write};
+    use zip::DateTime;
+    use zip::ZipWriter;
+    use zip::write::SimpleFileOptions;
+
+    fn create_zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let time = DateTime::from_date_and_time(2024, 1, 15, 10, 30, 0).unwrap();
+        let mut buf = Vec::new();
+        {
+            let mut zip = ZipWriter::new(Cursor::new(&mut buf));
+            for (name, content) in files {
+                let options = SimpleFileOptions::default().last_modified_time(time);
+                if name.ends_with('/') {
+                    zip.add_directory(*name, options).unwrap();
+                } else {
+                    zip.start_file(*name, options).unwrap();
+                    zip.write_all(content).unwrap();
+                }
+            }
+            zip.finish().unwrap();
+        }
+        buf
+    }
+
+    fn diff(zip_data: Vec<u8>, dir: &Path, extra_args: &[&str]) -> Vec<(String, Difference)> {
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+        let mut argv = vec!["unzip", "-qq", "test.zip"];
+        argv.extend_from_slice(extra_args);
+        let args = Args::parse_from(argv);
+        diff_entries(&mut archive, &args, dir)
+            .unwrap()
+            .into_iter()
+            .map(|finding| (finding.path, finding.difference))
+            .collect()
+    }
+
+    fn write(path: &Path, content: &[u8]) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+        let time = FileTime::from_unix_time(1_705_314_600, 0);
+        filetime::set_file_mtime(path, time).unwrap();
+    }
+
+    #[test]
+    fn test_diff_reports_each_kind() {
+        let zip_data = create_zip(&[
+            ("app/", b""),
+            ("app/same.txt", b"same"),
+            ("app/size.txt", b"short"),
+            ("app/crc.txt", b"abcd"),
+            ("app/time.txt", b"time"),
+            ("app/gone.txt", b"gone"),
+            ("app/kind", b"file"),
+        ]);
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        write(&root.join("app/same.txt"), b"same");
+        write(&root.join("app/size.txt"), b"longer");
+        write(&root.join("app/crc.txt"), b"abce");
+        fs::write(root.join("app/time.txt"), b"time").unwrap();
+        fs::create_dir_all(root.join("app/kind")).unwrap();
+        write(&root.join("app/new.txt"), b"new");
+        write(&root.join("cache/a/b.txt"), b"b");
+
+        let findings = diff(zip_data.clone(), root, &[]);
+        let paths: Vec<_> = findings.iter().map(|(path, _)| path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "app/size.txt",
+                "app/crc.txt",
+                "app/time.txt",
+                "app/gone.txt",
+                "app/kind",
+                "app/new.txt",
+                "cache/"
+            ]
+        );
+        assert_eq!(findings[0].1, Difference::Size { archive: 5, disk: 6 });
+        assert!(matches!(findings[1].1, Difference::Crc { .. }));
+        assert!(matches!(findings[2].1, Difference::Modified { .. }));
+        assert_eq!(findings[3].1, Difference::Missing);
+        assert_eq!(findings[4].1, Difference::WrongType { directory: false });
+        assert_eq!(findings[6].1, Difference::Extra);
+
+        // Patterns limit both the entries and the extra paths
+        let findings = diff(zip_data, root, &["app/*", "-x", "app/time.txt"]);
+        let paths: Vec<_> = findings.iter().map(|(path, _)| path.as_str()).collect();
+        assert_eq!(
+            paths,
+            ["app/size.txt", "app/crc.txt", "app/gone.txt", "app/kind", "app/new.txt"]
+        );
+    }
+
+    #[test]
+    fn test_diff_archive_fails_on_differences() {
+        let zip_data = create_zip(&[("a.txt", b"a")]);
+        let temp_dir = tempfile::tempdir().unwrap();
+        write(&temp_dir.path().join("a.txt"), b"a");
+        let args = Args::parse_from(["unzip", "-qq", "test.zip"]);
+
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+        assert!(diff_archive(&mut archive, &args, temp_dir.path()).is_ok());
+
+        write(&temp_dir.path().join("b.txt"), b"b");
+        assert!(matches!(
+            diff_archive(&mut archive, &args, temp_dir.path()),
+            Err(UnzipError::DiffFound { differences: 1 })
+        ));
+    }
+}
@@ -0,0 +1,158 @@
+//! Output directory timestamps (`--stamp-output-dir`)
+//!
+//! Build systems and sync jobs often decide whether an extracted tree is up to
+//! date by the modification time of its top directory, which extraction sets to
+//! whenever the last file was created. `--stamp-output-dir` sets it once
+//! extraction is done instead:
+//!
+//! - `--stamp-output-dir` or `--stamp-output-dir=archive`: to the modification
+//!   time of the archive file named on the command line
+//! - `--stamp-output-dir=newest`: to the newest modification time among the files
+//!   extracted, as they were written; with nothing extracted, it is left alone
+//!
+//! Only the output directory itself (`-d`, or the one `--smart-dir` picked) is
+//! stamped, not the directories below it or those of nested archives. Nothing is
+//! stamped after `--dry-run` or a failed extraction.
+//!
+//! # Examples
+//!
+//! ```
+//! use unzip::dirstamp::{StampSource, parse_stamp_source};
+//!
+//! assert_eq!(parse_stamp_source("newest"), Ok(StampSource::Newest));
+//! assert!(parse_stamp_source("oldest").is_err());
+//! ```
+
+use std::fmt;
+use std::path::Path;
+
+use filetime::FileTime;
+
+use crate::error::{Result, UnzipError};
+
+/// What `--stamp-output-dir` takes the output directory's time from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StampSource {
+    /// The archive file's modification time (default)
+    #[default]
+    Archive,
+    /// The newest modification time among the extracted files
+    Newest,
+}
+
+impl fmt::Display for StampSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Archive => write!(f, "archive"),
+            Self::Newest => write!(f, "newest"),
+        }
+    }
+}
+
+/// Parse a `--stamp-output-dir` source: `archive` or `newest`
+///
+/// # Errors
+///
+/// Returns a message suitable for clap if the source is unknown
+pub fn parse_stamp_source(value: &str) -> std::result::Result<StampSource, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "archive" => Ok(StampSource::Archive),
+        "newest" => Ok(StampSource::Newest),
+        _ => Err(format!("invalid stamp source: {} (expected archive or newest)", value)),
+    }
+}
+
+/// The modification time given to the output directory once extraction is done
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputDirStamp {
+    /// This time, such as the archive file's
+    Time(FileTime),
+    /// The newest modification time among the extracted files
+    Newest,
+}
+
+impl OutputDirStamp {
+    /// The stamp `source` asks for, reading the modification time of `archive`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `source` is the archive and its metadata cannot be read
+    pub fn from_source(source: StampSource, archive: &Path) -> Result<Self> {
+        match source {
+            StampSource::Archive => {
+                let metadata = std::fs::metadata(archive)
+                    .map_err(UnzipError::file("read modification time of", archive))?;
+                Ok(Self::Time(FileTime::from_last_modification_time(&metadata)))
+            },
+            StampSource::Newest => Ok(Self::Newest),
+        }
+    }
+}
+
+/// Tracks the newest file written and stamps the output directory at the end
+pub(crate) struct DirStamper {
+    stamp: OutputDirStamp,
+    newest: Option<FileTime>,
+}
+
+impl DirStamper {
+    pub(crate) fn new(stamp: OutputDirStamp) -> Self {
+        Self { stamp, newest: None }
+    }
+
+    /// Note the file written at `path`, once its timestamps were set
+    pub(crate) fn file_written(&mut self, path: &Path) {
+        if self.stamp != OutputDirStamp::Newest {
+            return;
+        }
+        if let Ok(metadata) = std::fs::metadata(path) {
+            let modified = FileTime::from_last_modification_time(&metadata);
+            self.newest = self.newest.max(Some(modified));
+        }
+    }
+
+    /// Set the modification time of `dir`, keeping its access time
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory's time cannot be set
+    pub(crate) fn stamp(self, dir: &Path) -> Result<()> {
+        let time = match self.stamp {
+            OutputDirStamp::Time(time) => time,
+            OutputDirStamp::Newest => match self.newest {
+                Some(time) => time,
+                None => return Ok(()),
+            },
+        };
+        filetime::set_file_mtime(dir, time)
+            .map_err(UnzipError::file("set modification time of", dir))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stamp_newest_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let (old, new) = (temp_dir.path().join("old"), temp_dir.path().join("new"));
+        std::fs::write(&old, b"").unwrap();
+        std::fs::write(&new, b"").unwrap();
+        filetime::set_file_mtime(&old, FileTime::from_unix_time(1_000_000_000, 0)).unwrap();
+        filetime::set_file_mtime(&new, FileTime::from_unix_time(1_600_000_000, 0)).unwrap();
+
+        let mut stamper = DirStamper::new(OutputDirStamp::Newest);
+        stamper.file_written(&new);
+        stamper.file_written(&old);
+        stamper.stamp(temp_dir.path()).unwrap();
+        let metadata = std::fs::metadata(temp_dir.path()).unwrap();
+        let stamped = FileTime::from_last_modification_time(&metadata);
+        assert_eq!(stamped, FileTime::from_unix_time(1_600_000_000, 0));
+
+        // Nothing written: the directory keeps its time
+        DirStamper::new(OutputDirStamp::Newest)
+            .stamp(Path::new("/nonexistent"))
+            .unwrap();
+    }
+}
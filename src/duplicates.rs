@@ -0,0 +1,508 @@
+//! Entries that share a name (`--duplicates first|last|rename|error`)
+//!
+//! The ZIP format does not forbid two entries with the same name, and appending
+//! tools produce them routinely. The zip crate indexes entries by name, so only
+//! the last entry of each name is ever seen and the others are dropped without
+//! a word. Before extraction the central directory is therefore read as it is
+//! stored, and the policy decides:
+//!
+//! - `last` (default) extracts only the last entry of each name, as before
+//! - `first` extracts only the first one
+//! - `rename` extracts them all, the later ones under `NAME (1).EXT`,
+//!   `NAME (2).EXT`, ... as [`--auto-rename`](crate::rename) numbers them,
+//!   skipping names other entries already use
+//! - `error` fails before anything is written, naming every duplicate
+//!
+//! Names are compared as decoded (`-O`/`-I` apply), before `-j`, `--transform`
+//! and the like; directory entries may repeat freely. Unless `-q` is given, a
+//! warning sums up the duplicates found.
+//!
+//! Where the policy keeps other entries than the zip crate would, the archive is
+//! read through a [`PatchedDirectory`]: the same bytes up to the central
+//! directory, followed by a rewritten directory without the entries left out
+//! and with the renamed ones under their new names. With `--member-index`, the
+//! rewritten directory holds just the entry asked for, so that the position
+//! counts every record, duplicates included, as listings show them.
+//!
+//! Duplicates are resolved where extraction knows the archive's source, as for
+//! the command line and [`extract_archive_threaded`](crate::extract_archive_threaded);
+//! functions given a `ZipArchive` see the archive as the zip crate reads it.
+//!
+//! # Examples
+//!
+//! ```
+//! use unzip::duplicates::{DuplicatePolicy, parse_duplicate_policy};
+//!
+//! assert_eq!(parse_duplicate_policy("rename"), Ok(DuplicatePolicy::Rename));
+//! assert_eq!(DuplicatePolicy::default(), DuplicatePolicy::Last);
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Arc;
+use zip::result::ZipError;
+
+use crate::central::{CentralDirectory, Layout};
+use crate::error::Result;
+use crate::options::ExtractOptions;
+use crate::records::{
+    CENTRAL_HEADER_LEN, EOCD_LEN, ZIP64_EOCD_LEN, ZIP64_LOCATOR_LEN, u16_at, u32_at, u64_at,
+};
+use crate::rename::free_path;
+
+/// Most names listed in the warning about duplicates
+const SUMMARY_NAMES: usize = 5;
+
+/// General purpose flag marking a UTF-8 name
+const UTF8_FLAG: u16 = 1 << 11;
+
+/// Which of the entries sharing a name to extract (`--duplicates`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Only the first entry of each name
+    First,
+    /// Only the last entry of each name (default)
+    #[default]
+    Last,
+    /// All of them, the later ones under numbered names
+    Rename,
+    /// None: fail before extracting anything
+    Error,
+}
+
+impl fmt::Display for DuplicatePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::First => write!(f, "first"),
+            Self::Last => write!(f, "last"),
+            Self::Rename => write!(f, "rename"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Parse a `--duplicates` policy: `first`, `last`, `rename` or `error`
+///
+/// # Errors
+///
+/// Returns a message suitable for clap if the policy is unknown
+pub fn parse_duplicate_policy(value: &str) -> Result<DuplicatePolicy, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "first" => Ok(DuplicatePolicy::First),
+        "last" => Ok(DuplicatePolicy::Last),
+        "rename" => Ok(DuplicatePolicy::Rename),
+        "error" => Ok(DuplicatePolicy::Error),
+        _ => Err(format!(
+            "invalid duplicates policy: {} (expected first, last, rename or error)",
+            value
+        )),
+    }
+}
+
+/// Entry names that appear more than once, with the `error` policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateNames {
+    /// The repeated names, in sorted order
+    pub names: Vec<String>,
+}
+
+impl fmt::Display for DuplicateNames {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "names used by more than one entry (--duplicates=error, {}):",
+            self.names.len()
+        )?;
+        for name in &self.names {
+            write!(f, "\n  {}", name)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for DuplicateNames {}
+
+/// A central directory rewritten by the duplicates policy, read in place of the
+/// archive's own
+///
+/// Only extraction creates these; see the [module documentation](self).
+#[derive(Debug)]
+pub struct PatchedDirectory {
+    /// Offset of the central directory, where the rewritten one takes over
+    start: u64,
+    /// The rewritten directory and end records
+    tail: Vec<u8>,
+}
+
+/// An archive read with its central directory replaced by a [`PatchedDirectory`]
+pub(crate) struct PatchedReader<R> {
+    inner: R,
+    directory: Arc<PatchedDirectory>,
+    position: u64,
+}
+
+impl<R: Read + Seek> PatchedReader<R> {
+    /// Read the archive in `inner`, which must be at its start, through `directory`
+    pub(crate) fn new(inner: R, directory: Arc<PatchedDirectory>) -> Self {
+        Self { inner, directory, position: 0 }
+    }
+}
+
+impl<R: Read + Seek> Read for PatchedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let start = self.directory.start;
+        let read = if self.position < start {
+            let limit = usize::try_from(start - self.position).unwrap_or(usize::MAX);
+            let len = limit.min(buf.len());
+            self.inner.read(&mut buf[..len])?
+        } else {
+            let tail = &self.directory.tail;
+            let at = usize::try_from(self.position - start).unwrap_or(usize::MAX).min(tail.len());
+            (&tail[at..]).read(buf)?
+        };
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl<R: Read + Seek> Seek for PatchedReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let len = self.directory.start + self.directory.tail.len() as u64;
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => len.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+        };
+        let target = target.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "seek before start of archive")
+        })?;
+        // Reads in front of the directory continue from where the inner reader is
+        if target < self.directory.start {
+            self.inner.seek(SeekFrom::Start(target))?;
+        }
+        self.position = target;
+        Ok(target)
+    }
+}
+
+/// What the policy decided for the duplicates of an archive
+#[derive(Debug, Default)]
+pub(crate) struct Duplicates {
+    policy: DuplicatePolicy,
+    /// Repeated names, sorted
+    names: Vec<String>,
+    /// The directory to read the archive through, if the zip crate's view of it
+    /// is not what the policy asks for
+    patch: Option<PatchedDirectory>,
+}
+
+impl Duplicates {
+    /// Read the central directory of the archive in `reader` and apply the
+    /// policy of `options` to the file entries that share a name
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DuplicateNames`] if there are duplicates and the policy is
+    /// `error`, or an error if the central directory cannot be read
+    pub(crate) fn scan<R: Read + Seek>(mut reader: R, options: &ExtractOptions) -> Result<Self> {
+        let mut directory = CentralDirectory::open(&mut reader)?;
+        let layout = directory.layout();
+        let mut names = Vec::new();
+        let mut indices: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, record) in directory.by_ref().enumerate() {
+            let record = record?;
+            let name = options.names.decode_raw(&record.name, record.is_utf8, record.host());
+            let name = name.into_owned();
+            if !name.ends_with('/') {
+                indices.entry(name.clone()).or_default().push(i);
+            }
+            names.push(name);
+        }
+        drop(directory);
+
+        let mut repeated: Vec<(&String, &Vec<usize>)> =
+            indices.iter().filter(|(_, indices)| indices.len() > 1).collect();
+        repeated.sort();
+        let policy = options.duplicates;
+        let mut duplicates = Self {
+            policy,
+            names: repeated.iter().map(|(name, _)| (*name).clone()).collect(),
+            patch: None,
+        };
+        if duplicates.names.is_empty() {
+            return Ok(duplicates);
+        }
+
+        // Records left out, and the later ones renamed
+        let mut left_out = HashSet::new();
+        let mut renamed = HashMap::new();
+        match (options.member_index, policy) {
+            (Some(member), _) => left_out.extend((0..names.len()).filter(|&i| i != member)),
+            (None, DuplicatePolicy::Last) => return Ok(duplicates),
+            (None, DuplicatePolicy::Error) => {
+                return Err(DuplicateNames { names: duplicates.names }.into());
+            },
+            (None, DuplicatePolicy::First) => {
+                for (_, indices) in &repeated {
+                    left_out.extend(&indices[1..]);
+                }
+            },
+            (None, DuplicatePolicy::Rename) => {
+                let taken: HashSet<&str> = names.iter().map(String::as_str).collect();
+                let mut numbered: HashSet<String> = HashSet::new();
+                for (name, indices) in &repeated {
+                    for &i in &indices[1..] {
+                        let free = free_path(Path::new(name.as_str()), |candidate| {
+                            let candidate = candidate.to_string_lossy();
+                            taken.contains(candidate.as_ref())
+                                || numbered.contains(candidate.as_ref())
+                        });
+                        let free = free.to_string_lossy().into_owned();
+                        numbered.insert(free.clone());
+                        renamed.insert(i, free);
+                    }
+                }
+            },
+        }
+        duplicates.patch = Some(patch_directory(&mut reader, layout, &left_out, &renamed)?);
+        Ok(duplicates)
+    }
+
+    /// Warning summing up the duplicates found, if any, unless `--member-index`
+    /// picked one entry anyway
+    pub(crate) fn summary(&self, options: &ExtractOptions) -> Option<String> {
+        if self.names.is_empty() || options.member_index.is_some() {
+            return None;
+        }
+        let mut listed = self.names.iter().take(SUMMARY_NAMES).cloned().collect::<Vec<_>>();
+        if self.names.len() > SUMMARY_NAMES {
+            listed.push(format!("and {} more", self.names.len() - SUMMARY_NAMES));
+        }
+        let outcome = match self.policy {
+            DuplicatePolicy::First => "extracting the first of each",
+            DuplicatePolicy::Last | DuplicatePolicy::Error => "extracting the last of each",
+            DuplicatePolicy::Rename => "extracting the later ones under numbered names",
+        };
+        Some(format!(
+            "   warning: names used by more than one entry ({}), {}: {}",
+            self.names.len(),
+            outcome,
+            listed.join(", ")
+        ))
+    }
+
+    /// The directory to read the archive through, if the policy needs one
+    pub(crate) fn into_patch(self) -> Option<PatchedDirectory> {
+        self.patch
+    }
+}
+
+/// Rewrite the central directory at `layout` without the records `left_out`
+/// and with the `renamed` ones under their new names, and the end records to
+/// match
+fn patch_directory<R: Read + Seek>(
+    reader: &mut R,
+    layout: Layout,
+    left_out: &HashSet<usize>,
+    renamed: &HashMap<usize, String>,
+) -> Result<PatchedDirectory> {
+    let invalid = |message| ZipError::InvalidArchive(message);
+    let mut old = vec![0u8; usize::try_from(layout.end - layout.start).unwrap_or(usize::MAX)];
+    reader.seek(SeekFrom::Start(layout.start))?;
+    reader.read_exact(&mut old)?;
+    let mut end_records = Vec::new();
+    reader.read_to_end(&mut end_records)?;
+
+    let mut tail = Vec::with_capacity(old.len() + end_records.len());
+    let mut kept = 0u64;
+    let mut rest = &old[..];
+    let mut i = 0;
+    while !rest.is_empty() {
+        let header = rest.get(..CENTRAL_HEADER_LEN).ok_or(invalid("Invalid Central Directory"))?;
+        let field16 = |at| usize::from(u16_at(header, at).unwrap_or_default());
+        let (name_len, extra_len) = (field16(28), field16(30));
+        let len = CENTRAL_HEADER_LEN + name_len + extra_len + field16(32);
+        let record = rest.get(..len).ok_or(invalid("Invalid Central Directory"))?;
+        rest = &rest[len..];
+        i += 1;
+        if left_out.contains(&(i - 1)) {
+            continue;
+        }
+        kept += 1;
+        let Some(name) = renamed.get(&(i - 1)) else {
+            tail.extend_from_slice(record);
+            continue;
+        };
+        // A Unicode Path field would still give the entry its old name
+        let extra_start = CENTRAL_HEADER_LEN + name_len;
+        let extra = without_field(&record[extra_start..extra_start + extra_len], 0x7075);
+        let mut header = header.to_vec();
+        let flags = u16_at(&header, 8).unwrap_or_default() | UTF8_FLAG;
+        header[8..10].copy_from_slice(&flags.to_le_bytes());
+        let name_len = u16::try_from(name.len()).map_err(|_| invalid("Renamed entry too long"))?;
+        header[28..30].copy_from_slice(&name_len.to_le_bytes());
+        header[30..32].copy_from_slice(&(extra.len() as u16).to_le_bytes());
+        tail.extend_from_slice(&header);
+        tail.extend_from_slice(name.as_bytes());
+        tail.extend_from_slice(&extra);
+        tail.extend_from_slice(&record[extra_start + extra_len..]);
+    }
+    let size = tail.len() as u64;
+
+    // The end records keep everything but the count and size of the directory
+    // and, for Zip64, the locator's offset of the Zip64 record, which moved
+    let eocd = usize::try_from(layout.eocd - layout.end).unwrap_or(usize::MAX);
+    if end_records.len() < eocd.saturating_add(EOCD_LEN) {
+        return Err(invalid("Invalid end of central directory").into());
+    }
+    if eocd > 0 {
+        if eocd < ZIP64_EOCD_LEN + ZIP64_LOCATOR_LEN {
+            return Err(invalid("Invalid ZIP64 end of central directory").into());
+        }
+        let record = &mut end_records[..ZIP64_EOCD_LEN];
+        record[24..32].copy_from_slice(&kept.to_le_bytes());
+        record[32..40].copy_from_slice(&kept.to_le_bytes());
+        record[40..48].copy_from_slice(&size.to_le_bytes());
+        let locator = &mut end_records[eocd - ZIP64_LOCATOR_LEN..eocd];
+        let declared = u64_at(locator, 8).unwrap_or_default();
+        let moved = declared.wrapping_sub(layout.end).wrapping_add(layout.start + size);
+        locator[8..16].copy_from_slice(&moved.to_le_bytes());
+    }
+    let record = &mut end_records[eocd..eocd + EOCD_LEN];
+    if u16_at(record, 10) != Some(u16::MAX) {
+        let kept = kept as u16;
+        record[8..10].copy_from_slice(&kept.to_le_bytes());
+        record[10..12].copy_from_slice(&kept.to_le_bytes());
+    }
+    if u32_at(record, 12) != Some(u32::MAX) {
+        record[12..16].copy_from_slice(&(size as u32).to_le_bytes());
+    }
+    tail.extend_from_slice(&end_records);
+    Ok(PatchedDirectory { start: layout.start, tail })
+}
+
+/// `extra` without the fields of type `id`
+fn without_field(mut extra: &[u8], id: u16) -> Vec<u8> {
+    let mut kept = Vec::with_capacity(extra.len());
+    while let (Some(field), Some(len)) = (u16_at(extra, 0), u16_at(extra, 2)) {
+        let len = (4 + usize::from(len)).min(extra.len());
+        if field != id {
+            kept.extend_from_slice(&extra[..len]);
+        }
+        extra = &extra[len..];
+    }
+    kept.extend_from_slice(extra);
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::ZipArchive;
+    use zip::ZipWriter;
+    use zip::write::SimpleFileOptions;
+
+    /// Archive of `(name, data)` entries; `#` in a name is written as `_` and
+    /// patched to `.` afterwards, since the writer refuses repeated names
+    fn archive_with(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in entries {
+            zip.start_file(name.replace('#', "_"), SimpleFileOptions::default()).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        let mut bytes = zip.finish().unwrap().into_inner();
+        for (name, _) in entries.iter().filter(|(name, _)| name.contains('#')) {
+            let (written, wanted) = (name.replace('#', "_"), name.replace('#', "."));
+            while let Some(at) = bytes.windows(written.len()).position(|w| w == written.as_bytes())
+            {
+                bytes[at..at + written.len()].copy_from_slice(wanted.as_bytes());
+            }
+        }
+        bytes
+    }
+
+    /// Names and contents of the entries extraction sees under `options`
+    fn extracted(bytes: &[u8], options: &ExtractOptions) -> Vec<(String, String)> {
+        let duplicates = Duplicates::scan(Cursor::new(bytes), options).unwrap();
+        match duplicates.into_patch() {
+            Some(patch) => entries(PatchedReader::new(Cursor::new(bytes), Arc::new(patch))),
+            None => entries(Cursor::new(bytes)),
+        }
+    }
+
+    fn entries<R: Read + Seek>(reader: R) -> Vec<(String, String)> {
+        let mut archive = ZipArchive::new(reader).unwrap();
+        (0..archive.len())
+            .map(|i| {
+                let mut file = archive.by_index(i).unwrap();
+                let mut data = String::new();
+                file.read_to_string(&mut data).unwrap();
+                (file.name().to_string(), data)
+            })
+            .collect()
+    }
+
+    fn pairs(list: &[(&str, &str)]) -> Vec<(String, String)> {
+        list.iter().map(|(name, data)| (name.to_string(), data.to_string())).collect()
+    }
+
+    #[test]
+    fn test_policies_pick_entries() {
+        let bytes =
+            archive_with(&[("a.txt", b"1"), ("b.txt", b"b"), ("a#txt", b"2"), ("a (1).txt", b"x")]);
+        let options = |policy| ExtractOptions::new().duplicates(policy);
+
+        let last = extracted(&bytes, &options(DuplicatePolicy::Last));
+        assert_eq!(last, pairs(&[("a.txt", "2"), ("b.txt", "b"), ("a (1).txt", "x")]));
+        let first = extracted(&bytes, &options(DuplicatePolicy::First));
+        assert_eq!(first, pairs(&[("a.txt", "1"), ("b.txt", "b"), ("a (1).txt", "x")]));
+        let renamed = extracted(&bytes, &options(DuplicatePolicy::Rename));
+        assert_eq!(
+            renamed,
+            pairs(&[("a.txt", "1"), ("b.txt", "b"), ("a (2).txt", "2"), ("a (1).txt", "x")])
+        );
+
+        let error = Duplicates::scan(Cursor::new(&bytes), &options(DuplicatePolicy::Error));
+        assert_eq!(
+            error.unwrap_err().to_string(),
+            "names used by more than one entry (--duplicates=error, 1):\n  a.txt"
+        );
+        let summary = Duplicates::scan(Cursor::new(&bytes), &options(DuplicatePolicy::First))
+            .unwrap()
+            .summary(&ExtractOptions::new());
+        assert_eq!(
+            summary.as_deref(),
+            Some(
+                "   warning: names used by more than one entry (1), extracting the first of each: a.txt"
+            )
+        );
+    }
+
+    #[test]
+    fn test_member_index_counts_every_record() {
+        let bytes = archive_with(&[("a.txt", b"1"), ("a#txt", b"2"), ("b.txt", b"b")]);
+        let options = ExtractOptions::new().member_index(Some(1));
+        assert_eq!(extracted(&bytes, &options), pairs(&[("a.txt", "2")]));
+        let options = ExtractOptions::new().member_index(Some(0));
+        assert_eq!(extracted(&bytes, &options), pairs(&[("a.txt", "1")]));
+    }
+
+    #[test]
+    fn test_no_patch_without_duplicates() {
+        let bytes = archive_with(&[("a.txt", b"a"), ("dir/", b""), ("dir/a.txt", b"b")]);
+        let options = ExtractOptions::new().duplicates(DuplicatePolicy::Error);
+        let duplicates = Duplicates::scan(Cursor::new(&bytes), &options).unwrap();
+        assert!(duplicates.summary(&options).is_none());
+        assert!(duplicates.into_patch().is_none());
+    }
+
+    #[test]
+    fn test_without_field() {
+        let extra = [0x75, 0x70, 1, 0, 9, 0x01, 0x00, 2, 0, 7, 7];
+        assert_eq!(without_field(&extra, 0x7075), [0x01, 0x00, 2, 0, 7, 7]);
+        assert_eq!(without_field(&extra[..3], 0x7075), extra[..3]);
+    }
+}
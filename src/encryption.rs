@@ -0,0 +1,262 @@
+//! Encryption scheme detection
+//!
+//! ZIP entries are encrypted either with traditional PKWARE encryption ("ZipCrypto")
+//! or with WinZip AES. AES entries carry an extra field (`0x9901`) recording the key
+//! strength and the vendor version:
+//!
+//! - AE-1 keeps the CRC-32 of the plaintext and relies on it for integrity
+//! - AE-2 zeroes the CRC-32 and relies on the HMAC authentication code alone
+//!
+//! The zip crate picks the matching decryption path from the same field, so this
+//! module only reports the scheme: [`entry_scheme`] for an entry of a
+//! [`zip::ZipArchive`], from the central extra field the zip crate keeps, and
+//! [`CentralRecord::encryption`](crate::central::CentralRecord::encryption) for the
+//! streaming central directory reader. Listings with `-v` and `--format csv`,
+//! `-Z -v` and `--lint` report it.
+//!
+//! # Examples
+//!
+//! ```
+//! use unzip::encryption::{AesStrength, AesVendor, EncryptionScheme, parse_scheme};
+//!
+//! let extra = [0x01, 0x99, 7, 0, 0x02, 0x00, b'A', b'E', 0x03, 0x08, 0x00];
+//! let scheme = parse_scheme(0x0001, 99, &extra);
+//! assert_eq!(
+//!     scheme,
+//!     EncryptionScheme::Aes { strength: AesStrength::Aes256, vendor: AesVendor::Ae2 }
+//! );
+//! assert_eq!(scheme.to_string(), "AES-256 (AE-2)");
+//! ```
+
+use std::fmt;
+use zip::read::ZipFile;
+
+/// General purpose flag: entry is encrypted
+const FLAG_ENCRYPTED: u16 = 0x0001;
+
+/// General purpose flag: PKWARE strong encryption
+const FLAG_STRONG_ENCRYPTION: u16 = 0x0040;
+
+/// Compression method recorded for WinZip AES entries
+const METHOD_AES: u16 = 99;
+
+/// Header ID of the WinZip AES extra field
+const AES_EXTRA_FIELD: u16 = 0x9901;
+
+/// Header ID of the PKWARE strong encryption header
+const STRONG_ENCRYPTION_FIELD: u16 = 0x0017;
+
+/// AES key strength
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AesStrength {
+    /// 128-bit key
+    Aes128,
+    /// 192-bit key
+    Aes192,
+    /// 256-bit key
+    Aes256,
+}
+
+impl AesStrength {
+    /// Key length in bits
+    pub fn bits(self) -> u16 {
+        match self {
+            Self::Aes128 => 128,
+            Self::Aes192 => 192,
+            Self::Aes256 => 256,
+        }
+    }
+}
+
+/// WinZip AES vendor version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AesVendor {
+    /// CRC-32 of the plaintext is stored
+    Ae1,
+    /// CRC-32 is zero; only the authentication code protects the data
+    Ae2,
+}
+
+/// How an entry is encrypted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncryptionScheme {
+    /// Not encrypted
+    None,
+    /// Traditional PKWARE encryption
+    ZipCrypto,
+    /// WinZip AES
+    Aes { strength: AesStrength, vendor: AesVendor },
+    /// PKWARE strong encryption, which cannot be decrypted
+    Strong,
+    /// Encrypted, but the AES extra field is missing or malformed
+    Unknown,
+}
+
+impl EncryptionScheme {
+    /// Whether the entry is encrypted at all
+    pub fn is_encrypted(self) -> bool {
+        self != Self::None
+    }
+}
+
+impl fmt::Display for EncryptionScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::ZipCrypto => write!(f, "ZipCrypto"),
+            Self::Aes { strength, vendor } => {
+                let vendor = match vendor {
+                    AesVendor::Ae1 => "AE-1",
+                    AesVendor::Ae2 => "AE-2",
+                };
+                write!(f, "AES-{} ({})", strength.bits(), vendor)
+            },
+            Self::Strong => write!(f, "PKWARE strong encryption (unsupported)"),
+            Self::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// Determine the scheme from central directory header fields
+///
+/// # Arguments
+///
+/// * `flags` - General purpose bit flags
+/// * `method` - Compression method as stored (99 for AES)
+/// * `extra` - Central directory extra field
+pub fn parse_scheme(flags: u16, method: u16, extra: &[u8]) -> EncryptionScheme {
+    if flags & FLAG_ENCRYPTED == 0 {
+        return EncryptionScheme::None;
+    }
+    if flags & FLAG_STRONG_ENCRYPTION != 0 {
+        return EncryptionScheme::Strong;
+    }
+    if method != METHOD_AES {
+        return EncryptionScheme::ZipCrypto;
+    }
+    find_field(extra, AES_EXTRA_FIELD).map_or(EncryptionScheme::Unknown, aes_scheme)
+}
+
+/// The encryption scheme of an entry of a [`zip::ZipArchive`]
+///
+/// The zip crate replaces the AES method code with the real one and keeps no
+/// flag for strong encryption, so both are told by their extra fields, which it
+/// leaves in [`ZipFile::extra_data`].
+pub fn entry_scheme(file: &ZipFile<'_>) -> EncryptionScheme {
+    if !file.encrypted() {
+        return EncryptionScheme::None;
+    }
+    let extra = file.extra_data().unwrap_or_default();
+    if let Some(data) = find_field(extra, AES_EXTRA_FIELD) {
+        return aes_scheme(data);
+    }
+    if find_field(extra, STRONG_ENCRYPTION_FIELD).is_some() {
+        return EncryptionScheme::Strong;
+    }
+    #[allow(deprecated)]
+    match file.compression() {
+        zip::CompressionMethod::Unsupported(METHOD_AES) => EncryptionScheme::Unknown,
+        _ => EncryptionScheme::ZipCrypto,
+    }
+}
+
+/// The data of the extra field `id` in `extra`, if there is one
+fn find_field(mut extra: &[u8], id: u16) -> Option<&[u8]> {
+    while extra.len() >= 4 {
+        let len = usize::from(u16::from_le_bytes([extra[2], extra[3]]));
+        let data = extra.get(4..4 + len)?;
+        if u16::from_le_bytes([extra[0], extra[1]]) == id {
+            return Some(data);
+        }
+        extra = &extra[4 + len..];
+    }
+    None
+}
+
+/// Decode the data of a WinZip AES extra field, [`EncryptionScheme::Unknown`]
+/// if it is malformed
+fn aes_scheme(data: &[u8]) -> EncryptionScheme {
+    if data.len() < 7 || &data[2..4] != b"AE" {
+        return EncryptionScheme::Unknown;
+    }
+    let vendor = match u16::from_le_bytes([data[0], data[1]]) {
+        1 => AesVendor::Ae1,
+        2 => AesVendor::Ae2,
+        _ => return EncryptionScheme::Unknown,
+    };
+    let strength = match data[4] {
+        1 => AesStrength::Aes128,
+        2 => AesStrength::Aes192,
+        3 => AesStrength::Aes256,
+        _ => return EncryptionScheme::Unknown,
+    };
+    EncryptionScheme::Aes { strength, vendor }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::central::CentralDirectory;
+    use std::io::{Cursor, Write};
+    use zip::unstable::write::FileOptionsExt;
+    use zip::write::SimpleFileOptions;
+    use zip::{AesMode, ZipArchive, ZipWriter};
+
+    fn encrypted_archive(options: SimpleFileOptions) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("secret.txt", options).unwrap();
+        zip.write_all(b"secret").unwrap();
+        zip.start_file("plain.txt", SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"plain").unwrap();
+        zip.finish().unwrap().into_inner()
+    }
+
+    /// The schemes of the entries, which the archive and the central directory
+    /// reader must agree on
+    fn schemes(bytes: Vec<u8>) -> Vec<EncryptionScheme> {
+        let mut archive = ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+        let schemes: Vec<_> = (0..archive.len())
+            .map(|i| entry_scheme(&archive.by_index_raw(i).unwrap()))
+            .collect();
+        let directory = CentralDirectory::open(Cursor::new(bytes)).unwrap();
+        let records: Vec<_> = directory.map(|record| record.unwrap().encryption).collect();
+        assert_eq!(schemes, records);
+        schemes
+    }
+
+    #[test]
+    fn test_detects_aes() {
+        let options = SimpleFileOptions::default().with_aes_encryption(AesMode::Aes128, "pw");
+        assert_eq!(
+            schemes(encrypted_archive(options)),
+            vec![
+                EncryptionScheme::Aes { strength: AesStrength::Aes128, vendor: AesVendor::Ae2 },
+                EncryptionScheme::None
+            ]
+        );
+    }
+
+    #[test]
+    fn test_detects_zipcrypto() {
+        let options = SimpleFileOptions::default()
+            .with_deprecated_encryption(b"pw")
+            .compression_method(zip::CompressionMethod::Stored);
+        assert_eq!(
+            schemes(encrypted_archive(options)),
+            vec![EncryptionScheme::ZipCrypto, EncryptionScheme::None]
+        );
+    }
+
+    #[test]
+    fn test_parse_scheme_edge_cases() {
+        let ae1 = [0x01, 0x99, 7, 0, 0x01, 0x00, b'A', b'E', 0x02, 0x08, 0x00];
+        assert_eq!(
+            parse_scheme(0x0001, 99, &ae1),
+            EncryptionScheme::Aes { strength: AesStrength::Aes192, vendor: AesVendor::Ae1 }
+        );
+        assert_eq!(parse_scheme(0x0041, 8, &[]), EncryptionScheme::Strong);
+        assert_eq!(parse_scheme(0x0001, 99, &[]), EncryptionScheme::Unknown);
+        assert_eq!(parse_scheme(0x0001, 99, &ae1[..6]), EncryptionScheme::Unknown);
+        assert_eq!(parse_scheme(0x0000, 99, &ae1), EncryptionScheme::None);
+    }
+}
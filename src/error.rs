@@ -0,0 +1,320 @@
+//! Error type of the library API
+//!
+//! Every fallible public function returns [`UnzipError`], so callers can tell a
+//! corrupt entry from a missing password, an unsupported compression method or a
+//! crossed extraction limit without parsing messages. Files that already exist are
+//! not errors; they are reported as [`SkipReason::Exists`](crate::SkipReason::Exists)
+//! events. The binary wraps these errors in `anyhow` for display.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use zip::ZipArchive;
+//! use unzip::{ExtractOptions, UnzipError, extract_archive};
+//!
+//! let mut archive = ZipArchive::new(File::open("archive.zip")?)?;
+//! match extract_archive(&mut archive, &ExtractOptions::new().output_dir("out")) {
+//!     Ok(()) => {},
+//!     Err(UnzipError::Limit(limit)) => eprintln!("refusing suspicious archive: {}", limit),
+//!     Err(UnzipError::Unsupported(what)) => eprintln!("cannot extract: {}", what),
+//!     Err(e) => return Err(e.into()),
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::io;
+use std::path::{Path, PathBuf};
+use zip::result::ZipError;
+
+use crate::comment_json::CommentJsonError;
+use crate::duplicates::DuplicateNames;
+use crate::expect::ExpectationFailed;
+use crate::invalid_names::InvalidEntryNames;
+use crate::limits::LimitExceeded;
+use crate::members::MissingMembers;
+use crate::opendoc::InvalidContainer;
+use crate::output_dir::OutputDirError;
+use crate::self_overwrite::SelfOverwrite;
+use crate::size_check::SizeCheckError;
+use crate::unmatched::Unmatched;
+use crate::wait_complete::IncompleteArchive;
+
+/// Result type of the library API
+pub type Result<T, E = UnzipError> = std::result::Result<T, E>;
+
+/// Everything that can go wrong while reading, testing or extracting an archive
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum UnzipError {
+    /// A file or directory on disk could not be opened, created or written
+    #[error("Failed to {action}: {}", path.display())]
+    File {
+        /// What was being done, e.g. "create file"
+        action: &'static str,
+        /// The file or directory involved
+        path: PathBuf,
+        /// The underlying I/O error
+        #[source]
+        source: io::Error,
+    },
+
+    /// The data of an entry could not be read or decompressed
+    #[error("Failed to read {name}: {source}")]
+    Read {
+        /// Entry name
+        name: String,
+        /// The underlying I/O error
+        #[source]
+        source: io::Error,
+    },
+
+    /// Writing output (e.g. to stdout for `-p`) failed
+    #[error(transparent)]
+    Io(io::Error),
+
+    /// The archive is malformed
+    #[error(transparent)]
+    Zip(ZipError),
+
+    /// The archive uses a feature this build cannot handle, such as an unknown
+    /// compression method
+    #[error("unsupported archive: {0}")]
+    Unsupported(&'static str),
+
+    /// An entry's data does not match its stored CRC32
+    #[error("{name} - CRC mismatch (stored: {stored:08x}, computed: {computed:08x})")]
+    CrcMismatch {
+        /// Entry name
+        name: String,
+        /// CRC32 recorded in the archive
+        stored: u32,
+        /// CRC32 of the data actually read
+        computed: u32,
+    },
+
+    /// A file read back from disk does not match the data written (`--verify`)
+    #[error("{} - verification failed (written: {written:08x}, read back: {read:08x})", path.display())]
+    VerifyFailed {
+        /// The extracted file
+        path: PathBuf,
+        /// CRC32 of the data written
+        written: u32,
+        /// CRC32 of the data read back
+        read: u32,
+    },
+
+    /// An entry's data reaches into another entry or the central directory, as in
+    /// overlapping-entry zip bombs
+    #[error("invalid zip file: {name} overlaps {other} (possible zip bomb)")]
+    OverlappingEntry {
+        /// Entry name
+        name: String,
+        /// The entry it overlaps, or "the central directory"
+        other: String,
+    },
+
+    /// An encrypted entry was found and no password is available
+    #[error("Password required but not available{}", for_file(.name))]
+    PasswordRequired {
+        /// Entry name, when known
+        name: Option<String>,
+    },
+
+    /// The supplied password does not decrypt the entry
+    #[error("invalid password")]
+    InvalidPassword,
+
+    /// Reading the password from the terminal failed
+    #[error("Failed to read password")]
+    PasswordPrompt(#[source] io::Error),
+
+    /// `-O` or `-I` named a character set that is not known
+    #[error("Unknown character set for {flag}: {label}")]
+    UnknownCharset {
+        /// The option that named it, `-O` or `-I`
+        flag: &'static str,
+        /// The unknown name
+        label: String,
+    },
+
+    /// The archive crossed an extraction limit
+    #[error(transparent)]
+    Limit(#[from] LimitExceeded),
+
+    /// The selected entries are not what `--expect-files` or `--expect-bytes` said
+    #[error(transparent)]
+    Expectation(#[from] ExpectationFailed),
+
+    /// Names listed with `--members-from` are not in the archive
+    #[error(transparent)]
+    MissingMembers(#[from] MissingMembers),
+
+    /// Member patterns matched no entry, or no entry was selected
+    #[error(transparent)]
+    Unmatched(#[from] Unmatched),
+
+    /// Entry names appear more than once and `--duplicates=error` was given
+    #[error(transparent)]
+    DuplicateNames(#[from] DuplicateNames),
+
+    /// Entry names do not decode and `--invalid-names=reject` was given
+    #[error(transparent)]
+    InvalidNames(#[from] InvalidEntryNames),
+
+    /// `--member-index` is past the last entry of the archive
+    #[error("no entry {index} in the archive, which has {entries} (--member-index counts from 0)")]
+    NoSuchEntry {
+        /// The requested index
+        index: usize,
+        /// Number of entries in the archive
+        entries: usize,
+    },
+
+    /// The archive breaks the conventions of its container type (`--opendoc-check`)
+    #[error(transparent)]
+    InvalidContainer(#[from] InvalidContainer),
+
+    /// Entries would be extracted over the archive being read
+    /// (`--allow-self-overwrite` to proceed)
+    #[error(transparent)]
+    SelfOverwrite(#[from] SelfOverwrite),
+
+    /// No entry has the name a library caller asked for
+    #[error("no entry named {name} in the archive")]
+    NoSuchName {
+        /// The requested name
+        name: String,
+    },
+
+    /// The archive comment is missing or not JSON (`--comment-json`)
+    #[error(transparent)]
+    CommentJson(#[from] CommentJsonError),
+
+    /// The output directory is missing with `--no-create-dir`, not a
+    /// directory, or not writable
+    #[error(transparent)]
+    OutputDir(#[from] OutputDirError),
+
+    /// The archive was still being written when `--wait-complete` gave up
+    #[error(transparent)]
+    Incomplete(#[from] IncompleteArchive),
+
+    /// Size fields disagree, or an entry decompressed to another size
+    /// (`--size-check`)
+    #[error(transparent)]
+    SizeCheck(#[from] SizeCheckError),
+
+    /// The virus scanner flagged entries, which were quarantined (`--scan-socket`)
+    #[error("Virus scan flagged {entries} entries")]
+    Infected {
+        /// Number of entries flagged
+        entries: usize,
+    },
+
+    /// `test_archive` found corrupt entries
+    #[error("Archive test failed with {errors} errors")]
+    TestFailed {
+        /// Number of entries that failed
+        errors: usize,
+    },
+
+    /// `lint_archive` found entries with bad data
+    #[error("Archive lint failed with {errors} errors")]
+    LintFailed {
+        /// Number of entries with bad data
+        errors: usize,
+    },
+
+    /// `diff_archive` found differences between the archive and a directory tree
+    #[error("Archive and directory differ in {differences} paths")]
+    DiffFound {
+        /// Number of paths that differ
+        differences: usize,
+    },
+
+    /// The operation was stopped through its [`CancelToken`](crate::CancelToken)
+    #[error("operation cancelled")]
+    Cancelled,
+
+    /// A background extraction thread panicked
+    #[error("extraction thread panicked")]
+    ThreadPanicked,
+}
+
+impl UnzipError {
+    /// Attach the action and path to an I/O error on the destination or archive file
+    pub(crate) fn file(action: &'static str, path: &Path) -> impl FnOnce(io::Error) -> Self {
+        move |source| Self::File { action, path: path.to_path_buf(), source }
+    }
+
+    /// Attach the entry name to an error reading its data, keeping crossed limits
+    /// as [`UnzipError::Limit`]
+    pub(crate) fn reading(name: &str) -> impl FnOnce(io::Error) -> Self {
+        move |source| match Self::from(source) {
+            Self::Io(source) => Self::Read { name: name.to_string(), source },
+            error => error,
+        }
+    }
+}
+
+fn for_file(name: &Option<String>) -> String {
+    name.as_ref().map(|name| format!(" for file: {}", name)).unwrap_or_default()
+}
+
+impl From<io::Error> for UnzipError {
+    /// Limits enforced while reading surface as I/O errors; unwrap them again
+    fn from(error: io::Error) -> Self {
+        if error.get_ref().is_some_and(|inner| inner.is::<LimitExceeded>()) {
+            let inner = error.into_inner().expect("checked above");
+            return Self::Limit(*inner.downcast::<LimitExceeded>().expect("checked above"));
+        }
+        Self::Io(error)
+    }
+}
+
+impl From<ZipError> for UnzipError {
+    fn from(error: ZipError) -> Self {
+        match error {
+            ZipError::Io(error) => error.into(),
+            ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED) => {
+                Self::PasswordRequired { name: None }
+            },
+            ZipError::UnsupportedArchive(what) => Self::Unsupported(what),
+            ZipError::InvalidPassword => Self::InvalidPassword,
+            error => Self::Zip(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_io_unwraps_limits() {
+        let limit = LimitExceeded::OutputSize { limit: 10 };
+        let error = UnzipError::from(io::Error::other(limit.clone()));
+        assert!(matches!(error, UnzipError::Limit(ref inner) if *inner == limit));
+        assert!(error.to_string().contains("--max-output-size"));
+
+        let error = UnzipError::from(io::Error::from(io::ErrorKind::UnexpectedEof));
+        assert!(matches!(error, UnzipError::Io(_)));
+    }
+
+    #[test]
+    fn test_from_zip_classifies() {
+        assert!(matches!(
+            UnzipError::from(ZipError::UnsupportedArchive("Compression method not supported")),
+            UnzipError::Unsupported(_)
+        ));
+        assert!(matches!(
+            UnzipError::from(ZipError::InvalidPassword),
+            UnzipError::InvalidPassword
+        ));
+        let error = UnzipError::from(ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED));
+        assert_eq!(error.to_string(), "Password required but not available");
+        let error = UnzipError::PasswordRequired { name: Some("a.txt".to_string()) };
+        assert_eq!(error.to_string(), "Password required but not available for file: a.txt");
+    }
+}
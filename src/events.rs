@@ -0,0 +1,666 @@
+//! Extraction event stream
+//!
+//! Extraction reports its progress as a sequence of [`ExtractEvent`] values. The CLI
+//! consumes them through a console reporter that drives the progress bar and prints
+//! Info-ZIP style messages, while GUI and file-manager integrations can consume the
+//! very same events through [`events`] to drive their own UI.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::path::{Path, PathBuf};
+//! use unzip::{ArchiveSource, ExtractEvent, ExtractOptions, events};
+//!
+//! let options = ExtractOptions::new().output_dir("out").quiet(2);
+//! let mut stream = events(ArchiveSource::FilePath(PathBuf::from("archive.zip")), &options);
+//! for event in &mut stream {
+//!     if let ExtractEvent::FileDone { name, bytes, .. } = event {
+//!         println!("{} ({} bytes)", name, bytes);
+//!     }
+//! }
+//! let report = stream.join()?;
+//! println!("{} files extracted", report.extracted);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use indicatif::{ProgressBar, ProgressStyle};
+use std::fmt;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread::{self, JoinHandle};
+
+use crate::compat::Compat;
+use crate::error::{Result, UnzipError};
+use crate::extract::{ArchiveSource, extract_archive_threaded_with};
+use crate::options::{ExtractOptions, OverwritePolicy};
+use crate::utils::format_size;
+
+/// Why an entry was not extracted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// Entry did not match the include/exclude patterns
+    Filtered,
+    /// Destination already exists and overwriting was not allowed
+    Exists,
+    /// Destination is missing (freshen) or newer than the entry (freshen/update)
+    UpToDate,
+    /// Destination already holds the entry's data (`--if-changed`)
+    Unchanged,
+    /// Entry name would escape the output directory
+    UnsafePath,
+    /// Entry name is empty after normalization (`""`, `"."`, `"/"`)
+    EmptyName,
+    /// Directory entry dropped because paths are junked (`-j`)
+    JunkedDirectory,
+    /// Nothing is left of the entry's path after `--strip-components` or
+    /// `--transform`
+    Stripped,
+    /// Directory path exceeds filesystem limits (`--flatten-long-paths`)
+    PathTooLong,
+    /// Entry is encrypted and no password was available
+    PasswordRequired,
+    /// Entry is encrypted and the password was rejected
+    InvalidPassword,
+    /// File entry left out because only directories are extracted (`--dirs-only`)
+    DirsOnly,
+    /// Entry extracted by an earlier run, as its checkpoint records (`--resume`)
+    Resumed,
+    /// Destination is a read-only file and `--force-readonly` was not given
+    ReadOnly,
+    /// The virus scanner flagged the data written, which was quarantined
+    /// (`--scan-socket`)
+    Infected,
+    /// The caller's [`EntryHooks`](crate::hooks::EntryHooks) chose to skip the
+    /// entry
+    Vetoed,
+}
+
+impl SkipReason {
+    /// Short label used in `--dry-run` output
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Filtered => "skip-pattern",
+            Self::Exists => "skip-exists",
+            Self::UpToDate => "skip-up-to-date",
+            Self::Unchanged => "skip-unchanged",
+            Self::UnsafePath => "skip-unsafe",
+            Self::EmptyName => "skip-empty-name",
+            Self::JunkedDirectory => "skip-junked",
+            Self::Stripped => "skip-stripped",
+            Self::PathTooLong => "skip-too-long",
+            Self::PasswordRequired | Self::InvalidPassword => "skip-password",
+            Self::DirsOnly => "skip-dirs-only",
+            Self::Resumed => "skip-resumed",
+            Self::ReadOnly => "skip-read-only",
+            Self::Infected => "skip-infected",
+            Self::Vetoed => "skip-hook",
+        }
+    }
+
+    /// Whether the entry was left out by choice, by patterns, hooks or
+    /// `--strip-components`, rather than rejected for its name
+    pub(crate) fn is_selection(self) -> bool {
+        matches!(self, Self::Filtered | Self::Stripped | Self::Vetoed)
+    }
+}
+
+/// What a dry run (`--dry-run`) would have done with an entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlannedAction {
+    /// Create the directory of a directory entry
+    CreateDirectory,
+    /// Write a file that does not exist yet
+    Extract,
+    /// Replace an existing file
+    Overwrite,
+}
+
+impl PlannedAction {
+    /// Short label used in `--dry-run` output
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::CreateDirectory => "create-dir",
+            Self::Extract => "extract",
+            Self::Overwrite => "would-overwrite",
+        }
+    }
+}
+
+/// Why an entry failed its integrity test
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TestFailure {
+    /// The data read does not match the CRC32 the archive records
+    CrcMismatch {
+        /// CRC32 recorded in the archive
+        stored: u32,
+        /// CRC32 of the data read
+        computed: u32,
+    },
+    /// The entry's compression method cannot be decoded
+    UnsupportedMethod(String),
+    /// The entry is encrypted and no password was given, or it was rejected
+    DecryptFailed(String),
+    /// The data could not be read or decompressed
+    Corrupt(String),
+    /// The data decompressed to another size than the one `--size-check`
+    /// settled on
+    SizeMismatch {
+        /// The size settled on
+        expected: u64,
+        /// The size of the data read
+        actual: u64,
+    },
+}
+
+impl TestFailure {
+    /// Short status used in `--report=json` output
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::CrcMismatch { .. } => "crc-mismatch",
+            Self::UnsupportedMethod(_) => "unsupported-method",
+            Self::DecryptFailed(_) => "decrypt-failed",
+            Self::Corrupt(_) => "corrupt",
+            Self::SizeMismatch { .. } => "size-mismatch",
+        }
+    }
+}
+
+impl fmt::Display for TestFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CrcMismatch { stored, computed } => {
+                write!(f, "CRC mismatch (stored: {:08x}, computed: {:08x})", stored, computed)
+            },
+            Self::UnsupportedMethod(message)
+            | Self::DecryptFailed(message)
+            | Self::Corrupt(message) => write!(f, "{}", message),
+            Self::SizeMismatch { expected, actual } => {
+                write!(f, "size mismatch (expected: {}, decompressed: {})", expected, actual)
+            },
+        }
+    }
+}
+
+/// Summary of a finished extraction run
+///
+/// In a dry run, the counts are of what would have been done.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExtractReport {
+    /// Number of files written to disk
+    pub extracted: usize,
+    /// Number of file entries that were not written
+    pub skipped: usize,
+    /// Number of entries refused because of an unsafe or empty name
+    pub rejected: usize,
+    /// Total uncompressed bytes written
+    pub bytes: u64,
+    /// Directory the archive was extracted into
+    pub output_dir: PathBuf,
+}
+
+/// A single step of an extraction run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExtractEvent {
+    /// Extraction is about to process `total_entries` entries
+    ///
+    /// Entries that `-f`/`-u` can skip up front are reported as [`SkipReason::UpToDate`]
+    /// before this event and are not counted.
+    Started { total_entries: usize, output_dir: PathBuf },
+    /// A directory entry was created on disk
+    DirCreated { path: PathBuf },
+    /// A file entry of `size` uncompressed bytes is about to be written
+    EntryStarted { name: String, size: u64 },
+    /// Another `bytes` bytes of a file entry were written
+    BytesWritten { name: String, bytes: u64 },
+    /// A file entry was written to `path`
+    FileDone { name: String, path: PathBuf, bytes: u64 },
+    /// An entry was not extracted
+    Skipped { name: String, reason: SkipReason },
+    /// A dry run decided what it would do with an entry, without doing it
+    Planned { name: String, action: PlannedAction },
+    /// A non-fatal condition worth telling the user about
+    Warning { message: String },
+    /// Extraction completed
+    Finished { report: ExtractReport },
+}
+
+/// Receiver of extraction progress, for GUI wrappers and other library consumers
+///
+/// Every method has an empty default, so implementations only override what they
+/// need. [`ExtractObserver::on_event`] dispatches each [`ExtractEvent`] to the
+/// matching method; the CLI progress bar is one implementation. Set an observer
+/// with [`crate::ExtractOptions::observer`], or pass one to
+/// [`crate::test_archive::test_archive_with`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use zip::ZipArchive;
+/// use unzip::{ExtractObserver, ExtractOptions, extract_archive};
+///
+/// #[derive(Default)]
+/// struct Progress {
+///     done: u64,
+///     total: usize,
+/// }
+///
+/// impl ExtractObserver for Progress {
+///     fn started(&mut self, total_entries: usize) {
+///         self.total = total_entries;
+///     }
+///
+///     fn bytes_written(&mut self, _name: &str, bytes: u64) {
+///         self.done += bytes;
+///         println!("{} bytes written", self.done);
+///     }
+/// }
+///
+/// let mut archive = ZipArchive::new(File::open("archive.zip")?)?;
+/// let options = ExtractOptions::new().output_dir("out").observer(Progress::default());
+/// extract_archive(&mut archive, &options)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub trait ExtractObserver {
+    /// Receive one event; the default calls the matching method below
+    fn on_event(&mut self, event: &ExtractEvent) {
+        match event {
+            ExtractEvent::Started { total_entries, .. } => self.started(*total_entries),
+            ExtractEvent::DirCreated { path } => self.dir_created(path),
+            ExtractEvent::EntryStarted { name, size } => self.entry_started(name, *size),
+            ExtractEvent::BytesWritten { name, bytes } => self.bytes_written(name, *bytes),
+            ExtractEvent::FileDone { name, path, bytes } => {
+                self.entry_finished(name, Some(path), *bytes)
+            },
+            ExtractEvent::Skipped { name, reason } => self.entry_skipped(name, *reason),
+            ExtractEvent::Planned { name, action } => self.entry_planned(name, *action),
+            ExtractEvent::Warning { message } => self.warning(message),
+            ExtractEvent::Finished { report } => self.finished(report),
+        }
+    }
+
+    /// Processing of `total_entries` entries begins
+    fn started(&mut self, _total_entries: usize) {}
+
+    /// A directory was created
+    fn dir_created(&mut self, _path: &Path) {}
+
+    /// A file entry of `size` uncompressed bytes is about to be written or tested
+    fn entry_started(&mut self, _name: &str, _size: u64) {}
+
+    /// Another `bytes` bytes of the current entry were written or tested
+    fn bytes_written(&mut self, _name: &str, _bytes: u64) {}
+
+    /// A file entry is complete; `path` is where it was written, `None` when testing
+    fn entry_finished(&mut self, _name: &str, _path: Option<&Path>, _bytes: u64) {}
+
+    /// An entry was not processed
+    fn entry_skipped(&mut self, _name: &str, _reason: SkipReason) {}
+    /// A dry run would do `action` with an entry
+    fn entry_planned(&mut self, _name: &str, _action: PlannedAction) {}
+
+    /// An entry failed its integrity test
+    fn entry_failed(&mut self, _name: &str, _error: &str) {}
+
+    /// An entry failed its integrity test for `failure`; the default calls
+    /// [`ExtractObserver::entry_failed`] with its message
+    fn entry_test_failed(&mut self, name: &str, failure: &TestFailure) {
+        self.entry_failed(name, &failure.to_string());
+    }
+
+    /// A non-fatal condition worth telling the user about
+    fn warning(&mut self, _message: &str) {}
+
+    /// Extraction completed
+    fn finished(&mut self, _report: &ExtractReport) {}
+}
+
+/// Live stream of extraction events produced by [`events`]
+///
+/// Iterating yields events as the background extraction produces them. Once the
+/// iterator is exhausted, call [`ExtractEvents::join`] to obtain the final result.
+pub struct ExtractEvents {
+    receiver: Receiver<ExtractEvent>,
+    handle: JoinHandle<Result<ExtractReport>>,
+}
+
+impl ExtractEvents {
+    /// Wait for the extraction to finish and return its report or error
+    ///
+    /// # Errors
+    ///
+    /// Returns the error that aborted the extraction, if any
+    pub fn join(self) -> Result<ExtractReport> {
+        drop(self.receiver);
+        self.handle.join().map_err(|_| UnzipError::ThreadPanicked)?
+    }
+}
+
+impl Iterator for ExtractEvents {
+    type Item = ExtractEvent;
+
+    fn next(&mut self) -> Option<ExtractEvent> {
+        self.receiver.recv().ok()
+    }
+}
+
+/// Run an extraction in the background and stream its events
+///
+/// Uses the same extraction paths as the CLI (serial or threaded depending on
+/// `options`), but instead of printing, every event is delivered through the
+/// returned iterator. An `on_event` callback in `options` is not called.
+///
+/// # Arguments
+///
+/// * `source` - Archive to extract
+/// * `options` - Extraction options
+pub fn events(source: ArchiveSource, options: &ExtractOptions) -> ExtractEvents {
+    let (sender, receiver) = mpsc::channel();
+    let options = options.clone();
+    let handle = thread::spawn(move || {
+        let mut forward = |event: ExtractEvent| {
+            // A dropped receiver only means the consumer stopped listening
+            let _ = sender.send(event);
+        };
+        extract_archive_threaded_with(source, &options, &mut forward)
+    });
+    ExtractEvents { receiver, handle }
+}
+
+/// Console reporter used by the CLI: progress bar plus Info-ZIP style messages,
+/// or with `--compat=infozip` Info-ZIP's own messages on stdout
+pub(crate) struct ConsoleReporter {
+    quiet: u8,
+    never_overwrite: bool,
+    dry_run: bool,
+    compat: Compat,
+    progress_bar: Option<ProgressBar>,
+    /// The first failure to write to stdout, after which nothing more is written
+    stdout: io::Result<()>,
+}
+
+impl ConsoleReporter {
+    pub(crate) fn new(options: &ExtractOptions) -> Self {
+        Self {
+            quiet: options.quiet,
+            never_overwrite: options.overwrite == OverwritePolicy::Never,
+            dry_run: options.dry_run,
+            compat: options.compat,
+            progress_bar: None,
+            stdout: Ok(()),
+        }
+    }
+
+    /// The first error writing to stdout, such as a reader like `head` closing it
+    pub(crate) fn stdout_result(self) -> io::Result<()> {
+        self.stdout
+    }
+
+    /// Write a line of report output to stdout, unless writing failed before
+    fn print(&mut self, line: fmt::Arguments<'_>) {
+        if self.stdout.is_ok() {
+            self.stdout = writeln!(io::stdout().lock(), "{}", line);
+        }
+    }
+
+    /// The progress bar being drawn, if any; a hidden one, as when stderr is not
+    /// a terminal, drops the lines printed through it
+    fn drawn_progress_bar(&self) -> Option<&ProgressBar> {
+        self.progress_bar.as_ref().filter(|pb| !pb.is_hidden())
+    }
+
+    fn println(&self, msg: String) {
+        match self.drawn_progress_bar() {
+            Some(pb) => pb.println(msg),
+            None => eprintln!("{}", msg.trim_start()),
+        }
+    }
+
+    fn tick(&self) {
+        if let Some(ref pb) = self.progress_bar {
+            pb.inc(1);
+        }
+    }
+
+    /// Print one line of `--dry-run` output: the action, then the entry name
+    fn plan(&mut self, label: &str, name: &str) {
+        if self.quiet < 2 {
+            self.print(format_args!("{:>15}: {}", label, name));
+        }
+    }
+}
+
+impl ExtractObserver for ConsoleReporter {
+    fn started(&mut self, total_entries: usize) {
+        if self.quiet == 0 && !self.dry_run && self.compat == Compat::Native {
+            let pb = ProgressBar::new(total_entries as u64);
+            if let Ok(style) = ProgressStyle::default_bar().template(
+                "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})",
+            ) {
+                pb.set_style(style.progress_chars("#>-"));
+            }
+            self.progress_bar = Some(pb);
+        }
+    }
+
+    fn dir_created(&mut self, path: &Path) {
+        if self.quiet == 0 && self.compat == Compat::InfoZip {
+            self.print(format_args!("   creating: {}/", shown_path(path)));
+        }
+        self.tick();
+    }
+
+    fn entry_finished(&mut self, name: &str, path: Option<&Path>, _bytes: u64) {
+        if self.quiet == 0 {
+            match self.compat {
+                Compat::InfoZip => self
+                    .print(format_args!(" extracting: {}", path.map_or(name.into(), shown_path))),
+                Compat::Native => self.println(format!("  extracting: {}", name)),
+            }
+        }
+        self.tick();
+    }
+
+    fn entry_skipped(&mut self, name: &str, reason: SkipReason) {
+        if self.dry_run {
+            self.plan(reason.label(), name);
+            return;
+        }
+        match reason {
+            // Info-ZIP skips silently, after asking unless `-n` was given
+            SkipReason::Exists if self.compat == Compat::InfoZip => {},
+            SkipReason::Exists if self.quiet == 0 => {
+                if self.never_overwrite {
+                    self.println(format!("    skipping: {} (already exists)", name));
+                } else {
+                    self.println(format!("    skipping: {} (use -o to overwrite)", name));
+                }
+            },
+            SkipReason::ReadOnly if self.quiet < 2 => {
+                self.println(format!(
+                    "    warning: skipped read-only file: {} (use --force-readonly to replace)",
+                    name
+                ));
+            },
+            SkipReason::UnsafePath if self.quiet < 2 => {
+                self.println(format!("    warning: skipped unsafe path: {}", name));
+            },
+            SkipReason::EmptyName if self.quiet < 2 => {
+                self.println(format!("    warning: skipped entry with empty name: {:?}", name));
+            },
+            SkipReason::InvalidPassword if self.quiet < 2 => {
+                self.println("    error: Invalid password".to_string());
+            },
+            SkipReason::PasswordRequired if self.quiet < 2 => {
+                self.println("    error: Password required".to_string());
+            },
+            _ => {},
+        }
+        self.tick();
+    }
+
+    fn entry_planned(&mut self, name: &str, action: PlannedAction) {
+        self.plan(action.label(), name);
+    }
+
+    fn warning(&mut self, message: &str) {
+        if self.quiet < 2 {
+            self.println(message.to_string());
+        }
+    }
+
+    fn finished(&mut self, report: &ExtractReport) {
+        if let Some(pb) = self.progress_bar.take() {
+            pb.finish_and_clear();
+        }
+        if self.quiet == 0 && self.dry_run {
+            self.print(format_args!(
+                "Dry run: would extract {} files ({}) to {}, skip {}, reject {}",
+                report.extracted,
+                format_size(report.bytes),
+                report.output_dir.display(),
+                report.skipped,
+                report.rejected
+            ));
+        } else if self.quiet == 0 && self.compat == Compat::Native {
+            self.print(format_args!(
+                "Extracted {} files ({}) to {}",
+                report.extracted,
+                format_size(report.bytes),
+                report.output_dir.display()
+            ));
+            if report.skipped > 0 {
+                self.print(format_args!("Skipped {} files", report.skipped));
+            }
+            if report.rejected > 0 {
+                self.print(format_args!("Rejected {} entries with unsafe names", report.rejected));
+            }
+        }
+    }
+}
+
+/// Path as Info-ZIP prints it, without a leading `./`
+fn shown_path(path: &Path) -> std::borrow::Cow<'_, str> {
+    path.strip_prefix(".").unwrap_or(path).to_string_lossy()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::{Cursor, Write};
+    use zip::ZipWriter;
+    use zip::write::SimpleFileOptions;
+
+    fn create_test_zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        {
+            let mut zip = ZipWriter::new(Cursor::new(&mut buf));
+            let options =
+                SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+
+            for (name, content) in files {
+                if name.ends_with('/') {
+                    zip.add_directory(*name, options).unwrap();
+                } else {
+                    zip.start_file(*name, options).unwrap();
+                    zip.write_all(content).unwrap();
+                }
+            }
+            zip.finish().unwrap();
+        }
+        buf
+    }
+
+    fn options_for(output_dir: PathBuf) -> ExtractOptions {
+        ExtractOptions::new().output_dir(output_dir).quiet(2).threads(1)
+    }
+
+    #[test]
+    fn test_events_reports_each_entry() {
+        let zip_data =
+            create_test_zip(&[("dir/", &[]), ("dir/a.txt", b"alpha"), ("b.log", b"beta")]);
+        let temp_dir = tempfile::tempdir().unwrap();
+        let zip_path = temp_dir.path().join("test.zip");
+        fs::write(&zip_path, zip_data).unwrap();
+
+        let options = options_for(temp_dir.path().join("out")).exclude(["*.log"]);
+
+        let mut stream = events(ArchiveSource::FilePath(zip_path), &options);
+        let collected: Vec<ExtractEvent> = (&mut stream).collect();
+        let report = stream.join().unwrap();
+
+        assert!(matches!(
+            collected.first(),
+            Some(ExtractEvent::Started { total_entries: 3, .. })
+        ));
+        assert!(collected.iter().any(|e| matches!(e, ExtractEvent::DirCreated { .. })));
+        assert!(collected.iter().any(|e| matches!(
+            e,
+            ExtractEvent::FileDone { name, bytes: 5, .. } if name == "dir/a.txt"
+        )));
+        assert!(collected.contains(&ExtractEvent::Skipped {
+            name: "b.log".to_string(),
+            reason: SkipReason::Filtered,
+        }));
+        assert!(matches!(collected.last(), Some(ExtractEvent::Finished { .. })));
+        assert_eq!(report.extracted, 1);
+        assert_eq!(report.skipped, 1);
+        assert_eq!(report.bytes, 5);
+    }
+
+    #[test]
+    fn test_events_threaded_finishes_with_report() {
+        let zip_data = create_test_zip(&[("a.txt", b"one"), ("b.txt", b"two")]);
+        let temp_dir = tempfile::tempdir().unwrap();
+        let zip_path = temp_dir.path().join("test.zip");
+        fs::write(&zip_path, zip_data).unwrap();
+
+        let options = options_for(temp_dir.path().join("out")).threads(2);
+
+        let mut stream = events(ArchiveSource::FilePath(zip_path), &options);
+        let done = (&mut stream).filter(|e| matches!(e, ExtractEvent::FileDone { .. })).count();
+        let report = stream.join().unwrap();
+
+        assert_eq!(done, 2);
+        assert_eq!(report.extracted, 2);
+        assert_eq!(report.bytes, 6);
+    }
+
+    #[test]
+    fn test_events_join_surfaces_errors() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let zip_path = temp_dir.path().join("missing.zip");
+        let options = options_for(temp_dir.path().join("out"));
+
+        let mut stream = events(ArchiveSource::FilePath(zip_path), &options);
+        assert_eq!((&mut stream).count(), 0);
+        assert!(stream.join().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_infected_warning_reaches_observer_without_progress_bar() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let zip_path = temp_dir.path().join("test.zip");
+        fs::write(&zip_path, create_test_zip(&[("x.exe", b"X5O!P%@AP EICAR")])).unwrap();
+        let socket = crate::scan::fake_scanner(temp_dir.path());
+        let options = options_for(temp_dir.path().join("out")).scan_socket(&socket);
+
+        let mut stream = events(ArchiveSource::FilePath(zip_path), &options);
+        let warnings: Vec<String> = (&mut stream)
+            .filter_map(|event| match event {
+                ExtractEvent::Warning { message } => Some(message),
+                _ => None,
+            })
+            .collect();
+        assert!(matches!(stream.join(), Err(UnzipError::Infected { entries: 1 })));
+        assert_eq!(warnings, ["    infected: x.exe (Eicar-Test-Signature), removed"]);
+
+        // A progress bar that is not drawn would drop the message, so it goes to stderr
+        let mut reporter = ConsoleReporter::new(&options);
+        reporter.progress_bar = Some(ProgressBar::hidden());
+        assert!(reporter.drawn_progress_bar().is_none());
+    }
+}
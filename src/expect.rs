@@ -0,0 +1,254 @@
+//! Entry count and size assertions (`--expect-files`, `--expect-bytes`)
+//!
+//! Deployment scripts know what an artifact should contain. With `--expect-files`
+//! and `--expect-bytes`, extraction first counts the file entries selected by the
+//! patterns and `-x` and adds up their uncompressed sizes, and refuses to start if
+//! either differs from what was expected. A truncated or wrong artifact then fails
+//! before anything is written instead of being half deployed.
+//!
+//! Expected values are exact (`120`) or allow a tolerance either way, absolute
+//! (`120+-2`, `1G+-10M`) or relative to the value (`1G+-5%`); `±` may be used
+//! instead of `+-`.
+//!
+//! # Examples
+//!
+//! ```
+//! use unzip::expect::{parse_expected_count, parse_expected_size};
+//!
+//! let files = parse_expected_count("120+-2").unwrap();
+//! assert!(files.matches(118) && !files.matches(123));
+//!
+//! let bytes = parse_expected_size("1M+-10%").unwrap();
+//! assert!(bytes.matches(1_000_000) && !bytes.matches(2_000_000));
+//! assert_eq!(bytes.to_string(), "1048576±104857");
+//! ```
+
+use std::fmt;
+use std::io::{Read, Seek};
+use zip::ZipArchive;
+
+use crate::args::Args;
+use crate::charset::NameDecoder;
+use crate::error::Result;
+use crate::limits::parse_size;
+use crate::utils::PatternMatcher;
+
+/// An expected value and how far the actual value may be off
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Expected {
+    /// The expected value
+    pub value: u64,
+    /// Largest allowed difference, either way
+    pub tolerance: u64,
+}
+
+impl Expected {
+    /// Expect exactly `value`
+    pub fn exact(value: u64) -> Self {
+        Self { value, tolerance: 0 }
+    }
+
+    /// Whether `actual` is within the tolerance of the expected value
+    pub fn matches(&self, actual: u64) -> bool {
+        actual.abs_diff(self.value) <= self.tolerance
+    }
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.tolerance == 0 {
+            write!(f, "{}", self.value)
+        } else {
+            write!(f, "{}±{}", self.value, self.tolerance)
+        }
+    }
+}
+
+/// What the selected entries of an archive must amount to; `None` means anything
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Expectations {
+    /// Number of selected file entries (`--expect-files`)
+    pub files: Option<Expected>,
+    /// Total uncompressed size of the selected files (`--expect-bytes`)
+    pub bytes: Option<Expected>,
+}
+
+/// Which expectation an archive failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExpectationFailed {
+    /// The selection holds a different number of files
+    Files { expected: Expected, actual: u64 },
+    /// The selected files add up to a different size
+    Bytes { expected: Expected, actual: u64 },
+}
+
+impl fmt::Display for ExpectationFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Files { expected, actual } => write!(
+                f,
+                "archive selection has {} files, expected {} (--expect-files)",
+                actual, expected
+            ),
+            Self::Bytes { expected, actual } => write!(
+                f,
+                "archive selection has {} bytes, expected {} (--expect-bytes)",
+                actual, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ExpectationFailed {}
+
+impl Expectations {
+    /// Read expectations from command-line arguments
+    pub fn from_args(args: &Args) -> Self {
+        Self { files: args.expect_files, bytes: args.expect_bytes }
+    }
+
+    /// Whether anything is expected
+    pub fn is_set(&self) -> bool {
+        *self != Self::default()
+    }
+
+    /// Check the selected file entries declared in the central directory
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`ExpectationFailed`] error for the first expectation the
+    /// selection misses, or an error if the central directory cannot be read
+    pub(crate) fn check_archive<R: Read + Seek>(
+        &self,
+        archive: &mut ZipArchive<R>,
+        names: &NameDecoder,
+        matcher: &PatternMatcher,
+    ) -> Result<()> {
+        if !self.is_set() {
+            return Ok(());
+        }
+
+        let (mut files, mut bytes) = (0u64, 0u64);
+        for i in 0..archive.len() {
+            let file = archive.by_index_raw(i)?;
+            if !file.is_dir() && matcher.should_extract(&names.decode(&file)) {
+                files += 1;
+                bytes = bytes.saturating_add(file.size());
+            }
+        }
+
+        if let Some(expected) = self.files
+            && !expected.matches(files)
+        {
+            return Err(ExpectationFailed::Files { expected, actual: files }.into());
+        }
+        if let Some(expected) = self.bytes
+            && !expected.matches(bytes)
+        {
+            return Err(ExpectationFailed::Bytes { expected, actual: bytes }.into());
+        }
+        Ok(())
+    }
+}
+
+/// Parse an `--expect-files` value: `N`, `N+-T` or `N+-P%`
+///
+/// # Errors
+///
+/// Returns a message suitable for clap if the value is not a valid count
+pub fn parse_expected_count(value: &str) -> Result<Expected, String> {
+    parse_expected(value, |n| n.parse().map_err(|_| format!("invalid count: {}", n)))
+}
+
+/// Parse an `--expect-bytes` value: `SIZE`, `SIZE+-SIZE` or `SIZE+-P%`, where sizes
+/// take the suffixes of [`parse_size`]
+///
+/// # Errors
+///
+/// Returns a message suitable for clap if the value is not a valid size
+pub fn parse_expected_size(value: &str) -> Result<Expected, String> {
+    parse_expected(value, parse_size)
+}
+
+fn parse_expected(
+    value: &str,
+    parse: impl Fn(&str) -> Result<u64, String>,
+) -> Result<Expected, String> {
+    let value = value.trim();
+    let Some((expected, tolerance)) = value.split_once("+-").or_else(|| value.split_once('±'))
+    else {
+        return parse(value).map(Expected::exact);
+    };
+    let expected = parse(expected.trim())?;
+    let tolerance = tolerance.trim();
+    let tolerance = match tolerance.strip_suffix('%') {
+        Some(percent) => {
+            let percent: u64 =
+                percent.parse().map_err(|_| format!("invalid tolerance: {}", tolerance))?;
+            (u128::from(expected) * u128::from(percent) / 100).min(u128::from(u64::MAX)) as u64
+        },
+        None => parse(tolerance)?,
+    };
+    Ok(Expected { value: expected, tolerance })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::UnzipError;
+    use std::io::{Cursor, Write};
+    use zip::ZipWriter;
+    use zip::write::SimpleFileOptions;
+
+    fn archive() -> ZipArchive<Cursor<Vec<u8>>> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.add_directory("app/", SimpleFileOptions::default()).unwrap();
+        for (name, size) in [("app/a.bin", 100), ("app/b.bin", 200), ("app/debug.log", 50)] {
+            zip.start_file(name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(&vec![0u8; size]).unwrap();
+        }
+        ZipArchive::new(zip.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_expected() {
+        assert_eq!(parse_expected_count("120"), Ok(Expected::exact(120)));
+        assert_eq!(parse_expected_count("120+-2"), Ok(Expected { value: 120, tolerance: 2 }));
+        assert_eq!(parse_expected_count("120 ± 2"), Ok(Expected { value: 120, tolerance: 2 }));
+        assert_eq!(parse_expected_size("1K+-10%"), Ok(Expected { value: 1024, tolerance: 102 }));
+        assert_eq!(parse_expected_size("1M+-4K").unwrap().tolerance, 4096);
+        assert!(parse_expected_count("1K").is_err());
+        assert!(parse_expected_count("120+-").is_err());
+        assert!(parse_expected_size("1G+-x%").is_err());
+    }
+
+    #[test]
+    fn test_check_archive_counts_selected_files() {
+        let mut archive = archive();
+        let names = NameDecoder::default();
+        let exclude = vec!["app/*.log".to_string()];
+        let matcher = PatternMatcher::new(&[], &exclude, false);
+
+        let exact =
+            Expectations { files: Some(Expected::exact(2)), bytes: Some(Expected::exact(300)) };
+        exact.check_archive(&mut archive, &names, &matcher).unwrap();
+
+        let all = PatternMatcher::new(&[], &[], false);
+        let error = exact.check_archive(&mut archive, &names, &all).unwrap_err();
+        let expected = Expected::exact(2);
+        assert!(matches!(
+            error,
+            UnzipError::Expectation(ExpectationFailed::Files { expected: e, actual: 3 }) if e == expected
+        ));
+        assert!(error.to_string().contains("--expect-files"));
+
+        let loose =
+            Expectations { files: None, bytes: Some(Expected { value: 340, tolerance: 10 }) };
+        loose.check_archive(&mut archive, &names, &all).unwrap();
+        let error = loose.check_archive(&mut archive, &names, &matcher).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "archive selection has 300 bytes, expected 340±10 (--expect-bytes)"
+        );
+    }
+}
@@ -19,31 +19,84 @@
 //! ```no_run
 //! use std::fs::File;
 //! use zip::ZipArchive;
-//! use unzip::{Args, extract_archive};
-//! use clap::Parser;
+//! use unzip::{ExtractOptions, OverwritePolicy, extract_archive};
 //!
 //! let file = File::open("archive.zip")?;
 //! let mut archive = ZipArchive::new(file)?;
-//! let args = Args::parse();
-//! extract_archive(&mut archive, &args)?;
+//! let options = ExtractOptions::new().output_dir("out").overwrite(OverwritePolicy::Update);
+//! extract_archive(&mut archive, &options)?;
 //! # Ok::<(), Box<dyn std::error::Error>>(())
 //! ```
 
-use anyhow::{Context, Result, bail};
-use indicatif::{ProgressBar, ProgressStyle};
+use filetime::FileTime;
 use memmap2::Mmap;
-use std::fs::{self, File};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File, Permissions};
 use std::io::{self, BufWriter, Read, Seek, Write};
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, mpsc};
 use std::thread;
+use std::time::Instant;
 use zip::ZipArchive;
-
-use crate::args::Args;
+use zip::read::ZipFile;
+
+use crate::casedirs::CaseDirs;
+use crate::charset::NameDecoder;
+use crate::checkpoint::Checkpoint;
+use crate::compat::{Compat, OverwritePrompt, Reply};
+use crate::destination::{Destination, missing_dirs};
+use crate::dirstamp::DirStamper;
+use crate::duplicates::{Duplicates, PatchedDirectory, PatchedReader};
+use crate::error::{Result, UnzipError};
+use crate::events::{
+    ConsoleReporter, ExtractEvent, ExtractObserver, ExtractReport, PlannedAction, SkipReason,
+};
+use crate::expect::Expectations;
+use crate::flatten::LongPathStore;
+use crate::hooks::{EntryAction, EntryHooks, EntryMeta, EntryOutcome};
+use crate::implied_dirs::stamp_implied_dirs;
+use crate::index::{ArchiveIndex, SliceEntry};
+use crate::invalid_names::check_names;
+use crate::legacy::{LegacyEntry, decodes};
+use crate::limits::{DEFAULT_MEMORY_LIMIT, LimitExceeded, OutputBudget};
 use crate::linux::{fadvise_dontneed, preallocate_file};
+use crate::list::stopped_reading;
+use crate::members::{check_member_index, check_members};
+use crate::multipart::ConcatReader;
+use crate::nested::{NestedBudget, is_archive_path, nested_dir};
+use crate::opendoc::check_container;
+use crate::options::{ExtractOptions, OverwritePolicy};
 use crate::password::{get_password, is_password_error, prompt_for_password};
-use crate::utils::{PatternMatcher, datetime_to_filetime, datetime_to_system_time, format_size};
+use crate::patch::PatchWriter;
+use crate::path_safety::{NameIssue, sanitize_entry_name};
+use crate::permissions::file_mode;
+use crate::priority::{Priority, extraction_order};
+use crate::raw_names::raw_output_path;
+use crate::reader::{MmapReader, split_for_threads};
+use crate::readonly::{is_read_only, unlock};
+use crate::recompress::{OutputCompression, OutputWriter, output_path};
+use crate::recovery::{Relabel, decode_relabelled, is_decode_error};
+use crate::rename::free_path;
+use crate::scaling::{JobQueue, SAMPLE_INTERVAL, Scaler, Workers};
+use crate::scan::{InStream, Verdict, quarantine, scan_file};
+use crate::self_overwrite::{SelfOverwrite, SourceGuard};
+use crate::shape::{ArchiveShape, DirStrategy, leaf_directories};
+use crate::shared::PendingFile;
+use crate::sink::{EntrySink, MemorySink, SinkEntry};
+use crate::size_check::SizeCheckError;
+use crate::snapshot::DestinationSnapshot;
+use crate::template::EntryFields;
+use crate::textmode::{
+    TextConverter, TextMode, is_text, looks_like_text, read_internal_attributes,
+};
+use crate::timestamps::EntryTimes;
+use crate::transaction::{Journal, Staging};
+use crate::transform::transform_path;
+use crate::unchanged::is_unchanged;
+use crate::utils::{PatternMatcher, lowercase};
+use crate::verify::verify_file;
 
 /// Buffer size for file I/O (256KB for better throughput)
 const BUFFER_SIZE: usize = 256 * 1024;
@@ -57,37 +110,108 @@ enum OverwriteDecision {
     Skip,
     /// Skip extraction quietly (no message)
     SkipQuietly,
+    /// Skip extraction quietly, the file already holds the entry's data
+    Unchanged,
 }
 
 pub enum ArchiveSource {
     FilePath(PathBuf),
     Mmap(Arc<Mmap>),
+    /// Pieces of a byte-concatenated split archive, in order
+    Parts(Vec<PathBuf>),
+    /// Another source read through a central directory rewritten by the
+    /// `--duplicates` policy (see [`crate::duplicates`])
+    Patched(Box<ArchiveSource>, Arc<PatchedDirectory>),
+}
+
+impl ArchiveSource {
+    /// The bytes of a memory-mapped archive
+    fn mapped(&self) -> Option<&[u8]> {
+        match self {
+            Self::Mmap(mmap) => Some(&mmap[..]),
+            Self::FilePath(_) | Self::Parts(_) | Self::Patched(..) => None,
+        }
+    }
+}
+
+/// Index of a memory-mapped archive, whose Stored and Deflate entries are then
+/// decoded from their checked slices of the mapping; `None` for other sources
+///
+/// # Errors
+///
+/// Returns [`UnzipError::OverlappingEntry`] if entries overlap, or an error if a
+/// local header cannot be read
+fn index_mapped<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    source: Option<&ArchiveSource>,
+) -> Result<Option<ArchiveIndex>> {
+    match source.and_then(ArchiveSource::mapped) {
+        Some(bytes) => ArchiveIndex::new(archive, bytes.len() as u64).map(Some),
+        None => Ok(None),
+    }
 }
 
 trait ReadSeek: Read + Seek + Send {}
 impl<T: Read + Seek + Send> ReadSeek for T {}
 
+/// Archive handle owned by one extraction worker
+enum WorkerArchive<'a> {
+    /// Clone of an archive parsed once over the shared memory map
+    Shared(ZipArchive<MmapReader>),
+    /// Archive reopened by the worker from the source path
+    Opened(ZipArchive<Box<dyn ReadSeek + 'a>>),
+}
+
+impl WorkerArchive<'_> {
+    fn by_index(&mut self, index: usize) -> zip::result::ZipResult<ZipFile<'_>> {
+        match self {
+            Self::Shared(archive) => archive.by_index(index),
+            Self::Opened(archive) => archive.by_index(index),
+        }
+    }
+
+    fn by_index_raw(&mut self, index: usize) -> zip::result::ZipResult<ZipFile<'_>> {
+        match self {
+            Self::Shared(archive) => archive.by_index_raw(index),
+            Self::Opened(archive) => archive.by_index_raw(index),
+        }
+    }
+
+    fn by_index_decrypt(
+        &mut self,
+        index: usize,
+        password: &[u8],
+    ) -> zip::result::ZipResult<ZipFile<'_>> {
+        match self {
+            Self::Shared(archive) => archive.by_index_decrypt(index, password),
+            Self::Opened(archive) => archive.by_index_decrypt(index, password),
+        }
+    }
+}
+
 /// Finalize an extracted file by setting modification time and permissions
 ///
 /// # Arguments
 ///
 /// * `outpath` - Path to the extracted file
-/// * `modified_time` - Optional modification time from archive
+/// * `times` - Modification and access times from the archive
 /// * `unix_mode` - Optional Unix permissions mode
 /// * `no_timestamps` - Skip timestamp restoration if true
 ///
 /// # Errors
 ///
 /// This function logs errors but does not fail the extraction process
-fn finalize_extracted_file(
+pub(crate) fn finalize_extracted_file(
     outpath: &std::path::Path,
-    modified_time: Option<zip::DateTime>,
+    times: EntryTimes,
     unix_mode: Option<u32>,
     no_timestamps: bool,
 ) {
-    if !no_timestamps && let Some(dt) = modified_time {
-        let mtime = datetime_to_filetime(dt);
-        filetime::set_file_mtime(outpath, mtime).ok();
+    if !no_timestamps && let Some(mtime) = times.modified {
+        match times.accessed {
+            Some(atime) => filetime::set_file_times(outpath, atime, mtime).ok(),
+            None => filetime::set_file_mtime(outpath, mtime).ok(),
+        };
     }
 
     #[cfg(unix)]
@@ -105,63 +229,391 @@ fn finalize_extracted_file(
     }
 }
 
+/// Directory entry whose metadata is applied once all files are written
+struct DirectoryMeta {
+    path: PathBuf,
+    times: EntryTimes,
+    unix_mode: Option<u32>,
+}
+
+/// Apply archived permissions and timestamps to extracted directories
+///
+/// Runs deepest-first after all files are written: creating files updates a
+/// directory's mtime, and a read-only parent would block work on its children.
+/// Timestamps are skipped from `-D` on (`no_timestamps >= 1`).
+fn finalize_directories(directories: &mut [DirectoryMeta], no_timestamps: u8) {
+    directories.sort_by_key(|dir| std::cmp::Reverse(dir.path.components().count()));
+    for dir in directories.iter() {
+        finalize_extracted_file(&dir.path, dir.times, dir.unix_mode, no_timestamps >= 1);
+    }
+}
+
+/// Give the directories `destination` created for files, without a directory
+/// entry of their own, the time `--implied-dir-times` asks for
+///
+/// Runs after [`finalize_directories`], so `newest` sees the final times of the
+/// directory entries inside them.
+fn stamp_created_dirs(
+    destination: &Destination,
+    directories: &[DirectoryMeta],
+    options: &ExtractOptions,
+) -> Result<()> {
+    let explicit: HashSet<&Path> = directories.iter().map(|dir| dir.path.as_path()).collect();
+    let implied = destination
+        .created_dirs()
+        .into_iter()
+        .filter(|dir| !explicit.contains(dir.as_path()));
+    stamp_implied_dirs(implied.collect(), options.implied_dir_times)
+}
+
+/// With `--chown`, give `path` its owner
+fn apply_owner(path: &Path, options: &ExtractOptions) -> Result<()> {
+    match options.owner {
+        Some(owner) => owner.apply(path).map_err(UnzipError::file("change owner of", path)),
+        None => Ok(()),
+    }
+}
+
+/// With `--chown`, give their owner to the directories created by the run:
+/// `created_output` for the output directory, those made through `destination`,
+/// and the extracted directory entries
+///
+/// Runs before [`finalize_directories`], since a change of owner clears setuid
+/// and setgid bits.
+fn chown_directories(
+    created_output: Vec<PathBuf>,
+    destination: &Destination,
+    directories: &[DirectoryMeta],
+    options: &ExtractOptions,
+) -> Result<()> {
+    if options.owner.is_none() {
+        return Ok(());
+    }
+    let mut dirs = created_output;
+    dirs.extend(destination.created_dirs());
+    dirs.extend(directories.iter().map(|dir| dir.path.clone()));
+    dirs.sort();
+    dirs.dedup();
+    dirs.iter().try_for_each(|dir| apply_owner(dir, options))
+}
+
+/// Data written for a file entry
+struct Written {
+    bytes: u64,
+    /// CRC32 of the data, computed only for `--verify`
+    crc32: Option<u32>,
+}
+
+/// How the data of a file entry is written
+#[derive(Debug, Clone, Copy)]
+struct EntryWrite {
+    /// Whether to convert line endings (`-a` / `-aa`), or `None` to decide from
+    /// the first block
+    convert: Option<bool>,
+    /// Whether to compute the CRC32 of the data written (`--verify`)
+    verify: bool,
+    /// Format to compress the data into (`--compress-output`)
+    compress: Option<OutputCompression>,
+    /// Whether the file is an existing one to patch block by block
+    /// (`--patch-in-place`)
+    patch: bool,
+    /// Size the data must decompress to (`--size-check`)
+    expected_size: Option<u64>,
+}
+
+/// Where [`extract_single_file`] writes the data of a file entry
+enum EntryWriter<'a> {
+    /// A file created for it, through a compressor with `--compress-output`
+    Create(OutputWriter<BufWriter<&'a File>>),
+    /// An existing file, rewritten only where it differs (`--patch-in-place`)
+    Patch(PatchWriter<'a>),
+}
+
+impl<'a> EntryWriter<'a> {
+    fn new(outfile: &'a File, mode: EntryWrite) -> io::Result<Self> {
+        if mode.patch {
+            return Ok(Self::Patch(PatchWriter::new(outfile)));
+        }
+        let writer = BufWriter::with_capacity(BUFFER_SIZE, outfile);
+        OutputWriter::new(writer, mode.compress).map(Self::Create)
+    }
+
+    /// Complete the data and write out what is buffered
+    fn finish(self) -> io::Result<()> {
+        match self {
+            Self::Create(writer) => {
+                writer.finish()?.into_inner().map_err(io::IntoInnerError::into_error)?;
+            },
+            Self::Patch(writer) => {
+                writer.finish()?;
+            },
+        }
+        Ok(())
+    }
+}
+
+impl Write for EntryWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Create(writer) => writer.write(buf),
+            Self::Patch(writer) => writer.write(buf),
+        }
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        match self {
+            Self::Create(writer) => writer.write_all(buf),
+            Self::Patch(writer) => writer.write_all(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Create(writer) => writer.flush(),
+            Self::Patch(writer) => writer.flush(),
+        }
+    }
+}
+
+/// Entry data [`extract_single_file`] decodes: through the zip crate, from the
+/// entry's slice of a memory-mapped archive, or with a legacy method's decoder
+trait EntryData: Read {
+    fn name(&self) -> &str;
+    fn size(&self) -> u64;
+    fn compressed_size(&self) -> u64;
+}
+
+impl EntryData for ZipFile<'_> {
+    fn name(&self) -> &str {
+        ZipFile::name(self)
+    }
+
+    fn size(&self) -> u64 {
+        ZipFile::size(self)
+    }
+
+    fn compressed_size(&self) -> u64 {
+        ZipFile::compressed_size(self)
+    }
+}
+
+impl EntryData for SliceEntry<'_> {
+    fn name(&self) -> &str {
+        SliceEntry::name(self)
+    }
+
+    fn size(&self) -> u64 {
+        SliceEntry::size(self)
+    }
+
+    fn compressed_size(&self) -> u64 {
+        SliceEntry::compressed_size(self)
+    }
+}
+
+impl<R: Read> EntryData for LegacyEntry<R> {
+    fn name(&self) -> &str {
+        LegacyEntry::name(self)
+    }
+
+    fn size(&self) -> u64 {
+        LegacyEntry::size(self)
+    }
+
+    fn compressed_size(&self) -> u64 {
+        LegacyEntry::compressed_size(self)
+    }
+}
+
 /// Extract a single file from the archive to the filesystem
 ///
 /// # Arguments
 ///
 /// * `file` - The zip file entry to extract
-/// * `outpath` - Destination path for the extracted file
+/// * `outfile` - Destination file, created through [`Destination::create_file`]
+///   or, to patch, opened by [`open_patched_file`]; dropping it from the page
+///   cache is up to the caller
 /// * `buffer` - Reusable buffer for I/O operations
+/// * `mode` - Line ending conversion, verification, compression and patching to
+///   apply
+/// * `budget` - Output limits shared by the whole extraction
+/// * `scan` - Virus scanner stream that also receives the data written
+///   (`--scan-socket`)
+/// * `on_progress` - Called with the size of every block written; an error stops
+///   the extraction, e.g. once it is cancelled
 ///
 /// # Returns
 ///
-/// Returns the number of bytes written
+/// Returns the number of bytes written and, with `verify`, their CRC32; both are
+/// of the data before compression
 ///
 /// # Errors
 ///
-/// Returns an error if file creation, writing, or finalization fails
+/// Returns an error if file creation, writing, or finalization fails, or if the
+/// entry crosses an extraction limit
 fn extract_single_file(
-    file: &mut zip::read::ZipFile,
-    outpath: &std::path::Path,
+    file: &mut dyn EntryData,
+    outfile: &File,
     buffer: &mut [u8],
-) -> Result<u64> {
+    mode: EntryWrite,
+    budget: &OutputBudget,
+    mut scan: Option<&mut InStream>,
+    on_progress: &mut dyn FnMut(u64) -> Result<()>,
+) -> Result<Written> {
     let size = file.size();
-
-    let outfile = File::create(outpath)
-        .with_context(|| format!("Failed to create file: {}", outpath.display()))?;
-
-    // Linux optimization: pre-allocate disk space to avoid fragmentation
-    if size > 0 {
-        preallocate_file(&outfile, size).ok();
-    }
+    let compressed_size = file.compressed_size();
+    let name = file.name().to_string();
+    let mut reader = budget.guard(file, &name, compressed_size);
 
     // Use larger buffer for better throughput
-    let mut writer = BufWriter::with_capacity(BUFFER_SIZE, outfile);
+    let mut writer = EntryWriter::new(outfile, mode)?;
 
     // Manual copy with reused buffer for less allocation
     let mut bytes_written = 0u64;
+    let mut decoded = 0u64;
+    let mut hasher = mode.verify.then(crc32fast::Hasher::new);
+    let mut converter: Option<TextConverter> = None;
+    let mut converted = Vec::new();
+    let mut first_block = true;
     loop {
-        let bytes_read = file.read(buffer)?;
+        let bytes_read = reader.read(buffer)?;
+        let chunk = &buffer[..bytes_read];
+
+        if first_block {
+            first_block = false;
+            if mode.convert.unwrap_or_else(|| looks_like_text(chunk)) {
+                converter = Some(TextConverter::new());
+            } else if size > 0 && mode.compress.is_none() && !mode.patch {
+                // Linux optimization: pre-allocate disk space to avoid fragmentation.
+                // Skipped for converted and compressed files, whose final size is
+                // not known, and for patched files, which already have it
+                preallocate_file(outfile, size).ok();
+            }
+        }
+
         if bytes_read == 0 {
             break;
         }
-        writer.write_all(&buffer[..bytes_read])?;
-        bytes_written += bytes_read as u64;
+        decoded += bytes_read as u64;
+        if let Some(ref mut converter) = converter {
+            converted.clear();
+            converter.convert(chunk, &mut converted);
+            writer.write_all(&converted)?;
+            scan.as_deref_mut().map_or(Ok(()), |scan| scan.send(&converted))?;
+            hasher.iter_mut().for_each(|hasher| hasher.update(&converted));
+            bytes_written += converted.len() as u64;
+            on_progress(converted.len() as u64)?;
+        } else {
+            writer.write_all(chunk)?;
+            scan.as_deref_mut().map_or(Ok(()), |scan| scan.send(chunk))?;
+            hasher.iter_mut().for_each(|hasher| hasher.update(chunk));
+            bytes_written += bytes_read as u64;
+            on_progress(bytes_read as u64)?;
+        }
     }
+    if let Some(ref mut converter) = converter {
+        converted.clear();
+        converter.finish(&mut converted);
+        writer.write_all(&converted)?;
+        scan.map_or(Ok(()), |scan| scan.send(&converted))?;
+        hasher.iter_mut().for_each(|hasher| hasher.update(&converted));
+        bytes_written += converted.len() as u64;
+        on_progress(converted.len() as u64)?;
+    }
+    if let Some(expected) = mode.expected_size
+        && decoded != expected
+    {
+        return Err(SizeCheckError::Decompressed { name, expected, actual: decoded }.into());
+    }
+
+    writer.finish()?;
+
+    Ok(Written { bytes: bytes_written, crc32: hasher.map(crc32fast::Hasher::finalize) })
+}
+
+/// Write an entry that failed to decode again, read as the other of Stored and
+/// Deflate (`--heuristic-decode`)
+///
+/// # Arguments
+///
+/// * `raw` - The entry opened with `by_index_raw`
+/// * `outpath` - Path of the partially written file, which is replaced
+/// * `destination` - Output directory the file is confined to
+/// * `error` - Why decoding failed, returned if the other method fails too
+///
+/// # Errors
+///
+/// Returns `error` when the entry does not decode the other way either, after
+/// removing the garbage written, or an error if the file cannot be written
+fn recover_entry(
+    raw: zip::result::ZipResult<ZipFile<'_>>,
+    outpath: &Path,
+    destination: &Destination,
+    error: UnzipError,
+) -> Result<Relabel> {
+    let outfile = destination
+        .create_file(outpath)
+        .map_err(UnzipError::file("create file", outpath))?;
+    let mut writer = BufWriter::with_capacity(BUFFER_SIZE, outfile);
+    let relabel = decode_relabelled(raw?, &mut writer)?;
+    writer.flush().map_err(UnzipError::file("write file", outpath))?;
+    relabel.ok_or_else(|| {
+        let _ = fs::remove_file(outpath);
+        error
+    })
+}
 
-    let inner_file = writer.into_inner()?;
+/// With `--scan-socket`, a scanner stream for the next file written
+fn open_scan(options: &ExtractOptions) -> Result<Option<InStream>> {
+    options.scan_socket.as_deref().map(InStream::open).transpose()
+}
 
-    // Linux optimization: tell kernel we're done with this file's cache
-    fadvise_dontneed(&inner_file, 0, size);
+/// With `--scan-socket`, the scanner's verdict on the file written at
+/// `write_path` for `outpath`: a flagged file is moved into the quarantine, and
+/// the warning to report is returned
+///
+/// `scan` is the stream that received the data as it was written, or `None` to
+/// scan the file itself, as for an entry recovered by `--heuristic-decode`.
+fn check_scan(
+    scan: Option<InStream>,
+    write_path: &Path,
+    outpath: &Path,
+    name: &str,
+    options: &ExtractOptions,
+) -> Result<Option<String>> {
+    let verdict = match (scan, &options.scan_socket) {
+        (Some(scan), _) => scan.verdict(),
+        (None, Some(socket)) => scan_file(socket, write_path),
+        (None, None) => return Ok(None),
+    };
+    // A file the scanner did not clear is not kept
+    let verdict = verdict.inspect_err(|_| {
+        let _ = fs::remove_file(write_path);
+    })?;
+    let Verdict::Infected(signature) = verdict else {
+        return Ok(None);
+    };
+    let message = match quarantine(write_path, outpath, options.quarantine.as_deref())? {
+        Some(path) => {
+            format!("    infected: {} ({}), quarantined as {}", name, signature, path.display())
+        },
+        None => format!("    infected: {} ({}), removed", name, signature),
+    };
+    Ok(Some(message))
+}
 
-    Ok(bytes_written)
+/// Remove a partially written file when extraction was cancelled, or with
+/// `--scan-socket`, whose verdict it will not get
+fn remove_unfinished(error: &UnzipError, outpath: &Path, options: &ExtractOptions) {
+    if matches!(error, UnzipError::Cancelled) || options.scan_socket.is_some() {
+        let _ = fs::remove_file(outpath);
+    }
 }
 
 fn open_archive_from_source(source: &ArchiveSource) -> Result<ZipArchive<Box<dyn ReadSeek + '_>>> {
     match source {
         ArchiveSource::FilePath(path) => {
-            let file = File::open(path)
-                .with_context(|| format!("Failed to open ZIP file: {}", path.display()))?;
+            let file = File::open(path).map_err(UnzipError::file("open ZIP file", path))?;
             let file_size = file.metadata()?.len();
             crate::linux::fadvise_sequential(&file, file_size);
             let reader: Box<dyn ReadSeek> = Box::new(file);
@@ -172,107 +624,590 @@ fn open_archive_from_source(source: &ArchiveSource) -> Result<ZipArchive<Box<dyn
             let reader: Box<dyn ReadSeek> = Box::new(cursor);
             Ok(ZipArchive::new(reader)?)
         },
+        ArchiveSource::Parts(parts) => {
+            let reader: Box<dyn ReadSeek> = Box::new(open_parts(parts)?);
+            Ok(ZipArchive::new(reader)?)
+        },
+        ArchiveSource::Patched(..) => Ok(ZipArchive::new(open_reader(source)?)?),
+    }
+}
+
+/// Plain reader over the archive bytes of `source`
+fn open_reader(source: &ArchiveSource) -> Result<Box<dyn ReadSeek + '_>> {
+    Ok(match source {
+        ArchiveSource::FilePath(path) => {
+            Box::new(File::open(path).map_err(UnzipError::file("open ZIP file", path))?)
+        },
+        ArchiveSource::Mmap(mmap) => Box::new(MmapReader::new(Arc::clone(mmap))),
+        ArchiveSource::Parts(parts) => Box::new(open_parts(parts)?),
+        ArchiveSource::Patched(base, directory) => {
+            Box::new(PatchedReader::new(open_reader(base)?, Arc::clone(directory)))
+        },
+    })
+}
+
+fn open_parts(parts: &[PathBuf]) -> Result<ConcatReader> {
+    let first = parts.first().map_or(Path::new(""), PathBuf::as_path);
+    ConcatReader::open(parts).map_err(UnzipError::file("open split archive", first))
+}
+
+/// Read the text flag of every entry from the central directory for `-a`
+///
+/// # Errors
+///
+/// Returns an error if the archive source cannot be read
+fn load_text_flags<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    source: &ArchiveSource,
+) -> Result<Vec<bool>> {
+    let mut reader = open_reader(source)?;
+    (0..archive.len())
+        .map(|i| {
+            let offset = archive.by_index_raw(i)?.central_header_start();
+            Ok(is_text(read_internal_attributes(&mut reader, offset)?))
+        })
+        .collect()
+}
+
+/// Resolve the path of an entry relative to the output directory
+///
+/// Applies the directory spellings of `--merge-case-dirs`, lowercasing (`-L`),
+/// `--strip-components`, path junking (`-j`) and `--transform` on top of
+/// [`sanitize_entry_name`], mapping unusable names to the matching [`SkipReason`].
+/// With `--raw-names`, the path is then made of the name's bytes.
+fn entry_output_path(
+    name: &str,
+    options: &ExtractOptions,
+    case_dirs: &CaseDirs,
+) -> Result<PathBuf, SkipReason> {
+    named_output_path(name, options, case_dirs).map(|path| restore_raw_name(path, options))
+}
+
+/// [`entry_output_path`] as text, before `--raw-names` restores the bytes
+fn named_output_path(
+    name: &str,
+    options: &ExtractOptions,
+    case_dirs: &CaseDirs,
+) -> Result<PathBuf, SkipReason> {
+    let name = if options.merge_case_dirs {
+        case_dirs.merge(name)
+    } else {
+        Cow::Borrowed(name)
+    };
+    let name = if options.lowercase {
+        lowercase(&name).into_owned()
+    } else {
+        name.into_owned()
+    };
+    let relative = sanitize_entry_name(&name).map_err(|issue| match issue {
+        NameIssue::Empty => SkipReason::EmptyName,
+        NameIssue::ParentDir | NameIssue::NulByte | NameIssue::NotRelative => {
+            SkipReason::UnsafePath
+        },
+    })?;
+    let relative = if options.strip_components > 0 {
+        let stripped: PathBuf = relative.components().skip(options.strip_components).collect();
+        if stripped.as_os_str().is_empty() {
+            return Err(SkipReason::Stripped);
+        }
+        stripped
+    } else {
+        relative
+    };
+    let relative = if options.junk_paths {
+        // Sanitized paths always end in a normal component
+        relative.file_name().map(PathBuf::from).unwrap_or(relative)
+    } else {
+        relative
+    };
+    if options.transforms.is_empty() {
+        return Ok(relative);
+    }
+    let mut path: Vec<_> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+    // Directories keep their trailing `/`, so `s,^docs/,manual/,` moves them too
+    if name.ends_with('/') {
+        path.push(Cow::Borrowed(""));
+    }
+    // Transforms may produce anything, so their result is sanitized again
+    sanitize_entry_name(&transform_path(&options.transforms, &path.join("/"))).map_err(|issue| {
+        match issue {
+            NameIssue::Empty => SkipReason::Stripped,
+            NameIssue::ParentDir | NameIssue::NulByte | NameIssue::NotRelative => {
+                SkipReason::UnsafePath
+            },
+        }
+    })
+}
+
+/// Resolve the path of the file entry `meta` relative to the output directory,
+/// once the patterns and then the caller's [`EntryHooks`] have selected it
+///
+/// A path the hooks rename the entry to is sanitized like an entry name.
+fn selected_output_path(
+    meta: &EntryMeta<'_>,
+    fields: &EntryFields,
+    matcher: &PatternMatcher,
+    options: &ExtractOptions,
+    case_dirs: &CaseDirs,
+) -> Result<PathBuf, SkipReason> {
+    if matcher.before_entry(meta) == EntryAction::Skip {
+        return Err(SkipReason::Filtered);
+    }
+    let action = options
+        .hooks
+        .as_ref()
+        .map_or(EntryAction::Extract, |hooks| hooks.before_entry(meta));
+    match action {
+        EntryAction::Extract => file_output_path(meta.name, fields, options, case_dirs),
+        EntryAction::Skip => Err(SkipReason::Vetoed),
+        EntryAction::Rename(path) => {
+            sanitize_entry_name(&path.to_string_lossy()).map_err(|issue| match issue {
+                NameIssue::Empty => SkipReason::EmptyName,
+                NameIssue::ParentDir | NameIssue::NulByte | NameIssue::NotRelative => {
+                    SkipReason::UnsafePath
+                },
+            })
+        },
+    }
+}
+
+/// Resolve the path of a file entry relative to the output directory
+///
+/// [`entry_output_path`], renamed after `--name-template` when one is set;
+/// directory entries keep their paths.
+fn file_output_path(
+    name: &str,
+    fields: &EntryFields,
+    options: &ExtractOptions,
+    case_dirs: &CaseDirs,
+) -> Result<PathBuf, SkipReason> {
+    let relative = named_output_path(name, options, case_dirs)?;
+    let Some(ref template) = options.name_template else {
+        return Ok(restore_raw_name(relative, options));
+    };
+    let path: Vec<_> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+    // Like transforms, a template may produce anything
+    sanitize_entry_name(&template.render(&path.join("/"), fields))
+        .map(|path| restore_raw_name(path, options))
+        .map_err(|issue| match issue {
+            NameIssue::Empty => SkipReason::Stripped,
+            NameIssue::ParentDir | NameIssue::NulByte | NameIssue::NotRelative => {
+                SkipReason::UnsafePath
+            },
+        })
+}
+
+/// The output path `relative` made of the bytes of the names with
+/// `--raw-names`, or as it is
+fn restore_raw_name(relative: PathBuf, options: &ExtractOptions) -> PathBuf {
+    if options.names.raw_names() {
+        raw_output_path(&relative)
+    } else {
+        relative
+    }
+}
+
+/// With `--opendoc-check`, check that the archive keeps the conventions of its
+/// container type
+///
+/// # Errors
+///
+/// Returns an [`InvalidContainer`](crate::opendoc::InvalidContainer) error if it
+/// does not, or an error if the archive cannot be read
+fn check_opendoc<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    options: &ExtractOptions,
+) -> Result<()> {
+    if options.opendoc_check {
+        check_container(archive)?;
+    }
+    Ok(())
+}
+
+/// Refuse to extract the archive onto itself: an entry written over the archive
+/// file or one of its volumes, or in place of a directory holding one
+///
+/// Nothing is checked with `--allow-self-overwrite`, in dry runs, when no file
+/// is replaced (`-n`, `--auto-rename`) or when the archive's files are unknown.
+///
+/// # Errors
+///
+/// Returns a [`SelfOverwrite`] error listing the offending entries, or an error
+/// if the archive's entry table cannot be read
+fn check_self_overwrite<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    options: &ExtractOptions,
+    names: &NameDecoder,
+    matcher: &PatternMatcher,
+    case_dirs: &CaseDirs,
+    output_dir: &Path,
+) -> Result<()> {
+    if options.sources.is_empty()
+        || options.allow_self_overwrite
+        || options.dry_run
+        || matches!(options.overwrite, OverwritePolicy::Never | OverwritePolicy::Rename)
+    {
+        return Ok(());
+    }
+    let guard = SourceGuard::new(&options.sources, output_dir);
+    let mut problems = Vec::new();
+    for i in (0..archive.len()).filter(|&i| options.considers(i)) {
+        let file = archive.by_index_raw(i)?;
+        let name = names.decode(&file);
+        let relative = if file.is_dir() {
+            let selected = !options.junk_paths && matcher.should_extract_dir(&name);
+            entry_output_path(&name, options, case_dirs).ok().filter(|_| selected)
+        } else {
+            let fields = EntryFields::of(i, &file);
+            let relative = file_output_path(&name, &fields, options, case_dirs).ok();
+            (relative.filter(|_| matcher.should_extract(&name)))
+                .map(|relative| output_path(relative, options.compress_output))
+        };
+        if let Some(relative) = relative {
+            problems.extend(guard.check(&relative, file.is_dir()));
+        }
     }
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(SelfOverwrite { problems }.into())
+    }
+}
+
+/// Directory a run extracts into: the output directory, or with `--smart-dir`
+/// the directory named after the archive inside it, unless every selected entry
+/// already lies in one top-level directory
+///
+/// # Errors
+///
+/// Returns an error if the archive's entry table cannot be read
+fn smart_output_dir<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    options: &ExtractOptions,
+    names: &NameDecoder,
+    matcher: &PatternMatcher,
+    case_dirs: &CaseDirs,
+) -> Result<PathBuf> {
+    let Some(ref smart_dir) = options.smart_dir else {
+        return Ok(options.output_dir.clone());
+    };
+    let mut top = None;
+    for i in (0..archive.len()).filter(|&i| options.considers(i)) {
+        let file = archive.by_index_raw(i)?;
+        let name = names.decode(&file);
+        let selected = if file.is_dir() {
+            !options.junk_paths && matcher.should_extract_dir(&name)
+        } else {
+            matcher.should_extract(&name)
+        };
+        let relative = if file.is_dir() {
+            entry_output_path(&name, options, case_dirs)
+        } else {
+            file_output_path(&name, &EntryFields::of(i, &file), options, case_dirs)
+        };
+        let Ok(relative) = relative else {
+            continue;
+        };
+        if !selected {
+            continue;
+        }
+        let mut components = relative.components();
+        let first = components.next().map(|first| first.as_os_str().to_owned());
+        // A file at the top level, or a second top-level directory, is clutter
+        if (!file.is_dir() && components.next().is_none())
+            || top.get_or_insert_with(|| first.clone()) != &first
+        {
+            return Ok(options.output_dir.join(smart_dir));
+        }
+    }
+    Ok(options.output_dir.clone())
+}
+
+/// Directories of the selected entries spelled in more than one case, and a
+/// warning for each, or what `--merge-case-dirs` makes of them
+///
+/// Lowercased (`-L`) and junked (`-j`) paths cannot differ in case, so nothing is
+/// scanned for them.
+fn scan_case_dirs<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    names: &NameDecoder,
+    matcher: &PatternMatcher,
+    options: &ExtractOptions,
+) -> Result<(CaseDirs, Vec<ExtractEvent>)> {
+    if options.lowercase || options.junk_paths {
+        return Ok((CaseDirs::default(), Vec::new()));
+    }
+    let case_dirs = CaseDirs::scan(archive, names, matcher)?;
+    let mut events = Vec::new();
+    for paths in case_dirs.conflicts() {
+        let (first, others) = paths.split_first().expect("conflicts have two paths or more");
+        let message = if options.merge_case_dirs {
+            let merged = case_dirs.merge(first);
+            others
+                .iter()
+                .map(|other| format!("   merged: {}/ -> {}/", other, merged))
+                .collect()
+        } else {
+            let spellings: Vec<String> = paths.iter().map(|path| format!("{}/", path)).collect();
+            vec![format!(
+                "warning:  {} differ only in case (--merge-case-dirs extracts them all into {}/)",
+                spellings.join(", "),
+                case_dirs.merge(first)
+            )]
+        };
+        events.extend(message.into_iter().map(|message| ExtractEvent::Warning { message }));
+    }
+    Ok((case_dirs, events))
 }
 
-fn candidate_thread_count(args: &Args) -> usize {
-    if args.quiet == 0 {
+fn candidate_thread_count(options: &ExtractOptions) -> usize {
+    // Per-file console messages must stay in order, dry runs and directory
+    // skeletons write no data to spread over threads, and overwrite prompts and
+    // free names for `--auto-rename` need one entry at a time
+    if (options.quiet == 0 && options.observer.is_none())
+        || options.dry_run
+        || options.dirs_only
+        || options.prompts_overwrite()
+        || options.overwrite == OverwritePolicy::Rename
+    {
         return 1;
     }
     let auto = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
-    let requested = args.threads.unwrap_or(auto);
+    let requested = options.threads.unwrap_or(auto);
     if requested == 0 { 1 } else { requested }
 }
 
-/// Determine whether to overwrite an existing file based on extraction args
+/// Times to restore on an extracted entry, read the way `--compat` asks for
+fn entry_times(file: &ZipFile, options: &ExtractOptions) -> EntryTimes {
+    match options.compat {
+        Compat::InfoZip => EntryTimes::info_zip(file),
+        Compat::Native => EntryTimes::from_entry(file),
+    }
+}
+
+/// Determine whether to overwrite an existing file based on the overwrite policy
 ///
 /// # Arguments
 ///
 /// * `outpath` - Path to the file that may exist
-/// * `args` - Command-line arguments with overwrite flags
-/// * `archive_modified` - Modification time from the archive file
+/// * `policy` - Overwrite policy (`-o`, `-n`, `-f`, `-u`)
+/// * `archive_modified` - Modification time of the archive entry
 ///
 /// # Returns
 ///
 /// Returns `OverwriteDecision` indicating whether to overwrite, skip with message, or skip quietly
 fn should_overwrite_file(
     outpath: &std::path::Path,
-    args: &Args,
-    archive_modified: Option<zip::DateTime>,
+    policy: OverwritePolicy,
+    archive_modified: Option<FileTime>,
 ) -> OverwriteDecision {
     if !outpath.exists() {
-        if args.freshen {
+        if policy == OverwritePolicy::Freshen {
             return OverwriteDecision::SkipQuietly;
         }
         return OverwriteDecision::Overwrite;
     }
 
-    if args.freshen || args.update {
-        if let Ok(meta) = outpath.metadata()
-            && let Ok(disk_mtime) = meta.modified()
-            && let Some(archive_mtime) = archive_modified
-        {
-            let archive_time = datetime_to_system_time(archive_mtime);
-            if archive_time <= disk_mtime {
+    match policy {
+        OverwritePolicy::Freshen | OverwritePolicy::Update => {
+            let disk_modified = outpath.metadata().and_then(|meta| meta.modified()).ok();
+            if is_up_to_date(archive_modified, disk_modified.map(FileTime::from_system_time)) {
                 return OverwriteDecision::SkipQuietly;
             }
-        }
-        return OverwriteDecision::Overwrite;
+            OverwriteDecision::Overwrite
+        },
+        OverwritePolicy::Always | OverwritePolicy::IfChanged => OverwriteDecision::Overwrite,
+        OverwritePolicy::Skip | OverwritePolicy::Never | OverwritePolicy::Rename => {
+            OverwriteDecision::Skip
+        },
     }
+}
 
-    if args.never_overwrite {
-        return OverwriteDecision::Skip;
-    } else if args.overwrite {
-        return OverwriteDecision::Overwrite;
-    }
+/// Whether a file modified at `disk_modified` is as new as an entry modified at
+/// `archive_modified`, so `-f` and `-u` keep it
+fn is_up_to_date(archive_modified: Option<FileTime>, disk_modified: Option<FileTime>) -> bool {
+    matches!((archive_modified, disk_modified), (Some(entry), Some(disk)) if entry <= disk)
+}
 
-    OverwriteDecision::Skip
+/// [`should_overwrite_file`], keeping files that already hold the entry's `size`
+/// bytes with CRC32 `crc32` under `--if-changed` and `--shared-dest`
+fn decide_overwrite(
+    outpath: &Path,
+    options: &ExtractOptions,
+    archive_modified: Option<FileTime>,
+    size: u64,
+    crc32: u32,
+) -> OverwriteDecision {
+    let decision = should_overwrite_file(outpath, options.overwrite, archive_modified);
+    let compare = match decision {
+        OverwriteDecision::Overwrite => {
+            options.overwrite == OverwritePolicy::IfChanged || options.shared_dest
+        },
+        OverwriteDecision::Skip => options.shared_dest,
+        _ => false,
+    };
+    if compare && outpath.exists() && is_unchanged(outpath, size, crc32).unwrap_or(false) {
+        return OverwriteDecision::Unchanged;
+    }
+    decision
 }
 
-/// Extract files to stdout for piping to other commands.
+/// Find the entries a freshen/update run would skip, deciding them in bulk
 ///
-///Writes file contents directly to stdout without creating files on disk.
-/// Directories are skipped. Multiple files are concatenated sequentially.
+/// Uses a [`DestinationSnapshot`] instead of per-entry existence and metadata
+/// checks, so a no-op `-f`/`-u` run reads each destination directory once and
+/// touches no file. Returns the index and decoded name of each up-to-date entry;
+/// all other entries go through the normal checks.
 ///
-/// # Arguments
+/// # Errors
 ///
-/// * `archive` - The ZIP archive to extract from
-/// * `args` - Command-line arguments controlling which files to extract
+/// Returns an error if the archive's entry table cannot be read
+fn prefilter_up_to_date<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    options: &ExtractOptions,
+    names: &NameDecoder,
+    matcher: &PatternMatcher,
+    case_dirs: &CaseDirs,
+    output_dir: &std::path::Path,
+    long_paths: Option<&LongPathStore>,
+) -> Result<Vec<(usize, String)>> {
+    let mut snapshot = DestinationSnapshot::new();
+    let mut up_to_date = Vec::new();
+
+    for i in (0..archive.len()).filter(|&i| options.considers(i)) {
+        // Raw access reads metadata only and works for encrypted entries
+        let file = archive.by_index_raw(i)?;
+        let name = names.decode(&file).into_owned();
+        let candidate = !file.is_dir() && matcher.should_extract(&name);
+        let fields = EntryFields::of(i, &file);
+        let outpath = match file_output_path(&name, &fields, options, case_dirs) {
+            Ok(relative) if candidate => {
+                output_path(output_dir.join(relative), options.compress_output)
+            },
+            _ => continue,
+        };
+        if long_paths.is_some_and(|store| store.exceeds_limits(&outpath)) {
+            continue;
+        }
+
+        let skip = match snapshot.file(&outpath) {
+            Some(disk) => is_up_to_date(entry_times(&file, options).modified, disk.modified),
+            None => options.overwrite == OverwritePolicy::Freshen,
+        };
+        if skip {
+            up_to_date.push((i, name));
+        }
+    }
+
+    Ok(up_to_date)
+}
+
+/// Mark the entries an earlier run extracted, as `checkpoint` records, in
+/// `prefiltered`, returning a skip event for each
 ///
 /// # Errors
 ///
-/// Returns an error if:
-/// - A file cannot be read from the archive
-/// - Writing to stdout fails
+/// Returns an error if the archive's entry table cannot be read
+fn prefilter_resumed<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    options: &ExtractOptions,
+    checkpoint: &Checkpoint,
+    prefiltered: &mut [bool],
+) -> Result<Vec<ExtractEvent>> {
+    let mut events = Vec::new();
+    for (i, prefiltered) in prefiltered.iter_mut().enumerate() {
+        let file = archive.by_index_raw(i)?;
+        if !*prefiltered && options.considers(i) && checkpoint.is_done(i, file.crc32(), file.size())
+        {
+            *prefiltered = true;
+            let name = options.names.decode(&file).into_owned();
+            events.push(ExtractEvent::Skipped { name, reason: SkipReason::Resumed });
+        }
+    }
+    if options.quiet == 0 && !events.is_empty() {
+        let message = format!("   resuming: {} entries already extracted", events.len());
+        events.insert(0, ExtractEvent::Warning { message });
+    }
+    Ok(events)
+}
+
+/// Extract files to stdout for piping to other commands.
+///
+///Writes file contents directly to stdout without creating files on disk.
+/// Directories are skipped. Multiple files are concatenated sequentially.
+///
+/// With [`ExtractOptions::pipe_banners`] (`-c`), each file is preceded by an
+/// Info-ZIP style `  inflating: NAME` line, unless quiet, and its line endings
+/// are converted as `-a` asks.
+///
+/// # Arguments
+///
+/// * `archive` - The ZIP archive to extract from
+/// * `options` - Extraction options controlling which files to extract
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - A file cannot be read from the archive
+/// - Writing to stdout fails
 ///
 /// # Examples
 ///
 /// ```no_run
 /// use std::fs::File;
 /// use zip::ZipArchive;
-/// use unzip::Args;
+/// use unzip::ExtractOptions;
 /// use unzip::extract::extract_to_pipe;
-/// use clap::Parser;
 ///
 /// let file = File::open("archive.zip")?;
 /// let mut archive = ZipArchive::new(file)?;
-/// let args = Args::parse();
-/// extract_to_pipe(&mut archive, &args)?;
+/// extract_to_pipe(&mut archive, &ExtractOptions::new().patterns(["notes.txt"]))?;
 /// # Ok::<(), Box<dyn std::error::Error>>(())
 /// ```
-pub fn extract_to_pipe<R: Read + Seek>(archive: &mut ZipArchive<R>, args: &Args) -> Result<()> {
+pub fn extract_to_pipe<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    options: &ExtractOptions,
+) -> Result<()> {
     let stdout = io::stdout();
     let mut stdout_lock = BufWriter::with_capacity(BUFFER_SIZE, stdout.lock());
+    pipe_entries(archive, options, &mut stdout_lock)
+}
+
+/// Write the selected entries to `out`, as [`extract_to_pipe`] does to stdout
+fn pipe_entries<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    options: &ExtractOptions,
+    out: &mut impl Write,
+) -> Result<()> {
     let mut buffer = vec![0u8; BUFFER_SIZE];
+    let banners = options.pipe_banners && options.quiet == 0;
+    // `-p` writes data as stored; `-c` converts text like extraction to files
+    let convert = if options.pipe_banners {
+        options.text_mode.converts(None)
+    } else {
+        Some(false)
+    };
 
-    let password = Mutex::new(get_password(args.password.as_deref(), args.quiet)?);
-    let matcher = PatternMatcher::new(&args.patterns, &args.exclude, args.case_insensitive);
-    let use_filters = !(args.patterns.is_empty() && args.exclude.is_empty());
-    let exact_target = if args.patterns.len() == 1
-        && args.exclude.is_empty()
-        && !args.case_insensitive
+    let password = Mutex::new(get_password(options.password.as_deref(), options.quiet)?);
+    let matcher = options.matcher();
+    let names = options.names.clone();
+    check_names(archive, &names)?;
+    check_members(archive, &names, &options.members)?;
+    check_member_index(archive, options.member_index)?;
+    let use_filters =
+        !(options.patterns.is_empty() && options.exclude.is_empty() && options.members.is_empty());
+    let exact_target = if options.patterns.len() == 1
+        && options.exclude.is_empty()
+        && options.members.is_empty()
+        && !options.case_insensitive
     {
-        let pattern = &args.patterns[0];
+        let pattern = &options.patterns[0];
         if !pattern.contains('*') && !pattern.contains('?') {
             Some(pattern.as_str())
         } else {
@@ -282,49 +1217,84 @@ pub fn extract_to_pipe<R: Read + Seek>(archive: &mut ZipArchive<R>, args: &Args)
         None
     };
 
-    for i in 0..archive.len() {
-        let mut write_file =
-            |file: &mut zip::read::ZipFile, name_for_msg: Option<&str>| -> Result<()> {
+    for i in (0..archive.len()).filter(|&i| options.considers(i)) {
+        options.cancel.check()?;
+        let mut write_file = |file: &mut zip::read::ZipFile, name: &str| -> Result<()> {
+            if banners {
+                let action = if file.compression() == zip::CompressionMethod::Stored {
+                    " extracting:"
+                } else {
+                    "  inflating:"
+                };
+                writeln!(out, "{} {}", action, name)?;
+            }
+            let mut converter: Option<TextConverter> = None;
+            let mut converted = Vec::new();
+            let mut first = true;
+            let mut legacy_entry = None;
+            let data: &mut dyn Read = if decodes(file) {
+                legacy_entry.insert(LegacyEntry::new(file))
+            } else {
+                file
+            };
             loop {
-                let bytes_read = match file.read(&mut buffer) {
+                let bytes_read = match data.read(&mut buffer) {
                     Ok(0) => break,
                     Ok(n) => n,
-                    Err(e) => {
-                        let name = name_for_msg.unwrap_or_else(|| file.name());
-                        bail!("Failed to read {}: {}", name, e);
-                    },
+                    Err(e) => return Err(UnzipError::reading(name)(e)),
                 };
-                if let Err(e) = stdout_lock.write_all(&buffer[..bytes_read]) {
-                    let name = name_for_msg.unwrap_or_else(|| file.name());
-                    bail!("Failed to write {} to stdout: {}", name, e);
+                let chunk = &buffer[..bytes_read];
+                if first {
+                    first = false;
+                    if convert.unwrap_or_else(|| looks_like_text(chunk)) {
+                        converter = Some(TextConverter::new());
+                    }
+                }
+                match converter {
+                    Some(ref mut converter) => {
+                        converted.clear();
+                        converter.convert(chunk, &mut converted);
+                        out.write_all(&converted)?;
+                    },
+                    None => out.write_all(chunk)?,
                 }
             }
+            if let Some(ref mut converter) = converter {
+                converted.clear();
+                converter.finish(&mut converted);
+                out.write_all(&converted)?;
+            }
             Ok(())
         };
 
         let mut decrypt_name: Option<String> = None;
         let needs_decrypt = {
-            let file_result = archive.by_index(i);
+            let file_result = if decodes(&archive.by_index_raw(i)?) {
+                archive.by_index_raw(i)
+            } else {
+                archive.by_index(i)
+            };
             match file_result {
                 Ok(mut file) => {
                     if file.is_dir() {
                         continue;
                     }
 
-                    let name = file.name();
+                    let name = names.decode(&file);
                     if let Some(target) = exact_target {
                         if name != target {
                             continue;
                         }
-                    } else if use_filters && !matcher.should_extract(name) {
+                    } else if use_filters && !matcher.should_extract(&name) {
                         continue;
                     }
 
+                    let name = name.into_owned();
                     if file.encrypted() {
-                        decrypt_name = Some(file.name().to_string());
+                        decrypt_name = Some(name);
                         true
                     } else {
-                        write_file(&mut file, None)?;
+                        write_file(&mut file, &name)?;
                         continue;
                     }
                 },
@@ -333,7 +1303,7 @@ pub fn extract_to_pipe<R: Read + Seek>(archive: &mut ZipArchive<R>, args: &Args)
                     if is_password_error(&err_str) {
                         true
                     } else {
-                        bail!("Failed to read file: {}", err_str);
+                        return Err(e.into());
                     }
                 },
             }
@@ -346,7 +1316,7 @@ pub fn extract_to_pipe<R: Read + Seek>(archive: &mut ZipArchive<R>, args: &Args)
         let decrypt_label = decrypt_name.as_deref();
         let mut pwd = password.lock().unwrap();
         if pwd.is_none() {
-            if args.quiet == 0 {
+            if options.quiet == 0 {
                 if let Some(name) = decrypt_label {
                     eprintln!("Encrypted file detected: {}", name);
                 } else {
@@ -359,42 +1329,325 @@ pub fn extract_to_pipe<R: Read + Seek>(archive: &mut ZipArchive<R>, args: &Args)
         drop(pwd);
 
         if let Some(ref pwd) = pwd_bytes {
-            let mut file = archive
-                .by_index_decrypt(i, pwd)
-                .with_context(|| {
-                    if let Some(name) = decrypt_label {
-                        format!("Failed to decrypt {}", name)
-                    } else {
-                        "Failed to decrypt file".to_string()
-                    }
-                })?;
+            let mut file = archive.by_index_decrypt(i, pwd)?;
 
             if file.is_dir() {
                 continue;
             }
 
-            let name = file.name();
+            let name = names.decode(&file);
             if let Some(target) = exact_target {
                 if name != target {
                     continue;
                 }
-            } else if use_filters && !matcher.should_extract(name) {
+            } else if use_filters && !matcher.should_extract(&name) {
                 continue;
             }
 
-            write_file(&mut file, None)?;
+            let name = name.into_owned();
+            write_file(&mut file, &name)?;
         } else {
-            if let Some(name) = decrypt_label {
-                bail!("Password required but not available for file: {}", name);
-            } else {
-                bail!("Password required but not available");
-            }
+            return Err(UnzipError::PasswordRequired { name: decrypt_name });
+        }
+    }
+
+    Ok(())
+}
+
+/// Extract archive contents into memory, keyed by output path.
+///
+/// Applies the same filters (patterns, `-x`, `-C`) and name policy (sanitizing,
+/// `-j`, `-L`) as [`extract_archive`], plus the extraction limits, but returns the
+/// file contents instead of writing them. Keys are the relative output paths with
+/// `/` separators; directories and rejected names are left out, and later
+/// duplicates replace earlier ones. Useful for testing against archive fixtures
+/// without a temporary directory.
+///
+/// Encrypted entries are decrypted with the configured password; there is no prompt.
+///
+/// # Arguments
+///
+/// * `archive` - The ZIP archive to extract from
+/// * `options` - Options controlling which entries are extracted and how they are named
+///
+/// # Errors
+///
+/// Returns an error if an entry cannot be read or decrypted, or if the archive
+/// crosses an extraction limit
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{Cursor, Write};
+/// use zip::{ZipArchive, ZipWriter, write::SimpleFileOptions};
+/// use unzip::{ExtractOptions, extract_to_vec};
+///
+/// let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+/// zip.start_file("docs/readme.txt", SimpleFileOptions::default())?;
+/// zip.write_all(b"hello")?;
+/// zip.start_file("build.log", SimpleFileOptions::default())?;
+/// let mut archive = ZipArchive::new(zip.finish()?)?;
+///
+/// let files = extract_to_vec(&mut archive, &ExtractOptions::new().exclude(["*.log"]))?;
+/// assert_eq!(files.keys().collect::<Vec<_>>(), ["docs/readme.txt"]);
+/// assert_eq!(files["docs/readme.txt"], b"hello");
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn extract_to_vec<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    options: &ExtractOptions,
+) -> Result<BTreeMap<String, Vec<u8>>> {
+    let mut sink = MemorySink::new();
+    extract_to_sink(archive, options, &mut sink)?;
+    Ok(sink.into_files())
+}
+
+/// Extract archive contents into a map from output path to data, never
+/// touching the filesystem.
+///
+/// Entries are selected and named as [`extract_to_vec`] does them, for test
+/// frameworks and plugin systems that need the contents alone. As nothing
+/// stops memory filling up the way a full disk would, the data is capped at
+/// the options' `max_output_size`, or [`DEFAULT_MEMORY_LIMIT`] without one;
+/// `max_ratio` and `max_entries` apply as set.
+///
+/// # Errors
+///
+/// Returns [`LimitExceeded::MemoryOutput`] if the data grows beyond the default
+/// cap, another [`UnzipError::Limit`] for a limit the options set, and an error
+/// if an entry cannot be read or decrypted
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{Cursor, Write};
+/// use zip::{ZipArchive, ZipWriter, write::SimpleFileOptions};
+/// use unzip::limits::ExtractionLimits;
+/// use unzip::{ExtractOptions, UnzipError, extract_to_memory};
+///
+/// let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+/// zip.start_file("plugin/manifest.toml", SimpleFileOptions::default())?;
+/// zip.write_all(b"name = \"demo\"")?;
+/// let mut archive = ZipArchive::new(zip.finish()?)?;
+///
+/// let files = extract_to_memory(&mut archive, &ExtractOptions::new())?;
+/// assert_eq!(files["plugin/manifest.toml"], b"name = \"demo\"");
+///
+/// let tiny = ExtractionLimits { max_output_size: Some(4), ..Default::default() };
+/// let error = extract_to_memory(&mut archive, &ExtractOptions::new().limits(tiny));
+/// assert!(matches!(error, Err(UnzipError::Limit(_))));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn extract_to_memory<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    options: &ExtractOptions,
+) -> Result<HashMap<String, Vec<u8>>> {
+    if options.limits.max_output_size.is_some() {
+        return Ok(extract_to_vec(archive, options)?.into_iter().collect());
+    }
+    let mut capped = options.clone();
+    capped.limits.max_output_size = Some(DEFAULT_MEMORY_LIMIT);
+    match extract_to_vec(archive, &capped) {
+        Ok(files) => Ok(files.into_iter().collect()),
+        Err(UnzipError::Limit(LimitExceeded::OutputSize { .. })) => {
+            Err(LimitExceeded::MemoryOutput { limit: DEFAULT_MEMORY_LIMIT }.into())
+        },
+        Err(error) => Err(error),
+    }
+}
+
+/// Extract archive contents into `sink` instead of the filesystem.
+///
+/// Selects, names and reads entries as [`extract_archive`] does, with the same
+/// filters, name policy, password and extraction limits, and hands each one to
+/// the [`EntrySink`]: directories first as they come, then every file's data
+/// through the writer the sink returns, then the directories' metadata, deepest
+/// first. Rejected names are left out, as there is no prompt or report.
+///
+/// # Arguments
+///
+/// * `archive` - The ZIP archive to extract from
+/// * `options` - Options controlling which entries are extracted and how they are named
+/// * `sink` - Where the entries go
+///
+/// # Errors
+///
+/// Returns an error if an entry cannot be read or decrypted, if the archive
+/// crosses an extraction limit, or if the sink fails
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{Cursor, Write};
+/// use zip::{ZipArchive, ZipWriter, write::SimpleFileOptions};
+/// use unzip::{ExtractOptions, extract_to_sink};
+/// use unzip::sink::DirSink;
+///
+/// let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+/// zip.start_file("docs/readme.txt", SimpleFileOptions::default())?;
+/// zip.write_all(b"hello")?;
+/// let mut archive = ZipArchive::new(zip.finish()?)?;
+///
+/// let dir = tempfile::tempdir()?;
+/// extract_to_sink(&mut archive, &ExtractOptions::new(), &mut DirSink::new(dir.path())?)?;
+/// assert_eq!(std::fs::read(dir.path().join("docs/readme.txt"))?, b"hello");
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn extract_to_sink<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    options: &ExtractOptions,
+    sink: &mut dyn EntrySink,
+) -> Result<()> {
+    let limits = options.limits;
+    limits.check_archive(archive)?;
+    let budget = OutputBudget::new(limits);
+    let matcher = options.matcher();
+    let names = options.names.clone();
+    options.expect.check_archive(archive, &names, &matcher)?;
+    check_names(archive, &names)?;
+    check_members(archive, &names, &options.members)?;
+    check_member_index(archive, options.member_index)?;
+    check_opendoc(archive, options)?;
+    let (case_dirs, _) = scan_case_dirs(archive, &names, &matcher, options)?;
+    let mut directories = Vec::new();
+
+    for i in (0..archive.len()).filter(|&i| options.considers(i)) {
+        options.cancel.check()?;
+        let (mut file, legacy) = open_data(archive, i, options)?;
+        let name = names.decode(&file).into_owned();
+        let is_dir = file.is_dir();
+        if is_dir && (options.junk_paths || !matcher.should_extract_dir(&name)) {
+            continue;
+        }
+        let path = if is_dir {
+            entry_output_path(&name, options, &case_dirs)
+        } else {
+            let meta = EntryMeta::of(i, &name, &file);
+            let fields = EntryFields::of(i, &file);
+            selected_output_path(&meta, &fields, &matcher, options, &case_dirs)
+        };
+        let Ok(path) = path else {
+            continue;
+        };
+        let times = if options.no_timestamps >= 1 {
+            EntryTimes::default()
+        } else {
+            entry_times(&file, options)
+        };
+        let entry =
+            SinkEntry { path, name, size: file.size(), times, mode: file_mode(&file), is_dir };
+        if is_dir {
+            sink.create_dir(&entry)
+                .map_err(UnzipError::file("create directory", &entry.path))?;
+            directories.push(entry);
+            continue;
         }
+        let mut writer =
+            sink.create_file(&entry).map_err(UnzipError::file("create file", &entry.path))?;
+        copy_data(&mut file, legacy, &entry.name, &budget, &mut *writer, &entry.path)?;
+        drop(writer);
+        sink.set_metadata(&entry)
+            .map_err(UnzipError::file("set metadata on", &entry.path))?;
     }
 
+    directories.sort_by_key(|dir| std::cmp::Reverse(dir.path.components().count()));
+    for dir in &directories {
+        sink.set_metadata(dir).map_err(UnzipError::file("set metadata on", &dir.path))?;
+    }
     Ok(())
 }
 
+/// Read the data of the entry at `index` into memory, within the extraction
+/// limits of `options` and decrypted with its password
+///
+/// # Errors
+///
+/// Returns an error if the entry cannot be read or decrypted, or its data
+/// crosses an extraction limit
+pub(crate) fn read_entry_data<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    index: usize,
+    name: &str,
+    options: &ExtractOptions,
+) -> Result<Vec<u8>> {
+    let budget = OutputBudget::new(options.limits);
+    let (mut file, legacy) = open_data(archive, index, options)?;
+    read_data(&mut file, legacy, name, &budget)
+}
+
+/// Open the entry at `index` to read its data, decrypted with the password of
+/// `options` if it is encrypted, along with whether it has a legacy method that
+/// [`LegacyEntry`] decodes
+fn open_data<'a, R: Read + Seek>(
+    archive: &'a mut ZipArchive<R>,
+    index: usize,
+    options: &ExtractOptions,
+) -> Result<(ZipFile<'a>, bool)> {
+    let (encrypted, legacy) = {
+        let raw = archive.by_index_raw(index)?;
+        (raw.encrypted(), decodes(&raw))
+    };
+    let file = match options.password.as_deref() {
+        Some(password) if encrypted => archive.by_index_decrypt(index, password.as_bytes())?,
+        _ if legacy => archive.by_index_raw(index)?,
+        _ => archive.by_index(index)?,
+    };
+    Ok((file, legacy))
+}
+
+/// Read all of the entry `file`, named `name`, through `budget`
+fn read_data(
+    file: &mut ZipFile<'_>,
+    legacy: bool,
+    name: &str,
+    budget: &OutputBudget,
+) -> Result<Vec<u8>> {
+    let mut contents = Vec::with_capacity(file.size().min(BUFFER_SIZE as u64) as usize);
+    let compressed_size = file.compressed_size();
+    let mut legacy_entry = None;
+    let data: &mut dyn Read = if legacy {
+        legacy_entry.insert(LegacyEntry::new(file))
+    } else {
+        file
+    };
+    budget
+        .guard(data, name, compressed_size)
+        .read_to_end(&mut contents)
+        .map_err(UnzipError::reading(name))?;
+    Ok(contents)
+}
+
+/// Copy all of the entry `file`, named `name`, through `budget` to `out`,
+/// which writes the output path `path`
+fn copy_data(
+    file: &mut ZipFile<'_>,
+    legacy: bool,
+    name: &str,
+    budget: &OutputBudget,
+    out: &mut dyn Write,
+    path: &Path,
+) -> Result<()> {
+    let mut buffer = vec![0; file.size().clamp(1, BUFFER_SIZE as u64) as usize];
+    let compressed_size = file.compressed_size();
+    let mut legacy_entry = None;
+    let data: &mut dyn Read = if legacy {
+        legacy_entry.insert(LegacyEntry::new(file))
+    } else {
+        file
+    };
+    let mut data = budget.guard(data, name, compressed_size);
+    loop {
+        let read = match data.read(&mut buffer) {
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            read => read.map_err(UnzipError::reading(name))?,
+        };
+        if read == 0 {
+            return out.flush().map_err(UnzipError::file("write", path));
+        }
+        out.write_all(&buffer[..read]).map_err(UnzipError::file("write", path))?;
+    }
+}
+
 /// Extract archive contents to the filesystem with Linux optimizations.
 ///
 /// This is the main extraction function that handles all ZIP archive extraction with
@@ -404,12 +1657,12 @@ pub fn extract_to_pipe<R: Read + Seek>(archive: &mut ZipArchive<R>, args: &Args)
 /// # Arguments
 ///
 /// * `archive` - The ZIP archive to extract from
-/// * `args` - Command-line arguments controlling extraction behavior including:
+/// * `options` - Extraction options controlling behavior including:
 ///   - Output directory (`-d`)
-///   - Overwrite mode (`-o`, `-n`, `-f`, `-u`)
+///   - Overwrite policy (`-o`, `-n`, `-f`, `-u`)
 ///   - Pattern filters (include/exclude)
 ///   - Directory flattening (`-j`)
-///   - Quiet mode (`-q`)
+///   - Console output (`-q`) or an `on_event` callback
 ///
 /// # Errors
 ///
@@ -432,73 +1685,368 @@ pub fn extract_to_pipe<R: Read + Seek>(archive: &mut ZipArchive<R>, args: &Args)
 /// ```no_run
 /// use std::fs::File;
 /// use zip::ZipArchive;
-/// use unzip::{Args, extract_archive};
+/// use unzip::{Args, ExtractOptions, extract_archive};
 /// use clap::Parser;
 ///
 /// let file = File::open("archive.zip")?;
 /// let mut archive = ZipArchive::new(file)?;
-/// let args = Args::parse();
-/// extract_archive(&mut archive, &args)?;
+/// let options = ExtractOptions::try_from(&Args::parse())?;
+/// extract_archive(&mut archive, &options)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn extract_archive<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    options: &ExtractOptions,
+) -> Result<()> {
+    with_event_sink(options, |on_event| {
+        let journal = open_journal(options);
+        let checkpoint = Checkpoint::open(options)?;
+        let result = extract_stamping(options, on_event, |on_event| {
+            journal.finish(extract_recursing(options, &journal, on_event, |on_event| {
+                extract_archive_serial(
+                    archive,
+                    None,
+                    options,
+                    &journal,
+                    checkpoint.as_ref(),
+                    on_event,
+                )
+            }))
+        });
+        finish_checkpoint(checkpoint, result, on_event)
+    })?;
+    Ok(())
+}
+
+/// Extract the single entry at `index` in the central directory, counting from 0
+///
+/// The entry is chosen by position alone, so a caller that already listed the
+/// archive gets exactly that entry even when others share its name. It is
+/// otherwise extracted as [`extract_archive`] would; this is the same as setting
+/// [`ExtractOptions::member_index`].
+///
+/// `index` counts the entries `archive` holds, in which only the last of the
+/// entries sharing a name remains (see [`crate::duplicates`]).
+///
+/// # Errors
+///
+/// Returns [`UnzipError::NoSuchEntry`] if the archive has no entry at `index`,
+/// and otherwise fails as [`extract_archive`] does
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use zip::ZipArchive;
+/// use unzip::{ExtractOptions, extract_entry_by_index};
+///
+/// let mut archive = ZipArchive::new(File::open("archive.zip")?)?;
+/// extract_entry_by_index(&mut archive, 3, &ExtractOptions::new().output_dir("out"))?;
 /// # Ok::<(), Box<dyn std::error::Error>>(())
 /// ```
-pub fn extract_archive<R: Read + Seek>(archive: &mut ZipArchive<R>, args: &Args) -> Result<()> {
-    extract_archive_serial(archive, args)
+pub fn extract_entry_by_index<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    index: usize,
+    options: &ExtractOptions,
+) -> Result<()> {
+    extract_archive(archive, &options.clone().member_index(Some(index)))
 }
 
-fn extract_archive_serial<R: Read + Seek>(archive: &mut ZipArchive<R>, args: &Args) -> Result<()> {
-    let output_dir = args.output_dir.clone().unwrap_or_else(|| PathBuf::from("."));
+/// Save or remove the checkpoint of a run once it ended with `result`
+fn finish_checkpoint<T>(
+    checkpoint: Option<Checkpoint>,
+    result: Result<T>,
+    on_event: &mut dyn FnMut(ExtractEvent),
+) -> Result<T> {
+    match checkpoint {
+        Some(checkpoint) => checkpoint.finish(result, on_event),
+        None => result,
+    }
+}
 
-    if !output_dir.exists() {
-        fs::create_dir_all(&output_dir).with_context(|| {
-            format!("Failed to create output directory: {}", output_dir.display())
-        })?;
+/// Journal of the run's changes, recording only with `--transactional`
+fn open_journal(options: &ExtractOptions) -> Journal {
+    Journal::new(options.transactional)
+}
+
+/// Staging directory for a `--transactional` run into `output_dir`
+fn open_staging(options: &ExtractOptions, output_dir: &Path) -> Result<Option<Staging>> {
+    options.transactional.then(|| Staging::create(output_dir)).transpose()
+}
+
+/// Where to write the file or directory for `outpath`: its place in the staging
+/// directory of a transactional run, or `outpath` itself
+fn write_path(staging: Option<&Staging>, outpath: &Path) -> PathBuf {
+    staging.map_or_else(|| outpath.to_path_buf(), |staging| staging.path_for(outpath))
+}
+
+/// Create the file an entry is extracted to; with `--shared-dest` it is a
+/// temporary file that must not exist yet
+fn create_output_file(destination: &Destination, path: &Path, exclusive: bool) -> Result<File> {
+    let created = if exclusive {
+        destination.create_new_file(path)
+    } else {
+        destination.create_file(path)
+    };
+    created.map_err(UnzipError::file("create file", path))
+}
+
+/// With `--patch-in-place`, the existing file at `write_path` opened to be
+/// patched, if the entry is written over it `in_place`, unconverted and
+/// uncompressed, and it already has the entry's `size`; `None` to create the
+/// file as usual
+fn open_patched_file(
+    destination: &Destination,
+    write_path: &Path,
+    in_place: bool,
+    convert: Option<bool>,
+    size: u64,
+    options: &ExtractOptions,
+) -> Option<File> {
+    if !options.patch_in_place
+        || !in_place
+        || convert != Some(false)
+        || options.compress_output.is_some()
+    {
+        return None;
+    }
+    let same_size = fs::symlink_metadata(write_path)
+        .is_ok_and(|meta| meta.file_type().is_file() && meta.len() == size);
+    same_size.then(|| destination.open_existing_file(write_path).ok()).flatten()
+}
+
+/// Whether `outpath` is a read-only file the entry would be written over
+/// `in_place` (not through a rename), and must be left alone without
+/// `--force-readonly`
+fn refuses_read_only(outpath: &Path, in_place: bool, options: &ExtractOptions) -> bool {
+    in_place && !options.force_readonly && is_read_only(outpath)
+}
+
+/// With `--force-readonly`, make a read-only file the entry is written over
+/// `in_place` writable, returning the permissions to give it back once replaced
+fn unlock_read_only(
+    outpath: &Path,
+    in_place: bool,
+    options: &ExtractOptions,
+) -> Result<Option<Permissions>> {
+    if !in_place || !options.force_readonly {
+        return Ok(None);
+    }
+    unlock(outpath).map_err(UnzipError::file("make writable", outpath))
+}
+
+/// Move a transactional run's files into place, before directory timestamps are
+/// restored
+fn commit_staging(
+    staging: Option<Staging>,
+    journal: &Journal,
+    options: &ExtractOptions,
+) -> Result<()> {
+    match staging {
+        Some(staging) => staging.commit(journal, options.follow_symlinks),
+        None => Ok(()),
+    }
+}
+
+/// Write new `--flatten-long-paths` mappings to the manifest, journaling it like
+/// extracted files when it is replaced
+fn write_manifest(store: &mut LongPathStore, journal: &Journal) -> Result<()> {
+    if !store.has_unwritten() {
+        return Ok(());
+    }
+    if store.replaces_manifest() {
+        let manifest = store.manifest_path();
+        if let Some(store_dir) = manifest.parent() {
+            journal.record_dirs(store_dir);
+        }
+        journal
+            .prepare_file(&manifest)
+            .map_err(UnzipError::file("back up file", &manifest))?;
+    }
+    store.write_manifest()
+}
+
+/// Run an extraction with its events going to the configured observer, or to the
+/// console reporter when none is set, and to the status board if there is one
+fn with_event_sink<T>(
+    options: &ExtractOptions,
+    run: impl FnOnce(&mut dyn FnMut(ExtractEvent)) -> Result<T>,
+) -> Result<T> {
+    let mut status = options.status.clone();
+    let mut record = |event: &ExtractEvent| {
+        if let Some(status) = status.as_mut() {
+            status.on_event(event);
+        }
+        if let Some(hooks) = &options.hooks {
+            match event {
+                ExtractEvent::FileDone { name, path, bytes } => {
+                    hooks.after_entry(name, &EntryOutcome::Extracted { path, bytes: *bytes })
+                },
+                ExtractEvent::Skipped { name, reason } => {
+                    hooks.after_entry(name, &EntryOutcome::Skipped(*reason))
+                },
+                _ => {},
+            }
+        }
+    };
+    match &options.observer {
+        Some(observer) => {
+            let mut observer = observer.lock().unwrap_or_else(PoisonError::into_inner);
+            run(&mut |event| {
+                record(&event);
+                observer.on_event(&event)
+            })
+        },
+        None => {
+            let mut reporter = ConsoleReporter::new(options);
+            let result = run(&mut |event| {
+                record(&event);
+                reporter.on_event(&event)
+            })?;
+            // A reader that stopped reading the report, like `head`, ends it quietly
+            stopped_reading(reporter.stdout_result().map_err(Into::into))?;
+            Ok(result)
+        },
     }
+}
+
+/// Extract entries one by one on the calling thread
+///
+/// `source` is the archive's origin when known; `-a` uses it to read the entries'
+/// text flags and otherwise guesses them from content, and entries of a
+/// memory-mapped archive are decoded from their slices of the mapping. With `--transactional`,
+/// entries are staged and moved into place at the end, recording the move in
+/// `journal`.
+fn extract_archive_serial<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    source: Option<&ArchiveSource>,
+    options: &ExtractOptions,
+    journal: &Journal,
+    checkpoint: Option<&Checkpoint>,
+    on_event: &mut dyn FnMut(ExtractEvent),
+) -> Result<ExtractReport> {
+    let limits = options.limits;
+    limits.check_archive(archive)?;
+    let index = index_mapped(archive, source)?;
+    let budget = OutputBudget::new(limits);
+    let matcher = options.matcher();
+    let names = options.names.clone();
+    options.expect.check_archive(archive, &names, &matcher)?;
+    check_names(archive, &names)?;
+    check_members(archive, &names, &options.members)?;
+    check_member_index(archive, options.member_index)?;
+    check_opendoc(archive, options)?;
+    let (case_dirs, case_events) = scan_case_dirs(archive, &names, &matcher, options)?;
+
+    let output_dir = smart_output_dir(archive, options, &names, &matcher, &case_dirs)?;
+    check_self_overwrite(archive, options, &names, &matcher, &case_dirs, &output_dir)?;
+
+    let created_output = missing_dirs(&output_dir);
+    // `None` in a dry run, which must not even create the output directory
+    let destination = if options.dry_run {
+        None
+    } else {
+        if !output_dir.exists() {
+            journal.record_dirs(&output_dir);
+            fs::create_dir_all(&output_dir)
+                .map_err(UnzipError::file("create output directory", &output_dir))?;
+        }
+        let destination = Destination::open(&output_dir, options.follow_symlinks)
+            .map_err(UnzipError::file("open output directory", &output_dir))?;
+        Some(destination)
+    };
+    let staging = match destination {
+        Some(_) => open_staging(options, &output_dir)?,
+        None => None,
+    };
 
     let total_files = archive.len();
     let mut extracted = 0usize;
     let mut skipped = 0usize;
+    let mut infected = 0usize;
+    let mut rejected = 0usize;
     let mut total_bytes = 0u64;
 
-    let password = Mutex::new(get_password(args.password.as_deref(), args.quiet)?);
+    let password = Mutex::new(get_password(options.password.as_deref(), options.quiet)?);
 
-    let progress_bar = if args.quiet == 0 {
-        let pb = ProgressBar::new(total_files as u64);
-        pb.set_style(
-            ProgressStyle::default_bar()
-                .template(
-                    "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})",
-                )?
-                .progress_chars("#>-"),
-        );
-        Some(pb)
-    } else {
-        None
+    // Track directories for timestamp restoration after extraction
+    let mut directories: Vec<DirectoryMeta> = Vec::new();
+    let text_mode = options.text_mode;
+    let text_flags = match source {
+        Some(source) if text_mode == TextMode::Auto => Some(load_text_flags(archive, source)?),
+        _ => None,
     };
+    let mut long_paths = options.flatten_long_paths.then(|| LongPathStore::new(&output_dir));
+    let mut prompt = options.prompts_overwrite().then(OverwritePrompt::default);
+
+    // Entries already up to date are reported before extraction starts so the
+    // progress bar only covers real work
+    let mut prefiltered = vec![false; total_files];
+    if options.overwrite.compares_times() {
+        let up_to_date = prefilter_up_to_date(
+            archive,
+            options,
+            &names,
+            &matcher,
+            &case_dirs,
+            &output_dir,
+            long_paths.as_ref(),
+        )?;
+        for (i, name) in up_to_date {
+            prefiltered[i] = true;
+            skipped += 1;
+            on_event(ExtractEvent::Skipped { name, reason: SkipReason::UpToDate });
+        }
+    }
+    if let Some(checkpoint) = checkpoint {
+        for event in prefilter_resumed(archive, options, checkpoint, &mut prefiltered)? {
+            skipped += usize::from(matches!(event, ExtractEvent::Skipped { .. }));
+            on_event(event);
+        }
+    }
 
-    // Track directories for timestamp restoration after extraction
-    let mut directories: Vec<(PathBuf, Option<zip::DateTime>)> = Vec::new();
-    let matcher = PatternMatcher::new(&args.patterns, &args.exclude, args.case_insensitive);
+    on_event(ExtractEvent::Started {
+        total_entries: options.considered_entries(total_files) - skipped,
+        output_dir: output_dir.clone(),
+    });
+    for event in case_events {
+        on_event(event);
+    }
 
     let mut buffer = vec![0u8; BUFFER_SIZE];
 
-    'main_loop: for i in 0..total_files {
-        let result = archive.by_index(i);
-        let mut file = if let Ok(f) = result {
-            f
-        } else {
-            let err_str = result.as_ref().err().unwrap().to_string();
-            let is_pwd_error = is_password_error(&err_str);
-            drop(result);
+    for i in entry_order(archive, options)? {
+        options.cancel.check()?;
+        if prefiltered[i] {
+            continue;
+        }
+        let sliced = index.as_ref().is_some_and(|index| index.slices(i));
+        let legacy = decodes(&archive.by_index_raw(i)?);
+        // Scoped so that `result` does not keep `archive` borrowed past `file`
+        let mut file = {
+            // A dry run only needs metadata, so encrypted entries need no password;
+            // neither do entries decoded from their slice of the mapping. Entries
+            // with legacy methods are decoded here, from their raw data
+            let result = if options.dry_run || sliced || legacy {
+                archive.by_index_raw(i)
+            } else {
+                archive.by_index(i)
+            };
+            if let Ok(f) = result {
+                f
+            } else {
+                let err = result.err().unwrap();
+                if !is_password_error(&err.to_string()) {
+                    return Err(err.into());
+                }
 
-            if is_pwd_error {
+                let name = archive.name_for_index(i).unwrap_or_default().to_string();
                 let mut pwd = password.lock().unwrap();
                 if pwd.is_none() {
-                    if args.quiet == 0 {
-                        if let Some(ref pb) = progress_bar {
-                            pb.println("Encrypted file detected".to_string());
-                        } else {
-                            eprintln!("Encrypted file detected");
-                        }
+                    if options.quiet == 0 {
+                        on_event(ExtractEvent::Warning {
+                            message: "Encrypted file detected".to_string(),
+                        });
                     }
                     *pwd = Some(prompt_for_password()?);
                 }
@@ -509,139 +2057,200 @@ fn extract_archive_serial<R: Read + Seek>(archive: &mut ZipArchive<R>, args: &Ar
                     match archive.by_index_decrypt(i, pwd) {
                         Ok(f) => f,
                         Err(_e) => {
-                            if args.quiet < 2 {
-                                if let Some(ref pb) = progress_bar {
-                                    pb.println("    error: Invalid password".to_string());
-                                } else {
-                                    eprintln!("error: Invalid password");
-                                }
-                            }
-                            if let Some(ref pb) = progress_bar {
-                                pb.inc(1);
-                            }
                             skipped += 1;
-                            continue 'main_loop;
+                            on_event(ExtractEvent::Skipped {
+                                name,
+                                reason: SkipReason::InvalidPassword,
+                            });
+                            continue;
                         },
                     }
                 } else {
-                    if args.quiet < 2 {
-                        if let Some(ref pb) = progress_bar {
-                            pb.println("    error: Password required".to_string());
-                        } else {
-                            eprintln!("error: Password required");
-                        }
-                    }
-                    if let Some(ref pb) = progress_bar {
-                        pb.inc(1);
-                    }
                     skipped += 1;
-                    continue 'main_loop;
+                    on_event(ExtractEvent::Skipped { name, reason: SkipReason::PasswordRequired });
+                    continue;
                 }
-            } else {
-                bail!("Failed to read file: {}", err_str);
             }
         };
 
-        let name = file.name().to_string();
-        let mtime = file.last_modified();
+        let name = names.decode(&file).into_owned();
+        let times = entry_times(&file, options);
         let size = file.size();
+        let crc32 = file.crc32();
         let is_dir = file.is_dir();
 
         if is_dir {
-            if !args.junk_paths {
-                let dir_name = if args.lowercase {
-                    name.to_lowercase()
-                } else {
-                    name.clone()
+            if options.junk_paths {
+                on_event(ExtractEvent::Skipped { name, reason: SkipReason::JunkedDirectory });
+            } else if !matcher.should_extract_dir(&name) {
+                skipped += 1;
+                on_event(ExtractEvent::Skipped { name, reason: SkipReason::Filtered });
+            } else {
+                let outpath = match entry_output_path(&name, options, &case_dirs) {
+                    Ok(relative) => output_dir.join(relative),
+                    Err(reason) => {
+                        if reason == SkipReason::Stripped {
+                            skipped += 1;
+                        } else {
+                            rejected += 1;
+                        }
+                        on_event(ExtractEvent::Skipped { name, reason });
+                        continue;
+                    },
                 };
-                let outpath = output_dir.join(&dir_name);
-                fs::create_dir_all(&outpath)
-                    .with_context(|| format!("Failed to create directory: {}", outpath.display()))?;
-                directories.push((outpath, mtime));
-            }
-            if let Some(ref pb) = progress_bar {
-                pb.inc(1);
+                if let Some(ref store) = long_paths
+                    && store.exceeds_limits(&outpath)
+                {
+                    on_event(ExtractEvent::Skipped { name, reason: SkipReason::PathTooLong });
+                    continue;
+                }
+                let Some(ref destination) = destination else {
+                    let action = PlannedAction::CreateDirectory;
+                    on_event(ExtractEvent::Planned { name, action });
+                    continue;
+                };
+                let write_path = write_path(staging.as_ref(), &outpath);
+                destination
+                    .create_dir_all(&write_path)
+                    .map_err(UnzipError::file("create directory", &write_path))?;
+                directories.push(DirectoryMeta {
+                    path: outpath.clone(),
+                    times,
+                    unix_mode: file_mode(&file),
+                });
+                on_event(ExtractEvent::DirCreated { path: outpath });
             }
             continue;
         }
 
-        if !matcher.should_extract(&name) {
-            if let Some(ref pb) = progress_bar {
-                pb.inc(1);
+        let fields = EntryFields::of(i, &file);
+        let meta = EntryMeta::of(i, &name, &file);
+        let outpath = match selected_output_path(&meta, &fields, &matcher, options, &case_dirs) {
+            Ok(relative) => output_dir.join(relative),
+            Err(reason) => {
+                if reason.is_selection() {
+                    skipped += 1;
+                } else {
+                    rejected += 1;
+                }
+                on_event(ExtractEvent::Skipped { name, reason });
+                continue;
+            },
+        };
+        if options.dirs_only {
+            if let Some(ref destination) = destination
+                && let Some(parent) = outpath.parent()
+                && !long_paths.as_ref().is_some_and(|store| store.exceeds_limits(parent))
+            {
+                let write_path = write_path(staging.as_ref(), parent);
+                destination
+                    .create_dir_all(&write_path)
+                    .map_err(UnzipError::file("create directory", &write_path))?;
             }
             skipped += 1;
+            on_event(ExtractEvent::Skipped { name, reason: SkipReason::DirsOnly });
             continue;
         }
+        let outpath = match long_paths.as_mut().and_then(|store| store.flatten(&outpath)) {
+            Some(flat) => {
+                on_event(ExtractEvent::Warning {
+                    message: format!("   flattened: {} -> {}", name, flat.display()),
+                });
+                // Record the mapping before the file it describes is stored
+                if let Some(ref mut store) = long_paths
+                    && destination.is_some()
+                {
+                    write_manifest(store, journal)?;
+                }
+                flat
+            },
+            None => outpath,
+        };
+        let outpath = output_path(outpath, options.compress_output);
+        let mut write_path = write_path(staging.as_ref(), &outpath);
 
-        let outpath = if args.junk_paths {
-            let filename = std::path::Path::new(&name)
-                .file_name()
-                .map(|s| s.to_string_lossy().to_string())
-                .unwrap_or_else(|| name.clone());
-            let filename = if args.lowercase {
-                filename.to_lowercase()
-            } else {
-                filename
-            };
-            output_dir.join(filename)
-        } else {
-            let name_out = if args.lowercase {
-                name.to_lowercase()
-            } else {
-                name.clone()
-            };
-            match file.enclosed_name() {
-                Some(_) => output_dir.join(&name_out),
-                None => {
-                    if let Some(ref pb) = progress_bar {
-                        pb.inc(1);
-                    }
-                    continue;
-                },
-            }
-        };
-
-        if let Some(parent) = outpath.parent()
+        if let Some(ref destination) = destination
+            && let Some(parent) = write_path.parent()
             && !parent.exists()
         {
-            fs::create_dir_all(parent)
-                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+            destination
+                .create_dir_all(parent)
+                .map_err(UnzipError::file("create directory", parent))?;
         }
 
-        let decision = should_overwrite_file(&outpath, args, mtime);
+        let mut decision = decide_overwrite(&outpath, options, times.modified, size, crc32);
+        let mut replace = options.overwrite.replaces_existing();
+        let mut outpath = outpath;
+        if decision == OverwriteDecision::Skip
+            && let Some(ref mut prompt) = prompt
+        {
+            match prompt.ask(&outpath)? {
+                Reply::Overwrite => {
+                    decision = OverwriteDecision::Overwrite;
+                    replace = true;
+                },
+                Reply::Skip => {},
+                Reply::Rename(renamed) => {
+                    write_path = self::write_path(staging.as_ref(), &renamed);
+                    outpath = renamed;
+                    decision = OverwriteDecision::Overwrite;
+                },
+            }
+        }
+        if decision == OverwriteDecision::Skip && options.overwrite == OverwritePolicy::Rename {
+            // A name staged earlier in this run is taken too, though not yet in place
+            let renamed = free_path(&outpath, |path| {
+                path.exists() || self::write_path(staging.as_ref(), path).exists()
+            });
+            on_event(ExtractEvent::Warning {
+                message: format!("   renamed: {} -> {}", name, renamed.display()),
+            });
+            write_path = self::write_path(staging.as_ref(), &renamed);
+            outpath = renamed;
+            decision = OverwriteDecision::Overwrite;
+        }
 
         match decision {
             OverwriteDecision::Skip => {
-                if args.quiet == 0
-                    && let Some(ref pb) = progress_bar
-                {
-                    let msg = if args.never_overwrite {
-                        format!("    skipping: {} (already exists)", name)
-                    } else {
-                        format!("    skipping: {} (use -o to overwrite)", name)
-                    };
-                    pb.println(msg);
-                }
-                if let Some(ref pb) = progress_bar {
-                    pb.inc(1);
-                }
                 skipped += 1;
+                on_event(ExtractEvent::Skipped { name, reason: SkipReason::Exists });
                 continue;
             },
             OverwriteDecision::SkipQuietly => {
-                if let Some(ref pb) = progress_bar {
-                    pb.inc(1);
-                }
                 skipped += 1;
+                on_event(ExtractEvent::Skipped { name, reason: SkipReason::UpToDate });
+                continue;
+            },
+            OverwriteDecision::Unchanged => {
+                skipped += 1;
+                on_event(ExtractEvent::Skipped { name, reason: SkipReason::Unchanged });
                 continue;
             },
             OverwriteDecision::Overwrite => {},
         }
+        let in_place = write_path == outpath && !options.writes_pending();
+        if refuses_read_only(&outpath, in_place, options) {
+            skipped += 1;
+            on_event(ExtractEvent::Skipped { name, reason: SkipReason::ReadOnly });
+            continue;
+        }
+
+        let Some(ref destination) = destination else {
+            let action = if outpath.exists() {
+                PlannedAction::Overwrite
+            } else {
+                PlannedAction::Extract
+            };
+            extracted += 1;
+            total_bytes += size;
+            on_event(ExtractEvent::Planned { name, action });
+            continue;
+        };
 
         let unix_mode = {
             #[cfg(unix)]
             {
-                file.unix_mode()
+                file_mode(&file)
             }
             #[cfg(not(unix))]
             {
@@ -649,231 +2258,668 @@ fn extract_archive_serial<R: Read + Seek>(archive: &mut ZipArchive<R>, args: &Ar
             }
         };
 
-        extract_single_file(&mut file, &outpath, &mut buffer)?;
+        let pending = options.writes_pending().then(|| PendingFile::new(&write_path));
+        if let Some(ref pending) = pending {
+            write_path = pending.path().to_path_buf();
+        }
 
-        finalize_extracted_file(&outpath, mtime, unix_mode, args.no_timestamps);
+        let text_flag = text_flags.as_ref().map(|flags| flags[i]);
+        let unlocked = unlock_read_only(&outpath, in_place, options)?;
+        on_event(ExtractEvent::EntryStarted { name: name.clone(), size });
+        let mut scan = open_scan(options)?;
+        let convert = text_mode.converts(text_flag);
+        let patched = open_patched_file(destination, &write_path, in_place, convert, size, options);
+        let patch = patched.is_some();
+        let outfile = match patched {
+            Some(outfile) => outfile,
+            None => create_output_file(destination, &write_path, pending.is_some())?,
+        };
+        let expected_size = options
+            .expected_sizes
+            .as_ref()
+            .map(|sizes| sizes.expected(file.header_start(), size));
+        let written = {
+            let mut slice = index
+                .as_ref()
+                .filter(|_| sliced)
+                .zip(source.and_then(ArchiveSource::mapped))
+                .and_then(|(index, bytes)| index.open(bytes, i, &name));
+            let mut legacy_entry = None;
+            let entry: &mut dyn EntryData = match slice {
+                Some(ref mut slice) => slice,
+                None if legacy => legacy_entry.insert(LegacyEntry::new(&mut file)),
+                None => &mut file,
+            };
+            extract_single_file(
+                entry,
+                &outfile,
+                &mut buffer,
+                EntryWrite {
+                    convert,
+                    verify: options.verify,
+                    compress: options.compress_output,
+                    patch,
+                    expected_size,
+                },
+                &budget,
+                scan.as_mut(),
+                &mut |bytes| {
+                    on_event(ExtractEvent::BytesWritten { name: name.clone(), bytes });
+                    options.cancel.check_within_entry()
+                },
+            )
+        };
+        let written = match written {
+            Err(error) if options.heuristic_decode && is_decode_error(&error) => {
+                let crc32 = file.crc32();
+                drop(file);
+                let raw = archive.by_index_raw(i);
+                let relabel = recover_entry(raw, &write_path, destination, error)?;
+                scan = None;
+                on_event(ExtractEvent::Warning {
+                    message: format!("   recovered: {} ({})", name, relabel),
+                });
+                Written { bytes: size, crc32: options.verify.then_some(crc32) }
+            },
+            written => written.inspect_err(|e| remove_unfinished(e, &write_path, options))?,
+        };
+        if let Some(crc32) = written.crc32 {
+            verify_file(&write_path, crc32)?;
+        }
+        if let Some(message) = check_scan(scan, &write_path, &outpath, &name, options)? {
+            skipped += 1;
+            infected += 1;
+            on_event(ExtractEvent::Warning { message });
+            on_event(ExtractEvent::Skipped { name, reason: SkipReason::Infected });
+            continue;
+        }
+        let bytes = written.bytes;
 
-        if args.quiet == 0
-            && let Some(ref pb) = progress_bar
-        {
-            pb.println(format!("  extracting: {}", name));
+        apply_owner(&write_path, options)?;
+        finalize_extracted_file(&write_path, times, unix_mode, options.no_timestamps >= 2);
+        if let Some(permissions) = unlocked {
+            fs::set_permissions(&write_path, permissions)
+                .map_err(UnzipError::file("restore permissions of", &write_path))?;
+        }
+        if let Some(pending) = pending {
+            // Only `--shared-dest` leaves a file placed by another process alone
+            let placed = pending
+                .place(&outpath, replace || !options.shared_dest, size, crc32)
+                .map_err(UnzipError::file("move into place", &outpath))?;
+            if let Some(reason) = placed.skip_reason() {
+                skipped += 1;
+                on_event(ExtractEvent::Skipped { name, reason });
+                continue;
+            }
         }
 
         extracted += 1;
         total_bytes += size;
 
-        if let Some(ref pb) = progress_bar {
-            pb.inc(1);
+        // Linux optimization: tell kernel we're done with this file's cache
+        if options.cache.drops(total_bytes) {
+            fadvise_dontneed(&outfile, 0, size);
         }
-    }
 
-    // Restore directory timestamps after all files extracted
-    // This must be done last because extracting files updates directory mtimes
-    if !args.no_timestamps {
-        for (dir_path, mtime) in directories.iter().rev() {
-            if let Some(dt) = mtime {
-                let filetime_mtime = datetime_to_filetime(*dt);
-                filetime::set_file_mtime(dir_path, filetime_mtime).ok();
-            }
+        if let Some(checkpoint) = checkpoint {
+            checkpoint.record(i, crc32, size)?;
         }
+        on_event(ExtractEvent::FileDone { name, path: outpath, bytes });
     }
 
-    if let Some(pb) = progress_bar {
-        pb.finish_and_clear();
+    commit_staging(staging, journal, options)?;
+
+    if let Some(ref destination) = destination {
+        chown_directories(created_output, destination, &directories, options)?;
+    }
+    // Restore directory metadata after all files extracted
+    // This must be done last because extracting files updates directory mtimes
+    finalize_directories(&mut directories, options.no_timestamps);
+    if let Some(ref destination) = destination {
+        stamp_created_dirs(destination, &directories, options)?;
     }
 
-    if args.quiet == 0 {
-        println!(
-            "Extracted {} files ({}) to {}",
-            extracted,
-            format_size(total_bytes),
-            output_dir.display()
-        );
-        if skipped > 0 {
-            println!("Skipped {} files", skipped);
-        }
+    let report = ExtractReport { extracted, skipped, rejected, bytes: total_bytes, output_dir };
+    on_event(ExtractEvent::Finished { report: report.clone() });
+
+    if infected > 0 {
+        return Err(UnzipError::Infected { entries: infected });
     }
+    Ok(report)
+}
 
+/// Extract an archive using multiple worker threads when it pays off.
+///
+/// Falls back to serial extraction when output is not quiet (so per-file messages
+/// stay ordered), when a single thread is requested, or when encrypted entries are
+/// present without a password.
+///
+/// # Arguments
+///
+/// * `source` - The archive to extract, either by path or as a shared memory map
+/// * `options` - Extraction options controlling behavior
+///
+/// # Errors
+///
+/// Returns an error if the archive cannot be opened or any entry fails to extract
+pub fn extract_archive_threaded(source: ArchiveSource, options: &ExtractOptions) -> Result<()> {
+    with_event_sink(options, |on_event| extract_archive_threaded_with(source, options, on_event))?;
     Ok(())
 }
 
-pub fn extract_archive_threaded(source: ArchiveSource, args: &Args) -> Result<()> {
-    let output_dir = args.output_dir.clone().unwrap_or_else(|| PathBuf::from("."));
+pub(crate) fn extract_archive_threaded_with(
+    source: ArchiveSource,
+    options: &ExtractOptions,
+    on_event: &mut dyn FnMut(ExtractEvent),
+) -> Result<ExtractReport> {
+    let journal = open_journal(options);
+    let checkpoint = Checkpoint::open(options)?;
+    let result = extract_stamping(options, on_event, |on_event| {
+        journal.finish(extract_recursing(options, &journal, on_event, |on_event| {
+            extract_threaded_journaled(source, options, &journal, checkpoint.as_ref(), on_event)
+        }))
+    });
+    finish_checkpoint(checkpoint, result, on_event)
+}
 
-    if !output_dir.exists() {
-        fs::create_dir_all(&output_dir).with_context(|| {
-            format!("Failed to create output directory: {}", output_dir.display())
-        })?;
+/// Run `extract`, then give the output directory the time `--stamp-output-dir`
+/// asks for (see [`crate::dirstamp`])
+fn extract_stamping(
+    options: &ExtractOptions,
+    on_event: &mut dyn FnMut(ExtractEvent),
+    extract: impl FnOnce(&mut dyn FnMut(ExtractEvent)) -> Result<ExtractReport>,
+) -> Result<ExtractReport> {
+    let Some(stamp) = options.stamp_output_dir.filter(|_| !options.dry_run) else {
+        return extract(on_event);
+    };
+    let mut stamper = DirStamper::new(stamp);
+    let report = extract(&mut |event| {
+        if let ExtractEvent::FileDone { path, .. } = &event {
+            stamper.file_written(path);
+        }
+        on_event(event);
+    })?;
+    stamper.stamp(&report.output_dir)?;
+    Ok(report)
+}
+
+/// Run `extract`, then extract the archives it wrote, level by level up to
+/// `--recurse-archives` levels deep (see [`crate::nested`])
+///
+/// Nested archives go through `journal` too, so a transactional run undoes them
+/// along with the rest. Files named `.zip` that do not open as archives are
+/// reported and left alone.
+fn extract_recursing(
+    options: &ExtractOptions,
+    journal: &Journal,
+    on_event: &mut dyn FnMut(ExtractEvent),
+    extract: impl FnOnce(&mut dyn FnMut(ExtractEvent)) -> Result<ExtractReport>,
+) -> Result<ExtractReport> {
+    if options.recurse_archives == 0 {
+        return extract(on_event);
+    }
+    let mut pending = Vec::new();
+    let report = extract(&mut |event| {
+        note_archive(&event, &mut pending);
+        on_event(event);
+    })?;
+
+    let archive_bytes = pending.iter().filter_map(|path| fs::metadata(path).ok()).map(|m| m.len());
+    let max_output_size = options.limits.max_output_size;
+    let mut budget = NestedBudget::new(max_output_size, report.bytes, archive_bytes.sum());
+    for _ in 0..options.recurse_archives {
+        let mut found = Vec::new();
+        for path in pending {
+            if File::open(&path).map_or(true, |file| ZipArchive::new(file).is_err()) {
+                on_event(ExtractEvent::Warning {
+                    message: format!(
+                        "    warning: {} is not an archive, left as is",
+                        path.display()
+                    ),
+                });
+                continue;
+            }
+            let nested = nested_options(options, &path, budget.remaining());
+            if options.quiet == 0 {
+                on_event(ExtractEvent::Warning {
+                    message: format!(
+                        "  recursing: {} -> {}",
+                        path.display(),
+                        nested.output_dir.display()
+                    ),
+                });
+            }
+            let source = ArchiveSource::FilePath(path);
+            let nested_report =
+                extract_threaded_journaled(source, &nested, journal, None, &mut |event| {
+                    note_archive(&event, &mut found);
+                    on_event(event);
+                })
+                .map_err(|error| budget.explain(error, max_output_size))?;
+            budget.spend(nested_report.bytes);
+        }
+        pending = found;
     }
+    for path in pending {
+        on_event(ExtractEvent::Warning {
+            message: format!(
+                "    warning: {} not extracted, deeper than --recurse-archives={}",
+                path.display(),
+                options.recurse_archives
+            ),
+        });
+    }
+    Ok(report)
+}
+
+/// Remember the file an event reports written if it is an archive to recurse into
+fn note_archive(event: &ExtractEvent, found: &mut Vec<PathBuf>) {
+    if let ExtractEvent::FileDone { path, .. } = event
+        && is_archive_path(path)
+    {
+        found.push(path.clone());
+    }
+}
+
+/// Options for extracting the nested archive at `path` into its own directory,
+/// allowed to write `remaining` bytes
+///
+/// Patterns, expectations, `--opendoc-check` and the path options that rename
+/// entries describe the outermost archive only; limits apply to every level.
+fn nested_options(options: &ExtractOptions, path: &Path, remaining: u64) -> ExtractOptions {
+    let mut nested = options.clone();
+    nested.output_dir = nested_dir(path);
+    nested.patterns.clear();
+    nested.exclude.clear();
+    nested.members.clear();
+    nested.smart_dir = None;
+    nested.strip_components = 0;
+    nested.transforms.clear();
+    nested.name_template = None;
+    nested.expect = Expectations::default();
+    nested.opendoc_check = false;
+    nested.sources = vec![path.to_path_buf()];
+    nested.limits.max_output_size = Some(remaining);
+    nested.recurse_archives = 0;
+    nested
+}
 
-    let mut candidate_threads = candidate_thread_count(args);
+/// Body of [`extract_archive_threaded_with`], recording the move of staged entries
+/// into place in `journal`
+fn extract_threaded_journaled(
+    source: ArchiveSource,
+    options: &ExtractOptions,
+    journal: &Journal,
+    checkpoint: Option<&Checkpoint>,
+    on_event: &mut dyn FnMut(ExtractEvent),
+) -> Result<ExtractReport> {
+    let (source, remapped) = resolve_duplicates(source, options, on_event)?;
+    let options = remapped.as_ref().unwrap_or(options);
+    let mut candidate_threads = candidate_thread_count(options);
     if candidate_threads <= 1 {
         let mut archive = open_archive_from_source(&source)?;
-        return extract_archive_serial(&mut archive, args);
+        return extract_archive_serial(
+            &mut archive,
+            Some(&source),
+            options,
+            journal,
+            checkpoint,
+            on_event,
+        );
     }
 
-    let matcher = PatternMatcher::new(&args.patterns, &args.exclude, args.case_insensitive);
-    let password_bytes = get_password(args.password.as_deref(), args.quiet)?;
+    let matcher = options.matcher();
+    let names = options.names.clone();
+    let password_bytes = get_password(options.password.as_deref(), options.quiet)?;
     let mut archive = open_archive_from_source(&source)?;
+    let limits = options.limits;
+    limits.check_archive(&mut archive)?;
+    let index = index_mapped(&mut archive, Some(&source))?.map(Arc::new);
+    options.expect.check_archive(&mut archive, &names, &matcher)?;
+    check_names(&mut archive, &names)?;
+    check_members(&mut archive, &names, &options.members)?;
+    check_member_index(&archive, options.member_index)?;
+    check_opendoc(&mut archive, options)?;
+    let (case_dirs, mut pre_events) = scan_case_dirs(&mut archive, &names, &matcher, options)?;
+
+    let output_dir = smart_output_dir(&mut archive, options, &names, &matcher, &case_dirs)?;
+    check_self_overwrite(&mut archive, options, &names, &matcher, &case_dirs, &output_dir)?;
+    let created_output = missing_dirs(&output_dir);
+    if !output_dir.exists() {
+        journal.record_dirs(&output_dir);
+        fs::create_dir_all(&output_dir)
+            .map_err(UnzipError::file("create output directory", &output_dir))?;
+    }
+    let destination = Destination::open(&output_dir, options.follow_symlinks)
+        .map_err(UnzipError::file("open output directory", &output_dir))?;
+    let staging = open_staging(options, &output_dir)?;
+    let budget = Arc::new(OutputBudget::new(limits));
     let total_files = archive.len();
-    let mut directories: Vec<(PathBuf, Option<zip::DateTime>)> = Vec::new();
+    let text_mode = options.text_mode;
+    let text_flags = if text_mode == TextMode::Auto {
+        Some(load_text_flags(&mut archive, &source)?)
+    } else {
+        None
+    };
+    let mut long_paths = options.flatten_long_paths.then(|| LongPathStore::new(&output_dir));
+    let mut directories: Vec<DirectoryMeta> = Vec::new();
     let mut jobs: Vec<FileJob> = Vec::new();
+    let mut up_to_date_events: Vec<ExtractEvent> = Vec::new();
     let mut skipped = 0usize;
+    let mut rejected = 0usize;
     let mut encrypted_found = false;
 
+    let mut prefiltered = vec![false; total_files];
+    if options.overwrite.compares_times() {
+        let up_to_date = prefilter_up_to_date(
+            &mut archive,
+            options,
+            &names,
+            &matcher,
+            &case_dirs,
+            &output_dir,
+            long_paths.as_ref(),
+        )?;
+        for (i, name) in up_to_date {
+            prefiltered[i] = true;
+            skipped += 1;
+            up_to_date_events.push(ExtractEvent::Skipped { name, reason: SkipReason::UpToDate });
+        }
+    }
+    if let Some(checkpoint) = checkpoint {
+        for event in prefilter_resumed(&mut archive, options, checkpoint, &mut prefiltered)? {
+            skipped += usize::from(matches!(event, ExtractEvent::Skipped { .. }));
+            up_to_date_events.push(event);
+        }
+    }
+    let prefiltered_count = skipped;
+
     for i in 0..total_files {
-        let file = archive.by_index(i)?;
-        let name = file.name().to_string();
+        if prefiltered[i] || !options.considers(i) {
+            continue;
+        }
+        let legacy = decodes(&archive.by_index_raw(i)?);
+        let file = if legacy {
+            archive.by_index_raw(i)?
+        } else {
+            archive.by_index(i)?
+        };
+        let name = names.decode(&file).into_owned();
         let is_dir = file.is_dir();
-        let mtime = file.last_modified();
+        let times = entry_times(&file, options);
         let size = file.size();
         let encrypted = file.encrypted();
 
         if is_dir {
-            if !args.junk_paths {
-                let dir_name = if args.lowercase {
-                    name.to_lowercase()
-                } else {
-                    name.clone()
-                };
-                directories.push((output_dir.join(dir_name), mtime));
+            if options.junk_paths {
+                pre_events
+                    .push(ExtractEvent::Skipped { name, reason: SkipReason::JunkedDirectory });
+            } else if !matcher.should_extract_dir(&name) {
+                skipped += 1;
+                pre_events.push(ExtractEvent::Skipped { name, reason: SkipReason::Filtered });
+            } else {
+                match entry_output_path(&name, options, &case_dirs)
+                    .map(|relative| output_dir.join(relative))
+                {
+                    Ok(path)
+                        if long_paths.as_ref().is_some_and(|store| store.exceeds_limits(&path)) =>
+                    {
+                        pre_events
+                            .push(ExtractEvent::Skipped { name, reason: SkipReason::PathTooLong });
+                    },
+                    Ok(path) => {
+                        directories.push(DirectoryMeta { path, times, unix_mode: file_mode(&file) })
+                    },
+                    Err(reason) => {
+                        if reason == SkipReason::Stripped {
+                            skipped += 1;
+                        } else {
+                            rejected += 1;
+                        }
+                        pre_events.push(ExtractEvent::Skipped { name, reason });
+                    },
+                }
             }
             continue;
         }
 
-        if !matcher.should_extract(&name) {
-            skipped += 1;
-            continue;
-        }
+        let fields = EntryFields::of(i, &file);
+        let meta = EntryMeta::of(i, &name, &file);
+        let outpath = match selected_output_path(&meta, &fields, &matcher, options, &case_dirs) {
+            Ok(relative) => output_dir.join(relative),
+            Err(reason) => {
+                if reason.is_selection() {
+                    skipped += 1;
+                } else {
+                    rejected += 1;
+                }
+                pre_events.push(ExtractEvent::Skipped { name, reason });
+                continue;
+            },
+        };
+        let outpath = match long_paths.as_mut().and_then(|store| store.flatten(&outpath)) {
+            Some(flat) => {
+                pre_events.push(ExtractEvent::Warning {
+                    message: format!("   flattened: {} -> {}", name, flat.display()),
+                });
+                flat
+            },
+            None => outpath,
+        };
+        let outpath = output_path(outpath, options.compress_output);
 
         if encrypted {
             encrypted_found = true;
         }
 
+        let text_flag = text_flags.as_ref().map(|flags| flags[i]);
+        let write_path = write_path(staging.as_ref(), &outpath);
         jobs.push(FileJob {
             index: i,
             name,
+            outpath,
+            write_path,
+            text_flag,
             size,
-            mtime,
+            times,
             encrypted,
+            legacy,
         });
     }
 
     if encrypted_found && password_bytes.is_none() {
+        // The serial run stages on its own
+        drop(staging);
         let mut archive = open_archive_from_source(&source)?;
-        return extract_archive_serial(&mut archive, args);
+        return extract_archive_serial(
+            &mut archive,
+            Some(&source),
+            options,
+            journal,
+            checkpoint,
+            on_event,
+        );
     }
 
-    if jobs.is_empty() {
-        for (dir_path, _) in &directories {
-            fs::create_dir_all(dir_path)
-                .with_context(|| format!("Failed to create directory: {}", dir_path.display()))?;
-        }
-        if !args.no_timestamps {
-            for (dir_path, mtime) in directories.iter().rev() {
-                if let Some(dt) = mtime {
-                    let filetime_mtime = datetime_to_filetime(*dt);
-                    filetime::set_file_mtime(dir_path, filetime_mtime).ok();
-                }
-            }
+    if let Some(ref mut store) = long_paths {
+        write_manifest(store, journal)?;
+    }
+
+    for event in up_to_date_events {
+        on_event(event);
+    }
+    on_event(ExtractEvent::Started {
+        total_entries: options.considered_entries(total_files) - prefiltered_count,
+        output_dir: output_dir.clone(),
+    });
+    for event in pre_events {
+        on_event(event);
+    }
+
+    for dir in &directories {
+        let write_path = write_path(staging.as_ref(), &dir.path);
+        destination
+            .create_dir_all(&write_path)
+            .map_err(UnzipError::file("create directory", &write_path))?;
+        on_event(ExtractEvent::DirCreated { path: dir.path.clone() });
+    }
+
+    // Archives with many files per directory get their directories in one pass,
+    // sparing the workers an existence check per file
+    let mut shape = ArchiveShape::default();
+    for job in &jobs {
+        shape.add_file(job.outpath.strip_prefix(&output_dir).unwrap_or(&job.outpath));
+    }
+    let dirs_ready = shape.dir_strategy() == DirStrategy::Bulk;
+    if dirs_ready {
+        let write_root = staging.as_ref().map_or(output_dir.as_path(), Staging::dir);
+        let parents = jobs
+            .iter()
+            .filter_map(|job| job.write_path.parent())
+            .filter(|parent| *parent != write_root)
+            .map(Path::to_path_buf);
+        for dir in leaf_directories(parents) {
+            destination
+                .create_dir_all(&dir)
+                .map_err(UnzipError::file("create directory", &dir))?;
         }
-        return Ok(());
     }
 
-    if candidate_threads > jobs.len() {
-        candidate_threads = jobs.len();
+    if jobs.is_empty() {
+        commit_staging(staging, journal, options)?;
+        chown_directories(created_output, &destination, &directories, options)?;
+        finalize_directories(&mut directories, options.no_timestamps);
+        stamp_created_dirs(&destination, &directories, options)?;
+        let report = ExtractReport { extracted: 0, skipped, rejected, bytes: 0, output_dir };
+        on_event(ExtractEvent::Finished { report: report.clone() });
+        return Ok(report);
     }
 
-    for (dir_path, _) in &directories {
-        fs::create_dir_all(dir_path)
-            .with_context(|| format!("Failed to create directory: {}", dir_path.display()))?;
+    if candidate_threads > jobs.len() {
+        candidate_threads = jobs.len();
     }
 
     drop(archive);
 
+    // Memory-mapped archives are parsed once more and shared; file paths are
+    // reopened by each worker
+    let shared_archives = match &source {
+        ArchiveSource::Mmap(mmap) => {
+            let archive = ZipArchive::new(MmapReader::new(Arc::clone(mmap)))?;
+            split_for_threads(&archive, candidate_threads)
+        },
+        ArchiveSource::FilePath(_) | ArchiveSource::Parts(_) | ArchiveSource::Patched(..) => {
+            Vec::new()
+        },
+    };
+
     let extracted = Arc::new(AtomicUsize::new(0));
     let skipped_files = Arc::new(AtomicUsize::new(skipped));
     let total_bytes = Arc::new(AtomicU64::new(0));
+    let infected = Arc::new(AtomicUsize::new(0));
     let source = Arc::new(source);
     let output_dir = Arc::new(output_dir);
+    let destination = Arc::new(destination);
     let password = Arc::new(password_bytes);
-    let args = Arc::new(args.clone());
-
-    let chunk_size = (jobs.len() + candidate_threads - 1) / candidate_threads;
+    let options = Arc::new(options.clone());
+    let checkpoint = checkpoint.cloned();
+    let (event_tx, event_rx) = mpsc::channel::<ExtractEvent>();
+
+    // Workers take jobs from one queue, priority entries first, and their
+    // number follows the throughput they reach (see `scaling`)
+    let priority = Priority::new(&options.priority, options.case_insensitive);
+    let (urgent, rest): (Vec<_>, Vec<_>) =
+        jobs.into_iter().partition(|job| priority.matches(&job.name));
+    let queue = Arc::new(JobQueue::new(urgent.into_iter().chain(rest)));
+    let mut scaler = Scaler::new(candidate_threads);
+    let workers = Workers::new(scaler.initial());
+    let shared_archives = Arc::new(Mutex::new(shared_archives));
     let mut handles = Vec::with_capacity(candidate_threads);
 
-    for chunk in jobs.chunks(chunk_size) {
-        let chunk = chunk.to_vec();
+    let spawn_worker = |events: mpsc::Sender<ExtractEvent>| {
         let source = Arc::clone(&source);
-        let output_dir = Arc::clone(&output_dir);
-        let args = Arc::clone(&args);
+        let options = Arc::clone(&options);
         let password = Arc::clone(&password);
+        let budget = Arc::clone(&budget);
+        let destination = Arc::clone(&destination);
+        let checkpoint = checkpoint.clone();
+        let index = index.clone();
+        let queue = Arc::clone(&queue);
+        let workers = Arc::clone(&workers);
+        let mut slot = workers.start();
+        let pool = Arc::clone(&shared_archives);
+        let shared = pool.lock().unwrap_or_else(PoisonError::into_inner).pop();
 
         let extracted_ref = Arc::clone(&extracted);
         let skipped_ref = Arc::clone(&skipped_files);
+        let infected_ref = Arc::clone(&infected);
         let bytes_ref = Arc::clone(&total_bytes);
 
-        handles.push(thread::spawn(move || -> Result<()> {
-            let mut archive = open_archive_from_source(&source)?;
+        thread::spawn(move || -> Result<()> {
+            let mut archive = match shared {
+                Some(archive) => WorkerArchive::Shared(archive),
+                None => WorkerArchive::Opened(open_archive_from_source(&source)?),
+            };
             let mut buffer = vec![0u8; BUFFER_SIZE];
 
-            for job in chunk {
-                let mut file = if job.encrypted {
+            while let Some(job) = slot.next_job(&queue) {
+                options.cancel.check()?;
+                let sliced = index.as_ref().is_some_and(|index| index.slices(job.index));
+                let mut file = if sliced || job.legacy {
+                    // Read for its metadata, or decoded here; either way the zip
+                    // crate does not decompress it
+                    archive.by_index_raw(job.index)?
+                } else if job.encrypted {
                     let pwd = password.as_ref().as_ref().ok_or_else(|| {
-                        anyhow::anyhow!("Password required for encrypted file")
+                        UnzipError::PasswordRequired { name: Some(job.name.clone()) }
                     })?;
                     archive.by_index_decrypt(job.index, pwd)?
                 } else {
                     archive.by_index(job.index)?
                 };
-
-                let outpath = if args.junk_paths {
-                    let filename = std::path::Path::new(&job.name)
-                        .file_name()
-                        .map(|s| s.to_string_lossy().to_string())
-                        .unwrap_or_else(|| job.name.clone());
-                    let filename = if args.lowercase {
-                        filename.to_lowercase()
-                    } else {
-                        filename
-                    };
-                    output_dir.join(filename)
-                } else {
-                    let name_out = if args.lowercase {
-                        job.name.to_lowercase()
-                    } else {
-                        job.name.clone()
-                    };
-                    match file.enclosed_name() {
-                        Some(_) => output_dir.join(&name_out),
-                        None => {
-                            skipped_ref.fetch_add(1, Ordering::Relaxed);
-                            continue;
-                        },
-                    }
+                let crc32 = file.crc32();
+
+                let outpath = job.outpath;
+                let in_place = job.write_path == outpath && !options.writes_pending();
+                let pending = options.writes_pending().then(|| PendingFile::new(&job.write_path));
+                let write_path = match pending {
+                    Some(ref pending) => pending.path().to_path_buf(),
+                    None => job.write_path,
                 };
 
-                if let Some(parent) = outpath.parent()
+                if !dirs_ready
+                    && let Some(parent) = write_path.parent()
                     && !parent.exists()
                 {
-                    fs::create_dir_all(parent)
-                        .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+                    destination
+                        .create_dir_all(parent)
+                        .map_err(UnzipError::file("create directory", parent))?;
                 }
 
-                let decision = should_overwrite_file(&outpath, &args, job.mtime);
+                let decision =
+                    decide_overwrite(&outpath, &options, job.times.modified, job.size, crc32);
 
-                match decision {
-                    OverwriteDecision::Skip | OverwriteDecision::SkipQuietly => {
-                        skipped_ref.fetch_add(1, Ordering::Relaxed);
-                        continue;
+                let reason = match decision {
+                    OverwriteDecision::Skip => Some(SkipReason::Exists),
+                    OverwriteDecision::SkipQuietly => Some(SkipReason::UpToDate),
+                    OverwriteDecision::Unchanged => Some(SkipReason::Unchanged),
+                    OverwriteDecision::Overwrite
+                        if refuses_read_only(&outpath, in_place, &options) =>
+                    {
+                        Some(SkipReason::ReadOnly)
                     },
-                    OverwriteDecision::Overwrite => {},
+                    OverwriteDecision::Overwrite => None,
+                };
+                if let Some(reason) = reason {
+                    skipped_ref.fetch_add(1, Ordering::Relaxed);
+                    let _ = events.send(ExtractEvent::Skipped { name: job.name, reason });
+                    continue;
                 }
 
                 let unix_mode = {
                     #[cfg(unix)]
                     {
-                        file.unix_mode()
+                        file_mode(&file)
                     }
                     #[cfg(not(unix))]
                     {
@@ -881,61 +2927,283 @@ pub fn extract_archive_threaded(source: ArchiveSource, args: &Args) -> Result<()
                     }
                 };
 
-                extract_single_file(&mut file, &outpath, &mut buffer)?;
-                finalize_extracted_file(&outpath, job.mtime, unix_mode, args.no_timestamps);
+                let unlocked = unlock_read_only(&outpath, in_place, &options)?;
+                let _ = events
+                    .send(ExtractEvent::EntryStarted { name: job.name.clone(), size: job.size });
+                let mut scan = open_scan(&options)?;
+                let convert = text_mode.converts(job.text_flag);
+                let patched = open_patched_file(
+                    &destination,
+                    &write_path,
+                    in_place,
+                    convert,
+                    job.size,
+                    &options,
+                );
+                let patch = patched.is_some();
+                let outfile = match patched {
+                    Some(outfile) => outfile,
+                    None => create_output_file(&destination, &write_path, pending.is_some())?,
+                };
+                let expected_size = options
+                    .expected_sizes
+                    .as_ref()
+                    .map(|sizes| sizes.expected(file.header_start(), job.size));
+                let written = {
+                    let mut slice = index
+                        .as_deref()
+                        .filter(|_| sliced)
+                        .zip(source.mapped())
+                        .and_then(|(index, bytes)| index.open(bytes, job.index, &job.name));
+                    let mut legacy_entry = None;
+                    let entry: &mut dyn EntryData = match slice {
+                        Some(ref mut slice) => slice,
+                        None if job.legacy => legacy_entry.insert(LegacyEntry::new(&mut file)),
+                        None => &mut file,
+                    };
+                    extract_single_file(
+                        entry,
+                        &outfile,
+                        &mut buffer,
+                        EntryWrite {
+                            convert,
+                            verify: options.verify,
+                            compress: options.compress_output,
+                            patch,
+                            expected_size,
+                        },
+                        &budget,
+                        scan.as_mut(),
+                        &mut |bytes| {
+                            workers.record(bytes);
+                            let _ = events
+                                .send(ExtractEvent::BytesWritten { name: job.name.clone(), bytes });
+                            options.cancel.check_within_entry()
+                        },
+                    )
+                };
+                let written = match written {
+                    Err(error) if options.heuristic_decode && is_decode_error(&error) => {
+                        let crc32 = file.crc32();
+                        drop(file);
+                        let raw = archive.by_index_raw(job.index);
+                        let relabel = recover_entry(raw, &write_path, &destination, error)?;
+                        scan = None;
+                        let _ = events.send(ExtractEvent::Warning {
+                            message: format!("   recovered: {} ({})", job.name, relabel),
+                        });
+                        Written { bytes: job.size, crc32: options.verify.then_some(crc32) }
+                    },
+                    written => {
+                        written.inspect_err(|e| remove_unfinished(e, &write_path, &options))?
+                    },
+                };
+                if let Some(crc32) = written.crc32 {
+                    verify_file(&write_path, crc32)?;
+                }
+                let checked = check_scan(scan, &write_path, &outpath, &job.name, &options)?;
+                if let Some(message) = checked {
+                    skipped_ref.fetch_add(1, Ordering::Relaxed);
+                    infected_ref.fetch_add(1, Ordering::Relaxed);
+                    let _ = events.send(ExtractEvent::Warning { message });
+                    let reason = SkipReason::Infected;
+                    let _ = events.send(ExtractEvent::Skipped { name: job.name, reason });
+                    continue;
+                }
+                let bytes = written.bytes;
+                apply_owner(&write_path, &options)?;
+                finalize_extracted_file(
+                    &write_path,
+                    job.times,
+                    unix_mode,
+                    options.no_timestamps >= 2,
+                );
+                if let Some(permissions) = unlocked {
+                    fs::set_permissions(&write_path, permissions)
+                        .map_err(UnzipError::file("restore permissions of", &write_path))?;
+                }
+                if let Some(pending) = pending {
+                    // Only `--shared-dest` leaves a file placed by another process alone
+                    let replace = options.overwrite.replaces_existing() || !options.shared_dest;
+                    let placed = pending
+                        .place(&outpath, replace, job.size, crc32)
+                        .map_err(UnzipError::file("move into place", &outpath))?;
+                    if let Some(reason) = placed.skip_reason() {
+                        skipped_ref.fetch_add(1, Ordering::Relaxed);
+                        let _ = events.send(ExtractEvent::Skipped { name: job.name, reason });
+                        continue;
+                    }
+                }
 
                 extracted_ref.fetch_add(1, Ordering::Relaxed);
-                bytes_ref.fetch_add(job.size, Ordering::Relaxed);
+                let written = bytes_ref.fetch_add(job.size, Ordering::Relaxed) + job.size;
+
+                // Linux optimization: tell kernel we're done with this file's cache
+                if options.cache.drops(written) {
+                    fadvise_dontneed(&outfile, 0, job.size);
+                }
+                if let Some(ref checkpoint) = checkpoint {
+                    checkpoint.record(job.index, crc32, job.size)?;
+                }
+                let _ =
+                    events.send(ExtractEvent::FileDone { name: job.name, path: outpath, bytes });
             }
 
+            // A later worker may reuse the parsed archive
+            if let WorkerArchive::Shared(archive) = archive {
+                pool.lock().unwrap_or_else(PoisonError::into_inner).push(archive);
+            }
             Ok(())
-        }));
+        })
+    };
+
+    for _ in 0..scaler.initial() {
+        handles.push(spawn_worker(event_tx.clone()));
+    }
+    // Workers hold the other senders; once the queue is empty no worker is
+    // added, and the loop ends when the running ones finish
+    let mut event_tx = Some(event_tx);
+    let mut sampled = Instant::now();
+    loop {
+        match event_rx.recv_timeout(SAMPLE_INTERVAL) {
+            Ok(event) => on_event(event),
+            Err(mpsc::RecvTimeoutError::Timeout) => {},
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+        let Some(tx) = &event_tx else {
+            continue;
+        };
+        let queued = queue.len();
+        if queued == 0 {
+            event_tx = None;
+            continue;
+        }
+        let elapsed = sampled.elapsed();
+        if elapsed < SAMPLE_INTERVAL {
+            continue;
+        }
+        sampled = Instant::now();
+        let rate = workers.take_work() as f64 / elapsed.as_secs_f64();
+        let active = workers.active();
+        let target = scaler.next(active, rate, queued);
+        workers.set_target(target);
+        for _ in active..target {
+            handles.push(spawn_worker(tx.clone()));
+        }
     }
 
     for handle in handles {
         handle.join().expect("thread panicked")?;
     }
 
-    if !args.no_timestamps {
-        for (dir_path, mtime) in directories.iter().rev() {
-            if let Some(dt) = mtime {
-                let filetime_mtime = datetime_to_filetime(*dt);
-                filetime::set_file_mtime(dir_path, filetime_mtime).ok();
-            }
-        }
+    commit_staging(staging, journal, &options)?;
+    chown_directories(created_output, &destination, &directories, &options)?;
+    finalize_directories(&mut directories, options.no_timestamps);
+    stamp_created_dirs(&destination, &directories, &options)?;
+
+    let report = ExtractReport {
+        extracted: extracted.load(Ordering::Relaxed),
+        skipped: skipped_files.load(Ordering::Relaxed),
+        rejected,
+        bytes: total_bytes.load(Ordering::Relaxed),
+        output_dir: output_dir.as_ref().clone(),
+    };
+    on_event(ExtractEvent::Finished { report: report.clone() });
+
+    let infected = infected.load(Ordering::Relaxed);
+    if infected > 0 {
+        return Err(UnzipError::Infected { entries: infected });
     }
+    Ok(report)
+}
 
-    if args.quiet == 0 {
-        let extract_count = extracted.load(Ordering::Relaxed);
-        let skip_count = skipped_files.load(Ordering::Relaxed);
-        let bytes = total_bytes.load(Ordering::Relaxed);
-        println!(
-            "Extracted {} files ({}) to {}",
-            extract_count,
-            format_size(bytes),
-            output_dir.display()
-        );
-        if skip_count > 0 {
-            println!("Skipped {} files", skip_count);
-        }
+/// Apply the `--duplicates` policy to entries that share a name: warn about
+/// them, fail for `error`, and read the archive through a rewritten central
+/// directory where the zip crate would pick other entries
+///
+/// A `--member-index` counts the records of the archive's own directory, so
+/// with duplicates it becomes the only entry of the rewritten one; the options
+/// to use instead are returned then.
+///
+/// # Errors
+///
+/// Returns [`UnzipError::DuplicateNames`] for duplicates under the `error` policy.
+/// A directory that cannot be read is left for opening the archive to report.
+fn resolve_duplicates(
+    source: ArchiveSource,
+    options: &ExtractOptions,
+    on_event: &mut dyn FnMut(ExtractEvent),
+) -> Result<(ArchiveSource, Option<ExtractOptions>)> {
+    let duplicates = match open_reader(&source).and_then(|reader| Duplicates::scan(reader, options))
+    {
+        Ok(duplicates) => duplicates,
+        Err(error @ UnzipError::DuplicateNames(_)) => return Err(error),
+        Err(_) => return Ok((source, None)),
+    };
+    if let Some(message) = duplicates.summary(options).filter(|_| options.quiet == 0) {
+        on_event(ExtractEvent::Warning { message });
     }
+    let Some(patch) = duplicates.into_patch() else {
+        return Ok((source, None));
+    };
+    let remapped = options.member_index.map(|_| options.clone().member_index(Some(0)));
+    Ok((ArchiveSource::Patched(Box::new(source), Arc::new(patch)), remapped))
+}
 
-    Ok(())
+/// Indices of the entries in the order to extract them, those `--priority`
+/// selects first, or just the one `--member-index` names
+fn entry_order<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    options: &ExtractOptions,
+) -> Result<Vec<usize>> {
+    if let Some(index) = options.member_index {
+        return Ok(vec![index]);
+    }
+    if options.priority.is_empty() {
+        return Ok((0..archive.len()).collect());
+    }
+    let mut names = Vec::with_capacity(archive.len());
+    for i in 0..archive.len() {
+        names.push(options.names.decode(&archive.by_index_raw(i)?).into_owned());
+    }
+    let names = names.iter().map(String::as_str);
+    Ok(extraction_order(names, &options.priority, options.case_insensitive))
 }
 
 #[derive(Clone)]
 struct FileJob {
     index: usize,
     name: String,
+    outpath: PathBuf,
+    /// Where the file is written: `outpath`, or its staged place
+    write_path: PathBuf,
+    text_flag: Option<bool>,
     size: u64,
-    mtime: Option<zip::DateTime>,
+    times: EntryTimes,
     encrypted: bool,
+    /// Decoded with [`LegacyEntry`] rather than by the zip crate
+    legacy: bool,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::args::Args;
+    use crate::cancel::CancelToken;
+    use crate::checkpoint::CheckpointInterval;
+    use crate::duplicates::DuplicatePolicy;
+    use crate::expect::Expected;
+    use crate::implied_dirs::ImpliedDirTimes;
+    use crate::invalid_names::InvalidNames;
+    use crate::limits::LimitExceeded;
+    use crate::linux::CachePolicy;
+    use crate::list::ListFormat;
+    use crate::template::parse_name_template;
+    use crate::transform::parse_transform;
+    use crate::utils::datetime_to_filetime;
     use std::io::Cursor;
+    use std::path::Path;
+    use std::time::Duration;
     use zip::ZipWriter;
     use zip::write::SimpleFileOptions;
 
@@ -965,26 +3233,109 @@ mod tests {
             output_dir: None,
             list_only: false,
             verbose: false,
+            list_format: ListFormat::InfoZip,
+            totals_only: None,
+            sort: None,
+            reverse: false,
+            tree: false,
+            print0: false,
+            du: false,
+            depth: None,
+            min_size: None,
+            max_size: None,
+            newer: None,
+            older: None,
             test: false,
+            report: None,
+            wait_complete: None,
+            size_check: None,
+            expected_sizes: None,
+            no_create_dir: false,
+            raw_names: false,
+            to_tar: None,
+            test_quick: false,
+            lint: false,
+            diff: None,
+            stats: false,
             pipe: false,
             comment_only: false,
+            comment_json: None,
             zipinfo: None,
+            extended_times: false,
             overwrite: true,
             never_overwrite: false,
+            auto_rename: false,
+            allow_self_overwrite: false,
+            if_changed: false,
             freshen: false,
             update: false,
             junk_paths: false,
+            strip_components: 0,
+            transform: Vec::new(),
+            name_template: None,
             case_insensitive: false,
+            wild_stop_at_dir: false,
             lowercase: false,
-            no_timestamps: false,
+            merge_case_dirs: false,
+            no_timestamps: 0,
             quiet: 2,
+            oem_charset: None,
+            iso_charset: None,
+            auto_encoding: false,
+            invalid_names: InvalidNames::Lossy,
+            text_mode: 0,
+            verbose_io: false,
+            cache: CachePolicy::Auto,
+            compat: Compat::Native,
+            recurse_archives: None,
+            subdir_per_archive: false,
+            archives: false,
+            smart_dir: false,
+            stamp_output_dir: None,
+            implied_dir_times: ImpliedDirTimes::Now,
+            flatten_long_paths: false,
+            follow_symlinks: false,
+            heuristic_decode: false,
+            salvage: false,
+            dry_run: false,
+            dirs_only: false,
+            transactional: false,
+            shared_dest: false,
+            patch_in_place: false,
+            force_readonly: false,
+            chown: None,
+            priority: Vec::new(),
+            crt: false,
+            filter: false,
+            checkpoint_every: None,
+            resume: false,
+            metrics_file: None,
+            verify: false,
+            compress_output: None,
+            scan_socket: None,
+            quarantine: None,
+            max_output_size: None,
+            max_ratio: None,
+            max_entries: None,
+            expect_files: None,
+            expect_bytes: None,
+            opendoc_check: false,
             threads: None,
             password: None,
             patterns: vec![],
             exclude: vec![],
+            include_from: Vec::new(),
+            exclude_from: Vec::new(),
+            members_from: None,
+            member_index: None,
+            duplicates: DuplicatePolicy::Last,
         }
     }
 
+    fn to_options(args: &Args) -> ExtractOptions {
+        ExtractOptions::try_from(args).unwrap()
+    }
+
     #[test]
     fn test_zip_extract_threaded() {
         let zip_data = create_test_zip(&[
@@ -1002,7 +3353,7 @@ mod tests {
         args.quiet = 2;
         args.threads = Some(2);
 
-        extract_archive_threaded(ArchiveSource::FilePath(zip_path), &args).unwrap();
+        extract_archive_threaded(ArchiveSource::FilePath(zip_path), &to_options(&args)).unwrap();
 
         let test_file = output_dir.join("test.txt");
         assert!(test_file.exists());
@@ -1027,7 +3378,7 @@ mod tests {
         let mut args = default_args();
         args.output_dir = Some(temp_dir.path().to_path_buf());
 
-        extract_archive(&mut archive, &args).unwrap();
+        extract_archive(&mut archive, &to_options(&args)).unwrap();
 
         let test_file = temp_dir.path().join("test.txt");
         assert!(test_file.exists());
@@ -1055,7 +3406,7 @@ mod tests {
         args.output_dir = Some(temp_dir.path().to_path_buf());
         args.patterns = vec!["*.txt".to_string()];
 
-        extract_archive(&mut archive, &args).unwrap();
+        extract_archive(&mut archive, &to_options(&args)).unwrap();
 
         assert!(temp_dir.path().join("file.txt").exists());
         assert!(!temp_dir.path().join("file.rs").exists());
@@ -1064,60 +3415,279 @@ mod tests {
     }
 
     #[test]
-    fn test_zip_extract_with_exclude() {
+    fn test_zip_extract_filters_directory_entries() {
         let zip_data = create_test_zip(&[
-            ("file.txt", b"Text file"),
-            ("file.rs", b"Rust file"),
-            ("debug.log", b"Log file"),
+            ("docs/", b""),
+            ("docs/empty/", b""),
+            ("docs/readme.txt", b"Readme"),
+            ("cache/", b""),
         ]);
-
         let temp_dir = tempfile::tempdir().unwrap();
-        let cursor = Cursor::new(zip_data);
-        let mut archive = ZipArchive::new(cursor).unwrap();
-
         let mut args = default_args();
         args.output_dir = Some(temp_dir.path().to_path_buf());
-        args.exclude = vec!["*.log".to_string()];
+        args.patterns = vec!["docs/*".to_string()];
 
-        extract_archive(&mut archive, &args).unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+        extract_archive(&mut archive, &to_options(&args)).unwrap();
 
-        assert!(temp_dir.path().join("file.txt").exists());
-        assert!(temp_dir.path().join("file.rs").exists());
-        assert!(!temp_dir.path().join("debug.log").exists());
+        assert!(temp_dir.path().join("docs/empty").is_dir());
+        assert!(temp_dir.path().join("docs/readme.txt").exists());
+        assert!(!temp_dir.path().join("cache").exists());
     }
 
     #[test]
-    fn test_zip_extract_junk_paths() {
-        let zip_data = create_test_zip(&[("deep/nested/path/file.txt", b"Content")]);
-
+    fn test_zip_extract_dirs_only() {
+        let zip_data = create_test_zip(&[
+            ("app/", b""),
+            ("app/logs/", b""),
+            ("app/bin/tool", b"binary"),
+            ("top.txt", b"top"),
+        ]);
         let temp_dir = tempfile::tempdir().unwrap();
-        let cursor = Cursor::new(zip_data);
-        let mut archive = ZipArchive::new(cursor).unwrap();
-
+        let zip_path = temp_dir.path().join("test.zip");
+        fs::write(&zip_path, zip_data).unwrap();
         let mut args = default_args();
-        args.output_dir = Some(temp_dir.path().to_path_buf());
-        args.junk_paths = true;
+        args.output_dir = Some(temp_dir.path().join("out"));
+        args.dirs_only = true;
+        args.threads = Some(4);
 
-        extract_archive(&mut archive, &args).unwrap();
+        extract_archive_threaded(ArchiveSource::FilePath(zip_path), &to_options(&args)).unwrap();
 
-        // File should be in root, not nested
-        assert!(temp_dir.path().join("file.txt").exists());
-        assert!(!temp_dir.path().join("deep").exists());
+        let out = temp_dir.path().join("out");
+        assert!(out.join("app/logs").is_dir());
+        assert!(out.join("app/bin").is_dir());
+        assert!(!out.join("app/bin/tool").exists());
+        assert!(!out.join("top.txt").exists());
     }
 
     #[test]
-    fn test_zip_extract_lowercase() {
-        let zip_data = create_test_zip(&[("FILE.TXT", b"Content"), ("Dir/NESTED.RS", b"Rust")]);
-
+    fn test_zip_extract_recurse_archives() {
+        let inner = create_test_zip(&[("deep.txt", b"deep")]);
+        let middle = create_test_zip(&[("inner.zip", &inner), ("mid.txt", b"mid")]);
+        let outer = create_test_zip(&[
+            ("vendor/middle.ZIP", &middle),
+            ("fake.zip", b"not an archive"),
+            ("top.txt", b"top"),
+        ]);
         let temp_dir = tempfile::tempdir().unwrap();
-        let cursor = Cursor::new(zip_data);
+        let zip_path = temp_dir.path().join("outer.zip");
+        fs::write(&zip_path, outer).unwrap();
+
+        let extract = |out: &str, depth: u32, max_output_size: Option<u64>| {
+            let mut args = default_args();
+            args.output_dir = Some(temp_dir.path().join(out));
+            args.recurse_archives = Some(depth);
+            args.max_output_size = max_output_size;
+            args.threads = Some(2);
+            let source = ArchiveSource::FilePath(zip_path.clone());
+            extract_archive_threaded(source, &to_options(&args))
+        };
+
+        extract("two", 2, None).unwrap();
+        let out = temp_dir.path().join("two");
+        assert_eq!(fs::read(out.join("vendor/middle/mid.txt")).unwrap(), b"mid");
+        assert_eq!(fs::read(out.join("vendor/middle/inner/deep.txt")).unwrap(), b"deep");
+        assert!(out.join("vendor/middle.ZIP").is_file());
+        assert!(!out.join("fake").exists());
+
+        extract("one", 1, None).unwrap();
+        let out = temp_dir.path().join("one");
+        assert!(out.join("vendor/middle/inner.zip").is_file());
+        assert!(!out.join("vendor/middle/inner").exists());
+
+        // The outer archive's own files leave a single byte for the nested ones
+        let limit = (middle.len() + b"not an archive".len() + b"top".len() + 1) as u64;
+        let err = extract("limited", 2, Some(limit)).unwrap_err();
+        assert!(
+            matches!(err, UnzipError::Limit(LimitExceeded::OutputSize { limit: l }) if l == limit)
+        );
+    }
+
+    #[test]
+    fn test_zip_extract_merge_case_dirs() {
+        let zip_data = create_test_zip(&[
+            ("Docs/", b""),
+            ("Docs/a.txt", b"a"),
+            ("docs/b.txt", b"b"),
+            ("DOCS/img/c.png", b"c"),
+        ]);
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        let options = ExtractOptions::new()
+            .output_dir(temp_dir.path().join("dry"))
+            .dry_run(true)
+            .on_event(move |event| sink.lock().unwrap().push(event));
+        let mut archive = ZipArchive::new(Cursor::new(zip_data.clone())).unwrap();
+        extract_archive(&mut archive, &options).unwrap();
+        let warnings: Vec<_> = events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|event| match event {
+                ExtractEvent::Warning { message } => Some(message.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                "warning:  Docs/, docs/, DOCS/ differ only in case (--merge-case-dirs extracts them \
+              all into Docs/)"
+            ]
+        );
+
+        let zip_path = temp_dir.path().join("test.zip");
+        fs::write(&zip_path, zip_data).unwrap();
+        let mut args = default_args();
+        args.output_dir = Some(temp_dir.path().join("out"));
+        args.merge_case_dirs = true;
+        args.threads = Some(4);
+        extract_archive_threaded(ArchiveSource::FilePath(zip_path), &to_options(&args)).unwrap();
+
+        let out = temp_dir.path().join("out");
+        let top: Vec<_> =
+            fs::read_dir(&out).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert_eq!(top, ["Docs"]);
+        assert_eq!(fs::read(out.join("Docs/b.txt")).unwrap(), b"b");
+        assert_eq!(fs::read(out.join("Docs/img/c.png")).unwrap(), b"c");
+    }
+
+    #[test]
+    fn test_zip_extract_with_exclude() {
+        let zip_data = create_test_zip(&[
+            ("file.txt", b"Text file"),
+            ("file.rs", b"Rust file"),
+            ("debug.log", b"Log file"),
+        ]);
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let cursor = Cursor::new(zip_data);
+        let mut archive = ZipArchive::new(cursor).unwrap();
+
+        let mut args = default_args();
+        args.output_dir = Some(temp_dir.path().to_path_buf());
+        args.exclude = vec!["*.log".to_string()];
+
+        extract_archive(&mut archive, &to_options(&args)).unwrap();
+
+        assert!(temp_dir.path().join("file.txt").exists());
+        assert!(temp_dir.path().join("file.rs").exists());
+        assert!(!temp_dir.path().join("debug.log").exists());
+    }
+
+    #[test]
+    fn test_zip_extract_junk_paths() {
+        let zip_data = create_test_zip(&[("deep/nested/path/file.txt", b"Content")]);
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let cursor = Cursor::new(zip_data);
+        let mut archive = ZipArchive::new(cursor).unwrap();
+
+        let mut args = default_args();
+        args.output_dir = Some(temp_dir.path().to_path_buf());
+        args.junk_paths = true;
+
+        extract_archive(&mut archive, &to_options(&args)).unwrap();
+
+        // File should be in root, not nested
+        assert!(temp_dir.path().join("file.txt").exists());
+        assert!(!temp_dir.path().join("deep").exists());
+    }
+
+    #[test]
+    fn test_zip_extract_strip_components() {
+        let zip_data = create_test_zip(&[
+            ("project-1.2.3/", b""),
+            ("project-1.2.3/src/", b""),
+            ("project-1.2.3/src/main.rs", b"fn main() {}"),
+            ("project-1.2.3/Cargo.toml", b"[package]"),
+            ("NOTICE", b"top level"),
+        ]);
+        let temp_dir = tempfile::tempdir().unwrap();
+        let zip_path = temp_dir.path().join("test.zip");
+        fs::write(&zip_path, zip_data).unwrap();
+
+        for threads in [1, 2] {
+            let output_dir = temp_dir.path().join(format!("out{}", threads));
+            let options = ExtractOptions::new()
+                .output_dir(&output_dir)
+                .threads(threads)
+                .strip_components(1)
+                .quiet(2);
+            let source = ArchiveSource::FilePath(zip_path.clone());
+            let report = extract_archive_threaded_with(source, &options, &mut |_| {}).unwrap();
+            assert_eq!((report.extracted, report.skipped, report.rejected), (2, 2, 0));
+            assert_eq!(fs::read(output_dir.join("src/main.rs")).unwrap(), b"fn main() {}");
+            assert!(output_dir.join("Cargo.toml").exists());
+            assert!(!output_dir.join("project-1.2.3").exists());
+            assert!(!output_dir.join("NOTICE").exists());
+        }
+
+        let mut args = default_args();
+        args.output_dir = Some(temp_dir.path().join("deeper"));
+        args.strip_components = 2;
+        extract_archive_threaded(ArchiveSource::FilePath(zip_path), &to_options(&args)).unwrap();
+        assert!(temp_dir.path().join("deeper/main.rs").exists());
+        assert!(!temp_dir.path().join("deeper/Cargo.toml").exists());
+    }
+
+    #[test]
+    fn test_zip_extract_name_template() {
+        let zip_data = create_test_zip(&[
+            ("a/", b""),
+            ("a/notes.txt", b"one"),
+            ("b/notes.txt", b"two"),
+            ("README", b"three"),
+        ]);
+        let temp_dir = tempfile::tempdir().unwrap();
+        let zip_path = temp_dir.path().join("test.zip");
+        fs::write(&zip_path, zip_data).unwrap();
+        let template = parse_name_template("{dir}/{index}-{stem}{ext}").unwrap();
+
+        for threads in [1, 2] {
+            let output_dir = temp_dir.path().join(format!("out{}", threads));
+            let options = ExtractOptions::new()
+                .output_dir(&output_dir)
+                .threads(threads)
+                .junk_paths(true)
+                .name_template(Some(template.clone()))
+                .quiet(2);
+            let source = ArchiveSource::FilePath(zip_path.clone());
+            let report = extract_archive_threaded_with(source, &options, &mut |_| {}).unwrap();
+            assert_eq!(report.extracted, 3);
+            assert_eq!(fs::read(output_dir.join("1-notes.txt")).unwrap(), b"one");
+            assert_eq!(fs::read(output_dir.join("2-notes.txt")).unwrap(), b"two");
+            assert_eq!(fs::read(output_dir.join("3-README")).unwrap(), b"three");
+        }
+
+        // Without -j the directories stay, and directory entries keep their names
+        let mut archive = ZipArchive::new(File::open(&zip_path).unwrap()).unwrap();
+        let crc = archive.by_index(1).unwrap().crc32();
+        let options = ExtractOptions::new()
+            .output_dir(temp_dir.path().join("kept"))
+            .name_template(parse_name_template("{path}.{crc}").ok())
+            .quiet(2);
+        extract_archive(&mut archive, &options).unwrap();
+        let renamed = format!("kept/a/notes.txt.{:08x}", crc);
+        assert_eq!(fs::read(temp_dir.path().join(renamed)).unwrap(), b"one");
+        assert!(temp_dir.path().join("kept/a").is_dir());
+    }
+
+    #[test]
+    fn test_zip_extract_lowercase() {
+        let zip_data = create_test_zip(&[("FILE.TXT", b"Content"), ("Dir/NESTED.RS", b"Rust")]);
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let cursor = Cursor::new(zip_data);
         let mut archive = ZipArchive::new(cursor).unwrap();
 
         let mut args = default_args();
         args.output_dir = Some(temp_dir.path().to_path_buf());
         args.lowercase = true;
 
-        extract_archive(&mut archive, &args).unwrap();
+        extract_archive(&mut archive, &to_options(&args)).unwrap();
 
         assert!(temp_dir.path().join("file.txt").exists());
         assert!(temp_dir.path().join("dir/nested.rs").exists());
@@ -1140,7 +3710,7 @@ mod tests {
         args.overwrite = false;
         args.never_overwrite = true;
 
-        extract_archive(&mut archive, &args).unwrap();
+        extract_archive(&mut archive, &to_options(&args)).unwrap();
 
         assert_eq!(fs::read_to_string(&existing_file).unwrap(), "Original content");
     }
@@ -1161,7 +3731,7 @@ mod tests {
         args.output_dir = Some(temp_dir.path().to_path_buf());
         args.overwrite = true;
 
-        extract_archive(&mut archive, &args).unwrap();
+        extract_archive(&mut archive, &to_options(&args)).unwrap();
 
         assert_eq!(fs::read_to_string(&existing_file).unwrap(), "New content");
     }
@@ -1179,7 +3749,7 @@ mod tests {
         let mut args = default_args();
         args.output_dir = Some(temp_dir.path().to_path_buf());
 
-        extract_archive(&mut archive, &args).unwrap();
+        extract_archive(&mut archive, &to_options(&args)).unwrap();
     }
 
     #[test]
@@ -1194,7 +3764,7 @@ mod tests {
         let mut args = default_args();
         args.output_dir = Some(temp_dir.path().to_path_buf());
 
-        extract_archive(&mut archive, &args).unwrap();
+        extract_archive(&mut archive, &to_options(&args)).unwrap();
 
         let extracted = fs::read(temp_dir.path().join("binary.bin")).unwrap();
         assert_eq!(extracted, binary_data);
@@ -1206,7 +3776,7 @@ mod tests {
         let path = temp_dir.path().join("nonexistent.txt");
         let args = default_args();
 
-        let decision = should_overwrite_file(&path, &args, None);
+        let decision = should_overwrite_file(&path, to_options(&args).overwrite, None);
         assert_eq!(decision, OverwriteDecision::Overwrite);
     }
 
@@ -1217,7 +3787,7 @@ mod tests {
         let mut args = default_args();
         args.freshen = true;
 
-        let decision = should_overwrite_file(&path, &args, None);
+        let decision = should_overwrite_file(&path, to_options(&args).overwrite, None);
         assert_eq!(decision, OverwriteDecision::SkipQuietly);
     }
 
@@ -1231,7 +3801,7 @@ mod tests {
         args.never_overwrite = true;
         args.overwrite = false;
 
-        let decision = should_overwrite_file(&path, &args, None);
+        let decision = should_overwrite_file(&path, to_options(&args).overwrite, None);
         assert_eq!(decision, OverwriteDecision::Skip);
     }
 
@@ -1244,7 +3814,7 @@ mod tests {
         let mut args = default_args();
         args.overwrite = true;
 
-        let decision = should_overwrite_file(&path, &args, None);
+        let decision = should_overwrite_file(&path, to_options(&args).overwrite, None);
         assert_eq!(decision, OverwriteDecision::Overwrite);
     }
 
@@ -1257,7 +3827,7 @@ mod tests {
         let mut args = default_args();
         args.overwrite = false;
 
-        let decision = should_overwrite_file(&path, &args, None);
+        let decision = should_overwrite_file(&path, to_options(&args).overwrite, None);
         assert_eq!(decision, OverwriteDecision::Skip);
     }
 
@@ -1278,11 +3848,1734 @@ mod tests {
         args.output_dir = Some(temp_dir.path().to_path_buf());
         args.patterns = vec!["*.txt".to_string(), "*.rs".to_string()];
 
-        extract_archive(&mut archive, &args).unwrap();
+        extract_archive(&mut archive, &to_options(&args)).unwrap();
 
         assert!(temp_dir.path().join("file.txt").exists());
         assert!(temp_dir.path().join("file.rs").exists());
         assert!(!temp_dir.path().join("file.md").exists());
         assert!(!temp_dir.path().join("file.json").exists());
     }
+
+    #[test]
+    fn test_wildcards_stop_at_dir() {
+        let zip_data = create_test_zip(&[("a.txt", b"top"), ("sub/b.txt", b"nested")]);
+        for (compat, stop_at_dir, nested) in [
+            (Compat::Native, false, false),
+            (Compat::InfoZip, false, true),
+            (Compat::InfoZip, true, false),
+        ] {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut archive = ZipArchive::new(Cursor::new(&zip_data)).unwrap();
+            let mut args = default_args();
+            args.output_dir = Some(temp_dir.path().to_path_buf());
+            args.patterns = vec!["*.txt".to_string()];
+            args.compat = compat;
+            args.wild_stop_at_dir = stop_at_dir;
+
+            extract_archive(&mut archive, &to_options(&args)).unwrap();
+            assert!(temp_dir.path().join("a.txt").exists());
+            assert_eq!(temp_dir.path().join("sub/b.txt").exists(), nested, "{}", compat);
+        }
+    }
+
+    #[test]
+    fn test_zip_extract_iso_charset_decodes_names() {
+        let mut zip_data = create_test_zip(&[("XXXX.txt", b"GBK name")]);
+        // Replace the ASCII placeholder with "测试" in GBK; EFS stays unset
+        let gbk = [0xB2, 0xE2, 0xCA, 0xD4];
+        let positions: Vec<usize> = zip_data
+            .windows(4)
+            .enumerate()
+            .filter(|(_, w)| *w == b"XXXX")
+            .map(|(i, _)| i)
+            .collect();
+        for pos in positions {
+            zip_data[pos..pos + 4].copy_from_slice(&gbk);
+        }
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let cursor = Cursor::new(zip_data);
+        let mut archive = ZipArchive::new(cursor).unwrap();
+
+        let mut args = default_args();
+        args.output_dir = Some(temp_dir.path().to_path_buf());
+        args.iso_charset = Some("CP936".to_string());
+
+        extract_archive(&mut archive, &to_options(&args)).unwrap();
+
+        let extracted = temp_dir.path().join("测试.txt");
+        assert_eq!(fs::read_to_string(extracted).unwrap(), "GBK name");
+    }
+
+    #[test]
+    fn test_zip_extract_invalid_names() {
+        // A non-ASCII name sets EFS; its "é" then turns into a stray 0xFF byte
+        let mut zip_data = create_test_zip(&[("é%.txt", b"bad name"), ("ok.txt", b"fine")]);
+        let positions: Vec<usize> = zip_data
+            .windows(2)
+            .enumerate()
+            .filter(|(_, w)| *w == "é".as_bytes())
+            .map(|(i, _)| i)
+            .collect();
+        for pos in positions {
+            zip_data[pos..pos + 2].copy_from_slice(b"\xff_");
+        }
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut args = default_args();
+        args.output_dir = Some(temp_dir.path().join("escaped"));
+        args.invalid_names = InvalidNames::Escape;
+        let mut archive = ZipArchive::new(Cursor::new(zip_data.clone())).unwrap();
+        extract_archive(&mut archive, &to_options(&args)).unwrap();
+        let escaped = temp_dir.path().join("escaped/%FF_%25.txt");
+        assert_eq!(fs::read(escaped).unwrap(), b"bad name");
+        assert!(temp_dir.path().join("escaped/ok.txt").exists());
+
+        args.output_dir = Some(temp_dir.path().join("rejected"));
+        args.invalid_names = InvalidNames::Reject;
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+        match extract_archive(&mut archive, &to_options(&args)) {
+            Err(UnzipError::InvalidNames(invalid)) => assert_eq!(invalid.names, ["%FF_%25.txt"]),
+            result => panic!("expected invalid names, got {:?}", result),
+        }
+        assert!(!temp_dir.path().join("rejected").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_zip_extract_raw_names_writes_name_bytes() {
+        use std::os::unix::ffi::OsStrExt;
+
+        // An ASCII name leaves EFS unset, so its bytes would otherwise be CP437
+        let mut zip_data = create_test_zip(&[("dir/rXsum%.doc", b"evidence")]);
+        let positions: Vec<usize> = zip_data
+            .windows(5)
+            .enumerate()
+            .filter(|(_, w)| *w == b"rXsum")
+            .map(|(i, _)| i)
+            .collect();
+        for pos in positions {
+            zip_data[pos + 1] = 0xe9;
+        }
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut args = default_args();
+        args.output_dir = Some(temp_dir.path().to_path_buf());
+        args.raw_names = true;
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+        let names = to_options(&args).names;
+        assert_eq!(names.decode(&archive.by_index_raw(0).unwrap()), "dir/r%E9sum%25.doc");
+        extract_archive(&mut archive, &to_options(&args)).unwrap();
+        let path = temp_dir.path().join(std::ffi::OsStr::from_bytes(b"dir/r\xe9sum%.doc"));
+        assert_eq!(fs::read(path).unwrap(), b"evidence");
+    }
+
+    #[test]
+    fn test_zip_extract_auto_encoding_detects_utf8_without_flag() {
+        let mut zip_data = create_test_zip(&[("XXXXXX.txt", b"UTF-8 name")]);
+        // Replace the ASCII placeholder with UTF-8 "测试" but leave EFS unset
+        let utf8 = "测试".as_bytes();
+        let positions: Vec<usize> = zip_data
+            .windows(6)
+            .enumerate()
+            .filter(|(_, w)| *w == b"XXXXXX")
+            .map(|(i, _)| i)
+            .collect();
+        for pos in positions {
+            zip_data[pos..pos + 6].copy_from_slice(utf8);
+        }
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let cursor = Cursor::new(zip_data);
+        let mut archive = ZipArchive::new(cursor).unwrap();
+
+        let mut args = default_args();
+        args.output_dir = Some(temp_dir.path().to_path_buf());
+        args.auto_encoding = true;
+
+        extract_archive(&mut archive, &to_options(&args)).unwrap();
+
+        let extracted = temp_dir.path().join("测试.txt");
+        assert_eq!(fs::read_to_string(extracted).unwrap(), "UTF-8 name");
+    }
+
+    #[test]
+    fn test_zip_extract_rejects_degenerate_names() {
+        let zip_data = create_test_zip(&[
+            ("", b"empty"),
+            (".", b"dot"),
+            ("../evil.txt", b"escape"),
+            ("./", &[]),
+            ("./ok/./file.txt", b"fine"),
+        ]);
+        let temp_dir = tempfile::tempdir().unwrap();
+        let out_dir = temp_dir.path().join("out");
+        let cursor = Cursor::new(zip_data);
+        let mut archive = ZipArchive::new(cursor).unwrap();
+
+        let mut args = default_args();
+        args.output_dir = Some(out_dir.clone());
+
+        let mut reasons = Vec::new();
+        let report = extract_archive_serial(
+            &mut archive,
+            None,
+            &to_options(&args),
+            &Journal::default(),
+            None,
+            &mut |event| {
+                if let ExtractEvent::Skipped { reason, .. } = event {
+                    reasons.push(reason);
+                }
+            },
+        )
+        .unwrap();
+
+        assert_eq!(report.extracted, 1);
+        assert_eq!(report.rejected, 4);
+        assert_eq!(
+            reasons,
+            vec![
+                SkipReason::EmptyName,
+                SkipReason::EmptyName,
+                SkipReason::UnsafePath,
+                SkipReason::EmptyName
+            ]
+        );
+        assert_eq!(fs::read_to_string(out_dir.join("ok/file.txt")).unwrap(), "fine");
+        assert!(!temp_dir.path().join("evil.txt").exists());
+    }
+
+    #[test]
+    fn test_zip_extract_threaded_rejects_degenerate_names() {
+        let zip_data = create_test_zip(&[
+            ("..", b"parent"),
+            ("a.txt", b"one"),
+            ("b.txt", b"two"),
+            ("dir/../../up.txt", b"escape"),
+        ]);
+        let temp_dir = tempfile::tempdir().unwrap();
+        let zip_path = temp_dir.path().join("test.zip");
+        fs::write(&zip_path, zip_data).unwrap();
+
+        let mut args = default_args();
+        args.output_dir = Some(temp_dir.path().join("out"));
+        args.threads = Some(2);
+
+        let report = extract_archive_threaded_with(
+            ArchiveSource::FilePath(zip_path),
+            &to_options(&args),
+            &mut |_| {},
+        )
+        .unwrap();
+
+        assert_eq!(report.extracted, 2);
+        assert_eq!(report.rejected, 2);
+        assert!(!temp_dir.path().join("up.txt").exists());
+    }
+
+    #[test]
+    fn test_entry_output_path_junk_uses_sanitized_name() {
+        let mut args = default_args();
+        args.junk_paths = true;
+        args.lowercase = true;
+
+        assert_eq!(
+            entry_output_path("./Dir//File.TXT", &to_options(&args), &CaseDirs::default()),
+            Ok(PathBuf::from("file.txt"))
+        );
+        let case_dirs = CaseDirs::default();
+        let path = |name| entry_output_path(name, &to_options(&args), &case_dirs);
+        assert_eq!(path("a/.."), Err(SkipReason::UnsafePath));
+        assert_eq!(path(""), Err(SkipReason::EmptyName));
+    }
+
+    #[test]
+    fn test_entry_output_path_strip_components() {
+        let mut args = default_args();
+        args.strip_components = 1;
+        let case_dirs = CaseDirs::default();
+        let path = |name| entry_output_path(name, &to_options(&args), &case_dirs);
+        assert_eq!(path("project-1.2.3/src/main.rs"), Ok(PathBuf::from("src/main.rs")));
+        assert_eq!(path("./project-1.2.3//README"), Ok(PathBuf::from("README")));
+        assert_eq!(path("project-1.2.3/"), Err(SkipReason::Stripped));
+        assert_eq!(path("setup.py"), Err(SkipReason::Stripped));
+        assert_eq!(path("../escape/a.txt"), Err(SkipReason::UnsafePath));
+    }
+
+    #[test]
+    fn test_entry_output_path_transform() {
+        let mut args = default_args();
+        args.lowercase = true;
+        args.transform = vec![
+            parse_transform(r"s/^docs\//manual\//").unwrap(),
+            parse_transform(r"s/\.txt$/.md/").unwrap(),
+            parse_transform(r"s/.*\.log$//").unwrap(),
+            parse_transform("s/^/..\\//").unwrap(),
+        ];
+        let case_dirs = CaseDirs::default();
+        let path = |name| entry_output_path(name, &to_options(&args), &case_dirs);
+        assert_eq!(path("Docs/Intro.TXT"), Err(SkipReason::UnsafePath));
+        args.transform.pop();
+        let path = |name| entry_output_path(name, &to_options(&args), &case_dirs);
+        assert_eq!(path("Docs/Intro.TXT"), Ok(PathBuf::from("manual/intro.md")));
+        assert_eq!(path("docs/"), Ok(PathBuf::from("manual")));
+        assert_eq!(path("docs"), Ok(PathBuf::from("docs")));
+        assert_eq!(path("build/out.log"), Err(SkipReason::Stripped));
+    }
+
+    #[test]
+    fn test_zip_extract_threaded_from_mmap() {
+        let zip_data =
+            create_test_zip(&[("a.txt", b"one"), ("b.txt", b"two"), ("c.txt", b"three")]);
+        let temp_dir = tempfile::tempdir().unwrap();
+        let zip_path = temp_dir.path().join("test.zip");
+        fs::write(&zip_path, zip_data).unwrap();
+        let file = File::open(&zip_path).unwrap();
+        let mmap = unsafe { Mmap::map(&file) }.unwrap();
+
+        let mut args = default_args();
+        args.output_dir = Some(temp_dir.path().join("out"));
+        args.threads = Some(3);
+
+        let report = extract_archive_threaded_with(
+            ArchiveSource::Mmap(Arc::new(mmap)),
+            &to_options(&args),
+            &mut |_| {},
+        )
+        .unwrap();
+
+        assert_eq!(report.extracted, 3);
+        assert_eq!(fs::read_to_string(temp_dir.path().join("out/c.txt")).unwrap(), "three");
+    }
+
+    #[test]
+    fn test_zip_extract_hooks_veto_and_rename_entries() {
+        struct Policy(Mutex<Vec<(String, Option<SkipReason>)>>);
+
+        impl EntryHooks for Policy {
+            fn before_entry(&self, entry: &EntryMeta<'_>) -> EntryAction {
+                match entry.name {
+                    "bad.exe" => EntryAction::Skip,
+                    "docs/a.txt" => EntryAction::Rename(PathBuf::from("renamed/a.txt")),
+                    "c.txt" => EntryAction::Rename(PathBuf::from("../escape.txt")),
+                    _ => EntryAction::Extract,
+                }
+            }
+
+            fn after_entry(&self, name: &str, outcome: &EntryOutcome<'_>) {
+                let reason = match outcome {
+                    EntryOutcome::Extracted { .. } => None,
+                    EntryOutcome::Skipped(reason) => Some(*reason),
+                };
+                self.0.lock().unwrap().push((name.to_string(), reason));
+            }
+        }
+
+        let zip_data = create_test_zip(&[
+            ("docs/a.txt", b"one"),
+            ("bad.exe", b"two"),
+            ("c.txt", b"three"),
+            ("d.log", b"four"),
+        ]);
+        let temp_dir = tempfile::tempdir().unwrap();
+        let zip_path = temp_dir.path().join("test.zip");
+        fs::write(&zip_path, zip_data).unwrap();
+
+        for threads in [1, 2] {
+            let output_dir = temp_dir.path().join(format!("out{}", threads));
+            let policy = Arc::new(Policy(Mutex::default()));
+            let options = ExtractOptions::new()
+                .output_dir(&output_dir)
+                .threads(threads)
+                .quiet(2)
+                .exclude(["*.log"])
+                .shared_hooks(policy.clone());
+            extract_archive_threaded(ArchiveSource::FilePath(zip_path.clone()), &options).unwrap();
+            assert_eq!(fs::read(output_dir.join("renamed/a.txt")).unwrap(), b"one");
+            assert!(!output_dir.join("bad.exe").exists());
+
+            let mut outcomes = policy.0.lock().unwrap().clone();
+            outcomes.sort_by(|a, b| a.0.cmp(&b.0));
+            assert_eq!(
+                outcomes,
+                [
+                    ("bad.exe".to_string(), Some(SkipReason::Vetoed)),
+                    ("c.txt".to_string(), Some(SkipReason::UnsafePath)),
+                    ("d.log".to_string(), Some(SkipReason::Filtered)),
+                    ("docs/a.txt".to_string(), None),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_zip_extract_mmap_rejects_overlapping_entries() {
+        let mut zip_data = create_test_zip(&[("a.txt", b"one"), ("b.txt", b"two")]);
+        // Point the second central directory record at the first local header
+        let central = zip_data.windows(4).rposition(|w| w == b"PK\x01\x02").unwrap();
+        zip_data[central + 42..central + 46].copy_from_slice(&0u32.to_le_bytes());
+        let temp_dir = tempfile::tempdir().unwrap();
+        let zip_path = temp_dir.path().join("test.zip");
+        fs::write(&zip_path, zip_data).unwrap();
+        let mmap = Arc::new(unsafe { Mmap::map(&File::open(&zip_path).unwrap()) }.unwrap());
+
+        for threads in [1, 2] {
+            let output_dir = temp_dir.path().join(format!("out{}", threads));
+            let options = ExtractOptions::new().output_dir(&output_dir).threads(threads).quiet(2);
+            let source = ArchiveSource::Mmap(Arc::clone(&mmap));
+            let error = extract_archive_threaded_with(source, &options, &mut |_| {}).unwrap_err();
+            assert!(matches!(error, UnzipError::OverlappingEntry { .. }));
+            assert!(!output_dir.exists());
+        }
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_zip_extract_text_mode_uses_text_flag() {
+        let mut zip_data =
+            create_test_zip(&[("text.txt", b"a\r\nb\r\n"), ("data.bin", b"a\r\nb\r\n")]);
+        // Flag only the first entry as text in its central directory header
+        let central = zip_data.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+        zip_data[central + 36] = 0x01;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let zip_path = temp_dir.path().join("test.zip");
+        fs::write(&zip_path, zip_data).unwrap();
+
+        let out_dir = temp_dir.path().join("out");
+        let mut args = default_args();
+        args.output_dir = Some(out_dir.clone());
+        args.text_mode = 1;
+
+        extract_archive_threaded(ArchiveSource::FilePath(zip_path), &to_options(&args)).unwrap();
+
+        assert_eq!(fs::read(out_dir.join("text.txt")).unwrap(), b"a\nb\n");
+        assert_eq!(fs::read(out_dir.join("data.bin")).unwrap(), b"a\r\nb\r\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_zip_extract_windows_attributes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut zip_data = create_test_zip(&[("locked.txt", b"a"), ("plain.txt", b"b")]);
+        // Rewrite both central headers as made on NTFS with MS-DOS attributes only
+        let headers: Vec<usize> = zip_data
+            .windows(4)
+            .enumerate()
+            .filter(|(_, w)| *w == b"PK\x01\x02")
+            .map(|(pos, _)| pos)
+            .collect();
+        for (header, attributes) in headers.into_iter().zip([0x21u32, 0x20]) {
+            zip_data[header + 5] = 10;
+            zip_data[header + 38..header + 42].copy_from_slice(&attributes.to_le_bytes());
+        }
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut args = default_args();
+        args.output_dir = Some(temp_dir.path().to_path_buf());
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+        extract_archive(&mut archive, &to_options(&args)).unwrap();
+
+        let mode = |name: &str| {
+            fs::metadata(temp_dir.path().join(name)).unwrap().permissions().mode() & 0o777
+        };
+        assert_eq!(mode("locked.txt"), 0o444);
+        // Default permissions rather than 0000 or executable
+        assert_ne!(mode("plain.txt") & 0o600, 0);
+        assert_eq!(mode("plain.txt") & 0o111, 0);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_zip_extract_text_mode_all_and_content_guess() {
+        let zip_data = create_test_zip(&[("text.txt", b"a\r\nb"), ("data.bin", b"\0\r\n")]);
+
+        for (count, expected_bin) in [(1u8, &b"\0\r\n"[..]), (2u8, &b"\0\n"[..])] {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut archive = ZipArchive::new(Cursor::new(zip_data.clone())).unwrap();
+
+            let mut args = default_args();
+            args.output_dir = Some(temp_dir.path().to_path_buf());
+            args.text_mode = count;
+
+            extract_archive(&mut archive, &to_options(&args)).unwrap();
+
+            assert_eq!(fs::read(temp_dir.path().join("text.txt")).unwrap(), b"a\nb");
+            assert_eq!(fs::read(temp_dir.path().join("data.bin")).unwrap(), expected_bin);
+        }
+    }
+
+    #[test]
+    fn test_zip_extract_flatten_long_paths() {
+        use crate::flatten::{MANIFEST_NAME, STORE_DIR};
+
+        let long_dir = format!("pkg/{}/", "d".repeat(300));
+        let long_file = format!("{}Gen.java", long_dir);
+        let zip_data = create_test_zip(&[
+            (long_dir.as_str(), &[]),
+            (long_file.as_str(), b"class Gen {}"),
+            ("short.txt", b"short"),
+        ]);
+
+        for threads in [1, 2] {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let zip_path = temp_dir.path().join("test.zip");
+            fs::write(&zip_path, &zip_data).unwrap();
+            let out_dir = temp_dir.path().join("out");
+
+            let mut args = default_args();
+            args.output_dir = Some(out_dir.clone());
+            args.flatten_long_paths = true;
+            args.threads = Some(threads);
+
+            let report = extract_archive_threaded_with(
+                ArchiveSource::FilePath(zip_path),
+                &to_options(&args),
+                &mut |_| {},
+            )
+            .unwrap();
+
+            assert_eq!(report.extracted, 2);
+            assert_eq!(fs::read_to_string(out_dir.join("short.txt")).unwrap(), "short");
+            let manifest = fs::read_to_string(out_dir.join(STORE_DIR).join(MANIFEST_NAME)).unwrap();
+            let (flat_name, intended) = manifest.trim_end().split_once('\t').unwrap();
+            assert_eq!(intended, out_dir.join(&long_file).to_string_lossy());
+            assert!(flat_name.ends_with(".java"));
+            assert_eq!(
+                fs::read_to_string(out_dir.join(STORE_DIR).join(flat_name)).unwrap(),
+                "class Gen {}"
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_zip_extract_restores_directory_metadata() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mtime = zip::DateTime::from_date_and_time(2020, 1, 2, 3, 4, 6).unwrap();
+        let options = SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored)
+            .last_modified_time(mtime);
+        let mut buf = Vec::new();
+        {
+            let mut zip = ZipWriter::new(Cursor::new(&mut buf));
+            zip.add_directory("outer/", options.unix_permissions(0o755)).unwrap();
+            zip.add_directory("outer/inner/", options.unix_permissions(0o750)).unwrap();
+            zip.start_file("outer/inner/file.txt", options).unwrap();
+            zip.write_all(b"data").unwrap();
+            zip.finish().unwrap();
+        }
+        let expected = datetime_to_filetime(mtime);
+
+        for (level, dir_restored, file_restored) in
+            [(0u8, true, true), (1, false, true), (2, false, false)]
+        {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut archive = ZipArchive::new(Cursor::new(buf.clone())).unwrap();
+            let mut args = default_args();
+            args.output_dir = Some(temp_dir.path().to_path_buf());
+            args.no_timestamps = level;
+
+            extract_archive(&mut archive, &to_options(&args)).unwrap();
+
+            let inner = temp_dir.path().join("outer/inner");
+            let inner_meta = fs::metadata(&inner).unwrap();
+            assert_eq!(inner_meta.permissions().mode() & 0o777, 0o750);
+            let dir_mtime = filetime::FileTime::from_last_modification_time(&inner_meta);
+            assert_eq!(dir_mtime == expected, dir_restored, "level {}", level);
+            let file_meta = fs::metadata(inner.join("file.txt")).unwrap();
+            let file_mtime = filetime::FileTime::from_last_modification_time(&file_meta);
+            assert_eq!(file_mtime == expected, file_restored, "level {}", level);
+        }
+    }
+
+    #[test]
+    fn test_zip_extract_stamp_output_dir() {
+        use crate::dirstamp::{OutputDirStamp, StampSource};
+
+        let mut buf = Vec::new();
+        {
+            let mut zip = ZipWriter::new(Cursor::new(&mut buf));
+            for (name, year) in [("old.txt", 2001), ("dir/new.txt", 2021), ("mid.txt", 2011)] {
+                let mtime = zip::DateTime::from_date_and_time(year, 1, 2, 3, 4, 6).unwrap();
+                zip.start_file(name, SimpleFileOptions::default().last_modified_time(mtime))
+                    .unwrap();
+                zip.write_all(name.as_bytes()).unwrap();
+            }
+            zip.finish().unwrap();
+        }
+        let newest = zip::DateTime::from_date_and_time(2021, 1, 2, 3, 4, 6).unwrap();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let zip_path = temp_dir.path().join("test.zip");
+        fs::write(&zip_path, buf).unwrap();
+        let archive_time = FileTime::from_unix_time(1_500_000_000, 0);
+        filetime::set_file_mtime(&zip_path, archive_time).unwrap();
+        let stamp = OutputDirStamp::from_source(StampSource::Archive, &zip_path).unwrap();
+
+        for threads in [1, 2] {
+            for (stamp, expected) in
+                [(stamp, archive_time), (OutputDirStamp::Newest, datetime_to_filetime(newest))]
+            {
+                let output_dir = temp_dir.path().join(format!("out{}", threads));
+                let options = ExtractOptions::new()
+                    .output_dir(&output_dir)
+                    .threads(threads)
+                    .overwrite(OverwritePolicy::Always)
+                    .stamp_output_dir(Some(stamp))
+                    .quiet(2);
+                let source = ArchiveSource::FilePath(zip_path.clone());
+                extract_archive_threaded_with(source, &options, &mut |_| {}).unwrap();
+                let metadata = fs::metadata(&output_dir).unwrap();
+                let stamped = FileTime::from_last_modification_time(&metadata);
+                assert_eq!(stamped, expected, "{} threads, {:?}", threads, stamp);
+            }
+        }
+    }
+
+    #[test]
+    fn test_zip_extract_implied_dir_times() {
+        let mut buf = Vec::new();
+        {
+            let mut zip = ZipWriter::new(Cursor::new(&mut buf));
+            let at = |year| {
+                let mtime = zip::DateTime::from_date_and_time(year, 1, 2, 3, 4, 6).unwrap();
+                SimpleFileOptions::default().last_modified_time(mtime)
+            };
+            zip.add_directory("e/", at(2010)).unwrap();
+            for (name, year) in [("a/old.txt", 2001), ("a/b/new.txt", 2021), ("e/f.txt", 2015)] {
+                zip.start_file(name, at(year)).unwrap();
+                zip.write_all(name.as_bytes()).unwrap();
+            }
+            zip.finish().unwrap();
+        }
+        let time = |year| {
+            datetime_to_filetime(zip::DateTime::from_date_and_time(year, 1, 2, 3, 4, 6).unwrap())
+        };
+        let temp_dir = tempfile::tempdir().unwrap();
+        let zip_path = temp_dir.path().join("test.zip");
+        fs::write(&zip_path, buf).unwrap();
+
+        for threads in [1, 2] {
+            for (policy, expected) in [
+                (ImpliedDirTimes::Newest, time(2021)),
+                (ImpliedDirTimes::Fixed(42), FileTime::from_unix_time(42, 0)),
+            ] {
+                let output_dir = temp_dir.path().join(format!("out{}-{}", threads, policy));
+                let options = ExtractOptions::new()
+                    .output_dir(&output_dir)
+                    .threads(threads)
+                    .implied_dir_times(policy)
+                    .quiet(2);
+                let source = ArchiveSource::FilePath(zip_path.clone());
+                extract_archive_threaded_with(source, &options, &mut |_| {}).unwrap();
+                let mtime = |dir: &str| {
+                    let metadata = fs::metadata(output_dir.join(dir)).unwrap();
+                    FileTime::from_last_modification_time(&metadata)
+                };
+                assert_eq!((mtime("a/b"), mtime("a")), (expected, expected), "{}", policy);
+                assert_eq!(mtime("e"), time(2010));
+            }
+        }
+    }
+
+    #[test]
+    fn test_zip_extract_restores_extended_timestamps() {
+        let mut data = vec![0x03];
+        data.extend_from_slice(&1_577_934_245u32.to_le_bytes());
+        data.extend_from_slice(&1_577_934_300u32.to_le_bytes());
+        let mut options = zip::write::FileOptions::<zip::write::ExtendedFileOptions>::default()
+            .compression_method(zip::CompressionMethod::Stored)
+            .last_modified_time(zip::DateTime::from_date_and_time(2020, 1, 2, 3, 4, 6).unwrap());
+        options.add_extra_data(0x5455, data.into_boxed_slice(), true).unwrap();
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("file.txt", options).unwrap();
+        zip.write_all(b"data").unwrap();
+        let mut archive = ZipArchive::new(zip.finish().unwrap()).unwrap();
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut args = default_args();
+        args.output_dir = Some(temp_dir.path().to_path_buf());
+        extract_archive(&mut archive, &to_options(&args)).unwrap();
+
+        let meta = fs::metadata(temp_dir.path().join("file.txt")).unwrap();
+        assert_eq!(
+            FileTime::from_last_modification_time(&meta),
+            FileTime::from_unix_time(1_577_934_245, 0)
+        );
+        assert_eq!(
+            FileTime::from_last_access_time(&meta),
+            FileTime::from_unix_time(1_577_934_300, 0)
+        );
+    }
+
+    #[test]
+    fn test_extract_to_vec_applies_filters_and_name_policy() {
+        let zip_data = create_test_zip(&[
+            ("dir/", b""),
+            ("dir/a.txt", b"alpha"),
+            ("dir/b.log", b"log"),
+            ("../evil.txt", b"evil"),
+            ("./dir//c.txt", b"gamma"),
+        ]);
+        let mut args = default_args();
+        args.exclude = vec!["dir/*.log".to_string()];
+
+        let mut archive = ZipArchive::new(Cursor::new(zip_data.clone())).unwrap();
+        let files = extract_to_vec(&mut archive, &to_options(&args)).unwrap();
+        assert_eq!(files.keys().collect::<Vec<_>>(), ["dir/a.txt", "dir/c.txt"]);
+        assert_eq!(files["dir/c.txt"], b"gamma");
+
+        args.junk_paths = true;
+        args.max_entries = Some(10);
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+        let files = extract_to_vec(&mut archive, &to_options(&args)).unwrap();
+        assert_eq!(files.keys().collect::<Vec<_>>(), ["a.txt", "c.txt"]);
+    }
+
+    #[test]
+    fn test_extract_to_memory_keeps_a_set_output_limit() {
+        let zip_data = create_test_zip(&[("a.txt", b"alpha"), ("b/c.txt", b"gamma")]);
+        let mut args = default_args();
+
+        let mut archive = ZipArchive::new(Cursor::new(zip_data.clone())).unwrap();
+        let files = extract_to_memory(&mut archive, &to_options(&args)).unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files["b/c.txt"], b"gamma");
+
+        args.max_output_size = Some(8);
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+        let error = extract_to_memory(&mut archive, &to_options(&args)).unwrap_err();
+        assert!(matches!(error, UnzipError::Limit(LimitExceeded::OutputSize { .. })));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_zip_extract_refuses_symlink_escape() {
+        let zip_data = create_test_zip(&[("link/evil.txt", b"pwned")]);
+        let temp_dir = tempfile::tempdir().unwrap();
+        let out_dir = temp_dir.path().join("out");
+        let outside = temp_dir.path().join("outside");
+        fs::create_dir_all(&out_dir).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, out_dir.join("link")).unwrap();
+
+        let mut args = default_args();
+        args.output_dir = Some(out_dir.clone());
+        let mut archive = ZipArchive::new(Cursor::new(zip_data.clone())).unwrap();
+        assert!(extract_archive(&mut archive, &to_options(&args)).is_err());
+        assert!(!outside.join("evil.txt").exists());
+
+        args.follow_symlinks = true;
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+        extract_archive(&mut archive, &to_options(&args)).unwrap();
+        assert!(outside.join("evil.txt").exists());
+    }
+
+    #[test]
+    fn test_zip_extract_enforces_output_limits() {
+        let zip_data = create_test_zip(&[("a.bin", &[0u8; 4096]), ("b.bin", &[0u8; 4096])]);
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut args = default_args();
+        args.output_dir = Some(temp_dir.path().to_path_buf());
+
+        args.max_entries = Some(1);
+        let mut archive = ZipArchive::new(Cursor::new(zip_data.clone())).unwrap();
+        let err = extract_archive(&mut archive, &to_options(&args)).unwrap_err();
+        assert!(err.to_string().contains("--max-entries"), "{}", err);
+        assert!(!temp_dir.path().join("a.bin").exists());
+
+        args.max_entries = None;
+        args.max_output_size = Some(6000);
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+        let err = extract_archive(&mut archive, &to_options(&args)).unwrap_err();
+        assert!(err.to_string().contains("--max-output-size"), "{}", err);
+        assert!(matches!(err, UnzipError::Limit(LimitExceeded::OutputSize { limit: 6000 })));
+    }
+
+    #[test]
+    fn test_zip_extract_checks_expectations_first() {
+        let zip_data = create_test_zip(&[("a.bin", &[0u8; 4096]), ("b.log", b"log")]);
+        let temp_dir = tempfile::tempdir().unwrap();
+        let zip_path = temp_dir.path().join("test.zip");
+        fs::write(&zip_path, &zip_data).unwrap();
+        let mut args = default_args();
+        args.output_dir = Some(temp_dir.path().join("out"));
+        args.expect_files = Some(Expected::exact(2));
+        args.exclude = vec!["*.log".to_string()];
+
+        let mut archive = ZipArchive::new(Cursor::new(zip_data.clone())).unwrap();
+        let err = extract_archive(&mut archive, &to_options(&args)).unwrap_err();
+        assert!(err.to_string().contains("--expect-files"), "{}", err);
+        assert!(!temp_dir.path().join("out").exists());
+
+        args.threads = Some(2);
+        let source = ArchiveSource::FilePath(zip_path.clone());
+        let err = extract_archive_threaded(source, &to_options(&args)).unwrap_err();
+        assert!(matches!(err, UnzipError::Expectation(_)), "{:?}", err);
+        assert!(!temp_dir.path().join("out/a.bin").exists());
+
+        args.expect_files = Some(Expected::exact(1));
+        args.expect_bytes = Some(Expected { value: 4000, tolerance: 100 });
+        let source = ArchiveSource::FilePath(zip_path);
+        extract_archive_threaded(source, &to_options(&args)).unwrap();
+        assert!(temp_dir.path().join("out/a.bin").exists());
+    }
+
+    #[test]
+    fn test_zip_extract_smart_dir() {
+        let tidy = create_test_zip(&[("app/", b""), ("app/bin/run", b"run"), ("app/README", b"r")]);
+        let loose = create_test_zip(&[("README", b"r"), ("src/main.rs", b"fn main() {}")]);
+
+        for threads in [1, 2] {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let output_dir = temp_dir.path().join("out");
+            let options =
+                ExtractOptions::new().output_dir(&output_dir).smart_dir("drop").threads(threads);
+            let extract = |zip_data: &[u8], options: &ExtractOptions| {
+                let zip_path = temp_dir.path().join("test.zip");
+                fs::write(&zip_path, zip_data).unwrap();
+                extract_archive_threaded(ArchiveSource::FilePath(zip_path), options).unwrap();
+            };
+
+            extract(&tidy, &options);
+            assert!(output_dir.join("app/bin/run").exists());
+            assert!(!output_dir.join("drop").exists());
+
+            extract(&loose, &options);
+            assert!(output_dir.join("drop/README").exists());
+            assert!(output_dir.join("drop/src/main.rs").exists());
+            assert!(!output_dir.join("README").exists());
+
+            // Only src/ is selected, which is a single top-level directory
+            extract(&loose, &options.clone().patterns(["src/*"]));
+            assert!(output_dir.join("src/main.rs").exists());
+        }
+    }
+
+    #[test]
+    fn test_zip_extract_members_exactly() {
+        let zip_data = create_test_zip(&[
+            ("docs/a*.txt", b"star"),
+            ("docs/ab.txt", b"ab"),
+            ("docs/notes.log", b"log"),
+        ]);
+        let temp_dir = tempfile::tempdir().unwrap();
+        let manifest = temp_dir.path().join("manifest");
+        let mut args = default_args();
+        args.output_dir = Some(temp_dir.path().join("out"));
+        args.members_from = Some(manifest.clone());
+
+        fs::write(&manifest, "docs/a*.txt\ndocs/missing.txt\n").unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(zip_data.clone())).unwrap();
+        let err = extract_archive(&mut archive, &to_options(&args)).unwrap_err();
+        assert!(matches!(&err, UnzipError::MissingMembers(m) if m.names == ["docs/missing.txt"]));
+        assert!(!temp_dir.path().join("out").exists());
+
+        fs::write(&manifest, "docs/a*.txt\n").unwrap();
+        args.patterns = vec!["*/*.log".to_string()];
+        extract_archive(&mut archive, &to_options(&args)).unwrap();
+        assert_eq!(fs::read(temp_dir.path().join("out/docs/a*.txt")).unwrap(), b"star");
+        assert!(temp_dir.path().join("out/docs/notes.log").exists());
+        assert!(!temp_dir.path().join("out/docs/ab.txt").exists());
+    }
+
+    #[test]
+    fn test_zip_extract_refuses_self_overwrite() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        fs::create_dir(temp_dir.path().join("dist")).unwrap();
+        let zip_path = temp_dir.path().join("dist/app.zip");
+        let zip_data = create_test_zip(&[("dist/readme.txt", b"hello"), ("dist/app.zip", b"old")]);
+        fs::write(&zip_path, &zip_data).unwrap();
+        let mut args = default_args();
+        args.zipfile = zip_path.clone();
+        // The output directory one level too high, through the archive's own
+        args.output_dir = Some(temp_dir.path().join("dist/.."));
+        args.overwrite = true;
+
+        for threads in [1, 2] {
+            args.threads = Some(threads);
+            let source = ArchiveSource::FilePath(zip_path.clone());
+            let err = extract_archive_threaded(source, &to_options(&args)).unwrap_err();
+            assert!(
+                matches!(&err, UnzipError::SelfOverwrite(e) if e.problems.len() == 1),
+                "{}",
+                err
+            );
+            assert_eq!(fs::read(&zip_path).unwrap(), zip_data);
+            assert!(!temp_dir.path().join("dist/readme.txt").exists());
+        }
+
+        // -n never replaces the archive
+        args.never_overwrite = true;
+        let mut archive = ZipArchive::new(File::open(&zip_path).unwrap()).unwrap();
+        extract_archive(&mut archive, &to_options(&args)).unwrap();
+        assert_eq!(fs::read(&zip_path).unwrap(), zip_data);
+        assert_eq!(fs::read(temp_dir.path().join("dist/readme.txt")).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_zip_extract_opendoc_check() {
+        let container = br#"<container><rootfiles><rootfile full-path="OEBPS/content.opf"/></rootfiles></container>"#;
+        let temp_dir = tempfile::tempdir().unwrap();
+        let zip_path = temp_dir.path().join("book.epub");
+        let mut args = default_args();
+        args.output_dir = Some(temp_dir.path().join("out"));
+        args.opendoc_check = true;
+
+        let broken = create_test_zip(&[
+            ("META-INF/container.xml", container),
+            ("mimetype", b"application/epub+zip"),
+        ]);
+        fs::write(&zip_path, &broken).unwrap();
+        for threads in [1, 2] {
+            args.threads = Some(threads);
+            let source = ArchiveSource::FilePath(zip_path.clone());
+            let err = extract_archive_threaded(source, &to_options(&args)).unwrap_err();
+            assert!(matches!(&err, UnzipError::InvalidContainer(c) if c.problems.len() == 2));
+            assert!(!temp_dir.path().join("out").exists());
+        }
+
+        let book = create_test_zip(&[
+            ("mimetype", b"application/epub+zip"),
+            ("META-INF/container.xml", container),
+            ("OEBPS/content.opf", b"<package/>"),
+        ]);
+        let mut archive = ZipArchive::new(Cursor::new(book)).unwrap();
+        extract_archive(&mut archive, &to_options(&args)).unwrap();
+        assert_eq!(
+            fs::read(temp_dir.path().join("out/mimetype")).unwrap(),
+            b"application/epub+zip"
+        );
+        assert!(temp_dir.path().join("out/OEBPS/content.opf").exists());
+    }
+
+    #[test]
+    fn test_zip_extract_threaded_wide_directory() {
+        let names: Vec<String> = (0..40).map(|i| format!("wide/dir/{}.txt", i)).collect();
+        let mut files: Vec<(&str, &[u8])> = names.iter().map(|n| (n.as_str(), &b"x"[..])).collect();
+        files.push(("other/one.txt", b"one"));
+        let zip_data = create_test_zip(&files);
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let zip_path = temp_dir.path().join("test.zip");
+        fs::write(&zip_path, zip_data).unwrap();
+
+        let output_dir = temp_dir.path().join("out");
+        let mut args = default_args();
+        args.output_dir = Some(output_dir.clone());
+        args.quiet = 2;
+        args.threads = Some(4);
+
+        extract_archive_threaded(ArchiveSource::FilePath(zip_path), &to_options(&args)).unwrap();
+
+        assert_eq!(fs::read_dir(output_dir.join("wide/dir")).unwrap().count(), 40);
+        assert_eq!(fs::read(output_dir.join("other/one.txt")).unwrap(), b"one");
+    }
+
+    #[test]
+    fn test_zip_extract_transactional_commits() {
+        let zip_data = create_test_zip(&[("docs/", b""), ("docs/a.txt", b"new"), ("b.txt", b"b")]);
+        let temp_dir = tempfile::tempdir().unwrap();
+        fs::write(temp_dir.path().join("b.txt"), b"old").unwrap();
+        let options = ExtractOptions::new()
+            .output_dir(temp_dir.path())
+            .overwrite(OverwritePolicy::Always)
+            .transactional(true)
+            .quiet(2);
+
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+        extract_archive(&mut archive, &options).unwrap();
+
+        assert_eq!(fs::read(temp_dir.path().join("docs/a.txt")).unwrap(), b"new");
+        assert_eq!(fs::read(temp_dir.path().join("b.txt")).unwrap(), b"b");
+        // Neither the staging directory nor backups are left behind
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_zip_extract_verify() {
+        let zip_data = create_test_zip(&[("dos.txt", b"a\r\nb\r\n"), ("sub/c.bin", b"c")]);
+        let temp_dir = tempfile::tempdir().unwrap();
+        let zip_path = temp_dir.path().join("test.zip");
+        fs::write(&zip_path, &zip_data).unwrap();
+
+        // Converted text verifies against the data written, not the entry's CRC32
+        let serial_dir = temp_dir.path().join("serial");
+        let options = ExtractOptions::new()
+            .output_dir(&serial_dir)
+            .text_mode(TextMode::All)
+            .verify(true)
+            .quiet(2);
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+        extract_archive(&mut archive, &options).unwrap();
+        assert_eq!(fs::read(serial_dir.join("dos.txt")).unwrap(), b"a\nb\n");
+
+        let mut args = default_args();
+        args.output_dir = Some(temp_dir.path().join("threaded"));
+        args.threads = Some(2);
+        args.verify = true;
+        extract_archive_threaded(ArchiveSource::FilePath(zip_path), &to_options(&args)).unwrap();
+        assert_eq!(fs::read(temp_dir.path().join("threaded/sub/c.bin")).unwrap(), b"c");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_zip_extract_scan_socket() {
+        let files: [(&str, &[u8]); 3] =
+            [("a.txt", b"clean"), ("bin/x.exe", b"X5O!P%@AP EICAR"), ("b.txt", b"fine")];
+        let zip_data = create_test_zip(&files);
+        let temp_dir = tempfile::tempdir().unwrap();
+        let socket = crate::scan::fake_scanner(temp_dir.path());
+        let zip_path = temp_dir.path().join("test.zip");
+        fs::write(&zip_path, &zip_data).unwrap();
+
+        let out = temp_dir.path().join("serial");
+        let jail = temp_dir.path().join("quarantine");
+        let options = ExtractOptions::new()
+            .output_dir(&out)
+            .scan_socket(&socket)
+            .quarantine(&jail)
+            .quiet(2);
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+        let error = extract_archive(&mut archive, &options).unwrap_err();
+        assert!(matches!(error, UnzipError::Infected { entries: 1 }), "{}", error);
+        assert_eq!(fs::read(out.join("b.txt")).unwrap(), b"fine");
+        assert!(!out.join("bin/x.exe").exists());
+        assert_eq!(fs::read(jail.join("x.exe")).unwrap(), b"X5O!P%@AP EICAR");
+
+        // Files are only put in place once cleared, so an existing one is kept
+        fs::create_dir_all(temp_dir.path().join("threaded/bin")).unwrap();
+        fs::write(temp_dir.path().join("threaded/bin/x.exe"), b"original").unwrap();
+        let mut args = default_args();
+        args.output_dir = Some(temp_dir.path().join("threaded"));
+        args.threads = Some(2);
+        args.overwrite = true;
+        args.scan_socket = Some(socket);
+        let mut skipped = Vec::new();
+        let source = ArchiveSource::FilePath(zip_path);
+        let error = extract_archive_threaded_with(source, &to_options(&args), &mut |event| {
+            if let ExtractEvent::Skipped { name, reason } = event {
+                skipped.push((name, reason));
+            }
+        })
+        .unwrap_err();
+        assert!(matches!(error, UnzipError::Infected { entries: 1 }), "{}", error);
+        assert_eq!(skipped, [("bin/x.exe".to_string(), SkipReason::Infected)]);
+        assert!(temp_dir.path().join("threaded/a.txt").exists());
+        assert_eq!(fs::read(temp_dir.path().join("threaded/bin/x.exe")).unwrap(), b"original");
+        let names: Vec<_> = fs::read_dir(temp_dir.path().join("threaded/bin")).unwrap().collect();
+        assert_eq!(names.len(), 1);
+    }
+
+    #[test]
+    fn test_zip_extract_compress_output() {
+        let log = b"GET /index.html 200\n".repeat(500);
+        let zip_data = create_test_zip(&[("logs/app.log", &log), ("empty.txt", b"")]);
+        let temp_dir = tempfile::tempdir().unwrap();
+        let zip_path = temp_dir.path().join("test.zip");
+        fs::write(&zip_path, &zip_data).unwrap();
+        let compression = Some(OutputCompression::Zstd { level: 19 });
+
+        let serial_dir = temp_dir.path().join("serial");
+        let options = ExtractOptions::new()
+            .output_dir(&serial_dir)
+            .compress_output(compression)
+            .quiet(2);
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+        extract_archive(&mut archive, &options).unwrap();
+        assert!(!serial_dir.join("logs/app.log").exists());
+        let compressed = fs::read(serial_dir.join("logs/app.log.zst")).unwrap();
+        assert!(compressed.len() < log.len() / 10);
+        assert_eq!(zstd::decode_all(&compressed[..]).unwrap(), log);
+        let empty = fs::read(serial_dir.join("empty.txt.zst")).unwrap();
+        assert!(zstd::decode_all(&empty[..]).unwrap().is_empty());
+
+        // Existing .zst files count for -n, the raw names do not
+        let mut args = default_args();
+        args.output_dir = Some(temp_dir.path().join("threaded"));
+        args.threads = Some(2);
+        args.never_overwrite = true;
+        args.compress_output = compression;
+        fs::create_dir(temp_dir.path().join("threaded")).unwrap();
+        fs::write(temp_dir.path().join("threaded/empty.txt.zst"), b"old").unwrap();
+        extract_archive_threaded(ArchiveSource::FilePath(zip_path), &to_options(&args)).unwrap();
+        let compressed = fs::read(temp_dir.path().join("threaded/logs/app.log.zst")).unwrap();
+        assert_eq!(zstd::decode_all(&compressed[..]).unwrap(), log);
+        assert_eq!(fs::read(temp_dir.path().join("threaded/empty.txt.zst")).unwrap(), b"old");
+    }
+
+    #[test]
+    fn test_zip_extract_transactional_rolls_back() {
+        let zip_data = create_test_zip(&[
+            ("sub/a.bin", &[1u8; 4096]),
+            ("keep.txt", &[2u8; 4096]),
+            ("z.bin", &[3u8; 4096]),
+        ]);
+        let temp_dir = tempfile::tempdir().unwrap();
+        let zip_path = temp_dir.path().join("test.zip");
+        fs::write(&zip_path, zip_data).unwrap();
+
+        for threads in [1, 2] {
+            let output_dir = temp_dir.path().join(format!("out{}", threads));
+            fs::create_dir(&output_dir).unwrap();
+            fs::write(output_dir.join("keep.txt"), b"old").unwrap();
+
+            let mut args = default_args();
+            args.output_dir = Some(output_dir.clone());
+            args.overwrite = true;
+            args.quiet = 2;
+            args.threads = Some(threads);
+            args.max_output_size = Some(6000);
+            args.transactional = true;
+
+            let source = ArchiveSource::FilePath(zip_path.clone());
+            let err = extract_archive_threaded(source, &to_options(&args)).unwrap_err();
+            assert!(matches!(err, UnzipError::Limit(_)), "{}", err);
+            assert_eq!(fs::read(output_dir.join("keep.txt")).unwrap(), b"old");
+            assert_eq!(fs::read_dir(&output_dir).unwrap().count(), 1);
+        }
+    }
+
+    #[test]
+    fn test_zip_extract_update_prefilters_up_to_date_entries() {
+        let zip_data = create_test_zip(&[("a.txt", b"one"), ("sub/b.txt", b"two")]);
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut args = default_args();
+        args.output_dir = Some(temp_dir.path().to_path_buf());
+
+        let mut archive = ZipArchive::new(Cursor::new(zip_data.clone())).unwrap();
+        extract_archive(&mut archive, &to_options(&args)).unwrap();
+
+        // A second update run finds everything up to date before it starts
+        args.update = true;
+        let mut archive = ZipArchive::new(Cursor::new(zip_data.clone())).unwrap();
+        let mut events = Vec::new();
+        let report = extract_archive_serial(
+            &mut archive,
+            None,
+            &to_options(&args),
+            &Journal::default(),
+            None,
+            &mut |event| events.push(event),
+        )
+        .unwrap();
+
+        assert_eq!(report.extracted, 0);
+        assert_eq!(report.skipped, 2);
+        assert!(matches!(events[2], ExtractEvent::Started { total_entries: 0, .. }));
+        assert!(events[..2].iter().all(|event| matches!(
+            event,
+            ExtractEvent::Skipped { reason: SkipReason::UpToDate, .. }
+        )));
+
+        // Freshen skips files that are missing on disk without creating their directories
+        fs::remove_dir_all(temp_dir.path().join("sub")).unwrap();
+        args.update = false;
+        args.freshen = true;
+        args.threads = Some(2);
+        let zip_path = temp_dir.path().join("test.zip");
+        fs::write(&zip_path, zip_data).unwrap();
+        let report = extract_archive_threaded_with(
+            ArchiveSource::FilePath(zip_path),
+            &to_options(&args),
+            &mut |_| {},
+        )
+        .unwrap();
+
+        assert_eq!(report.skipped, 2);
+        assert!(!temp_dir.path().join("sub").exists());
+    }
+
+    #[test]
+    fn test_zip_extract_with_options_builder() {
+        let zip_data = create_test_zip(&[("keep.txt", b"new"), ("docs/skip.log", b"log")]);
+        let temp_dir = tempfile::tempdir().unwrap();
+        fs::write(temp_dir.path().join("keep.txt"), b"old").unwrap();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        let options = ExtractOptions::new()
+            .output_dir(temp_dir.path())
+            .overwrite(OverwritePolicy::Never)
+            .exclude(["**/*.log"])
+            .on_event(move |event| sink.lock().unwrap().push(event));
+
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+        extract_archive(&mut archive, &options).unwrap();
+
+        assert_eq!(fs::read(temp_dir.path().join("keep.txt")).unwrap(), b"old");
+        assert!(!temp_dir.path().join("docs/skip.log").exists());
+        let events = events.lock().unwrap();
+        assert!(events.contains(&ExtractEvent::Skipped {
+            name: "keep.txt".to_string(),
+            reason: SkipReason::Exists,
+        }));
+        assert!(matches!(events.last(), Some(ExtractEvent::Finished { .. })));
+    }
+
+    #[test]
+    fn test_zip_extract_if_changed() {
+        let zip_data = create_test_zip(&[("same.txt", b"same"), ("edited.txt", b"original")]);
+        for threads in [1, 2] {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let zip_path = temp_dir.path().join("test.zip");
+            fs::write(&zip_path, &zip_data).unwrap();
+            let out = temp_dir.path().join("out");
+            fs::create_dir(&out).unwrap();
+            fs::write(out.join("same.txt"), b"same").unwrap();
+            fs::write(out.join("edited.txt"), b"edited!!").unwrap();
+            let old = FileTime::from_unix_time(1_000_000_000, 0);
+            filetime::set_file_mtime(out.join("same.txt"), old).unwrap();
+            let mut args = default_args();
+            args.output_dir = Some(out.clone());
+            args.if_changed = true;
+            args.threads = Some(threads);
+
+            let mut events = Vec::new();
+            let source = ArchiveSource::FilePath(zip_path);
+            let report = extract_archive_threaded_with(source, &to_options(&args), &mut |event| {
+                events.push(event)
+            })
+            .unwrap();
+            assert_eq!((report.extracted, report.skipped), (1, 1));
+            assert!(events.contains(&ExtractEvent::Skipped {
+                name: "same.txt".to_string(),
+                reason: SkipReason::Unchanged,
+            }));
+            assert_eq!(fs::read(out.join("edited.txt")).unwrap(), b"original");
+            let mtime =
+                FileTime::from_last_modification_time(&out.join("same.txt").metadata().unwrap());
+            assert_eq!(mtime, old);
+        }
+    }
+
+    #[test]
+    fn test_zip_extract_patch_in_place() {
+        let image: Vec<u8> = (0..3 * crate::patch::BLOCK_SIZE).map(|i| (i % 253) as u8).collect();
+        let zip_data = create_test_zip(&[("disk.img", &image), ("notes.txt", b"new notes")]);
+        for threads in [1, 2] {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let zip_path = temp_dir.path().join("test.zip");
+            fs::write(&zip_path, &zip_data).unwrap();
+            let out = temp_dir.path().join("out");
+            fs::create_dir(&out).unwrap();
+            let mut old = image.clone();
+            old[crate::patch::BLOCK_SIZE + 7] ^= 0xff;
+            fs::write(out.join("disk.img"), &old).unwrap();
+            fs::write(out.join("notes.txt"), b"old").unwrap();
+            let mut args = default_args();
+            args.output_dir = Some(out.clone());
+            args.overwrite = true;
+            args.patch_in_place = true;
+            args.threads = Some(threads);
+
+            let source = ArchiveSource::FilePath(zip_path);
+            let report =
+                extract_archive_threaded_with(source, &to_options(&args), &mut |_| {}).unwrap();
+            assert_eq!(report.extracted, 2);
+            assert_eq!(fs::read(out.join("disk.img")).unwrap(), image);
+            assert_eq!(fs::read(out.join("notes.txt")).unwrap(), b"new notes");
+        }
+    }
+
+    #[test]
+    fn test_pipe_entries_with_banners() {
+        let mut buf = Vec::new();
+        {
+            let mut zip = ZipWriter::new(Cursor::new(&mut buf));
+            let stored =
+                SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+            zip.start_file("a.txt", stored).unwrap();
+            zip.write_all(b"one\r\ntwo\r\n").unwrap();
+            zip.start_file("b.txt", SimpleFileOptions::default()).unwrap();
+            zip.write_all(b"three\r\n").unwrap();
+            zip.finish().unwrap();
+        }
+        let pipe = |options: ExtractOptions| {
+            let mut archive = ZipArchive::new(Cursor::new(buf.clone())).unwrap();
+            let mut out = Vec::new();
+            pipe_entries(&mut archive, &options, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!(pipe(ExtractOptions::new()), "one\r\ntwo\r\nthree\r\n");
+        // Conversion is for -c only
+        assert_eq!(pipe(ExtractOptions::new().text_mode(TextMode::All)), "one\r\ntwo\r\nthree\r\n");
+        assert_eq!(
+            pipe(ExtractOptions::new().pipe_banners(true)),
+            " extracting: a.txt\none\r\ntwo\r\n  inflating: b.txt\nthree\r\n"
+        );
+        #[cfg(not(windows))]
+        assert_eq!(
+            pipe(ExtractOptions::new().pipe_banners(true).text_mode(TextMode::All).quiet(1)),
+            "one\ntwo\nthree\n"
+        );
+    }
+
+    #[test]
+    fn test_zip_extract_force_readonly() {
+        let zip_data = create_test_zip(&[("locked.txt", b"new"), ("plain.txt", b"new")]);
+        for threads in [1, 2] {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let zip_path = temp_dir.path().join("test.zip");
+            fs::write(&zip_path, &zip_data).unwrap();
+            let out = temp_dir.path().join("out");
+            fs::create_dir(&out).unwrap();
+            fs::write(out.join("locked.txt"), b"old").unwrap();
+            let mut permissions = fs::metadata(out.join("locked.txt")).unwrap().permissions();
+            permissions.set_readonly(true);
+            fs::set_permissions(out.join("locked.txt"), permissions).unwrap();
+            let mut args = default_args();
+            args.output_dir = Some(out.clone());
+            args.overwrite = true;
+            args.threads = Some(threads);
+
+            let mut events = Vec::new();
+            let source = ArchiveSource::FilePath(zip_path.clone());
+            let report = extract_archive_threaded_with(source, &to_options(&args), &mut |event| {
+                events.push(event)
+            })
+            .unwrap();
+            assert_eq!((report.extracted, report.skipped), (1, 1));
+            assert!(events.contains(&ExtractEvent::Skipped {
+                name: "locked.txt".to_string(),
+                reason: SkipReason::ReadOnly,
+            }));
+            assert_eq!(fs::read(out.join("locked.txt")).unwrap(), b"old");
+
+            args.force_readonly = true;
+            let source = ArchiveSource::FilePath(zip_path);
+            let report =
+                extract_archive_threaded_with(source, &to_options(&args), &mut |_| {}).unwrap();
+            assert_eq!((report.extracted, report.skipped), (2, 0));
+            assert_eq!(fs::read(out.join("locked.txt")).unwrap(), b"new");
+            assert!(fs::metadata(out.join("locked.txt")).unwrap().permissions().readonly());
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_zip_extract_chown() {
+        use std::os::unix::fs::MetadataExt;
+
+        let zip_data =
+            create_test_zip(&[("docs/", b""), ("docs/a.txt", b"a"), ("src/b.txt", b"b")]);
+        for threads in [1, 2] {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let zip_path = temp_dir.path().join("test.zip");
+            fs::write(&zip_path, &zip_data).unwrap();
+            // Another owner where the tests may give files away, our own otherwise
+            let meta = fs::metadata(&zip_path).unwrap();
+            let (uid, gid) = if std::os::unix::fs::lchown(&zip_path, Some(4321), Some(4322)).is_ok()
+            {
+                (4321, 4322)
+            } else {
+                (meta.uid(), meta.gid())
+            };
+            let out = temp_dir.path().join("out/app");
+            let mut args = default_args();
+            args.output_dir = Some(out.clone());
+            args.chown = Some(crate::owner::parse_owner(&format!("{}:{}", uid, gid)).unwrap());
+            args.threads = Some(threads);
+
+            let source = ArchiveSource::FilePath(zip_path);
+            extract_archive_threaded_with(source, &to_options(&args), &mut |_| {}).unwrap();
+            for path in ["..", "", "docs", "docs/a.txt", "src", "src/b.txt"] {
+                let meta = fs::metadata(out.join(path)).unwrap();
+                assert_eq!((meta.uid(), meta.gid()), (uid, gid), "{}", path);
+            }
+        }
+    }
+
+    #[test]
+    fn test_zip_extract_priority_first() {
+        let zip_data = create_test_zip(&[
+            ("data/0.bin", b"0"),
+            ("data/1.bin", b"1"),
+            ("app/manifest.json", b"{}"),
+            ("data/2.bin", b"2"),
+            ("data.index", b"i"),
+        ]);
+        for threads in [1, 2] {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let zip_path = temp_dir.path().join("test.zip");
+            fs::write(&zip_path, &zip_data).unwrap();
+            let mut args = default_args();
+            args.output_dir = Some(temp_dir.path().join("out"));
+            args.priority = vec!["manifest.json".to_string(), "*.index".to_string()];
+            args.threads = Some(threads);
+
+            let mut done = Vec::new();
+            let source = ArchiveSource::FilePath(zip_path);
+            let report = extract_archive_threaded_with(source, &to_options(&args), &mut |event| {
+                if let ExtractEvent::FileDone { name, .. } = event {
+                    done.push(name);
+                }
+            })
+            .unwrap();
+            assert_eq!(report.extracted, 5);
+            if threads == 1 {
+                let expected = ["app/manifest.json", "data.index", "data/0.bin", "data/1.bin"];
+                assert_eq!(done[..4], expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_zip_extract_member_index() {
+        let zip_data = create_test_zip(&[("a.txt", b"a"), ("b.txt", b"b"), ("c.txt", b"c")]);
+        for threads in [1, 2] {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let zip_path = temp_dir.path().join("test.zip");
+            fs::write(&zip_path, &zip_data).unwrap();
+            let out = temp_dir.path().join("out");
+            let mut args = default_args();
+            args.output_dir = Some(out.clone());
+            args.member_index = Some(1);
+            args.threads = Some(threads);
+
+            let source = ArchiveSource::FilePath(zip_path.clone());
+            let report =
+                extract_archive_threaded_with(source, &to_options(&args), &mut |_| {}).unwrap();
+            assert_eq!((report.extracted, report.skipped), (1, 0));
+            assert_eq!(fs::read(out.join("b.txt")).unwrap(), b"b");
+            assert!(!out.join("a.txt").exists() && !out.join("c.txt").exists());
+
+            args.member_index = Some(3);
+            let source = ArchiveSource::FilePath(zip_path);
+            let error =
+                extract_archive_threaded_with(source, &to_options(&args), &mut |_| {}).unwrap_err();
+            assert!(matches!(error, UnzipError::NoSuchEntry { index: 3, entries: 3 }));
+        }
+
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+        let options = ExtractOptions::new().member_index(Some(2));
+        let files = extract_to_vec(&mut archive, &options).unwrap();
+        assert_eq!(files.keys().collect::<Vec<_>>(), ["c.txt"]);
+    }
+
+    #[test]
+    fn test_zip_extract_duplicates() {
+        let mut zip_data = create_test_zip(&[("a.txt", b"1"), ("a_txt", b"2"), ("b.txt", b"b")]);
+        // The writer refuses repeated names, so the second one is renamed afterwards
+        while let Some(at) = zip_data.windows(5).position(|window| window == b"a_txt") {
+            zip_data[at..at + 5].copy_from_slice(b"a.txt");
+        }
+        // The files each policy leaves, with their contents
+        type Expected = &'static [(&'static str, &'static [u8])];
+        let cases: [(DuplicatePolicy, Expected); 3] = [
+            (DuplicatePolicy::Last, &[("a.txt", b"2")]),
+            (DuplicatePolicy::First, &[("a.txt", b"1")]),
+            (DuplicatePolicy::Rename, &[("a.txt", b"1"), ("a (1).txt", b"2")]),
+        ];
+        for threads in [1, 2] {
+            for (policy, expected) in cases {
+                let temp_dir = tempfile::tempdir().unwrap();
+                let zip_path = temp_dir.path().join("test.zip");
+                fs::write(&zip_path, &zip_data).unwrap();
+                let out = temp_dir.path().join("out");
+                let mut args = default_args();
+                args.output_dir = Some(out.clone());
+                args.duplicates = policy;
+                args.threads = Some(threads);
+                args.quiet = 0;
+
+                let mut warnings = Vec::new();
+                let source = ArchiveSource::FilePath(zip_path.clone());
+                extract_archive_threaded_with(source, &to_options(&args), &mut |event| {
+                    if let ExtractEvent::Warning { message } = event {
+                        warnings.push(message);
+                    }
+                })
+                .unwrap();
+                for (name, data) in expected {
+                    assert_eq!(fs::read(out.join(name)).unwrap(), *data, "{} {}", policy, name);
+                }
+                assert_eq!(fs::read_dir(&out).unwrap().count(), expected.len() + 1);
+                assert!(warnings.iter().any(|message| message.contains("more than one entry")));
+
+                args.duplicates = DuplicatePolicy::Error;
+                args.output_dir = Some(temp_dir.path().join("error"));
+                let source = ArchiveSource::FilePath(zip_path);
+                let error = extract_archive_threaded_with(source, &to_options(&args), &mut |_| {})
+                    .unwrap_err();
+                assert!(matches!(error, UnzipError::DuplicateNames(_)));
+                assert!(!temp_dir.path().join("error").exists());
+            }
+        }
+    }
+
+    #[test]
+    fn test_zip_extract_shared_dest() {
+        let big = b"0123456789".repeat(100_000);
+        let zip_data =
+            create_test_zip(&[("same.txt", b"same"), ("other.txt", b"new"), ("big.bin", &big)]);
+        let temp_dir = tempfile::tempdir().unwrap();
+        let zip_path = temp_dir.path().join("test.zip");
+        fs::write(&zip_path, &zip_data).unwrap();
+        let out = temp_dir.path().join("out");
+        fs::create_dir(&out).unwrap();
+        fs::write(out.join("same.txt"), b"same").unwrap();
+        fs::write(out.join("other.txt"), b"old").unwrap();
+        let mut args = default_args();
+        args.output_dir = Some(out.clone());
+        args.overwrite = false;
+        args.shared_dest = true;
+
+        let mut events = Vec::new();
+        let source = ArchiveSource::FilePath(zip_path.clone());
+        extract_archive_threaded_with(source, &to_options(&args), &mut |event| events.push(event))
+            .unwrap();
+        let skipped = |name: &str, reason| {
+            events.contains(&ExtractEvent::Skipped { name: name.to_string(), reason })
+        };
+        assert!(skipped("same.txt", SkipReason::Unchanged));
+        assert!(skipped("other.txt", SkipReason::Exists));
+        assert_eq!(fs::read(out.join("other.txt")).unwrap(), b"old");
+
+        // Processes racing on one directory each see whole files, and leave no
+        // temporary files behind
+        fs::remove_file(out.join("big.bin")).unwrap();
+        args.overwrite = true;
+        args.threads = Some(2);
+        let options = to_options(&args);
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                let source = ArchiveSource::FilePath(zip_path.clone());
+                scope.spawn(|| extract_archive_threaded(source, &options).unwrap());
+            }
+        });
+        assert_eq!(fs::read(out.join("big.bin")).unwrap(), big);
+        assert_eq!(fs::read(out.join("other.txt")).unwrap(), b"new");
+        assert_eq!(fs::read_dir(&out).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn test_zip_extract_auto_rename() {
+        // -L maps both entries to a.txt, which also exists already
+        let zip_data = create_test_zip(&[("A.txt", b"upper"), ("a.txt", b"lower")]);
+        for transactional in [false, true] {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let zip_path = temp_dir.path().join("test.zip");
+            fs::write(&zip_path, &zip_data).unwrap();
+            let out = temp_dir.path().join("out");
+            fs::create_dir(&out).unwrap();
+            fs::write(out.join("a.txt"), b"old").unwrap();
+            let mut args = default_args();
+            args.output_dir = Some(out.clone());
+            args.overwrite = false;
+            args.auto_rename = true;
+            args.lowercase = true;
+            args.transactional = transactional;
+            args.threads = Some(4);
+
+            let source = ArchiveSource::FilePath(zip_path);
+            let options = to_options(&args);
+            let report = extract_archive_threaded_with(source, &options, &mut |_| {}).unwrap();
+            assert_eq!(report.extracted, 2);
+            assert_eq!(fs::read(out.join("a.txt")).unwrap(), b"old");
+            assert_eq!(fs::read(out.join("a (1).txt")).unwrap(), b"upper");
+            assert_eq!(fs::read(out.join("a (2).txt")).unwrap(), b"lower");
+        }
+    }
+
+    #[test]
+    fn test_zip_extract_dry_run_writes_nothing() {
+        let zip_data = create_test_zip(&[
+            ("docs/", b""),
+            ("docs/new.txt", b"new"),
+            ("keep.txt", b"new"),
+            ("skip.log", b"log"),
+        ]);
+        let temp_dir = tempfile::tempdir().unwrap();
+        fs::write(temp_dir.path().join("keep.txt"), b"old").unwrap();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        let options = ExtractOptions::new()
+            .output_dir(temp_dir.path())
+            .overwrite(OverwritePolicy::Always)
+            .exclude(["*.log"])
+            .dry_run(true)
+            .on_event(move |event| sink.lock().unwrap().push(event));
+
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+        extract_archive(&mut archive, &options).unwrap();
+
+        assert_eq!(fs::read(temp_dir.path().join("keep.txt")).unwrap(), b"old");
+        assert!(!temp_dir.path().join("docs").exists());
+        let planned: Vec<_> = events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|event| match event {
+                ExtractEvent::Planned { name, action } => Some((name.clone(), action.label())),
+                ExtractEvent::Skipped { name, reason } => Some((name.clone(), reason.label())),
+                ExtractEvent::Finished { report } => {
+                    assert_eq!((report.extracted, report.skipped), (2, 1));
+                    None
+                },
+                _ => None,
+            })
+            .collect();
+        let planned: Vec<_> = planned.iter().map(|(name, label)| (name.as_str(), *label)).collect();
+        assert_eq!(
+            planned,
+            [
+                ("docs/", "create-dir"),
+                ("docs/new.txt", "extract"),
+                ("keep.txt", "would-overwrite"),
+                ("skip.log", "skip-pattern"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_zip_extract_dry_run_missing_output_dir() {
+        let zip_data = create_test_zip(&[("a.txt", b"a")]);
+        let temp_dir = tempfile::tempdir().unwrap();
+        let out_dir = temp_dir.path().join("missing");
+        fs::write(temp_dir.path().join("test.zip"), zip_data).unwrap();
+
+        let mut args = default_args();
+        args.output_dir = Some(out_dir.clone());
+        args.dry_run = true;
+        let source = ArchiveSource::FilePath(temp_dir.path().join("test.zip"));
+        extract_archive_threaded(source, &to_options(&args)).unwrap();
+
+        assert!(!out_dir.exists());
+    }
+
+    #[derive(Default)]
+    struct Tally {
+        started: Vec<(String, u64)>,
+        written: u64,
+        finished: Vec<String>,
+    }
+
+    impl ExtractObserver for Tally {
+        fn entry_started(&mut self, name: &str, size: u64) {
+            self.started.push((name.to_string(), size));
+        }
+
+        fn bytes_written(&mut self, _name: &str, bytes: u64) {
+            self.written += bytes;
+        }
+
+        fn entry_finished(&mut self, name: &str, path: Option<&Path>, _bytes: u64) {
+            assert!(path.is_some_and(|path| path.exists()));
+            self.finished.push(name.to_string());
+        }
+    }
+
+    #[test]
+    fn test_zip_extract_reports_to_observer() {
+        let zip_data = create_test_zip(&[("a.txt", b"hello"), ("dir/b.bin", &[7u8; 1000])]);
+
+        for threads in [1, 2] {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let tally = Arc::new(Mutex::new(Tally::default()));
+            let options = ExtractOptions::new()
+                .output_dir(temp_dir.path())
+                .threads(threads)
+                .shared_observer(tally.clone());
+
+            let mut archive = ZipArchive::new(Cursor::new(zip_data.clone())).unwrap();
+            extract_archive(&mut archive, &options).unwrap();
+
+            let mut tally = tally.lock().unwrap();
+            tally.started.sort();
+            tally.finished.sort();
+            assert_eq!(tally.started, [("a.txt".to_string(), 5), ("dir/b.bin".to_string(), 1000)]);
+            assert_eq!(tally.written, 1005);
+            assert_eq!(tally.finished, ["a.txt", "dir/b.bin"]);
+        }
+    }
+
+    struct CancelAfterFirstBlock(CancelToken);
+
+    impl ExtractObserver for CancelAfterFirstBlock {
+        fn bytes_written(&mut self, _name: &str, _bytes: u64) {
+            self.0.cancel();
+        }
+    }
+
+    #[test]
+    fn test_zip_extract_cancel_removes_partial_file() {
+        let big = vec![7u8; BUFFER_SIZE * 3];
+        let zip_data = create_test_zip(&[("big.bin", &big), ("later.txt", b"never")]);
+
+        for threads in [1, 2] {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let cancel = CancelToken::new();
+            let options = ExtractOptions::new()
+                .output_dir(temp_dir.path())
+                .threads(threads)
+                .quiet(2)
+                .cancel_token(cancel.clone())
+                .observer(CancelAfterFirstBlock(cancel));
+
+            let mut archive = ZipArchive::new(Cursor::new(zip_data.clone())).unwrap();
+            let err = extract_archive(&mut archive, &options).unwrap_err();
+            assert!(matches!(err, UnzipError::Cancelled), "{}", err);
+            assert!(!temp_dir.path().join("big.bin").exists());
+            assert!(!temp_dir.path().join("later.txt").exists());
+        }
+    }
+
+    struct StopAfterFirstBlock(CancelToken);
+
+    impl ExtractObserver for StopAfterFirstBlock {
+        fn bytes_written(&mut self, _name: &str, _bytes: u64) {
+            self.0.stop();
+        }
+    }
+
+    #[test]
+    fn test_zip_extract_stop_checkpoints_and_resumes() {
+        let big = vec![7u8; BUFFER_SIZE * 3];
+        let zip_data = create_test_zip(&[("big.bin", &big), ("later.txt", b"later")]);
+        let temp_dir = tempfile::tempdir().unwrap();
+        let checkpoint = temp_dir.path().join(crate::checkpoint::CHECKPOINT_FILE);
+        let cancel = CancelToken::new();
+        let interval = CheckpointInterval { time: Some(Duration::from_secs(3600)), bytes: None };
+        let options = ExtractOptions::new()
+            .output_dir(temp_dir.path())
+            .quiet(2)
+            .checkpoint_every(Some(interval))
+            .cancel_token(cancel.clone())
+            .observer(StopAfterFirstBlock(cancel));
+
+        let mut archive = ZipArchive::new(Cursor::new(zip_data.clone())).unwrap();
+        let err = extract_archive(&mut archive, &options).unwrap_err();
+        assert!(matches!(err, UnzipError::Cancelled), "{}", err);
+        assert_eq!(fs::read(temp_dir.path().join("big.bin")).unwrap(), big);
+        assert!(!temp_dir.path().join("later.txt").exists());
+        assert!(checkpoint.exists());
+
+        let mut reasons = Vec::new();
+        let options = ExtractOptions::new().output_dir(temp_dir.path()).quiet(2).resume(true);
+        let report = extract_archive_serial(
+            &mut archive,
+            None,
+            &options,
+            &Journal::default(),
+            Checkpoint::open(&options).unwrap().as_ref(),
+            &mut |event| {
+                if let ExtractEvent::Skipped { reason, .. } = event {
+                    reasons.push(reason);
+                }
+            },
+        )
+        .unwrap();
+        assert_eq!((report.extracted, report.skipped), (1, 1));
+        assert_eq!(reasons, [SkipReason::Resumed]);
+        assert_eq!(fs::read(temp_dir.path().join("later.txt")).unwrap(), b"later");
+
+        extract_archive(&mut archive, &options).unwrap();
+        assert!(!checkpoint.exists());
+    }
 }
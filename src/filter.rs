@@ -0,0 +1,391 @@
+//! funzip-compatible filter (`--filter`)
+//!
+//! Info-ZIP's `funzip` reads a zip or gzip stream, typically from a pipe, and
+//! writes the data of its first member to stdout: `curl -s URL | funzip > file`.
+//! Nothing is seeked, so the central directory is never looked at; the local file
+//! header in front of the data says all there is to know, and everything after the
+//! first member is left unread. `--filter` does the same, reading stdin when no
+//! file (or `-`) is given, so it can replace `funzip` in existing pipelines.
+//!
+//! As with `funzip`, the member must be Stored or Deflated. A Deflated member may
+//! be followed by a data descriptor, as streaming writers leave it; a Stored one
+//! cannot be, since nothing would tell where its data ends. ZipCrypto members are
+//! decrypted with the `-P` password or one asked for on the terminal. A gzip
+//! stream is decoded as a single member, checking its CRC32 and length.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::io;
+//! use unzip::filter::filter_first_member;
+//!
+//! // funzip < archive.zip > first-file
+//! filter_first_member(io::stdin().lock(), None, &mut io::stdout().lock())?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use flate2::bufread::{DeflateDecoder, GzDecoder};
+use std::io::{self, BufRead, BufReader, Cursor, Read, Write};
+use zip::result::ZipError;
+
+use crate::error::{Result, UnzipError};
+use crate::password::prompt_for_password;
+use crate::records::{
+    DESCRIPTOR_SIGNATURE, LOCAL_HEADER_LEN, LOCAL_SIGNATURE, u16_at, u32_at, u64_at,
+};
+
+/// First two bytes of a gzip stream
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Length of the ZipCrypto encryption header in front of the data
+const ENCRYPTION_HEADER_LEN: u64 = 12;
+
+/// General purpose flags: encrypted, sizes and CRC32 in a data descriptor
+const FLAG_ENCRYPTED: u16 = 1;
+const FLAG_DESCRIPTOR: u16 = 1 << 3;
+
+/// Read buffer for the input stream
+const BUFFER_SIZE: usize = 256 * 1024;
+
+/// Write the decompressed data of the first member of the zip or gzip stream
+/// `input` to `out`, returning its size
+///
+/// `password` decrypts a ZipCrypto member; without it, one is asked for on the
+/// terminal if the member turns out to be encrypted.
+///
+/// # Errors
+///
+/// Returns an error if the input is neither a zip nor a gzip stream, the member
+/// is not Stored or Deflated, the password is wrong, or the data does not match
+/// its CRC32
+pub fn filter_first_member<R: Read>(
+    mut input: R,
+    password: Option<&[u8]>,
+    out: &mut impl Write,
+) -> Result<u64> {
+    let mut magic = [0u8; 4];
+    input.read_exact(&mut magic).map_err(|_| not_a_stream())?;
+    if magic[..2] == GZIP_MAGIC {
+        let input = BufReader::with_capacity(BUFFER_SIZE, Cursor::new(magic).chain(input));
+        return Ok(io::copy(&mut GzDecoder::new(input), out)?);
+    }
+    if u32::from_le_bytes(magic) != LOCAL_SIGNATURE {
+        return Err(not_a_stream());
+    }
+
+    let mut header = [0u8; LOCAL_HEADER_LEN - 4];
+    input.read_exact(&mut header)?;
+    let field16 = |at| u16_at(&header, at).unwrap_or_default();
+    let field32 = |at| u32_at(&header, at).unwrap_or_default();
+    let flags = field16(2);
+    let method = field16(4);
+    let mut compressed_size = u64::from(field32(14));
+    let mut name = vec![0u8; usize::from(field16(22))];
+    let mut extra = vec![0u8; usize::from(field16(24))];
+    input.read_exact(&mut name)?;
+    input.read_exact(&mut extra)?;
+    if field32(14) == u32::MAX {
+        compressed_size =
+            zip64_compressed_size(&extra, field32(18) == u32::MAX).unwrap_or(compressed_size);
+    }
+    let name = String::from_utf8_lossy(&name).into_owned();
+    let has_descriptor = flags & FLAG_DESCRIPTOR != 0;
+
+    let mut data = MemberData::new(input);
+    if flags & FLAG_ENCRYPTED != 0 {
+        let password = match password {
+            Some(password) => password.to_vec(),
+            None => prompt_for_password()?,
+        };
+        // The last header byte repeats the top byte of the CRC32, or of the
+        // modification time when the CRC32 follows the data
+        let check = if has_descriptor {
+            header[7]
+        } else {
+            header[13]
+        };
+        data.decrypt(&password, check)?;
+        compressed_size = compressed_size.saturating_sub(ENCRYPTION_HEADER_LEN);
+    }
+
+    let mut hasher = crc32fast::Hasher::new();
+    let size = match method {
+        0 if has_descriptor => {
+            return Err(UnzipError::Unsupported("stored first member with a data descriptor"));
+        },
+        0 => copy_hashing(&mut (&mut data).take(compressed_size), out, &mut hasher),
+        8 => copy_hashing(&mut DeflateDecoder::new(&mut data), out, &mut hasher),
+        _ => return Err(UnzipError::Unsupported("first member is not stored or deflated")),
+    }
+    .map_err(UnzipError::reading(&name))?;
+
+    let stored = if has_descriptor {
+        descriptor_crc32(&mut data.into_rest())?
+    } else {
+        field32(10)
+    };
+    let computed = hasher.finalize();
+    if computed != stored {
+        return Err(UnzipError::CrcMismatch { name, stored, computed });
+    }
+    Ok(size)
+}
+
+fn not_a_stream() -> UnzipError {
+    ZipError::InvalidArchive("input is neither a zip nor a gzip stream").into()
+}
+
+/// Copy `reader` to `out`, hashing what passes
+fn copy_hashing(
+    reader: &mut impl Read,
+    out: &mut impl Write,
+    hasher: &mut crc32fast::Hasher,
+) -> io::Result<u64> {
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut copied = 0u64;
+    loop {
+        let n = match reader.read(&mut buffer)? {
+            0 => return Ok(copied),
+            n => n,
+        };
+        hasher.update(&buffer[..n]);
+        out.write_all(&buffer[..n])?;
+        copied += n as u64;
+    }
+}
+
+/// Compressed size from the Zip64 extra field of a local header, which holds
+/// the uncompressed size first when `has_size` says that one overflowed too
+fn zip64_compressed_size(mut extra: &[u8], has_size: bool) -> Option<u64> {
+    while let (Some(id), Some(len)) = (u16_at(extra, 0), u16_at(extra, 2)) {
+        let len = usize::from(len);
+        let data = extra.get(4..4 + len)?;
+        if id == 0x0001 {
+            return u64_at(data, if has_size { 8 } else { 0 });
+        }
+        extra = &extra[4 + len..];
+    }
+    None
+}
+
+/// CRC32 from the data descriptor after the member's data, with or without its
+/// optional signature
+fn descriptor_crc32(rest: &mut impl Read) -> Result<u32> {
+    let mut field = [0u8; 4];
+    rest.read_exact(&mut field)?;
+    if u32::from_le_bytes(field) == DESCRIPTOR_SIGNATURE {
+        rest.read_exact(&mut field)?;
+    }
+    Ok(u32::from_le_bytes(field))
+}
+
+/// The input after a local header, decrypted if the member is encrypted
+///
+/// The Deflate decoder takes only the bytes it needs from the buffer, so what is
+/// left after the data, the data descriptor, can still be read as it is stored,
+/// not put through the cipher.
+struct MemberData<R> {
+    inner: R,
+    cipher: Option<ZipCrypto>,
+    raw: Vec<u8>,
+    plain: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: Read> MemberData<R> {
+    fn new(inner: R) -> Self {
+        Self { inner, cipher: None, raw: Vec::new(), plain: Vec::new(), pos: 0 }
+    }
+
+    /// Decrypt the rest with ZipCrypto, checking `password` against the last byte
+    /// of the encryption header
+    fn decrypt(&mut self, password: &[u8], check: u8) -> Result<()> {
+        let mut cipher = ZipCrypto::new(password);
+        let mut header = [0u8; ENCRYPTION_HEADER_LEN as usize];
+        self.read_exact(&mut header)?;
+        header.iter_mut().for_each(|byte| *byte = cipher.decrypt(*byte));
+        if header[11] != check {
+            return Err(UnzipError::InvalidPassword);
+        }
+        // The rest of the buffer was read before there was a cipher
+        self.plain.clone_from(&self.raw);
+        self.plain[self.pos..].iter_mut().for_each(|byte| *byte = cipher.decrypt(*byte));
+        self.cipher = Some(cipher);
+        Ok(())
+    }
+
+    /// The input from the first byte not yet consumed, as stored
+    fn into_rest(self) -> impl Read {
+        Cursor::new(self.raw[self.pos..].to_vec()).chain(self.inner)
+    }
+}
+
+impl<R: Read> Read for MemberData<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: Read> BufRead for MemberData<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.raw.len() {
+            self.raw.resize(BUFFER_SIZE, 0);
+            let n = self.inner.read(&mut self.raw)?;
+            self.raw.truncate(n);
+            self.pos = 0;
+            if let Some(cipher) = &mut self.cipher {
+                self.plain.clear();
+                self.plain.extend(self.raw.iter().map(|&byte| cipher.decrypt(byte)));
+            }
+        }
+        match self.cipher {
+            Some(_) => Ok(&self.plain[self.pos..]),
+            None => Ok(&self.raw[self.pos..]),
+        }
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.pos = (self.pos + amount).min(self.raw.len());
+    }
+}
+
+/// Traditional PKWARE encryption (ZipCrypto), decrypting byte by byte
+struct ZipCrypto {
+    keys: [u32; 3],
+}
+
+impl ZipCrypto {
+    fn new(password: &[u8]) -> Self {
+        let mut cipher = Self { keys: [0x1234_5678, 0x2345_6789, 0x3456_7890] };
+        password.iter().for_each(|&byte| cipher.update(byte));
+        cipher
+    }
+
+    fn update(&mut self, byte: u8) {
+        self.keys[0] = crc32_byte(self.keys[0], byte);
+        self.keys[1] = self.keys[1]
+            .wrapping_add(self.keys[0] & 0xff)
+            .wrapping_mul(134_775_813)
+            .wrapping_add(1);
+        self.keys[2] = crc32_byte(self.keys[2], (self.keys[1] >> 24) as u8);
+    }
+
+    fn decrypt(&mut self, byte: u8) -> u8 {
+        let temp = (self.keys[2] | 2) & 0xffff;
+        let plain = byte ^ (temp.wrapping_mul(temp ^ 1) >> 8) as u8;
+        self.update(plain);
+        plain
+    }
+}
+
+/// One step of the CRC32 the cipher's key schedule is built on
+fn crc32_byte(crc: u32, byte: u8) -> u32 {
+    CRC32_TABLE[((crc ^ u32::from(byte)) & 0xff) as usize] ^ (crc >> 8)
+}
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use flate2::write::{DeflateEncoder, GzEncoder};
+    use zip::ZipWriter;
+    use zip::unstable::write::FileOptionsExt;
+    use zip::write::SimpleFileOptions;
+
+    fn archive(options: SimpleFileOptions, data: &[u8]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("first.txt", options).unwrap();
+        zip.write_all(data).unwrap();
+        zip.start_file("second.txt", SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"never read").unwrap();
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn filter(input: &[u8], password: Option<&[u8]>) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        filter_first_member(input, password, &mut out)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn test_filter_first_member_of_zip_and_gzip() {
+        let data = b"first member data\n".repeat(500);
+        for method in [zip::CompressionMethod::STORE, zip::CompressionMethod::DEFLATE] {
+            let options = SimpleFileOptions::default().compression_method(method);
+            assert_eq!(filter(&archive(options, &data), None).unwrap(), data);
+        }
+
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(&data).unwrap();
+        assert_eq!(filter(&gzip.finish().unwrap(), None).unwrap(), data);
+
+        assert!(filter(b"plain text, not an archive", None).is_err());
+    }
+
+    #[test]
+    fn test_filter_deflated_member_with_data_descriptor() {
+        // A streaming writer's output: sizes and CRC32 zero until after the data
+        let data = b"streamed\n".repeat(100);
+        let mut deflate = DeflateEncoder::new(Vec::new(), Compression::default());
+        deflate.write_all(&data).unwrap();
+        let compressed = deflate.finish().unwrap();
+        let mut bytes = LOCAL_SIGNATURE.to_le_bytes().to_vec();
+        bytes.extend([20, 0, 8, 0, 8, 0, 0, 0, 0, 0]);
+        bytes.extend([0; 12]);
+        bytes.extend([10, 0, 0, 0]);
+        bytes.extend(b"stream.txt");
+        bytes.extend(&compressed);
+        bytes.extend(DESCRIPTOR_SIGNATURE.to_le_bytes());
+        bytes.extend(crc32fast::hash(&data).to_le_bytes());
+        bytes.extend((compressed.len() as u32).to_le_bytes());
+        bytes.extend((data.len() as u32).to_le_bytes());
+        bytes.extend(b"PK\x03\x04 and the next member");
+        assert_eq!(filter(&bytes, None).unwrap(), data);
+
+        // The descriptor's CRC32 is checked
+        let end = bytes
+            .windows(4)
+            .position(|window| window == DESCRIPTOR_SIGNATURE.to_le_bytes())
+            .unwrap();
+        bytes[end + 4] ^= 0xff;
+        assert!(matches!(filter(&bytes, None), Err(UnzipError::CrcMismatch { .. })));
+    }
+
+    #[test]
+    fn test_filter_zipcrypto_member() {
+        let data = b"attack at dawn\n".repeat(50);
+        for method in [zip::CompressionMethod::STORE, zip::CompressionMethod::DEFLATE] {
+            let options = SimpleFileOptions::default()
+                .compression_method(method)
+                .with_deprecated_encryption(b"secret");
+            let bytes = archive(options, &data);
+            assert_eq!(filter(&bytes, Some(b"secret")).unwrap(), data);
+            // A wrong password fails the check byte, or rarely the CRC32
+            assert!(filter(&bytes, Some(b"wrong")).is_err());
+        }
+    }
+}
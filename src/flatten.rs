@@ -0,0 +1,276 @@
+//! Flat storage for entries whose paths exceed filesystem limits
+//!
+//! Some filesystems have much tighter limits than the usual 255-byte names and
+//! 4096-byte paths (eCryptfs allows about 143 bytes per name), which deeply nested
+//! generated sources easily exceed. With `--flatten-long-paths`, such files are
+//! written under a short hashed name inside [`STORE_DIR`] and their intended paths
+//! are recorded in [`MANIFEST_NAME`] next to them, one `name<TAB>path` line each,
+//! instead of aborting the whole extraction.
+//!
+//! The mappings already in a manifest are kept, and a name recorded for another
+//! path gets a `-N` suffix, so runs into the same directory add to one manifest.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::path::Path;
+//! use unzip::flatten::LongPathStore;
+//!
+//! let mut store = LongPathStore::new(Path::new("out"));
+//! let long = Path::new("out").join("a".repeat(300));
+//! if let Some(flat) = store.flatten(&long) {
+//!     println!("{} stored as {}", long.display(), flat.display());
+//! }
+//! store.write_manifest()?;
+//! # Ok::<(), unzip::UnzipError>(())
+//! ```
+
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Result, UnzipError};
+use crate::linux::name_max;
+
+/// Directory (inside the output directory) holding flattened files
+pub const STORE_DIR: &str = ".long-paths";
+
+/// Manifest file inside [`STORE_DIR`]
+pub const MANIFEST_NAME: &str = "MANIFEST";
+
+/// Maximum path length assumed for every filesystem
+const PATH_MAX: usize = 4096;
+
+/// Longest extension kept on a flattened name
+const MAX_EXTENSION: usize = 16;
+
+/// Maps over-long output paths to short names and remembers the mapping
+#[derive(Debug)]
+pub struct LongPathStore {
+    store_dir: PathBuf,
+    name_max: usize,
+    /// The manifest's mappings, then those added in this run
+    entries: Vec<(String, PathBuf)>,
+    /// Position of each name in `entries`
+    names: HashMap<String, usize>,
+    /// How many of `entries` came from the manifest
+    loaded: usize,
+    /// How many of `entries` the manifest file holds
+    written: usize,
+    /// Whether the manifest file was written in this run
+    replaced: bool,
+}
+
+impl LongPathStore {
+    /// Create a store for an existing output directory
+    ///
+    /// The name length limit is read from the output directory's filesystem.
+    pub fn new(output_dir: &Path) -> Self {
+        Self::with_limit(output_dir, name_max(output_dir))
+    }
+
+    /// Create a store with an explicit name length limit
+    ///
+    /// The mappings of an existing manifest are read so they are kept and reused.
+    pub fn with_limit(output_dir: &Path, name_max: usize) -> Self {
+        let store_dir = output_dir.join(STORE_DIR);
+        let entries: Vec<(String, PathBuf)> = fs::read_to_string(store_dir.join(MANIFEST_NAME))
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| line.split_once('\t'))
+            .map(|(name, path)| (name.to_string(), PathBuf::from(path)))
+            .collect();
+        let names = entries.iter().enumerate().map(|(i, (name, _))| (name.clone(), i)).collect();
+        let loaded = entries.len();
+        Self { store_dir, name_max, entries, names, loaded, written: loaded, replaced: false }
+    }
+
+    /// Whether `path` has a component or total length beyond the limits
+    pub fn exceeds_limits(&self, path: &Path) -> bool {
+        path.as_os_str().len() >= PATH_MAX
+            || path.components().any(|c| c.as_os_str().len() > self.name_max)
+    }
+
+    /// Return the flat replacement for `path` if it is too long
+    ///
+    /// A path flattened before keeps its name. A new one is recorded for the
+    /// manifest, with a `-N` suffix if its hashed name belongs to another path.
+    /// Paths within the limits return `None` and are not recorded.
+    pub fn flatten(&mut self, path: &Path) -> Option<PathBuf> {
+        if !self.exceeds_limits(path) {
+            return None;
+        }
+
+        let hash = format!("{:016x}", fnv1a(path.as_os_str().as_encoded_bytes()));
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .filter(|ext| ext.len() <= MAX_EXTENSION)
+            .map_or(String::new(), |ext| format!(".{}", ext));
+
+        for suffix in 0.. {
+            let name = match suffix {
+                0 => format!("{}{}", hash, ext),
+                n => format!("{}-{}{}", hash, n, ext),
+            };
+            match self.names.get(&name) {
+                Some(&i) if self.entries[i].1 == path => return Some(self.store_dir.join(name)),
+                Some(_) => continue,
+                None => {
+                    self.names.insert(name.clone(), self.entries.len());
+                    self.entries.push((name.clone(), path.to_path_buf()));
+                    return Some(self.store_dir.join(name));
+                },
+            }
+        }
+        unreachable!("suffixes are unbounded")
+    }
+
+    /// Number of mappings added in this run
+    pub fn len(&self) -> usize {
+        self.entries.len() - self.loaded
+    }
+
+    /// Whether no mapping has been added in this run
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether some mapping is not in the manifest file yet
+    pub fn has_unwritten(&self) -> bool {
+        self.written < self.entries.len()
+    }
+
+    /// Whether the next [`LongPathStore::write_manifest`] replaces the manifest file
+    /// rather than appending to it
+    pub fn replaces_manifest(&self) -> bool {
+        !self.replaced
+    }
+
+    /// Where [`LongPathStore::write_manifest`] writes the manifest
+    pub fn manifest_path(&self) -> PathBuf {
+        self.store_dir.join(MANIFEST_NAME)
+    }
+
+    /// Write the mappings added since the last call to the manifest
+    ///
+    /// The first write in a run replaces the manifest with all mappings, the ones
+    /// it held included, and later writes append to it. Call it as flattened files
+    /// are stored so an interrupted run still leaves their mappings behind.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the manifest cannot be written
+    pub fn write_manifest(&mut self) -> Result<()> {
+        if !self.has_unwritten() {
+            return Ok(());
+        }
+
+        let from = if self.replaced { self.written } else { 0 };
+        let mut manifest = String::new();
+        for (name, path) in &self.entries[from..] {
+            manifest.push_str(name);
+            manifest.push('\t');
+            manifest.push_str(&path.to_string_lossy());
+            manifest.push('\n');
+        }
+
+        fs::create_dir_all(&self.store_dir)
+            .map_err(UnzipError::file("create directory", &self.store_dir))?;
+        let manifest_path = self.manifest_path();
+        OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(self.replaced)
+            .truncate(!self.replaced)
+            .open(&manifest_path)
+            .and_then(|mut file| file.write_all(manifest.as_bytes()))
+            .map_err(UnzipError::file("write manifest", &manifest_path))?;
+        self.written = self.entries.len();
+        self.replaced = true;
+        Ok(())
+    }
+}
+
+/// 64-bit FNV-1a hash, stable across runs and platforms
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flatten_only_long_paths() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut store = LongPathStore::with_limit(temp_dir.path(), 20);
+
+        let short = temp_dir.path().join("com/example/A.java");
+        assert_eq!(store.flatten(&short), None);
+
+        let long = temp_dir.path().join("com/example/AVeryLongGeneratedName.java");
+        let flat = store.flatten(&long).unwrap();
+        assert_eq!(flat.parent().unwrap(), temp_dir.path().join(STORE_DIR));
+        assert_eq!(flat.extension().unwrap(), "java");
+        assert_eq!(store.len(), 1);
+
+        // Names are stable across runs
+        let mut again = LongPathStore::with_limit(temp_dir.path(), 20);
+        assert_eq!(again.flatten(&long), Some(flat));
+    }
+
+    #[test]
+    fn test_write_manifest() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut store = LongPathStore::with_limit(temp_dir.path(), 8);
+        store.write_manifest().unwrap();
+        assert!(!temp_dir.path().join(STORE_DIR).exists());
+
+        let long = temp_dir.path().join("deeply_nested/file");
+        let flat = store.flatten(&long).unwrap();
+        store.write_manifest().unwrap();
+
+        let manifest =
+            fs::read_to_string(temp_dir.path().join(STORE_DIR).join(MANIFEST_NAME)).unwrap();
+        let name = flat.file_name().unwrap().to_str().unwrap();
+        assert_eq!(manifest, format!("{}\t{}\n", name, long.display()));
+    }
+
+    #[test]
+    fn test_manifest_merges_and_avoids_collisions() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let first = temp_dir.path().join("first_long_path/file.txt");
+        let second = temp_dir.path().join("second_long_path/file.txt");
+
+        let mut store = LongPathStore::with_limit(temp_dir.path(), 8);
+        let flat_first = store.flatten(&first).unwrap();
+        store.write_manifest().unwrap();
+
+        // A later run keeps the first mapping and appends its own
+        let mut again = LongPathStore::with_limit(temp_dir.path(), 8);
+        assert_eq!(again.flatten(&first), Some(flat_first.clone()));
+        assert!(again.is_empty());
+        // Claim the second path's hashed name for another path, as a collision would
+        let name = format!("{:016x}.txt", fnv1a(second.as_os_str().as_encoded_bytes()));
+        again.names.insert(name.clone(), 0);
+        let flat_second = again.flatten(&second).unwrap();
+        assert_ne!(flat_second.file_name().unwrap(), name.as_str());
+        assert!(flat_second.to_str().unwrap().ends_with("-1.txt"));
+        again.write_manifest().unwrap();
+        let flat_third = again.flatten(&temp_dir.path().join("third_long_path/x")).unwrap();
+        again.write_manifest().unwrap();
+
+        let manifest = fs::read_to_string(again.manifest_path()).unwrap();
+        let names: Vec<&str> =
+            manifest.lines().map(|line| line.split_once('\t').unwrap().0).collect();
+        let file_name = |path: &Path| path.file_name().unwrap().to_str().unwrap().to_string();
+        assert_eq!(
+            names,
+            [file_name(&flat_first), file_name(&flat_second), file_name(&flat_third)]
+        );
+    }
+}
@@ -9,6 +9,10 @@
 //! - `**` - Matches zero or more characters, including directory separator `/`
 //! - `?` - Matches exactly one character, but not directory separator `/`
 //!
+//! These are the semantics of Info-ZIP's `-W`. Without it, Info-ZIP lets `*` and
+//! `?` match `/` as well; [`glob_match_with`] and [`Wildcards::MatchDir`] do the
+//! same, which `--compat=infozip` uses unless `-W` is given.
+//!
 //! # Algorithm
 //!
 //! Uses backtracking algorithm to handle complex wildcard patterns efficiently.
@@ -26,6 +30,16 @@
 //! assert!(!glob_match("*.txt", "file.rs"));
 //! ```
 
+/// Whether `*` and `?` match the directory separator `/`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Wildcards {
+    /// `*` and `?` stop at `/` and only `**` crosses it, as with Info-ZIP's `-W`
+    #[default]
+    StopAtDir,
+    /// `*` and `?` match `/` too, as Info-ZIP does without `-W`
+    MatchDir,
+}
+
 /// Match a glob pattern against text
 ///
 /// Supports:
@@ -33,10 +47,25 @@
 /// - `**` matches any characters including `/`
 /// - `?` matches any single character except `/`
 pub fn glob_match(pattern: &str, text: &str) -> bool {
-    glob_match_impl(pattern.as_bytes(), text.as_bytes())
+    glob_match_with(pattern, text, Wildcards::StopAtDir)
 }
 
-fn glob_match_impl(pattern: &[u8], text: &[u8]) -> bool {
+/// Match a glob pattern against text, with `*` and `?` matching `/` or not as
+/// `wildcards` says
+///
+/// # Examples
+///
+/// ```
+/// use unzip::glob::{Wildcards, glob_match_with};
+///
+/// assert!(glob_match_with("*.txt", "docs/a.txt", Wildcards::MatchDir));
+/// assert!(!glob_match_with("*.txt", "docs/a.txt", Wildcards::StopAtDir));
+/// ```
+pub fn glob_match_with(pattern: &str, text: &str, wildcards: Wildcards) -> bool {
+    glob_match_impl(pattern.as_bytes(), text.as_bytes(), wildcards == Wildcards::MatchDir)
+}
+
+fn glob_match_impl(pattern: &[u8], text: &[u8], match_dir: bool) -> bool {
     let mut px = 0;
     let mut tx = 0;
     let mut next_px = 0;
@@ -59,7 +88,7 @@ fn glob_match_impl(pattern: &[u8], text: &[u8]) -> bool {
                         }
                         // Try to match rest at every position
                         for i in tx..=text.len() {
-                            if glob_match_impl(&pattern[px..], &text[i..]) {
+                            if glob_match_impl(&pattern[px..], &text[i..], match_dir) {
                                 return true;
                             }
                         }
@@ -72,7 +101,7 @@ fn glob_match_impl(pattern: &[u8], text: &[u8]) -> bool {
                     continue;
                 },
                 b'?' => {
-                    if tx < text.len() && text[tx] != b'/' {
+                    if tx < text.len() && (match_dir || text[tx] != b'/') {
                         px += 1;
                         tx += 1;
                         continue;
@@ -91,7 +120,7 @@ fn glob_match_impl(pattern: &[u8], text: &[u8]) -> bool {
         // Mismatch - try backtracking
         if next_tx > 0 && next_tx <= text.len() {
             // Don't let * match /
-            if text[next_tx - 1] == b'/' {
+            if !match_dir && text[next_tx - 1] == b'/' {
                 return false;
             }
             px = next_px;
@@ -183,6 +212,17 @@ mod tests {
         assert!(!glob_match("*", "path/file.txt"));
     }
 
+    #[test]
+    fn test_glob_match_wildcards_match_dir() {
+        let matches = |pattern, text| glob_match_with(pattern, text, Wildcards::MatchDir);
+        assert!(matches("*", "path/file.txt"));
+        assert!(matches("*.txt", "a/b/file.txt"));
+        assert!(matches("src/*", "src/lib/mod.rs"));
+        assert!(matches("a?b", "a/b"));
+        assert!(matches("**/*.rs", "src/lib/mod.rs"));
+        assert!(!matches("*.txt", "a/b/file.rs"));
+    }
+
     #[test]
     fn test_glob_match_double_star_only() {
         assert!(glob_match("**", "file.txt"));
@@ -0,0 +1,160 @@
+//! Per-entry policy hooks for library callers
+//!
+//! An embedder with its own rules, say extracting only files whose CRC-32 a
+//! database of known-good releases lists, sets [`EntryHooks`] on
+//! [`ExtractOptions`](crate::ExtractOptions) instead of forking the extraction
+//! loop. [`EntryHooks::before_entry`] sees every file entry the patterns
+//! select and answers with an [`EntryAction`]: extract it, skip it, or write
+//! it under another path. [`EntryHooks::after_entry`] then hears how each
+//! entry ended.
+//!
+//! The command line's own include and exclude patterns are one implementation
+//! of the trait, consulted first; the caller's hooks only see the entries they
+//! select. A renamed path is relative to the output directory and sanitized
+//! like an entry name, and it replaces the `-j`, `--strip-components`,
+//! `--transform` and `--name-template` naming. Hooks are called on the thread
+//! that plans the extraction, in archive order, also when threads write the
+//! files.
+//!
+//! ```
+//! use std::io::{Cursor, Write};
+//! use std::sync::{Arc, Mutex};
+//! use zip::{ZipArchive, ZipWriter, write::SimpleFileOptions};
+//! use unzip::hooks::{EntryAction, EntryHooks, EntryMeta, EntryOutcome};
+//! use unzip::{ExtractOptions, extract_archive};
+//!
+//! /// Extracts only known-good files, and records what happened to each entry
+//! #[derive(Default)]
+//! struct KnownGood {
+//!     log: Mutex<Vec<String>>,
+//! }
+//!
+//! impl EntryHooks for KnownGood {
+//!     fn before_entry(&self, entry: &EntryMeta<'_>) -> EntryAction {
+//!         if entry.crc32 == crc32fast::hash(b"trusted") {
+//!             EntryAction::Extract
+//!         } else {
+//!             EntryAction::Skip
+//!         }
+//!     }
+//!
+//!     fn after_entry(&self, name: &str, outcome: &EntryOutcome<'_>) {
+//!         let done = matches!(outcome, EntryOutcome::Extracted { .. });
+//!         self.log.lock().unwrap().push(format!("{} {}", name, done));
+//!     }
+//! }
+//!
+//! let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+//! zip.start_file("app.bin", SimpleFileOptions::default())?;
+//! zip.write_all(b"trusted")?;
+//! zip.start_file("dropper.bin", SimpleFileOptions::default())?;
+//! zip.write_all(b"tampered")?;
+//! let mut archive = ZipArchive::new(zip.finish()?)?;
+//!
+//! let dir = tempfile::tempdir()?;
+//! let hooks = Arc::new(KnownGood::default());
+//! let options = ExtractOptions::new().output_dir(dir.path()).quiet(2).shared_hooks(hooks.clone());
+//! extract_archive(&mut archive, &options)?;
+//! assert!(dir.path().join("app.bin").exists());
+//! assert!(!dir.path().join("dropper.bin").exists());
+//! assert_eq!(*hooks.log.lock().unwrap(), ["app.bin true", "dropper.bin false"]);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::path::{Path, PathBuf};
+use zip::read::ZipFile;
+
+use crate::events::SkipReason;
+use crate::permissions::file_mode;
+use crate::utils::PatternMatcher;
+
+/// A file entry as [`EntryHooks::before_entry`] sees it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryMeta<'a> {
+    /// Position in the central directory, counting from 0
+    pub index: usize,
+    /// Name, decoded with the character set of the options
+    pub name: &'a str,
+    /// Uncompressed size in bytes
+    pub size: u64,
+    /// Compressed size in bytes
+    pub compressed_size: u64,
+    /// CRC-32 of the uncompressed data, as the archive records it
+    pub crc32: u32,
+    /// Last modification time, if the entry has a valid one
+    pub modified: Option<zip::DateTime>,
+    /// Whether the entry's data is encrypted
+    pub encrypted: bool,
+    /// Unix mode from the archive, if it records one
+    pub mode: Option<u32>,
+}
+
+impl<'a> EntryMeta<'a> {
+    /// Describe the entry `file` at `index`, named `name`
+    pub(crate) fn of(index: usize, name: &'a str, file: &ZipFile<'_>) -> Self {
+        Self {
+            index,
+            name,
+            size: file.size(),
+            compressed_size: file.compressed_size(),
+            crc32: file.crc32(),
+            modified: file.last_modified(),
+            encrypted: file.encrypted(),
+            mode: file_mode(file),
+        }
+    }
+}
+
+/// What to do with a file entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryAction {
+    /// Extract it where the options put it
+    Extract,
+    /// Leave it out, reported as [`SkipReason::Vetoed`]
+    Skip,
+    /// Extract it to this path below the output directory instead
+    Rename(PathBuf),
+}
+
+/// How an entry ended, as [`EntryHooks::after_entry`] hears it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryOutcome<'a> {
+    /// The file was written to `path`
+    Extracted {
+        /// Where the file was written
+        path: &'a Path,
+        /// Bytes written
+        bytes: u64,
+    },
+    /// The entry was not extracted
+    Skipped(SkipReason),
+}
+
+/// Policy consulted for every file entry of an extraction
+///
+/// An error that stops the extraction is returned by the extraction call
+/// rather than passed to [`EntryHooks::after_entry`].
+pub trait EntryHooks: Send + Sync {
+    /// Decide what to do with `entry`; extract it by default
+    fn before_entry(&self, entry: &EntryMeta<'_>) -> EntryAction {
+        let _ = entry;
+        EntryAction::Extract
+    }
+
+    /// Hear that the entry named `name` was extracted or skipped; nothing by
+    /// default
+    fn after_entry(&self, name: &str, outcome: &EntryOutcome<'_>) {
+        let _ = (name, outcome);
+    }
+}
+
+/// The include, exclude and `-C` rules of the options
+impl EntryHooks for PatternMatcher<'_> {
+    fn before_entry(&self, entry: &EntryMeta<'_>) -> EntryAction {
+        if self.should_extract(entry.name) {
+            EntryAction::Extract
+        } else {
+            EntryAction::Skip
+        }
+    }
+}
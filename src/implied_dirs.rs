@@ -0,0 +1,167 @@
+//! Timestamps of directories implied by file entries (`--implied-dir-times`)
+//!
+//! Archives often hold `docs/a.txt` without a `docs/` entry. Extraction creates
+//! such directories on the way, and as they have no entry to take a time from,
+//! they keep the time they were created at, which makes the extracted tree
+//! differ from run to run. Build caches that hash or compare directory times
+//! then see a change every time. `--implied-dir-times=POLICY` chooses:
+//!
+//! - `now` (default): leave them with the time extraction created them
+//! - `newest`: the newest modification time among what is directly inside them,
+//!   once their files and subdirectories have their own times
+//! - `source-date-epoch`: the time in the `SOURCE_DATE_EPOCH` environment
+//!   variable, as reproducible builds set it
+//! - `SECONDS`: a fixed time, in seconds since the Unix epoch
+//!
+//! Only directories the run created and that have no entry of their own are
+//! stamped; directory entries keep their archived times and existing
+//! directories are left alone. The output directory itself is stamped by
+//! `--stamp-output-dir`.
+//!
+//! # Examples
+//!
+//! ```
+//! use unzip::implied_dirs::{ImpliedDirTimes, parse_implied_dir_times};
+//!
+//! assert_eq!(parse_implied_dir_times("newest"), Ok(ImpliedDirTimes::Newest));
+//! assert_eq!(parse_implied_dir_times("1700000000"), Ok(ImpliedDirTimes::Fixed(1_700_000_000)));
+//! assert!(parse_implied_dir_times("yesterday").is_err());
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use filetime::FileTime;
+
+use crate::error::{Result, UnzipError};
+
+/// Environment variable reproducible builds set to their fixed time
+pub const SOURCE_DATE_EPOCH: &str = "SOURCE_DATE_EPOCH";
+
+/// What `--implied-dir-times` gives directories without an entry of their own
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ImpliedDirTimes {
+    /// The time they were created at (default)
+    #[default]
+    Now,
+    /// The newest modification time of what is directly inside them
+    Newest,
+    /// This time, in seconds since the Unix epoch
+    Fixed(i64),
+}
+
+impl fmt::Display for ImpliedDirTimes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Now => write!(f, "now"),
+            Self::Newest => write!(f, "newest"),
+            Self::Fixed(seconds) => write!(f, "{}", seconds),
+        }
+    }
+}
+
+/// Parse an `--implied-dir-times` policy: `now`, `newest`, `source-date-epoch`
+/// (read from the environment) or a number of seconds since the Unix epoch
+///
+/// # Errors
+///
+/// Returns a message suitable for clap if the policy is unknown, or if
+/// `SOURCE_DATE_EPOCH` is asked for but not set to a number of seconds
+pub fn parse_implied_dir_times(value: &str) -> std::result::Result<ImpliedDirTimes, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "now" => Ok(ImpliedDirTimes::Now),
+        "newest" => Ok(ImpliedDirTimes::Newest),
+        "source-date-epoch" => {
+            let epoch = std::env::var(SOURCE_DATE_EPOCH)
+                .map_err(|_| format!("{} is not set", SOURCE_DATE_EPOCH))?;
+            epoch
+                .trim()
+                .parse()
+                .map(ImpliedDirTimes::Fixed)
+                .map_err(|_| format!("invalid {}: {} (expected seconds)", SOURCE_DATE_EPOCH, epoch))
+        },
+        _ => value.parse().map(ImpliedDirTimes::Fixed).map_err(|_| {
+            format!(
+                "invalid directory time policy: {} (expected now, newest, \
+                 source-date-epoch or seconds since the epoch)",
+                value
+            )
+        }),
+    }
+}
+
+/// Give the directories in `dirs`, created without an entry of their own,
+/// their time under `policy`, deepest first so `newest` sees the times of the
+/// directories inside
+///
+/// Directories that are gone, as after a transactional run moved them, are
+/// skipped.
+///
+/// # Errors
+///
+/// Returns an error if a directory cannot be read or its time cannot be set
+pub(crate) fn stamp_implied_dirs(mut dirs: Vec<PathBuf>, policy: ImpliedDirTimes) -> Result<()> {
+    if policy == ImpliedDirTimes::Now {
+        return Ok(());
+    }
+    dirs.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+    dirs.dedup();
+    for dir in &dirs {
+        let time = match policy {
+            ImpliedDirTimes::Now => continue,
+            ImpliedDirTimes::Fixed(seconds) => FileTime::from_unix_time(seconds, 0),
+            ImpliedDirTimes::Newest => match newest_inside(dir) {
+                Ok(Some(time)) => time,
+                Ok(None) => continue,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(UnzipError::file("read directory", dir)(e)),
+            },
+        };
+        match filetime::set_file_mtime(dir, time) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {},
+            result => result.map_err(UnzipError::file("set modification time of", dir))?,
+        }
+    }
+    Ok(())
+}
+
+/// The newest modification time among the entries of `dir`, not following
+/// symlinks, or `None` if it is empty
+fn newest_inside(dir: &Path) -> io::Result<Option<FileTime>> {
+    let mut newest = None;
+    for entry in fs::read_dir(dir)? {
+        let metadata = entry?.path().symlink_metadata()?;
+        newest = newest.max(Some(FileTime::from_last_modification_time(&metadata)));
+    }
+    Ok(newest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stamp_implied_dirs_newest() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let (outer, inner) = (temp_dir.path().join("a"), temp_dir.path().join("a/b"));
+        fs::create_dir_all(&inner).unwrap();
+        fs::write(inner.join("f"), b"").unwrap();
+        fs::write(outer.join("g"), b"").unwrap();
+        let time = |seconds| FileTime::from_unix_time(seconds, 0);
+        filetime::set_file_mtime(inner.join("f"), time(1_600_000_000)).unwrap();
+        filetime::set_file_mtime(outer.join("g"), time(1_000_000_000)).unwrap();
+
+        let gone = temp_dir.path().join("gone");
+        let dirs = vec![outer.clone(), inner.clone(), gone];
+        stamp_implied_dirs(dirs, ImpliedDirTimes::Newest).unwrap();
+        let mtime = |dir: &Path| FileTime::from_last_modification_time(&dir.metadata().unwrap());
+        assert_eq!(mtime(&inner), time(1_600_000_000));
+        // The directory inside counts, with the time it was just given
+        assert_eq!(mtime(&outer), time(1_600_000_000));
+
+        stamp_implied_dirs(vec![outer.clone()], ImpliedDirTimes::Fixed(42)).unwrap();
+        assert_eq!(mtime(&outer), time(42));
+    }
+}
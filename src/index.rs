@@ -0,0 +1,269 @@
+//! Bounds-checked entry slices of memory-mapped archives
+//!
+//! The zip crate reads an entry through a seekable reader and buffers of its own,
+//! trusting the offsets in the central directory. For an archive in memory, an
+//! [`ArchiveIndex`] records the exact range of every entry's compressed data and
+//! checks once, up front, that each range lies between the start of the archive
+//! and its central directory and that no entry reaches into the next local header.
+//! Overlapping entries are how some zip bombs get past the ratio Deflate allows;
+//! they are rejected with [`UnzipError::OverlappingEntry`], as Info-ZIP does.
+//!
+//! Stored and Deflate entries, which are nearly all of them, are then decoded
+//! straight from their `&[u8]` slice of the mapping: no seeking, no intermediate
+//! buffer, and no way to read past the range the index checked. Their size and
+//! CRC32 are verified at the end, as the zip crate would. Encrypted entries and
+//! other methods still go through the zip crate.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use std::io::Cursor;
+//! use memmap2::Mmap;
+//! use zip::ZipArchive;
+//! use unzip::index::ArchiveIndex;
+//!
+//! let mmap = unsafe { Mmap::map(&File::open("archive.zip")?)? };
+//! let mut archive = ZipArchive::new(Cursor::new(&mmap[..]))?;
+//! let index = ArchiveIndex::new(&mut archive, mmap.len() as u64)?;
+//! println!("entry 0 holds {} compressed bytes", index.data(&mmap, 0).len());
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use flate2::bufread::DeflateDecoder;
+use std::io::{self, Read, Seek};
+use zip::{CompressionMethod, ZipArchive};
+
+use crate::error::{Result, UnzipError};
+
+/// Where each entry's compressed data lies in an archive, checked not to overlap
+#[derive(Debug, Clone)]
+pub struct ArchiveIndex {
+    spans: Vec<Span>,
+}
+
+/// Compressed data of one entry and what its central directory record says
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    start: usize,
+    end: usize,
+    size: u64,
+    crc32: u32,
+    /// How the data decodes from its slice, `None` if only the zip crate can
+    method: Option<SliceMethod>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SliceMethod {
+    Stored,
+    Deflated,
+}
+
+impl ArchiveIndex {
+    /// Index the entries of `archive`, read from `len` bytes in memory
+    ///
+    /// # Errors
+    ///
+    /// Returns [`UnzipError::OverlappingEntry`] if an entry's data reaches past
+    /// the next local header or into the central directory, or an error if a
+    /// local header cannot be read
+    pub fn new<R: Read + Seek>(archive: &mut ZipArchive<R>, len: u64) -> Result<Self> {
+        let limit = archive.central_directory_start().min(len);
+        let mut spans = Vec::with_capacity(archive.len());
+        let mut headers = Vec::with_capacity(archive.len());
+        for i in 0..archive.len() {
+            let file = archive.by_index_raw(i)?;
+            let start = file.data_start();
+            let Some(end) = start.checked_add(file.compressed_size()).filter(|&end| end <= limit)
+            else {
+                return Err(overlapping(file.name(), "the central directory"));
+            };
+            let method = match file.compression() {
+                _ if file.encrypted() => None,
+                CompressionMethod::Stored => Some(SliceMethod::Stored),
+                CompressionMethod::Deflated => Some(SliceMethod::Deflated),
+                _ => None,
+            };
+            headers.push((file.header_start(), end, i));
+            // Both fit in usize: they lie within `len` bytes held in memory
+            spans.push(Span {
+                start: start as usize,
+                end: end as usize,
+                size: file.size(),
+                crc32: file.crc32(),
+                method,
+            });
+        }
+
+        headers.sort_unstable();
+        for pair in headers.windows(2) {
+            let ((_, end, first), (next_header, _, second)) = (pair[0], pair[1]);
+            if end > next_header {
+                let name = |i| archive.name_for_index(i).unwrap_or_default().to_string();
+                return Err(overlapping(&name(first), &name(second)));
+            }
+        }
+        Ok(Self { spans })
+    }
+
+    /// Compressed data of the entry at `index` in `bytes`, the archive the index
+    /// was built for
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not an entry of the archive
+    pub fn data<'a>(&self, bytes: &'a [u8], index: usize) -> &'a [u8] {
+        let span = &self.spans[index];
+        bytes.get(span.start..span.end).unwrap_or_default()
+    }
+
+    /// Whether the entry at `index` can be decoded from its slice, which needs it
+    /// to be unencrypted and Stored or Deflate
+    pub(crate) fn slices(&self, index: usize) -> bool {
+        self.spans.get(index).is_some_and(|span| span.method.is_some())
+    }
+
+    /// Reader decoding the entry at `index` from its slice of `bytes`, `None` for
+    /// entries only the zip crate can read
+    pub(crate) fn open<'a>(
+        &self,
+        bytes: &'a [u8],
+        index: usize,
+        name: &'a str,
+    ) -> Option<SliceEntry<'a>> {
+        let span = *self.spans.get(index)?;
+        let data = self.data(bytes, index);
+        let decoder = match span.method? {
+            SliceMethod::Stored => Decoder::Stored(data),
+            SliceMethod::Deflated => Decoder::Deflated(DeflateDecoder::new(data)),
+        };
+        Some(SliceEntry {
+            name,
+            size: span.size,
+            compressed_size: data.len() as u64,
+            crc32: span.crc32,
+            decoder,
+            hasher: crc32fast::Hasher::new(),
+            produced: 0,
+        })
+    }
+}
+
+fn overlapping(name: &str, other: &str) -> UnzipError {
+    UnzipError::OverlappingEntry { name: name.to_string(), other: other.to_string() }
+}
+
+/// Decoded data of an entry read from its slice of the archive
+///
+/// Fails with [`io::ErrorKind::InvalidData`] if the data decodes to another size
+/// or CRC32 than the central directory records.
+pub(crate) struct SliceEntry<'a> {
+    name: &'a str,
+    size: u64,
+    compressed_size: u64,
+    crc32: u32,
+    decoder: Decoder<'a>,
+    hasher: crc32fast::Hasher,
+    produced: u64,
+}
+
+enum Decoder<'a> {
+    Stored(&'a [u8]),
+    Deflated(DeflateDecoder<&'a [u8]>),
+}
+
+impl SliceEntry<'_> {
+    /// Name of the entry
+    pub(crate) fn name(&self) -> &str {
+        self.name
+    }
+
+    /// Uncompressed size the central directory records
+    pub(crate) fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Length of the compressed data
+    pub(crate) fn compressed_size(&self) -> u64 {
+        self.compressed_size
+    }
+}
+
+impl Read for SliceEntry<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let n = match &mut self.decoder {
+            Decoder::Stored(data) => data.read(buf)?,
+            Decoder::Deflated(decoder) => decoder.read(buf)?,
+        };
+        self.hasher.update(&buf[..n]);
+        self.produced += n as u64;
+        if self.produced > self.size || (n == 0 && self.produced < self.size) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "entry data does not match its recorded size",
+            ));
+        }
+        if n == 0 && self.hasher.clone().finalize() != self.crc32 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid checksum"));
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::ZipWriter;
+    use zip::write::SimpleFileOptions;
+
+    fn archive_bytes() -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        zip.start_file("stored.txt", stored).unwrap();
+        zip.write_all(b"stored data").unwrap();
+        zip.start_file("deflated.txt", SimpleFileOptions::default()).unwrap();
+        zip.write_all(&b"deflated data ".repeat(100)).unwrap();
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn read_entry(index: &ArchiveIndex, bytes: &[u8], i: usize) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        index.open(bytes, i, "entry").unwrap().read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    #[test]
+    fn test_entries_decode_from_slices() {
+        let bytes = archive_bytes();
+        let mut archive = ZipArchive::new(Cursor::new(&bytes[..])).unwrap();
+        let index = ArchiveIndex::new(&mut archive, bytes.len() as u64).unwrap();
+
+        assert_eq!(index.data(&bytes, 0), b"stored data");
+        assert_eq!(read_entry(&index, &bytes, 0).unwrap(), b"stored data");
+        assert_eq!(read_entry(&index, &bytes, 1).unwrap(), b"deflated data ".repeat(100));
+
+        let mut corrupt = bytes.clone();
+        let start = index.spans[0].start;
+        corrupt[start] ^= 0xff;
+        let error = read_entry(&index, &corrupt, 0).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_overlapping_entries_are_rejected() {
+        // Point the second central directory record at the first local header
+        let mut bytes = archive_bytes();
+        let mut archive = ZipArchive::new(Cursor::new(&bytes[..])).unwrap();
+        let record = archive.by_index_raw(1).unwrap().central_header_start() as usize;
+        bytes[record + 42..record + 46].copy_from_slice(&0u32.to_le_bytes());
+
+        let mut archive = ZipArchive::new(Cursor::new(&bytes[..])).unwrap();
+        let error = ArchiveIndex::new(&mut archive, bytes.len() as u64).unwrap_err();
+        assert!(matches!(error, UnzipError::OverlappingEntry { .. }));
+        assert!(error.to_string().contains("possible zip bomb"));
+    }
+}
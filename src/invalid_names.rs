@@ -0,0 +1,180 @@
+//! Entry names that are not valid in their character set (`--invalid-names`)
+//!
+//! A name flagged as UTF-8 (general purpose bit 11) whose bytes are not UTF-8,
+//! or one that the `-O` / `-I` charset cannot decode, is invalid. By default the
+//! bad bytes become U+FFFD, as the zip crate reads them, which loses them for good
+//! and can make two names the same. `--invalid-names=POLICY` chooses otherwise:
+//!
+//! - `lossy` (default): replace the bytes that do not decode with U+FFFD
+//! - `escape`: keep the name's bytes, writing those that are not UTF-8 as `%XX`
+//!   and `%` itself as `%25`, so `a\xff.txt` is extracted as `a%FF.txt` and
+//!   [`unescape_name`] gives back the exact bytes. Valid names are left alone
+//! - `reject`: refuse the archive before anything is extracted, listed or tested,
+//!   naming the invalid entries (escaped)
+//!
+//! Names of archives without the UTF-8 flag and without `-O` / `-I` are CP437,
+//! which decodes any byte, so only the flag and the charset options make a name
+//! invalid. Forensic users get the raw bytes from
+//! [`CentralRecord::name`](crate::central::CentralRecord::name) and, for each
+//! invalid name, from the `Raw name:` line of `zipinfo -v`.
+//!
+//! # Examples
+//!
+//! ```
+//! use unzip::invalid_names::{InvalidNames, escape_name, parse_invalid_names, unescape_name};
+//!
+//! assert_eq!(parse_invalid_names("escape"), Ok(InvalidNames::Escape));
+//! let escaped = escape_name(b"50%\xff.txt");
+//! assert_eq!(escaped, "50%25%FF.txt");
+//! assert_eq!(unescape_name(&escaped), b"50%\xff.txt");
+//! ```
+
+use std::fmt;
+use std::io::{Read, Seek};
+
+use zip::HasZipMetadata;
+use zip::ZipArchive;
+
+use crate::charset::NameDecoder;
+use crate::error::Result;
+
+/// What `--invalid-names` does with names that do not decode
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InvalidNames {
+    /// Replace the bad bytes with U+FFFD (default)
+    #[default]
+    Lossy,
+    /// Percent-encode the bad bytes, reversibly
+    Escape,
+    /// Refuse the archive
+    Reject,
+}
+
+impl fmt::Display for InvalidNames {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Lossy => write!(f, "lossy"),
+            Self::Escape => write!(f, "escape"),
+            Self::Reject => write!(f, "reject"),
+        }
+    }
+}
+
+/// Parse an `--invalid-names` policy: `lossy`, `escape` or `reject`
+///
+/// # Errors
+///
+/// Returns a message suitable for clap if the policy is unknown
+pub fn parse_invalid_names(value: &str) -> std::result::Result<InvalidNames, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "lossy" => Ok(InvalidNames::Lossy),
+        "escape" => Ok(InvalidNames::Escape),
+        "reject" => Ok(InvalidNames::Reject),
+        _ => Err(format!("invalid name policy: {} (expected lossy, escape or reject)", value)),
+    }
+}
+
+/// Entries whose names are invalid, with `--invalid-names=reject`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidEntryNames {
+    /// The names, escaped as with `--invalid-names=escape`, in archive order
+    pub names: Vec<String>,
+}
+
+impl fmt::Display for InvalidEntryNames {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} entry names do not decode (--invalid-names=reject):", self.names.len())?;
+        for name in &self.names {
+            write!(f, "\n  {}", name)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for InvalidEntryNames {}
+
+/// The name `raw` as text, with `%` and each byte that is not part of valid
+/// UTF-8 written as `%XX`
+pub fn escape_name(raw: &[u8]) -> String {
+    let mut escaped = String::with_capacity(raw.len() + 8);
+    for chunk in raw.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '%' => escaped.push_str("%25"),
+                c => escaped.push(c),
+            }
+        }
+        for byte in chunk.invalid() {
+            escaped.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    escaped
+}
+
+/// The bytes of a name escaped by [`escape_name`]
+///
+/// A `%` without two hex digits after it is kept as it is.
+pub fn unescape_name(escaped: &str) -> Vec<u8> {
+    let bytes = escaped.as_bytes();
+    let mut raw = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|hex| std::str::from_utf8(hex).ok());
+        match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            Some(byte) if bytes[i] == b'%' => {
+                raw.push(byte);
+                i += 3;
+            },
+            _ => {
+                raw.push(bytes[i]);
+                i += 1;
+            },
+        }
+    }
+    raw
+}
+
+/// With `--invalid-names=reject`, check every entry name of the archive
+///
+/// # Errors
+///
+/// Returns an [`InvalidEntryNames`] error naming the entries whose names do not
+/// decode, or an error if the central directory cannot be read
+pub(crate) fn check_names<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    names: &NameDecoder,
+) -> Result<()> {
+    if names.invalid_names() != InvalidNames::Reject {
+        return Ok(());
+    }
+    let mut invalid = Vec::new();
+    for i in 0..archive.len() {
+        let file = archive.by_index_raw(i)?;
+        let (raw, meta) = (file.name_raw(), file.get_metadata());
+        if names.is_invalid(raw, meta.is_utf8, meta.system as u8) {
+            invalid.push(escape_name(raw));
+        }
+    }
+    if invalid.is_empty() {
+        Ok(())
+    } else {
+        Err(InvalidEntryNames { names: invalid }.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_name_round_trips() {
+        assert_eq!(escape_name(b"plain/name.txt"), "plain/name.txt");
+        assert_eq!(escape_name("caf\u{e9}".as_bytes()), "caf\u{e9}");
+        // A truncated sequence and a stray continuation byte
+        let raw = b"a\xc3/\x80%41";
+        assert_eq!(escape_name(raw), "a%C3/%80%2541");
+        assert_eq!(unescape_name(&escape_name(raw)), raw);
+        assert_eq!(unescape_name("100%"), b"100%");
+        assert_eq!(unescape_name("%zz%4"), b"%zz%4");
+    }
+}
@@ -0,0 +1,802 @@
+//! Legacy compression methods: Shrink, Reduce, Implode and PPMd, and LZMA
+//!
+//! PKZIP 1.x wrote Shrink (LZW with partial clearing, method 1), Reduce
+//! (methods 2 to 5, one per compression factor) and Implode (method 6); PPMd
+//! (method 98) comes from WinZip and 7-Zip. The zip crate decodes none of them,
+//! nor LZMA (method 14) as ZIP frames it (see [`crate::lzma`]), so such entries
+//! are opened raw and decoded here by a [`LegacyEntry`], which checks the size
+//! and CRC32 the central directory records, as the zip crate does for the
+//! methods it knows. Deflate64, the other method old Windows archives use, and
+//! XZ the zip crate handles itself.
+//!
+//! Implode comes in four variants, chosen by two general purpose flags the zip
+//! crate does not expose: a 4 or 8 KiB window, and whether literals are
+//! Shannon-Fano coded. The variant is found by decoding with each until one
+//! reproduces the recorded CRC32. PKZIP 1.x imploded entries are small, so this
+//! costs little, and the literal table is told apart by its length anyway.
+//!
+//! Encrypted entries with these methods are not supported. PPMd needs the `ppmd`
+//! feature, on by default.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use std::io;
+//! use zip::ZipArchive;
+//! use unzip::legacy::{LegacyEntry, decodes};
+//!
+//! let mut archive = ZipArchive::new(File::open("pkzip1.zip")?)?;
+//! let mut file = archive.by_index_raw(0)?;
+//! if decodes(&file) {
+//!     io::copy(&mut LegacyEntry::new(&mut file), &mut io::stdout())?;
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::io::{self, Read};
+use std::sync::Arc;
+use zip::CompressionMethod;
+use zip::read::ZipFile;
+
+use crate::lzma::LzmaReader;
+use crate::verify::CrcChecker;
+
+/// Size of the largest back reference window, Implode's 8 KiB
+const WINDOW: usize = 8192;
+
+/// Number of Shrink codes, with codes of up to 13 bits
+const CODES: usize = 8192;
+
+/// Shrink code that introduces a control code
+const CONTROL: u16 = 256;
+
+/// Marks a Shrink code that is not in use
+const FREE: u16 = u16::MAX;
+
+/// Byte that starts a back reference in Reduce data
+const DLE: u8 = 0x90;
+
+/// Whether `file`, opened raw, is an entry only this module can decode: an
+/// unencrypted one with a legacy method, or LZMA
+pub fn decodes(file: &ZipFile<'_>) -> bool {
+    !file.encrypted() && method_code(file.compression()).is_some_and(decoded_here)
+}
+
+/// Code of a method the zip crate does not decode, `None` for the rest
+#[allow(deprecated)]
+fn method_code(method: CompressionMethod) -> Option<u16> {
+    match method {
+        CompressionMethod::Lzma => Some(14),
+        CompressionMethod::Unsupported(code) => Some(code),
+        _ => None,
+    }
+}
+
+fn decoded_here(code: u16) -> bool {
+    matches!(code, 1..=6 | 14) || (cfg!(feature = "ppmd") && code == 98)
+}
+
+/// Decoded data of a legacy entry
+///
+/// The raw data is read at the first read. Fails with
+/// [`io::ErrorKind::InvalidData`] if the data decodes to another CRC32 than the
+/// central directory records, or with an error if it cannot be decoded to the
+/// recorded size.
+pub struct LegacyEntry<R: Read> {
+    raw: Option<R>,
+    name: String,
+    method: u16,
+    size: u64,
+    compressed_size: u64,
+    crc32: u32,
+    decoder: Option<Decoder<R>>,
+    hasher: crc32fast::Hasher,
+    produced: u64,
+}
+
+impl<'a, 'b> LegacyEntry<&'a mut ZipFile<'b>> {
+    /// Decode `file`, an entry opened raw for which [`decodes`] holds
+    pub fn new(file: &'a mut ZipFile<'b>) -> Self {
+        Self {
+            name: file.name().to_string(),
+            method: method_code(file.compression()).unwrap_or_default(),
+            size: file.size(),
+            compressed_size: file.compressed_size(),
+            crc32: file.crc32(),
+            raw: Some(file),
+            decoder: None,
+            hasher: crc32fast::Hasher::new(),
+            produced: 0,
+        }
+    }
+}
+
+impl<R: Read> LegacyEntry<R> {
+    /// Name of the entry
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Uncompressed size the central directory records
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Length of the compressed data
+    pub fn compressed_size(&self) -> u64 {
+        self.compressed_size
+    }
+}
+
+impl<R: Read> Read for LegacyEntry<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let decoder = match &mut self.decoder {
+            Some(decoder) => decoder,
+            None => {
+                let raw = self.raw.take().ok_or_else(truncated)?;
+                let decoder = Decoder::new(self.method, raw, self.size, self.crc32)?;
+                self.decoder.insert(decoder)
+            },
+        };
+        let left = usize::try_from(self.size - self.produced).unwrap_or(usize::MAX);
+        let wanted = buf.len().min(left);
+        if wanted == 0 {
+            if self.hasher.clone().finalize() != self.crc32 {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid checksum"));
+            }
+            return Ok(0);
+        }
+        let n = decoder.read(&mut buf[..wanted])?;
+        if n == 0 {
+            return Err(truncated());
+        }
+        self.hasher.update(&buf[..n]);
+        self.produced += n as u64;
+        Ok(n)
+    }
+}
+
+/// Decoder of one of the methods, producing data until told to stop
+enum Decoder<R: Read> {
+    Shrunk(Unshrink),
+    Reduced(LzReader<Expand>),
+    Imploded(LzReader<Explode>),
+    Lzma(Box<LzmaReader<R>>),
+    #[cfg(feature = "ppmd")]
+    Ppmd(Box<ppmd_rust::Ppmd8Decoder<R>>),
+}
+
+impl<R: Read> Decoder<R> {
+    /// Decoder for the compressed data `raw` of an entry with method `method`,
+    /// which decodes to `size` bytes with the CRC32 `crc32`
+    ///
+    /// LZMA and PPMd data is decoded as it is read. The PKZIP 1.x methods, only
+    /// ever used for small entries, read all of it first.
+    fn new(method: u16, mut raw: R, size: u64, crc32: u32) -> io::Result<Self> {
+        match method {
+            14 => return Ok(Self::Lzma(Box::new(LzmaReader::new(raw, size)?))),
+            #[cfg(feature = "ppmd")]
+            98 => return Ok(Self::Ppmd(Box::new(ppmd(raw)?))),
+            _ => {},
+        }
+        let mut data = Vec::new();
+        raw.read_to_end(&mut data)?;
+        let data: Arc<[u8]> = data.into();
+        Ok(match method {
+            1 => Self::Shrunk(Unshrink::new(Bits::new(data))),
+            2..=5 => {
+                let factor = (method - 1) as u8;
+                Self::Reduced(LzReader::new(Expand::new(Bits::new(data), factor)?))
+            },
+            6 => Self::Imploded(Explode::detect(data, size, crc32)?),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "Compression method not supported",
+                ));
+            },
+        })
+    }
+}
+
+impl<R: Read> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Shrunk(decoder) => decoder.read(buf),
+            Self::Reduced(decoder) => decoder.read(buf),
+            Self::Imploded(decoder) => decoder.read(buf),
+            Self::Lzma(decoder) => decoder.read(buf),
+            #[cfg(feature = "ppmd")]
+            Self::Ppmd(decoder) => decoder.read(buf),
+        }
+    }
+}
+
+/// PPMd variant I revision 1 decoder for `raw`, which starts with the order,
+/// memory size and restore method packed in two bytes
+#[cfg(feature = "ppmd")]
+fn ppmd<R: Read>(mut raw: R) -> io::Result<ppmd_rust::Ppmd8Decoder<R>> {
+    let mut props = [0; 2];
+    raw.read_exact(&mut props)?;
+    let props = u16::from_le_bytes(props);
+    let order = u32::from(props & 0x0f) + 1;
+    let memory = (u32::from((props >> 4) & 0xff) + 1) << 20;
+    let restore = ppmd_rust::RestoreMethod::from(props >> 12);
+    ppmd_rust::Ppmd8Decoder::new(raw, order, memory, restore)
+        .map_err(|_| invalid("invalid PPMd parameters"))
+}
+
+fn truncated() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "compressed data ends early")
+}
+
+fn invalid(what: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, what)
+}
+
+/// Least significant bit first reader of compressed data
+struct Bits {
+    data: Arc<[u8]>,
+    pos: usize,
+    buffer: u64,
+    count: u32,
+}
+
+impl Bits {
+    fn new(data: Arc<[u8]>) -> Self {
+        Self { data, pos: 0, buffer: 0, count: 0 }
+    }
+
+    /// The next `n` bits, at most 32, the first one lowest
+    fn read(&mut self, n: u32) -> io::Result<u32> {
+        while self.count < n {
+            let byte = *self.data.get(self.pos).ok_or_else(truncated)?;
+            self.buffer |= u64::from(byte) << self.count;
+            self.pos += 1;
+            self.count += 8;
+        }
+        let value = (self.buffer & ((1 << n) - 1)) as u32;
+        self.buffer >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    fn byte(&mut self) -> io::Result<u8> {
+        Ok(self.read(8)? as u8)
+    }
+}
+
+/// Shrink decoder: LZW with 9 to 13 bit codes and partial clearing
+///
+/// Follows Info-ZIP's unshrink: a code's string is found by walking its
+/// parents, so a code added on top of a cleared one takes whatever string the
+/// cleared code is given next.
+struct Unshrink {
+    bits: Bits,
+    parent: Vec<u16>,
+    value: Vec<u8>,
+    code_size: u32,
+    /// Lowest code not in use, `CODES` if the table is full
+    next_free: usize,
+    /// The previous code and the first byte of its string
+    previous: Option<(u16, u8)>,
+    /// String of the current code, and how much of it was read
+    string: Vec<u8>,
+    pos: usize,
+}
+
+impl Unshrink {
+    fn new(bits: Bits) -> Self {
+        let mut parent = vec![FREE; CODES];
+        parent[..usize::from(CONTROL)].fill(0);
+        Self {
+            bits,
+            parent,
+            value: (0..CODES).map(|code| code as u8).collect(),
+            code_size: 9,
+            next_free: usize::from(CONTROL) + 1,
+            previous: None,
+            string: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// The next code, after acting on any control codes in front of it
+    fn next_code(&mut self) -> io::Result<u16> {
+        loop {
+            let code = self.bits.read(self.code_size)? as u16;
+            if code != CONTROL {
+                return Ok(code);
+            }
+            match self.bits.read(self.code_size)? {
+                1 if self.code_size < 13 => self.code_size += 1,
+                2 => self.partial_clear(),
+                _ => return Err(invalid("invalid Shrink control code")),
+            }
+        }
+    }
+
+    /// Free every code that is not the prefix of another
+    fn partial_clear(&mut self) {
+        let first = usize::from(CONTROL) + 1;
+        let mut is_parent = vec![false; CODES];
+        for &parent in &self.parent[first..] {
+            if parent != FREE {
+                is_parent[usize::from(parent)] = true;
+            }
+        }
+        for (parent, &is_parent) in self.parent.iter_mut().zip(&is_parent).skip(first) {
+            if !is_parent {
+                *parent = FREE;
+            }
+        }
+        self.next_free = first;
+        self.find_free();
+    }
+
+    fn find_free(&mut self) {
+        while self.next_free < CODES && self.parent[self.next_free] != FREE {
+            self.next_free += 1;
+        }
+    }
+
+    /// Add the string of `parent` followed by `byte` as the lowest free code
+    fn add(&mut self, parent: u16, byte: u8) {
+        if self.next_free < CODES {
+            self.parent[self.next_free] = parent;
+            self.value[self.next_free] = byte;
+            self.next_free += 1;
+            self.find_free();
+        }
+    }
+
+    /// Put the string of `code` in `self.string`
+    fn walk(&mut self, mut code: u16) -> io::Result<()> {
+        self.string.clear();
+        while code > CONTROL {
+            if self.string.len() >= CODES {
+                return Err(invalid("Shrink code refers to itself"));
+            }
+            self.string.push(self.value[usize::from(code)]);
+            code = self.parent[usize::from(code)];
+        }
+        if code >= CONTROL {
+            return Err(invalid("Shrink code refers to a cleared code"));
+        }
+        self.string.push(code as u8);
+        self.string.reverse();
+        Ok(())
+    }
+
+    /// Decode the next code into `self.string`
+    fn next_string(&mut self) -> io::Result<()> {
+        let code = self.next_code()?;
+        self.pos = 0;
+        match self.previous {
+            None if code < CONTROL => {
+                self.string.clear();
+                self.string.push(code as u8);
+            },
+            None => return Err(invalid("Shrink data does not start with a literal")),
+            Some((previous, first)) if self.parent[usize::from(code)] == FREE => {
+                // The code about to be added: the previous string and its first byte
+                if usize::from(code) != self.next_free {
+                    return Err(invalid("invalid Shrink code"));
+                }
+                self.add(previous, first);
+                self.walk(code)?;
+            },
+            Some((previous, _)) => {
+                self.walk(code)?;
+                self.add(previous, self.string[0]);
+            },
+        }
+        self.previous = Some((code, self.string[0]));
+        Ok(())
+    }
+}
+
+impl Read for Unshrink {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut n = 0;
+        while n < buf.len() {
+            if self.pos == self.string.len() {
+                self.next_string()?;
+            }
+            let chunk = (buf.len() - n).min(self.string.len() - self.pos);
+            buf[n..n + chunk].copy_from_slice(&self.string[self.pos..self.pos + chunk]);
+            self.pos += chunk;
+            n += chunk;
+        }
+        Ok(n)
+    }
+}
+
+/// What an LZ77 style decoder found next
+enum Token {
+    Literal(u8),
+    Copy { distance: usize, len: usize },
+}
+
+/// Source of the tokens of Reduce and Implode data
+trait Tokens {
+    fn next_token(&mut self) -> io::Result<Token>;
+}
+
+/// Output of an LZ77 style decoder through its window
+struct LzReader<T> {
+    tokens: T,
+    /// The last `WINDOW` bytes output, zeros before the start as PKZIP has them
+    window: Box<[u8; WINDOW]>,
+    pos: usize,
+    /// Back reference being copied: its distance and the bytes left
+    distance: usize,
+    left: usize,
+}
+
+impl<T: Tokens> LzReader<T> {
+    fn new(tokens: T) -> Self {
+        Self { tokens, window: Box::new([0; WINDOW]), pos: 0, distance: 0, left: 0 }
+    }
+
+    fn push(&mut self, byte: u8) {
+        self.window[self.pos] = byte;
+        self.pos = (self.pos + 1) % WINDOW;
+    }
+}
+
+impl<T: Tokens> Read for LzReader<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut n = 0;
+        while n < buf.len() {
+            if self.left > 0 {
+                let chunk = self.left.min(buf.len() - n);
+                for slot in &mut buf[n..n + chunk] {
+                    let byte = self.window[(self.pos + WINDOW - self.distance) % WINDOW];
+                    self.push(byte);
+                    *slot = byte;
+                }
+                self.left -= chunk;
+                n += chunk;
+                continue;
+            }
+            match self.tokens.next_token()? {
+                Token::Literal(byte) => {
+                    self.push(byte);
+                    buf[n] = byte;
+                    n += 1;
+                },
+                Token::Copy { distance, len } => (self.distance, self.left) = (distance, len),
+            }
+        }
+        Ok(n)
+    }
+}
+
+/// Reduce decoder: bytes predicted from follower sets of the byte before them,
+/// carrying back references after a DLE byte
+struct Expand {
+    bits: Bits,
+    /// The bytes likely to follow each byte, read ahead of the data
+    followers: Vec<Vec<u8>>,
+    last: u8,
+    /// Bits of a back reference's V byte that count towards its length
+    len_bits: u32,
+}
+
+impl Expand {
+    fn new(mut bits: Bits, factor: u8) -> io::Result<Self> {
+        let mut followers = vec![Vec::new(); 256];
+        for set in followers.iter_mut().rev() {
+            let len = bits.read(6)?;
+            if len > 32 {
+                return Err(invalid("invalid Reduce follower set"));
+            }
+            for _ in 0..len {
+                set.push(bits.byte()?);
+            }
+        }
+        Ok(Self { bits, followers, last: 0, len_bits: u32::from(8 - factor) })
+    }
+
+    fn next_byte(&mut self) -> io::Result<u8> {
+        let set = &self.followers[usize::from(self.last)];
+        let byte = if set.is_empty() || self.bits.read(1)? == 1 {
+            self.bits.byte()?
+        } else {
+            let index_bits = (usize::BITS - (set.len() - 1).leading_zeros()).max(1);
+            let index = self.bits.read(index_bits)? as usize;
+            *set.get(index).ok_or_else(|| invalid("invalid Reduce follower index"))?
+        };
+        self.last = byte;
+        Ok(byte)
+    }
+}
+
+impl Tokens for Expand {
+    fn next_token(&mut self) -> io::Result<Token> {
+        let byte = self.next_byte()?;
+        if byte != DLE {
+            return Ok(Token::Literal(byte));
+        }
+        let v = usize::from(self.next_byte()?);
+        if v == 0 {
+            return Ok(Token::Literal(DLE));
+        }
+        let mask = (1 << self.len_bits) - 1;
+        let mut len = v & mask;
+        if len == mask {
+            len += usize::from(self.next_byte()?);
+        }
+        let distance = ((v >> self.len_bits) << 8) + usize::from(self.next_byte()?) + 1;
+        Ok(Token::Copy { distance, len: len + 3 })
+    }
+}
+
+/// Implode decoder: literals and back references, Shannon-Fano coded
+struct Explode {
+    bits: Bits,
+    literals: Option<ShannonFano>,
+    lengths: ShannonFano,
+    distances: ShannonFano,
+    min_len: usize,
+    /// Low bits of a distance stored as they are
+    low_bits: u32,
+}
+
+impl Explode {
+    fn new(mut bits: Bits, large_window: bool, literal_tree: bool) -> io::Result<Self> {
+        let literals = literal_tree.then(|| ShannonFano::read(&mut bits, 256)).transpose()?;
+        let lengths = ShannonFano::read(&mut bits, 64)?;
+        let distances = ShannonFano::read(&mut bits, 64)?;
+        Ok(Self {
+            bits,
+            literals,
+            lengths,
+            distances,
+            min_len: if literal_tree { 3 } else { 2 },
+            low_bits: if large_window { 7 } else { 6 },
+        })
+    }
+
+    /// Decoder for the variant of Implode that decodes `data` to `size` bytes
+    /// with the CRC32 `crc32`, or else the first that decodes it at all
+    fn detect(data: Arc<[u8]>, size: u64, crc32: u32) -> io::Result<LzReader<Self>> {
+        let open = |(large_window, literal_tree)| {
+            Self::new(Bits::new(Arc::clone(&data)), large_window, literal_tree).map(LzReader::new)
+        };
+        let mut checker = CrcChecker::new();
+        let mut decodes = None;
+        for variant in [(true, true), (true, false), (false, true), (false, false)] {
+            let Ok(decoder) = open(variant) else {
+                continue;
+            };
+            match checker.crc32_of(&mut decoder.take(size)) {
+                Ok(crc) if crc == crc32 => return open(variant),
+                Ok(_) => {
+                    decodes.get_or_insert(variant);
+                },
+                Err(_) => {},
+            }
+        }
+        open(decodes.ok_or_else(|| invalid("invalid Implode data"))?)
+    }
+}
+
+impl Tokens for Explode {
+    fn next_token(&mut self) -> io::Result<Token> {
+        if self.bits.read(1)? == 1 {
+            let byte = match &self.literals {
+                Some(literals) => literals.decode(&mut self.bits)? as u8,
+                None => self.bits.byte()?,
+            };
+            return Ok(Token::Literal(byte));
+        }
+        let low = self.bits.read(self.low_bits)? as usize;
+        let high = usize::from(self.distances.decode(&mut self.bits)?);
+        let distance = ((high << self.low_bits) | low) + 1;
+        let symbol = usize::from(self.lengths.decode(&mut self.bits)?);
+        let mut len = symbol + self.min_len;
+        if symbol == 63 {
+            len += usize::from(self.bits.byte()?);
+        }
+        Ok(Token::Copy { distance, len })
+    }
+}
+
+/// Implode's Shannon-Fano code: canonical, shortest codes first, with every
+/// bit inverted
+struct ShannonFano {
+    /// Number of codes of each length
+    counts: [u16; 17],
+    /// Symbols ordered by code length, then value
+    symbols: Vec<u16>,
+}
+
+impl ShannonFano {
+    /// Read the code lengths of `n` symbols, run-length coded
+    fn read(bits: &mut Bits, n: usize) -> io::Result<Self> {
+        let mut lengths = Vec::with_capacity(n);
+        for _ in 0..=bits.byte()? {
+            let byte = bits.byte()?;
+            let (len, run) = ((byte & 0x0f) + 1, usize::from(byte >> 4) + 1);
+            if lengths.len() + run > n {
+                return Err(invalid("too many Implode code lengths"));
+            }
+            lengths.extend(std::iter::repeat_n(len, run));
+        }
+        if lengths.len() < n {
+            return Err(invalid("too few Implode code lengths"));
+        }
+        let mut counts = [0u16; 17];
+        for &len in &lengths {
+            counts[usize::from(len)] += 1;
+        }
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = left * 2 - i32::from(count);
+            if left < 0 {
+                return Err(invalid("oversubscribed Implode code"));
+            }
+        }
+        if left != 0 {
+            return Err(invalid("incomplete Implode code"));
+        }
+        let mut symbols: Vec<u16> = (0..n as u16).collect();
+        symbols.sort_by_key(|&symbol| lengths[usize::from(symbol)]);
+        Ok(Self { counts, symbols })
+    }
+
+    fn decode(&self, bits: &mut Bits) -> io::Result<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= (bits.read(1)? ^ 1) as i32;
+            let count = i32::from(count);
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid("invalid Implode code"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Least significant bit first writer, to build test data
+    #[derive(Default)]
+    struct BitWriter {
+        bytes: Vec<u8>,
+        count: u32,
+    }
+
+    impl BitWriter {
+        fn write(&mut self, value: u32, n: u32) {
+            for i in 0..n {
+                if self.count.is_multiple_of(8) {
+                    self.bytes.push(0);
+                }
+                let bit = ((value >> i) & 1) as u8;
+                *self.bytes.last_mut().unwrap() |= bit << (self.count % 8);
+                self.count += 1;
+            }
+        }
+
+        /// A Shannon-Fano code of `len` bits, most significant bit first and
+        /// inverted, as Implode stores them
+        fn code(&mut self, code: u32, len: u32) {
+            for i in (0..len).rev() {
+                self.write(((code >> i) & 1) ^ 1, 1);
+            }
+        }
+    }
+
+    fn decode(method: u16, data: Vec<u8>, size: usize) -> io::Result<Vec<u8>> {
+        let mut out = vec![0; size];
+        let crc32 = 0; // Only Implode looks at it
+        Decoder::new(method, &data[..], size as u64, crc32)?.read_exact(&mut out)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn test_unshrink() {
+        // The LZW example of Welch's paper, as PKZIP shrinks it
+        let shrunk = vec![0x61, 0xc4, 0x04, 0x1c, 0x23, 0xb0, 0x60, 0x98, 0x83, 0x08, 0xc3, 0x00];
+        assert_eq!(decode(1, shrunk, 17).unwrap(), b"ababcbababaaaaaaa");
+
+        // Two literals, a partial clear that frees the code they added, then
+        // the KwKwK case on that code
+        let mut bits = BitWriter::default();
+        for code in [u32::from(b'x'), u32::from(b'y'), 256, 2, 257] {
+            bits.write(code, 9);
+        }
+        assert_eq!(decode(1, bits.bytes, 4).unwrap(), b"xyyy");
+        assert!(decode(1, vec![0x00, 0x03], 2).is_err());
+    }
+
+    #[test]
+    fn test_expand() {
+        let mut bits = BitWriter::default();
+        // Empty follower sets but the one after 'a', which holds 'b' and 'c'
+        for byte in (0..=255u8).rev() {
+            if byte == b'a' {
+                bits.write(2, 6);
+                bits.write(u32::from(b'b'), 8);
+                bits.write(u32::from(b'c'), 8);
+            } else {
+                bits.write(0, 6);
+            }
+        }
+        bits.write(u32::from(b'a'), 8);
+        bits.write(0, 1); // follower 1 of 'a'
+        bits.write(1, 1);
+        bits.write(u32::from(DLE), 8);
+        bits.write(0, 8); // a literal DLE
+        bits.write(u32::from(DLE), 8);
+        // Factor 2: length 2 + 3 in the low 6 bits of V, distance 3 in W
+        bits.write(2, 8);
+        bits.write(2, 8);
+        assert_eq!(decode(3, bits.bytes, 8).unwrap(), b"ac\x90ac\x90ac");
+    }
+
+    /// Implode data with no literal tree and 6 bit codes for every length and
+    /// distance symbol, holding "abc" and a back reference repeating it 3 times
+    fn imploded(large_window: bool) -> Vec<u8> {
+        let mut bits = BitWriter::default();
+        for _ in 0..2 {
+            bits.write(3, 8);
+            for _ in 0..4 {
+                bits.write(0xf5, 8); // 16 lengths of 6 bits
+            }
+        }
+        for byte in b"abc" {
+            bits.write(1, 1);
+            bits.write(u32::from(*byte), 8);
+        }
+        bits.write(0, 1);
+        bits.write(2, if large_window { 7 } else { 6 }); // distance 3
+        bits.code(0, 6);
+        bits.code(7, 6); // length 7 + 2
+        bits.bytes
+    }
+
+    #[test]
+    fn test_explode_detects_variant() {
+        let expected = b"abcabcabcabc";
+        let crc32 = crc32fast::hash(expected);
+        for large_window in [false, true] {
+            let data: Arc<[u8]> = imploded(large_window).into();
+            let mut out = Vec::new();
+            Explode::detect(data, 12, crc32)
+                .unwrap()
+                .take(12)
+                .read_to_end(&mut out)
+                .unwrap();
+            assert_eq!(out, expected);
+        }
+        assert!(Explode::detect(vec![0xff; 4].into(), 12, crc32).is_err());
+    }
+
+    #[cfg(feature = "ppmd")]
+    #[test]
+    fn test_ppmd() {
+        use std::io::Write;
+        let text = b"PPMd compresses text like this text very well. ".repeat(20);
+        // Order 6, 16 MiB, restart
+        let props: u16 = 5 | (15 << 4);
+        let mut data = props.to_le_bytes().to_vec();
+        let restore = ppmd_rust::RestoreMethod::Restart;
+        let mut encoder = ppmd_rust::Ppmd8Encoder::new(&mut data, 6, 16 << 20, restore).unwrap();
+        encoder.write_all(&text).unwrap();
+        encoder.finish(false).unwrap();
+        assert_eq!(decode(98, data, text.len()).unwrap(), text);
+    }
+}
@@ -6,13 +6,91 @@
 //!
 //! # Features
 //!
-//! - Info-ZIP compatible command-line interface
-//! - Multiple overwrite modes (always, never, freshen, update)
-//! - Pattern-based file filtering with glob support
-//! - Archive listing and integrity testing
-//! - Progress reporting
-//! - File timestamp and permission preservation
+//! - Info-ZIP compatible command-line interface, including its free argument order
+//! - `--compat=infozip` output, timestamps, exit codes and overwrite prompts for scripts
+//! - Info-ZIP's exact `-l` and `-v` listing layout by default, or `--format pretty`
+//! - CSV listings (`--format csv`) and NUL-delimited names (`--print0`)
+//! - Listings sorted by name, size, ratio or time, and bounded by size and date
+//! - Multiple overwrite modes (always, never, freshen, update, `--auto-rename`)
+//! - Refusal to extract an archive over itself or its split volumes
+//!   (`--allow-self-overwrite` to proceed)
+//! - `--if-changed` updates that compare content by size and CRC32 instead of times
+//! - `--patch-in-place` rewriting of only the changed blocks of existing files
+//! - `--dry-run` reporting of what extraction would do, without writing anything
+//! - Pattern-based file filtering with glob support, and Info-ZIP's `-W` wildcard semantics
+//! - `--strip-components` removal of leading path components, like tar
+//! - `--transform 's/REGEX/REPLACEMENT/'` rewriting of output paths, like tar
+//! - `--name-template` output names built from entry metadata (CRC-32, index, date)
+//! - Exact member lists (`--members-from`) that fail on any name missing from the archive
+//! - Info-ZIP's `caution: filename not matched` and exit code 11 for patterns matching nothing
+//! - Single entries by central-directory position ([`extract_entry_by_index`])
+//! - Entry name character set conversion for non-UTF-8 archives (`-O` / `-I`)
+//! - `--invalid-names` escaping or rejection of names that do not decode
+//! - `--raw-names` byte-exact names on disk for forensic extraction, shown escaped
+//! - Normalization and rejection of unsafe or degenerate entry names
+//! - Warnings about directories spelled in different cases, merged with `--merge-case-dirs`
+//! - Writes confined to the output directory, even through pre-existing symlinks
+//! - Flat storage plus manifest for paths beyond filesystem limits
+//! - Text mode line ending conversion (`-a` / `-aa`)
+//! - Output size, compression ratio and entry count limits for untrusted archives
+//! - Rejection of overlapping entries (zip bombs), checked against each entry's exact data range
+//! - File count and total size assertions for deployment scripts (`--expect-files`)
+//! - `--opendoc-check` of EPUB, OpenDocument and Office Open XML container conventions
+//! - Output directory checked before the archive is scanned, and `--no-create-dir` to require it
+//! - `--smart-dir` extraction into a directory named after the archive when it has no single top directory
+//! - `--stamp-output-dir` to give the output directory the archive's or the newest file's modification time
+//! - `--implied-dir-times` for deterministic times of directories that have no entry
+//! - Several archives per run, from `'*.zip'` wildcards or a list, optionally `--subdir-per-archive`
+//! - `--wait-complete` polling of archives still being uploaded until their central directory is in place
+//! - Automatic joining of split archives (`.zip.001`, `.partNN.zip`, `.z01` ... `.zip`)
+//! - `--recurse-archives` extraction of zips inside zips, with depth and size guards
+//! - Self-extracting archives, and archives whose comment was cut off, like Info-ZIP
+//! - `--comment-json` export of JSON metadata kept in the archive comment
+//! - funzip-compatible `--filter` of the first member of a zip or gzip stream
+//! - Archive listing, streamed as the central directory is read, and integrity testing
+//! - Shrink, Reduce, Implode and PPMd entries of old archives, beside Deflate64 and the rest
+//! - LZMA and XZ entries of 7-Zip archives
+//! - TAP reports of integrity tests (`--report=tap`) for test harnesses
+//! - `--test-quick` header-only consistency check, without decompressing any data
+//! - `--size-check` reconciliation of 32-bit, Zip64 and data descriptor sizes
+//! - Listing totals alone (`--totals-only`), as one text or JSON line
+//! - Tree view of the entries with per-directory sizes (`--tree`)
+//! - `find -ls` and `ls -lR` listings (`--format find`, `--format ls-lR`) for mirror tooling
+//! - All-or-nothing `--transactional` extraction through a staging directory
+//! - Read-only destination files skipped with a warning, or replaced with `--force-readonly`
+//! - `--shared-dest` extraction through temporary names, safe next to other processes
+//! - `--checkpoint-every` and `--resume` for workers that may be evicted mid-extraction
+//! - `--metrics-file` run counters in Prometheus textfile format for scheduled jobs
+//! - `--verify` read-back of every written file against the CRC32 of its data
+//! - `--compress-output=zst` streaming recompression of extracted files into `NAME.zst`
+//! - `--scan-socket` virus scanning through clamd while writing, with a quarantine
+//! - `--heuristic-decode` recovery of entries with a mislabelled Stored/Deflate method
+//! - `--salvage` of archives with a missing or damaged central directory from local headers
+//! - Archive shape statistics (`--stats`) that also pick the directory creation strategy
+//! - Deep `--lint` check: re-compression round trip and legacy reader compatibility
+//! - `--diff DIR` comparison of the archive with a deployed tree
+//! - Encryption scheme detection (ZipCrypto, AES AE-1/AE-2) in zipinfo
+//! - Progress reporting and an extraction event stream for GUI integrations
+//! - Status snapshots of long extractions on SIGUSR1, without a progress terminal
+//! - [`ExtractObserver`] callbacks for per-entry progress of extraction and testing
+//! - Library-friendly [`ExtractOptions`] builder, independent of the CLI arguments
+//! - Typed [`UnzipError`] so callers can react to specific failures
+//! - Cancellation of running extractions and tests through a [`CancelToken`]
+//! - In-memory extraction ([`extract_to_vec`]) for testing against archive fixtures
+//! - [`extract_to_memory`] for plugin systems, capped at 1 GiB unless a limit is set
+//! - `--to-tar` conversion of the archive to a tar stream, written to a file or stdout
+//! - [`extract_to_sink`] into any [`sink::EntrySink`]: memory, a tar stream, an object store
+//! - [`hooks::EntryHooks`] to extract, skip or rename each entry by the caller's own policy
+//! - [`UnzipSession`] to list, test, extract and read entries of any seekable reader
+//! - `--chown USER:GROUP` ownership for root-run deployments, without a `chown -R`
+//! - Pattern files (`--include-from`, `--exclude-from`) merged with the command line's
+//! - `--duplicates POLICY` to choose which of the entries sharing a name is extracted
+//! - `--priority PATTERNS` to extract manifests and indexes before the bulk data
+//! - Extraction workers scaled up and down by measured throughput, up to `-T`
+//! - File timestamp and permission preservation, with sub-second UT/NTFS times
+//! - Permissions interpreted per host OS, so Windows archives extract sensibly
 //! - Linux kernel optimizations for maximum throughput
+//! - `--cache` control over whether extraction evicts the rest of the page cache
 //!
 //! # Performance
 //!
@@ -27,29 +105,112 @@
 //! ```no_run
 //! use std::fs::File;
 //! use zip::ZipArchive;
-//! use unzip::{Args, extract_archive};
-//! use clap::Parser;
+//! use unzip::{ExtractOptions, OverwritePolicy, extract_archive};
 //!
 //! let file = File::open("archive.zip")?;
 //! let mut archive = ZipArchive::new(file)?;
-//! let args = Args::parse();
-//! extract_archive(&mut archive, &args)?;
+//! let options = ExtractOptions::new().output_dir("out").overwrite(OverwritePolicy::Always);
+//! extract_archive(&mut archive, &options)?;
 //! # Ok::<(), Box<dyn std::error::Error>>(())
 //! ```
 
 pub mod args;
+pub mod batch;
+pub mod cancel;
+pub mod casedirs;
+pub mod central;
+pub mod charset;
+pub mod checkpoint;
+pub mod comment_json;
+pub mod compat;
+pub mod destination;
+pub mod diff;
+pub mod dirstamp;
+pub mod duplicates;
+pub mod encryption;
+pub mod error;
+pub mod events;
+pub mod expect;
 pub mod extract;
+pub mod filter;
+pub mod flatten;
 pub mod glob;
+pub mod hooks;
+pub mod implied_dirs;
+pub mod index;
+pub mod invalid_names;
+pub mod legacy;
+pub mod limits;
+pub mod lint;
 pub mod linux;
 pub mod list;
+pub mod list_filter;
+pub mod lzma;
+pub mod members;
+pub mod metrics;
+pub mod multipart;
+pub mod nested;
+pub mod opendoc;
+pub mod options;
+pub mod output_dir;
+pub mod owner;
 pub mod password;
+pub mod patch;
+pub mod path_safety;
+pub mod pattern_files;
+pub mod permissions;
+pub mod priority;
+pub mod quick_test;
+pub mod raw_names;
+pub mod reader;
+pub mod readonly;
+pub mod recompress;
+pub mod records;
+pub mod recovery;
+pub mod rename;
+pub mod report;
+pub mod salvage;
+pub mod scaling;
+pub mod scan;
+pub mod self_overwrite;
+pub mod session;
+pub mod sfx;
+pub mod shape;
+pub mod shared;
+pub mod sink;
+pub mod size_check;
+pub mod snapshot;
+pub mod status;
+pub mod tar;
+pub mod template;
 pub mod test_archive;
+pub mod textmode;
+pub mod timestamps;
+pub mod transaction;
+pub mod transform;
+pub mod tree;
+pub mod unchanged;
+pub mod unix_listing;
+pub mod unmatched;
 pub mod utils;
+pub mod verify;
+pub mod wait_complete;
 pub mod zipinfo;
 
 pub use args::Args;
-pub use extract::{ArchiveSource, extract_archive, extract_archive_threaded};
+pub use cancel::CancelToken;
+pub use error::UnzipError;
+pub use events::{
+    ExtractEvent, ExtractEvents, ExtractObserver, ExtractReport, PlannedAction, SkipReason,
+    TestFailure, events,
+};
+pub use extract::{
+    ArchiveSource, extract_archive, extract_archive_threaded, extract_entry_by_index,
+    extract_to_memory, extract_to_sink, extract_to_vec,
+};
 pub use glob::glob_match;
 pub use list::{display_comment, list_contents};
-pub use test_archive::test_archive;
+pub use options::{ExtractOptions, OverwritePolicy};
+pub use session::{EntryInfo, UnzipSession};
+pub use test_archive::{TestReport, test_archive, test_archive_with};
 pub use utils::{format_size, should_extract};
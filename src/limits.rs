@@ -0,0 +1,301 @@
+//! Extraction limits against decompression bombs
+//!
+//! `--max-output-size`, `--max-ratio` and `--max-entries` let CI systems unpack
+//! untrusted archives safely. Limits are enforced twice:
+//!
+//! - Before any data is read, [`ExtractionLimits::check_archive`] rejects archives
+//!   whose central directory already declares too many entries, too much output, or
+//!   an entry with an excessive compression ratio
+//! - While data is decompressed, [`OutputBudget::guard`] counts the bytes actually
+//!   produced, since declared sizes are attacker controlled
+//!
+//! Either check fails with a [`LimitExceeded`] error naming the limit.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::io::Read;
+//! use unzip::limits::{ExtractionLimits, OutputBudget};
+//!
+//! let limits = ExtractionLimits { max_output_size: Some(4), ..Default::default() };
+//! let budget = OutputBudget::new(limits);
+//! let mut reader = budget.guard(&b"too long"[..], "bomb.txt", 8);
+//! let mut out = Vec::new();
+//! assert!(reader.read_to_end(&mut out).is_err());
+//! ```
+
+use std::fmt;
+use std::io::{self, Read, Seek};
+use std::sync::atomic::{AtomicU64, Ordering};
+use zip::ZipArchive;
+
+use crate::args::Args;
+use crate::error::Result;
+
+/// Output an extraction into memory may hold when no `max_output_size` is set
+/// (see [`crate::extract::extract_to_memory`])
+pub const DEFAULT_MEMORY_LIMIT: u64 = 1024 * 1024 * 1024;
+
+/// Limits on what an archive may expand to; `None` means unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExtractionLimits {
+    /// Maximum total number of bytes written (`--max-output-size`)
+    pub max_output_size: Option<u64>,
+    /// Maximum uncompressed/compressed size ratio of any entry (`--max-ratio`)
+    pub max_ratio: Option<u64>,
+    /// Maximum number of entries in the archive (`--max-entries`)
+    pub max_entries: Option<usize>,
+}
+
+/// Which limit an archive exceeded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LimitExceeded {
+    /// The archive has more entries than allowed
+    Entries { entries: usize, limit: usize },
+    /// Total output is larger than allowed
+    OutputSize { limit: u64 },
+    /// An entry expands more than allowed
+    Ratio { name: String, limit: u64 },
+    /// Archives inside the archive expand beyond the default `--recurse-archives`
+    /// guard
+    NestedOutput { limit: u64 },
+    /// An extraction into memory grows beyond [`DEFAULT_MEMORY_LIMIT`]
+    MemoryOutput { limit: u64 },
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Entries { entries, limit } => {
+                write!(f, "archive has {} entries, more than --max-entries {}", entries, limit)
+            },
+            Self::OutputSize { limit } => {
+                write!(f, "extracted data exceeds --max-output-size of {} bytes", limit)
+            },
+            Self::Ratio { name, limit } => write!(
+                f,
+                "{} expands more than --max-ratio {} times its compressed size",
+                name, limit
+            ),
+            Self::NestedOutput { limit } => write!(
+                f,
+                "nested archives expand beyond {} bytes; set --max-output-size to allow more",
+                limit
+            ),
+            Self::MemoryOutput { limit } => write!(
+                f,
+                "in-memory extraction exceeds {} bytes; set max_output_size to allow more",
+                limit
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+impl ExtractionLimits {
+    /// Read limits from command-line arguments
+    pub fn from_args(args: &Args) -> Self {
+        Self {
+            max_output_size: args.max_output_size,
+            max_ratio: args.max_ratio,
+            max_entries: args.max_entries,
+        }
+    }
+
+    /// Whether any limit is set
+    pub fn is_limited(&self) -> bool {
+        *self != Self::default()
+    }
+
+    /// Check the sizes declared in the central directory
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`LimitExceeded`] error for the first limit the archive declares
+    /// itself beyond, or an error if the central directory cannot be read
+    pub fn check_archive<R: Read + Seek>(&self, archive: &mut ZipArchive<R>) -> Result<()> {
+        if !self.is_limited() {
+            return Ok(());
+        }
+        if let Some(limit) = self.max_entries
+            && archive.len() > limit
+        {
+            return Err(LimitExceeded::Entries { entries: archive.len(), limit }.into());
+        }
+
+        let mut declared = 0u64;
+        for i in 0..archive.len() {
+            let file = archive.by_index_raw(i)?;
+            declared = declared.saturating_add(file.size());
+            if let Some(limit) = self.max_output_size
+                && declared > limit
+            {
+                return Err(LimitExceeded::OutputSize { limit }.into());
+            }
+            if let Some(limit) = self.max_ratio
+                && exceeds_ratio(file.size(), file.compressed_size(), limit)
+            {
+                return Err(LimitExceeded::Ratio { name: file.name().to_string(), limit }.into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether `produced` bytes from `compressed` input exceed a ratio limit
+fn exceeds_ratio(produced: u64, compressed: u64, limit: u64) -> bool {
+    produced > compressed.max(1).saturating_mul(limit)
+}
+
+/// Running total of output, shared by all workers of one extraction
+#[derive(Debug, Default)]
+pub struct OutputBudget {
+    limits: ExtractionLimits,
+    written: AtomicU64,
+}
+
+impl OutputBudget {
+    /// Create a budget with nothing spent
+    pub fn new(limits: ExtractionLimits) -> Self {
+        Self { limits, written: AtomicU64::new(0) }
+    }
+
+    /// Wrap an entry reader so its output counts against the limits
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - Decompressing reader of one entry
+    /// * `name` - Entry name, for error messages
+    /// * `compressed_size` - Compressed size of the entry
+    pub fn guard<'a, R: Read>(
+        &'a self,
+        reader: R,
+        name: &'a str,
+        compressed_size: u64,
+    ) -> LimitedReader<'a, R> {
+        LimitedReader { inner: reader, budget: self, name, compressed_size, produced: 0 }
+    }
+}
+
+/// Reader returned by [`OutputBudget::guard`]
+///
+/// Fails with an `io::Error` wrapping [`LimitExceeded`] as soon as a limit is crossed.
+pub struct LimitedReader<'a, R> {
+    inner: R,
+    budget: &'a OutputBudget,
+    name: &'a str,
+    compressed_size: u64,
+    produced: u64,
+}
+
+impl<R: Read> Read for LimitedReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        let limits = &self.budget.limits;
+        if n == 0 || !limits.is_limited() {
+            return Ok(n);
+        }
+
+        self.produced += n as u64;
+        if let Some(limit) = limits.max_ratio
+            && exceeds_ratio(self.produced, self.compressed_size, limit)
+        {
+            let name = self.name.to_string();
+            return Err(io::Error::other(LimitExceeded::Ratio { name, limit }));
+        }
+        let written = self.budget.written.fetch_add(n as u64, Ordering::Relaxed) + n as u64;
+        if let Some(limit) = limits.max_output_size
+            && written > limit
+        {
+            return Err(io::Error::other(LimitExceeded::OutputSize { limit }));
+        }
+        Ok(n)
+    }
+}
+
+/// Parse a byte count with an optional binary suffix (`K`, `M`, `G`, `T`)
+///
+/// # Errors
+///
+/// Returns a message suitable for clap if the value is not a valid size
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (digits, suffix) = value.split_at(split);
+    let shift = match suffix.to_ascii_uppercase().trim_end_matches(['B', 'I']) {
+        "" => 0,
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        "T" => 40,
+        _ => return Err(format!("invalid size suffix: {}", suffix)),
+    };
+    let number: u64 = digits.parse().map_err(|_| format!("invalid size: {}", value))?;
+    number
+        .checked_mul(1 << shift)
+        .ok_or_else(|| format!("size too large: {}", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::UnzipError;
+    use std::io::{Cursor, Write};
+    use zip::ZipWriter;
+    use zip::write::SimpleFileOptions;
+
+    fn zeros_archive(entries: usize, size: usize) -> ZipArchive<Cursor<Vec<u8>>> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for i in 0..entries {
+            zip.start_file(format!("zeros{}.bin", i), SimpleFileOptions::default()).unwrap();
+            zip.write_all(&vec![0u8; size]).unwrap();
+        }
+        ZipArchive::new(zip.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("4K"), Ok(4096));
+        assert_eq!(parse_size("2MiB"), Ok(2 << 20));
+        assert_eq!(parse_size("1g"), Ok(1 << 30));
+        assert!(parse_size("1X").is_err());
+        assert!(parse_size("").is_err());
+    }
+
+    #[test]
+    fn test_check_archive_declared_limits() {
+        let mut archive = zeros_archive(3, 100_000);
+        assert!(ExtractionLimits::default().check_archive(&mut archive).is_ok());
+
+        let limits = ExtractionLimits { max_entries: Some(2), ..Default::default() };
+        let err = limits.check_archive(&mut archive).unwrap_err();
+        assert!(matches!(
+            err,
+            UnzipError::Limit(LimitExceeded::Entries { entries: 3, limit: 2 })
+        ));
+
+        let limits = ExtractionLimits { max_output_size: Some(250_000), ..Default::default() };
+        assert!(limits.check_archive(&mut archive).is_err());
+
+        let limits = ExtractionLimits { max_ratio: Some(10), ..Default::default() };
+        let err = limits.check_archive(&mut archive).unwrap_err();
+        assert!(err.to_string().contains("zeros0.bin"), "{}", err);
+    }
+
+    #[test]
+    fn test_guard_counts_actual_output() {
+        let budget =
+            OutputBudget::new(ExtractionLimits { max_output_size: Some(10), ..Default::default() });
+        let mut out = Vec::new();
+        budget.guard(&[1u8; 6][..], "a", 6).read_to_end(&mut out).unwrap();
+        let err = budget.guard(&[1u8; 6][..], "b", 6).read_to_end(&mut out).unwrap_err();
+        assert!(err.to_string().contains("--max-output-size"));
+
+        let budget =
+            OutputBudget::new(ExtractionLimits { max_ratio: Some(2), ..Default::default() });
+        let err = budget.guard(&[0u8; 100][..], "bomb", 10).read_to_end(&mut out).unwrap_err();
+        assert!(err.to_string().contains("bomb"));
+    }
+}
@@ -0,0 +1,347 @@
+//! Deep compatibility check for archives that will be re-served (`--lint`)
+//!
+//! Goes further than `-t`: besides checking every entry's CRC32, the decompressed
+//! data is deflated again and inflated once more, and the CRC of that round trip
+//! must match too. This catches decoder edge cases that would otherwise be passed
+//! on when the data is re-compressed. Entries stored with methods that readers
+//! limited to Stored and Deflate cannot handle, such as Deflate64 with its 64 KiB
+//! distances, are reported as compatibility warnings. Encrypted entries are
+//! reported with their scheme (see [`crate::encryption`]), as their data cannot
+//! be checked without the password.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use zip::ZipArchive;
+//! use unzip::Args;
+//! use unzip::lint::lint_entries;
+//! use clap::Parser;
+//!
+//! let mut archive = ZipArchive::new(File::open("archive.zip")?)?;
+//! for finding in lint_entries(&mut archive, &Args::parse())? {
+//!     println!("{}: {}", finding.name, finding.issue);
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use flate2::write::{DeflateDecoder, DeflateEncoder};
+use flate2::{Compression, CrcWriter};
+use std::fmt;
+use std::io::{self, Read, Seek, Write};
+use zip::{CompressionMethod, ZipArchive};
+
+use crate::args::Args;
+use crate::charset::NameDecoder;
+use crate::encryption::{EncryptionScheme, entry_scheme};
+use crate::error::{Result, UnzipError};
+use crate::invalid_names::check_names;
+use crate::legacy::{LegacyEntry, decodes};
+use crate::limits::{ExtractionLimits, LimitExceeded, OutputBudget};
+
+/// A problem found in one entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LintIssue {
+    /// The entry uses a method that readers limited to Stored and Deflate lack
+    LegacyIncompatible {
+        /// Name of the method
+        method: &'static str,
+    },
+    /// The entry data could not be read
+    Unreadable {
+        /// Why reading failed
+        error: String,
+    },
+    /// The decompressed data does not match the stored CRC32
+    CrcMismatch { stored: u32, computed: u32 },
+    /// Deflating and inflating the data again changed it
+    RoundTripMismatch { computed: u32, round_trip: u32 },
+    /// The entry is encrypted, so its data was not checked
+    Encrypted {
+        /// How it is encrypted
+        scheme: EncryptionScheme,
+    },
+}
+
+impl LintIssue {
+    /// Whether the issue means the data is bad, rather than merely less portable
+    pub fn is_error(&self) -> bool {
+        !matches!(self, Self::LegacyIncompatible { .. } | Self::Encrypted { .. })
+    }
+}
+
+impl fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LegacyIncompatible { method } => {
+                write!(f, "uses {}, which many legacy readers cannot decompress", method)
+            },
+            Self::Unreadable { error } => write!(f, "{}", error),
+            Self::CrcMismatch { stored, computed } => {
+                write!(f, "CRC mismatch (stored: {:08x}, computed: {:08x})", stored, computed)
+            },
+            Self::RoundTripMismatch { computed, round_trip } => write!(
+                f,
+                "data changed after re-compression (CRC {:08x}, after round trip: {:08x})",
+                computed, round_trip
+            ),
+            Self::Encrypted { scheme } => {
+                write!(f, "encrypted with {}; its data was not checked", scheme)
+            },
+        }
+    }
+}
+
+/// An issue together with the entry it was found in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintFinding {
+    /// Entry name
+    pub name: String,
+    /// What is wrong with it
+    pub issue: LintIssue,
+}
+
+/// Method name for entries that need more than Stored or Deflate to read
+fn legacy_gap(method: CompressionMethod) -> Option<&'static str> {
+    match method {
+        CompressionMethod::STORE | CompressionMethod::DEFLATE => None,
+        CompressionMethod::DEFLATE64 => Some("Deflate64 (64 KiB distances)"),
+        CompressionMethod::BZIP2 => Some("bzip2"),
+        CompressionMethod::LZMA => Some("LZMA"),
+        CompressionMethod::ZSTD => Some("Zstandard"),
+        CompressionMethod::XZ => Some("XZ"),
+        // Reported as LintIssue::Encrypted, with its scheme
+        CompressionMethod::AES => None,
+        _ => Some("a non-standard compression method"),
+    }
+}
+
+/// Check every entry's data and portability, returning what was found
+///
+/// Encrypted entries are only checked for portability and reported with their
+/// scheme. Entries excluded by the patterns in `args` are skipped.
+///
+/// # Errors
+///
+/// Returns an error if the archive's entry table cannot be read, a charset is
+/// unknown, or an extraction limit is crossed
+pub fn lint_entries<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    args: &Args,
+) -> Result<Vec<LintFinding>> {
+    let limits = ExtractionLimits::from_args(args);
+    limits.check_archive(archive)?;
+    let budget = OutputBudget::new(limits);
+    let matcher = args.matcher();
+    let names = NameDecoder::from_args(args)?;
+    check_names(archive, &names)?;
+    let mut buffer = vec![0u8; 256 * 1024];
+    let mut findings = Vec::new();
+
+    for i in 0..archive.len() {
+        let (name, encrypted) = {
+            let file = archive.by_index_raw(i)?;
+            let name = names.decode(&file).into_owned();
+            if !matcher.should_extract(&name) {
+                continue;
+            }
+            if let Some(method) = legacy_gap(file.compression()) {
+                let issue = LintIssue::LegacyIncompatible { method };
+                findings.push(LintFinding { name: name.clone(), issue });
+            }
+            (name, entry_scheme(&file))
+        };
+        if encrypted.is_encrypted() {
+            findings.push(LintFinding { name, issue: LintIssue::Encrypted { scheme: encrypted } });
+            continue;
+        }
+
+        let legacy = decodes(&archive.by_index_raw(i)?);
+        let mut file = if legacy {
+            archive.by_index_raw(i)?
+        } else {
+            archive.by_index(i)?
+        };
+        let stored = file.crc32();
+        let compressed_size = file.compressed_size();
+        let mut legacy_entry = None;
+        let data: &mut dyn Read = if legacy {
+            legacy_entry.insert(LegacyEntry::new(&mut file))
+        } else {
+            &mut file
+        };
+        let mut reader = budget.guard(data, &name, compressed_size);
+        match round_trip(&mut reader, &mut buffer) {
+            Ok((computed, _)) if computed != stored => {
+                let issue = LintIssue::CrcMismatch { stored, computed };
+                findings.push(LintFinding { name, issue });
+            },
+            Ok((computed, round_trip)) if computed != round_trip => {
+                let issue = LintIssue::RoundTripMismatch { computed, round_trip };
+                findings.push(LintFinding { name, issue });
+            },
+            Ok(_) => {},
+            // A crossed limit ends the whole check rather than one entry
+            Err(e) if e.get_ref().is_some_and(|inner| inner.is::<LimitExceeded>()) => {
+                return Err(e.into());
+            },
+            Err(e) => {
+                findings.push(LintFinding {
+                    name,
+                    issue: LintIssue::Unreadable { error: e.to_string() },
+                });
+            },
+        }
+    }
+
+    Ok(findings)
+}
+
+/// Read `reader` to the end, returning the CRC32 of its data and of the same data
+/// after deflating and inflating it again
+fn round_trip(reader: &mut impl Read, buffer: &mut [u8]) -> io::Result<(u32, u32)> {
+    let mut hasher = crc32fast::Hasher::new();
+    let sink = DeflateDecoder::new(CrcWriter::new(io::sink()));
+    let mut encoder = DeflateEncoder::new(sink, Compression::fast());
+    loop {
+        let n = reader.read(buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+        encoder.write_all(&buffer[..n])?;
+    }
+    let inflated = encoder.finish()?.finish()?;
+    Ok((hasher.finalize(), inflated.crc().sum()))
+}
+
+/// Run the `--lint` check and print its findings Info-ZIP style
+///
+/// # Errors
+///
+/// Returns [`UnzipError::LintFailed`] if any entry's data is bad, after checking
+/// all of them, plus the errors of [`lint_entries`]
+pub fn lint_archive<R: Read + Seek>(archive: &mut ZipArchive<R>, args: &Args) -> Result<()> {
+    let findings = lint_entries(archive, args)?;
+    let errors = findings.iter().filter(|finding| finding.issue.is_error()).count();
+    let warnings = findings.len() - errors;
+
+    if args.quiet < 2 {
+        for finding in &findings {
+            let level = if finding.issue.is_error() {
+                "error"
+            } else {
+                "warning"
+            };
+            eprintln!("{}: {} - {}", level, finding.name, finding.issue);
+        }
+        let mut stdout = io::stdout().lock();
+        if findings.is_empty() {
+            writeln!(stdout, "No problems detected in {}.", args.zipfile.display())?;
+        } else {
+            writeln!(
+                stdout,
+                "{} error(s) and {} warning(s) detected in {}.",
+                errors,
+                warnings,
+                args.zipfile.display()
+            )?;
+        }
+    }
+
+    if errors > 0 {
+        return Err(UnzipError::LintFailed { errors });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::io::Cursor;
+    use zip::ZipWriter;
+    use zip::write::SimpleFileOptions;
+
+    fn create_zip(files: &[(&str, CompressionMethod, &[u8])]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        {
+            let mut zip = ZipWriter::new(Cursor::new(&mut buf));
+            for (name, method, content) in files {
+                zip.start_file(*name, SimpleFileOptions::default().compression_method(*method))
+                    .unwrap();
+                zip.write_all(content).unwrap();
+            }
+            zip.finish().unwrap();
+        }
+        buf
+    }
+
+    fn args() -> Args {
+        Args::parse_from(["unzip", "--lint", "-qq", "test.zip"])
+    }
+
+    #[test]
+    fn test_lint_clean_and_legacy_methods() {
+        let text = b"hello hello hello hello".repeat(100);
+        let zip_data = create_zip(&[
+            ("plain.txt", CompressionMethod::Stored, &text),
+            ("deflated.txt", CompressionMethod::Deflated, &text),
+            ("packed.txt", CompressionMethod::Bzip2, &text),
+        ]);
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+
+        let findings = lint_entries(&mut archive, &args()).unwrap();
+        assert_eq!(
+            findings,
+            [LintFinding {
+                name: "packed.txt".to_string(),
+                issue: LintIssue::LegacyIncompatible { method: "bzip2" },
+            }]
+        );
+        assert!(lint_archive(&mut archive, &args()).is_ok());
+    }
+
+    #[test]
+    fn test_lint_reports_encryption_scheme() {
+        use crate::encryption::{AesStrength, AesVendor};
+        use zip::AesMode;
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().with_aes_encryption(AesMode::Aes256, "pw");
+        zip.start_file("secret.txt", options).unwrap();
+        zip.write_all(b"secret").unwrap();
+        let mut archive = zip.finish_into_readable().unwrap();
+
+        let findings = lint_entries(&mut archive, &args()).unwrap();
+        let scheme =
+            EncryptionScheme::Aes { strength: AesStrength::Aes256, vendor: AesVendor::Ae2 };
+        assert_eq!(findings.len(), 1, "{:?}", findings);
+        assert_eq!(findings[0].issue, LintIssue::Encrypted { scheme });
+        assert!(lint_archive(&mut archive, &args()).is_ok());
+    }
+
+    #[test]
+    fn test_lint_reports_crc_mismatch() {
+        let mut zip_data = create_zip(&[("a.txt", CompressionMethod::Stored, b"hello world")]);
+        let pos = zip_data.windows(11).position(|w| w == b"hello world").unwrap();
+        zip_data[pos] = b'j';
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+
+        let findings = lint_entries(&mut archive, &args()).unwrap();
+        // The zip reader verifies the CRC itself and fails the read
+        assert!(findings[0].issue.is_error(), "{:?}", findings);
+        assert!(matches!(
+            lint_archive(&mut archive, &args()),
+            Err(UnzipError::LintFailed { errors: 1 })
+        ));
+    }
+
+    #[test]
+    fn test_round_trip_crc() {
+        let data = b"abcabcabc".repeat(1000);
+        let (computed, round_trip) = round_trip(&mut &data[..], &mut [0u8; 64]).unwrap();
+        assert_eq!(computed, crc32fast::hash(&data));
+        assert_eq!(computed, round_trip);
+    }
+}
@@ -5,61 +5,139 @@
 //! - `madvise(MADV_WILLNEED)` - Pre-fault pages for faster access
 //! - `fallocate()` - Pre-allocate disk space to avoid fragmentation
 //! - `fadvise(POSIX_FADV_SEQUENTIAL)` - Hint for file access patterns
+//! - `fadvise(POSIX_FADV_DONTNEED)` - Drop written files and the archive from the
+//!   page cache, as [`CachePolicy`] (`--cache`) decides
+//!
+//! The hints are best effort and their failures are ignored during extraction,
+//! but each returns its outcome and [`preallocations`] counts what
+//! [`preallocate_file`] did, so `--verbose-io` can report what was actually
+//! applied. [`probe_target`] checks which of them a destination filesystem
+//! honors.
 
+use std::fmt;
 use std::fs::File;
 use std::num::NonZeroU64;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Files [`preallocate_file`] reserved space for in this run
+static PREALLOCATED: AtomicU64 = AtomicU64::new(0);
+
+/// Files [`preallocate_file`] could not reserve space for in this run
+static NOT_PREALLOCATED: AtomicU64 = AtomicU64::new(0);
 
-/// Apply madvise hints to memory-mapped region for sequential reading
+/// Why the first of those failed
+static PREALLOCATE_ERROR: OnceLock<String> = OnceLock::new();
+
+/// Apply madvise hints to memory-mapped region for sequential reading,
+/// returning whether the kernel accepted them
 #[cfg(target_os = "linux")]
-pub fn madvise_sequential(addr: *const u8, len: usize) {
+pub fn madvise_sequential(addr: *const u8, len: usize) -> Probe {
     use rustix::mm::{Advice, madvise};
 
     // SAFETY: addr and len come from a valid mmap region
     unsafe {
         let ptr = addr as *mut std::ffi::c_void;
         // Sequential access pattern - kernel can read-ahead aggressively
-        let _ = madvise(ptr, len, Advice::Sequential);
+        let sequential = madvise(ptr, len, Advice::Sequential);
         // Tell kernel we'll need this data soon
-        let _ = madvise(ptr, len, Advice::WillNeed);
+        let will_need = madvise(ptr, len, Advice::WillNeed);
+        to_probe(sequential.and(will_need))
     }
 }
 
 #[cfg(not(target_os = "linux"))]
-pub fn madvise_sequential(_addr: *const u8, _len: usize) {
-    // No-op on non-Linux platforms
+pub fn madvise_sequential(_addr: *const u8, _len: usize) -> Probe {
+    Probe::Unavailable
 }
 
 /// Pre-allocate disk space for a file to avoid fragmentation
+///
+/// The outcome is counted for [`preallocations`].
+///
+/// # Errors
+///
+/// Returns the error of `fallocate()`, or [`std::io::ErrorKind::Unsupported`]
+/// elsewhere than Linux
 #[cfg(target_os = "linux")]
 pub fn preallocate_file(file: &File, size: u64) -> std::io::Result<()> {
     use rustix::fs::{FallocateFlags, fallocate};
 
-    if size > 0 {
-        // Pre-allocate space without zeroing (faster)
-        let _ = fallocate(file, FallocateFlags::empty(), 0, size);
+    if size == 0 {
+        return Ok(());
+    }
+    // Pre-allocate space without zeroing (faster)
+    match fallocate(file, FallocateFlags::empty(), 0, size) {
+        Ok(()) => {
+            PREALLOCATED.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        },
+        Err(err) => {
+            NOT_PREALLOCATED.fetch_add(1, Ordering::Relaxed);
+            let _ = PREALLOCATE_ERROR.set(err.to_string());
+            Err(err.into())
+        },
     }
-    Ok(())
 }
 
 #[cfg(not(target_os = "linux"))]
 pub fn preallocate_file(_file: &File, _size: u64) -> std::io::Result<()> {
-    Ok(())
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+/// What [`preallocate_file`] did so far in this run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Preallocations {
+    /// Files space was reserved for
+    pub files: u64,
+    /// Files `fallocate()` failed for
+    pub failed: u64,
+    /// Why it failed the first time
+    pub error: Option<String>,
+}
+
+impl fmt::Display for Preallocations {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !cfg!(target_os = "linux") {
+            return write!(f, "n/a");
+        }
+        write!(f, "{} files", self.files)?;
+        if self.failed > 0 {
+            write!(f, ", failed for {}", self.failed)?;
+        }
+        if let Some(error) = &self.error {
+            write!(f, " ({})", error)?;
+        }
+        Ok(())
+    }
 }
 
-/// Advise kernel about file access pattern
+/// Count what [`preallocate_file`] did so far in this run
+pub fn preallocations() -> Preallocations {
+    Preallocations {
+        files: PREALLOCATED.load(Ordering::Relaxed),
+        failed: NOT_PREALLOCATED.load(Ordering::Relaxed),
+        error: PREALLOCATE_ERROR.get().cloned(),
+    }
+}
+
+/// Advise kernel about file access pattern, returning whether it accepted the
+/// hints
 #[cfg(target_os = "linux")]
-pub fn fadvise_sequential(file: &File, len: u64) {
+pub fn fadvise_sequential(file: &File, len: u64) -> Probe {
     use rustix::fs::{Advice, fadvise};
 
     // Tell kernel we'll read sequentially
-    let _ = fadvise(file, 0, NonZeroU64::new(len), Advice::Sequential);
+    let sequential = fadvise(file, 0, NonZeroU64::new(len), Advice::Sequential);
     // And that we'll need the data soon
-    let _ = fadvise(file, 0, NonZeroU64::new(len), Advice::WillNeed);
+    let will_need = fadvise(file, 0, NonZeroU64::new(len), Advice::WillNeed);
+    to_probe(sequential.and(will_need))
 }
 
 #[cfg(not(target_os = "linux"))]
-pub fn fadvise_sequential(_file: &File, _len: u64) {
-    // No-op on non-Linux platforms
+pub fn fadvise_sequential(_file: &File, _len: u64) -> Probe {
+    Probe::Unavailable
 }
 
 /// Advise kernel we're done with file data (can be evicted from cache)
@@ -75,6 +153,74 @@ pub fn fadvise_dontneed(_file: &File, _offset: u64, _len: u64) {
     // No-op on non-Linux platforms
 }
 
+/// Fraction of physical memory beyond which `--cache=auto` drops data from the
+/// page cache: a run that large would evict most of what else is cached anyway
+const AUTO_DROP_FRACTION: u64 = 4;
+
+/// What to do with the page cache of extracted files and the archive (`--cache`)
+///
+/// Dropping suits one-shot ingest of large archives, which would otherwise push
+/// everything else out of the cache. Keeping suits development machines, where
+/// extracted files are opened right away and repeated runs read the same archive.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CachePolicy {
+    /// Leave extracted files and the archive cached
+    Keep,
+    /// Drop each extracted file once written, and the archive when done
+    Drop,
+    /// Drop only data beyond a quarter of physical memory (default)
+    #[default]
+    Auto,
+}
+
+impl CachePolicy {
+    /// Whether to drop data from the cache once `total` bytes have gone through it:
+    /// everything extracted so far, or the size of the archive
+    pub fn drops(self, total: u64) -> bool {
+        match self {
+            Self::Keep => false,
+            Self::Drop => true,
+            Self::Auto => physical_memory().is_some_and(|ram| total > ram / AUTO_DROP_FRACTION),
+        }
+    }
+}
+
+impl fmt::Display for CachePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Keep => write!(f, "keep"),
+            Self::Drop => write!(f, "drop"),
+            Self::Auto => write!(f, "auto"),
+        }
+    }
+}
+
+/// Parse a `--cache` mode: `keep`, `drop` or `auto`
+///
+/// # Errors
+///
+/// Returns a message suitable for clap if the mode is unknown
+pub fn parse_cache_policy(value: &str) -> Result<CachePolicy, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "keep" => Ok(CachePolicy::Keep),
+        "drop" => Ok(CachePolicy::Drop),
+        "auto" => Ok(CachePolicy::Auto),
+        _ => Err(format!("invalid cache mode: {} (expected keep, drop or auto)", value)),
+    }
+}
+
+/// Physical memory size in bytes
+#[cfg(target_os = "linux")]
+pub fn physical_memory() -> Option<u64> {
+    let info = rustix::system::sysinfo();
+    (info.totalram as u64).checked_mul(u64::from(info.mem_unit))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn physical_memory() -> Option<u64> {
+    None
+}
+
 /// Sync file data to disk efficiently using fdatasync
 #[cfg(target_os = "linux")]
 pub fn sync_file_data(file: &File) {
@@ -88,3 +234,175 @@ pub fn sync_file_data(file: &File) {
 pub fn sync_file_data(_file: &File) {
     // No-op on non-Linux platforms
 }
+
+/// Maximum file name length (bytes) on the filesystem holding `dir`
+#[cfg(target_os = "linux")]
+pub fn name_max(dir: &Path) -> usize {
+    match rustix::fs::statfs(dir) {
+        Ok(stat) if stat.f_namelen > 0 => stat.f_namelen as usize,
+        _ => 255,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn name_max(_dir: &Path) -> usize {
+    255
+}
+
+/// Outcome of probing a single optimization
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Probe {
+    /// The kernel accepted the call
+    Supported,
+    /// The call failed with the given error
+    Failed(String),
+    /// Not available on this platform
+    Unavailable,
+}
+
+impl fmt::Display for Probe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Supported => write!(f, "yes"),
+            Self::Failed(err) => write!(f, "no ({})", err),
+            Self::Unavailable => write!(f, "n/a"),
+        }
+    }
+}
+
+/// Which optimizations a destination directory supports
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptimizationReport {
+    /// Directory that was probed
+    pub target: PathBuf,
+    /// Filesystem name (from the `statfs` magic number)
+    pub filesystem: String,
+    /// `fallocate()` pre-allocation
+    pub fallocate: Probe,
+    /// `posix_fadvise()` access hints
+    pub fadvise: Probe,
+    /// `O_TMPFILE` anonymous temporary files
+    pub o_tmpfile: Probe,
+}
+
+impl fmt::Display for OptimizationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "I/O optimizations for {} ({}):", self.target.display(), self.filesystem)?;
+        writeln!(f, "  fallocate: {}", self.fallocate)?;
+        writeln!(f, "  fadvise:   {}", self.fadvise)?;
+        write!(f, "  O_TMPFILE: {}", self.o_tmpfile)
+    }
+}
+
+/// Name a filesystem from its `statfs` magic number
+#[cfg(target_os = "linux")]
+fn filesystem_name(magic: u64) -> String {
+    let name = match magic {
+        0xEF53 => "ext2/3/4",
+        0x5846_5342 => "xfs",
+        0x9123_683E => "btrfs",
+        0x2FC1_2FC1 => "zfs",
+        0x0102_1994 => "tmpfs",
+        0x794C_7630 => "overlayfs",
+        0xF15F => "ecryptfs",
+        0x6969 => "nfs",
+        0xFF53_4D42 => "cifs",
+        0xFE53_4D42 => "smb2",
+        0x6573_5546 => "fuse",
+        0x4D44 => "vfat",
+        0x5346_544E => "ntfs",
+        0xF2F5_2010 => "f2fs",
+        _ => return format!("unknown, magic 0x{:x}", magic),
+    };
+    name.to_string()
+}
+
+/// The outcome of a system call
+#[cfg(target_os = "linux")]
+fn to_probe(result: rustix::io::Result<()>) -> Probe {
+    match result {
+        Ok(()) => Probe::Supported,
+        Err(err) => Probe::Failed(err.to_string()),
+    }
+}
+
+/// Probe which optimizations the filesystem holding `dir` supports
+///
+/// Uses an `O_TMPFILE` file when possible and otherwise a short-lived hidden file
+/// in `dir`, so nothing is left behind.
+#[cfg(target_os = "linux")]
+pub fn probe_target(dir: &Path) -> OptimizationReport {
+    use rustix::fs::{Advice, FallocateFlags, Mode, OFlags, fadvise, fallocate, open, statfs};
+
+    let filesystem = match statfs(dir) {
+        // f_type is a signed word on some targets; the magic numbers fit in 32 bits
+        Ok(stat) => filesystem_name(stat.f_type as u64 & 0xFFFF_FFFF),
+        Err(err) => format!("statfs failed: {}", err),
+    };
+
+    let tmpfile = open(dir, OFlags::RDWR | OFlags::TMPFILE | OFlags::CLOEXEC, Mode::from(0o600));
+    let o_tmpfile = to_probe(tmpfile.as_ref().map(|_| ()).map_err(|err| *err));
+
+    let probe_path = dir.join(format!(".unzip-probe-{}", std::process::id()));
+    let file = match tmpfile {
+        Ok(fd) => Ok(File::from(fd)),
+        Err(_) => File::options().read(true).write(true).create_new(true).open(&probe_path),
+    };
+
+    let (fallocate, fadvise) = match file {
+        Ok(file) => {
+            let fallocate = to_probe(fallocate(&file, FallocateFlags::empty(), 0, 4096));
+            let fadvise = to_probe(fadvise(&file, 0, None, Advice::Sequential));
+            // Only present when O_TMPFILE was unavailable
+            let _ = std::fs::remove_file(&probe_path);
+            (fallocate, fadvise)
+        },
+        Err(err) => {
+            let failed = Probe::Failed(format!("cannot create probe file: {}", err));
+            (failed.clone(), failed)
+        },
+    };
+
+    OptimizationReport { target: dir.to_path_buf(), filesystem, fallocate, fadvise, o_tmpfile }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn probe_target(dir: &Path) -> OptimizationReport {
+    OptimizationReport {
+        target: dir.to_path_buf(),
+        filesystem: "unknown".to_string(),
+        fallocate: Probe::Unavailable,
+        fadvise: Probe::Unavailable,
+        o_tmpfile: Probe::Unavailable,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_target_leaves_no_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let report = probe_target(temp_dir.path());
+
+        assert_eq!(report.target, temp_dir.path());
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+        #[cfg(target_os = "linux")]
+        assert_ne!(report.fadvise, Probe::Unavailable);
+        assert!(report.to_string().contains("fallocate:"));
+    }
+
+    #[test]
+    fn test_cache_policy() {
+        assert_eq!(parse_cache_policy("keep"), Ok(CachePolicy::Keep));
+        assert_eq!(parse_cache_policy("drop"), Ok(CachePolicy::Drop));
+        assert_eq!(parse_cache_policy("auto"), Ok(CachePolicy::Auto));
+        assert!(parse_cache_policy("never").is_err());
+
+        assert!(!CachePolicy::Keep.drops(u64::MAX));
+        assert!(CachePolicy::Drop.drops(0));
+        assert!(!CachePolicy::Auto.drops(0));
+        assert_eq!(CachePolicy::Auto.to_string(), "auto");
+    }
+}
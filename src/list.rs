@@ -14,23 +14,88 @@
 //! - CRC32 checksums
 //! - File attributes and permissions
 //!
+//! Both formats follow Info-ZIP's layout by default, after an `Archive:` line, so
+//! scripts that parse `unzip -l` keep working: byte counts, `MM-DD-YYYY HH:MM`
+//! dates, the compression method in `-v`, and the `-------` totals block.
+//! `--format pretty` lists with human-readable sizes and ISO dates instead, and
+//! with `-v` names the encryption scheme after each encrypted entry
+//! (`secret.txt  [AES-256 (AE-2)]`, see [`crate::encryption`]):
+//!
+//! ```text
+//!       Size             Modified  Name
+//! ----------  -------------------  ----------------------------------------
+//!       900B  2024-03-15 12:34:56  docs/readme.txt
+//! ----------  -------------------  ----------------------------------------
+//!       900B                       1 files
+//! ```
+//!
+//! Member patterns and `-x` select the entries listed, and the totals cover
+//! only those, as with Info-ZIP's `unzip -l archive.zip '*.txt'`. So do the size
+//! and date bounds of [`crate::list_filter`], which also sorts listings.
+//!
+//! `--totals-only` prints nothing but those totals, on one line that stays the
+//! same across `-v` and `--format`, for dashboards that only need the sizes:
+//!
+//! ```text
+//! $ unzip --totals-only data.zip
+//! files=3 size=10240 compressed=4096 ratio=60%
+//! $ unzip --totals-only=json data.zip
+//! {"archive":"data.zip","files":3,"size":10240,"compressed":4096,"ratio":60}
+//! ```
+//!
+//! The ratio is the space saved in percent, rounded as Info-ZIP's `-v` does.
+//!
+//! `--tree` draws the entries as a tree with per-directory sizes instead (see
+//! [`crate::tree`]).
+//!
+//! For other programs, `--format csv` writes a header row and one row per entry,
+//! with the same columns for `-l` and `-v` and no totals, and `--print0` writes
+//! only the names, each ended by a NUL byte, for `xargs -0`:
+//!
+//! ```text
+//! name,size,compressed,ratio,modified,crc32,method,encryption
+//! "notes, 2024.txt",900,512,43,2024-03-15 12:34:56,3610a686,Defl:N,
+//! secret.txt,1024,1060,0,2024-03-15 12:35:02,00000000,Defl:N,AES-256 (AE-2)
+//! ```
+//!
+//! `--format find` and `--format ls-lR` list the entries as `find . -ls` and
+//! `ls -lR` would list the unpacked archive, for tools built around those (see
+//! [`crate::unix_listing`]).
+//!
 //! # Examples
 //!
 //! ```no_run
 //! use std::fs::File;
 //! use zip::ZipArchive;
-//! use unzip::list_contents;
+//! use unzip::{Args, list_contents};
+//! use clap::Parser;
 //!
 //! let file = File::open("archive.zip")?;
 //! let mut archive = ZipArchive::new(file)?;
-//! list_contents(&mut archive, false)?;  // Short format
+//! let args = Args::parse();
+//! list_contents(&mut archive, &args)?;  // Short format unless -v was given
 //! # Ok::<(), Box<dyn std::error::Error>>(())
 //! ```
 
-use anyhow::Result;
-use std::io::{Read, Seek, Write};
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{self, Read, Seek, Write};
+use std::path::Path;
+use std::time::SystemTime;
 use zip::ZipArchive;
 
+use crate::args::Args;
+use crate::central::CentralDirectory;
+use crate::charset::NameDecoder;
+use crate::encryption::{EncryptionScheme, entry_scheme};
+use crate::error::{Result, UnzipError};
+use crate::invalid_names::{InvalidEntryNames, InvalidNames, check_names, escape_name};
+use crate::list_filter::{ListDate, ListFilter, SortKey};
+use crate::permissions::{display_mode, entry_display_mode};
+use crate::tree::Tree;
+use crate::unix_listing::{LsTree, write_find_line};
+use crate::utils::PatternMatcher;
+
 struct DateTimeCache {
     last: Option<zip::DateTime>,
     buf: [u8; 19],
@@ -186,16 +251,19 @@ pub fn display_comment<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<()
 /// List the contents of a ZIP archive in short or verbose format.
 ///
 /// Displays information about all files in the archive without extracting them.
-/// The output format depends on the verbose flag:
+/// The output format depends on the verbose flag (`-v`):
 ///
-/// - **Short format** (`verbose = false`): Shows file sizes, modification dates, and names
-/// - **Verbose format** (`verbose = true`): Shows uncompressed size, compressed size,
+/// - **Short format** (`args.verbose = false`): Shows file sizes, modification dates, and names
+/// - **Verbose format** (`args.verbose = true`): Shows uncompressed size, compressed size,
 ///   compression ratio, date/time, CRC32 checksum, and name
 ///
+/// Entry names are decoded according to the `-O` / `-I` character set options.
+/// A reader that stops early, like `head`, ends the listing without an error.
+///
 /// # Arguments
 ///
 /// * `archive` - The ZIP archive to list
-/// * `verbose` - If true, use verbose format with detailed file information
+/// * `args` - Command-line arguments selecting the format and name decoding
 ///
 /// # Errors
 ///
@@ -208,67 +276,419 @@ pub fn display_comment<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<()
 /// ```no_run
 /// use std::fs::File;
 /// use zip::ZipArchive;
-/// use unzip::list_contents;
+/// use std::path::PathBuf;
+/// use unzip::{Args, list_contents};
 ///
 /// let file = File::open("archive.zip")?;
 /// let mut archive = ZipArchive::new(file)?;
 ///
 /// // Short format
-/// list_contents(&mut archive, false)?;
+/// let mut args = Args { zipfile: PathBuf::from("archive.zip"), ..Default::default() };
+/// list_contents(&mut archive, &args)?;
 ///
 /// // Verbose format
-/// list_contents(&mut archive, true)?;
+/// args.verbose = true;
+/// list_contents(&mut archive, &args)?;
 /// # Ok::<(), Box<dyn std::error::Error>>(())
 /// ```
-pub fn list_contents<R: Read + Seek>(archive: &mut ZipArchive<R>, verbose: bool) -> Result<()> {
+pub fn list_contents<R: Read + Seek>(archive: &mut ZipArchive<R>, args: &Args) -> Result<()> {
+    let names = NameDecoder::from_args(args)?;
+    check_names(archive, &names)?;
     let stdout = std::io::stdout();
-    let mut out = std::io::BufWriter::new(stdout.lock());
-    let mut datetime_cache = DateTimeCache::new();
-    let mut size_buf = [0u8; 32];
-    let mut num_buf = [0u8; 32];
-    let mut crc_buf = [0u8; 8];
-    if verbose {
-        writeln!(
-            &mut out,
-            "{:>8}  {:>8}  {:>5}  {:>19}  {:>8}  Name",
-            "Length", "Size", "Ratio", "Date & Time", "CRC-32"
-        )?;
-        writeln!(&mut out, "{}", "-".repeat(80))?;
-    } else {
-        writeln!(&mut out, "{:>10}  {:>19}  Name", "Size", "Modified")?;
-        writeln!(&mut out, "{:->10}  {:->19}  {:->40}", "", "", "")?;
+    let mut lister = Lister::new(std::io::BufWriter::new(stdout.lock()), args);
+    stopped_reading(list_entries(archive, &names, &mut lister))
+}
+
+fn list_entries<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    names: &NameDecoder,
+    lister: &mut Lister<'_, impl Write>,
+) -> Result<()> {
+    lister.header()?;
+    for i in 0..archive.len() {
+        let file = archive.by_index_raw(i)?;
+        let name = names.decode(&file);
+        if !lister.selection.selects(&name) {
+            continue;
+        }
+        lister.entry(&Listed {
+            name,
+            size: file.size(),
+            compressed: file.compressed_size(),
+            modified: file.last_modified(),
+            crc32: file.crc32(),
+            method: file.compression(),
+            mode: display_mode(&file),
+            encryption: entry_scheme(&file),
+        })?;
     }
+    Ok(lister.footer()?)
+}
 
-    let mut total_size: u64 = 0;
-    let mut total_compressed: u64 = 0;
-    let mut file_count = 0;
+/// List an archive as [`list_contents`] does, printing each entry as soon as its
+/// central directory record has been read
+///
+/// Nothing waits for the whole central directory: lines leave through a buffer
+/// that is written out whenever it fills, so `unzip -l huge.zip | head` returns
+/// at once, and only the totals wait for the last record.
+///
+/// # Errors
+///
+/// Returns an error if a central directory record cannot be read, or its name
+/// does not decode with `--invalid-names=reject`, after listing the entries
+/// before it
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use unzip::Args;
+/// use unzip::central::CentralDirectory;
+/// use unzip::list::list_streaming;
+///
+/// let directory = CentralDirectory::open(File::open("huge.zip")?)?;
+/// list_streaming(directory, &Args::default())?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn list_streaming<R: Read + Seek>(directory: CentralDirectory<R>, args: &Args) -> Result<()> {
+    let names = NameDecoder::from_args(args)?;
+    let stdout = std::io::stdout();
+    let mut lister = Lister::new(std::io::BufWriter::new(stdout.lock()), args);
+    stopped_reading(list_records(directory, &names, &mut lister))
+}
+
+fn list_records<R: Read + Seek>(
+    directory: CentralDirectory<R>,
+    names: &NameDecoder,
+    lister: &mut Lister<'_, impl Write>,
+) -> Result<()> {
+    lister.header()?;
+    for record in directory {
+        let record = record?;
+        if names.invalid_names() == InvalidNames::Reject
+            && names.is_invalid(&record.name, record.is_utf8, record.host())
+        {
+            return Err(InvalidEntryNames { names: vec![escape_name(&record.name)] }.into());
+        }
+        let name = names.decode_raw(&record.name, record.is_utf8, record.host());
+        if !lister.selection.selects(&name) {
+            continue;
+        }
+        lister.entry(&Listed {
+            name,
+            size: record.size,
+            compressed: record.compressed_size,
+            modified: record.modified,
+            crc32: record.crc32,
+            method: record.compression(),
+            mode: entry_display_mode(
+                record.host(),
+                record.external_attributes,
+                record.name.ends_with(b"/"),
+            ),
+            encryption: record.encryption,
+        })?;
+    }
+    Ok(lister.footer()?)
+}
 
-    // Pre-allocate line buffer to avoid allocations per file
-    let mut line_buf = Vec::with_capacity(512);
+/// Treat a reader that stopped reading the listing, like `head`, as its end
+pub(crate) fn stopped_reading(result: Result<()>) -> Result<()> {
+    match result {
+        Err(UnzipError::Io(error)) if error.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    }
+}
 
-    for i in 0..archive.len() {
-        let file = archive.by_index_raw(i)?;
-        let size = file.size();
-        let compressed = file.compressed_size();
-        total_size += size;
-        total_compressed += compressed;
-        file_count += 1;
+/// Layout of `-l` and `-v` listings (`--format`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ListFormat {
+    /// Info-ZIP's columns and totals, byte for byte (default)
+    #[default]
+    InfoZip,
+    /// Human-readable sizes and ISO dates
+    Pretty,
+    /// Comma-separated values with a header row and no totals, quoted as RFC
+    /// 4180 asks
+    Csv,
+    /// The lines of `find . -ls` in the unpacked archive
+    Find,
+    /// The listing of `ls -lR` in the unpacked archive, directory by directory
+    LsLR,
+}
+
+impl fmt::Display for ListFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InfoZip => write!(f, "infozip"),
+            Self::Pretty => write!(f, "pretty"),
+            Self::Csv => write!(f, "csv"),
+            Self::Find => write!(f, "find"),
+            Self::LsLR => write!(f, "ls-lR"),
+        }
+    }
+}
 
-        let datetime_str = datetime_cache.as_str(file.last_modified());
-        let name = file.name();
+/// Parse a `--format` listing layout: `infozip`, `pretty`, `csv`, `find` or
+/// `ls-lR`
+///
+/// # Errors
+///
+/// Returns a message suitable for clap if the layout is unknown
+pub fn parse_list_format(value: &str) -> std::result::Result<ListFormat, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "infozip" => Ok(ListFormat::InfoZip),
+        "pretty" => Ok(ListFormat::Pretty),
+        "csv" => Ok(ListFormat::Csv),
+        "find" => Ok(ListFormat::Find),
+        "ls-lr" => Ok(ListFormat::LsLR),
+        _ => Err(format!(
+            "invalid listing format: {} (expected infozip, pretty, csv, find or ls-lR)",
+            value
+        )),
+    }
+}
+
+/// How `--totals-only` prints the totals of a listing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TotalsFormat {
+    /// `files=N size=N compressed=N ratio=N%`
+    #[default]
+    Text,
+    /// A JSON object with the archive path and the same numbers
+    Json,
+}
+
+impl fmt::Display for TotalsFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Text => write!(f, "text"),
+            Self::Json => write!(f, "json"),
+        }
+    }
+}
+
+/// Parse a `--totals-only` format: `text` or `json`
+///
+/// # Errors
+///
+/// Returns a message suitable for clap if the format is unknown
+pub fn parse_totals_format(value: &str) -> std::result::Result<TotalsFormat, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "text" => Ok(TotalsFormat::Text),
+        "json" => Ok(TotalsFormat::Json),
+        _ => Err(format!("invalid totals format: {} (expected text or json)", value)),
+    }
+}
+
+/// What a listing line shows of one entry
+struct Listed<'a> {
+    name: Cow<'a, str>,
+    size: u64,
+    compressed: u64,
+    modified: Option<zip::DateTime>,
+    crc32: u32,
+    method: zip::CompressionMethod,
+    /// Unix mode with the file type, defaults filled in
+    mode: u32,
+    encryption: EncryptionScheme,
+}
+
+/// Writes a listing one entry at a time, keeping the totals for its last line
+struct Lister<'a, W: Write> {
+    out: W,
+    /// The entries listed: those the member patterns and `-x` select
+    selection: PatternMatcher<'a>,
+    verbose: bool,
+    format: ListFormat,
+    /// Print only the names, each ended by a NUL byte (`--print0`)
+    print0: bool,
+    /// Print only the totals (`--totals-only`), naming `archive` in JSON
+    totals_only: Option<TotalsFormat>,
+    archive: &'a Path,
+    /// Entries outside these bounds are left out (`--min-size`, `--newer`, ...)
+    filter: ListFilter,
+    /// Order to list the entries in at the end (`--sort`, `--reverse`), which
+    /// holds them back until then
+    sort: Option<(SortKey, bool)>,
+    held: Vec<Listed<'static>>,
+    /// Entries gathered for `--tree` or `--du`, drawn at the end instead of
+    /// listed
+    tree: Option<Tree>,
+    /// Directory levels to summarize, for `--du`
+    du: Option<usize>,
+    /// Entries gathered for `--format ls-lR`, listed at the end
+    ls_tree: Option<LsTree>,
+    /// Reference for the `ls` dates of `--format find` and `ls-lR`
+    now: SystemTime,
+    total_size: u64,
+    total_compressed: u64,
+    file_count: u64,
+    datetime_cache: DateTimeCache,
+    size_buf: [u8; 32],
+    num_buf: [u8; 32],
+    crc_buf: [u8; 8],
+    // Reused for every line to avoid allocations per file
+    line_buf: Vec<u8>,
+}
+
+impl<'a, W: Write> Lister<'a, W> {
+    fn new(out: W, args: &'a Args) -> Self {
+        Self {
+            out,
+            selection: args.matcher(),
+            verbose: args.verbose,
+            format: args.list_format,
+            print0: args.print0,
+            totals_only: args.totals_only,
+            archive: &args.zipfile,
+            filter: ListFilter::from_args(args),
+            sort: args.sort.map(|key| (key, args.reverse)),
+            held: Vec::new(),
+            tree: (args.tree || args.du).then(Tree::new),
+            du: args.du.then(|| args.depth.unwrap_or(1)),
+            ls_tree: (args.list_format == ListFormat::LsLR
+                && !(args.tree || args.du || args.print0 || args.totals_only.is_some()))
+            .then(LsTree::new),
+            now: SystemTime::now(),
+            total_size: 0,
+            total_compressed: 0,
+            file_count: 0,
+            datetime_cache: DateTimeCache::new(),
+            size_buf: [0u8; 32],
+            num_buf: [0u8; 32],
+            crc_buf: [0u8; 8],
+            line_buf: Vec::with_capacity(512),
+        }
+    }
+
+    fn header(&mut self) -> io::Result<()> {
+        if self.totals_only.is_some() || self.tree.is_some() || self.print0 {
+            return Ok(());
+        }
+        let out = &mut self.out;
+        if matches!(self.format, ListFormat::Find | ListFormat::LsLR) {
+            return Ok(());
+        }
+        if self.format == ListFormat::Csv {
+            return writeln!(out, "name,size,compressed,ratio,modified,crc32,method,encryption");
+        }
+        match (self.format == ListFormat::InfoZip, self.verbose) {
+            (true, true) => {
+                writeln!(out, " Length   Method    Size  Cmpr    Date    Time   CRC-32   Name")?;
+                writeln!(out, "--------  ------  ------- ---- ---------- ----- --------  ----")
+            },
+            (true, false) => {
+                writeln!(out, "  Length      Date    Time    Name")?;
+                writeln!(out, "---------  ---------- -----   ----")
+            },
+            (false, true) => {
+                writeln!(
+                    out,
+                    "{:>8}  {:>8}  {:>5}  {:>19}  {:>8}  Name",
+                    "Length", "Size", "Ratio", "Date & Time", "CRC-32"
+                )?;
+                writeln!(out, "{}", "-".repeat(80))
+            },
+            (false, false) => {
+                writeln!(out, "{:>10}  {:>19}  Name", "Size", "Modified")?;
+                writeln!(out, "{:->10}  {:->19}  {:->40}", "", "", "")
+            },
+        }
+    }
+
+    fn entry(&mut self, entry: &Listed<'_>) -> io::Result<()> {
+        if !self.filter.admits(entry.size, entry.modified) {
+            return Ok(());
+        }
+        if let Some(tree) = &mut self.tree {
+            tree.add(&entry.name, entry.size, entry.compressed, entry.modified);
+            return Ok(());
+        }
+        if let Some(ls_tree) = &mut self.ls_tree {
+            ls_tree.add(&entry.name, entry.mode, entry.size, entry.modified);
+            return Ok(());
+        }
+        if self.sort.is_some() {
+            let name = Cow::Owned(entry.name.to_string());
+            self.held.push(Listed { name, ..*entry });
+            return Ok(());
+        }
+        self.write_entry(entry)
+    }
+
+    fn write_entry(&mut self, entry: &Listed<'_>) -> io::Result<()> {
+        self.total_size += entry.size;
+        self.total_compressed += entry.compressed;
+        self.file_count += 1;
+        if self.totals_only.is_some() {
+            return Ok(());
+        }
+        if self.print0 {
+            self.out.write_all(entry.name.as_bytes())?;
+            return self.out.write_all(b"\0");
+        }
+        match self.format {
+            ListFormat::InfoZip => self.info_zip_entry(entry),
+            ListFormat::Pretty => self.native_entry(entry),
+            ListFormat::Csv => self.csv_entry(entry),
+            ListFormat::Find => write_find_line(
+                &mut self.out,
+                self.file_count,
+                &entry.name,
+                entry.mode,
+                entry.size,
+                entry.modified,
+                self.now,
+            ),
+            // Gathered in the tree before getting here
+            ListFormat::LsLR => Ok(()),
+        }
+    }
+
+    /// Entry row of a `--format csv` listing, the same with and without `-v`
+    fn csv_entry(&mut self, entry: &Listed<'_>) -> io::Result<()> {
+        let modified = match entry.modified {
+            Some(_) => self.datetime_cache.as_str(entry.modified),
+            None => "",
+        };
+        let encryption = match entry.encryption {
+            EncryptionScheme::None => String::new(),
+            scheme => scheme.to_string(),
+        };
+        writeln!(
+            self.out,
+            "{},{},{},{},{},{:08x},{},{}",
+            csv_field(&entry.name),
+            entry.size,
+            entry.compressed,
+            ratio(entry.size, entry.compressed),
+            modified,
+            entry.crc32,
+            info_zip_method(entry.method),
+            encryption
+        )
+    }
+
+    fn native_entry(&mut self, entry: &Listed<'_>) -> io::Result<()> {
+        let (size, compressed) = (entry.size, entry.compressed);
+        let datetime_str = self.datetime_cache.as_str(entry.modified);
+        let line_buf = &mut self.line_buf;
+        let num_buf = &mut self.num_buf;
 
         line_buf.clear();
 
-        if verbose {
+        if self.verbose {
+            // Stored and encrypted entries can grow, which lists as no saving
             let ratio = if size > 0 {
-                100 - (compressed * 100 / size)
+                100u64.saturating_sub(compressed * 100 / size)
             } else {
                 0
             };
 
             // Build complete line in buffer with single write
             // Right-align size (8 chars)
-            let size_len = write_u64(&mut num_buf, size);
+            let size_len = write_u64(num_buf, size);
             for _ in 0..(8_usize.saturating_sub(size_len)) {
                 line_buf.push(b' ');
             }
@@ -276,7 +696,7 @@ pub fn list_contents<R: Read + Seek>(archive: &mut ZipArchive<R>, verbose: bool)
             line_buf.extend_from_slice(b"  ");
 
             // Right-align compressed size (8 chars)
-            let comp_len = write_u64(&mut num_buf, compressed);
+            let comp_len = write_u64(num_buf, compressed);
             for _ in 0..(8_usize.saturating_sub(comp_len)) {
                 line_buf.push(b' ');
             }
@@ -284,7 +704,7 @@ pub fn list_contents<R: Read + Seek>(archive: &mut ZipArchive<R>, verbose: bool)
             line_buf.extend_from_slice(b"  ");
 
             // Right-align ratio (4 chars)
-            let ratio_len = write_u64(&mut num_buf, ratio as u64);
+            let ratio_len = write_u64(num_buf, ratio);
             for _ in 0..(4_usize.saturating_sub(ratio_len)) {
                 line_buf.push(b' ');
             }
@@ -294,17 +714,11 @@ pub fn list_contents<R: Read + Seek>(archive: &mut ZipArchive<R>, verbose: bool)
             line_buf.extend_from_slice(datetime_str.as_bytes());
             line_buf.extend_from_slice(b"  ");
 
-            write_hex_u32(&mut crc_buf, file.crc32());
-            line_buf.extend_from_slice(&crc_buf);
+            write_hex_u32(&mut self.crc_buf, entry.crc32);
+            line_buf.extend_from_slice(&self.crc_buf);
             line_buf.extend_from_slice(b"  ");
-
-            line_buf.extend_from_slice(name.as_bytes());
-            line_buf.push(b'\n');
-
-            // Single write for entire line
-            out.write_all(&line_buf)?;
         } else {
-            let size_str = size_to_str(&mut size_buf, size);
+            let size_str = size_to_str(&mut self.size_buf, size);
 
             // Right-align size (10 chars)
             for _ in 0..(10_usize.saturating_sub(size_str.len())) {
@@ -314,48 +728,219 @@ pub fn list_contents<R: Read + Seek>(archive: &mut ZipArchive<R>, verbose: bool)
             line_buf.extend_from_slice(b"  ");
             line_buf.extend_from_slice(datetime_str.as_bytes());
             line_buf.extend_from_slice(b"  ");
-            line_buf.extend_from_slice(name.as_bytes());
-            line_buf.push(b'\n');
+        }
+        line_buf.extend_from_slice(entry.name.as_bytes());
+        if self.verbose && entry.encryption.is_encrypted() {
+            line_buf.extend_from_slice(format!("  [{}]", entry.encryption).as_bytes());
+        }
+        line_buf.push(b'\n');
+
+        // Single write for entire line
+        self.out.write_all(line_buf)
+    }
 
-            // Single write for entire line
-            out.write_all(&line_buf)?;
+    /// Entry line the way Info-ZIP's `unzip -l` (or `-v`) prints it
+    fn info_zip_entry(&mut self, entry: &Listed<'_>) -> io::Result<()> {
+        let datetime = match entry.modified {
+            Some(dt) => format!(
+                "{:02}-{:02}-{:04} {:02}:{:02}",
+                dt.month(),
+                dt.day(),
+                dt.year(),
+                dt.hour(),
+                dt.minute()
+            ),
+            None => "00-00-1980 00:00".to_string(),
+        };
+        if self.verbose {
+            writeln!(
+                self.out,
+                "{:>8}  {:<6}{:>9} {:>3}% {} {:08x}  {}",
+                entry.size,
+                info_zip_method(entry.method),
+                entry.compressed,
+                ratio(entry.size, entry.compressed),
+                datetime,
+                entry.crc32,
+                entry.name
+            )
+        } else {
+            writeln!(self.out, "{:>9}  {}   {}", entry.size, datetime, entry.name)
         }
     }
 
-    if verbose {
-        writeln!(&mut out, "{}", "-".repeat(80))?;
-        let ratio = if total_size > 0 {
-            100 - (total_compressed * 100 / total_size)
+    fn footer(&mut self) -> io::Result<()> {
+        if let Some(tree) = self.tree.take() {
+            match self.du {
+                Some(depth) => tree.write_du(&mut self.out, depth, self.sort)?,
+                None => {
+                    tree.write(&mut self.out, &self.archive.display().to_string(), self.sort)?
+                },
+            }
+            return self.out.flush();
+        }
+        if let Some(ls_tree) = self.ls_tree.take() {
+            ls_tree.write(&mut self.out, self.now)?;
+            return self.out.flush();
+        }
+        if let Some((key, reverse)) = self.sort {
+            let mut held = std::mem::take(&mut self.held);
+            sort_entries(&mut held, key, reverse);
+            for entry in &held {
+                self.write_entry(entry)?;
+            }
+        }
+        let (total_size, total_compressed) = (self.total_size, self.total_compressed);
+        let tabular = matches!(self.format, ListFormat::Csv | ListFormat::Find);
+        if self.totals_only.is_none() && (self.print0 || tabular) {
+            // Totals would be one more row to a spreadsheet or find's output, or
+            // a name to xargs
+            return self.out.flush();
+        }
+        let file_count = self.file_count;
+        let out = &mut self.out;
+        if let Some(format) = self.totals_only {
+            let ratio = ratio(total_size, total_compressed);
+            match format {
+                TotalsFormat::Text => writeln!(
+                    out,
+                    "files={} size={} compressed={} ratio={}%",
+                    file_count, total_size, total_compressed, ratio
+                )?,
+                TotalsFormat::Json => writeln!(
+                    out,
+                    "{{\"archive\":{},\"files\":{},\"size\":{},\"compressed\":{},\"ratio\":{}}}",
+                    json_string(&self.archive.to_string_lossy()),
+                    file_count,
+                    total_size,
+                    total_compressed,
+                    ratio
+                )?,
+            }
+        } else if self.format == ListFormat::InfoZip {
+            let files = if file_count == 1 { "file" } else { "files" };
+            if self.verbose {
+                writeln!(out, "--------          -------  ---                            -------")?;
+                writeln!(
+                    out,
+                    "{:>8}{:>17} {:>3}%                            {} {}",
+                    total_size,
+                    total_compressed,
+                    ratio(total_size, total_compressed),
+                    file_count,
+                    files
+                )?;
+            } else {
+                writeln!(out, "---------                     -------")?;
+                writeln!(out, "{:>9}                     {} {}", total_size, file_count, files)?;
+            }
+        } else if self.verbose {
+            writeln!(out, "{}", "-".repeat(80))?;
+            let ratio = (total_compressed * 100)
+                .checked_div(total_size)
+                .map_or(0, |percent| 100u64.saturating_sub(percent));
+            writeln!(
+                out,
+                "{:>8}  {:>8}  {:>4}%  {:>19}  {:>8}  {} files",
+                total_size, total_compressed, ratio, "", "", file_count
+            )?;
         } else {
-            0
-        };
-        writeln!(
-            &mut out,
-            "{:>8}  {:>8}  {:>4}%  {:>19}  {:>8}  {} files",
-            total_size, total_compressed, ratio, "", "", file_count
-        )?;
-    } else {
-        writeln!(&mut out, "{:->10}  {:->19}  {:->40}", "", "", "")?;
-        let total_str = size_to_str(&mut size_buf, total_size);
+            writeln!(out, "{:->10}  {:->19}  {:->40}", "", "", "")?;
+            let total_str = size_to_str(&mut self.size_buf, total_size);
 
-        // Build footer line in buffer with single write
-        line_buf.clear();
-        for _ in 0..(10_usize.saturating_sub(total_str.len())) {
-            line_buf.push(b' ');
+            // Build footer line in buffer with single write
+            let line_buf = &mut self.line_buf;
+            line_buf.clear();
+            for _ in 0..(10_usize.saturating_sub(total_str.len())) {
+                line_buf.push(b' ');
+            }
+            line_buf.extend_from_slice(total_str.as_bytes());
+            line_buf.extend_from_slice(b"  ");
+            line_buf.extend_from_slice(b"                   ");
+            line_buf.extend_from_slice(b"  ");
+
+            let count_len = write_u64(&mut self.num_buf, file_count);
+            line_buf.extend_from_slice(&self.num_buf[..count_len]);
+            line_buf.extend_from_slice(b" files\n");
+
+            out.write_all(line_buf)?;
         }
-        line_buf.extend_from_slice(total_str.as_bytes());
-        line_buf.extend_from_slice(b"  ");
-        line_buf.extend_from_slice(b"                   ");
-        line_buf.extend_from_slice(b"  ");
+        // The buffer would otherwise be written out on drop, ignoring errors
+        out.flush()
+    }
+}
 
-        let count_len = write_u64(&mut num_buf, file_count as u64);
-        line_buf.extend_from_slice(&num_buf[..count_len]);
-        line_buf.extend_from_slice(b" files\n");
+/// Sort `entries` by `key`, in descending order if `reverse`; equal entries
+/// keep their archive order either way
+fn sort_entries(entries: &mut [Listed<'_>], key: SortKey, reverse: bool) {
+    let mtime = |entry: &Listed<'_>| entry.modified.map(ListDate::from_datetime);
+    entries.sort_by(|a, b| {
+        let order = match key {
+            SortKey::Name => a.name.cmp(&b.name),
+            SortKey::Size => a.size.cmp(&b.size),
+            SortKey::Ratio => ratio(a.size, a.compressed).cmp(&ratio(b.size, b.compressed)),
+            SortKey::Mtime => mtime(a).cmp(&mtime(b)),
+        };
+        if reverse { order.reverse() } else { order }
+    });
+}
 
-        out.write_all(&line_buf)?;
+/// Space saved by compression in percent, as Info-ZIP rounds it
+fn ratio(size: u64, compressed: u64) -> u64 {
+    if size == 0 || compressed >= size {
+        return 0;
     }
+    ((size - compressed) * 100 + size / 2) / size
+}
 
-    Ok(())
+/// `text` as a CSV field, quoted if it holds a comma, quote or line break
+fn csv_field(text: &str) -> Cow<'_, str> {
+    if text.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", text.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// `text` as a JSON string
+pub(crate) fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if c < ' ' => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// Compression method as Info-ZIP's `unzip -v` names it
+fn info_zip_method(method: zip::CompressionMethod) -> &'static str {
+    match method {
+        zip::CompressionMethod::Stored => "Stored",
+        zip::CompressionMethod::Deflated => "Defl:N",
+        zip::CompressionMethod::Deflate64 => "Def64",
+        zip::CompressionMethod::Bzip2 => "BZip2",
+        zip::CompressionMethod::Lzma => "LZMA",
+        zip::CompressionMethod::Xz => "XZ",
+        zip::CompressionMethod::Zstd => "Zstd",
+        #[allow(deprecated)]
+        zip::CompressionMethod::Unsupported(code) => match code {
+            1 => "Shrunk",
+            2 => "Reduce1",
+            3 => "Reduce2",
+            4 => "Reduce3",
+            5 => "Reduce4",
+            6 => "Implode",
+            98 => "PPMd",
+            _ => "Unk",
+        },
+        _ => "Unk",
+    }
 }
 
 #[cfg(test)]
@@ -365,6 +950,10 @@ mod tests {
     use zip::ZipWriter;
     use zip::write::SimpleFileOptions;
 
+    fn list_args(verbose: bool) -> Args {
+        Args { verbose, ..Default::default() }
+    }
+
     fn create_test_zip(files: &[(&str, &[u8])]) -> Vec<u8> {
         let mut buf = Vec::new();
         {
@@ -415,7 +1004,7 @@ mod tests {
         let mut archive = ZipArchive::new(cursor).unwrap();
 
         // Should not panic and should return Ok
-        let result = list_contents(&mut archive, false);
+        let result = list_contents(&mut archive, &list_args(false));
         assert!(result.is_ok());
     }
 
@@ -428,10 +1017,288 @@ mod tests {
         let mut archive = ZipArchive::new(cursor).unwrap();
 
         // Should not panic and should return Ok
-        let result = list_contents(&mut archive, true);
+        let result = list_contents(&mut archive, &list_args(true));
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_list_info_zip_layout() {
+        let mut buf = Vec::new();
+        {
+            let mut zip = ZipWriter::new(Cursor::new(&mut buf));
+            let time = zip::DateTime::from_date_and_time(2020, 1, 2, 3, 4, 6).unwrap();
+            let options = SimpleFileOptions::default()
+                .compression_method(zip::CompressionMethod::Stored)
+                .last_modified_time(time);
+            zip.start_file("a.txt", options).unwrap();
+            zip.write_all(b"alpha").unwrap();
+            zip.finish().unwrap();
+        }
+        let mut archive = ZipArchive::new(Cursor::new(buf)).unwrap();
+        let names = NameDecoder::default();
+        let mut listing = |verbose| {
+            let args = Args { verbose, ..Default::default() };
+            let mut lister = Lister::new(Vec::new(), &args);
+            list_entries(&mut archive, &names, &mut lister).unwrap();
+            String::from_utf8(lister.out).unwrap()
+        };
+
+        assert_eq!(
+            listing(false),
+            "  Length      Date    Time    Name\n\
+             ---------  ---------- -----   ----\n\
+             \x20       5  01-02-2020 03:04   a.txt\n\
+             ---------                     -------\n\
+             \x20       5                     1 file\n"
+        );
+
+        let out = listing(true);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[2], "       5  Stored        5   0% 01-02-2020 03:04 d0e0396a  a.txt");
+        assert_eq!(lines[4], "       5                5   0%                            1 file");
+        // Names and the file count line up under the last column of dashes
+        let name_column = lines[2].find("a.txt");
+        assert_eq!(lines[1].rfind(' ').map(|space| space + 1), name_column);
+        assert_eq!(lines[4].find("1 file"), name_column);
+    }
+
+    #[test]
+    fn test_list_records_matches_list_entries() {
+        let zip_data =
+            create_test_zip(&[("dir/", &[]), ("dir/a.txt", b"alpha"), ("テスト.txt", b"beta")]);
+        let names = NameDecoder::default();
+        for (verbose, list_format) in [
+            (false, ListFormat::Pretty),
+            (true, ListFormat::Pretty),
+            (true, ListFormat::InfoZip),
+        ] {
+            let args = Args { verbose, list_format, ..Default::default() };
+            let mut archive = ZipArchive::new(Cursor::new(&zip_data)).unwrap();
+            let mut indexed = Lister::new(Vec::new(), &args);
+            list_entries(&mut archive, &names, &mut indexed).unwrap();
+
+            let directory = CentralDirectory::open(Cursor::new(&zip_data)).unwrap();
+            let mut streamed = Lister::new(Vec::new(), &args);
+            list_records(directory, &names, &mut streamed).unwrap();
+            assert_eq!(String::from_utf8(streamed.out), String::from_utf8(indexed.out));
+        }
+    }
+
+    #[test]
+    fn test_list_selected_entries() {
+        let zip_data = create_test_zip(&[("a.txt", b"alpha"), ("b.log", b"beta"), ("c.txt", b"")]);
+        let args = Args {
+            patterns: vec!["*.txt".to_string()],
+            exclude: vec!["c*".to_string()],
+            ..Default::default()
+        };
+        let directory = CentralDirectory::open(Cursor::new(&zip_data)).unwrap();
+        let mut lister = Lister::new(Vec::new(), &args);
+        list_records(directory, &NameDecoder::default(), &mut lister).unwrap();
+        let out = String::from_utf8(lister.out).unwrap();
+        assert!(out.contains("   a.txt\n"));
+        assert!(!out.contains("b.log") && !out.contains("c.txt"));
+        assert!(out.ends_with("       5                     1 file\n"));
+    }
+
+    #[test]
+    fn test_list_sorted_and_filtered() {
+        let zip_data = create_test_zip(&[
+            ("b.txt", b"12345"),
+            ("a.txt", b"123"),
+            ("c.txt", b"1234567"),
+            ("d.txt", b"123"),
+            ("e.txt", b""),
+        ]);
+        let list = |args: &Args| {
+            let directory = CentralDirectory::open(Cursor::new(&zip_data)).unwrap();
+            let mut lister = Lister::new(Vec::new(), args);
+            list_records(directory, &NameDecoder::default(), &mut lister).unwrap();
+            let out = String::from_utf8(lister.out).unwrap();
+            let names: Vec<String> = (out.lines())
+                .filter_map(|line| line.strip_suffix(".txt")?.rsplit(' ').next())
+                .map(str::to_string)
+                .collect();
+            (names, out.lines().last().unwrap().to_string())
+        };
+
+        let args = Args { sort: Some(SortKey::Name), reverse: true, ..Default::default() };
+        assert_eq!(list(&args).0, ["e", "d", "c", "b", "a"]);
+        // Equal sizes keep archive order, also reversed
+        let args = Args { sort: Some(SortKey::Size), reverse: true, ..Default::default() };
+        assert_eq!(list(&args).0, ["c", "b", "a", "d", "e"]);
+
+        let args = Args {
+            sort: Some(SortKey::Size),
+            min_size: Some(1),
+            max_size: Some(5),
+            ..Default::default()
+        };
+        let (names, totals) = list(&args);
+        assert_eq!(names, ["a", "d", "b"]);
+        assert_eq!(totals, "       11                     3 files");
+    }
+
+    #[test]
+    fn test_list_csv_and_print0() {
+        let zip_data = create_test_zip(&[("a, \"b\".txt", b"12345"), ("line\nbreak", b"")]);
+        let list = |args: &Args| {
+            let directory = CentralDirectory::open(Cursor::new(&zip_data)).unwrap();
+            let mut lister = Lister::new(Vec::new(), args);
+            list_records(directory, &NameDecoder::default(), &mut lister).unwrap();
+            String::from_utf8(lister.out).unwrap()
+        };
+
+        let args = Args { list_format: ListFormat::Csv, verbose: true, ..Default::default() };
+        let out = list(&args);
+        let rows: Vec<&str> = out.split_inclusive('\n').collect();
+        assert_eq!(rows[0], "name,size,compressed,ratio,modified,crc32,method,encryption\n");
+        assert!(rows[1].starts_with("\"a, \"\"b\"\".txt\",5,"), "{}", rows[1]);
+        assert!(rows[1].ends_with(",cbf53a1c,Stored,\n"), "{}", rows[1]);
+        assert!(rows[2].starts_with("\"line\n"));
+        assert_eq!(rows.len(), 4);
+
+        let args = Args { print0: true, list_only: true, ..Default::default() };
+        assert_eq!(list(&args), "a, \"b\".txt\0line\nbreak\0");
+        let args = Args { sort: Some(SortKey::Size), ..args };
+        assert_eq!(list(&args), "line\nbreak\0a, \"b\".txt\0");
+    }
+
+    #[test]
+    fn test_list_find_and_ls_lr() {
+        let zip_data = create_test_zip(&[("docs/a.txt", b"12345"), ("b.txt", b"")]);
+        let list = |args: &Args| {
+            let directory = CentralDirectory::open(Cursor::new(&zip_data)).unwrap();
+            let mut lister = Lister::new(Vec::new(), args);
+            list_records(directory, &NameDecoder::default(), &mut lister).unwrap();
+            String::from_utf8(lister.out).unwrap()
+        };
+
+        let args = Args { list_format: ListFormat::Find, ..Default::default() };
+        let out = list(&args);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2, "{}", out);
+        assert!(lines[0].starts_with("        1      1 -rw-r--r--   1 0        0   "), "{}", out);
+        assert!(lines[0].ends_with(" ./docs/a.txt"), "{}", out);
+        assert!(lines[1].starts_with("        2      0 -rw-r--r--"), "{}", out);
+
+        let args = Args { list_format: ListFormat::LsLR, sort: Some(SortKey::Size), ..args };
+        let out = list(&args);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[..2], [".:", "total 0"], "{}", out);
+        assert!(lines[2].starts_with("-rw-r--r-- 1 0 0 0 ") && lines[2].ends_with(" b.txt"));
+        assert!(lines[3].starts_with("drwxr-xr-x 2 0 0 0 Jan  1  1980 docs"), "{}", out);
+        assert_eq!(lines[4..7], ["", "./docs:", "total 1"], "{}", out);
+        assert!(lines[7].ends_with(" a.txt"), "{}", out);
+        assert_eq!(lines.len(), 8);
+
+        assert_eq!(parse_list_format("LS-LR"), Ok(ListFormat::LsLR));
+        assert_eq!(ListFormat::LsLR.to_string(), "ls-lR");
+    }
+
+    #[test]
+    fn test_list_tree() {
+        let zip_data = create_test_zip(&[
+            ("docs/", b""),
+            ("docs/big.txt", &[b'x'; 2048]),
+            ("docs/api/ref.txt", b"ref"),
+            ("a.txt", b"12345"),
+            ("skip.log", b"log"),
+        ]);
+        let list = |args: &Args| {
+            let directory = CentralDirectory::open(Cursor::new(&zip_data)).unwrap();
+            let mut lister = Lister::new(Vec::new(), args);
+            list_records(directory, &NameDecoder::default(), &mut lister).unwrap();
+            String::from_utf8(lister.out).unwrap()
+        };
+
+        let args = Args {
+            zipfile: "site.zip".into(),
+            exclude: vec!["*.log".to_string()],
+            tree: true,
+            ..Default::default()
+        };
+        let expected = "\
+[  2.0K]  site.zip
+├── [    5B]  a.txt
+└── [  2.0K]  docs
+    ├── [    3B]  api
+    │   └── [    3B]  ref.txt
+    └── [  2.0K]  big.txt
+
+2 directories, 3 files
+";
+        assert_eq!(list(&args), expected);
+
+        let args = Args { sort: Some(SortKey::Size), reverse: true, ..args };
+        let out = list(&args);
+        let names: Vec<&str> = out.lines().filter_map(|line| line.split("]  ").nth(1)).collect();
+        assert_eq!(names, ["site.zip", "docs", "big.txt", "api", "ref.txt", "a.txt"]);
+    }
+
+    #[test]
+    fn test_list_du() {
+        let zip_data = create_test_zip(&[
+            ("docs/big.txt", &[b'x'; 2048]),
+            ("docs/api/ref.txt", b"ref"),
+            ("src/main.rs", b"fn main() {}"),
+            ("a.txt", b"12345"),
+        ]);
+        let list = |args: &Args| {
+            let directory = CentralDirectory::open(Cursor::new(&zip_data)).unwrap();
+            let mut lister = Lister::new(Vec::new(), args);
+            list_records(directory, &NameDecoder::default(), &mut lister).unwrap();
+            String::from_utf8(lister.out).unwrap()
+        };
+        let dirs = |out: &str| -> Vec<String> {
+            out.lines()
+                .skip(1)
+                .map(|line| line.split("  ").last().unwrap().to_string())
+                .collect()
+        };
+
+        let args = Args { du: true, ..Default::default() };
+        let out = list(&args);
+        assert!(out.starts_with(
+            "    Size  Compressed  Directory
+"
+        ));
+        assert!(out.contains(
+            "
+    2.0K"
+        ));
+        assert_eq!(dirs(&out), ["docs/", "src/", "total"]);
+
+        let args = Args { depth: Some(2), sort: Some(SortKey::Size), ..args };
+        assert_eq!(dirs(&list(&args)), ["src/", "docs/api/", "docs/", "total"]);
+        let args = Args { depth: Some(0), ..args };
+        assert_eq!(dirs(&list(&args)), ["total"]);
+    }
+
+    #[test]
+    fn test_list_totals_only() {
+        let zip_data = create_test_zip(&[("a.txt", b"alpha"), ("b.log", b"beta")]);
+        let list = |totals_only| {
+            let args = Args {
+                zipfile: "my \"data\".zip".into(),
+                totals_only: Some(totals_only),
+                verbose: true,
+                ..Default::default()
+            };
+            let directory = CentralDirectory::open(Cursor::new(&zip_data)).unwrap();
+            let mut lister = Lister::new(Vec::new(), &args);
+            list_records(directory, &NameDecoder::default(), &mut lister).unwrap();
+            String::from_utf8(lister.out).unwrap()
+        };
+        assert_eq!(list(TotalsFormat::Text), "files=2 size=9 compressed=9 ratio=0%\n");
+        assert_eq!(
+            list(TotalsFormat::Json),
+            "{\"archive\":\"my \\\"data\\\".zip\",\"files\":2,\"size\":9,\"compressed\":9,\"ratio\":0}\n"
+        );
+        assert_eq!(parse_totals_format("JSON"), Ok(TotalsFormat::Json));
+        assert!(parse_totals_format("csv").is_err());
+    }
+
     #[test]
     fn test_list_contents_empty_archive() {
         let zip_data = create_test_zip(&[]);
@@ -442,12 +1309,12 @@ mod tests {
         assert_eq!(archive.len(), 0);
 
         // Should handle empty archives gracefully
-        let result = list_contents(&mut archive, false);
+        let result = list_contents(&mut archive, &list_args(false));
         assert!(result.is_ok());
 
         let cursor = Cursor::new(zip_data);
         let mut archive = ZipArchive::new(cursor).unwrap();
-        let result = list_contents(&mut archive, true);
+        let result = list_contents(&mut archive, &list_args(true));
         assert!(result.is_ok());
     }
 
@@ -465,7 +1332,7 @@ mod tests {
         let mut archive = ZipArchive::new(cursor).unwrap();
 
         // Should handle directories correctly
-        let result = list_contents(&mut archive, false);
+        let result = list_contents(&mut archive, &list_args(false));
         assert!(result.is_ok());
     }
 
@@ -479,7 +1346,7 @@ mod tests {
         let mut archive = ZipArchive::new(cursor).unwrap();
 
         // Should handle large files correctly
-        let result = list_contents(&mut archive, true);
+        let result = list_contents(&mut archive, &list_args(true));
         assert!(result.is_ok());
     }
 
@@ -497,12 +1364,12 @@ mod tests {
         let mut archive = ZipArchive::new(cursor).unwrap();
 
         // Should handle Unicode filenames correctly
-        let result = list_contents(&mut archive, false);
+        let result = list_contents(&mut archive, &list_args(false));
         assert!(result.is_ok());
 
         let cursor = Cursor::new(zip_data);
         let mut archive = ZipArchive::new(cursor).unwrap();
-        let result = list_contents(&mut archive, true);
+        let result = list_contents(&mut archive, &list_args(true));
         assert!(result.is_ok());
     }
 
@@ -557,4 +1424,13 @@ mod tests {
         let result = display_comment(&mut archive);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_info_zip_method_names() {
+        assert_eq!(info_zip_method(zip::CompressionMethod::Lzma), "LZMA");
+        assert_eq!(info_zip_method(zip::CompressionMethod::Xz), "XZ");
+        #[allow(deprecated)]
+        let shrunk = zip::CompressionMethod::Unsupported(1);
+        assert_eq!(info_zip_method(shrunk), "Shrunk");
+    }
 }
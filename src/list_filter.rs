@@ -0,0 +1,216 @@
+//! Sorted and filtered listings (`--sort`, `--min-size`, `--newer`, ...)
+//!
+//! Finding the largest or newest entries of a huge archive should not take
+//! `unzip -l | sort | awk`. With `-l` or `-v` (and `--totals-only`):
+//!
+//! - `--sort KEY` lists by `name`, `size` (uncompressed), `ratio` (space saved)
+//!   or `mtime`, smallest or oldest first, and `--reverse` turns that around;
+//!   entries that compare equal keep their archive order. A sorted listing is
+//!   printed once the whole central directory has been read.
+//! - `--min-size SIZE` and `--max-size SIZE` keep entries whose uncompressed
+//!   size is within the bounds, both inclusive (`K`, `M`, `G`, `T` suffixes)
+//! - `--newer DATE` and `--older DATE` keep entries modified after or before
+//!   DATE, given as `YYYY-MM-DD` (midnight) or `YYYY-MM-DD HH:MM[:SS]` and
+//!   compared with the entry's DOS time as stored. Entries without one are left
+//!   out
+//!
+//! The totals cover the entries listed.
+//!
+//! # Examples
+//!
+//! ```
+//! use unzip::list_filter::{ListFilter, SortKey, parse_list_date, parse_sort_key};
+//!
+//! assert_eq!(parse_sort_key("mtime"), Ok(SortKey::Mtime));
+//! let filter = ListFilter {
+//!     min_size: Some(1024),
+//!     newer: Some(parse_list_date("2024-01-01").unwrap()),
+//!     ..Default::default()
+//! };
+//! let modified = zip::DateTime::from_date_and_time(2024, 3, 15, 12, 0, 0).ok();
+//! assert!(filter.admits(4096, modified));
+//! assert!(!filter.admits(100, modified));
+//! ```
+
+use std::fmt;
+
+use crate::args::Args;
+
+/// What `--sort` orders a listing by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// Entry name, byte by byte
+    Name,
+    /// Uncompressed size
+    Size,
+    /// Space saved by compression, in percent
+    Ratio,
+    /// Modification time
+    Mtime,
+}
+
+impl fmt::Display for SortKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Name => write!(f, "name"),
+            Self::Size => write!(f, "size"),
+            Self::Ratio => write!(f, "ratio"),
+            Self::Mtime => write!(f, "mtime"),
+        }
+    }
+}
+
+/// Parse a `--sort` key: `name`, `size`, `ratio` or `mtime`
+///
+/// # Errors
+///
+/// Returns a message suitable for clap if the key is unknown
+pub fn parse_sort_key(value: &str) -> std::result::Result<SortKey, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "name" => Ok(SortKey::Name),
+        "size" => Ok(SortKey::Size),
+        "ratio" => Ok(SortKey::Ratio),
+        "mtime" => Ok(SortKey::Mtime),
+        _ => Err(format!("invalid sort key: {} (expected name, size, ratio or mtime)", value)),
+    }
+}
+
+/// A `--newer` or `--older` date, to the second
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ListDate(u64);
+
+impl ListDate {
+    fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Self {
+        let date = (u64::from(year) * 100 + u64::from(month)) * 100 + u64::from(day);
+        let time = (u64::from(hour) * 100 + u64::from(minute)) * 100 + u64::from(second);
+        Self(date * 1_000_000 + time)
+    }
+
+    /// The date of an entry's DOS modification time
+    pub fn from_datetime(datetime: zip::DateTime) -> Self {
+        Self::new(
+            datetime.year(),
+            datetime.month(),
+            datetime.day(),
+            datetime.hour(),
+            datetime.minute(),
+            datetime.second(),
+        )
+    }
+}
+
+/// Parse a `--newer` or `--older` date: `YYYY-MM-DD`, or with a time of day as
+/// `YYYY-MM-DD HH:MM[:SS]` (also with `T` in between)
+///
+/// # Errors
+///
+/// Returns a message suitable for clap if the date is malformed or out of range
+pub fn parse_list_date(value: &str) -> std::result::Result<ListDate, String> {
+    let invalid = || format!("invalid date: {} (expected YYYY-MM-DD [HH:MM[:SS]])", value);
+    let (date, time) = match value.trim().split_once([' ', 'T']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (value.trim(), None),
+    };
+    let numbers = |text: &str, count| -> Option<Vec<u16>> {
+        let parts: Vec<u16> =
+            text.split(&['-', ':'][..]).map(|n| n.parse().ok()).collect::<Option<_>>()?;
+        (count..=3).contains(&parts.len()).then_some(parts)
+    };
+    let date = numbers(date, 3).ok_or_else(invalid)?;
+    let time = match time {
+        Some(time) => numbers(time, 2).ok_or_else(invalid)?,
+        None => vec![0, 0],
+    };
+    let second = time.get(2).copied().unwrap_or(0);
+    let in_range = (1..=12).contains(&date[1])
+        && (1..=31).contains(&date[2])
+        && time[0] < 24
+        && time[1] < 60
+        && second < 60;
+    if !in_range {
+        return Err(invalid());
+    }
+    let [month, day, hour, minute, second] =
+        [date[1], date[2], time[0], time[1], second].map(|n| n as u8);
+    Ok(ListDate::new(date[0], month, day, hour, minute, second))
+}
+
+/// Bounds an entry must be within to be listed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ListFilter {
+    /// Smallest uncompressed size listed (`--min-size`)
+    pub min_size: Option<u64>,
+    /// Largest uncompressed size listed (`--max-size`)
+    pub max_size: Option<u64>,
+    /// Only entries modified after this (`--newer`)
+    pub newer: Option<ListDate>,
+    /// Only entries modified before this (`--older`)
+    pub older: Option<ListDate>,
+}
+
+impl ListFilter {
+    /// The bounds `--min-size`, `--max-size`, `--newer` and `--older` ask for
+    pub fn from_args(args: &Args) -> Self {
+        Self {
+            min_size: args.min_size,
+            max_size: args.max_size,
+            newer: args.newer,
+            older: args.older,
+        }
+    }
+
+    /// Whether an entry of `size` uncompressed bytes, modified at `modified`,
+    /// is listed
+    pub fn admits(&self, size: u64, modified: Option<zip::DateTime>) -> bool {
+        if self.min_size.is_some_and(|min| size < min)
+            || self.max_size.is_some_and(|max| size > max)
+        {
+            return false;
+        }
+        if self.newer.is_none() && self.older.is_none() {
+            return true;
+        }
+        let Some(modified) = modified.map(ListDate::from_datetime) else {
+            return false;
+        };
+        self.newer.is_none_or(|newer| modified > newer)
+            && self.older.is_none_or(|older| modified < older)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_list_date() {
+        let date = parse_list_date("2024-03-15").unwrap();
+        assert_eq!(date, ListDate::new(2024, 3, 15, 0, 0, 0));
+        assert_eq!(parse_list_date("2024-03-15 12:34"), Ok(ListDate::new(2024, 3, 15, 12, 34, 0)));
+        assert_eq!(
+            parse_list_date("2024-03-15T12:34:56"),
+            Ok(ListDate::new(2024, 3, 15, 12, 34, 56))
+        );
+        assert!(date < parse_list_date("2024-03-15 00:00:01").unwrap());
+        for invalid in ["2024-13-01", "2024-03", "2024-03-15 25:00", "yesterday", "2024-03-15 12"] {
+            assert!(parse_list_date(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_list_filter_admits() {
+        let modified = zip::DateTime::from_date_and_time(2024, 3, 15, 12, 34, 56).ok();
+        let filter = ListFilter {
+            min_size: Some(10),
+            max_size: Some(100),
+            newer: parse_list_date("2024-03-15 12:34:55").ok(),
+            older: parse_list_date("2024-03-16").ok(),
+        };
+        assert!(filter.admits(10, modified) && filter.admits(100, modified));
+        assert!(!filter.admits(9, modified) && !filter.admits(101, modified));
+        assert!(!filter.admits(50, None));
+        let newer = ListFilter { newer: parse_list_date("2024-03-15 12:34:56").ok(), ..filter };
+        assert!(!newer.admits(50, modified));
+        assert!(ListFilter::default().admits(0, None));
+    }
+}
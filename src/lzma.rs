@@ -0,0 +1,124 @@
+//! LZMA entries (method 14), as 7-Zip writes them
+//!
+//! In a ZIP entry, LZMA data starts with the version of the LZMA SDK that wrote
+//! it and the length of the properties that follow, always 5, and the stream
+//! after them may or may not end with an end marker, as a general purpose flag
+//! tells. The zip crate reads the data as an `.lzma` file instead, whose header
+//! is laid out differently, and fails on every such entry. [`LzmaReader`]
+//! decodes it with lzma-rs, told the uncompressed size the central directory
+//! records, so it stops there whether or not an end marker follows.
+//!
+//! [`crate::legacy::LegacyEntry`] uses it for LZMA entries, and checks their
+//! CRC32 as for the other methods the zip crate cannot decode.
+
+use lzma_rs::decompress::{Options, Stream, UnpackedSize};
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+
+/// Length of the LZMA properties: the lc/lp/pb byte and the dictionary size
+const PROPERTIES_LEN: u16 = 5;
+
+/// Compressed bytes handed to the decoder at a time, which bounds what one read
+/// decodes ahead
+const CHUNK_SIZE: usize = 4096;
+
+/// Decoder of the LZMA data of a ZIP entry, read as it is decoded
+///
+/// lzma-rs hands decoded data on as its dictionary fills, and the last of it
+/// once the stream is finished at the end of the compressed data.
+pub struct LzmaReader<R> {
+    raw: R,
+    /// The decoder, until the compressed data ends
+    stream: Option<Stream<VecDeque<u8>>>,
+    /// What the decoder still held when it was finished
+    rest: VecDeque<u8>,
+    chunk: Vec<u8>,
+}
+
+impl<R: Read> LzmaReader<R> {
+    /// Decode `raw`, the compressed data of an LZMA entry whose uncompressed size
+    /// is `size`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the header cannot be read, or
+    /// [`io::ErrorKind::InvalidData`] if its properties are not 5 bytes long
+    pub fn new(mut raw: R, size: u64) -> io::Result<Self> {
+        let mut header = [0; 4];
+        raw.read_exact(&mut header)?;
+        if u16::from_le_bytes([header[2], header[3]]) != PROPERTIES_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid LZMA properties length",
+            ));
+        }
+        let options = Options {
+            unpacked_size: UnpackedSize::UseProvided(Some(size)),
+            memlimit: None,
+            allow_incomplete: true,
+        };
+        Ok(Self {
+            raw,
+            stream: Some(Stream::new_with_options(&options, VecDeque::new())),
+            rest: VecDeque::new(),
+            chunk: vec![0; CHUNK_SIZE],
+        })
+    }
+}
+
+impl<R: Read> Read for LzmaReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let Some(stream) = &mut self.stream else {
+                return self.rest.read(buf);
+            };
+            let output = stream
+                .get_output_mut()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid LZMA stream"))?;
+            if !output.is_empty() || buf.is_empty() {
+                return output.read(buf);
+            }
+            let n = self.raw.read(&mut self.chunk)?;
+            if n == 0 {
+                if let Some(stream) = self.stream.take() {
+                    self.rest = stream.finish().map_err(io::Error::from)?;
+                }
+                continue;
+            }
+            let mut input = &self.chunk[..n];
+            while !input.is_empty() {
+                // Nothing taken once the size is decoded: the rest is the end marker
+                let used = stream.write(input)?;
+                if used == 0 {
+                    break;
+                }
+                input = &input[used..];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lzma_reader() {
+        let text = b"7-Zip writes LZMA entries like this one. ".repeat(50);
+        let mut compressed = Vec::new();
+        lzma_rs::lzma_compress(&mut &text[..], &mut compressed).unwrap();
+        // ZIP's header in place of the .lzma one: SDK version 9.20, 5 bytes of
+        // properties, then the properties without the 8 byte size
+        let mut data = vec![9, 20, 5, 0];
+        data.extend_from_slice(&compressed[..5]);
+        data.extend_from_slice(&compressed[13..]);
+
+        let mut out = Vec::new();
+        let reader = LzmaReader::new(&data[..], text.len() as u64).unwrap();
+        reader.take(text.len() as u64).read_to_end(&mut out).unwrap();
+        assert_eq!(out, text);
+
+        data[2] = 4;
+        assert!(LzmaReader::new(&data[..], text.len() as u64).is_err());
+    }
+}
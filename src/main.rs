@@ -15,91 +15,702 @@
 //! The main entry point handles:
 //! 1. CLI argument parsing and validation
 //! 2. File opening and memory mapping decisions
-//! 3. Dispatching to appropriate operation (list, test, extract, pipe)
+//! 3. Dispatching to appropriate operation (list, test, extract, pipe), once per
+//!    archive when several are given
+//! 4. Reporting errors, with Info-ZIP's exit codes under `--compat=infozip`
 //!
 //! Files >1MB use memory mapping for better performance, while smaller files
 //! use traditional file I/O to avoid mmap overhead.
 
 use anyhow::{Context, Result, bail};
-use clap::Parser;
 use memmap2::Mmap;
 use std::fs::File;
-use std::io::{Cursor, Read, Seek};
-use std::sync::Arc;
+use std::io::{self, BufWriter, Cursor, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::{Arc, LazyLock};
 use zip::ZipArchive;
+use zip::result::ZipError;
 
 use unzip::args::Args;
+use unzip::batch::Batch;
+use unzip::cancel::CancelToken;
+use unzip::central::CentralDirectory;
+use unzip::comment_json::write_comment_json;
+use unzip::compat::{Compat, exit_code};
+use unzip::diff::diff_archive;
+use unzip::error::UnzipError;
 use unzip::extract::{ArchiveSource, extract_archive, extract_archive_threaded, extract_to_pipe};
-use unzip::linux::{fadvise_sequential, madvise_sequential};
-use unzip::list::{display_comment, list_contents};
+use unzip::filter::filter_first_member;
+use unzip::invalid_names::InvalidNames;
+use unzip::lint::lint_archive;
+use unzip::linux::{
+    Probe, fadvise_dontneed, fadvise_sequential, madvise_sequential, preallocations, probe_target,
+};
+use unzip::list::{ListFormat, display_comment, list_contents, list_streaming};
+use unzip::metrics::RunMetrics;
+use unzip::multipart::{ConcatReader, find_parts};
+use unzip::options::ExtractOptions;
+use unzip::output_dir::check_output_dir;
+use unzip::password::get_password;
+use unzip::quick_test::quick_test;
+use unzip::salvage::{Salvaged, salvage};
+use unzip::sfx::{EmbeddedReader, locate_archive};
+use unzip::shape::display_stats;
+use unzip::size_check::{ExpectedSizes, SizeCheck, check_sizes};
+use unzip::status::StatusBoard;
+use unzip::tar::extract_to_tar;
 use unzip::test_archive::test_archive;
+use unzip::unmatched::{Unmatched, unmatched_patterns};
+use unzip::wait_complete::wait_complete;
 use unzip::zipinfo::display_zipinfo;
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+/// What SIGUSR1 prints about the extraction running, and the totals
+/// `--metrics-file` exports
+static STATUS: LazyLock<StatusBoard> = LazyLock::new(StatusBoard::new);
 
-    if args.overwrite && args.never_overwrite {
-        bail!("Cannot specify both -o (overwrite) and -n (never overwrite)");
+fn main() -> ExitCode {
+    let args = Args::parse_info_zip();
+    if is_extract(&args) {
+        print_status_on_signal();
+    }
+    if args.metrics_file.is_some() {
+        // The run's duration counts from here
+        LazyLock::force(&STATUS);
+    }
+
+    // Only operations that watch the token take over Ctrl-C
+    let cancel = if is_extract(&args) || args.test || args.test_quick || args.pipe || args.crt {
+        cancel_on_interrupt(args.checkpoint_every.is_some())
+    } else {
+        CancelToken::new()
+    };
+
+    // Before any archive is opened, let alone scanned
+    if is_extract(&args) {
+        let dir = args.output_dir.as_deref().unwrap_or(Path::new("."));
+        if let Err(error) = check_output_dir(dir, !args.no_create_dir, !args.dry_run) {
+            return report_error(&args, &UnzipError::from(error).into());
+        }
+    }
+
+    let batch = match Batch::from_args(&args) {
+        Ok(batch) => batch,
+        Err(error) => return report_error(&args, &error.into()),
+    };
+    if batch.is_batch() {
+        // One TAP stream per archive would not make one valid report
+        if args.report.is_some() {
+            let error = anyhow::anyhow!("--report takes a single archive");
+            return report_error(&args, &error);
+        }
+        let (code, failed) = run_batch(&args, &batch, &cancel);
+        write_metrics(&args, batch.archives.len(), failed);
+        return code;
+    }
+    let args = batch.args_for(&args, &batch.archives[0]);
+    let (code, failed) = match run(&args, &cancel) {
+        Ok(()) => (ExitCode::SUCCESS, 0),
+        Err(error) => (report_error(&args, &error), 1),
+    };
+    write_metrics(&args, 1, failed);
+    code
+}
+
+/// Write the run's counters to `--metrics-file`, if given, reporting a failure
+/// to write them without changing the exit status
+fn write_metrics(args: &Args, archives: usize, failed: usize) {
+    let Some(path) = &args.metrics_file else {
+        return;
+    };
+    let metrics = RunMetrics::new(&STATUS.snapshot(), archives, failed);
+    if let Err(error) = metrics.write_textfile(path) {
+        eprintln!("unzip: {}", error);
+    }
+}
+
+/// Print an error and return the exit status for it: Info-ZIP's code with
+/// `--compat=infozip`, otherwise 1, 11 for members missing from the archive and
+/// patterns matching nothing, or 13 and 14 for a missing or invalid `--comment-json`
+fn report_error(args: &Args, error: &anyhow::Error) -> ExitCode {
+    if args.compat == Compat::InfoZip {
+        eprintln!("unzip:  {:#}", error);
+        return ExitCode::from(exit_code(error.chain()));
     }
+    // Reported the way returning the error from `main` would
+    eprintln!("Error: {:?}", error);
+    // Pipelines checking a manifest tell this failure apart like Info-ZIP's
+    let missing_members = error.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref(),
+            Some(UnzipError::MissingMembers(_) | UnzipError::Unmatched(_))
+        )
+    });
+    let comment_json = error.chain().find_map(|cause| match cause.downcast_ref() {
+        Some(UnzipError::CommentJson(error)) => Some(error.exit_code()),
+        _ => None,
+    });
+    if missing_members {
+        ExitCode::from(11)
+    } else if let Some(code) = comment_json {
+        ExitCode::from(code)
+    } else {
+        ExitCode::FAILURE
+    }
+}
 
-    let is_extract = !args.zipinfo.is_some()
+/// Process each archive of a batch, carrying on past failed ones, then report how
+/// many succeeded
+///
+/// The exit status is that of the first failure, returned with the number of
+/// archives that failed.
+fn run_batch(args: &Args, batch: &Batch, cancel: &CancelToken) -> (ExitCode, usize) {
+    let mut status = None;
+    let mut failed = 0;
+    for (i, archive) in batch.archives.iter().enumerate() {
+        if cancel.is_cancelled() {
+            break;
+        }
+        let args = batch.args_for(args, archive);
+        if args.quiet == 0 && !reported(&args, write_banner(&args, i, archive), &mut status) {
+            break;
+        }
+        if let Err(error) = run(&args, cancel) {
+            failed += 1;
+            let code = report_error(&args, &error);
+            status.get_or_insert(code);
+        }
+    }
+
+    let succeeded = batch.archives.len() - failed;
+    if args.compat == Compat::InfoZip {
+        if args.quiet == 0 && succeeded > 0 {
+            let summary = writeln!(
+                io::stdout().lock(),
+                "\n{} {} successfully processed.",
+                succeeded,
+                archives_were(succeeded)
+            );
+            reported(args, summary, &mut status);
+        }
+        if args.quiet < 2 && failed > 0 {
+            eprintln!("{} {} fatal errors.", failed, archives_had(failed));
+        }
+    } else if args.quiet == 0 {
+        let summary = writeln!(
+            io::stdout().lock(),
+            "\nProcessed {} archives: {} succeeded, {} failed",
+            batch.archives.len(),
+            succeeded,
+            failed
+        );
+        reported(args, summary, &mut status);
+    } else if args.quiet < 2 && failed > 0 {
+        eprintln!("{} of {} archives failed", failed, batch.archives.len());
+    }
+    (status.unwrap_or(ExitCode::SUCCESS), failed)
+}
+
+/// Write the blank line between archives of a batch and, natively, the archive's
+/// name
+fn write_banner(args: &Args, index: usize, archive: &Path) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    if index > 0 {
+        writeln!(stdout)?;
+    }
+    if args.compat == Compat::Native {
+        writeln!(stdout, "Archive: {}", archive.display())?;
+    }
+    Ok(())
+}
+
+/// Whether `written`, part of the batch report on stdout, went out
+///
+/// A reader that stopped reading, like `head`, ends the report quietly; other
+/// failures are reported, and the first one sets the exit status if no archive
+/// failed before it.
+fn reported(args: &Args, written: io::Result<()>, status: &mut Option<ExitCode>) -> bool {
+    match written {
+        Ok(()) => true,
+        Err(error) if error.kind() == io::ErrorKind::BrokenPipe => false,
+        Err(error) => {
+            status.get_or_insert(report_error(args, &error.into()));
+            false
+        },
+    }
+}
+
+/// "archives were" or, for one, "archive was", as Info-ZIP's batch summary says
+fn archives_were(count: usize) -> &'static str {
+    if count == 1 {
+        "archive was"
+    } else {
+        "archives were"
+    }
+}
+
+/// "archives had" or, for one, "archive had"
+fn archives_had(count: usize) -> &'static str {
+    if count == 1 {
+        "archive had"
+    } else {
+        "archives had"
+    }
+}
+
+/// Whether the arguments ask for extraction rather than another command
+fn is_extract(args: &Args) -> bool {
+    !args.zipinfo.is_some()
         && !args.comment_only
+        && args.comment_json.is_none()
         && !args.list_only
         && !args.verbose
+        && args.totals_only.is_none()
+        && !args.tree
+        && !args.print0
+        && !args.du
         && !args.test
-        && !args.pipe;
+        && !args.test_quick
+        && !args.lint
+        && args.diff.is_none()
+        && !args.stats
+        && !args.pipe
+        && !args.crt
+        && !args.filter
+        && args.to_tar.is_none()
+}
+
+/// Whether the arguments ask for an `-l`, `-v`, `--totals-only`, `--tree`,
+/// `--print0` or `--du` listing, which [`run_command`] would pick
+fn is_listing(args: &Args) -> bool {
+    args.zipinfo.is_none()
+        && !args.comment_only
+        && args.comment_json.is_none()
+        && (args.list_only
+            || args.verbose
+            || args.totals_only.is_some()
+            || args.tree
+            || args.print0
+            || args.du)
+}
+
+fn run(args: &Args, cancel: &CancelToken) -> Result<()> {
+    if args.overwrite && args.never_overwrite {
+        bail!("Cannot specify both -o (overwrite) and -n (never overwrite)");
+    }
+
+    if args.filter {
+        return run_filter(args);
+    }
+
+    if let Some(timeout) = args.wait_complete {
+        wait_complete(&args.zipfile, timeout, cancel)?;
+    }
+
+    let checked;
+    let args = match args.size_check {
+        Some(mode) if is_extract(args) || args.test => {
+            let sizes = check_entry_sizes(args, mode)?;
+            checked = Args { expected_sizes: Some(Arc::new(sizes)), ..args.clone() };
+            &checked
+        },
+        _ => args,
+    };
+
+    let is_extract = is_extract(args);
+    let unmatched = if is_extract || args.test || args.test_quick || is_listing(args) {
+        find_unmatched(args)
+    } else {
+        None
+    };
+    if let Some(unmatched) = unmatched.as_ref().filter(|unmatched| unmatched.selected == 0) {
+        // Nothing to do: Info-ZIP stops after the cautions
+        print_archive_header(args, is_extract);
+        print_cautions(args, unmatched);
+        return Err(UnzipError::from(unmatched.clone()).into());
+    }
+    run_archive(args, is_extract, cancel)?;
+    match unmatched {
+        Some(unmatched) => {
+            print_cautions(args, &unmatched);
+            Err(UnzipError::from(unmatched).into())
+        },
+        None => Ok(()),
+    }
+}
+
+/// The member patterns of `args` that match no entry of the archive (see
+/// [`unzip::unmatched`])
+///
+/// An archive whose central directory cannot be read is left to the command to
+/// report.
+fn find_unmatched(args: &Args) -> Option<Unmatched> {
+    let result = match find_parts(&args.zipfile) {
+        Some(parts) => ConcatReader::open(&parts)
+            .map_err(UnzipError::from)
+            .and_then(|reader| unmatched_patterns(reader, args)),
+        None => File::open(&args.zipfile)
+            .map_err(UnzipError::from)
+            .and_then(|file| unmatched_patterns(file, args)),
+    };
+    result.ok().flatten()
+}
+
+/// Compare the size fields of every entry before the archive is extracted or
+/// tested (`--size-check`), warning about those `lenient` lets through
+fn check_entry_sizes(args: &Args, mode: SizeCheck) -> Result<ExpectedSizes> {
+    let warn = |warning: &str| {
+        if args.quiet < 2 {
+            eprintln!("warning [{}]:  {}", args.zipfile.display(), warning);
+        }
+    };
+    let sizes = match find_parts(&args.zipfile) {
+        Some(parts) => check_sizes(ConcatReader::open(&parts)?, mode, warn),
+        None => {
+            let file = File::open(&args.zipfile)
+                .with_context(|| format!("Failed to open ZIP file: {}", args.zipfile.display()))?;
+            check_sizes(file, mode, warn)
+        },
+    };
+    Ok(sizes?)
+}
+
+/// Print Info-ZIP's caution for each pattern that matched nothing
+fn print_cautions(args: &Args, unmatched: &Unmatched) {
+    if args.quiet < 2 {
+        for caution in unmatched.cautions() {
+            eprintln!("{}", caution);
+        }
+    }
+}
+
+/// Open the archive, or the parts of a split one, and run the command on it
+fn run_archive(args: &Args, is_extract: bool, cancel: &CancelToken) -> Result<()> {
+    if let Some(parts) = find_parts(&args.zipfile) {
+        print_archive_header(args, is_extract);
+        return run_parts(parts, args, is_extract, cancel);
+    }
 
     let file = File::open(&args.zipfile)
         .with_context(|| format!("Failed to open ZIP file: {}", args.zipfile.display()))?;
+    print_archive_header(args, is_extract);
+
+    if args.salvage {
+        return run_salvage(&file, args, cancel);
+    }
 
     let file_size = file.metadata()?.len();
 
     if file_size > 1024 * 1024 {
         // Linux optimization: hint kernel about sequential access
-        fadvise_sequential(&file, file_size);
+        let fadvised = fadvise_sequential(&file, file_size);
 
         let mmap = unsafe { Mmap::map(&file) }.with_context(|| "Failed to memory-map file")?;
 
         // Linux optimization: tell kernel we'll read sequentially
-        madvise_sequential(mmap.as_ptr(), mmap.len());
+        let madvised = madvise_sequential(mmap.as_ptr(), mmap.len());
 
         if is_extract {
+            if args.verbose_io {
+                report_io(args, "memory-mapped", &[("fadvise", &fadvised), ("madvise", &madvised)]);
+            }
             let source = ArchiveSource::Mmap(Arc::new(mmap));
-            extract_archive_threaded(source, &args)
+            let result = extract_or_scan(source, || file.try_clone(), args, cancel);
+            release_archive(&file, file_size, args);
+            report_preallocations(args);
+            result
         } else {
-            let cursor = Cursor::new(&mmap[..]);
-            let mut archive = ZipArchive::new(cursor)
-                .with_context(|| format!("Failed to read ZIP archive: {}", args.zipfile.display()))?;
-            run_command(&mut archive, &args)
+            run_or_scan(|| Ok(Cursor::new(&mmap[..])), args, cancel)
         }
     } else {
         // For smaller files, still hint sequential access
-        fadvise_sequential(&file, file_size);
+        let fadvised = fadvise_sequential(&file, file_size);
 
         if is_extract {
+            if args.verbose_io {
+                report_io(args, "buffered reads", &[("fadvise", &fadvised)]);
+            }
             let source = ArchiveSource::FilePath(args.zipfile.clone());
-            extract_archive_threaded(source, &args)
+            let result = extract_or_scan(source, || file.try_clone(), args, cancel);
+            release_archive(&file, file_size, args);
+            report_preallocations(args);
+            result
         } else {
-            let mut archive = ZipArchive::new(file)
-                .with_context(|| format!("Failed to read ZIP archive: {}", args.zipfile.display()))?;
-            run_command(&mut archive, &args)
+            run_or_scan(|| file.try_clone(), args, cancel)
+        }
+    }
+}
+
+/// Write the first member of the zip or gzip stream on stdin, or in the file
+/// given, to stdout (`--filter`)
+fn run_filter(args: &Args) -> Result<()> {
+    let password = get_password(args.password.as_deref(), args.quiet)?;
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    if args.zipfile == Path::new("-") {
+        filter_first_member(io::stdin().lock(), password.as_deref(), &mut out)?;
+    } else {
+        let file = File::open(&args.zipfile)
+            .with_context(|| format!("Failed to open ZIP file: {}", args.zipfile.display()))?;
+        filter_first_member(file, password.as_deref(), &mut out)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Read a byte-concatenated split archive (`.zip.001`, `.partNN.zip`) as one file
+fn run_parts(
+    parts: Vec<PathBuf>,
+    args: &Args,
+    is_extract: bool,
+    cancel: &CancelToken,
+) -> Result<()> {
+    if args.quiet == 0 && !args.pipe {
+        eprintln!("Joining {} split archive parts from {}", parts.len(), parts[0].display());
+    }
+    if is_extract {
+        if args.verbose_io {
+            report_io(args, "buffered reads of joined parts", &[]);
         }
+        let files: Vec<File> = parts.iter().filter_map(|part| File::open(part).ok()).collect();
+        let source = ArchiveSource::Parts(parts.clone());
+        let result = extract_or_scan(source, || ConcatReader::open(&parts), args, cancel);
+        let sizes: Vec<u64> =
+            files.iter().map(|file| file.metadata().map_or(0, |meta| meta.len())).collect();
+        if args.cache.drops(sizes.iter().sum()) {
+            for (file, size) in files.iter().zip(sizes) {
+                fadvise_dontneed(file, 0, size);
+            }
+        }
+        report_preallocations(args);
+        return result;
     }
+    run_or_scan(|| ConcatReader::open(&parts), args, cancel)
 }
 
-fn run_command<R: Read + Seek>(archive: &mut ZipArchive<R>, args: &Args) -> Result<()> {
+/// Extract with worker threads, or serially through a scan for the archive if it
+/// does not open as is (see [`open_embedded_archive`])
+fn extract_or_scan<R: Read + Seek>(
+    source: ArchiveSource,
+    reopen: impl FnOnce() -> io::Result<R>,
+    args: &Args,
+    cancel: &CancelToken,
+) -> Result<()> {
+    match extract_archive_threaded(source, &extract_options(args, cancel)?) {
+        Err(UnzipError::Zip(error @ ZipError::InvalidArchive(_))) => {
+            let reader = reopen()
+                .with_context(|| format!("Failed to open ZIP file: {}", args.zipfile.display()))?;
+            run_command(&mut open_embedded_archive(reader, args, error)?, args, cancel)
+        },
+        result => Ok(result?),
+    }
+}
+
+/// Run a command other than threaded extraction on the archive, scanning for it if
+/// it does not open as is (see [`open_embedded_archive`])
+fn run_or_scan<R: Read + Seek>(
+    open: impl Fn() -> io::Result<R>,
+    args: &Args,
+    cancel: &CancelToken,
+) -> Result<()> {
+    let open =
+        || open().with_context(|| format!("Failed to open ZIP file: {}", args.zipfile.display()));
+    if args.test_quick {
+        return Ok(quick_test(open()?, args, cancel)?);
+    }
+    if is_listing(args) && args.invalid_names != InvalidNames::Reject {
+        // Listed as the central directory is read, rather than after indexing all
+        // of it; archives it cannot make out take the usual way and its errors
+        if let Ok(directory) = CentralDirectory::open(open()?) {
+            return Ok(list_streaming(directory, args)?);
+        }
+    }
+    match ZipArchive::new(open()?) {
+        Ok(mut archive) => run_command(&mut archive, args, cancel),
+        Err(error) => run_command(&mut open_embedded_archive(open()?, args, error)?, args, cancel),
+    }
+}
+
+/// Open an archive that `ZipArchive::new` rejected with `error` by scanning for its
+/// end of central directory record, as Info-ZIP does for self-extractors whose
+/// comment was cut off
+fn open_embedded_archive<R: Read + Seek>(
+    mut reader: R,
+    args: &Args,
+    error: ZipError,
+) -> Result<ZipArchive<EmbeddedReader<R>>> {
+    let context = || format!("Failed to read ZIP archive: {}", args.zipfile.display());
+    let Some(found) = locate_archive(&mut reader).ok().flatten() else {
+        return Err(anyhow::Error::new(error).context(context()));
+    };
+    let archive = ZipArchive::new(EmbeddedReader::new(reader, found)).with_context(context)?;
+    if found.comment_truncated && args.quiet == 0 {
+        eprintln!("warning [{}]:  zipfile comment truncated", args.zipfile.display());
+    }
+    Ok(archive)
+}
+
+/// Run the command on the entries recovered from the archive's local file headers
+/// (`--salvage`), after reporting those that could not be recovered
+fn run_salvage(file: &File, args: &Args, cancel: &CancelToken) -> Result<()> {
+    let mmap = unsafe { Mmap::map(file) }.with_context(|| "Failed to memory-map file")?;
+    let Salvaged { mut archive, lost } = salvage(&mmap)
+        .with_context(|| format!("Failed to salvage ZIP archive: {}", args.zipfile.display()))?;
+    if args.quiet < 2 {
+        for entry in &lost {
+            eprintln!(
+                "salvage: skipping {} (offset {}): {}",
+                entry.name, entry.offset, entry.problem
+            );
+        }
+    }
+    if args.quiet == 0 {
+        eprintln!("salvage: recovered {} entries, skipped {}", archive.len(), lost.len());
+    }
+    run_command(&mut archive, args, cancel)
+}
+
+/// Print Info-ZIP's `Archive:` line ahead of a listing in Info-ZIP's layout, and
+/// of extracting or testing with `--compat=infozip`
+fn print_archive_header(args: &Args, is_extract: bool) {
+    let listing = (args.list_only || args.verbose)
+        && args.totals_only.is_none()
+        && !args.tree
+        && !args.print0
+        && !args.du;
+    let info_zip_listing = listing && args.list_format == ListFormat::InfoZip;
+    if args.quiet == 0
+        && (info_zip_listing
+            || (args.compat == Compat::InfoZip && (is_extract || args.test || args.crt)))
+    {
+        println!("Archive:  {}", args.zipfile.display());
+    }
+}
+
+/// Cancel the running operation on Ctrl-C, SIGTERM or SIGHUP, so the file being
+/// written is removed before exiting
+///
+/// A `checkpointed` run instead finishes the files being written, so the
+/// checkpoint covers them; a second signal cancels it right away.
+fn cancel_on_interrupt(checkpointed: bool) -> CancelToken {
+    let cancel = CancelToken::new();
+    let token = cancel.clone();
+    // Without a handler, Ctrl-C keeps its default behavior of killing the process
+    let _ = ctrlc::set_handler(move || {
+        if checkpointed && !token.is_cancelled() {
+            token.stop();
+        } else {
+            token.cancel();
+        }
+    });
+    cancel
+}
+
+/// Print a status snapshot to stderr on SIGUSR1, for extractions that run
+/// without a progress bar to look at
+#[cfg(unix)]
+fn print_status_on_signal() {
+    use signal_hook::consts::SIGUSR1;
+    use signal_hook::iterator::Signals;
+
+    LazyLock::force(&STATUS);
+    // Without a handler, SIGUSR1 keeps its default behavior of ending the process
+    let Ok(mut signals) = Signals::new([SIGUSR1]) else {
+        return;
+    };
+    std::thread::spawn(move || {
+        for _ in signals.forever() {
+            eprint!("{}", STATUS.snapshot());
+        }
+    });
+}
+
+#[cfg(not(unix))]
+fn print_status_on_signal() {}
+
+/// Drop the archive from the page cache once extraction is done, if `--cache`
+/// asks for it
+fn release_archive(file: &File, size: u64, args: &Args) {
+    if args.cache.drops(size) {
+        fadvise_dontneed(file, 0, size);
+    }
+}
+
+fn extract_options(args: &Args, cancel: &CancelToken) -> Result<ExtractOptions> {
+    let options = ExtractOptions::try_from(args)?.cancel_token(cancel.clone());
+    Ok(options.status_board(STATUS.clone()))
+}
+
+/// Print how the archive is read, what became of the access hints given for it,
+/// and which optimizations the output filesystem supports (`--verbose-io`)
+fn report_io(args: &Args, access: &str, hints: &[(&str, &Probe)]) {
+    eprintln!("Archive access: {}", access);
+    if hints.is_empty() {
+        eprintln!("  no access hints");
+    }
+    for (call, outcome) in hints {
+        eprintln!("  {}(SEQUENTIAL, WILLNEED): {}", call, outcome);
+    }
+    // The output directory may not exist yet; probe the filesystem it will live on
+    let output_dir = args.output_dir.clone().unwrap_or_else(|| PathBuf::from("."));
+    let target = output_dir.ancestors().find(|dir| dir.is_dir()).unwrap_or(Path::new("."));
+    eprintln!("{}", probe_target(target));
+    eprintln!("Page cache: {} (--cache)", args.cache);
+}
+
+/// Print how many extracted files `fallocate()` reserved space for (`--verbose-io`)
+fn report_preallocations(args: &Args) {
+    if args.verbose_io {
+        eprintln!("Preallocated: {}", preallocations());
+    }
+}
+
+fn run_command<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    args: &Args,
+    cancel: &CancelToken,
+) -> Result<()> {
     if args.zipinfo.is_some() {
         display_zipinfo(archive, args)?;
     } else if args.comment_only {
         display_comment(archive)?;
-    } else if args.list_only || args.verbose {
-        list_contents(archive, args.verbose)?;
+    } else if let Some(path) = &args.comment_json {
+        write_comment_json(archive, path)?;
+    } else if is_listing(args) {
+        list_contents(archive, args)?;
+    } else if args.lint {
+        lint_archive(archive, args)?;
+    } else if let Some(dir) = &args.diff {
+        diff_archive(archive, args, dir)?;
+    } else if args.stats {
+        display_stats(archive, args)?;
     } else if args.test {
-        test_archive(archive, args)?;
-    } else if args.pipe {
-        extract_to_pipe(archive, args)?;
+        test_archive(archive, args, cancel)?;
+    } else if args.pipe || args.crt {
+        extract_to_pipe(archive, &extract_options(args, cancel)?)?;
+    } else if let Some(target) = &args.to_tar {
+        write_tar(archive, args, target, cancel)?;
+    } else {
+        extract_archive(archive, &extract_options(args, cancel)?)?;
+    }
+    Ok(())
+}
+
+/// Convert the archive to a tar stream written to `target`, or stdout for `-`
+/// (`--to-tar`)
+fn write_tar<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    args: &Args,
+    target: &Path,
+    cancel: &CancelToken,
+) -> Result<()> {
+    let options = extract_options(args, cancel)?;
+    if target == Path::new("-") {
+        extract_to_tar(archive, &options, BufWriter::new(io::stdout().lock()))?;
     } else {
-        extract_archive(archive, args)?;
+        let file = File::create(target)
+            .with_context(|| format!("Failed to create tar file: {}", target.display()))?;
+        extract_to_tar(archive, &options, BufWriter::new(file))?;
     }
     Ok(())
 }
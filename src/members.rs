@@ -0,0 +1,198 @@
+//! Extraction of an exact list of entries (`--members-from`)
+//!
+//! Build pipelines often need a precise file set out of an archive, which globs
+//! cannot express: a pattern matching nothing is not an error, and names holding
+//! `*` or `?` cannot be selected alone. With `--members-from FILE`, FILE lists
+//! entry names, one per line, that are compared literally with the entry names in
+//! the archive. Before anything is written, every listed name must be in the
+//! archive; otherwise the run fails with [`MissingMembers`], naming all of them,
+//! and exits with code 11 (Info-ZIP's "no matching files").
+//!
+//! Listed members add to the patterns given on the command line, and `-x` still
+//! leaves entries out. A directory may be listed with or without its trailing
+//! `/`. Blank lines are ignored, and `-` reads the list from stdin.
+//!
+//! Callers that already listed the archive can instead name a single entry by
+//! its position in the central directory with `--member-index N`, counting from
+//! 0 as [`CentralDirectory`](crate::central::CentralDirectory) yields them. Only
+//! that entry is considered, whatever names it shares with others; patterns and
+//! `-x` still apply to it. An index past the last entry fails with
+//! [`UnzipError::NoSuchEntry`], exit code 11.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use std::path::Path;
+//! use zip::ZipArchive;
+//! use unzip::members::read_members;
+//! use unzip::{ExtractOptions, extract_archive};
+//!
+//! let members = read_members(Path::new("release.manifest"))?;
+//! let options = ExtractOptions::new().output_dir("dist").members(members);
+//! extract_archive(&mut ZipArchive::new(File::open("build.zip")?)?, &options)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Seek};
+use std::path::Path;
+use zip::ZipArchive;
+
+use crate::charset::NameDecoder;
+use crate::error::{Result, UnzipError};
+
+/// Listed members that are not in the archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingMembers {
+    /// The missing names, in sorted order
+    pub names: Vec<String>,
+}
+
+impl fmt::Display for MissingMembers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} listed members not found in the archive (--members-from):",
+            self.names.len()
+        )?;
+        for name in &self.names {
+            write!(f, "\n  {}", name)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MissingMembers {}
+
+/// Read a member list, one entry name per line, from `path`, or from stdin for `-`
+///
+/// Line endings (`\n` or `\r\n`) and blank lines are dropped; everything else on
+/// a line, spaces included, is part of the name. The names come back sorted and
+/// without duplicates.
+///
+/// # Errors
+///
+/// Returns an error if the list cannot be read or is not UTF-8
+pub fn read_members(path: &Path) -> Result<Vec<String>> {
+    let text = if path == Path::new("-") {
+        let mut text = String::new();
+        io::stdin()
+            .read_to_string(&mut text)
+            .map_err(UnzipError::file("read member list", path))?;
+        text
+    } else {
+        fs::read_to_string(path).map_err(UnzipError::file("read member list", path))?
+    };
+    let mut members: Vec<String> =
+        text.lines().filter(|line| !line.is_empty()).map(str::to_string).collect();
+    members.sort();
+    members.dedup();
+    Ok(members)
+}
+
+/// Whether `name` is one of the sorted `members`, a directory also by its name
+/// without the trailing `/`
+pub(crate) fn is_member(members: &[String], name: &str) -> bool {
+    let listed = |name: &str| members.binary_search_by(|member| member.as_str().cmp(name)).is_ok();
+    !members.is_empty() && (listed(name) || name.strip_suffix('/').is_some_and(listed))
+}
+
+/// Check that `index`, when given, is the position of an entry of the archive
+///
+/// # Errors
+///
+/// Returns [`UnzipError::NoSuchEntry`] if the archive has no entry at `index`
+pub(crate) fn check_member_index<R: Read + Seek>(
+    archive: &ZipArchive<R>,
+    index: Option<usize>,
+) -> Result<()> {
+    match index {
+        Some(index) if index >= archive.len() => {
+            Err(UnzipError::NoSuchEntry { index, entries: archive.len() })
+        },
+        _ => Ok(()),
+    }
+}
+
+/// Check that every one of the sorted `members` names an entry of the archive
+///
+/// # Errors
+///
+/// Returns a [`MissingMembers`] error naming all missing members, or an error if
+/// the central directory cannot be read
+pub(crate) fn check_members<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    names: &NameDecoder,
+    members: &[String],
+) -> Result<()> {
+    if members.is_empty() {
+        return Ok(());
+    }
+    let mut found = vec![false; members.len()];
+    for i in 0..archive.len() {
+        let file = archive.by_index_raw(i)?;
+        let name = names.decode(&file);
+        let bare = name.strip_suffix('/').filter(|_| file.is_dir());
+        for name in [Some(name.as_ref()), bare].into_iter().flatten() {
+            if let Ok(index) = members.binary_search_by(|member| member.as_str().cmp(name)) {
+                found[index] = true;
+            }
+        }
+    }
+    let names: Vec<String> = members
+        .iter()
+        .zip(found)
+        .filter(|(_, found)| !found)
+        .map(|(member, _)| member.clone())
+        .collect();
+    if names.is_empty() {
+        Ok(())
+    } else {
+        Err(MissingMembers { names }.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::ZipWriter;
+    use zip::write::SimpleFileOptions;
+
+    #[test]
+    fn test_read_members_sorts_and_keeps_spaces() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("manifest");
+        fs::write(&path, "b/report *final*.txt\r\n\na.txt\n a.txt\na.txt\n").unwrap();
+        let members = read_members(&path).unwrap();
+        assert_eq!(members, [" a.txt", "a.txt", "b/report *final*.txt"]);
+        assert!(is_member(&members, "b/report *final*.txt"));
+        assert!(!is_member(&members, "b/report final.txt"));
+    }
+
+    #[test]
+    fn test_check_members_names_all_missing() {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.add_directory("docs/", SimpleFileOptions::default()).unwrap();
+        zip.start_file("docs/a?.txt", SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"a").unwrap();
+        let mut archive = ZipArchive::new(zip.finish().unwrap()).unwrap();
+        let names = NameDecoder::default();
+
+        let present = ["docs".to_string(), "docs/a?.txt".to_string()];
+        check_members(&mut archive, &names, &present).unwrap();
+
+        let listed = ["docs/a1.txt".to_string(), "docs/a?.txt".to_string(), "zz".to_string()];
+        let error = check_members(&mut archive, &names, &listed).unwrap_err();
+        assert!(matches!(
+            &error,
+            UnzipError::MissingMembers(missing) if missing.names == ["docs/a1.txt", "zz"]
+        ));
+        assert_eq!(
+            error.to_string(),
+            "2 listed members not found in the archive (--members-from):\n  docs/a1.txt\n  zz"
+        );
+    }
+}
@@ -0,0 +1,234 @@
+//! Run metrics for monitoring (`--metrics-file`)
+//!
+//! Scheduled extraction jobs are easier to watch from a dashboard than from their
+//! logs. With `--metrics-file /var/lib/node_exporter/unzip.prom`, the run writes
+//! its counters there when it completes, in the Prometheus text exposition
+//! format the node_exporter textfile collector reads:
+//!
+//! ```text
+//! # HELP unzip_bytes_extracted_total Bytes written to extracted files, after decompression.
+//! # TYPE unzip_bytes_extracted_total counter
+//! unzip_bytes_extracted_total 1048576
+//! # HELP unzip_entries_extracted_total Files extracted.
+//! # TYPE unzip_entries_extracted_total counter
+//! unzip_entries_extracted_total 42
+//! ...
+//! ```
+//!
+//! The counters are those of the whole run, across archives: bytes and files
+//! extracted, entries skipped, warnings, archives processed and archives that
+//! failed (`unzip_errors_total`), along with the duration, the throughput, the
+//! time the run finished and whether it succeeded. The file is written next to
+//! its final name and renamed into place, so the collector never reads half of
+//! it; a failed or cancelled run still writes it. Failing to write it is reported
+//! on stderr without changing the exit status.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::time::Duration;
+//! use unzip::metrics::RunMetrics;
+//!
+//! let metrics = RunMetrics {
+//!     bytes: 2048,
+//!     files: 2,
+//!     duration: Duration::from_secs(2),
+//!     archives: 1,
+//!     ..RunMetrics::default()
+//! };
+//! let text = metrics.to_string();
+//! assert!(text.contains("\nunzip_bytes_extracted_total 2048\n"));
+//! assert!(text.contains("\nunzip_throughput_bytes_per_second 1024\n"));
+//! assert!(text.contains("\nunzip_last_run_success 1\n"));
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use crate::error::{Result, UnzipError};
+use crate::status::StatusSnapshot;
+
+/// Counters of a completed run, as `--metrics-file` writes them
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunMetrics {
+    /// Bytes written to extracted files, after decompression
+    pub bytes: u64,
+    /// Files extracted
+    pub files: u64,
+    /// Entries skipped: existing, unsafe or read-only destinations and the like
+    pub skipped: u64,
+    /// Warnings reported while extracting
+    pub warnings: u64,
+    /// Archives processed
+    pub archives: usize,
+    /// Archives that failed
+    pub errors: usize,
+    /// Time the run took
+    pub duration: Duration,
+    /// When the run finished
+    pub finished: SystemTime,
+}
+
+impl Default for RunMetrics {
+    fn default() -> Self {
+        Self {
+            bytes: 0,
+            files: 0,
+            skipped: 0,
+            warnings: 0,
+            archives: 0,
+            errors: 0,
+            duration: Duration::ZERO,
+            finished: SystemTime::UNIX_EPOCH,
+        }
+    }
+}
+
+impl RunMetrics {
+    /// The metrics of a run that just finished, from the status board's totals
+    /// and the number of archives processed and failed
+    pub fn new(snapshot: &StatusSnapshot, archives: usize, errors: usize) -> Self {
+        Self {
+            bytes: snapshot.bytes,
+            files: snapshot.files,
+            skipped: snapshot.skipped,
+            warnings: snapshot.warnings,
+            archives,
+            errors,
+            duration: snapshot.elapsed,
+            finished: SystemTime::now(),
+        }
+    }
+
+    /// Bytes extracted per second over the whole run
+    pub fn throughput(&self) -> f64 {
+        let secs = self.duration.as_secs_f64();
+        if secs > 0.0 {
+            self.bytes as f64 / secs
+        } else {
+            0.0
+        }
+    }
+
+    /// Write the metrics to `path` through a temporary file renamed into place
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the temporary file cannot be written or renamed
+    pub fn write_textfile(&self, path: &Path) -> Result<()> {
+        // The textfile collector only reads names ending in .prom
+        let temp = path.with_extension("prom.tmp");
+        fs::write(&temp, self.to_string()).map_err(UnzipError::file("write metrics", &temp))?;
+        fs::rename(&temp, path).map_err(UnzipError::file("write metrics", path))
+    }
+}
+
+/// Write one metric with its `# HELP` and `# TYPE` lines
+fn metric(
+    f: &mut fmt::Formatter<'_>,
+    name: &str,
+    kind: &str,
+    help: &str,
+    value: impl fmt::Display,
+) -> fmt::Result {
+    writeln!(f, "# HELP {} {}", name, help)?;
+    writeln!(f, "# TYPE {} {}", name, kind)?;
+    writeln!(f, "{} {}", name, value)
+}
+
+impl fmt::Display for RunMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let finished = self.finished.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default();
+        metric(
+            f,
+            "unzip_bytes_extracted_total",
+            "counter",
+            "Bytes written to extracted files, after decompression.",
+            self.bytes,
+        )?;
+        metric(f, "unzip_entries_extracted_total", "counter", "Files extracted.", self.files)?;
+        metric(f, "unzip_entries_skipped_total", "counter", "Entries skipped.", self.skipped)?;
+        metric(
+            f,
+            "unzip_warnings_total",
+            "counter",
+            "Warnings while extracting.",
+            self.warnings,
+        )?;
+        metric(f, "unzip_archives_total", "counter", "Archives processed.", self.archives)?;
+        metric(f, "unzip_errors_total", "counter", "Archives that failed.", self.errors)?;
+        metric(
+            f,
+            "unzip_duration_seconds",
+            "gauge",
+            "Duration of the run.",
+            self.duration.as_secs_f64(),
+        )?;
+        metric(
+            f,
+            "unzip_throughput_bytes_per_second",
+            "gauge",
+            "Bytes extracted per second over the run.",
+            self.throughput().round(),
+        )?;
+        metric(
+            f,
+            "unzip_last_run_timestamp_seconds",
+            "gauge",
+            "Unix time the run finished.",
+            finished.as_secs(),
+        )?;
+        metric(
+            f,
+            "unzip_last_run_success",
+            "gauge",
+            "Whether every archive of the run succeeded.",
+            u8::from(self.errors == 0),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_textfile() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("unzip.prom");
+        let metrics = RunMetrics {
+            bytes: 1500,
+            files: 3,
+            skipped: 1,
+            archives: 2,
+            errors: 1,
+            duration: Duration::from_millis(1500),
+            finished: SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+            ..RunMetrics::default()
+        };
+        metrics.write_textfile(&path).unwrap();
+
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with(
+            "# HELP unzip_bytes_extracted_total Bytes written to extracted files, after \
+             decompression.\n# TYPE unzip_bytes_extracted_total counter\n\
+             unzip_bytes_extracted_total 1500\n"
+        ));
+        for line in [
+            "unzip_entries_extracted_total 3",
+            "unzip_entries_skipped_total 1",
+            "unzip_warnings_total 0",
+            "unzip_archives_total 2",
+            "unzip_errors_total 1",
+            "unzip_duration_seconds 1.5",
+            "unzip_throughput_bytes_per_second 1000",
+            "unzip_last_run_timestamp_seconds 1700000000",
+            "unzip_last_run_success 0",
+        ] {
+            assert!(text.lines().any(|l| l == line), "missing {line}");
+        }
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,570 @@
+//! Split archives: byte-concatenated pieces (`.zip.001`, `.partNN.zip`) and PKWARE
+//! split sets (`.z01` ... `.zip`)
+//!
+//! Many tools split a large archive by simply cutting it into numbered pieces:
+//! `photos.zip.001`, `photos.zip.002`, ... or `photos.part1.zip`, `photos.part2.zip`.
+//! The pieces carry no headers of their own, so joining them back together yields
+//! the original archive.
+//!
+//! PKWARE split archives, as written by `zip -s`, WinZip and 7-Zip, name their
+//! volumes `photos.z01`, `photos.z02`, ... with the last one `photos.zip`. Joined,
+//! they hold the entries in order too, but the central directory locates each entry
+//! by volume number and offset within that volume, which the zip crate does not
+//! support. When the end of central directory record says the archive spans
+//! volumes, [`ConcatReader`] serves a central directory rewritten with offsets into
+//! the joined stream in place of the original one.
+//!
+//! [`find_parts`] recognizes all three naming schemes from any one piece, and
+//! [`ConcatReader`] presents the pieces as one seekable stream, so the rest of the
+//! crate reads them like a single file.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::path::Path;
+//! use zip::ZipArchive;
+//! use unzip::multipart::{ConcatReader, find_parts};
+//!
+//! if let Some(parts) = find_parts(Path::new("photos.zip.001")) {
+//!     let archive = ZipArchive::new(ConcatReader::open(&parts)?)?;
+//!     println!("{} entries in {} parts", archive.len(), parts.len());
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::records::{
+    CENTRAL_HEADER_LEN, CENTRAL_SIGNATURE, EOCD_LEN, EOCD_SIGNATURE, ZIP64_EOCD_LEN,
+    ZIP64_EOCD_SIGNATURE, ZIP64_LOCATOR_LEN, ZIP64_LOCATOR_SIGNATURE, u16_at, u32_at, u64_at,
+};
+use crate::salvage::{ZIP64_EXTRA_ID, ZIP64_MARKER, end_records, extra_fields};
+
+/// How the pieces of a split archive are named
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PartNaming {
+    /// Everything before the part number (`photos.zip.` or `photos.part`)
+    prefix: String,
+    /// Everything after the part number (empty or `.zip`)
+    suffix: String,
+    /// Digits in the part number, for zero padding
+    width: usize,
+}
+
+impl PartNaming {
+    /// Recognize `NAME.zip.NNN` and `NAME.partNN.zip`
+    fn parse(file_name: &str) -> Option<Self> {
+        let lower = file_name.to_ascii_lowercase();
+        if let Some(dot) = lower.rfind('.') {
+            let digits = &lower[dot + 1..];
+            if is_part_number(digits) && lower[..dot].ends_with(".zip") {
+                return Some(Self {
+                    prefix: file_name[..=dot].to_string(),
+                    suffix: String::new(),
+                    width: digits.len(),
+                });
+            }
+        }
+        let stem = lower.strip_suffix(".zip")?;
+        let part = stem.rfind(".part")?;
+        let digits = &stem[part + ".part".len()..];
+        if !is_part_number(digits) {
+            return None;
+        }
+        Some(Self {
+            prefix: file_name[..part + ".part".len()].to_string(),
+            suffix: file_name[stem.len()..].to_string(),
+            width: digits.len(),
+        })
+    }
+
+    fn name(&self, number: usize) -> String {
+        format!("{}{:0width$}{}", self.prefix, number, self.suffix, width = self.width)
+    }
+}
+
+fn is_part_number(digits: &str) -> bool {
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Find all pieces of a split archive
+///
+/// `path` may name any piece, or a `NAME.zip` that does not exist next to a
+/// `NAME.zip.001`. Pieces are numbered from 1 (or 0 when a piece 0 exists) and
+/// collected until the first gap. The volumes of a PKWARE split set are found from
+/// `NAME.zip` or any `NAME.zNN`, and end with `NAME.zip`.
+///
+/// Returns `None` unless at least two pieces exist, so single archives that merely
+/// look numbered are opened as usual.
+pub fn find_parts(path: &Path) -> Option<Vec<PathBuf>> {
+    let file_name = path.file_name()?.to_str()?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    if let Some(volumes) = find_volumes(dir, file_name) {
+        return Some(volumes);
+    }
+
+    let naming = match PartNaming::parse(file_name) {
+        Some(naming) => naming,
+        None if !path.exists() && file_name.to_ascii_lowercase().ends_with(".zip") => {
+            PartNaming { prefix: format!("{}.", file_name), suffix: String::new(), width: 3 }
+        },
+        None => return None,
+    };
+
+    let first = if dir.join(naming.name(0)).is_file() {
+        0
+    } else {
+        1
+    };
+    let parts: Vec<PathBuf> = (first..)
+        .map(|number| dir.join(naming.name(number)))
+        .take_while(|part| part.is_file())
+        .collect();
+    (parts.len() >= 2).then_some(parts)
+}
+
+/// Find the volumes `NAME.z01`, `NAME.z02`, ... and `NAME.zip` of a PKWARE split
+/// archive from the name of any of them
+fn find_volumes(dir: &Path, file_name: &str) -> Option<Vec<PathBuf>> {
+    let (stem, extension) = file_name.rsplit_once('.')?;
+    let lower = extension.to_ascii_lowercase();
+    let numbered = lower.len() >= 3 && lower.starts_with('z') && is_part_number(&lower[1..]);
+    if lower != "zip" && !numbered {
+        return None;
+    }
+    // Keep the case of the extension given, as in `SETUP.Z01` and `SETUP.ZIP`
+    let (z, zip) = if extension.starts_with('Z') {
+        ("Z", "ZIP")
+    } else {
+        ("z", "zip")
+    };
+
+    let mut volumes: Vec<PathBuf> = (1..)
+        .map(|number| dir.join(format!("{}.{}{:02}", stem, z, number)))
+        .take_while(|volume| volume.is_file())
+        .collect();
+    let last = dir.join(format!("{}.{}", stem, zip));
+    if volumes.is_empty() || !last.is_file() {
+        return None;
+    }
+    volumes.push(last);
+    Some(volumes)
+}
+
+/// One piece of a split archive and where it sits in the joined stream
+#[derive(Debug)]
+struct Part {
+    file: File,
+    start: u64,
+    len: u64,
+}
+
+/// Central directory of a PKWARE split archive, rewritten to locate entries in the
+/// joined stream and served from where the original one starts
+#[derive(Debug)]
+struct Directory {
+    start: u64,
+    bytes: Vec<u8>,
+}
+
+/// `Read + Seek` over pieces of a split archive as if they were one file
+#[derive(Debug)]
+pub struct ConcatReader {
+    parts: Vec<Part>,
+    directory: Option<Directory>,
+    len: u64,
+    pos: u64,
+}
+
+impl ConcatReader {
+    /// Open the pieces in order
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a piece cannot be opened or its size read, or if the
+    /// pieces are volumes of a PKWARE split archive that are incomplete or whose
+    /// central directory is invalid
+    pub fn open(paths: &[PathBuf]) -> io::Result<Self> {
+        let mut parts = Vec::with_capacity(paths.len());
+        let mut start = 0;
+        for path in paths {
+            let file = File::open(path)?;
+            let len = file.metadata()?.len();
+            parts.push(Part { file, start, len });
+            start += len;
+        }
+        let mut reader = Self { parts, directory: None, len: start, pos: 0 };
+        if let Some(directory) = reader.spanned_directory()? {
+            reader.len = directory.start + directory.bytes.len() as u64;
+            reader.directory = Some(directory);
+        }
+        reader.pos = 0;
+        Ok(reader)
+    }
+
+    /// Length of the joined archive
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether the pieces are all empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl ConcatReader {
+    /// The rewritten central directory, if the pieces are the volumes of a PKWARE
+    /// split archive
+    fn spanned_directory(&mut self) -> io::Result<Option<Directory>> {
+        let Some(eocd_at) = self.end_record()? else {
+            return Ok(None);
+        };
+        let eocd = self.read_at(eocd_at, EOCD_LEN)?;
+        let field16 = |at| u16_at(&eocd, at).unwrap_or_default();
+        let field32 = |at| u32_at(&eocd, at).unwrap_or_default();
+        let mut volumes = u64::from(field16(4)) + 1;
+        let mut cd_disk = u32::from(field16(6));
+        let mut cd_size = u64::from(field32(12));
+        let mut cd_offset = u64::from(field32(16));
+
+        let zip64 = [4, 6, 8, 10].into_iter().any(|at| field16(at) == u16::MAX)
+            || [12, 16].into_iter().any(|at| field32(at) == ZIP64_MARKER);
+        let locator = match eocd_at.checked_sub(ZIP64_LOCATOR_LEN as u64) {
+            Some(at) if zip64 => Some(self.read_at(at, ZIP64_LOCATOR_LEN)?),
+            _ => None,
+        };
+        let locator = locator.filter(|locator| u32_at(locator, 0) == Some(ZIP64_LOCATOR_SIGNATURE));
+        if let Some(locator) = &locator {
+            volumes = u64::from(u32_at(locator, 16).unwrap_or_default());
+        }
+        if volumes <= 1 {
+            return Ok(None);
+        }
+        if self.parts.len() as u64 != volumes {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "split archive spans {} volumes, but {} were found",
+                    volumes,
+                    self.parts.len()
+                ),
+            ));
+        }
+        if let Some(locator) = &locator {
+            let record_disk = u32_at(locator, 4).unwrap_or_default();
+            let record_offset = u64_at(locator, 8).unwrap_or_default();
+            let record =
+                self.read_at(self.volume_start(record_disk)? + record_offset, ZIP64_EOCD_LEN)?;
+            if u32_at(&record, 0) != Some(ZIP64_EOCD_SIGNATURE) {
+                return Err(invalid_directory());
+            }
+            cd_disk = u32_at(&record, 20).unwrap_or_default();
+            cd_size = u64_at(&record, 40).unwrap_or_default();
+            cd_offset = u64_at(&record, 48).unwrap_or_default();
+        }
+
+        let start = self.volume_start(cd_disk)? + cd_offset;
+        let cd_len = usize::try_from(cd_size).map_err(|_| invalid_directory())?;
+        let cd = self.read_at(start, cd_len)?;
+        let mut bytes = Vec::with_capacity(cd.len());
+        let (mut at, mut count) = (0, 0);
+        while at < cd.len() {
+            at += self.rewrite_header(&cd[at..], &mut bytes)?;
+            count += 1;
+        }
+        end_records(&mut bytes, count, start);
+        Ok(Some(Directory { start, bytes }))
+    }
+
+    /// Append the central directory file header at the start of `record` to `out`,
+    /// located by its offset in the joined stream; returns the length of the header
+    fn rewrite_header(&self, record: &[u8], out: &mut Vec<u8>) -> io::Result<usize> {
+        if u32_at(record, 0) != Some(CENTRAL_SIGNATURE) {
+            return Err(invalid_directory());
+        }
+        let field16 = |at| u16_at(record, at).ok_or_else(invalid_directory);
+        let field32 = |at| u32_at(record, at).ok_or_else(invalid_directory);
+        let name_len = usize::from(field16(28)?);
+        let extra_len = usize::from(field16(30)?);
+        let comment_len = usize::from(field16(32)?);
+        let extra_start = CENTRAL_HEADER_LEN + name_len;
+        let len = extra_start + extra_len + comment_len;
+        if record.len() < len {
+            return Err(invalid_directory());
+        }
+        let extra = &record[extra_start..extra_start + extra_len];
+
+        // The ZIP64 field holds, in this order, each value whose own field is maxed out
+        let zip64 = extra_fields(extra).find(|(id, _)| *id == ZIP64_EXTRA_ID).map(|(_, f)| f);
+        let zip64 = zip64.unwrap_or_default();
+        let mut kept = Vec::new();
+        let mut at = 0;
+        for field in [24, 20] {
+            if field32(field)? == ZIP64_MARKER {
+                kept.extend_from_slice(zip64.get(at..at + 8).ok_or_else(invalid_directory)?);
+                at += 8;
+            }
+        }
+        let mut offset = u64::from(field32(42)?);
+        if offset == u64::from(ZIP64_MARKER) {
+            offset = u64_at(zip64, at).ok_or_else(invalid_directory)?;
+            at += 8;
+        }
+        let disk = match field16(34)? {
+            u16::MAX => u32_at(zip64, at).ok_or_else(invalid_directory)?,
+            disk => u32::from(disk),
+        };
+
+        let offset = self.volume_start(disk)? + offset;
+        let offset32 = match u32::try_from(offset) {
+            Ok(offset) if offset != ZIP64_MARKER => offset,
+            _ => {
+                kept.extend(offset.to_le_bytes());
+                ZIP64_MARKER
+            },
+        };
+        let mut new_extra = Vec::new();
+        if !kept.is_empty() {
+            new_extra.extend(ZIP64_EXTRA_ID.to_le_bytes());
+            new_extra.extend((kept.len() as u16).to_le_bytes());
+            new_extra.extend(kept);
+        }
+        for (id, field) in extra_fields(extra).filter(|(id, _)| *id != ZIP64_EXTRA_ID) {
+            new_extra.extend(id.to_le_bytes());
+            new_extra.extend((field.len() as u16).to_le_bytes());
+            new_extra.extend(field);
+        }
+
+        out.extend(&record[..30]);
+        out.extend((new_extra.len() as u16).to_le_bytes());
+        out.extend(&record[32..34]); // comment length
+        out.extend(0u16.to_le_bytes()); // disk number
+        out.extend(&record[36..42]); // attributes
+        out.extend(offset32.to_le_bytes());
+        out.extend(&record[CENTRAL_HEADER_LEN..extra_start]);
+        out.extend(new_extra);
+        out.extend(&record[extra_start + extra_len..len]);
+        Ok(len)
+    }
+
+    /// Position of the end of central directory record that ends the last piece,
+    /// where PKWARE split archives keep it
+    fn end_record(&mut self) -> io::Result<Option<u64>> {
+        let Some(last) = self.parts.last() else {
+            return Ok(None);
+        };
+        let end = last.start + last.len;
+        let tail_start = end.saturating_sub((EOCD_LEN + usize::from(u16::MAX)) as u64);
+        let tail_start = tail_start.max(last.start);
+        let tail = self.read_at(tail_start, (end - tail_start) as usize)?;
+        let found = (0..tail.len().saturating_sub(EOCD_LEN - 1)).rev().find(|&at| {
+            u32_at(&tail, at) == Some(EOCD_SIGNATURE)
+                && u16_at(&tail, at + 20)
+                    .is_some_and(|comment| at + EOCD_LEN + usize::from(comment) == tail.len())
+        });
+        Ok(found.map(|at| tail_start + at as u64))
+    }
+
+    /// Where volume `disk` starts in the joined stream
+    fn volume_start(&self, disk: u32) -> io::Result<u64> {
+        let part = self.parts.get(disk as usize).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("split archive refers to missing volume {}", u64::from(disk) + 1),
+            )
+        })?;
+        Ok(part.start)
+    }
+
+    fn read_at(&mut self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let mut bytes = vec![0u8; len];
+        self.seek(SeekFrom::Start(offset))?;
+        self.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+fn invalid_directory() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "invalid central directory in split archive")
+}
+
+impl Read for ConcatReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.pos >= self.len {
+            return Ok(0);
+        }
+        if let Some(directory) = &self.directory
+            && self.pos >= directory.start
+        {
+            let from = &directory.bytes[(self.pos - directory.start) as usize..];
+            let n = from.len().min(buf.len());
+            buf[..n].copy_from_slice(&from[..n]);
+            self.pos += n as u64;
+            return Ok(n);
+        }
+        let data_end = self.directory.as_ref().map_or(self.len, |directory| directory.start);
+        // Last piece starting at or before the position; empty pieces are skipped over
+        let index = self.parts.partition_point(|part| part.start <= self.pos) - 1;
+        let part = &mut self.parts[index];
+        let offset = self.pos - part.start;
+        let available = (part.len - offset).min(data_end - self.pos).min(buf.len() as u64) as usize;
+
+        part.file.seek(SeekFrom::Start(offset))?;
+        let n = part.file.read(&mut buf[..available])?;
+        if n == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "split archive piece shrank while reading",
+            ));
+        }
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for ConcatReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.len.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+        };
+        let target = target.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "seek before start of split archive")
+        })?;
+        self.pos = target;
+        Ok(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::{Cursor, Write};
+    use zip::write::SimpleFileOptions;
+    use zip::{ZipArchive, ZipWriter};
+
+    fn split_archive(dir: &Path, names: &[&str]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for i in 0..20 {
+            zip.start_file(format!("file{}.txt", i), SimpleFileOptions::default()).unwrap();
+            zip.write_all(format!("content {}", i).repeat(50).as_bytes()).unwrap();
+        }
+        let bytes = zip.finish().unwrap().into_inner();
+        let chunk = bytes.len().div_ceil(names.len());
+        for (name, piece) in names.iter().zip(bytes.chunks(chunk)) {
+            fs::write(dir.join(name), piece).unwrap();
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_part_naming() {
+        let naming = PartNaming::parse("Photos.ZIP.002").unwrap();
+        assert_eq!(naming.name(1), "Photos.ZIP.001");
+        let naming = PartNaming::parse("photos.part07.zip").unwrap();
+        assert_eq!(naming.name(10), "photos.part10.zip");
+        assert!(PartNaming::parse("photos.zip").is_none());
+        assert!(PartNaming::parse("photos.z01").is_none());
+        assert!(PartNaming::parse("report.2024.txt").is_none());
+    }
+
+    #[test]
+    fn test_find_parts_from_any_piece() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        split_archive(dir, &["a.zip.001", "a.zip.002", "a.zip.003"]);
+
+        let expected: Vec<PathBuf> =
+            ["a.zip.001", "a.zip.002", "a.zip.003"].iter().map(|n| dir.join(n)).collect();
+        assert_eq!(find_parts(&dir.join("a.zip.002")), Some(expected.clone()));
+        assert_eq!(find_parts(&dir.join("a.zip")), Some(expected));
+
+        fs::write(dir.join("single.zip.001"), b"PK").unwrap();
+        assert_eq!(find_parts(&dir.join("single.zip.001")), None);
+    }
+
+    #[test]
+    fn test_concat_reader_reads_across_pieces() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        let names = ["b.part1.zip", "b.part2.zip", "b.part3.zip"];
+        let bytes = split_archive(dir, &names);
+
+        let parts = find_parts(&dir.join("b.part1.zip")).unwrap();
+        let mut reader = ConcatReader::open(&parts).unwrap();
+        assert_eq!(reader.len(), bytes.len() as u64);
+
+        let mut joined = Vec::new();
+        reader.read_to_end(&mut joined).unwrap();
+        assert_eq!(joined, bytes);
+
+        let mut archive = ZipArchive::new(ConcatReader::open(&parts).unwrap()).unwrap();
+        let mut content = String::new();
+        archive.by_name("file19.txt").unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!(content, "content 19".repeat(50));
+    }
+
+    /// Cut `bytes` into PKWARE split volumes of `volume` bytes, as `zip -s` would
+    fn spanned_volumes(bytes: &[u8], volume: usize) -> Vec<Vec<u8>> {
+        let mut data = b"PK\x07\x08".to_vec();
+        data.extend(bytes);
+        let locate = |pos: usize| ((pos / volume) as u16, (pos % volume) as u32);
+        let eocd = data.len() - 22;
+        let cd_offset = u32_at(&data, eocd + 16).unwrap() as usize + 4;
+        let mut at = cd_offset;
+        while at < eocd {
+            let (disk, offset) = locate(u32_at(&data, at + 42).unwrap() as usize + 4);
+            data[at + 34..at + 36].copy_from_slice(&disk.to_le_bytes());
+            data[at + 42..at + 46].copy_from_slice(&offset.to_le_bytes());
+            let variable: usize =
+                [28, 30, 32].map(|f| usize::from(u16_at(&data, at + f).unwrap())).iter().sum();
+            at += CENTRAL_HEADER_LEN + variable;
+        }
+        let (cd_disk, cd_offset) = locate(cd_offset);
+        let last = ((data.len() - 1) / volume) as u16;
+        data[eocd + 4..eocd + 6].copy_from_slice(&last.to_le_bytes());
+        data[eocd + 6..eocd + 8].copy_from_slice(&cd_disk.to_le_bytes());
+        data[eocd + 16..eocd + 20].copy_from_slice(&cd_offset.to_le_bytes());
+        data.chunks(volume).map(<[u8]>::to_vec).collect()
+    }
+
+    #[test]
+    fn test_spanned_archive() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        let bytes = split_archive(dir, &["unused.zip"]);
+        let volumes = spanned_volumes(&bytes, 400);
+        let last = volumes.len() - 1;
+        for (number, volume) in volumes.iter().enumerate() {
+            let name = if number == last {
+                "c.zip".to_string()
+            } else {
+                format!("c.z{:02}", number + 1)
+            };
+            fs::write(dir.join(name), volume).unwrap();
+        }
+
+        let parts = find_parts(&dir.join("c.z02")).unwrap();
+        assert_eq!(parts.len(), volumes.len());
+        assert_eq!(find_parts(&dir.join("c.zip")), Some(parts.clone()));
+        assert_eq!(parts.last(), Some(&dir.join("c.zip")));
+
+        let mut archive = ZipArchive::new(ConcatReader::open(&parts).unwrap()).unwrap();
+        assert_eq!(archive.len(), 20);
+        for name in ["file0.txt", "file19.txt"] {
+            let mut content = String::new();
+            archive.by_name(name).unwrap().read_to_string(&mut content).unwrap();
+            assert_eq!(content, name.replace("file", "content ").replace(".txt", "").repeat(50));
+        }
+
+        fs::remove_file(dir.join("c.z02")).unwrap();
+        let parts = find_parts(&dir.join("c.zip")).unwrap();
+        let error = ConcatReader::open(&parts).unwrap_err();
+        assert!(error.to_string().contains("volumes, but 2 were found"), "{}", error);
+    }
+}
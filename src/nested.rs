@@ -0,0 +1,151 @@
+//! Extraction of archives inside archives (`--recurse-archives`)
+//!
+//! Vendor drops often ship zips of zips. With `--recurse-archives=N`, every `.zip`
+//! file an extraction writes is extracted in turn into a directory named after it
+//! (`drivers/net.zip` into `drivers/net/`), level by level up to `N` levels below
+//! the archive given on the command line. The archive files themselves are kept.
+//!
+//! Nested archives are a classic decompression bomb, so two guards apply:
+//!
+//! - Depth: at most [`MAX_RECURSION_DEPTH`] levels; archives found below the
+//!   requested depth are reported and left as they are
+//! - Size: all nested archives together may write what is left of
+//!   `--max-output-size`, or without it [`NESTED_EXPANSION`] times the size of the
+//!   archives found in the first level ([`NestedBudget`])
+//!
+//! `--max-ratio` and `--max-entries` apply to every nested archive, while patterns,
+//! exclusions and `--expect-*` only select entries of the outermost one.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::path::Path;
+//! use unzip::nested::{is_archive_path, nested_dir, parse_recursion_depth};
+//!
+//! assert_eq!(parse_recursion_depth("2"), Ok(2));
+//! assert!(is_archive_path(Path::new("drivers/NET.ZIP")));
+//! assert_eq!(nested_dir(Path::new("drivers/net.zip")), Path::new("drivers/net"));
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use crate::error::UnzipError;
+use crate::limits::LimitExceeded;
+
+/// Deepest nesting `--recurse-archives` accepts
+pub const MAX_RECURSION_DEPTH: u32 = 16;
+
+/// How many times their own size nested archives may expand to together, unless
+/// `--max-output-size` sets the limit
+pub const NESTED_EXPANSION: u64 = 100;
+
+/// Parse a `--recurse-archives` depth, from 1 to [`MAX_RECURSION_DEPTH`]
+///
+/// # Errors
+///
+/// Returns a message suitable for clap if the depth is not a number in range
+pub fn parse_recursion_depth(value: &str) -> Result<u32, String> {
+    match value.trim().parse::<u32>() {
+        Ok(depth @ 1..=MAX_RECURSION_DEPTH) => Ok(depth),
+        _ => Err(format!(
+            "invalid recursion depth: {} (expected 1 to {})",
+            value, MAX_RECURSION_DEPTH
+        )),
+    }
+}
+
+/// Whether an extracted file is an archive to recurse into, judged by its `.zip`
+/// extension in any case
+pub fn is_archive_path(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+}
+
+/// Directory a nested archive is extracted into: its path without the extension
+pub fn nested_dir(path: &Path) -> PathBuf {
+    path.with_extension("")
+}
+
+/// Output still allowed to the nested archives of one extraction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NestedBudget {
+    /// Total output allowed to all nested archives
+    limit: u64,
+    /// Whether `limit` comes from `--max-output-size` rather than the default
+    explicit: bool,
+    /// Output written by nested archives so far
+    spent: u64,
+}
+
+impl NestedBudget {
+    /// Budget for the nested archives found by an extraction that wrote `written`
+    /// bytes
+    ///
+    /// # Arguments
+    ///
+    /// * `max_output_size` - `--max-output-size`, which covers all levels together
+    /// * `written` - Bytes the outermost extraction wrote
+    /// * `archive_bytes` - Total size of the archives found in the first level
+    pub fn new(max_output_size: Option<u64>, written: u64, archive_bytes: u64) -> Self {
+        match max_output_size {
+            Some(limit) => Self { limit: limit.saturating_sub(written), explicit: true, spent: 0 },
+            None => Self {
+                limit: archive_bytes.saturating_mul(NESTED_EXPANSION),
+                explicit: false,
+                spent: 0,
+            },
+        }
+    }
+
+    /// Output the next nested archive may write
+    pub fn remaining(&self) -> u64 {
+        self.limit.saturating_sub(self.spent)
+    }
+
+    /// Count the output of a nested archive against the budget
+    pub fn spend(&mut self, bytes: u64) {
+        self.spent = self.spent.saturating_add(bytes);
+    }
+
+    /// Replace the output size error of a nested extraction, which names only what
+    /// was left for it, with one naming the limit the user can change
+    pub fn explain(&self, error: UnzipError, max_output_size: Option<u64>) -> UnzipError {
+        match (error, max_output_size) {
+            (UnzipError::Limit(LimitExceeded::OutputSize { .. }), Some(limit)) if self.explicit => {
+                LimitExceeded::OutputSize { limit }.into()
+            },
+            (UnzipError::Limit(LimitExceeded::OutputSize { .. }), _) => {
+                LimitExceeded::NestedOutput { limit: self.limit }.into()
+            },
+            (error, _) => error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_recursion_depth() {
+        assert_eq!(parse_recursion_depth("1"), Ok(1));
+        assert_eq!(parse_recursion_depth("16"), Ok(16));
+        assert!(parse_recursion_depth("0").is_err());
+        assert!(parse_recursion_depth("17").is_err());
+        assert!(parse_recursion_depth("deep").is_err());
+    }
+
+    #[test]
+    fn test_budget_shares_limit_across_archives() {
+        let mut budget = NestedBudget::new(Some(1000), 400, 50);
+        assert_eq!(budget.remaining(), 600);
+        budget.spend(250);
+        assert_eq!(budget.remaining(), 350);
+        let error = budget.explain(LimitExceeded::OutputSize { limit: 350 }.into(), Some(1000));
+        assert!(matches!(error, UnzipError::Limit(LimitExceeded::OutputSize { limit: 1000 })));
+
+        let budget = NestedBudget::new(None, 400, 50);
+        assert_eq!(budget.remaining(), 50 * NESTED_EXPANSION);
+        let error = budget.explain(LimitExceeded::OutputSize { limit: 1 }.into(), None);
+        assert!(matches!(error, UnzipError::Limit(LimitExceeded::NestedOutput { limit: 5000 })));
+    }
+}
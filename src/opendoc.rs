@@ -0,0 +1,411 @@
+//! Structural checks of document containers (`--opendoc-check`)
+//!
+//! EPUB books, OpenDocument files (`.odt`, `.ods`, ...) and Office Open XML files
+//! (`.docx`, `.xlsx`, ...) are ZIP archives with conventions of their own, which
+//! publishing pipelines check before unpacking them. With `--opendoc-check`, the
+//! container type is detected and its conventions are verified before anything
+//! is written:
+//!
+//! - EPUB and OpenDocument: the `mimetype` entry comes first, is stored
+//!   uncompressed without an extra field, and holds the media type alone
+//! - EPUB: `META-INF/container.xml` exists and the package documents it names
+//!   are in the archive
+//! - OpenDocument: `META-INF/manifest.xml` exists and every file it lists is in
+//!   the archive
+//! - Office Open XML: `[Content_Types].xml` and `_rels/.rels` exist and the main
+//!   document part `_rels/.rels` points to is in the archive
+//!
+//! A container breaking them fails with [`InvalidContainer`], listing every
+//! problem. The check reads only the few parts it needs; combine it with
+//! `--dry-run` to validate without extracting. Options that rewrite entry paths
+//! (`-j`, `-L`, `--strip-components`, `--transform`) cannot be combined with it,
+//! so the extracted tree keeps the layout the container's own paths refer to.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use zip::ZipArchive;
+//! use unzip::opendoc::{Container, check_container};
+//!
+//! let mut archive = ZipArchive::new(File::open("book.epub")?)?;
+//! assert_eq!(check_container(&mut archive)?, Container::Epub);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::fmt;
+use std::io::{Read, Seek};
+use zip::{CompressionMethod, ZipArchive};
+
+use crate::error::{Result, UnzipError};
+
+/// Name of the entry holding the media type of EPUB and OpenDocument containers
+const MIMETYPE: &str = "mimetype";
+const EPUB_MEDIA_TYPE: &str = "application/epub+zip";
+const OPENDOCUMENT_MEDIA_TYPE_PREFIX: &str = "application/vnd.oasis.opendocument.";
+const CONTENT_TYPES: &str = "[Content_Types].xml";
+/// Most bytes read from a part the check parses; manifests are far smaller
+const PART_LIMIT: u64 = 4 << 20;
+
+/// Kind of document container
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Container {
+    /// EPUB publication (`application/epub+zip`)
+    Epub,
+    /// OpenDocument file (`application/vnd.oasis.opendocument.*`)
+    OpenDocument,
+    /// Office Open XML package, identified by `[Content_Types].xml`
+    OfficeOpenXml,
+}
+
+impl fmt::Display for Container {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Epub => "EPUB",
+            Self::OpenDocument => "OpenDocument",
+            Self::OfficeOpenXml => "Office Open XML",
+        })
+    }
+}
+
+/// An archive that is not a container, or breaks its container's conventions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidContainer {
+    /// The detected container, `None` if the archive is not one
+    pub container: Option<Container>,
+    /// What is wrong, one sentence each
+    pub problems: Vec<String>,
+}
+
+impl fmt::Display for InvalidContainer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.container {
+            Some(container) => write!(f, "invalid {} container (--opendoc-check):", container)?,
+            None => write!(
+                f,
+                "not an EPUB, OpenDocument or Office Open XML container (--opendoc-check):"
+            )?,
+        }
+        for problem in &self.problems {
+            write!(f, "\n  {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for InvalidContainer {}
+
+/// Detect the container type of `archive` and check its conventions
+///
+/// # Errors
+///
+/// Returns an [`InvalidContainer`] error listing what is wrong, or an error if
+/// the archive cannot be read
+pub fn check_container<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<Container> {
+    let mut problems = Vec::new();
+    let container = if let Some(index) = archive.index_for_name(MIMETYPE) {
+        let media_type = read_part(archive, MIMETYPE)?.unwrap_or_default();
+        let container = if media_type.trim() == EPUB_MEDIA_TYPE {
+            Container::Epub
+        } else if media_type.trim().starts_with(OPENDOCUMENT_MEDIA_TYPE_PREFIX) {
+            Container::OpenDocument
+        } else {
+            return Err(not_a_container(format!(
+                "unknown media type {:?} in mimetype",
+                media_type
+            )));
+        };
+        check_mimetype(archive, index, &media_type, &mut problems)?;
+        container
+    } else if archive.index_for_name(CONTENT_TYPES).is_some() {
+        Container::OfficeOpenXml
+    } else {
+        return Err(not_a_container(format!(
+            "neither {} nor {} is present",
+            MIMETYPE, CONTENT_TYPES
+        )));
+    };
+
+    match container {
+        Container::Epub => check_epub(archive, &mut problems)?,
+        Container::OpenDocument => check_opendocument(archive, &mut problems)?,
+        Container::OfficeOpenXml => check_office_open_xml(archive, &mut problems)?,
+    }
+    if problems.is_empty() {
+        Ok(container)
+    } else {
+        Err(InvalidContainer { container: Some(container), problems }.into())
+    }
+}
+
+fn not_a_container(problem: String) -> UnzipError {
+    InvalidContainer { container: None, problems: vec![problem] }.into()
+}
+
+/// Check that the `mimetype` entry at `index` is first, stored, without an extra
+/// field in its local header, and holds `media_type` without surrounding space
+fn check_mimetype<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    index: usize,
+    media_type: &str,
+    problems: &mut Vec<String>,
+) -> Result<()> {
+    let file = archive.by_index_raw(index)?;
+    if index != 0 {
+        problems.push(format!("mimetype is entry {}, not the first", index + 1));
+    }
+    if file.compression() != CompressionMethod::Stored {
+        problems.push(format!("mimetype is compressed ({}), not stored", file.compression()));
+    }
+    if file.encrypted() {
+        problems.push("mimetype is encrypted".to_string());
+    }
+    // Local header: 30 fixed bytes, the name, then the extra field
+    let extra = file.data_start() - file.header_start() - 30 - file.name_raw().len() as u64;
+    if extra > 0 {
+        problems.push(format!("mimetype has a {}-byte extra field in its local header", extra));
+    }
+    if media_type.trim() != media_type {
+        problems.push("mimetype holds white space around the media type".to_string());
+    }
+    Ok(())
+}
+
+/// Check that `META-INF/container.xml` names package documents that exist
+fn check_epub<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    problems: &mut Vec<String>,
+) -> Result<()> {
+    const CONTAINER_XML: &str = "META-INF/container.xml";
+    let Some(xml) = read_part(archive, CONTAINER_XML)? else {
+        problems.push(format!("{} is missing", CONTAINER_XML));
+        return Ok(());
+    };
+    let roots: Vec<String> =
+        tags(&xml, "rootfile").filter_map(|tag| attribute(tag, "full-path")).collect();
+    if roots.is_empty() {
+        problems.push(format!("{} names no rootfile", CONTAINER_XML));
+    }
+    for root in roots {
+        require_part(archive, CONTAINER_XML, &root, problems);
+    }
+    Ok(())
+}
+
+/// Check that `META-INF/manifest.xml` exists and lists only files that exist
+fn check_opendocument<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    problems: &mut Vec<String>,
+) -> Result<()> {
+    const MANIFEST_XML: &str = "META-INF/manifest.xml";
+    let Some(xml) = read_part(archive, MANIFEST_XML)? else {
+        problems.push(format!("{} is missing", MANIFEST_XML));
+        return Ok(());
+    };
+    // Directories, the package itself (`/`) among them, need no entry of their own
+    let files: Vec<String> = tags(&xml, "manifest:file-entry")
+        .filter_map(|tag| attribute(tag, "manifest:full-path"))
+        .filter(|path| !path.ends_with('/'))
+        .collect();
+    for file in files {
+        require_part(archive, MANIFEST_XML, &file, problems);
+    }
+    Ok(())
+}
+
+/// Check that `_rels/.rels` exists and its main document part does
+fn check_office_open_xml<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    problems: &mut Vec<String>,
+) -> Result<()> {
+    const RELS: &str = "_rels/.rels";
+    let Some(xml) = read_part(archive, RELS)? else {
+        problems.push(format!("{} is missing", RELS));
+        return Ok(());
+    };
+    let targets: Vec<String> = tags(&xml, "Relationship")
+        .filter(|tag| attribute(tag, "Type").is_some_and(|kind| kind.ends_with("/officeDocument")))
+        .filter_map(|tag| attribute(tag, "Target"))
+        .collect();
+    if targets.is_empty() {
+        problems.push(format!("{} has no officeDocument relationship", RELS));
+    }
+    for target in targets {
+        require_part(archive, RELS, target.trim_start_matches('/'), problems);
+    }
+    Ok(())
+}
+
+/// Record a problem if `part`, referred to by `referrer`, is not in the archive
+fn require_part<R: Read + Seek>(
+    archive: &ZipArchive<R>,
+    referrer: &str,
+    part: &str,
+    problems: &mut Vec<String>,
+) {
+    if archive.index_for_name(part).is_none() {
+        problems.push(format!("{} refers to {}, which is missing", referrer, part));
+    }
+}
+
+/// Text of the entry `name`, `None` if there is none; at most [`PART_LIMIT`]
+/// bytes are read
+fn read_part<R: Read + Seek>(archive: &mut ZipArchive<R>, name: &str) -> Result<Option<String>> {
+    let Some(index) = archive.index_for_name(name) else {
+        return Ok(None);
+    };
+    let mut data = Vec::new();
+    archive
+        .by_index(index)?
+        .take(PART_LIMIT)
+        .read_to_end(&mut data)
+        .map_err(UnzipError::reading(name))?;
+    Ok(Some(String::from_utf8_lossy(&data).into_owned()))
+}
+
+/// Attributes of each `<element ...>` tag in `xml`
+///
+/// Good enough for the machine-written manifests checked here, which need no
+/// namespace resolution; comments and CDATA are not skipped.
+fn tags<'a>(xml: &'a str, element: &'a str) -> impl Iterator<Item = &'a str> {
+    xml.split('<').skip(1).filter_map(move |tag| {
+        let attributes = tag.strip_prefix(element)?;
+        let attributes = &attributes[..attributes.find('>').unwrap_or(attributes.len())];
+        attributes
+            .starts_with(|c: char| c.is_whitespace() || c == '/')
+            .then_some(attributes)
+    })
+}
+
+/// Value of the attribute `name` in the attributes of a tag, with the predefined
+/// entities decoded
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    let mut rest = attributes;
+    while let Some(at) = rest.find(name) {
+        let preceded = rest[..at].ends_with(char::is_whitespace);
+        rest = &rest[at + name.len()..];
+        let Some(value) = rest.trim_start().strip_prefix('=').map(str::trim_start) else {
+            continue;
+        };
+        let Some(quote) = value.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            continue;
+        };
+        let value = &value[1..];
+        let end = value.find(quote)?;
+        if preceded {
+            return Some(unescape(&value[..end]));
+        }
+    }
+    None
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::ZipWriter;
+    use zip::write::SimpleFileOptions;
+
+    fn archive(entries: &[(&str, &str, CompressionMethod)]) -> ZipArchive<Cursor<Vec<u8>>> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data, method) in entries {
+            zip.start_file(*name, SimpleFileOptions::default().compression_method(*method))
+                .unwrap();
+            zip.write_all(data.as_bytes()).unwrap();
+        }
+        ZipArchive::new(zip.finish().unwrap()).unwrap()
+    }
+
+    fn problems(archive: &mut ZipArchive<Cursor<Vec<u8>>>) -> Vec<String> {
+        match check_container(archive).unwrap_err() {
+            UnzipError::InvalidContainer(invalid) => invalid.problems,
+            error => panic!("unexpected error: {}", error),
+        }
+    }
+
+    const STORED: CompressionMethod = CompressionMethod::Stored;
+    const DEFLATED: CompressionMethod = CompressionMethod::Deflated;
+    const EPUB_CONTAINER: &str = r#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>"#;
+
+    #[test]
+    fn test_epub() {
+        let mut valid = archive(&[
+            ("mimetype", EPUB_MEDIA_TYPE, STORED),
+            ("META-INF/container.xml", EPUB_CONTAINER, DEFLATED),
+            ("OEBPS/content.opf", "<package/>", DEFLATED),
+        ]);
+        assert_eq!(check_container(&mut valid).unwrap(), Container::Epub);
+
+        let mut invalid = archive(&[
+            ("META-INF/container.xml", EPUB_CONTAINER, DEFLATED),
+            ("mimetype", "application/epub+zip\n", DEFLATED),
+        ]);
+        assert_eq!(
+            problems(&mut invalid),
+            [
+                "mimetype is entry 2, not the first",
+                "mimetype is compressed (Deflated), not stored",
+                "mimetype holds white space around the media type",
+                "META-INF/container.xml refers to OEBPS/content.opf, which is missing",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_opendocument() {
+        let manifest = r#"<manifest:manifest xmlns:manifest="urn:oasis:names:tc:opendocument:xmlns:manifest:1.0">
+ <manifest:file-entry manifest:full-path="/" manifest:media-type="application/vnd.oasis.opendocument.text"/>
+ <manifest:file-entry manifest:full-path="content.xml" manifest:media-type="text/xml"/>
+ <manifest:file-entry manifest:full-path="Pictures/a&amp;b.png" manifest:media-type="image/png"/>
+</manifest:manifest>"#;
+        let mut odt = archive(&[
+            ("mimetype", "application/vnd.oasis.opendocument.text", STORED),
+            ("content.xml", "<office:document-content/>", DEFLATED),
+            ("META-INF/manifest.xml", manifest, DEFLATED),
+        ]);
+        assert_eq!(
+            problems(&mut odt),
+            ["META-INF/manifest.xml refers to Pictures/a&b.png, which is missing"]
+        );
+    }
+
+    #[test]
+    fn test_office_open_xml() {
+        let rels = r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="word/document.xml"/>
+</Relationships>"#;
+        let mut docx = archive(&[
+            ("[Content_Types].xml", "<Types/>", DEFLATED),
+            ("_rels/.rels", rels, DEFLATED),
+            ("word/document.xml", "<w:document/>", DEFLATED),
+        ]);
+        assert_eq!(check_container(&mut docx).unwrap(), Container::OfficeOpenXml);
+
+        let mut broken = archive(&[("[Content_Types].xml", "<Types/>", DEFLATED)]);
+        assert_eq!(problems(&mut broken), ["_rels/.rels is missing"]);
+    }
+
+    #[test]
+    fn test_not_a_container() {
+        let mut plain = archive(&[("readme.txt", "hello", DEFLATED)]);
+        let error = check_container(&mut plain).unwrap_err();
+        assert!(error.to_string().starts_with("not an EPUB, OpenDocument or Office Open XML"));
+
+        let mut unknown = archive(&[("mimetype", "text/plain", STORED)]);
+        assert_eq!(problems(&mut unknown), ["unknown media type \"text/plain\" in mimetype"]);
+    }
+}
@@ -0,0 +1,952 @@
+//! Extraction options for library users
+//!
+//! [`ExtractOptions`] holds everything extraction needs to know, without the
+//! listing, zipinfo and other command-line-only fields of [`Args`]. Options are built
+//! with chained setters starting from [`ExtractOptions::new`]; the binary converts
+//! its parsed [`Args`] with [`ExtractOptions::try_from`].
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use zip::ZipArchive;
+//! use unzip::{ExtractEvent, ExtractOptions, OverwritePolicy, extract_archive};
+//!
+//! let mut archive = ZipArchive::new(File::open("archive.zip")?)?;
+//! let options = ExtractOptions::new()
+//!     .output_dir("out")
+//!     .overwrite(OverwritePolicy::Always)
+//!     .patterns(["src/**/*.rs"])
+//!     .threads(4)
+//!     .on_event(|event| {
+//!         if let ExtractEvent::FileDone { name, .. } = event {
+//!             println!("extracted {}", name);
+//!         }
+//!     });
+//! extract_archive(&mut archive, &options)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::args::Args;
+use crate::batch::subdir_for;
+use crate::cancel::CancelToken;
+use crate::charset::NameDecoder;
+use crate::checkpoint::CheckpointInterval;
+use crate::compat::Compat;
+use crate::dirstamp::OutputDirStamp;
+use crate::duplicates::DuplicatePolicy;
+use crate::error::{Result, UnzipError};
+use crate::events::{ExtractEvent, ExtractObserver};
+use crate::expect::Expectations;
+use crate::hooks::EntryHooks;
+use crate::implied_dirs::ImpliedDirTimes;
+use crate::limits::ExtractionLimits;
+use crate::linux::CachePolicy;
+use crate::members::read_members;
+use crate::multipart::find_parts;
+use crate::owner::Owner;
+use crate::recompress::OutputCompression;
+use crate::size_check::ExpectedSizes;
+use crate::status::StatusBoard;
+use crate::template::NameTemplate;
+use crate::textmode::TextMode;
+use crate::transform::Transform;
+use crate::utils::PatternMatcher;
+
+/// What to do when an extracted file already exists
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverwritePolicy {
+    /// Keep existing files and suggest `-o` (default)
+    #[default]
+    Skip,
+    /// Replace existing files (`-o`)
+    Always,
+    /// Keep existing files silently (`-n`)
+    Never,
+    /// Only replace existing files that are older than the entry (`-f`)
+    Freshen,
+    /// Replace older files and create missing ones (`-u`)
+    Update,
+    /// Replace existing files only if their content differs from the entry's
+    /// (`--if-changed`)
+    IfChanged,
+    /// Keep existing files and extract under a free name, `NAME (1).EXT`,
+    /// `NAME (2).EXT`, ... (`--auto-rename`)
+    Rename,
+}
+
+impl OverwritePolicy {
+    /// Whether an existing file may be replaced without asking
+    pub fn replaces_existing(self) -> bool {
+        matches!(self, Self::Always | Self::Freshen | Self::Update | Self::IfChanged)
+    }
+
+    /// Whether existing files are compared by modification time (`-f`, `-u`)
+    pub fn compares_times(self) -> bool {
+        matches!(self, Self::Freshen | Self::Update)
+    }
+}
+
+/// Observer shared between clones of [`ExtractOptions`]
+pub type SharedObserver = Arc<Mutex<dyn ExtractObserver + Send>>;
+
+/// Adapter that lets a closure observe raw events
+struct EventFn<F>(F);
+
+impl<F: FnMut(ExtractEvent)> ExtractObserver for EventFn<F> {
+    fn on_event(&mut self, event: &ExtractEvent) {
+        (self.0)(event.clone());
+    }
+}
+
+/// Options controlling how an archive is extracted
+#[derive(Clone)]
+pub struct ExtractOptions {
+    pub(crate) output_dir: PathBuf,
+    pub(crate) smart_dir: Option<PathBuf>,
+    pub(crate) stamp_output_dir: Option<OutputDirStamp>,
+    pub(crate) implied_dir_times: ImpliedDirTimes,
+    pub(crate) overwrite: OverwritePolicy,
+    pub(crate) patterns: Vec<String>,
+    pub(crate) exclude: Vec<String>,
+    pub(crate) members: Vec<String>,
+    pub(crate) member_index: Option<usize>,
+    pub(crate) duplicates: DuplicatePolicy,
+    pub(crate) case_insensitive: bool,
+    pub(crate) wild_stop_at_dir: bool,
+    pub(crate) junk_paths: bool,
+    pub(crate) strip_components: usize,
+    pub(crate) transforms: Vec<Transform>,
+    pub(crate) name_template: Option<NameTemplate>,
+    pub(crate) lowercase: bool,
+    pub(crate) merge_case_dirs: bool,
+    pub(crate) no_timestamps: u8,
+    pub(crate) quiet: u8,
+    pub(crate) names: NameDecoder,
+    pub(crate) text_mode: TextMode,
+    pub(crate) flatten_long_paths: bool,
+    pub(crate) follow_symlinks: bool,
+    pub(crate) heuristic_decode: bool,
+    pub(crate) dry_run: bool,
+    pub(crate) dirs_only: bool,
+    pub(crate) recurse_archives: u32,
+    pub(crate) transactional: bool,
+    pub(crate) shared_dest: bool,
+    pub(crate) patch_in_place: bool,
+    pub(crate) force_readonly: bool,
+    pub(crate) owner: Option<Owner>,
+    pub(crate) priority: Vec<String>,
+    pub(crate) pipe_banners: bool,
+    pub(crate) checkpoint_every: Option<CheckpointInterval>,
+    pub(crate) resume: bool,
+    pub(crate) verify: bool,
+    pub(crate) expected_sizes: Option<Arc<ExpectedSizes>>,
+    pub(crate) compress_output: Option<OutputCompression>,
+    pub(crate) scan_socket: Option<PathBuf>,
+    pub(crate) quarantine: Option<PathBuf>,
+    pub(crate) cache: CachePolicy,
+    pub(crate) compat: Compat,
+    pub(crate) limits: ExtractionLimits,
+    pub(crate) expect: Expectations,
+    pub(crate) opendoc_check: bool,
+    pub(crate) sources: Vec<PathBuf>,
+    pub(crate) allow_self_overwrite: bool,
+    pub(crate) threads: Option<usize>,
+    pub(crate) password: Option<String>,
+    pub(crate) observer: Option<SharedObserver>,
+    pub(crate) hooks: Option<Arc<dyn EntryHooks>>,
+    pub(crate) status: Option<StatusBoard>,
+    pub(crate) cancel: CancelToken,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        Self {
+            output_dir: PathBuf::from("."),
+            smart_dir: None,
+            stamp_output_dir: None,
+            implied_dir_times: ImpliedDirTimes::Now,
+            overwrite: OverwritePolicy::default(),
+            patterns: Vec::new(),
+            exclude: Vec::new(),
+            members: Vec::new(),
+            member_index: None,
+            duplicates: DuplicatePolicy::Last,
+            case_insensitive: false,
+            wild_stop_at_dir: false,
+            junk_paths: false,
+            strip_components: 0,
+            transforms: Vec::new(),
+            name_template: None,
+            lowercase: false,
+            merge_case_dirs: false,
+            no_timestamps: 0,
+            quiet: 0,
+            names: NameDecoder::default(),
+            text_mode: TextMode::Binary,
+            flatten_long_paths: false,
+            follow_symlinks: false,
+            heuristic_decode: false,
+            dry_run: false,
+            dirs_only: false,
+            recurse_archives: 0,
+            transactional: false,
+            shared_dest: false,
+            patch_in_place: false,
+            force_readonly: false,
+            owner: None,
+            priority: Vec::new(),
+            pipe_banners: false,
+            checkpoint_every: None,
+            resume: false,
+            verify: false,
+            expected_sizes: None,
+            compress_output: None,
+            scan_socket: None,
+            quarantine: None,
+            cache: CachePolicy::default(),
+            compat: Compat::default(),
+            limits: ExtractionLimits::default(),
+            expect: Expectations::default(),
+            opendoc_check: false,
+            sources: Vec::new(),
+            allow_self_overwrite: false,
+            threads: None,
+            password: None,
+            observer: None,
+            hooks: None,
+            status: None,
+            cancel: CancelToken::default(),
+        }
+    }
+}
+
+impl fmt::Debug for ExtractOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExtractOptions")
+            .field("output_dir", &self.output_dir)
+            .field("smart_dir", &self.smart_dir)
+            .field("stamp_output_dir", &self.stamp_output_dir)
+            .field("implied_dir_times", &self.implied_dir_times)
+            .field("overwrite", &self.overwrite)
+            .field("patterns", &self.patterns)
+            .field("exclude", &self.exclude)
+            .field("members", &self.members)
+            .field("member_index", &self.member_index)
+            .field("duplicates", &self.duplicates)
+            .field("case_insensitive", &self.case_insensitive)
+            .field("wild_stop_at_dir", &self.wild_stop_at_dir)
+            .field("junk_paths", &self.junk_paths)
+            .field("strip_components", &self.strip_components)
+            .field("transforms", &self.transforms)
+            .field("name_template", &self.name_template)
+            .field("lowercase", &self.lowercase)
+            .field("merge_case_dirs", &self.merge_case_dirs)
+            .field("no_timestamps", &self.no_timestamps)
+            .field("quiet", &self.quiet)
+            .field("names", &self.names)
+            .field("text_mode", &self.text_mode)
+            .field("flatten_long_paths", &self.flatten_long_paths)
+            .field("follow_symlinks", &self.follow_symlinks)
+            .field("heuristic_decode", &self.heuristic_decode)
+            .field("dry_run", &self.dry_run)
+            .field("dirs_only", &self.dirs_only)
+            .field("recurse_archives", &self.recurse_archives)
+            .field("transactional", &self.transactional)
+            .field("shared_dest", &self.shared_dest)
+            .field("patch_in_place", &self.patch_in_place)
+            .field("force_readonly", &self.force_readonly)
+            .field("owner", &self.owner)
+            .field("priority", &self.priority)
+            .field("pipe_banners", &self.pipe_banners)
+            .field("checkpoint_every", &self.checkpoint_every)
+            .field("resume", &self.resume)
+            .field("verify", &self.verify)
+            .field("expected_sizes", &self.expected_sizes.is_some())
+            .field("compress_output", &self.compress_output)
+            .field("scan_socket", &self.scan_socket)
+            .field("quarantine", &self.quarantine)
+            .field("cache", &self.cache)
+            .field("compat", &self.compat)
+            .field("limits", &self.limits)
+            .field("expect", &self.expect)
+            .field("opendoc_check", &self.opendoc_check)
+            .field("sources", &self.sources)
+            .field("allow_self_overwrite", &self.allow_self_overwrite)
+            .field("threads", &self.threads)
+            .field("password", &self.password.as_ref().map(|_| "<hidden>"))
+            .field("observer", &self.observer.is_some())
+            .field("hooks", &self.hooks.is_some())
+            .field("status", &self.status.is_some())
+            .field("cancel", &self.cancel)
+            .finish()
+    }
+}
+
+impl ExtractOptions {
+    /// Options matching a plain `unzip archive.zip`: extract everything into the
+    /// current directory, keeping existing files
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Directory to extract into, created if missing (`-d`)
+    pub fn output_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.output_dir = dir.into();
+        self
+    }
+
+    /// Extract into `dir` inside the output directory, unless all selected
+    /// entries already lie in one top-level directory (`--smart-dir`)
+    ///
+    /// Keeps archives without a top-level directory from scattering their files
+    /// over the output directory. The command line names `dir` after the archive.
+    pub fn smart_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.smart_dir = Some(dir.into());
+        self
+    }
+
+    /// Once extracted, give the output directory this modification time
+    /// (`--stamp-output-dir`, see [`crate::dirstamp`])
+    pub fn stamp_output_dir(mut self, stamp: Option<OutputDirStamp>) -> Self {
+        self.stamp_output_dir = stamp;
+        self
+    }
+
+    /// Modification time of the directories created for files without an entry
+    /// of their own (`--implied-dir-times`, see [`crate::implied_dirs`])
+    pub fn implied_dir_times(mut self, policy: ImpliedDirTimes) -> Self {
+        self.implied_dir_times = policy;
+        self
+    }
+
+    /// What to do with files that already exist
+    pub fn overwrite(mut self, policy: OverwritePolicy) -> Self {
+        self.overwrite = policy;
+        self
+    }
+
+    /// Only extract entries matching one of these glob patterns
+    pub fn patterns<I, S>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.patterns = patterns.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Skip entries matching any of these glob patterns (`-x`)
+    pub fn exclude<I, S>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.exclude = patterns.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Also extract the entries with exactly these names, and fail before writing
+    /// anything if one of them is not in the archive (`--members-from`)
+    ///
+    /// Names are compared literally, without glob matching or `-C`. See
+    /// [`crate::members`].
+    pub fn members<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.members = names.into_iter().map(Into::into).collect();
+        self.members.sort();
+        self.members.dedup();
+        self
+    }
+
+    /// Consider only the entry at this position in the central directory, counting
+    /// from 0 (`--member-index`)
+    ///
+    /// Patterns and exclusions still apply to it. See [`crate::members`].
+    pub fn member_index(mut self, index: Option<usize>) -> Self {
+        self.member_index = index;
+        self
+    }
+
+    /// Which of the entries sharing a name to extract (`--duplicates`), the last
+    /// by default
+    ///
+    /// See [`crate::duplicates`].
+    pub fn duplicates(mut self, policy: DuplicatePolicy) -> Self {
+        self.duplicates = policy;
+        self
+    }
+
+    /// Whether the entry at `index` is considered at all, which only
+    /// `--member-index` restricts
+    pub(crate) fn considers(&self, index: usize) -> bool {
+        self.member_index.is_none_or(|member| member == index)
+    }
+
+    /// Number of the archive's `entries` the run considers
+    pub(crate) fn considered_entries(&self, entries: usize) -> usize {
+        self.member_index.map_or(entries, |_| 1)
+    }
+
+    /// Whether files are written under a temporary name and renamed into place:
+    /// with `--shared-dest`, and with `--scan-socket` so that only files the
+    /// scanner cleared reach their destination
+    pub(crate) fn writes_pending(&self) -> bool {
+        self.shared_dest || self.scan_socket.is_some()
+    }
+
+    /// Matcher selecting the entries the patterns, exclusions and members ask for
+    pub(crate) fn matcher(&self) -> PatternMatcher<'_> {
+        PatternMatcher::new(&self.patterns, &self.exclude, self.case_insensitive)
+            .with_members(&self.members)
+            .with_wildcards(self.compat.wildcards(self.wild_stop_at_dir))
+    }
+
+    /// Match patterns case-insensitively (`-C`)
+    pub fn case_insensitive(mut self, yes: bool) -> Self {
+        self.case_insensitive = yes;
+        self
+    }
+
+    /// Keep `*` and `?` from matching `/` under `--compat=infozip` (`-W`); outside
+    /// it, they never do
+    pub fn wild_stop_at_dir(mut self, yes: bool) -> Self {
+        self.wild_stop_at_dir = yes;
+        self
+    }
+
+    /// Extract every file into the output directory itself (`-j`)
+    pub fn junk_paths(mut self, yes: bool) -> Self {
+        self.junk_paths = yes;
+        self
+    }
+
+    /// Remove the first `count` components from entry paths (`--strip-components`)
+    ///
+    /// `project-1.2.3/src/main.rs` lands as `src/main.rs` with a count of 1.
+    /// Entries with nothing left of their path, like `project-1.2.3/` itself, are
+    /// skipped with [`SkipReason::Stripped`](crate::events::SkipReason::Stripped).
+    pub fn strip_components(mut self, count: usize) -> Self {
+        self.strip_components = count;
+        self
+    }
+
+    /// Rewrite output paths with these sed expressions, in order (`--transform`)
+    ///
+    /// They apply after `-L`, `--strip-components` and `-j`. See
+    /// [`crate::transform`].
+    pub fn transforms<I>(mut self, transforms: I) -> Self
+    where
+        I: IntoIterator<Item = Transform>,
+    {
+        self.transforms = transforms.into_iter().collect();
+        self
+    }
+
+    /// Name extracted files after this template (`--name-template`)
+    ///
+    /// It applies to file entries, after [`transforms`](Self::transforms). See
+    /// [`crate::template`].
+    pub fn name_template(mut self, template: Option<NameTemplate>) -> Self {
+        self.name_template = template;
+        self
+    }
+
+    /// Lowercase extracted names (`-L`)
+    pub fn lowercase(mut self, yes: bool) -> Self {
+        self.lowercase = yes;
+        self
+    }
+
+    /// Write directories spelled in more than one case across entries, such as
+    /// `Docs/` and `docs/`, under the spelling that comes first in the archive
+    /// (`--merge-case-dirs`)
+    ///
+    /// Without it, such directories are reported as [`ExtractEvent::Warning`]
+    /// events and extracted as spelled.
+    pub fn merge_case_dirs(mut self, yes: bool) -> Self {
+        self.merge_case_dirs = yes;
+        self
+    }
+
+    /// Skip restoring directory timestamps (1, `-D`) or all timestamps (2, `-DD`)
+    pub fn no_timestamps(mut self, level: u8) -> Self {
+        self.no_timestamps = level;
+        self
+    }
+
+    /// Console verbosity when no callback is set: 0 shows progress, 1 warnings
+    /// only, 2 nothing (`-q`, `-qq`)
+    ///
+    /// Parallel extraction is only used from level 1 on, so messages stay ordered.
+    pub fn quiet(mut self, level: u8) -> Self {
+        self.quiet = level;
+        self
+    }
+
+    /// Decoder for entry names that are not flagged as UTF-8 (`-O`, `-I`)
+    pub fn names(mut self, names: NameDecoder) -> Self {
+        self.names = names;
+        self
+    }
+
+    /// Line ending conversion (`-a`, `-aa`)
+    pub fn text_mode(mut self, mode: TextMode) -> Self {
+        self.text_mode = mode;
+        self
+    }
+
+    /// Store files whose paths exceed filesystem limits under short names
+    /// (`--flatten-long-paths`)
+    pub fn flatten_long_paths(mut self, yes: bool) -> Self {
+        self.flatten_long_paths = yes;
+        self
+    }
+
+    /// Write through symlinks already in the output directory (`--follow-symlinks`)
+    pub fn follow_symlinks(mut self, yes: bool) -> Self {
+        self.follow_symlinks = yes;
+        self
+    }
+
+    /// Retry entries that fail to decode as the other of Stored and Deflate
+    /// (`--heuristic-decode`); recovered entries are reported as warnings
+    pub fn heuristic_decode(mut self, yes: bool) -> Self {
+        self.heuristic_decode = yes;
+        self
+    }
+
+    /// Decide what to do with every entry, but write nothing (`--dry-run`)
+    ///
+    /// Entries are reported as [`ExtractEvent::Planned`] or
+    /// [`ExtractEvent::Skipped`] events; the output directory is not even created.
+    /// Dry runs always extract serially.
+    pub fn dry_run(mut self, yes: bool) -> Self {
+        self.dry_run = yes;
+        self
+    }
+
+    /// Create the directory skeleton only (`--dirs-only`)
+    ///
+    /// Directory entries are created as usual, and so are the parent directories
+    /// of selected files; the files themselves are reported as
+    /// [`SkipReason::DirsOnly`](crate::SkipReason::DirsOnly). Such runs always
+    /// extract serially.
+    pub fn dirs_only(mut self, yes: bool) -> Self {
+        self.dirs_only = yes;
+        self
+    }
+
+    /// Extract the `.zip` files an extraction writes into directories named after
+    /// them, up to `depth` levels deep; 0 turns this off (`--recurse-archives`)
+    ///
+    /// Nested archives together may write what is left of the output size limit,
+    /// or without one [`NESTED_EXPANSION`](crate::nested::NESTED_EXPANSION) times
+    /// their own size.
+    pub fn recurse_archives(mut self, depth: u32) -> Self {
+        self.recurse_archives = depth;
+        self
+    }
+
+    /// Extract all entries or none (`--transactional`)
+    ///
+    /// Entries are extracted into a hidden staging directory inside the output
+    /// directory and moved into place once all of them succeeded. A failure while
+    /// moving is rolled back, restoring overwritten files from backups kept next
+    /// to them until the run ends.
+    pub fn transactional(mut self, yes: bool) -> Self {
+        self.transactional = yes;
+        self
+    }
+
+    /// Extract safely while other processes extract into the same output
+    /// directory (`--shared-dest`)
+    ///
+    /// Files are written under temporary names and renamed into place; files
+    /// another process already placed with the same content are skipped. See
+    /// [`crate::shared`].
+    pub fn shared_dest(mut self, yes: bool) -> Self {
+        self.shared_dest = yes;
+        self
+    }
+
+    /// Write over existing files that have the entry's size block by block,
+    /// rewriting only the blocks that differ (`--patch-in-place`)
+    ///
+    /// Files of another size, and entries converted or compressed on the way,
+    /// are written as usual. See [`crate::patch`].
+    pub fn patch_in_place(mut self, yes: bool) -> Self {
+        self.patch_in_place = yes;
+        self
+    }
+
+    /// Replace read-only files, giving them their permissions back afterwards
+    /// (`--force-readonly`)
+    ///
+    /// Without it, an existing file without write permission is skipped with a
+    /// warning instead of failing the extraction. See [`crate::readonly`].
+    pub fn force_readonly(mut self, yes: bool) -> Self {
+        self.force_readonly = yes;
+        self
+    }
+
+    /// Give extracted files and the directories the run creates this owner and
+    /// group (`--chown`)
+    ///
+    /// Needs `CAP_CHOWN`; see [`crate::owner`].
+    pub fn owner(mut self, owner: Option<Owner>) -> Self {
+        self.owner = owner;
+        self
+    }
+
+    /// Extract the entries these patterns select before all others
+    /// (`--priority`)
+    ///
+    /// See [`crate::priority`] for how patterns match and what ordering threaded
+    /// extraction guarantees.
+    pub fn priority<I, S>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.priority = patterns.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Precede each file [`extract_to_pipe`](crate::extract::extract_to_pipe)
+    /// writes with an `  inflating: NAME` line, and convert text files as
+    /// [`ExtractOptions::text_mode`] asks (`-c`)
+    pub fn pipe_banners(mut self, yes: bool) -> Self {
+        self.pipe_banners = yes;
+        self
+    }
+
+    /// Record the extracted entries in a checkpoint file in the output directory
+    /// at `interval`, and when extraction fails or is stopped
+    /// (`--checkpoint-every`)
+    ///
+    /// See [`crate::checkpoint`]. Cancel with [`CancelToken::stop`] rather than
+    /// [`CancelToken::cancel`] to finish the entries being written first.
+    pub fn checkpoint_every(mut self, interval: Option<CheckpointInterval>) -> Self {
+        self.checkpoint_every = interval;
+        self
+    }
+
+    /// Skip the entries the checkpoint of an earlier run records as extracted
+    /// (`--resume`)
+    ///
+    /// Without a checkpoint in the output directory, everything is extracted.
+    pub fn resume(mut self, yes: bool) -> Self {
+        self.resume = yes;
+        self
+    }
+
+    /// Read every file back after writing it and fail on a CRC32 mismatch
+    /// (`--verify`)
+    ///
+    /// Each file is flushed to disk and dropped from the page cache first, so the
+    /// data really comes from the storage device. This costs a full read of the
+    /// output and defeats `--cache=keep`.
+    pub fn verify(mut self, yes: bool) -> Self {
+        self.verify = yes;
+        self
+    }
+
+    /// Fail if an entry decompresses to another size than `sizes` expects
+    /// (`--size-check`)
+    ///
+    /// See [`crate::size_check::check_sizes`], which settles on the sizes.
+    pub fn expected_sizes(mut self, sizes: ExpectedSizes) -> Self {
+        self.expected_sizes = Some(Arc::new(sizes));
+        self
+    }
+
+    /// Compress every extracted file into `NAME.zst` instead of writing it as is
+    /// (`--compress-output`)
+    ///
+    /// Directories are created as usual. Do not combine with [`Self::verify`],
+    /// which would read back the compressed data.
+    pub fn compress_output(mut self, compression: Option<OutputCompression>) -> Self {
+        self.compress_output = compression;
+        self
+    }
+
+    /// Stream every file written to the ClamAV-compatible daemon on `socket`
+    /// and keep flagged files out of the output (`--scan-socket`)
+    ///
+    /// See [`crate::scan`].
+    pub fn scan_socket(mut self, socket: impl Into<PathBuf>) -> Self {
+        self.scan_socket = Some(socket.into());
+        self
+    }
+
+    /// Directory to move flagged files into instead of removing them
+    /// (`--quarantine`)
+    pub fn quarantine(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.quarantine = Some(dir.into());
+        self
+    }
+
+    /// Whether extracted files are dropped from the page cache (`--cache`)
+    pub fn cache(mut self, policy: CachePolicy) -> Self {
+        self.cache = policy;
+        self
+    }
+
+    /// Follow Info-ZIP's conventions or this tool's own (`--compat`)
+    ///
+    /// In [`Compat::InfoZip`] mode, the console reporter prints Info-ZIP's messages
+    /// without a progress bar, timestamps are restored the way Info-ZIP does, and
+    /// an existing file asks on stdin whether to replace it unless an overwrite
+    /// policy other than [`OverwritePolicy::Skip`] is set.
+    pub fn compat(mut self, compat: Compat) -> Self {
+        self.compat = compat;
+        self
+    }
+
+    /// Whether existing files are asked about instead of skipped
+    pub(crate) fn prompts_overwrite(&self) -> bool {
+        self.compat == Compat::InfoZip
+            && self.overwrite == OverwritePolicy::Skip
+            && !self.dry_run
+            && self.observer.is_none()
+    }
+
+    /// Output size, ratio and entry count limits
+    pub fn limits(mut self, limits: ExtractionLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Number and total size of the selected files, checked before anything is
+    /// written (`--expect-files`, `--expect-bytes`)
+    pub fn expect(mut self, expectations: Expectations) -> Self {
+        self.expect = expectations;
+        self
+    }
+
+    /// Check the conventions of EPUB, OpenDocument and Office Open XML containers
+    /// before anything is written (`--opendoc-check`)
+    ///
+    /// See [`crate::opendoc`].
+    pub fn opendoc_check(mut self, check: bool) -> Self {
+        self.opendoc_check = check;
+        self
+    }
+
+    /// Files the archive is read from, its volumes for a split archive, which
+    /// no entry may be extracted over
+    ///
+    /// See [`crate::self_overwrite`]. Without any, nothing is checked.
+    pub fn sources<I, P>(mut self, paths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.sources = paths.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Extract even entries that would overwrite the archive's own files
+    /// (`--allow-self-overwrite`)
+    pub fn allow_self_overwrite(mut self, allow: bool) -> Self {
+        self.allow_self_overwrite = allow;
+        self
+    }
+
+    /// Most worker threads, scaled by throughput; 0 or 1 extracts serially
+    /// (`-T`)
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    /// Password for encrypted entries (`-P`)
+    ///
+    /// Without one, encrypted entries prompt on the terminal.
+    pub fn password(mut self, password: impl Into<String>) -> Self {
+        self.password = Some(password.into());
+        self
+    }
+
+    /// Report progress to `observer` instead of the console
+    pub fn observer(mut self, observer: impl ExtractObserver + Send + 'static) -> Self {
+        self.observer = Some(Arc::new(Mutex::new(observer)));
+        self
+    }
+
+    /// Report progress to an observer the caller keeps a handle on
+    pub fn shared_observer(mut self, observer: SharedObserver) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Consult `hooks` for every file entry the patterns select, and tell them
+    /// how each entry ended (see [`crate::hooks`])
+    pub fn hooks(self, hooks: impl EntryHooks + 'static) -> Self {
+        self.shared_hooks(Arc::new(hooks))
+    }
+
+    /// Consult hooks the caller keeps a handle on
+    pub fn shared_hooks(mut self, hooks: Arc<dyn EntryHooks>) -> Self {
+        self.hooks = Some(hooks);
+        self
+    }
+
+    /// Stop the extraction once `token` is cancelled
+    pub fn cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel = token;
+        self
+    }
+
+    /// Keep `board` up to date with the extraction's progress, in addition to
+    /// the observer or console output
+    pub fn status_board(mut self, board: StatusBoard) -> Self {
+        self.status = Some(board);
+        self
+    }
+
+    /// Receive raw extraction events instead of console output
+    pub fn on_event(self, callback: impl FnMut(ExtractEvent) + Send + 'static) -> Self {
+        self.observer(EventFn(callback))
+    }
+}
+
+impl TryFrom<&Args> for ExtractOptions {
+    type Error = UnzipError;
+
+    /// Collect the extraction options from parsed command-line arguments
+    ///
+    /// When several overwrite flags are given, `-f` wins over `-u`, `-u` over
+    /// `--if-changed`, `--if-changed` over `--auto-rename`, `--auto-rename` over
+    /// `-n`, and `-n` over `-o`.
+    fn try_from(args: &Args) -> Result<Self> {
+        let overwrite = if args.freshen {
+            OverwritePolicy::Freshen
+        } else if args.update {
+            OverwritePolicy::Update
+        } else if args.if_changed {
+            OverwritePolicy::IfChanged
+        } else if args.auto_rename {
+            OverwritePolicy::Rename
+        } else if args.never_overwrite {
+            OverwritePolicy::Never
+        } else if args.overwrite {
+            OverwritePolicy::Always
+        } else {
+            OverwritePolicy::Skip
+        };
+        Ok(Self {
+            output_dir: args.output_dir.clone().unwrap_or_else(|| PathBuf::from(".")),
+            smart_dir: args.smart_dir.then(|| subdir_for(None, &args.zipfile)),
+            stamp_output_dir: (args.stamp_output_dir)
+                .map(|source| OutputDirStamp::from_source(source, &args.zipfile))
+                .transpose()?,
+            implied_dir_times: args.implied_dir_times,
+            overwrite,
+            patterns: args.patterns.clone(),
+            exclude: args.exclude.clone(),
+            members: match &args.members_from {
+                Some(path) => read_members(path)?,
+                None => Vec::new(),
+            },
+            member_index: args.member_index,
+            duplicates: args.duplicates,
+            case_insensitive: args.case_insensitive,
+            wild_stop_at_dir: args.wild_stop_at_dir,
+            junk_paths: args.junk_paths,
+            strip_components: args.strip_components,
+            transforms: args.transform.clone(),
+            name_template: args.name_template.clone(),
+            lowercase: args.lowercase,
+            merge_case_dirs: args.merge_case_dirs,
+            no_timestamps: args.no_timestamps,
+            quiet: args.quiet,
+            names: NameDecoder::from_args(args)?,
+            text_mode: TextMode::from_count(args.text_mode),
+            flatten_long_paths: args.flatten_long_paths,
+            follow_symlinks: args.follow_symlinks,
+            heuristic_decode: args.heuristic_decode,
+            dry_run: args.dry_run,
+            dirs_only: args.dirs_only,
+            recurse_archives: args.recurse_archives.unwrap_or(0),
+            transactional: args.transactional,
+            shared_dest: args.shared_dest,
+            patch_in_place: args.patch_in_place,
+            force_readonly: args.force_readonly,
+            owner: args.chown,
+            priority: args.priority.clone(),
+            pipe_banners: args.crt,
+            checkpoint_every: args.checkpoint_every,
+            resume: args.resume,
+            verify: args.verify,
+            expected_sizes: args.expected_sizes.clone(),
+            compress_output: args.compress_output,
+            scan_socket: args.scan_socket.clone(),
+            quarantine: args.quarantine.clone(),
+            cache: args.cache,
+            compat: args.compat,
+            limits: ExtractionLimits::from_args(args),
+            expect: Expectations::from_args(args),
+            opendoc_check: args.opendoc_check,
+            sources: if args.zipfile == Path::new("-") {
+                Vec::new()
+            } else {
+                find_parts(&args.zipfile).unwrap_or_else(|| vec![args.zipfile.clone()])
+            },
+            allow_self_overwrite: args.allow_self_overwrite,
+            threads: args.threads,
+            password: args.password.clone(),
+            observer: None,
+            hooks: None,
+            status: None,
+            cancel: CancelToken::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_from_args() {
+        let args = Args::parse_from(["unzip", "-n", "-o", "-j", "-d", "out", "a.zip", "*.txt"]);
+        let options = ExtractOptions::try_from(&args).unwrap();
+        assert_eq!(options.overwrite, OverwritePolicy::Never);
+        assert_eq!(options.output_dir, PathBuf::from("out"));
+        assert_eq!(options.patterns, ["*.txt"]);
+        assert!(options.junk_paths);
+
+        let args = Args::parse_from(["unzip", "-u", "-f", "a.zip"]);
+        assert_eq!(ExtractOptions::try_from(&args).unwrap().overwrite, OverwritePolicy::Freshen);
+
+        let args = Args::parse_from(["unzip", "-n", "--auto-rename", "a.zip"]);
+        assert_eq!(ExtractOptions::try_from(&args).unwrap().overwrite, OverwritePolicy::Rename);
+        assert!(Args::try_parse_from(["unzip", "-o", "--auto-rename", "a.zip"]).is_err());
+
+        let args = Args::parse_from(["unzip", "-o", "--if-changed", "a.zip"]);
+        assert_eq!(ExtractOptions::try_from(&args).unwrap().overwrite, OverwritePolicy::IfChanged);
+
+        let args = Args::parse_from(["unzip", "-O", "no-such-charset", "a.zip"]);
+        assert!(ExtractOptions::try_from(&args).is_err());
+    }
+
+    #[test]
+    fn test_builder_defaults() {
+        let options = ExtractOptions::new().exclude(["*.log"]).threads(2);
+        assert_eq!(options.output_dir, PathBuf::from("."));
+        assert_eq!(options.overwrite, OverwritePolicy::Skip);
+        assert_eq!(options.exclude, ["*.log"]);
+        assert_eq!(options.threads, Some(2));
+        assert!(!format!("{:?}", options.password("secret")).contains("secret"));
+    }
+}
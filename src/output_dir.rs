@@ -0,0 +1,171 @@
+//! Output directory checks before extraction (`-d`, `--no-create-dir`)
+//!
+//! `-d DIR` creates `DIR` and any missing parents, as Info-ZIP does. Scripts
+//! that would rather catch a typo than extract into a fresh `/tpm` pass
+//! `--no-create-dir`, which makes a missing `DIR` an error instead.
+//!
+//! Either way the target is checked before the archive is opened, so a bad
+//! `-d` fails at once rather than after scanning a large archive: an existing
+//! `DIR` must be a directory this process can write to, and a missing one must
+//! be creatable, below a writable directory. `--dry-run` writes nothing and
+//! skips the write permission checks. Directories made below `DIR`, by
+//! `--smart-dir` or `--subdir-per-archive`, are still created.
+//!
+//! ```text
+//! $ unzip --no-create-dir site.zip -d /tpm/site
+//! Error: output directory /tpm/site does not exist (--no-create-dir)
+//! ```
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Why the output directory cannot be extracted into
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputDirError {
+    /// The directory does not exist and `--no-create-dir` was given
+    Missing(PathBuf),
+    /// The path, or the nearest of its parents that exists, is not a directory
+    NotADirectory(PathBuf),
+    /// The directory, or the one it would be created in, is not writable
+    NotWritable(PathBuf),
+}
+
+impl fmt::Display for OutputDirError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing(dir) => {
+                write!(f, "output directory {} does not exist (--no-create-dir)", dir.display())
+            },
+            Self::NotADirectory(path) => {
+                write!(f, "output directory: {} exists but is not a directory", path.display())
+            },
+            Self::NotWritable(dir) => {
+                write!(f, "output directory: {} is not writable", dir.display())
+            },
+        }
+    }
+}
+
+impl std::error::Error for OutputDirError {}
+
+/// Check that `dir` can be extracted into, allowing it to be missing only if
+/// `create`
+///
+/// Nothing is created here; extraction creates the directory later. With
+/// `check_writable` false, as in a dry run, only the types of the paths are
+/// checked.
+///
+/// # Errors
+///
+/// Returns [`OutputDirError::Missing`] if `dir` does not exist and `create` is
+/// false, [`OutputDirError::NotADirectory`] if it or the nearest existing
+/// parent is not a directory, and [`OutputDirError::NotWritable`] if that
+/// directory cannot be written to
+///
+/// # Examples
+///
+/// ```
+/// use unzip::output_dir::{OutputDirError, check_output_dir};
+///
+/// let dir = tempfile::tempdir()?;
+/// assert_eq!(check_output_dir(dir.path(), false, true), Ok(()));
+/// let missing = dir.path().join("tpm");
+/// assert_eq!(check_output_dir(&missing, true, true), Ok(()));
+/// assert_eq!(check_output_dir(&missing, false, true), Err(OutputDirError::Missing(missing)));
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn check_output_dir(
+    dir: &Path,
+    create: bool,
+    check_writable: bool,
+) -> Result<(), OutputDirError> {
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let existing = if dir.exists() {
+        dir
+    } else if !create {
+        return Err(OutputDirError::Missing(dir.to_path_buf()));
+    } else {
+        dir.ancestors()
+            .skip(1)
+            .map(|parent| {
+                if parent.as_os_str().is_empty() {
+                    Path::new(".")
+                } else {
+                    parent
+                }
+            })
+            .find(|parent| parent.exists())
+            .unwrap_or(Path::new("."))
+    };
+    if !existing.is_dir() {
+        return Err(OutputDirError::NotADirectory(existing.to_path_buf()));
+    }
+    if check_writable && !writable(existing) {
+        return Err(OutputDirError::NotWritable(existing.to_path_buf()));
+    }
+    Ok(())
+}
+
+/// Whether entries can be created in the directory `dir`
+#[cfg(target_os = "linux")]
+fn writable(dir: &Path) -> bool {
+    use rustix::fs::{Access, access};
+    access(dir, Access::WRITE_OK | Access::EXEC_OK).is_ok()
+}
+
+/// Whether entries can be created in the directory `dir`
+#[cfg(not(target_os = "linux"))]
+fn writable(dir: &Path) -> bool {
+    dir.metadata().is_ok_and(|metadata| !metadata.permissions().readonly())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_parents_are_checked_up_to_an_existing_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("a/b/c");
+        assert_eq!(check_output_dir(&nested, true, true), Ok(()));
+        assert_eq!(
+            check_output_dir(&nested, false, true),
+            Err(OutputDirError::Missing(nested.clone()))
+        );
+
+        let file = dir.path().join("file.txt");
+        std::fs::write(&file, b"").unwrap();
+        assert_eq!(
+            check_output_dir(&file, true, true),
+            Err(OutputDirError::NotADirectory(file.clone()))
+        );
+        assert_eq!(
+            check_output_dir(&file.join("sub"), true, true),
+            Err(OutputDirError::NotADirectory(file))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_read_only_directory_is_not_writable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let locked = dir.path().join("locked");
+        std::fs::create_dir(&locked).unwrap();
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o555)).unwrap();
+        // Root writes anywhere, as access(2) reports
+        if writable(&locked) {
+            return;
+        }
+        assert_eq!(
+            check_output_dir(&locked.join("out"), true, true),
+            Err(OutputDirError::NotWritable(locked.clone()))
+        );
+        assert_eq!(check_output_dir(&locked.join("out"), true, false), Ok(()));
+    }
+}
@@ -0,0 +1,161 @@
+//! Ownership of extracted files (`--chown USER:GROUP`)
+//!
+//! Deployments run as root usually want the result owned by a service account.
+//! A `chown -R` afterwards walks the whole tree a second time; with `--chown`,
+//! every file is given its owner right after it is written, and every directory
+//! the run created or extracted in the deferred pass that also restores their
+//! times and permissions. Ownership is changed before permissions are set, since
+//! a change of owner clears setuid and setgid bits.
+//!
+//! The syntax is that of `chown`: `USER:GROUP`, `USER` alone, `:GROUP` alone, or
+//! `USER:` for the user and their login group. Names are looked up in
+//! `/etc/passwd` and `/etc/group`; numeric IDs are used as they are, which also
+//! covers accounts only known to a directory service.
+//!
+//! Changing the owner needs `CAP_CHOWN` (root); without it the first file that
+//! cannot be given away fails the extraction. Directories created by moving a
+//! `--transactional` run into place are not covered, so the two options conflict.
+//!
+//! # Examples
+//!
+//! ```
+//! use unzip::owner::parse_owner;
+//!
+//! let owner = parse_owner("0:0")?;
+//! assert_eq!((owner.uid(), owner.gid()), (Some(0), Some(0)));
+//! assert_eq!(parse_owner(":100")?.uid(), None);
+//! # Ok::<(), String>(())
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// User and group database files, in `/etc/passwd` and `/etc/group` format
+const PASSWD: &str = "/etc/passwd";
+const GROUP: &str = "/etc/group";
+
+/// Owner and group to give extracted files; `None` leaves that part unchanged
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Owner {
+    uid: Option<u32>,
+    gid: Option<u32>,
+}
+
+impl Owner {
+    /// User ID to set
+    pub fn uid(&self) -> Option<u32> {
+        self.uid
+    }
+
+    /// Group ID to set
+    pub fn gid(&self) -> Option<u32> {
+        self.gid
+    }
+
+    /// Give `path` this owner and group, without following a symlink there
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ownership cannot be changed, typically for lack of
+    /// `CAP_CHOWN`
+    pub fn apply(&self, path: &Path) -> io::Result<()> {
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::lchown(path, self.uid, self.gid)
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = path;
+            Err(io::Error::new(io::ErrorKind::Unsupported, "file ownership is Unix only"))
+        }
+    }
+}
+
+/// Parse a `--chown` argument, `USER:GROUP`, `USER`, `:GROUP` or `USER:`
+///
+/// # Errors
+///
+/// Returns a message suitable for clap if a name is unknown or nothing is given
+pub fn parse_owner(spec: &str) -> Result<Owner, String> {
+    let (user, group) = match spec.split_once(':') {
+        Some((user, group)) => (user, Some(group)),
+        None => (spec, None),
+    };
+    let read = |path| fs::read_to_string(path).unwrap_or_default();
+
+    let (uid, login_gid) = if user.is_empty() {
+        (None, None)
+    } else if let Ok(uid) = user.parse() {
+        (Some(uid), lookup_login_group(&read(PASSWD), |fields| fields[2] == user))
+    } else {
+        let passwd = read(PASSWD);
+        let uid = lookup_id(&passwd, user).ok_or_else(|| format!("unknown user: {}", user))?;
+        (Some(uid), lookup_login_group(&passwd, |fields| fields[0] == user))
+    };
+    let gid = match group {
+        None => None,
+        // `USER:` means the user's login group, as with chown
+        Some("") if uid.is_some() => {
+            Some(login_gid.ok_or_else(|| format!("no login group for user: {}", user))?)
+        },
+        Some("") => return Err(format!("invalid owner: {} (expected USER:GROUP)", spec)),
+        Some(group) => Some(match group.parse() {
+            Ok(gid) => gid,
+            Err(_) => {
+                lookup_id(&read(GROUP), group).ok_or_else(|| format!("unknown group: {}", group))?
+            },
+        }),
+    };
+    if uid.is_none() && gid.is_none() {
+        return Err(format!("invalid owner: {} (expected USER:GROUP)", spec));
+    }
+    Ok(Owner { uid, gid })
+}
+
+/// ID (third field) of `name` in a passwd or group database
+fn lookup_id(database: &str, name: &str) -> Option<u32> {
+    database
+        .lines()
+        .map(|line| line.split(':').collect::<Vec<_>>())
+        .find(|fields| fields.len() > 2 && fields[0] == name)
+        .and_then(|fields| fields[2].parse().ok())
+}
+
+/// Login group (fourth field) of the first passwd entry `matches` accepts
+fn lookup_login_group(passwd: &str, matches: impl Fn(&[&str]) -> bool) -> Option<u32> {
+    passwd
+        .lines()
+        .map(|line| line.split(':').collect::<Vec<_>>())
+        .find(|fields| fields.len() > 3 && matches(fields))
+        .and_then(|fields| fields[3].parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASSWD: &str = "root:x:0:0:root:/root:/bin/sh\nsvc:x:990:985::/srv:/sbin/nologin\n";
+    const GROUP: &str = "root:x:0:\nwww:x:33:svc\n";
+
+    #[test]
+    fn test_database_lookups() {
+        assert_eq!(lookup_id(PASSWD, "svc"), Some(990));
+        assert_eq!(lookup_id(GROUP, "www"), Some(33));
+        assert_eq!(lookup_id(GROUP, "ww"), None);
+        assert_eq!(lookup_id("broken\n", "broken"), None);
+        assert_eq!(lookup_login_group(PASSWD, |fields| fields[0] == "svc"), Some(985));
+        assert_eq!(lookup_login_group(PASSWD, |fields| fields[2] == "0"), Some(0));
+    }
+
+    #[test]
+    fn test_parse_owner_forms() {
+        assert_eq!(parse_owner("1000:1001"), Ok(Owner { uid: Some(1000), gid: Some(1001) }));
+        assert_eq!(parse_owner("1000"), Ok(Owner { uid: Some(1000), gid: None }));
+        assert_eq!(parse_owner(":1001"), Ok(Owner { uid: None, gid: Some(1001) }));
+        assert_eq!(parse_owner("root:"), Ok(Owner { uid: Some(0), gid: Some(0) }));
+        for spec in ["", ":", "no-such-user-here", "0:no-such-group-here"] {
+            assert!(parse_owner(spec).is_err(), "{}", spec);
+        }
+    }
+}
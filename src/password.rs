@@ -3,7 +3,7 @@
 //! Provides secure password input functionality with interactive prompts
 //! and validation for encrypted archive extraction.
 
-use anyhow::{Context, Result};
+use crate::error::{Result, UnzipError};
 
 /// Get password for encrypted archive
 ///
@@ -51,7 +51,7 @@ pub fn get_password(password_arg: Option<&str>, quiet: u8) -> Result<Option<Vec<
 /// Returns an error if password reading fails
 pub fn prompt_for_password() -> Result<Vec<u8>> {
     let password = rpassword::prompt_password("Enter password for encrypted files: ")
-        .context("Failed to read password")?;
+        .map_err(UnzipError::PasswordPrompt)?;
 
     Ok(password.into_bytes())
 }
@@ -0,0 +1,175 @@
+//! Rewriting only the changed blocks of existing files (`--patch-in-place`)
+//!
+//! Updating a large member, a database or a VM image, normally truncates the
+//! existing file and writes every byte again. On copy-on-write filesystems
+//! (Btrfs, ZFS, XFS with reflinks) that unshares every extent, and snapshots
+//! taken before the update stop sharing any of the file's data with it.
+//!
+//! With `--patch-in-place`, an existing file that already has the entry's size
+//! is opened without truncating it, and the entry's data is compared with the
+//! file block by block ([`BLOCK_SIZE`]): blocks that are equal are left alone,
+//! the others are written over in place. The file keeps its inode, hard links
+//! and unchanged extents. Files of another size, and entries converted with
+//! `-a` or compressed with `--compress-output`, are written as usual.
+//!
+//! The comparison costs a read of the existing file, like `--if-changed`, which
+//! combines with it: files with the same CRC32 are skipped without writing
+//! anything, and the others are patched. A patch interrupted halfway leaves a
+//! mix of old and new blocks, so it does not go with `--transactional` or
+//! `--shared-dest`, which never write over the destination file directly.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::fs::{self, OpenOptions};
+//! use std::io::Write;
+//! use unzip::patch::{BLOCK_SIZE, PatchWriter};
+//!
+//! let dir = tempfile::tempdir()?;
+//! let path = dir.path().join("disk.img");
+//! let mut data = vec![0u8; 4 * BLOCK_SIZE];
+//! fs::write(&path, &data)?;
+//!
+//! data[BLOCK_SIZE + 1] = 0xff;
+//! let file = OpenOptions::new().read(true).write(true).open(&path)?;
+//! let mut writer = PatchWriter::new(&file);
+//! writer.write_all(&data)?;
+//! let patched = writer.finish()?;
+//! assert_eq!((patched.bytes, patched.rewritten), (data.len() as u64, BLOCK_SIZE as u64));
+//! assert_eq!(fs::read(&path)?, data);
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+/// Size of the blocks compared and rewritten: 64 KiB
+pub const BLOCK_SIZE: usize = 64 * 1024;
+
+/// Writer over an existing file that only writes the blocks that differ
+#[derive(Debug)]
+pub struct PatchWriter<'a> {
+    file: &'a File,
+    block: Vec<u8>,
+    existing: Vec<u8>,
+    offset: u64,
+    rewritten: u64,
+}
+
+/// What a [`PatchWriter`] did to its file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Patched {
+    /// Bytes of data written through the patcher, the file's final size
+    pub bytes: u64,
+    /// Bytes of it that differed from the file and were rewritten
+    pub rewritten: u64,
+}
+
+impl<'a> PatchWriter<'a> {
+    /// Patch `file`, which must be open for reading and writing, from its start
+    pub fn new(file: &'a File) -> Self {
+        Self {
+            file,
+            block: Vec::with_capacity(BLOCK_SIZE),
+            existing: vec![0; BLOCK_SIZE],
+            offset: 0,
+            rewritten: 0,
+        }
+    }
+
+    /// Compare the last, partial block, and cut the file at the end of the data
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, written or truncated
+    pub fn finish(mut self) -> io::Result<Patched> {
+        self.patch_block()?;
+        if self.file.metadata()?.len() != self.offset {
+            self.file.set_len(self.offset)?;
+        }
+        Ok(Patched { bytes: self.offset, rewritten: self.rewritten })
+    }
+
+    /// Compare the buffered block with the file at its offset, writing it over
+    /// the file if they differ
+    fn patch_block(&mut self) -> io::Result<()> {
+        if self.block.is_empty() {
+            return Ok(());
+        }
+        let len = self.block.len();
+        let mut file = self.file;
+        file.seek(SeekFrom::Start(self.offset))?;
+        let existing = &mut self.existing[..len];
+        let mut read = 0;
+        while read < len {
+            match file.read(&mut existing[read..]) {
+                Ok(0) => break,
+                Ok(n) => read += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {},
+                Err(e) => return Err(e),
+            }
+        }
+        if read < len || *existing != self.block[..] {
+            file.seek(SeekFrom::Start(self.offset))?;
+            file.write_all(&self.block)?;
+            self.rewritten += len as u64;
+        }
+        self.offset += len as u64;
+        self.block.clear();
+        Ok(())
+    }
+}
+
+impl Write for PatchWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = buf.len().min(BLOCK_SIZE - self.block.len());
+        self.block.extend_from_slice(&buf[..n]);
+        if self.block.len() == BLOCK_SIZE {
+            self.patch_block()?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, OpenOptions};
+
+    fn patch(path: &std::path::Path, data: &[u8]) -> Patched {
+        let file = OpenOptions::new().read(true).write(true).open(path).unwrap();
+        let mut writer = PatchWriter::new(&file);
+        // Uneven writes, across block boundaries
+        for chunk in data.chunks(10_000) {
+            writer.write_all(chunk).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn test_patch_writer_rewrites_changed_blocks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.bin");
+        let old: Vec<u8> = (0..3 * BLOCK_SIZE + 100).map(|i| (i % 251) as u8).collect();
+        fs::write(&path, &old).unwrap();
+
+        assert_eq!(patch(&path, &old), Patched { bytes: old.len() as u64, rewritten: 0 });
+
+        let mut new = old.clone();
+        new[0] ^= 1;
+        new[3 * BLOCK_SIZE + 99] ^= 1;
+        let patched = patch(&path, &new);
+        assert_eq!(patched.rewritten, BLOCK_SIZE as u64 + 100);
+        assert_eq!(fs::read(&path).unwrap(), new);
+
+        // Shorter and longer data than the file
+        assert_eq!(patch(&path, &new[..BLOCK_SIZE]).rewritten, 0);
+        assert_eq!(fs::read(&path).unwrap(), &new[..BLOCK_SIZE]);
+        assert_eq!(patch(&path, &new).rewritten, (new.len() - BLOCK_SIZE) as u64);
+        assert_eq!(fs::read(&path).unwrap(), new);
+    }
+}
@@ -0,0 +1,134 @@
+//! Entry name safety checks
+//!
+//! Archive entry names are untrusted input. Before an entry is written, its name is
+//! normalized into a relative path that cannot leave the output directory:
+//!
+//! - Empty components and `.` are dropped (`a//./b` becomes `a/b`)
+//! - A leading `/` is stripped, as Info-ZIP does for absolute paths
+//! - Any `..` component or embedded NUL byte rejects the entry
+//! - On Windows, a component holding `\` or `:` rejects the entry, as it could
+//!   name a parent, a root or a drive (`..\x`, `\x`, `C:\x`); as a last check,
+//!   a path the host does not read as plain relative components is rejected too
+//! - A name that normalizes to nothing (`""`, `"."`, `"/"`) rejects the entry
+//!
+//! # Examples
+//!
+//! ```
+//! use std::path::PathBuf;
+//! use unzip::path_safety::{NameIssue, sanitize_entry_name};
+//!
+//! assert_eq!(sanitize_entry_name("/etc/./passwd"), Ok(PathBuf::from("etc/passwd")));
+//! assert_eq!(sanitize_entry_name("../evil"), Err(NameIssue::ParentDir));
+//! assert_eq!(sanitize_entry_name("."), Err(NameIssue::Empty));
+//! ```
+
+use std::fmt;
+use std::path::{Component, PathBuf};
+
+/// Why an entry name cannot be used as an output path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameIssue {
+    /// Name is empty once `.` components and separators are removed
+    Empty,
+    /// Name contains a `..` component
+    ParentDir,
+    /// Name contains a NUL byte
+    NulByte,
+    /// Name holds what the host reads as a drive, root or parent, like a
+    /// Windows `C:\` or `..\`
+    NotRelative,
+}
+
+impl fmt::Display for NameIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "empty name"),
+            Self::ParentDir => write!(f, "\"..\" path component"),
+            Self::NulByte => write!(f, "NUL byte in name"),
+            Self::NotRelative => write!(f, "drive, root or parent in a path component"),
+        }
+    }
+}
+
+/// Normalize an entry name into a safe relative path
+///
+/// # Arguments
+///
+/// * `name` - Decoded entry name as stored in the archive
+///
+/// # Errors
+///
+/// Returns the [`NameIssue`] that makes the name unusable
+pub fn sanitize_entry_name(name: &str) -> Result<PathBuf, NameIssue> {
+    if name.contains('\0') {
+        return Err(NameIssue::NulByte);
+    }
+
+    let mut path = PathBuf::new();
+    for component in name.split('/') {
+        match component {
+            "" | "." => {},
+            ".." => return Err(NameIssue::ParentDir),
+            // Windows reads these as separators and drive letters
+            normal if cfg!(windows) && normal.contains(['\\', ':']) => {
+                return Err(NameIssue::NotRelative);
+            },
+            normal => path.push(normal),
+        }
+    }
+
+    if path.as_os_str().is_empty() {
+        return Err(NameIssue::Empty);
+    }
+    if !path.components().all(|component| matches!(component, Component::Normal(_))) {
+        return Err(NameIssue::NotRelative);
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_plain_names() {
+        assert_eq!(sanitize_entry_name("file.txt"), Ok(PathBuf::from("file.txt")));
+        assert_eq!(sanitize_entry_name("dir/sub/"), Ok(PathBuf::from("dir/sub")));
+        assert_eq!(sanitize_entry_name("..hidden"), Ok(PathBuf::from("..hidden")));
+    }
+
+    #[test]
+    fn test_sanitize_normalizes_dots_and_separators() {
+        assert_eq!(sanitize_entry_name("./a//./b"), Ok(PathBuf::from("a/b")));
+        assert_eq!(sanitize_entry_name("/abs/path"), Ok(PathBuf::from("abs/path")));
+    }
+
+    #[test]
+    fn test_sanitize_rejects_degenerate_names() {
+        for name in ["", ".", "./", "/", "//", "./."] {
+            assert_eq!(sanitize_entry_name(name), Err(NameIssue::Empty), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn test_sanitize_rejects_parent_and_nul() {
+        assert_eq!(sanitize_entry_name(".."), Err(NameIssue::ParentDir));
+        assert_eq!(sanitize_entry_name("../"), Err(NameIssue::ParentDir));
+        assert_eq!(sanitize_entry_name("a/../b"), Err(NameIssue::ParentDir));
+        assert_eq!(sanitize_entry_name("a\0b"), Err(NameIssue::NulByte));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_sanitize_rejects_windows_separators_and_drives() {
+        for name in ["..\\x", "C:\\x", "\\x", "a/C:x", "a\\b"] {
+            assert_eq!(sanitize_entry_name(name), Err(NameIssue::NotRelative), "{:?}", name);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_sanitize_keeps_backslash_and_colon_on_unix() {
+        assert_eq!(sanitize_entry_name("a\\b:c"), Ok(PathBuf::from("a\\b:c")));
+    }
+}
@@ -0,0 +1,59 @@
+//! Pattern lists read from files (`--include-from`, `--exclude-from`)
+//!
+//! Build scripts that select hundreds of paths run into the command line length
+//! limit. `--include-from FILE` and `--exclude-from FILE` read the patterns from
+//! FILE instead, one glob per line, and add them to those given as arguments and
+//! with `-x`. Both may be repeated, and `-` reads stdin.
+//!
+//! Leading and trailing whitespace is dropped, as are blank lines and lines
+//! starting with `#`; a pattern that starts with a literal `#` can use `?` there.
+//! The patterns are merged with the command line's when the archives of the run
+//! are sorted out ([`Batch::from_args`](crate::batch::Batch::from_args)), so every
+//! mode that takes patterns (extraction, `-t`, `-Z`, `--diff`, ...) sees them.
+//!
+//! # Examples
+//!
+//! ```
+//! use unzip::pattern_files::parse_patterns;
+//!
+//! let text = "# generated by build.sh\n*.so\n\n  lib/**/*.a  \r\n";
+//! assert_eq!(parse_patterns(text), ["*.so", "lib/**/*.a"]);
+//! ```
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::error::{Result, UnzipError};
+
+/// Patterns in the text of a pattern file, in order
+pub fn parse_patterns(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Read the patterns of each file in `paths`, or of stdin for `-`
+///
+/// # Errors
+///
+/// Returns an error if a file cannot be read or is not UTF-8
+pub fn read_patterns<P: AsRef<Path>>(paths: &[P]) -> Result<Vec<String>> {
+    let mut patterns = Vec::new();
+    for path in paths {
+        let path = path.as_ref();
+        let text = if path == Path::new("-") {
+            let mut text = String::new();
+            io::stdin()
+                .read_to_string(&mut text)
+                .map_err(UnzipError::file("read pattern file", path))?;
+            text
+        } else {
+            fs::read_to_string(path).map_err(UnzipError::file("read pattern file", path))?
+        };
+        patterns.extend(parse_patterns(&text));
+    }
+    Ok(patterns)
+}
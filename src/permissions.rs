@@ -0,0 +1,147 @@
+//! Host-specific interpretation of external file attributes
+//!
+//! What the external attributes of an entry mean depends on the host that made
+//! the archive, recorded in the high byte of "version made by":
+//!
+//! - Unix hosts keep the `st_mode` of the file in the high 16 bits. Some tools
+//!   record a Unix host but leave those bits zero; such entries fall back to the
+//!   MS-DOS bits instead of being extracted with mode 0000
+//! - MS-DOS, Windows (NTFS, VFAT) and OS/2 hosts only set MS-DOS attribute bits in
+//!   the low byte. Nothing there says a file is executable, so files get the
+//!   default mode, minus write permission when the read-only bit is set
+//!
+//! The zip crate only tells DOS and Unix hosts apart, so entries from other hosts
+//! are treated as Unix when their high bits hold a file type, and as MS-DOS
+//! otherwise.
+//!
+//! # Examples
+//!
+//! ```
+//! use unzip::permissions::{HOST_DOS, HOST_UNIX, entry_mode};
+//!
+//! // -rwxr-xr-x from a Unix archiver
+//! assert_eq!(entry_mode(HOST_UNIX, 0o100755 << 16, false), Some(0o100755));
+//! // Read-only file from Windows: never executable
+//! assert_eq!(entry_mode(HOST_DOS, 0x01, false), Some(0o100444));
+//! // Plain file from Windows: keep the default permissions
+//! assert_eq!(entry_mode(HOST_DOS, 0x20, false), None);
+//! ```
+
+use zip::HasZipMetadata;
+use zip::read::ZipFile;
+
+/// Host byte of MS-DOS and FAT file systems
+pub const HOST_DOS: u8 = 0;
+
+/// Host byte of Unix
+pub const HOST_UNIX: u8 = 3;
+
+/// MS-DOS read-only attribute
+const DOS_READ_ONLY: u32 = 0x01;
+
+/// MS-DOS directory attribute
+const DOS_DIRECTORY: u32 = 0x10;
+
+/// File type bits of a Unix mode
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+
+/// Default mode of files and directories, before the umask
+const DEFAULT_FILE_MODE: u32 = 0o644;
+const DEFAULT_DIR_MODE: u32 = 0o755;
+
+/// Unix mode to give an entry, or `None` to keep the default permissions
+///
+/// The result includes the file type bits, like `st_mode`.
+///
+/// # Arguments
+///
+/// * `host` - Host byte of "version made by" (`HOST_UNIX`, `HOST_DOS`, ...)
+/// * `external_attributes` - External file attributes from the central directory
+/// * `is_dir` - Whether the entry name ends in `/`
+pub fn entry_mode(host: u8, external_attributes: u32, is_dir: bool) -> Option<u32> {
+    let unix = external_attributes >> 16;
+    let unix_like = host == HOST_UNIX || (host != HOST_DOS && unix & S_IFMT != 0);
+    if unix_like && unix & 0o7777 != 0 {
+        if unix & S_IFMT != 0 {
+            return Some(unix);
+        }
+        let file_type = if is_dir { S_IFDIR } else { S_IFREG };
+        return Some(file_type | unix);
+    }
+
+    // MS-DOS attributes: read-only is the only permission they carry, and it is
+    // meaningless on Windows directories
+    let is_dir = is_dir || external_attributes & DOS_DIRECTORY != 0;
+    if is_dir || external_attributes & DOS_READ_ONLY == 0 {
+        return None;
+    }
+    Some(S_IFREG | (DEFAULT_FILE_MODE & !0o222))
+}
+
+/// Unix mode of an archive entry, interpreted for the host that made it
+pub fn file_mode(file: &ZipFile<'_>) -> Option<u32> {
+    let meta = file.get_metadata();
+    entry_mode(meta.system as u8, meta.external_attributes, file.is_dir())
+}
+
+/// Mode shown for an entry in listings, with defaults filled in
+pub fn display_mode(file: &ZipFile<'_>) -> u32 {
+    let meta = file.get_metadata();
+    entry_display_mode(meta.system as u8, meta.external_attributes, file.is_dir())
+}
+
+/// Mode shown in listings for an entry by its raw attributes, as
+/// [`display_mode`] shows it
+pub fn entry_display_mode(host: u8, external_attributes: u32, is_dir: bool) -> u32 {
+    entry_mode(host, external_attributes, is_dir).unwrap_or(if is_dir {
+        S_IFDIR | DEFAULT_DIR_MODE
+    } else {
+        S_IFREG | DEFAULT_FILE_MODE
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Host byte of Windows NTFS, which the zip crate reports as unknown
+    const HOST_NTFS: u8 = 10;
+
+    #[test]
+    fn test_unix_modes() {
+        assert_eq!(entry_mode(HOST_UNIX, 0o100755 << 16, false), Some(0o100755));
+        assert_eq!(entry_mode(HOST_UNIX, (0o040700 << 16) | 0x10, true), Some(0o040700));
+        // Permission bits without a file type
+        assert_eq!(entry_mode(HOST_UNIX, 0o600 << 16, false), Some(0o100600));
+        assert_eq!(entry_mode(HOST_UNIX, 0o700 << 16, true), Some(0o040700));
+    }
+
+    #[test]
+    fn test_unix_host_without_mode_uses_dos_bits() {
+        assert_eq!(entry_mode(HOST_UNIX, 0, false), None);
+        assert_eq!(entry_mode(HOST_UNIX, 0x20, false), None);
+        assert_eq!(entry_mode(HOST_UNIX, 0x21, false), Some(0o100444));
+        // A bare file type is no mode either
+        assert_eq!(entry_mode(HOST_UNIX, 0o100000 << 16, false), None);
+    }
+
+    #[test]
+    fn test_dos_attributes_never_executable() {
+        assert_eq!(entry_mode(HOST_DOS, 0x20, false), None);
+        assert_eq!(entry_mode(HOST_DOS, 0x01, false), Some(0o100444));
+        assert_eq!(entry_mode(HOST_DOS, 0x11, true), None);
+        assert_eq!(entry_mode(HOST_DOS, 0x10, false), None);
+        // DOS hosts ignore whatever is in the high bits
+        assert_eq!(entry_mode(HOST_DOS, 0o100777 << 16, false), None);
+    }
+
+    #[test]
+    fn test_other_hosts() {
+        assert_eq!(entry_mode(HOST_NTFS, 0x21, false), Some(0o100444));
+        assert_eq!(entry_mode(HOST_NTFS, 0x20, false), None);
+        // Unix-style hosts the zip crate does not know, such as Mac OS X
+        assert_eq!(entry_mode(19, 0o100755 << 16, false), Some(0o100755));
+    }
+}
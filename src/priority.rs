@@ -0,0 +1,108 @@
+//! Extraction order hints (`--priority PATTERNS`)
+//!
+//! A consumer that starts as soon as its manifests or indexes exist should not
+//! have to wait for the bulk data that happens to precede them in the archive.
+//! `--priority 'manifest.json,*.index'` extracts the matching entries before
+//! all others; within each group the archive order is kept.
+//!
+//! Patterns are globs as for selection, matched against the whole name; one
+//! without a `/` is also matched against the file name, so `manifest.json`
+//! puts `app/manifest.json` first too. `-C` makes them case-insensitive.
+//!
+//! Serial extraction simply visits the entries in that order. Threaded
+//! extraction queues them in that order, and each worker takes the next entry
+//! when it finishes one. There is no barrier between the groups: a worker done
+//! with the last priority entry moves on to the bulk data while another may
+//! still be writing a manifest.
+//!
+//! # Examples
+//!
+//! ```
+//! use unzip::priority::extraction_order;
+//!
+//! let patterns = vec!["manifest.json".to_string(), "*.index".to_string()];
+//! let names = ["data/0.bin", "data/1.bin", "app/manifest.json", "a.index"];
+//! assert_eq!(extraction_order(names, &patterns, false), vec![2, 3, 0, 1]);
+//! ```
+
+use std::borrow::Cow;
+
+use crate::glob::glob_match;
+use crate::utils::lowercase;
+
+/// Whether one of `patterns` selects `name` for early extraction
+pub fn is_priority(name: &str, patterns: &[String], case_insensitive: bool) -> bool {
+    Priority::new(patterns, case_insensitive).matches(name)
+}
+
+/// Priority patterns prepared once for matching many names, lowercased for `-C`
+pub(crate) struct Priority<'a> {
+    patterns: Vec<Cow<'a, str>>,
+    case_insensitive: bool,
+}
+
+impl<'a> Priority<'a> {
+    pub(crate) fn new(patterns: &'a [String], case_insensitive: bool) -> Self {
+        let patterns = patterns
+            .iter()
+            .map(|pattern| {
+                if case_insensitive {
+                    lowercase(pattern)
+                } else {
+                    Cow::Borrowed(pattern.as_str())
+                }
+            })
+            .collect();
+        Self { patterns, case_insensitive }
+    }
+
+    /// Whether one of the patterns selects `name` for early extraction
+    pub(crate) fn matches(&self, name: &str) -> bool {
+        if self.patterns.is_empty() {
+            return false;
+        }
+        let name = if self.case_insensitive {
+            lowercase(name)
+        } else {
+            Cow::Borrowed(name)
+        };
+        let file_name = name.trim_end_matches('/').rsplit('/').next().unwrap_or_default();
+        self.patterns.iter().any(|pattern| {
+            glob_match(pattern, &name) || (!pattern.contains('/') && glob_match(pattern, file_name))
+        })
+    }
+}
+
+/// Positions of `names` in the order to extract them: those `patterns` select
+/// first, then the rest, each group in its original order
+pub fn extraction_order<'a>(
+    names: impl IntoIterator<Item = &'a str>,
+    patterns: &[String],
+    case_insensitive: bool,
+) -> Vec<usize> {
+    let priority = Priority::new(patterns, case_insensitive);
+    let (mut first, rest): (Vec<_>, Vec<_>) =
+        names.into_iter().enumerate().partition(|(_, name)| priority.matches(name));
+    first.extend(rest);
+    first.into_iter().map(|(i, _)| i).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns(list: &[&str]) -> Vec<String> {
+        list.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn test_is_priority_matches_name_or_file_name() {
+        let patterns = patterns(&["manifest.json", "meta/*.idx"]);
+        assert!(is_priority("manifest.json", &patterns, false));
+        assert!(is_priority("app/v1/manifest.json", &patterns, false));
+        assert!(is_priority("meta/a.idx", &patterns, false));
+        assert!(!is_priority("other/meta/a.idx", &patterns, false));
+        assert!(!is_priority("Manifest.JSON", &patterns, false));
+        assert!(is_priority("app/Manifest.JSON", &patterns, true));
+    }
+}
@@ -0,0 +1,337 @@
+//! Header-only archive check (`--test-quick`)
+//!
+//! `-t` decompresses every entry to check its CRC32, which for a multi-gigabyte
+//! archive takes as long as extracting it. `--test-quick` reads no data at all:
+//! it streams the central directory (see [`crate::central`]) and holds each
+//! record against the local file header it points to, in seconds, as a sanity
+//! check before committing to the full test or to an extraction. An entry fails
+//! when:
+//!
+//! - there is no local file header where the central directory says, or it
+//!   lies past the start of the central directory
+//! - the local header disagrees with the central record on the name, the
+//!   compression method or encryption, or, unless sizes follow the data in a
+//!   data descriptor, on the CRC32 and sizes
+//! - a data descriptor disagrees with the central record on the CRC32 or sizes
+//! - its data runs into the central directory
+//! - it is stored without encryption but its two sizes differ
+//! - its compression method is one this build cannot decompress
+//!
+//! Local headers are read in batches between runs of central records, so memory
+//! stays flat however many entries there are. A passing check says nothing about
+//! the data itself; only `-t` can tell it decompresses to the right CRC32.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::io::{Cursor, Write};
+//! use unzip::quick_test::check_headers;
+//! use zip::ZipWriter;
+//! use zip::write::SimpleFileOptions;
+//!
+//! let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+//! zip.start_file("hello.txt", SimpleFileOptions::default())?;
+//! zip.write_all(b"hello")?;
+//! let data = zip.finish()?.into_inner();
+//!
+//! let mut problems = Vec::new();
+//! let checked = check_headers(Cursor::new(&data), |_, found| {
+//!     problems.extend_from_slice(found);
+//!     Ok(())
+//! })?;
+//! assert_eq!((checked, problems.len()), (1, 0));
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::io::{self, Read, Seek, Write};
+use zip::CompressionMethod;
+
+use crate::args::Args;
+use crate::cancel::CancelToken;
+use crate::central::{
+    CentralDirectory, CentralRecord, Descriptor, LocalHeader, read_descriptor, read_local_header,
+};
+use crate::charset::NameDecoder;
+use crate::error::{Result, UnzipError};
+use crate::records::LOCAL_HEADER_LEN;
+
+/// Central records read ahead of checking their local headers
+const BATCH: usize = 4096;
+
+/// Check the local headers of every entry of the archive in `reader` against
+/// its central directory, calling `visit` with each record and the problems
+/// found with it, none if it passed
+///
+/// Returns the number of entries checked.
+///
+/// # Errors
+///
+/// Returns an error if the central directory cannot be read, or the first error
+/// `visit` returns
+pub fn check_headers<R: Read + Seek>(
+    reader: R,
+    mut visit: impl FnMut(&CentralRecord, &[String]) -> Result<()>,
+) -> Result<u64> {
+    let mut directory = CentralDirectory::open(reader)?;
+    let cd_start = directory.layout().start;
+    let mut checked = 0;
+    loop {
+        let batch = directory.by_ref().take(BATCH).collect::<Result<Vec<_>>>()?;
+        if batch.is_empty() {
+            return Ok(checked);
+        }
+        directory.with_reader(|reader| {
+            for record in &batch {
+                let problems = check_entry(reader, record, cd_start);
+                visit(record, &problems)?;
+                checked += 1;
+            }
+            Ok(())
+        })?;
+    }
+}
+
+/// Check the archive's headers and report on the entries `args` selects
+/// (`--test-quick`)
+///
+/// # Errors
+///
+/// Returns [`UnzipError::TestFailed`] if any entry fails, after checking all
+/// of them, or an error if the central directory cannot be read, `cancel`
+/// is cancelled or the report cannot be written to stdout
+pub fn quick_test<R: Read + Seek>(reader: R, args: &Args, cancel: &CancelToken) -> Result<()> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let names = NameDecoder::from_args(args)?;
+    let selection = args.matcher();
+    let (mut selected, mut errors) = (0, 0);
+    check_headers(reader, |record, problems| {
+        if cancel.is_cancelled() {
+            return Err(UnzipError::Cancelled);
+        }
+        let name = names.decode_raw(&record.name, record.is_utf8, record.host());
+        if !selection.selects(&name) {
+            return Ok(());
+        }
+        selected += 1;
+        if problems.is_empty() {
+            if args.quiet == 0 {
+                writeln!(out, "    checking: {:<22}   OK", name)?;
+            }
+        } else {
+            errors += 1;
+            if args.quiet < 2 {
+                for problem in problems {
+                    eprintln!("error: {} - {}", name, problem);
+                }
+            }
+        }
+        Ok(())
+    })?;
+
+    if args.quiet < 2 {
+        if errors == 0 {
+            writeln!(
+                out,
+                "No errors detected in headers of {}.  {} files checked.",
+                args.zipfile.display(),
+                selected
+            )?;
+        } else {
+            writeln!(
+                out,
+                "{} error(s) detected in headers of {}.  {} files checked.",
+                errors,
+                args.zipfile.display(),
+                selected
+            )?;
+        }
+    }
+    if errors > 0 {
+        return Err(UnzipError::TestFailed { errors });
+    }
+    Ok(())
+}
+
+/// What is wrong with the headers of the entry `record` describes
+fn check_entry<R: Read + Seek>(
+    reader: &mut R,
+    record: &CentralRecord,
+    cd_start: u64,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    #[allow(deprecated)]
+    let unsupported = matches!(record.compression(), CompressionMethod::Unsupported(_));
+    if unsupported {
+        problems.push(format!("unsupported compression method {}", record.method));
+    }
+    if record.method == 0 && !record.is_encrypted() && record.size != record.compressed_size {
+        problems.push(format!(
+            "stored entry has size {} but compressed size {}",
+            record.size, record.compressed_size
+        ));
+    }
+    if record.header_offset.saturating_add(LOCAL_HEADER_LEN as u64) > cd_start {
+        problems.push(format!(
+            "local header offset {} is past the central directory",
+            record.header_offset
+        ));
+        return problems;
+    }
+    let LocalHeader { record: local, data_start, .. } =
+        match read_local_header(reader, record.header_offset) {
+            Ok(Some(found)) => found,
+            Ok(None) => {
+                problems.push(format!("no local header at offset {}", record.header_offset));
+                return problems;
+            },
+            Err(error) => {
+                problems.push(format!("cannot read local header: {}", error));
+                return problems;
+            },
+        };
+
+    // A name from the central Unicode Path field may have no local counterpart
+    let unicode_path = record.is_utf8 && record.flags & (1 << 11) == 0;
+    if local.name != record.name && !unicode_path {
+        problems.push(format!("local header has name {}", String::from_utf8_lossy(&local.name)));
+    }
+    if local.method != record.method {
+        problems.push(format!(
+            "local header has method {}, central directory {}",
+            local.method, record.method
+        ));
+    }
+    if local.is_encrypted() != record.is_encrypted() {
+        problems.push("local header and central directory disagree on encryption".to_string());
+    }
+    let data_end = data_start.saturating_add(record.compressed_size);
+    if data_end > cd_start {
+        problems
+            .push(format!("data runs {} bytes into the central directory", data_end - cd_start));
+        return problems;
+    }
+    if local.flags & (1 << 3) == 0 {
+        compare_sizes(&mut problems, "local header", record, &local);
+    } else {
+        match read_descriptor(reader, data_end) {
+            Ok(descriptor) => {
+                let descriptor = with_descriptor(record, &descriptor);
+                compare_sizes(&mut problems, "data descriptor", record, &descriptor)
+            },
+            Err(error) => problems.push(format!("cannot read data descriptor: {}", error)),
+        }
+    }
+    problems
+}
+
+/// Record problems where the CRC32 and sizes of `other`, from `source`, differ
+/// from the central record's
+fn compare_sizes(
+    problems: &mut Vec<String>,
+    source: &str,
+    record: &CentralRecord,
+    other: &CentralRecord,
+) {
+    if other.crc32 != record.crc32 {
+        problems.push(format!(
+            "{} has CRC32 {:08x}, central directory {:08x}",
+            source, other.crc32, record.crc32
+        ));
+    }
+    if other.compressed_size != record.compressed_size {
+        problems.push(format!(
+            "{} has compressed size {}, central directory {}",
+            source, other.compressed_size, record.compressed_size
+        ));
+    }
+    if other.size != record.size {
+        problems
+            .push(format!("{} has size {}, central directory {}", source, other.size, record.size));
+    }
+}
+
+/// A copy of `record` with the CRC32 and sizes of `descriptor`, its 64-bit
+/// reading taken only when that agrees with the central record
+fn with_descriptor(record: &CentralRecord, descriptor: &Descriptor) -> CentralRecord {
+    let mut copy = record.clone();
+    copy.crc32 = descriptor.crc32;
+    (copy.compressed_size, copy.size) = match descriptor.wide {
+        Some(wide) if wide == (record.compressed_size, record.size) => wide,
+        _ => descriptor.narrow,
+    };
+    copy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::ZipWriter;
+    use zip::write::SimpleFileOptions;
+
+    /// The problems found with each entry of `data`, by name
+    fn problems(data: &[u8]) -> Vec<(String, Vec<String>)> {
+        let mut found = Vec::new();
+        check_headers(Cursor::new(data), |record, problems| {
+            found.push((String::from_utf8_lossy(&record.name).into_owned(), problems.to_vec()));
+            Ok(())
+        })
+        .unwrap();
+        found
+    }
+
+    fn archive() -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.add_directory("dir/", SimpleFileOptions::default()).unwrap();
+        zip.start_file("dir/a.txt", SimpleFileOptions::default().large_file(true))
+            .unwrap();
+        zip.write_all(&b"quick check\n".repeat(50)).unwrap();
+        let stored = SimpleFileOptions::default().compression_method(CompressionMethod::STORE);
+        zip.start_file("b.bin", stored).unwrap();
+        zip.write_all(b"stored").unwrap();
+        let mut data = b"stub in front".to_vec();
+        data.extend(zip.finish().unwrap().into_inner());
+        data
+    }
+
+    #[test]
+    fn test_check_headers_passes_consistent_archive() {
+        let found = problems(&archive());
+        assert_eq!(found.len(), 3);
+        assert!(found.iter().all(|(_, problems)| problems.is_empty()), "{:?}", found);
+    }
+
+    #[test]
+    fn test_check_headers_finds_inconsistencies() {
+        let data = archive();
+        let local = |name: &[u8]| {
+            let at = data.windows(name.len()).position(|window| window == name).unwrap();
+            at - LOCAL_HEADER_LEN
+        };
+        let (a, b) = (local(b"dir/a.txt"), local(b"b.bin"));
+        let mut damaged = data.clone();
+        damaged[a + 8] = 0; // method: stored
+        damaged[a + 14] ^= 0xff; // CRC32
+        damaged[b + 30] = b'c'; // name
+        let found = problems(&damaged);
+        assert_eq!(found[0].1, Vec::<String>::new());
+        assert_eq!(
+            found[1].1,
+            [
+                "local header has method 0, central directory 8".to_string(),
+                format!(
+                    "local header has CRC32 {:08x}, central directory {:08x}",
+                    crc32fast::hash(&b"quick check\n".repeat(50)) ^ 0xff,
+                    crc32fast::hash(&b"quick check\n".repeat(50))
+                ),
+            ]
+        );
+        assert_eq!(found[2].1, ["local header has name c.bin"]);
+
+        let mut damaged = data.clone();
+        damaged[b..b + 4].copy_from_slice(b"JUNK");
+        let found = problems(&damaged);
+        assert_eq!(found[2].1, [format!("no local header at offset {}", b)]);
+    }
+}
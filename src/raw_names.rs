@@ -0,0 +1,109 @@
+//! Byte-exact entry names for forensic extraction (`--raw-names`)
+//!
+//! Every other name mode decodes names into text: CP437 or the `-O` / `-I`
+//! charset, U+FFFD for bytes that do not decode, `%XX` with
+//! `--invalid-names=escape`. An investigator extracting evidence wants the
+//! names on disk to be the archive's bytes, whatever they are.
+//!
+//! `--raw-names` decodes nothing. Each name is shown as
+//! [`escape_name`](crate::invalid_names::escape_name) writes
+//! it, with `%` and every byte that is not part of valid UTF-8 as `%XX`, in
+//! listings, messages, reports and the patterns it is matched against; that
+//! text maps back to the exact bytes with
+//! [`unescape_name`](crate::invalid_names::unescape_name). On Unix the files
+//! and directories are then created under those exact bytes, here
+//! `r\xe9sum\xe9.doc` and `100%.txt`:
+//!
+//! ```text
+//! $ unzip --raw-names evidence.zip
+//! Archive:  evidence.zip
+//!   inflating: r%E9sum%E9.doc
+//!   inflating: 100%25.txt
+//! ```
+//!
+//! Names are sanitized as usual before their bytes are restored, and a
+//! component whose bytes would be `.`, `..` or hold a `/` or NUL, which only a
+//! `--transform` or `--name-template` can produce, keeps its escaped text.
+//! Elsewhere than Unix, file names are text, and the escaped names are used.
+//! `--raw-names` replaces `-O`, `-I`, `--auto-encoding` and `--invalid-names`.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::path::Path;
+//! use unzip::invalid_names::escape_name;
+//! use unzip::raw_names::raw_output_path;
+//!
+//! let escaped = escape_name(b"docs/r\xe9sum\xe9.doc");
+//! assert_eq!(escaped, "docs/r%E9sum%E9.doc");
+//! let path = raw_output_path(Path::new(&escaped));
+//! # #[cfg(unix)]
+//! assert_eq!(
+//!     std::os::unix::ffi::OsStrExt::as_bytes(path.as_os_str()),
+//!     b"docs/r\xe9sum\xe9.doc"
+//! );
+//! ```
+
+use std::path::{Path, PathBuf};
+
+/// The relative output path `escaped`, built from names escaped by
+/// [`escape_name`](crate::invalid_names::escape_name), with each component made
+/// of the bytes it stands for
+///
+/// Components whose bytes would not be a plain file name stay escaped, and on
+/// hosts without byte file names the whole path does.
+pub fn raw_output_path(escaped: &Path) -> PathBuf {
+    #[cfg(unix)]
+    {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+        use std::path::Component;
+
+        use crate::invalid_names::unescape_name;
+
+        escaped
+            .components()
+            .map(|component| match component {
+                Component::Normal(text) => {
+                    let raw = unescape_name(&text.to_string_lossy());
+                    let plain = !matches!(raw.as_slice(), b"." | b"..")
+                        && !raw.contains(&b'/')
+                        && !raw.contains(&0);
+                    if plain {
+                        OsStr::from_bytes(&raw).to_os_string()
+                    } else {
+                        text.to_os_string()
+                    }
+                },
+                other => other.as_os_str().to_os_string(),
+            })
+            .collect()
+    }
+    #[cfg(not(unix))]
+    {
+        escaped.to_path_buf()
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::ffi::OsStrExt;
+
+    fn bytes(path: &Path) -> &[u8] {
+        path.as_os_str().as_bytes()
+    }
+
+    #[test]
+    fn test_raw_output_path_restores_bytes() {
+        assert_eq!(bytes(&raw_output_path(Path::new("a/100%25.txt"))), b"a/100%.txt");
+        assert_eq!(bytes(&raw_output_path(Path::new("%FF%FE/x"))), b"\xff\xfe/x");
+    }
+
+    #[test]
+    fn test_raw_output_path_keeps_unsafe_components_escaped() {
+        assert_eq!(bytes(&raw_output_path(Path::new("%2E%2E/x"))), b"%2E%2E/x");
+        assert_eq!(bytes(&raw_output_path(Path::new("a%2Fb"))), b"a%2Fb");
+        assert_eq!(bytes(&raw_output_path(Path::new("a%00b"))), b"a%00b");
+    }
+}
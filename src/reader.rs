@@ -0,0 +1,144 @@
+//! Shared archive readers for parallel workers
+//!
+//! A `ZipArchive` keeps its parsed central directory behind an `Arc`, so cloning an
+//! archive whose reader is `Clone` is cheap: every clone shares the metadata and gets
+//! its own read position. [`MmapReader`] is such a reader over a memory-mapped file,
+//! and [`split_for_threads`] hands out one archive handle per worker, so parallel
+//! subsystems neither reopen the file nor serialize reads behind a mutex.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use std::sync::Arc;
+//! use memmap2::Mmap;
+//! use zip::ZipArchive;
+//! use unzip::reader::{MmapReader, split_for_threads};
+//!
+//! let file = File::open("archive.zip")?;
+//! let mmap = Arc::new(unsafe { Mmap::map(&file)? });
+//! let archive = ZipArchive::new(MmapReader::new(mmap))?;
+//! for mut worker_archive in split_for_threads(&archive, 4) {
+//!     std::thread::spawn(move || worker_archive.by_index(0).map(|f| f.size()));
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use memmap2::Mmap;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::sync::Arc;
+use zip::ZipArchive;
+
+/// Cloneable `Read + Seek` cursor over a shared memory map
+///
+/// Clones share the mapping and start at the position of the original.
+#[derive(Debug, Clone)]
+pub struct MmapReader {
+    cursor: Cursor<SharedMmap>,
+}
+
+#[derive(Debug, Clone)]
+struct SharedMmap(Arc<Mmap>);
+
+impl AsRef<[u8]> for SharedMmap {
+    fn as_ref(&self) -> &[u8] {
+        &self.0[..]
+    }
+}
+
+impl MmapReader {
+    /// Create a reader positioned at the start of the mapping
+    pub fn new(mmap: Arc<Mmap>) -> Self {
+        Self { cursor: Cursor::new(SharedMmap(mmap)) }
+    }
+}
+
+impl Read for MmapReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.cursor.read(buf)
+    }
+}
+
+impl Seek for MmapReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.cursor.seek(pos)
+    }
+}
+
+/// Produce `n` independent handles to the same archive
+///
+/// The central directory is parsed once by the caller; each handle only clones the
+/// reader. At least one handle is always returned.
+///
+/// # Arguments
+///
+/// * `archive` - Archive opened over a cheaply cloneable reader
+/// * `n` - Number of workers
+pub fn split_for_threads<R: Read + Seek + Clone>(
+    archive: &ZipArchive<R>,
+    n: usize,
+) -> Vec<ZipArchive<R>> {
+    (0..n.max(1)).map(|_| archive.clone()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::ZipWriter;
+    use zip::write::SimpleFileOptions;
+
+    fn mmap_of(files: &[(&str, &[u8])]) -> (tempfile::NamedTempFile, Arc<Mmap>) {
+        let mut temp = tempfile::NamedTempFile::new().unwrap();
+        {
+            let mut zip = ZipWriter::new(temp.as_file_mut());
+            let options =
+                SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+            for (name, content) in files {
+                zip.start_file(*name, options).unwrap();
+                zip.write_all(content).unwrap();
+            }
+            zip.finish().unwrap();
+        }
+        let mmap = unsafe { Mmap::map(temp.as_file()) }.unwrap();
+        (temp, Arc::new(mmap))
+    }
+
+    #[test]
+    fn test_mmap_reader_clones_have_independent_positions() {
+        let (_temp, mmap) = mmap_of(&[("a.txt", b"alpha")]);
+        let mut reader = MmapReader::new(mmap);
+        let mut clone = reader.clone();
+
+        let mut first = [0u8; 2];
+        reader.read_exact(&mut first).unwrap();
+        let mut second = [0u8; 2];
+        clone.read_exact(&mut second).unwrap();
+
+        assert_eq!(first, *b"PK");
+        assert_eq!(second, *b"PK");
+        assert_eq!(reader.stream_position().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_split_for_threads_reads_in_parallel() {
+        let (_temp, mmap) = mmap_of(&[("a.txt", b"alpha"), ("b.txt", b"beta")]);
+        let archive = ZipArchive::new(MmapReader::new(mmap)).unwrap();
+
+        let handles: Vec<_> = split_for_threads(&archive, 2)
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut archive)| {
+                std::thread::spawn(move || {
+                    let mut content = String::new();
+                    archive.by_index(i).unwrap().read_to_string(&mut content).unwrap();
+                    content
+                })
+            })
+            .collect();
+        let contents: Vec<String> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        assert_eq!(contents, vec!["alpha", "beta"]);
+        assert_eq!(split_for_threads(&archive, 0).len(), 1);
+    }
+}
@@ -0,0 +1,87 @@
+//! Replacing read-only files (`--force-readonly`)
+//!
+//! A destination file without write permission, such as one extracted earlier
+//! from an entry with mode `0444`, cannot be opened for writing. Rather than
+//! letting such a file end the whole run, it is skipped with a warning, as
+//! [`SkipReason::ReadOnly`](crate::events::SkipReason::ReadOnly), even with `-o`.
+//! With `--force-readonly` it is made writable, replaced, and given its previous
+//! permissions back, so it stays read-only.
+//!
+//! A file counts as read-only by its permission bits, so the decision is the same
+//! for root, who could write it anyway. Only files written in place are affected:
+//! `--transactional` and `--shared-dest` replace files through a rename, which
+//! needs write permission on the directory only.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::path::Path;
+//! use unzip::readonly::is_read_only;
+//!
+//! if is_read_only(Path::new("out/config.ini")) {
+//!     println!("pass --force-readonly to replace out/config.ini");
+//! }
+//! ```
+
+use std::fs::{self, Permissions};
+use std::io;
+use std::path::Path;
+
+/// Whether `path` is an existing regular file without write permission
+pub fn is_read_only(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.permissions().readonly())
+}
+
+/// Make the file at `path` writable if it is read-only, returning its previous
+/// permissions to restore once it has been replaced
+///
+/// # Errors
+///
+/// Returns an error if the permissions cannot be changed
+pub(crate) fn unlock(path: &Path) -> io::Result<Option<Permissions>> {
+    if !is_read_only(path) {
+        return Ok(None);
+    }
+    let previous = fs::metadata(path)?.permissions();
+    fs::set_permissions(path, writable(&previous))?;
+    Ok(Some(previous))
+}
+
+/// `permissions` with write permission for the owner
+fn writable(permissions: &Permissions) -> Permissions {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        Permissions::from_mode(permissions.mode() | 0o200)
+    }
+    #[cfg(not(unix))]
+    {
+        let mut permissions = permissions.clone();
+        permissions.set_readonly(false);
+        permissions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unlock_returns_previous_permissions() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("locked.txt");
+        fs::write(&path, b"data").unwrap();
+        assert!(!is_read_only(&path));
+        assert_eq!(unlock(&path).unwrap(), None);
+
+        let mut permissions = fs::metadata(&path).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&path, permissions.clone()).unwrap();
+        assert!(is_read_only(&path));
+        assert!(!is_read_only(temp_dir.path()));
+        assert!(!is_read_only(&temp_dir.path().join("missing")));
+
+        assert_eq!(unlock(&path).unwrap(), Some(permissions));
+        assert!(!is_read_only(&path));
+    }
+}
@@ -0,0 +1,178 @@
+//! Recompression of extracted files (`--compress-output`)
+//!
+//! Logs and other bulky text are often compressed again right after extraction.
+//! Doing that as a second step reads and writes every file twice. With
+//! `--compress-output=zst[:LEVEL]`, each file entry is compressed with Zstandard
+//! while it is extracted and written as `NAME.zst`; the raw data never reaches
+//! the disk. Directories are created as usual.
+//!
+//! Sizes reported and limited (`--max-output-size`) are those of the
+//! uncompressed data. Timestamps and permissions of the entry apply to the
+//! `.zst` file.
+//!
+//! # Examples
+//!
+//! ```
+//! use unzip::recompress::{OutputCompression, parse_output_compression};
+//!
+//! assert_eq!(parse_output_compression("zst:19"), Ok(OutputCompression::Zstd { level: 19 }));
+//! assert_eq!(parse_output_compression("zst"), Ok(OutputCompression::Zstd { level: 3 }));
+//! ```
+
+use std::ffi::OsString;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// Zstandard level used when none is given, zstd's own default
+pub const DEFAULT_ZSTD_LEVEL: i32 = 3;
+
+/// Format extracted files are compressed into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputCompression {
+    /// Zstandard at `level` (1 to 22), written as `NAME.zst`
+    Zstd {
+        /// Compression level
+        level: i32,
+    },
+}
+
+impl OutputCompression {
+    /// File name extension appended to compressed files, without the dot
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Zstd { .. } => "zst",
+        }
+    }
+}
+
+impl fmt::Display for OutputCompression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Zstd { level } => write!(f, "zst:{}", level),
+        }
+    }
+}
+
+/// Parse a `--compress-output` value: `zst` or `zst:LEVEL`, LEVEL from 1 to 22
+///
+/// # Errors
+///
+/// Returns a message suitable for clap if the format or level is unknown
+pub fn parse_output_compression(value: &str) -> Result<OutputCompression, String> {
+    let (format, level) = match value.split_once(':') {
+        Some((format, level)) => (format, Some(level)),
+        None => (value, None),
+    };
+    match format.to_ascii_lowercase().as_str() {
+        "zst" | "zstd" => {},
+        _ => return Err(format!("invalid output compression: {} (expected zst[:LEVEL])", value)),
+    }
+    let level = match level.map(str::parse) {
+        None => DEFAULT_ZSTD_LEVEL,
+        Some(Ok(level @ 1..=22)) => level,
+        Some(_) => return Err(format!("invalid zstd level: {} (expected 1 to 22)", value)),
+    };
+    Ok(OutputCompression::Zstd { level })
+}
+
+/// Where an entry extracted to `path` is written: `path` itself, or with the
+/// extension of `compression` appended
+pub(crate) fn output_path(path: PathBuf, compression: Option<OutputCompression>) -> PathBuf {
+    let Some(compression) = compression else {
+        return path;
+    };
+    let mut name = OsString::from(path);
+    name.push(".");
+    name.push(compression.extension());
+    PathBuf::from(name)
+}
+
+/// Writer for an entry's data, compressing it if asked to
+pub(crate) enum OutputWriter<W: Write> {
+    /// Data is written as is
+    Plain(W),
+    /// Data is compressed with Zstandard
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+}
+
+impl<W: Write> OutputWriter<W> {
+    /// Write to `inner`, through a compressor for `compression`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the compressor cannot be set up
+    pub(crate) fn new(inner: W, compression: Option<OutputCompression>) -> io::Result<Self> {
+        match compression {
+            None => Ok(Self::Plain(inner)),
+            Some(OutputCompression::Zstd { level }) => {
+                zstd::stream::write::Encoder::new(inner, level).map(Self::Zstd)
+            },
+        }
+    }
+
+    /// Complete the compressed stream and return the inner writer
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the end of the stream cannot be written
+    pub(crate) fn finish(self) -> io::Result<W> {
+        match self {
+            Self::Plain(inner) => Ok(inner),
+            Self::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for OutputWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(inner) => inner.write(buf),
+            Self::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        match self {
+            Self::Plain(inner) => inner.write_all(buf),
+            Self::Zstd(encoder) => encoder.write_all(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(inner) => inner.flush(),
+            Self::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_output_compression() {
+        assert_eq!(parse_output_compression("ZSTD:1"), Ok(OutputCompression::Zstd { level: 1 }));
+        assert!(parse_output_compression("zst:0").is_err());
+        assert!(parse_output_compression("zst:23").is_err());
+        assert!(parse_output_compression("zst:fast").is_err());
+        assert!(parse_output_compression("gz").is_err());
+        assert_eq!(OutputCompression::Zstd { level: 7 }.to_string(), "zst:7");
+    }
+
+    #[test]
+    fn test_writer_round_trip() {
+        let compression = Some(OutputCompression::Zstd { level: DEFAULT_ZSTD_LEVEL });
+        let path = output_path(PathBuf::from("logs/app.log"), compression);
+        assert_eq!(path, PathBuf::from("logs/app.log.zst"));
+        assert_eq!(output_path(PathBuf::from("a.txt"), None), PathBuf::from("a.txt"));
+
+        let data = b"line\n".repeat(1000);
+        let mut writer = OutputWriter::new(Vec::new(), compression).unwrap();
+        writer.write_all(&data).unwrap();
+        let compressed = writer.finish().unwrap();
+        assert!(compressed.len() < data.len() / 10);
+        assert_eq!(zstd::decode_all(&compressed[..]).unwrap(), data);
+    }
+}
@@ -0,0 +1,65 @@
+//! Signatures and fixed sizes of the ZIP records parsed by hand
+//!
+//! The zip crate does not expose every record, and damaged archives have to be
+//! scanned for them, so several modules read records themselves. They share these
+//! values from section 4.3 of PKWARE's APPNOTE, and the readers for the
+//! little-endian fields of records. Signatures are little-endian `u32`s; scans of
+//! raw bytes compare against their `to_le_bytes()`.
+//!
+//! # Examples
+//!
+//! ```
+//! use unzip::records::{EOCD_LEN, EOCD_SIGNATURE};
+//!
+//! let mut record = EOCD_SIGNATURE.to_le_bytes().to_vec();
+//! record.resize(EOCD_LEN, 0);
+//! assert!(record.starts_with(b"PK\x05\x06"));
+//! ```
+
+/// Signature of a local file header
+pub const LOCAL_SIGNATURE: u32 = 0x0403_4b50;
+
+/// Signature that may start a data descriptor
+pub const DESCRIPTOR_SIGNATURE: u32 = 0x0807_4b50;
+
+/// Signature of a central directory file header
+pub const CENTRAL_SIGNATURE: u32 = 0x0201_4b50;
+
+/// Signature of the Zip64 end of central directory record
+pub const ZIP64_EOCD_SIGNATURE: u32 = 0x0606_4b50;
+
+/// Signature of the Zip64 end of central directory locator
+pub const ZIP64_LOCATOR_SIGNATURE: u32 = 0x0706_4b50;
+
+/// Signature of the end of central directory record
+pub const EOCD_SIGNATURE: u32 = 0x0605_4b50;
+
+/// Size of a local file header without its name and extra field
+pub const LOCAL_HEADER_LEN: usize = 30;
+
+/// Size of a central directory file header without its variable fields
+pub const CENTRAL_HEADER_LEN: usize = 46;
+
+/// Size of the Zip64 end of central directory record without extensible data
+pub const ZIP64_EOCD_LEN: usize = 56;
+
+/// Size of the Zip64 end of central directory locator
+pub const ZIP64_LOCATOR_LEN: usize = 20;
+
+/// Size of the end of central directory record without its comment
+pub const EOCD_LEN: usize = 22;
+
+/// The little-endian `u16` at `at` in `data`, or `None` past its end
+pub(crate) fn u16_at(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+/// The little-endian `u32` at `at` in `data`, or `None` past its end
+pub(crate) fn u32_at(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// The little-endian `u64` at `at` in `data`, or `None` past its end
+pub(crate) fn u64_at(data: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(data.get(at..at + 8)?.try_into().ok()?))
+}
@@ -0,0 +1,272 @@
+//! Recovery of entries with a mislabelled compression method (`--heuristic-decode`)
+//!
+//! Some broken writers, including a few 7-Zip variants, record deflate data with
+//! method Stored or stored data with method Deflate. Such entries fail their CRC
+//! check or do not inflate at all. With `--heuristic-decode`, an entry that fails
+//! like that is read again with the other interpretation, and accepted only if the
+//! result matches both the stored CRC32 and the declared size.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use std::io;
+//! use zip::ZipArchive;
+//! use unzip::recovery::decode_relabelled;
+//!
+//! let mut archive = ZipArchive::new(File::open("broken.zip")?)?;
+//! if let Some(relabel) = decode_relabelled(archive.by_index_raw(0)?, &mut io::sink())? {
+//!     println!("entry 0 decodes as {}", relabel);
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use flate2::read::DeflateDecoder;
+use std::fmt;
+use std::io::{self, Read, Write};
+use zip::CompressionMethod;
+use zip::read::ZipFile;
+
+use crate::error::{Result, UnzipError};
+
+/// The interpretation that made a mislabelled entry decode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relabel {
+    /// Labelled Stored, but holds deflate data
+    DeflatedAsStored,
+    /// Labelled Deflate, but holds the data as is
+    StoredAsDeflated,
+}
+
+impl Relabel {
+    /// The alternative to try for an entry stored with `method`
+    pub fn for_method(method: CompressionMethod) -> Option<Self> {
+        match method {
+            CompressionMethod::Stored => Some(Self::DeflatedAsStored),
+            CompressionMethod::Deflated => Some(Self::StoredAsDeflated),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Relabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DeflatedAsStored => write!(f, "deflate data labelled Stored"),
+            Self::StoredAsDeflated => write!(f, "stored data labelled Deflate"),
+        }
+    }
+}
+
+/// Whether an error reading an entry means its data did not decode, as opposed to
+/// a failing destination, a crossed limit or a cancelled run
+pub fn is_decode_error(error: &UnzipError) -> bool {
+    match error {
+        UnzipError::Io(source) | UnzipError::Read { source, .. } => is_corrupt_data(source),
+        UnzipError::CrcMismatch { .. } => true,
+        _ => false,
+    }
+}
+
+/// Whether an error from an entry reader means its data is corrupt, which is how
+/// the zip crate reports CRC mismatches and undecodable streams
+pub fn is_corrupt_data(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::InvalidData | io::ErrorKind::InvalidInput | io::ErrorKind::UnexpectedEof
+    )
+}
+
+/// Decode an entry with the other of Stored and Deflate, writing the data to `out`
+///
+/// `raw` must come from [`zip::ZipArchive::by_index_raw`]. Returns the
+/// interpretation that worked, or `None` if the entry is encrypted, uses another
+/// method, or does not match its CRC32 and size this way either; whatever was
+/// written to `out` is then garbage.
+///
+/// # Errors
+///
+/// Returns an error only if writing to `out` fails
+pub fn decode_relabelled(raw: ZipFile<'_>, out: &mut dyn Write) -> Result<Option<Relabel>> {
+    let Some(relabel) = Relabel::for_method(raw.compression()) else {
+        return Ok(None);
+    };
+    if raw.encrypted() {
+        return Ok(None);
+    }
+    let (size, crc32) = (raw.size(), raw.crc32());
+    let reader: Box<dyn Read + '_> = match relabel {
+        Relabel::DeflatedAsStored => Box::new(DeflateDecoder::new(raw)),
+        Relabel::StoredAsDeflated => Box::new(raw),
+    };
+    // One byte past the declared size is enough to tell the data is wrong
+    let mut reader = reader.take(size.saturating_add(1));
+
+    let mut hasher = crc32fast::Hasher::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut produced = 0u64;
+    loop {
+        let n = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(_) => return Ok(None),
+        };
+        hasher.update(&buffer[..n]);
+        out.write_all(&buffer[..n])?;
+        produced += n as u64;
+    }
+
+    Ok((produced == size && hasher.finalize() == crc32).then_some(relabel))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::Args;
+    use crate::cancel::CancelToken;
+    use crate::events::ExtractObserver;
+    use crate::options::{ExtractOptions, OverwritePolicy};
+    use clap::Parser;
+    use flate2::Compression;
+    use flate2::write::DeflateEncoder;
+    use std::fs;
+    use std::io::Cursor;
+    use zip::ZipArchive;
+
+    /// Archive with one entry `a.txt` holding `data` under `method`, with the CRC32
+    /// and size of `content`
+    fn mislabelled_zip(method: u16, data: &[u8], content: &[u8]) -> Vec<u8> {
+        let crc = crc32fast::hash(content).to_le_bytes();
+        let sizes = [(data.len() as u32).to_le_bytes(), (content.len() as u32).to_le_bytes()];
+        let mut common = Vec::new();
+        common.extend_from_slice(&20u16.to_le_bytes()); // version needed
+        common.extend_from_slice(&0u16.to_le_bytes()); // flags
+        common.extend_from_slice(&method.to_le_bytes());
+        common.extend_from_slice(&[0, 0, 0x21, 0]); // 1980-01-01 00:00
+        common.extend_from_slice(&crc);
+        common.extend_from_slice(&sizes.concat());
+        common.extend_from_slice(&5u16.to_le_bytes()); // name length
+        common.extend_from_slice(&0u16.to_le_bytes()); // extra length
+
+        let mut buf = b"PK\x03\x04".to_vec();
+        buf.extend_from_slice(&common);
+        buf.extend_from_slice(b"a.txt");
+        buf.extend_from_slice(data);
+
+        let central_start = buf.len();
+        buf.extend_from_slice(b"PK\x01\x02");
+        buf.extend_from_slice(&((3u16 << 8) | 20).to_le_bytes()); // made by Unix
+        buf.extend_from_slice(&common);
+        buf.extend_from_slice(&[0; 6]); // comment length, disk, internal attributes
+        buf.extend_from_slice(&(0o100644u32 << 16).to_le_bytes());
+        buf.extend_from_slice(&0u32.to_le_bytes()); // local header offset
+        buf.extend_from_slice(b"a.txt");
+        let central_size = (buf.len() - central_start) as u32;
+
+        buf.extend_from_slice(b"PK\x05\x06");
+        buf.extend_from_slice(&[0, 0, 0, 0, 1, 0, 1, 0]);
+        buf.extend_from_slice(&central_size.to_le_bytes());
+        buf.extend_from_slice(&(central_start as u32).to_le_bytes());
+        buf.extend_from_slice(&0u16.to_le_bytes());
+        buf
+    }
+
+    fn deflate(content: &[u8]) -> Vec<u8> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(content).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn content() -> Vec<u8> {
+        b"hello heuristic decode ".repeat(40)
+    }
+
+    #[test]
+    fn test_deflate_data_labelled_stored() {
+        let zip_data = mislabelled_zip(0, &deflate(&content()), &content());
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+
+        let mut out = Vec::new();
+        let relabel = decode_relabelled(archive.by_index_raw(0).unwrap(), &mut out).unwrap();
+        assert_eq!(relabel, Some(Relabel::DeflatedAsStored));
+        assert_eq!(out, content());
+    }
+
+    #[test]
+    fn test_stored_data_labelled_deflate() {
+        let zip_data = mislabelled_zip(8, &content(), &content());
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+
+        let mut out = Vec::new();
+        let relabel = decode_relabelled(archive.by_index_raw(0).unwrap(), &mut out).unwrap();
+        assert_eq!(relabel, Some(Relabel::StoredAsDeflated));
+        assert_eq!(out, content());
+    }
+
+    #[test]
+    fn test_corrupt_data_is_not_recovered() {
+        let mut data = deflate(&content());
+        let last = data.len() - 1;
+        data[last] ^= 0xff;
+        let zip_data = mislabelled_zip(0, &data, &content());
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+
+        let relabel = decode_relabelled(archive.by_index_raw(0).unwrap(), &mut io::sink());
+        assert_eq!(relabel.unwrap(), None);
+    }
+
+    #[test]
+    fn test_is_decode_error() {
+        let crc = io::Error::new(io::ErrorKind::InvalidData, "Invalid checksum");
+        assert!(is_decode_error(&UnzipError::Io(crc)));
+        assert!(!is_decode_error(&UnzipError::Cancelled));
+        let full = io::Error::new(io::ErrorKind::StorageFull, "disk full");
+        assert!(!is_decode_error(&UnzipError::Io(full)));
+    }
+
+    #[derive(Default)]
+    struct Warnings(Vec<String>);
+
+    impl ExtractObserver for Warnings {
+        fn warning(&mut self, message: &str) {
+            self.0.push(message.to_string());
+        }
+    }
+
+    #[test]
+    fn test_extract_recovers_only_when_enabled() {
+        let zip_data = mislabelled_zip(0, &deflate(&content()), &content());
+        let temp_dir = tempfile::tempdir().unwrap();
+        let options = ExtractOptions::new()
+            .output_dir(temp_dir.path())
+            .overwrite(OverwritePolicy::Always)
+            .quiet(2);
+
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+        let err = crate::extract_archive(&mut archive, &options).unwrap_err();
+        assert!(is_decode_error(&err), "{:?}", err);
+
+        let options = options.heuristic_decode(true).on_event(|_| {});
+        crate::extract_archive(&mut archive, &options).unwrap();
+        assert_eq!(fs::read(temp_dir.path().join("a.txt")).unwrap(), content());
+    }
+
+    #[test]
+    fn test_archive_reports_recovered_entries() {
+        let zip_data = mislabelled_zip(8, &content(), &content());
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+        let mut args = Args::parse_from(["unzip", "-t", "-qq", "test.zip"]);
+        let cancel = CancelToken::new();
+
+        let mut warnings = Warnings::default();
+        let report = crate::test_archive_with(&mut archive, &args, &mut warnings, &cancel).unwrap();
+        assert_eq!(report.errors, 1);
+
+        args.heuristic_decode = true;
+        let mut warnings = Warnings::default();
+        let report = crate::test_archive_with(&mut archive, &args, &mut warnings, &cancel).unwrap();
+        assert_eq!(report, crate::TestReport { tested: 1, errors: 0 });
+        assert_eq!(warnings.0.len(), 1);
+        assert!(warnings.0[0].contains("stored data labelled Deflate"), "{:?}", warnings.0);
+    }
+}
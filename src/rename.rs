@@ -0,0 +1,68 @@
+//! Extraction under free names next to existing files (`--auto-rename`)
+//!
+//! Collecting files from many archives into one directory runs into names that
+//! already exist. Instead of skipping such an entry (the default, `-n`) or
+//! replacing the file (`-o`), `--auto-rename` writes it next to the existing
+//! one under the first free name of `NAME (1).EXT`, `NAME (2).EXT`, ..., the
+//! way file managers and browsers do. The number goes before the last
+//! extension: `report.txt` becomes `report (1).txt`, `.profile` becomes
+//! `.profile (1)`.
+//!
+//! The same names are offered by the `[r]ename` answer of the `--compat=infozip`
+//! overwrite prompt: an empty new name picks the first free one.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::path::Path;
+//! use unzip::rename::free_path;
+//!
+//! let taken = [Path::new("out/report.txt"), Path::new("out/report (1).txt")];
+//! let free = free_path(Path::new("out/report.txt"), |path| taken.contains(&path));
+//! assert_eq!(free, Path::new("out/report (2).txt"));
+//! ```
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// First of `NAME (1).EXT`, `NAME (2).EXT`, ... next to `path` for which `taken`
+/// is false
+pub fn free_path(path: &Path, taken: impl Fn(&Path) -> bool) -> PathBuf {
+    (1u64..)
+        .map(|n| numbered(path, n))
+        .find(|candidate| !taken(candidate))
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// `path` with ` (n)` inserted before the extension of its file name
+fn numbered(path: &Path, n: u64) -> PathBuf {
+    let mut name = OsString::from(path.file_stem().unwrap_or_default());
+    name.push(format!(" ({})", n));
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_numbered_names() {
+        assert_eq!(numbered(Path::new("a/file.txt"), 1), Path::new("a/file (1).txt"));
+        assert_eq!(numbered(Path::new("logs.tar.gz"), 2), Path::new("logs.tar (2).gz"));
+        assert_eq!(numbered(Path::new(".profile"), 3), Path::new(".profile (3)"));
+        assert_eq!(numbered(Path::new("Makefile"), 10), Path::new("Makefile (10)"));
+    }
+
+    #[test]
+    fn test_free_path_skips_taken_names() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("a.txt");
+        std::fs::write(temp_dir.path().join("a (1).txt"), "taken").unwrap();
+        assert_eq!(free_path(&path, Path::exists), temp_dir.path().join("a (2).txt"));
+        assert_eq!(free_path(&path, |_| false), temp_dir.path().join("a (1).txt"));
+    }
+}
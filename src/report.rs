@@ -0,0 +1,426 @@
+//! Machine-readable test reports (`-t --report FORMAT`)
+//!
+//! Test harnesses want one result per entry rather than the console lines of
+//! `unzip -t`. `--report=tap` writes the [Test Anything Protocol] to stdout as the
+//! entries are tested, so `prove` and similar consumers see results while a large
+//! archive is still being read:
+//!
+//! ```text
+//! TAP version 13
+//! ok 1 - docs/readme.txt
+//! not ok 2 - data/blob.bin
+//!   ---
+//!   message: 'CRC mismatch (stored: 1c291ca3, computed: 0d4a1185)'
+//!   ...
+//! 1..2
+//! ```
+//!
+//! Entries excluded by patterns are not tests and do not appear. Warnings, such as
+//! entries recovered by `--heuristic-decode`, become `#` comments, and an error that
+//! stops testing (a crossed limit, an unreadable archive) ends the stream with
+//! `Bail out!`. The plan comes last because the number of tested entries is only
+//! known at the end. The exit code is the same as without `--report`.
+//!
+//! [Test Anything Protocol]: https://testanything.org/tap-version-13-specification.html
+//!
+//! `--report=json` writes one JSON document for pipelines that triage bad
+//! archives, streamed as the entries are tested. Each tested entry has a status
+//! (`ok`, or the [`TestFailure`] label: `crc-mismatch` with the stored and
+//! computed CRC-32, `unsupported-method`, `decrypt-failed` or `corrupt`) and
+//! the summary counts the failures by status:
+//!
+//! ```text
+//! {"archive":"data.zip","entries":[
+//! {"name":"docs/readme.txt","status":"ok","size":1024},
+//! {"name":"data/blob.bin","status":"crc-mismatch","message":"CRC mismatch (stored: 1c291ca3, computed: 0d4a1185)","stored_crc32":"1c291ca3","computed_crc32":"0d4a1185"}
+//! ],"warnings":[],"summary":{"tested":2,"ok":1,"failed":1,"skipped":0,"failures":{"crc-mismatch":1}}}
+//! ```
+//!
+//! Skipped entries count in the summary only. An error that stops testing
+//! closes the document with an `"error"` member before the summary, so it stays
+//! valid JSON.
+//!
+//! # Examples
+//!
+//! ```
+//! use unzip::ExtractObserver;
+//! use unzip::report::TapReporter;
+//!
+//! let mut tap = TapReporter::new(Vec::new());
+//! tap.entry_finished("a.txt", None, 5);
+//! tap.entry_failed("b#1.txt", "CRC mismatch");
+//! let out = String::from_utf8(tap.finish()?).unwrap();
+//! assert!(out.contains("ok 1 - a.txt\nnot ok 2 - b\\#1.txt\n"));
+//! assert!(out.ends_with("1..2\n"));
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::events::{ExtractObserver, SkipReason, TestFailure};
+use crate::list::json_string;
+
+/// Format of the report `--report` writes instead of the console output of `-t`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// Test Anything Protocol, version 13
+    Tap,
+    /// One JSON document with a status per entry and a summary
+    Json,
+}
+
+impl fmt::Display for ReportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tap => write!(f, "tap"),
+            Self::Json => write!(f, "json"),
+        }
+    }
+}
+
+/// Parse a `--report` format: `tap` or `json`
+///
+/// # Errors
+///
+/// Returns a message suitable for clap if the format is unknown
+pub fn parse_report_format(value: &str) -> std::result::Result<ReportFormat, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "tap" => Ok(ReportFormat::Tap),
+        "json" => Ok(ReportFormat::Json),
+        _ => Err(format!("invalid report format: {} (expected tap or json)", value)),
+    }
+}
+
+/// [`ExtractObserver`] writing test results as TAP, one line per tested entry
+///
+/// Each line is flushed as soon as it is written. Write errors are kept and
+/// returned by [`TapReporter::finish`]; nothing more is written after one.
+pub struct TapReporter<W: Write> {
+    out: W,
+    started: bool,
+    tests: usize,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> TapReporter<W> {
+    /// Start a report on `out`; the version line is written with the first line
+    pub fn new(out: W) -> Self {
+        Self { out, started: false, tests: 0, error: None }
+    }
+
+    /// End the stream with `Bail out!` and `reason`, for an error that stopped testing
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error writing to the output
+    pub fn bail_out(mut self, reason: &str) -> io::Result<W> {
+        self.write(&format!("Bail out! {}\n", single_line(reason)));
+        self.into_inner()
+    }
+
+    /// Write the plan, `1..N` for the N entries tested, and return the output
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error writing to the output
+    pub fn finish(mut self) -> io::Result<W> {
+        let plan = format!("1..{}\n", self.tests);
+        self.write(&plan);
+        self.into_inner()
+    }
+
+    fn into_inner(mut self) -> io::Result<W> {
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => Ok(self.out),
+        }
+    }
+
+    fn result(&mut self, ok: bool, name: &str) {
+        self.tests += 1;
+        let status = if ok { "ok" } else { "not ok" };
+        let line = format!("{} {} - {}\n", status, self.tests, escape_description(name));
+        self.write(&line);
+    }
+
+    fn write(&mut self, text: &str) {
+        if self.error.is_some() {
+            return;
+        }
+        let header = if self.started { "" } else { "TAP version 13\n" };
+        self.started = true;
+        let result = self
+            .out
+            .write_all(header.as_bytes())
+            .and_then(|()| self.out.write_all(text.as_bytes()))
+            .and_then(|()| self.out.flush());
+        if let Err(error) = result {
+            self.error = Some(error);
+        }
+    }
+}
+
+impl<W: Write> ExtractObserver for TapReporter<W> {
+    fn entry_finished(&mut self, name: &str, _path: Option<&Path>, _bytes: u64) {
+        self.result(true, name);
+    }
+
+    fn entry_failed(&mut self, name: &str, error: &str) {
+        self.result(false, name);
+        let diagnostic = format!("  ---\n  message: {}\n  ...\n", yaml_string(error));
+        self.write(&diagnostic);
+    }
+
+    fn warning(&mut self, message: &str) {
+        let comment = format!("# {}\n", single_line(message.trim_start()));
+        self.write(&comment);
+    }
+}
+
+/// A report `-t --report` streams, ended once testing stops
+pub(crate) trait Report: ExtractObserver {
+    /// End the report, with the error that stopped testing if there was one
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error writing to the output
+    fn end(self: Box<Self>, error: Option<&str>) -> io::Result<()>;
+}
+
+impl<W: Write> Report for TapReporter<W> {
+    fn end(self: Box<Self>, error: Option<&str>) -> io::Result<()> {
+        match error {
+            Some(reason) => self.bail_out(reason).map(drop),
+            None => self.finish().map(drop),
+        }
+    }
+}
+
+/// [`ExtractObserver`] writing test results as one JSON document, an entry at a
+/// time
+///
+/// Each entry is flushed as soon as it is written. Write errors are kept and
+/// returned by [`JsonReporter::finish`]; nothing more is written after one.
+pub struct JsonReporter<W: Write> {
+    out: W,
+    archive: String,
+    entries: usize,
+    ok: usize,
+    skipped: usize,
+    failures: BTreeMap<&'static str, usize>,
+    warnings: Vec<String>,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> JsonReporter<W> {
+    /// Start a report on `out` about the archive named `archive`; the opening
+    /// of the document is written with the first entry
+    pub fn new(out: W, archive: &str) -> Self {
+        Self {
+            out,
+            archive: archive.to_string(),
+            entries: 0,
+            ok: 0,
+            skipped: 0,
+            failures: BTreeMap::new(),
+            warnings: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Close the document with `"error": reason` and the summary, for an error
+    /// that stopped testing
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error writing to the output
+    pub fn bail_out(self, reason: &str) -> io::Result<W> {
+        self.close(Some(reason))
+    }
+
+    /// Close the document with the warnings and the summary, and return the
+    /// output
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error writing to the output
+    pub fn finish(self) -> io::Result<W> {
+        self.close(None)
+    }
+
+    fn close(mut self, reason: Option<&str>) -> io::Result<W> {
+        let mut text = String::new();
+        if self.entries == 0 {
+            text.push_str(&self.opening());
+        }
+        let warnings: Vec<String> = self.warnings.iter().map(|w| json_string(w)).collect();
+        text.push_str(&format!("\n],\"warnings\":[{}]", warnings.join(",")));
+        if let Some(reason) = reason {
+            text.push_str(&format!(",\"error\":{}", json_string(reason)));
+        }
+        let failed: usize = self.failures.values().sum();
+        let failures: Vec<String> = (self.failures.iter())
+            .map(|(status, count)| format!("\"{}\":{}", status, count))
+            .collect();
+        text.push_str(&format!(
+            ",\"summary\":{{\"tested\":{},\"ok\":{},\"failed\":{},\"skipped\":{},\"failures\":{{{}}}}}}}\n",
+            self.ok + failed,
+            self.ok,
+            failed,
+            self.skipped,
+            failures.join(",")
+        ));
+        self.write(&text);
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => Ok(self.out),
+        }
+    }
+
+    fn opening(&self) -> String {
+        format!("{{\"archive\":{},\"entries\":[", json_string(&self.archive))
+    }
+
+    /// Write the entry object `fields`, after the opening or a comma
+    fn entry(&mut self, fields: &str) {
+        let separator = if self.entries == 0 {
+            self.opening()
+        } else {
+            ",".to_string()
+        };
+        self.entries += 1;
+        self.write(&format!("{}\n{{{}}}", separator, fields));
+    }
+
+    fn write(&mut self, text: &str) {
+        if self.error.is_some() {
+            return;
+        }
+        let result = self.out.write_all(text.as_bytes()).and_then(|()| self.out.flush());
+        if let Err(error) = result {
+            self.error = Some(error);
+        }
+    }
+}
+
+impl<W: Write> ExtractObserver for JsonReporter<W> {
+    fn entry_finished(&mut self, name: &str, _path: Option<&Path>, bytes: u64) {
+        self.ok += 1;
+        let fields = format!("\"name\":{},\"status\":\"ok\",\"size\":{}", json_string(name), bytes);
+        self.entry(&fields);
+    }
+
+    fn entry_skipped(&mut self, _name: &str, _reason: SkipReason) {
+        self.skipped += 1;
+    }
+
+    fn entry_failed(&mut self, name: &str, error: &str) {
+        self.entry_test_failed(name, &TestFailure::Corrupt(error.to_string()));
+    }
+
+    fn entry_test_failed(&mut self, name: &str, failure: &TestFailure) {
+        *self.failures.entry(failure.label()).or_default() += 1;
+        let mut fields = format!(
+            "\"name\":{},\"status\":\"{}\",\"message\":{}",
+            json_string(name),
+            failure.label(),
+            json_string(&failure.to_string())
+        );
+        if let TestFailure::CrcMismatch { stored, computed } = failure {
+            fields.push_str(&format!(
+                ",\"stored_crc32\":\"{:08x}\",\"computed_crc32\":\"{:08x}\"",
+                stored, computed
+            ));
+        }
+        self.entry(&fields);
+    }
+
+    fn warning(&mut self, message: &str) {
+        self.warnings.push(message.trim_start().to_string());
+    }
+}
+
+impl<W: Write> Report for JsonReporter<W> {
+    fn end(self: Box<Self>, error: Option<&str>) -> io::Result<()> {
+        match error {
+            Some(reason) => self.bail_out(reason).map(drop),
+            None => self.finish().map(drop),
+        }
+    }
+}
+
+/// `name` as a test description: `#` would start a directive, so it is escaped
+fn escape_description(name: &str) -> String {
+    single_line(name).replace('\\', "\\\\").replace('#', "\\#")
+}
+
+/// `text` with line breaks replaced, as TAP is line-oriented
+fn single_line(text: &str) -> String {
+    text.replace(['\r', '\n'], " ")
+}
+
+/// `text` as a single-quoted YAML scalar
+fn yaml_string(text: &str) -> String {
+    format!("'{}'", single_line(text).replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tap_stream() {
+        let mut tap = TapReporter::new(Vec::new());
+        tap.entry_finished("dir/a.txt", None, 5);
+        tap.warning("   recovered: b.txt (stored, labelled deflate)");
+        tap.entry_finished("b.txt", None, 3);
+        tap.entry_failed("c.txt", "it's\nbroken");
+        let out = String::from_utf8(tap.finish().unwrap()).unwrap();
+        assert_eq!(
+            out,
+            "TAP version 13\n\
+             ok 1 - dir/a.txt\n\
+             # recovered: b.txt (stored, labelled deflate)\n\
+             ok 2 - b.txt\n\
+             not ok 3 - c.txt\n  ---\n  message: 'it''s broken'\n  ...\n\
+             1..3\n"
+        );
+
+        let out = TapReporter::new(Vec::new()).bail_out("limit\ncrossed").unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "TAP version 13\nBail out! limit crossed\n");
+    }
+
+    #[test]
+    fn test_json_stream() {
+        let mut json = JsonReporter::new(Vec::new(), "a \"b\".zip");
+        json.entry_finished("dir/a.txt", None, 5);
+        json.warning("   recovered: b.txt (stored, labelled deflate)");
+        json.entry_skipped("b.log", SkipReason::Filtered);
+        json.entry_test_failed("c.txt", &TestFailure::CrcMismatch { stored: 0xab, computed: 1 });
+        json.entry_test_failed("d.txt", &TestFailure::UnsupportedMethod("lzma".to_string()));
+        let out = String::from_utf8(json.finish().unwrap()).unwrap();
+        assert_eq!(
+            out,
+            "{\"archive\":\"a \\\"b\\\".zip\",\"entries\":[\n\
+             {\"name\":\"dir/a.txt\",\"status\":\"ok\",\"size\":5},\n\
+             {\"name\":\"c.txt\",\"status\":\"crc-mismatch\",\
+             \"message\":\"CRC mismatch (stored: 000000ab, computed: 00000001)\",\
+             \"stored_crc32\":\"000000ab\",\"computed_crc32\":\"00000001\"},\n\
+             {\"name\":\"d.txt\",\"status\":\"unsupported-method\",\"message\":\"lzma\"}\n\
+             ],\"warnings\":[\"recovered: b.txt (stored, labelled deflate)\"],\
+             \"summary\":{\"tested\":3,\"ok\":1,\"failed\":2,\"skipped\":1,\
+             \"failures\":{\"crc-mismatch\":1,\"unsupported-method\":1}}}\n"
+        );
+
+        let out = JsonReporter::new(Vec::new(), "a.zip").bail_out("limit crossed").unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"archive\":\"a.zip\",\"entries\":[\n],\"warnings\":[],\
+             \"error\":\"limit crossed\",\"summary\":{\"tested\":0,\"ok\":0,\"failed\":0,\
+             \"skipped\":0,\"failures\":{}}}\n"
+        );
+    }
+}
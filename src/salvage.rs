@@ -0,0 +1,564 @@
+//! Recovery of archives with a missing or damaged central directory (`--salvage`)
+//!
+//! Every entry's data is preceded by a local file header repeating most of what
+//! the central directory says about it. When a download or a disk cut the archive
+//! short, or the central directory is corrupt, [`salvage`] ignores whatever central
+//! directory there is and scans the file from the start for local file headers.
+//! From those it builds a new central directory, kept in memory and read through a
+//! [`SalvagedReader`] as if it followed the data, and opens that as an ordinary
+//! archive. Entries whose sizes are only in a data descriptor are found through the
+//! descriptor's signature.
+//!
+//! Each entry found is then read through once. Entries whose data runs past the end
+//! of the file, does not decompress or fails its CRC32 check are left out and
+//! reported as [`LostEntry`] values, so that listing, testing and extraction see
+//! only what can really be recovered. Encrypted entries cannot be checked without
+//! the password and are kept as found.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use unzip::salvage::salvage;
+//!
+//! let data = std::fs::read("truncated.zip")?;
+//! let salvaged = salvage(&data)?;
+//! println!("recovered {} entries", salvaged.archive.len());
+//! for lost in &salvaged.lost {
+//!     println!("skipped {} at offset {}: {}", lost.name, lost.offset, lost.problem);
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom};
+use zip::ZipArchive;
+use zip::result::ZipError;
+
+use crate::error::{Result, UnzipError};
+use crate::records::{
+    CENTRAL_SIGNATURE, DESCRIPTOR_SIGNATURE, EOCD_SIGNATURE, LOCAL_HEADER_LEN, LOCAL_SIGNATURE,
+    ZIP64_EOCD_LEN, ZIP64_EOCD_SIGNATURE, ZIP64_LOCATOR_SIGNATURE, u16_at, u32_at, u64_at,
+};
+
+/// General purpose flag: the entry is encrypted
+const FLAG_ENCRYPTED: u16 = 1;
+
+/// General purpose flag: sizes and CRC32 follow the data in a data descriptor
+const FLAG_DESCRIPTOR: u16 = 1 << 3;
+
+/// Header ID of the ZIP64 extended information extra field
+pub(crate) const ZIP64_EXTRA_ID: u16 = 0x0001;
+
+/// Version needed to extract archives using ZIP64 records
+const ZIP64_VERSION: u16 = 45;
+
+/// Marker for a 32-bit field whose value is in a ZIP64 record
+pub(crate) const ZIP64_MARKER: u32 = u32::MAX;
+
+/// Why an entry found in the file could not be recovered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Unrecoverable {
+    /// The entry's data runs past the end of the file
+    Truncated,
+    /// The sizes are only in a data descriptor, and none was found
+    MissingDescriptor,
+    /// The data does not decompress, fails its CRC32 check or uses an unsupported
+    /// compression method
+    Unreadable(String),
+}
+
+impl fmt::Display for Unrecoverable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated => write!(f, "data runs past the end of the file"),
+            Self::MissingDescriptor => {
+                write!(f, "sizes are in a data descriptor that was not found")
+            },
+            Self::Unreadable(error) => write!(f, "data cannot be read: {}", error),
+        }
+    }
+}
+
+/// An entry that [`salvage`] found but could not recover
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LostEntry {
+    /// Name from the local file header
+    pub name: String,
+    /// Offset of the local file header in the file
+    pub offset: u64,
+    /// Why the entry was left out
+    pub problem: Unrecoverable,
+}
+
+/// The archive rebuilt from the local file headers, and the entries left out
+pub struct Salvaged<'a> {
+    /// The recovered entries, in the order they appear in the file
+    pub archive: ZipArchive<SalvagedReader<'a>>,
+    /// Entries found but not recovered, in the order they appear in the file
+    pub lost: Vec<LostEntry>,
+}
+
+/// What the central directory needs to know about an entry found by the scan
+#[derive(Debug, Clone)]
+struct FoundEntry {
+    offset: u64,
+    version: u16,
+    flags: u16,
+    method: u16,
+    time: u16,
+    date: u16,
+    crc32: u32,
+    compressed: u64,
+    uncompressed: u64,
+    name: Vec<u8>,
+    /// The local extra field without its ZIP64 record, which is rewritten
+    extra: Vec<u8>,
+}
+
+impl FoundEntry {
+    fn lost(&self, problem: Unrecoverable) -> LostEntry {
+        lost_entry(&self.name, self.offset, problem)
+    }
+}
+
+fn lost_entry(name: &[u8], offset: u64, problem: Unrecoverable) -> LostEntry {
+    LostEntry { name: String::from_utf8_lossy(name).into_owned(), offset, problem }
+}
+
+/// Sizes and CRC32 read from a data descriptor
+struct Descriptor {
+    crc32: u32,
+    compressed: u64,
+    uncompressed: u64,
+    /// Offset just past the descriptor
+    end: usize,
+}
+
+/// Rebuild the archive in `data` from its local file headers
+///
+/// # Errors
+///
+/// Returns an error if `data` holds no local file header at all, or if the rebuilt
+/// central directory cannot be opened
+pub fn salvage(data: &[u8]) -> Result<Salvaged<'_>> {
+    let (mut entries, mut lost) = scan(data);
+    if entries.is_empty() && lost.is_empty() {
+        return Err(UnzipError::Zip(ZipError::InvalidArchive("no local file headers found")));
+    }
+
+    let mut archive = rebuild(data, &entries)?;
+    let mut unreadable = Vec::new();
+    for (i, entry) in entries.iter().enumerate() {
+        // Encrypted data cannot be checked without the password
+        if entry.flags & FLAG_ENCRYPTED != 0 {
+            continue;
+        }
+        let result = archive
+            .by_index(i)
+            .map_err(UnzipError::from)
+            .and_then(|mut file| io::copy(&mut file, &mut io::sink()).map_err(UnzipError::from));
+        if let Err(error) = result {
+            unreadable.push((i, error.to_string()));
+        }
+    }
+    if !unreadable.is_empty() {
+        for (i, error) in unreadable.into_iter().rev() {
+            lost.push(entries.remove(i).lost(Unrecoverable::Unreadable(error)));
+        }
+        archive = rebuild(data, &entries)?;
+    }
+    lost.sort_by_key(|entry| entry.offset);
+    Ok(Salvaged { archive, lost })
+}
+
+/// Find every local file header in `data`, skipping over the data of each entry
+///
+/// A name seen again replaces the earlier entry, as appending an updated file to
+/// an archive does.
+fn scan(data: &[u8]) -> (Vec<FoundEntry>, Vec<LostEntry>) {
+    let (mut entries, mut lost) = (Vec::<FoundEntry>::new(), Vec::new());
+    let mut pos = 0;
+    while let Some(at) = find(data, pos, &LOCAL_SIGNATURE.to_le_bytes()) {
+        match read_local(data, at) {
+            Some((Ok(entry), next)) => {
+                entries.retain(|earlier| earlier.name != entry.name);
+                entries.push(entry);
+                pos = next;
+            },
+            Some((Err(entry), next)) => {
+                lost.push(entry);
+                pos = next;
+            },
+            // Not a header after all, just the signature bytes inside other data
+            None => pos = at + 1,
+        }
+    }
+    (entries, lost)
+}
+
+fn find(data: &[u8], from: usize, signature: &[u8]) -> Option<usize> {
+    let haystack = data.get(from..)?;
+    haystack
+        .windows(signature.len())
+        .position(|window| window == signature)
+        .map(|at| from + at)
+}
+
+/// Read the local file header at `offset`
+///
+/// Returns `None` if there is no plausible header there, and otherwise the entry,
+/// or why it cannot be recovered, along with where to continue scanning.
+fn read_local(data: &[u8], offset: usize) -> Option<(Result<FoundEntry, LostEntry>, usize)> {
+    let flags = u16_at(data, offset + 6)?;
+    let name_len = usize::from(u16_at(data, offset + 26)?);
+    let extra_len = usize::from(u16_at(data, offset + 28)?);
+    let name_start = offset + LOCAL_HEADER_LEN;
+    let data_start = name_start + name_len + extra_len;
+    if name_len == 0 || data_start > data.len() {
+        return None;
+    }
+    let name = &data[name_start..name_start + name_len];
+    let local_extra = &data[name_start + name_len..data_start];
+
+    let mut crc32 = u32_at(data, offset + 14)?;
+    let mut compressed = u64::from(u32_at(data, offset + 18)?);
+    let mut uncompressed = u64::from(u32_at(data, offset + 22)?);
+    let zip64 = extra_fields(local_extra).find(|(id, _)| *id == ZIP64_EXTRA_ID);
+    if let Some((_, field)) = zip64
+        && (compressed == u64::from(ZIP64_MARKER) || uncompressed == u64::from(ZIP64_MARKER))
+    {
+        uncompressed = u64_at(field, 0)?;
+        compressed = u64_at(field, 8)?;
+    }
+
+    let lost = |problem| lost_entry(name, offset as u64, problem);
+    let mut next = data_start;
+    if flags & FLAG_DESCRIPTOR != 0 && compressed == 0 {
+        let Some(descriptor) = find_descriptor(data, data_start, zip64.is_some()) else {
+            return Some((Err(lost(Unrecoverable::MissingDescriptor)), next));
+        };
+        crc32 = descriptor.crc32;
+        compressed = descriptor.compressed;
+        uncompressed = descriptor.uncompressed;
+        next = descriptor.end;
+    } else {
+        let data_end = usize::try_from(compressed).ok().and_then(|len| data_start.checked_add(len));
+        let Some(data_end) = data_end.filter(|end| *end <= data.len()) else {
+            return Some((Err(lost(Unrecoverable::Truncated)), next));
+        };
+        next = data_end;
+        if flags & FLAG_DESCRIPTOR != 0
+            && let Some(descriptor) = read_descriptor(data, data_end, zip64.is_some())
+        {
+            crc32 = descriptor.crc32;
+            next = descriptor.end;
+        }
+    }
+
+    let entry = FoundEntry {
+        offset: offset as u64,
+        version: u16_at(data, offset + 4)?,
+        flags,
+        method: u16_at(data, offset + 8)?,
+        time: u16_at(data, offset + 10)?,
+        date: u16_at(data, offset + 12)?,
+        crc32,
+        compressed,
+        uncompressed,
+        name: name.to_vec(),
+        extra: extra_fields(local_extra)
+            .filter(|(id, _)| *id != ZIP64_EXTRA_ID)
+            .flat_map(|(id, field)| {
+                let mut bytes = id.to_le_bytes().to_vec();
+                bytes.extend((field.len() as u16).to_le_bytes());
+                bytes.extend(field);
+                bytes
+            })
+            .collect(),
+    };
+    Some((Ok(entry), next))
+}
+
+/// The `(header ID, data)` records of an extra field, up to the first malformed one
+pub(crate) fn extra_fields(extra: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+    let mut pos = 0;
+    std::iter::from_fn(move || {
+        let id = u16_at(extra, pos)?;
+        let len = usize::from(u16_at(extra, pos + 2)?);
+        let field = extra.get(pos + 4..pos + 4 + len)?;
+        pos += 4 + len;
+        Some((id, field))
+    })
+}
+
+/// Find the data descriptor of an entry whose data starts at `data_start`
+///
+/// The descriptor is the first one, after the signature, whose compressed size
+/// equals its distance from the start of the data.
+fn find_descriptor(data: &[u8], data_start: usize, zip64: bool) -> Option<Descriptor> {
+    let mut pos = data_start;
+    while let Some(at) = find(data, pos, &DESCRIPTOR_SIGNATURE.to_le_bytes()) {
+        if let Some(descriptor) = read_descriptor(data, at, zip64)
+            && descriptor.compressed == (at - data_start) as u64
+        {
+            return Some(descriptor);
+        }
+        pos = at + 1;
+    }
+    None
+}
+
+/// Read a data descriptor at `at`, with or without its optional signature
+fn read_descriptor(data: &[u8], at: usize, zip64: bool) -> Option<Descriptor> {
+    let at = if u32_at(data, at)? == DESCRIPTOR_SIGNATURE {
+        at + 4
+    } else {
+        at
+    };
+    let crc32 = u32_at(data, at)?;
+    let (compressed, uncompressed, end) = if zip64 {
+        (u64_at(data, at + 4)?, u64_at(data, at + 12)?, at + 20)
+    } else {
+        (u64::from(u32_at(data, at + 4)?), u64::from(u32_at(data, at + 8)?), at + 12)
+    };
+    Some(Descriptor { crc32, compressed, uncompressed, end })
+}
+
+/// Open `data` as an archive with a central directory listing `entries`
+fn rebuild<'a>(data: &'a [u8], entries: &[FoundEntry]) -> Result<ZipArchive<SalvagedReader<'a>>> {
+    let directory = central_directory(entries, data.len() as u64);
+    Ok(ZipArchive::new(SalvagedReader { data, directory, pos: 0 })?)
+}
+
+/// A central directory for `entries` starting at `cd_offset`, followed by the end
+/// of central directory records
+fn central_directory(entries: &[FoundEntry], cd_offset: u64) -> Vec<u8> {
+    let mut cd = Vec::new();
+    for entry in entries {
+        // Values that do not fit their 32-bit fields move to a ZIP64 record
+        let mut zip64 = Vec::new();
+        let mut field32 = |value: u64| match u32::try_from(value) {
+            Ok(value) if value != ZIP64_MARKER => value,
+            _ => {
+                zip64.extend(value.to_le_bytes());
+                ZIP64_MARKER
+            },
+        };
+        let uncompressed = field32(entry.uncompressed);
+        let compressed = field32(entry.compressed);
+        let offset = field32(entry.offset);
+        let mut extra = Vec::new();
+        if !zip64.is_empty() {
+            extra.extend(ZIP64_EXTRA_ID.to_le_bytes());
+            extra.extend((zip64.len() as u16).to_le_bytes());
+            extra.extend(zip64);
+        }
+        extra.extend(&entry.extra);
+        let extra = &extra[..extra.len().min(usize::from(u16::MAX))];
+
+        cd.extend(CENTRAL_SIGNATURE.to_le_bytes());
+        cd.extend(entry.version.to_le_bytes()); // version made by: MS-DOS
+        cd.extend(entry.version.to_le_bytes());
+        cd.extend(entry.flags.to_le_bytes());
+        cd.extend(entry.method.to_le_bytes());
+        cd.extend(entry.time.to_le_bytes());
+        cd.extend(entry.date.to_le_bytes());
+        cd.extend(entry.crc32.to_le_bytes());
+        cd.extend(compressed.to_le_bytes());
+        cd.extend(uncompressed.to_le_bytes());
+        cd.extend((entry.name.len() as u16).to_le_bytes());
+        cd.extend((extra.len() as u16).to_le_bytes());
+        cd.extend(0u16.to_le_bytes()); // comment length
+        cd.extend(0u16.to_le_bytes()); // disk number
+        cd.extend(0u16.to_le_bytes()); // internal attributes
+        cd.extend(0u32.to_le_bytes()); // external attributes
+        cd.extend(offset.to_le_bytes());
+        cd.extend(&entry.name);
+        cd.extend(extra);
+    }
+
+    end_records(&mut cd, entries.len() as u64, cd_offset);
+    cd
+}
+
+/// Append the end of central directory records to the central directory `cd`
+/// holding `count` entries and starting at `cd_offset`, with ZIP64 records when
+/// the values need them
+pub(crate) fn end_records(cd: &mut Vec<u8>, count: u64, cd_offset: u64) {
+    let cd_size = cd.len() as u64;
+    let zip64_end = cd_offset + cd_size;
+    let needs_zip64 = count >= u64::from(u16::MAX)
+        || cd_size >= u64::from(ZIP64_MARKER)
+        || cd_offset >= u64::from(ZIP64_MARKER);
+    if needs_zip64 {
+        cd.extend(ZIP64_EOCD_SIGNATURE.to_le_bytes());
+        cd.extend((ZIP64_EOCD_LEN as u64 - 12).to_le_bytes()); // size of the rest of the record
+        cd.extend(ZIP64_VERSION.to_le_bytes());
+        cd.extend(ZIP64_VERSION.to_le_bytes());
+        cd.extend(0u32.to_le_bytes()); // this disk
+        cd.extend(0u32.to_le_bytes()); // disk with the central directory
+        cd.extend(count.to_le_bytes());
+        cd.extend(count.to_le_bytes());
+        cd.extend(cd_size.to_le_bytes());
+        cd.extend(cd_offset.to_le_bytes());
+
+        cd.extend(ZIP64_LOCATOR_SIGNATURE.to_le_bytes());
+        cd.extend(0u32.to_le_bytes()); // disk with the ZIP64 record
+        cd.extend(zip64_end.to_le_bytes());
+        cd.extend(1u32.to_le_bytes()); // total disks
+    }
+
+    let count16 = if needs_zip64 { u16::MAX } else { count as u16 };
+    let field32 = |value: u64| {
+        if needs_zip64 {
+            ZIP64_MARKER
+        } else {
+            value as u32
+        }
+    };
+    cd.extend(EOCD_SIGNATURE.to_le_bytes());
+    cd.extend(0u16.to_le_bytes()); // this disk
+    cd.extend(0u16.to_le_bytes()); // disk with the central directory
+    cd.extend(count16.to_le_bytes());
+    cd.extend(count16.to_le_bytes());
+    cd.extend(field32(cd_size).to_le_bytes());
+    cd.extend(field32(cd_offset).to_le_bytes());
+    cd.extend(0u16.to_le_bytes()); // comment length
+}
+
+/// Reader over a file's data followed by a central directory rebuilt by [`salvage`]
+pub struct SalvagedReader<'a> {
+    data: &'a [u8],
+    directory: Vec<u8>,
+    pos: u64,
+}
+
+impl SalvagedReader<'_> {
+    fn len(&self) -> u64 {
+        (self.data.len() + self.directory.len()) as u64
+    }
+}
+
+impl Read for SalvagedReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let data_len = self.data.len() as u64;
+        let (source, start) = if self.pos < data_len {
+            (self.data, self.pos)
+        } else {
+            (&self.directory[..], self.pos - data_len)
+        };
+        let Some(remaining) = usize::try_from(start).ok().and_then(|start| source.get(start..))
+        else {
+            return Ok(0);
+        };
+        let n = buf.len().min(remaining.len());
+        buf[..n].copy_from_slice(&remaining[..n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for SalvagedReader<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.len().checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+        };
+        self.pos = target.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "seek before start of archive")
+        })?;
+        Ok(self.pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::SimpleFileOptions;
+    use zip::{CompressionMethod, ZipWriter};
+
+    fn archive_bytes() -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        zip.add_directory("docs/", stored).unwrap();
+        zip.start_file("docs/a.txt", SimpleFileOptions::default()).unwrap();
+        zip.write_all(&b"first file, compressed ".repeat(50)).unwrap();
+        zip.start_file("docs/b.txt", stored).unwrap();
+        zip.write_all(b"second file, stored").unwrap();
+        zip.start_file("docs/c.txt", stored).unwrap();
+        zip.write_all(&b"third file ".repeat(20)).unwrap();
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn read(archive: &mut ZipArchive<SalvagedReader<'_>>, name: &str) -> Vec<u8> {
+        let mut content = Vec::new();
+        archive.by_name(name).unwrap().read_to_end(&mut content).unwrap();
+        content
+    }
+
+    #[test]
+    fn test_salvage_without_central_directory() {
+        let data = archive_bytes();
+        let cd_start = find(&data, 0, b"PK\x01\x02").unwrap();
+        let Salvaged { mut archive, lost } = salvage(&data[..cd_start]).unwrap();
+        assert!(lost.is_empty(), "{:?}", lost);
+        let names: Vec<&str> = archive.file_names().collect();
+        assert_eq!(names, ["docs/", "docs/a.txt", "docs/b.txt", "docs/c.txt"]);
+        assert_eq!(read(&mut archive, "docs/a.txt"), b"first file, compressed ".repeat(50));
+        assert_eq!(read(&mut archive, "docs/b.txt"), b"second file, stored");
+    }
+
+    #[test]
+    fn test_salvage_reports_truncated_and_corrupt_entries() {
+        let mut data = archive_bytes();
+        let b_data = find(&data, 0, b"second file").unwrap();
+        data[b_data] ^= 0xFF;
+        let c_data = find(&data, 0, b"third file").unwrap();
+        data.truncate(c_data + 10);
+
+        let Salvaged { mut archive, lost } = salvage(&data).unwrap();
+        assert_eq!(archive.len(), 2);
+        assert_eq!(read(&mut archive, "docs/a.txt").len(), 23 * 50);
+        assert_eq!(lost.len(), 2);
+        assert_eq!(lost[0].name, "docs/b.txt");
+        assert!(matches!(lost[0].problem, Unrecoverable::Unreadable(_)), "{:?}", lost[0]);
+        assert_eq!(lost[1].name, "docs/c.txt");
+        assert_eq!(lost[1].problem, Unrecoverable::Truncated);
+        assert_eq!(lost[1].problem.to_string(), "data runs past the end of the file");
+    }
+
+    #[test]
+    fn test_salvage_finds_data_descriptor() {
+        let content = b"streamed without sizes up front";
+        let mut data = Vec::new();
+        data.extend(LOCAL_SIGNATURE.to_le_bytes());
+        data.extend(20u16.to_le_bytes());
+        data.extend(FLAG_DESCRIPTOR.to_le_bytes());
+        data.extend([0u8; 6]); // stored, no timestamp
+        data.extend([0u8; 12]); // CRC32 and sizes deferred to the descriptor
+        data.extend(9u16.to_le_bytes());
+        data.extend(0u16.to_le_bytes());
+        data.extend(b"streamed!");
+        data.extend(content);
+        data.extend(DESCRIPTOR_SIGNATURE.to_le_bytes());
+        data.extend(crc32fast::hash(content).to_le_bytes());
+        data.extend((content.len() as u32).to_le_bytes());
+        data.extend((content.len() as u32).to_le_bytes());
+
+        let Salvaged { mut archive, lost } = salvage(&data).unwrap();
+        assert!(lost.is_empty(), "{:?}", lost);
+        assert_eq!(read(&mut archive, "streamed!"), content);
+
+        data.truncate(data.len() - 16);
+        let Salvaged { archive, lost } = salvage(&data).unwrap();
+        assert_eq!(archive.len(), 0);
+        assert_eq!(lost[0].problem, Unrecoverable::MissingDescriptor);
+    }
+
+    #[test]
+    fn test_salvage_without_headers() {
+        assert!(salvage(b"not a zip file at all").is_err());
+    }
+}
@@ -0,0 +1,309 @@
+//! Throughput-aware worker scaling for threaded extraction
+//!
+//! No single thread count suits every archive: thousands of small deflated
+//! files keep every core busy inflating, while a few huge Stored media files
+//! are bound by the disk, where extra writers only add seeks. Threaded
+//! extraction therefore puts the entries in one queue, `--priority` entries
+//! first, and each worker takes the next entry when it finishes one. A
+//! controller samples the work done every [`SAMPLE_INTERVAL`] and moves the
+//! number of workers, at most `-T` (the CPU count by default):
+//!
+//! - It adds a worker while the last one added raised throughput by at least
+//!   half of what an average worker contributes, and there are more queued
+//!   entries than workers.
+//! - When an added worker did not pay off, it removes it and keeps removing
+//!   while throughput holds, stopping at the count below which throughput
+//!   drops.
+//! - Once settled, a sharp change in throughput, such as the archive moving
+//!   from text files to media, or a few seconds of steady work start a new
+//!   probe.
+//!
+//! Work counts bytes written plus a fixed cost per entry, so small files
+//! weigh what their per-file work costs. A worker leaves between entries, so
+//! one writing a large file finishes it first.
+//!
+//! # Examples
+//!
+//! ```
+//! use unzip::scaling::Scaler;
+//!
+//! // Each worker adds 100 MB/s: the controller climbs to the ceiling of 4
+//! let mut scaler = Scaler::new(4);
+//! let mut workers = scaler.initial();
+//! for _ in 0..4 {
+//!     workers = scaler.next(workers, 100.0 * workers as f64, 1000);
+//! }
+//! assert_eq!(workers, 4);
+//! ```
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+/// How often the controller samples throughput
+pub const SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Work an entry costs besides its bytes, for opening, creating and finishing it
+const ENTRY_WORK: u64 = 64 * 1024;
+
+/// Share of an average worker's throughput a worker must add to be kept
+const GAIN: f64 = 0.5;
+
+/// Throughput change that starts a new probe while settled
+const SHIFT: f64 = 0.3;
+
+/// Samples of steady work after which a settled controller probes upward
+const PROBE_AFTER: u32 = 20;
+
+/// Direction the controller is moving the worker count in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Probe {
+    Up,
+    Down,
+    Settled,
+}
+
+/// Hill-climbing choice of the number of workers from sampled throughput
+#[derive(Debug, Clone)]
+pub struct Scaler {
+    max: usize,
+    probe: Probe,
+    /// Workers and throughput of the previous sample
+    last: Option<(usize, f64)>,
+    /// Samples since the controller settled
+    steady: u32,
+}
+
+impl Scaler {
+    /// Control up to `max` workers
+    pub fn new(max: usize) -> Self {
+        Self { max: max.max(1), probe: Probe::Up, last: None, steady: 0 }
+    }
+
+    /// Workers to start with: half the ceiling, from which the controller
+    /// climbs or backs off
+    pub fn initial(&self) -> usize {
+        self.max.div_ceil(2)
+    }
+
+    /// Workers to run next, given the `active` workers of the last sample, the
+    /// `rate` of work they did and the number of entries still `queued`
+    pub fn next(&mut self, active: usize, rate: f64, queued: usize) -> usize {
+        let active = active.max(1);
+        let target = match self.last {
+            None => active + 1,
+            Some((previous, previous_rate)) => {
+                let per_worker = previous_rate / previous.max(1) as f64;
+                let change = rate - previous_rate;
+                match self.probe {
+                    Probe::Up if active > previous => {
+                        if change >= per_worker * GAIN * (active - previous) as f64 {
+                            active + 1
+                        } else {
+                            self.probe = Probe::Down;
+                            previous
+                        }
+                    },
+                    Probe::Down if active < previous => {
+                        if -change > per_worker * GAIN * (previous - active) as f64 {
+                            self.settle();
+                            previous
+                        } else {
+                            active - 1
+                        }
+                    },
+                    // The last change has not taken effect yet
+                    Probe::Up | Probe::Down => active,
+                    Probe::Settled => {
+                        self.steady += 1;
+                        // The first sample at the settled count is the baseline
+                        if self.steady == 1 {
+                            active
+                        } else if rate < previous_rate * (1.0 - SHIFT) {
+                            self.probe = Probe::Down;
+                            active - 1
+                        } else if rate > previous_rate * (1.0 + SHIFT) || self.steady >= PROBE_AFTER
+                        {
+                            self.probe = Probe::Up;
+                            active + 1
+                        } else {
+                            active
+                        }
+                    },
+                }
+            },
+        };
+        self.last = Some((active, rate));
+
+        // More workers than queued entries would only wait
+        let ceiling = if queued > active {
+            self.max
+        } else {
+            active.min(self.max)
+        };
+        let clamped = target.clamp(1, ceiling);
+        if clamped != target {
+            self.settle();
+        }
+        clamped
+    }
+
+    fn settle(&mut self) {
+        self.probe = Probe::Settled;
+        self.steady = 0;
+    }
+}
+
+/// Jobs shared by the workers, taken in order
+#[derive(Debug)]
+pub(crate) struct JobQueue<T> {
+    jobs: Mutex<VecDeque<T>>,
+}
+
+impl<T> JobQueue<T> {
+    pub(crate) fn new(jobs: impl IntoIterator<Item = T>) -> Self {
+        Self { jobs: Mutex::new(jobs.into_iter().collect()) }
+    }
+
+    fn pop(&self) -> Option<T> {
+        self.jobs.lock().unwrap_or_else(PoisonError::into_inner).pop_front()
+    }
+
+    /// Jobs no worker has taken yet
+    pub(crate) fn len(&self) -> usize {
+        self.jobs.lock().unwrap_or_else(PoisonError::into_inner).len()
+    }
+}
+
+/// Running workers, the number wanted and the work done since the last sample
+#[derive(Debug, Default)]
+pub(crate) struct Workers {
+    active: AtomicUsize,
+    target: AtomicUsize,
+    work: AtomicU64,
+}
+
+impl Workers {
+    pub(crate) fn new(target: usize) -> Arc<Self> {
+        Arc::new(Self { target: AtomicUsize::new(target), ..Self::default() })
+    }
+
+    /// Count a new worker, which holds the returned slot while it runs
+    pub(crate) fn start(self: &Arc<Self>) -> WorkerSlot {
+        self.active.fetch_add(1, Ordering::Relaxed);
+        WorkerSlot { workers: Arc::clone(self), retired: false }
+    }
+
+    pub(crate) fn active(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_target(&self, target: usize) {
+        self.target.store(target, Ordering::Relaxed);
+    }
+
+    /// Count `bytes` written
+    pub(crate) fn record(&self, bytes: u64) {
+        self.work.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Work done since the last call
+    pub(crate) fn take_work(&self) -> u64 {
+        self.work.swap(0, Ordering::Relaxed)
+    }
+}
+
+/// A running worker's place in [`Workers`], given up when it is dropped
+#[derive(Debug)]
+pub(crate) struct WorkerSlot {
+    workers: Arc<Workers>,
+    retired: bool,
+}
+
+impl WorkerSlot {
+    /// The next job for this worker, or `None` once the queue is empty or
+    /// there are more workers than wanted and this one leaves
+    pub(crate) fn next_job<T>(&mut self, queue: &JobQueue<T>) -> Option<T> {
+        let workers = &self.workers;
+        let mut active = workers.active.load(Ordering::Relaxed);
+        while active > workers.target.load(Ordering::Relaxed).max(1) {
+            match workers.active.compare_exchange(
+                active,
+                active - 1,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => {
+                    self.retired = true;
+                    return None;
+                },
+                Err(current) => active = current,
+            }
+        }
+        let job = queue.pop()?;
+        workers.record(ENTRY_WORK);
+        Some(job)
+    }
+}
+
+impl Drop for WorkerSlot {
+    fn drop(&mut self) {
+        if !self.retired {
+            self.workers.active.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run the controller against `throughput`, returning the worker counts it
+    /// picks
+    fn run(max: usize, samples: usize, throughput: impl Fn(usize) -> f64) -> Vec<usize> {
+        let mut scaler = Scaler::new(max);
+        let mut workers = scaler.initial();
+        let mut counts = vec![workers];
+        for _ in 0..samples {
+            workers = scaler.next(workers, throughput(workers), 1000);
+            counts.push(workers);
+        }
+        counts
+    }
+
+    #[test]
+    fn test_scaler_climbs_while_workers_add_throughput() {
+        assert_eq!(run(8, 6, |n| 100.0 * n as f64), [4, 5, 6, 7, 8, 8, 8]);
+    }
+
+    #[test]
+    fn test_scaler_backs_off_when_bound_by_the_disk() {
+        // Throughput stops growing past two workers
+        let counts = run(8, 6, |n| 100.0 * n.min(2) as f64);
+        assert_eq!(counts, [4, 5, 4, 3, 2, 1, 2]);
+        assert_eq!(run(8, 12, |n| 100.0 * n.min(2) as f64)[7..], [2, 2, 2, 2, 2, 2]);
+    }
+
+    #[test]
+    fn test_scaler_keeps_to_the_queue() {
+        let mut scaler = Scaler::new(8);
+        assert_eq!(scaler.next(4, 400.0, 3), 4);
+        assert_eq!(scaler.next(4, 400.0, 2), 4);
+    }
+
+    #[test]
+    fn test_workers_leave_above_the_target() {
+        let workers = Workers::new(2);
+        let queue = JobQueue::new(0..10);
+        let mut slots: Vec<_> = (0..3).map(|_| workers.start()).collect();
+        assert_eq!(slots[0].next_job(&queue), None);
+        assert_eq!(workers.active(), 2);
+        assert_eq!(slots[1].next_job(&queue), Some(0));
+        assert_eq!(slots[2].next_job(&queue), Some(1));
+        slots.clear();
+        assert_eq!(workers.active(), 0);
+        assert_eq!(queue.len(), 8);
+        assert_eq!(workers.take_work(), 2 * ENTRY_WORK);
+    }
+}
@@ -0,0 +1,273 @@
+//! Virus scanning while extracting (`--scan-socket`, `--quarantine`)
+//!
+//! Untrusted uploads are usually extracted and then scanned, which reads every
+//! file twice and leaves malware on disk in the meantime. With
+//! `--scan-socket /run/clamav/clamd.ctl`, the data of each file entry is sent to
+//! a ClamAV-compatible daemon as it is written, over a Unix socket using the
+//! `INSTREAM` command, and the daemon's verdict is awaited before the file is put
+//! in place. Data is scanned as written, before `--compress-output` and after
+//! `-a` line ending conversion.
+//!
+//! A flagged file never reaches its destination: it is moved into the
+//! `--quarantine` directory, under its file name or a free `NAME (N).EXT`, or
+//! removed without one. The entry is reported as
+//! [`SkipReason::Infected`](crate::events::SkipReason::Infected) with a warning
+//! naming the signature, extraction carries on, and the run then fails with
+//! [`UnzipError::Infected`] (with `--transactional`, nothing is kept).
+//!
+//! Each file is written under a temporary name next to its destination, as with
+//! `--shared-dest`, and renamed into place only after a clean verdict, so an
+//! existing file an infected entry would have replaced is kept.
+//!
+//! Scanning fails closed: a daemon that cannot be reached or answers with an
+//! error (such as `INSTREAM size limit exceeded`, see `StreamMaxLength`) stops
+//! the extraction, and the file it was writing is removed.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::path::Path;
+//! use unzip::scan::{InStream, Verdict};
+//!
+//! let mut scan = InStream::open(Path::new("/run/clamav/clamd.ctl"))?;
+//! scan.send(b"some data")?;
+//! if let Verdict::Infected(signature) = scan.verdict()? {
+//!     println!("found {}", signature);
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::{Result, UnzipError};
+use crate::rename::free_path;
+
+/// Largest chunk sent in one `INSTREAM` frame
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// What the scanner found in a stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// Nothing
+    Clean,
+    /// The named signature
+    Infected(String),
+}
+
+#[cfg(unix)]
+type Connection = std::os::unix::net::UnixStream;
+
+#[cfg(not(unix))]
+type Connection = File;
+
+/// One `INSTREAM` session: the data of a single file, then the verdict
+pub struct InStream {
+    connection: Connection,
+    socket: PathBuf,
+}
+
+impl InStream {
+    /// Connect to the daemon listening on `socket` and start a stream
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the socket cannot be connected to or written, or on
+    /// platforms without Unix sockets
+    pub fn open(socket: &Path) -> Result<Self> {
+        let connection = connect(socket).map_err(UnzipError::file("connect to scanner", socket))?;
+        let mut stream = Self { connection, socket: socket.to_path_buf() };
+        stream.write(b"zINSTREAM\0")?;
+        Ok(stream)
+    }
+
+    /// Send the next part of the data
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to the socket fails
+    pub fn send(&mut self, data: &[u8]) -> Result<()> {
+        for chunk in data.chunks(CHUNK_SIZE) {
+            self.write(&(chunk.len() as u32).to_be_bytes())?;
+            self.write(chunk)?;
+        }
+        Ok(())
+    }
+
+    /// End the stream and wait for the scanner's verdict
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the socket fails or the scanner answers with an error
+    pub fn verdict(mut self) -> Result<Verdict> {
+        self.write(&[0; 4])?;
+        // The reply to a `z` command ends with a NUL byte
+        let mut reply = Vec::new();
+        let mut buffer = [0u8; 256];
+        while !reply.contains(&0) && reply.len() < 4096 {
+            let n = (self.connection.read(&mut buffer))
+                .map_err(UnzipError::file("read from scanner", &self.socket))?;
+            if n == 0 {
+                break;
+            }
+            reply.extend_from_slice(&buffer[..n]);
+        }
+        parse_reply(&String::from_utf8_lossy(&reply))
+            .map_err(UnzipError::file("scan with", &self.socket))
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.connection
+            .write_all(data)
+            .map_err(UnzipError::file("send data to scanner", &self.socket))
+    }
+}
+
+#[cfg(unix)]
+fn connect(socket: &Path) -> io::Result<Connection> {
+    Connection::connect(socket)
+}
+
+#[cfg(not(unix))]
+fn connect(_socket: &Path) -> io::Result<Connection> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "scanner sockets need Unix sockets"))
+}
+
+/// Scan the file at `path` through the daemon on `socket`
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or scanning fails
+pub fn scan_file(socket: &Path, path: &Path) -> Result<Verdict> {
+    let mut file = File::open(path).map_err(UnzipError::file("open file", path))?;
+    let mut stream = InStream::open(socket)?;
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    loop {
+        let n = file.read(&mut buffer).map_err(UnzipError::file("read file", path))?;
+        if n == 0 {
+            break;
+        }
+        stream.send(&buffer[..n])?;
+    }
+    stream.verdict()
+}
+
+/// The verdict in a reply such as `stream: OK` or `stream: Eicar-Signature FOUND`
+fn parse_reply(reply: &str) -> io::Result<Verdict> {
+    let reply = reply.trim_end_matches(['\0', '\n']);
+    let result = reply.strip_prefix("stream: ").unwrap_or(reply);
+    if result == "OK" {
+        Ok(Verdict::Clean)
+    } else if let Some(signature) = result.strip_suffix(" FOUND") {
+        Ok(Verdict::Infected(signature.to_string()))
+    } else {
+        Err(io::Error::other(format!("scanner replied {:?}", reply)))
+    }
+}
+
+/// Move the flagged file at `path` into `quarantine`, under the file name of
+/// `outpath` or a free `NAME (N).EXT`, or remove it without a quarantine
+///
+/// Returns where the file was moved. A rename across filesystems falls back to
+/// a copy.
+///
+/// # Errors
+///
+/// Returns an error if the quarantine cannot be created or the file cannot be
+/// moved or removed
+pub(crate) fn quarantine(
+    path: &Path,
+    outpath: &Path,
+    quarantine: Option<&Path>,
+) -> Result<Option<PathBuf>> {
+    let Some(dir) = quarantine else {
+        fs::remove_file(path).map_err(UnzipError::file("remove infected file", path))?;
+        return Ok(None);
+    };
+    fs::create_dir_all(dir).map_err(UnzipError::file("create directory", dir))?;
+    let mut target = dir.join(outpath.file_name().unwrap_or_default());
+    if target.exists() {
+        target = free_path(&target, Path::exists);
+    }
+    if fs::rename(path, &target).is_err() {
+        fs::copy(path, &target).map_err(UnzipError::file("quarantine file", &target))?;
+        fs::remove_file(path).map_err(UnzipError::file("remove infected file", path))?;
+    }
+    Ok(Some(target))
+}
+
+/// A stand-in for clamd on a socket in `dir`, flagging data that holds `EICAR`
+#[cfg(all(test, unix))]
+pub(crate) fn fake_scanner(dir: &Path) -> PathBuf {
+    use std::os::unix::net::UnixListener;
+
+    let socket = dir.join("clamd.sock");
+    let listener = UnixListener::bind(&socket).unwrap();
+    std::thread::spawn(move || {
+        for mut connection in listener.incoming().flatten() {
+            let mut command = [0u8; 10];
+            connection.read_exact(&mut command).unwrap();
+            assert_eq!(&command, b"zINSTREAM\0");
+            let mut data = Vec::new();
+            loop {
+                let mut len = [0u8; 4];
+                connection.read_exact(&mut len).unwrap();
+                let len = u32::from_be_bytes(len) as usize;
+                if len == 0 {
+                    break;
+                }
+                let start = data.len();
+                data.resize(start + len, 0);
+                connection.read_exact(&mut data[start..]).unwrap();
+            }
+            let found = data.windows(5).any(|window| window == b"EICAR");
+            let reply: &[u8] = if found {
+                b"stream: Eicar-Test-Signature FOUND\0"
+            } else {
+                b"stream: OK\0"
+            };
+            connection.write_all(reply).unwrap();
+        }
+    });
+    socket
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_reply() {
+        assert_eq!(parse_reply("stream: OK\0").unwrap(), Verdict::Clean);
+        assert_eq!(
+            parse_reply("stream: Win.Test.EICAR_HDB-1 FOUND\0").unwrap(),
+            Verdict::Infected("Win.Test.EICAR_HDB-1".to_string())
+        );
+        let error = parse_reply("INSTREAM size limit exceeded. ERROR\0").unwrap_err();
+        assert!(error.to_string().contains("size limit"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_and_quarantine() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let socket = fake_scanner(temp_dir.path());
+
+        let mut stream = InStream::open(&socket).unwrap();
+        stream.send(&vec![b'x'; 3 * CHUNK_SIZE]).unwrap();
+        assert_eq!(stream.verdict().unwrap(), Verdict::Clean);
+
+        let path = temp_dir.path().join("written");
+        fs::write(&path, b"X5O!P%@AP EICAR").unwrap();
+        let verdict = scan_file(&socket, &path).unwrap();
+        assert_eq!(verdict, Verdict::Infected("Eicar-Test-Signature".to_string()));
+
+        let jail = temp_dir.path().join("quarantine");
+        fs::create_dir(&jail).unwrap();
+        fs::write(jail.join("a.exe"), b"earlier").unwrap();
+        let moved = quarantine(&path, Path::new("out/a.exe"), Some(&jail)).unwrap();
+        assert_eq!(moved, Some(jail.join("a (1).exe")));
+        assert!(!path.exists());
+    }
+}
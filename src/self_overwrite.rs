@@ -0,0 +1,153 @@
+//! Refusing to extract an archive onto itself (`--allow-self-overwrite`)
+//!
+//! When the output directory comes from path arithmetic that goes wrong, as in a
+//! CI job whose `$PWD` is not what its script assumed, an archive can end up
+//! extracted into the directory it is read from. If it holds a copy of itself,
+//! as build artifacts that bundle their own directory do, the copy is written
+//! over the very file being read, and the rest of the run reads garbage.
+//!
+//! Before anything is written, every selected entry's output path is therefore
+//! compared with the archive file and, for split archives, each of its volumes
+//! (see [`crate::multipart`]). The run fails with [`SelfOverwrite`] when an entry
+//! would be written to one of them, or when a file or symlink entry would take
+//! the place of a directory holding one. Paths are compared after resolving the
+//! output directory, so `..` and symlinks leading to it do not hide a
+//! collision. `-n` and `--auto-rename` runs, which never replace a file, and dry
+//! runs are not checked; `--allow-self-overwrite` extracts regardless.
+//!
+//! # Examples
+//!
+//! ```
+//! use unzip::self_overwrite::SourceGuard;
+//!
+//! let dir = tempfile::tempdir()?;
+//! let archive = dir.path().join("dist/app.zip");
+//! std::fs::create_dir(dir.path().join("dist"))?;
+//! std::fs::write(&archive, b"PK")?;
+//!
+//! let guard = SourceGuard::new(&[archive], dir.path());
+//! assert!(guard.check("dist/app.zip".as_ref(), false).is_some());
+//! assert!(guard.check("dist".as_ref(), false).is_some());
+//! assert!(guard.check("dist".as_ref(), true).is_none());
+//! assert!(guard.check("dist/readme.txt".as_ref(), false).is_none());
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Entries that would be extracted over the archive being read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelfOverwrite {
+    /// What each offending entry would do, one sentence each
+    pub problems: Vec<String>,
+}
+
+impl fmt::Display for SelfOverwrite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "refusing to extract the archive onto itself (--allow-self-overwrite to proceed):"
+        )?;
+        for problem in &self.problems {
+            write!(f, "\n  {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SelfOverwrite {}
+
+/// The files an extraction reads from, resolved against the output directory
+/// they must not be extracted over
+#[derive(Debug, Clone)]
+pub struct SourceGuard {
+    output_dir: PathBuf,
+    sources: Vec<PathBuf>,
+}
+
+impl SourceGuard {
+    /// Guard the archive files `sources` from entries extracted into `output_dir`
+    pub fn new(sources: &[PathBuf], output_dir: &Path) -> Self {
+        Self {
+            output_dir: resolve(output_dir),
+            sources: sources.iter().map(|source| resolve(source)).collect(),
+        }
+    }
+
+    /// What extracting an entry to `relative`, a path inside the output
+    /// directory, would do to the archive; `None` if it leaves it alone
+    pub fn check(&self, relative: &Path, is_dir: bool) -> Option<String> {
+        let path = self.output_dir.join(relative);
+        for source in &self.sources {
+            if *source == path {
+                return Some(format!(
+                    "{} would overwrite {}",
+                    relative.display(),
+                    source.display()
+                ));
+            }
+            if !is_dir && source.starts_with(&path) {
+                return Some(format!(
+                    "{} would replace the directory {} holding {}",
+                    relative.display(),
+                    path.display(),
+                    source.display()
+                ));
+            }
+        }
+        None
+    }
+}
+
+/// `path` made absolute, resolving symlinks along the part of it that exists
+/// and `..` as the filesystem would
+fn resolve(path: &Path) -> PathBuf {
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut resolved = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {},
+            Component::ParentDir => {
+                resolved.pop();
+            },
+            component => {
+                resolved.push(component);
+                // A symlink must be resolved before a `..` after it applies
+                if let Ok(canonical) = fs::canonicalize(&resolved) {
+                    resolved = canonical;
+                }
+            },
+        }
+    }
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_guard_resolves_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("ci/work")).unwrap();
+        let archive = dir.path().join("ci/build.zip");
+        fs::write(&archive, b"PK").unwrap();
+
+        // The output directory reached through `..` and not created yet
+        let output = dir.path().join("ci/work/../out/..");
+        let guard = SourceGuard::new(&[archive], &output);
+        let problem = guard.check(Path::new("build.zip"), false).unwrap();
+        assert!(problem.starts_with("build.zip would overwrite "), "{}", problem);
+        assert!(guard.check(Path::new("work/build.zip"), false).is_none());
+
+        #[cfg(unix)]
+        {
+            let link = dir.path().join("link");
+            std::os::unix::fs::symlink(dir.path().join("ci"), &link).unwrap();
+            let guard = SourceGuard::new(&[dir.path().join("ci/build.zip")], &link);
+            assert!(guard.check(Path::new("build.zip"), true).is_some());
+        }
+    }
+}
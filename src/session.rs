@@ -0,0 +1,259 @@
+//! One archive and one set of options for library callers
+//!
+//! [`UnzipSession`] holds a [`ZipArchive`] over any `Read + Seek` source, a
+//! file, a `Cursor` over bytes already in memory or a caller's own reader,
+//! together with the [`ExtractOptions`] every operation on it follows, so a
+//! program lists, tests and extracts without building CLI [`crate::Args`] or
+//! threading the archive through the free functions:
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use unzip::{ExtractOptions, UnzipSession};
+//!
+//! let mut session = UnzipSession::new(File::open("site.zip")?)?
+//!     .with_options(ExtractOptions::new().output_dir("out").exclude(["*.log"]));
+//! for entry in session.list()? {
+//!     println!("{:>10}  {}", entry.size, entry.name);
+//! }
+//! if session.test()?.errors == 0 {
+//!     session.extract()?;
+//! }
+//! let index = session.read_entry_to_vec("index.html")?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! Names are the decoded names listings show, with the character set of the
+//! options. Member patterns, `exclude` and `member_index` select the entries of
+//! [`UnzipSession::list`], [`UnzipSession::test`] and [`UnzipSession::extract`];
+//! the entries asked for by name are read or extracted whatever they say.
+
+use std::io::{Read, Seek};
+use zip::ZipArchive;
+
+use crate::error::{Result, UnzipError};
+use crate::events::ExtractObserver;
+use crate::extract::{extract_archive, read_entry_data};
+use crate::options::ExtractOptions;
+use crate::permissions::display_mode;
+use crate::test_archive::{TestReport, TestSettings, test_entries};
+
+/// An entry as [`UnzipSession::list`] describes it
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntryInfo {
+    /// Position in the central directory, counting from 0
+    pub index: usize,
+    /// Name, decoded with the character set of the options
+    pub name: String,
+    /// Uncompressed size in bytes
+    pub size: u64,
+    /// Compressed size in bytes
+    pub compressed_size: u64,
+    /// Last modification time, if the entry has a valid one
+    pub modified: Option<zip::DateTime>,
+    /// CRC-32 of the uncompressed data
+    pub crc32: u32,
+    /// Compression method
+    pub method: zip::CompressionMethod,
+    /// Whether the entry is a directory
+    pub is_dir: bool,
+    /// Whether the entry's data is encrypted
+    pub encrypted: bool,
+    /// Unix mode with the file type, with the defaults listings show filled in
+    pub mode: u32,
+}
+
+/// A ZIP archive opened from any seekable reader, with the options to list,
+/// test and extract it by
+#[derive(Debug)]
+pub struct UnzipSession<R: Read + Seek> {
+    archive: ZipArchive<R>,
+    options: ExtractOptions,
+}
+
+impl<R: Read + Seek> UnzipSession<R> {
+    /// Open the archive in `reader`, with default options
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `reader` does not hold a readable ZIP archive
+    pub fn new(reader: R) -> Result<Self> {
+        Ok(Self::from_archive(ZipArchive::new(reader)?))
+    }
+
+    /// Wrap an archive that is already open, with default options
+    pub fn from_archive(archive: ZipArchive<R>) -> Self {
+        Self { archive, options: ExtractOptions::new() }
+    }
+
+    /// Use `options` for every operation from now on
+    pub fn with_options(mut self, options: ExtractOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// The options operations follow
+    pub fn options(&self) -> &ExtractOptions {
+        &self.options
+    }
+
+    /// The archive, for what the session does not offer
+    pub fn archive(&mut self) -> &mut ZipArchive<R> {
+        &mut self.archive
+    }
+
+    /// Give the archive back, ending the session
+    pub fn into_archive(self) -> ZipArchive<R> {
+        self.archive
+    }
+
+    /// Describe the entries the options select, in archive order
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an entry's central directory record cannot be read
+    pub fn list(&mut self) -> Result<Vec<EntryInfo>> {
+        let matcher = self.options.matcher();
+        let mut entries = Vec::new();
+        for index in (0..self.archive.len()).filter(|&i| self.options.considers(i)) {
+            let file = self.archive.by_index_raw(index)?;
+            let name = self.options.names.decode(&file);
+            if !matcher.selects(&name) {
+                continue;
+            }
+            entries.push(EntryInfo {
+                index,
+                name: name.into_owned(),
+                size: file.size(),
+                compressed_size: file.compressed_size(),
+                modified: file.last_modified(),
+                crc32: file.crc32(),
+                method: file.compression(),
+                is_dir: file.is_dir(),
+                encrypted: file.encrypted(),
+                mode: display_mode(&file),
+            });
+        }
+        Ok(entries)
+    }
+
+    /// Check the CRC-32 of every entry the options select, as `unzip -t` does
+    ///
+    /// Entries report to the options' observer, if they have one, and are
+    /// otherwise tested silently. As with [`crate::test_archive_with`], failed
+    /// entries are counted in [`TestReport::errors`] rather than returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the archive cannot be read, an extraction limit is
+    /// crossed, or the options' cancel token is cancelled
+    pub fn test(&mut self) -> Result<TestReport> {
+        let settings = TestSettings::from_options(&self.options);
+        let cancel = &self.options.cancel;
+        match &self.options.observer {
+            Some(observer) => {
+                let mut observer =
+                    observer.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+                test_entries(&mut self.archive, &settings, &mut *observer, cancel)
+            },
+            None => test_entries(&mut self.archive, &settings, &mut Silent, cancel),
+        }
+    }
+
+    /// Extract the entries the options select, as [`extract_archive`] does
+    ///
+    /// # Errors
+    ///
+    /// Fails as [`extract_archive`] does
+    pub fn extract(&mut self) -> Result<()> {
+        extract_archive(&mut self.archive, &self.options)
+    }
+
+    /// Extract the entry named `name` alone, under the options' output
+    /// directory and naming rules, whatever their member patterns select
+    ///
+    /// # Errors
+    ///
+    /// Returns [`UnzipError::NoSuchName`] if no entry has that name, and
+    /// otherwise fails as [`extract_archive`] does
+    pub fn extract_entry(&mut self, name: &str) -> Result<()> {
+        let index = self.index_of(name)?;
+        let mut options = self.options.clone();
+        options.patterns.clear();
+        options.exclude.clear();
+        options.members.clear();
+        options.member_index = Some(index);
+        extract_archive(&mut self.archive, &options)
+    }
+
+    /// Read the data of the entry named `name` into memory, decrypted with the
+    /// options' password and within their extraction limits
+    ///
+    /// # Errors
+    ///
+    /// Returns [`UnzipError::NoSuchName`] if no entry has that name, or an
+    /// error if the entry cannot be read or decrypted or crosses a limit
+    pub fn read_entry_to_vec(&mut self, name: &str) -> Result<Vec<u8>> {
+        let index = self.index_of(name)?;
+        read_entry_data(&mut self.archive, index, name, &self.options)
+    }
+
+    /// Position of the entry whose decoded name is `name`
+    fn index_of(&mut self, name: &str) -> Result<usize> {
+        for index in 0..self.archive.len() {
+            if self.options.names.decode(&self.archive.by_index_raw(index)?) == name {
+                return Ok(index);
+            }
+        }
+        Err(UnzipError::NoSuchName { name: name.to_string() })
+    }
+}
+
+/// Observer of tests run without one
+struct Silent;
+
+impl ExtractObserver for Silent {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::ZipWriter;
+    use zip::write::SimpleFileOptions;
+
+    fn session(files: &[(&str, &[u8])]) -> UnzipSession<Cursor<Vec<u8>>> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in files {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        UnzipSession::from_archive(zip.finish_into_readable().unwrap())
+    }
+
+    #[test]
+    fn test_session_lists_and_tests_selected_entries() {
+        let mut session = session(&[("docs/a.txt", b"hello"), ("build.log", b"noise")])
+            .with_options(ExtractOptions::new().exclude(["*.log"]));
+        let entries = session.list().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!((entries[0].index, entries[0].name.as_str()), (0, "docs/a.txt"));
+        assert_eq!((entries[0].size, entries[0].is_dir), (5, false));
+        assert_eq!(session.test().unwrap(), TestReport { tested: 1, errors: 0 });
+    }
+
+    #[test]
+    fn test_session_reads_and_extracts_by_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = ExtractOptions::new().output_dir(dir.path()).quiet(2).patterns(["*.md"]);
+        let mut session =
+            session(&[("docs/a.txt", b"hello"), ("b.txt", b"bye")]).with_options(options);
+        assert_eq!(session.read_entry_to_vec("docs/a.txt").unwrap(), b"hello");
+
+        // Entries asked for by name are extracted whatever the patterns select
+        session.extract_entry("b.txt").unwrap();
+        assert_eq!(std::fs::read(dir.path().join("b.txt")).unwrap(), b"bye");
+        assert!(!dir.path().join("docs").exists());
+
+        let error = session.read_entry_to_vec("missing.txt").unwrap_err();
+        assert!(matches!(error, UnzipError::NoSuchName { ref name } if name == "missing.txt"));
+    }
+}
@@ -0,0 +1,274 @@
+//! Archives embedded in other data: self-extracting executables and cut-off tails
+//!
+//! A self-extracting archive is a ZIP appended to an executable stub, and other
+//! tools prepend headers or append signatures and padding. The zip crate already
+//! scans for the end of central directory record and works out how far data in
+//! front has moved the archive, whether or not the writer adjusted the offsets
+//! (`zip -A`). It still rejects an archive whose record declares a longer comment
+//! than the file holds, as happens when an SFX stub or a download truncates the
+//! tail, while Info-ZIP warns and carries on. [`open_embedded`] does the same: it
+//! scans the file backwards for an end of central directory record whose central
+//! directory is really there, and reads the archive through an [`EmbeddedReader`]
+//! that ends where the archive does and declares only the comment bytes present.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use zip::ZipArchive;
+//! use unzip::sfx::open_embedded;
+//!
+//! match ZipArchive::new(File::open("setup.exe")?) {
+//!     Ok(archive) => println!("{} entries", archive.len()),
+//!     Err(error) => match open_embedded(File::open("setup.exe")?)? {
+//!         Some(archive) => println!("{} entries, after scanning", archive.len()),
+//!         None => return Err(error.into()),
+//!     },
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::io::{self, Read, Seek, SeekFrom};
+use zip::ZipArchive;
+use zip::result::ZipResult;
+
+use crate::records::{
+    CENTRAL_SIGNATURE, EOCD_LEN, EOCD_SIGNATURE, ZIP64_LOCATOR_LEN, ZIP64_LOCATOR_SIGNATURE,
+    u16_at, u32_at,
+};
+
+/// Offset of the comment length within the end of central directory record
+const COMMENT_LEN_OFFSET: u64 = 20;
+
+/// Bytes read per step while scanning backwards
+const SCAN_CHUNK: u64 = 1024 * 1024;
+
+/// Where an archive found by [`locate_archive`] ends within its file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmbeddedArchive {
+    /// Offset of the end of central directory record
+    pub eocd: u64,
+    /// Length of the archive comment actually present in the file
+    pub comment_len: u16,
+    /// Whether the record declares a longer comment than the file holds
+    pub comment_truncated: bool,
+}
+
+impl EmbeddedArchive {
+    /// Offset just past the archive
+    pub fn end(&self) -> u64 {
+        self.eocd + EOCD_LEN as u64 + u64::from(self.comment_len)
+    }
+}
+
+/// Find the last end of central directory record in `reader` that belongs to an
+/// archive, however much data follows it
+///
+/// A record counts when the central directory it describes starts right before
+/// it, or, for ZIP64 archives, when the ZIP64 locator does.
+///
+/// # Errors
+///
+/// Returns an error if `reader` cannot be read
+pub fn locate_archive<R: Read + Seek>(reader: &mut R) -> io::Result<Option<EmbeddedArchive>> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    let mut chunk = Vec::new();
+    let mut end = len;
+    while end >= EOCD_LEN as u64 {
+        // Overlap chunks so that a signature across their boundary is seen
+        let start = end.saturating_sub(SCAN_CHUNK);
+        let read_end = (end + 3).min(len);
+        chunk.resize((read_end - start) as usize, 0);
+        reader.seek(SeekFrom::Start(start))?;
+        reader.read_exact(&mut chunk)?;
+
+        for at in (0..chunk.len().saturating_sub(3)).rev() {
+            if chunk[at..at + 4] != EOCD_SIGNATURE.to_le_bytes() {
+                continue;
+            }
+            let eocd = start + at as u64;
+            if let Some(found) = check_record(reader, eocd, len)? {
+                return Ok(Some(found));
+            }
+        }
+        if start == 0 {
+            break;
+        }
+        end = start;
+    }
+    Ok(None)
+}
+
+/// The archive ending with the record at `eocd`, if that record is genuine
+fn check_record<R: Read + Seek>(
+    reader: &mut R,
+    eocd: u64,
+    len: u64,
+) -> io::Result<Option<EmbeddedArchive>> {
+    if eocd + EOCD_LEN as u64 > len {
+        return Ok(None);
+    }
+    let mut record = [0u8; EOCD_LEN];
+    reader.seek(SeekFrom::Start(eocd))?;
+    reader.read_exact(&mut record)?;
+    let field16 = |at| u16_at(&record, at).unwrap_or_default();
+    let field32 = |at| u32_at(&record, at).unwrap_or_default();
+    let entries = field16(10);
+    let cd_size = field32(12);
+    let cd_offset = field32(16);
+
+    let genuine = if cd_size == u32::MAX || cd_offset == u32::MAX || entries == u16::MAX {
+        let locator = ZIP64_LOCATOR_LEN as u64;
+        eocd >= locator && signature_at(reader, eocd - locator, ZIP64_LOCATOR_SIGNATURE)?
+    } else if entries == 0 {
+        cd_size == 0
+    } else {
+        u64::from(cd_size) <= eocd
+            && signature_at(reader, eocd - u64::from(cd_size), CENTRAL_SIGNATURE)?
+    };
+    if !genuine {
+        return Ok(None);
+    }
+
+    let available = (len - eocd - EOCD_LEN as u64).min(u64::from(u16::MAX)) as u16;
+    let declared = field16(COMMENT_LEN_OFFSET as usize);
+    Ok(Some(EmbeddedArchive {
+        eocd,
+        comment_len: declared.min(available),
+        comment_truncated: declared > available,
+    }))
+}
+
+fn signature_at<R: Read + Seek>(reader: &mut R, at: u64, signature: u32) -> io::Result<bool> {
+    let mut bytes = [0u8; 4];
+    reader.seek(SeekFrom::Start(at))?;
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes) == signature)
+}
+
+/// Reader over the part of a file up to the end of an embedded archive, declaring
+/// only the comment bytes that are present
+pub struct EmbeddedReader<R> {
+    inner: R,
+    archive: EmbeddedArchive,
+    pos: u64,
+}
+
+impl<R: Read + Seek> EmbeddedReader<R> {
+    /// View `inner` as ending with `archive`
+    pub fn new(inner: R, archive: EmbeddedArchive) -> Self {
+        Self { inner, archive, pos: 0 }
+    }
+}
+
+impl<R: Read + Seek> Read for EmbeddedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.archive.end().saturating_sub(self.pos);
+        let want = buf.len().min(usize::try_from(remaining).unwrap_or(usize::MAX));
+        if want == 0 {
+            return Ok(0);
+        }
+        self.inner.seek(SeekFrom::Start(self.pos))?;
+        let n = self.inner.read(&mut buf[..want])?;
+
+        // Patch the comment length wherever it falls within this read
+        let field = self.archive.eocd + COMMENT_LEN_OFFSET;
+        for (i, byte) in self.archive.comment_len.to_le_bytes().into_iter().enumerate() {
+            let at = field + i as u64;
+            if (self.pos..self.pos + n as u64).contains(&at) {
+                buf[(at - self.pos) as usize] = byte;
+            }
+        }
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for EmbeddedReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.archive.end().checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+        };
+        self.pos = target.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "seek before start of archive")
+        })?;
+        Ok(self.pos)
+    }
+}
+
+/// Open an archive that `ZipArchive::new` rejected by scanning for it
+///
+/// Returns `None` if no archive is found anywhere in `reader`.
+///
+/// # Errors
+///
+/// Returns an error if `reader` cannot be read, or if the archive found is itself
+/// invalid
+pub fn open_embedded<R: Read + Seek>(
+    mut reader: R,
+) -> ZipResult<Option<ZipArchive<EmbeddedReader<R>>>> {
+    let Some(found) = locate_archive(&mut reader)? else {
+        return Ok(None);
+    };
+    Ok(Some(ZipArchive::new(EmbeddedReader::new(reader, found))?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::ZipWriter;
+    use zip::write::SimpleFileOptions;
+
+    fn archive_bytes(comment: &str) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("hello.txt", SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"hello from the payload").unwrap();
+        zip.set_comment(comment);
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn read_hello<R: Read + Seek>(archive: &mut ZipArchive<R>) -> String {
+        let mut content = String::new();
+        archive.by_name("hello.txt").unwrap().read_to_string(&mut content).unwrap();
+        content
+    }
+
+    #[test]
+    fn test_locate_past_stub_and_trailer() {
+        let mut data = b"MZ\x90\x00 stub PK\x05\x06 with a false signature".repeat(100);
+        data.extend(archive_bytes(""));
+        let archive_end = data.len() as u64;
+        data.extend(vec![0xAA; 2 * SCAN_CHUNK as usize]);
+
+        let found = locate_archive(&mut Cursor::new(&data)).unwrap().unwrap();
+        assert_eq!(found.end(), archive_end);
+        assert!(!found.comment_truncated);
+
+        let mut archive = open_embedded(Cursor::new(&data)).unwrap().unwrap();
+        assert_eq!(read_hello(&mut archive), "hello from the payload");
+    }
+
+    #[test]
+    fn test_sfx_with_truncated_comment() {
+        let mut data = b"MZ\x90\x00 self-extractor stub".repeat(100);
+        data.extend(archive_bytes("a comment that gets cut off"));
+        data.truncate(data.len() - 10);
+        assert!(ZipArchive::new(Cursor::new(&data)).is_err());
+
+        let found = locate_archive(&mut Cursor::new(&data)).unwrap().unwrap();
+        assert!(found.comment_truncated);
+        let mut archive = open_embedded(Cursor::new(&data)).unwrap().unwrap();
+        assert_eq!(archive.comment(), b"a comment that ge");
+        assert_eq!(read_hello(&mut archive), "hello from the payload");
+    }
+
+    #[test]
+    fn test_no_archive() {
+        let data = b"not a zip file, PK\x05\x06 notwithstanding".repeat(10);
+        assert_eq!(locate_archive(&mut Cursor::new(&data)).unwrap(), None);
+        assert!(open_embedded(Cursor::new(&data)).unwrap().is_none());
+    }
+}
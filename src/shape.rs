@@ -0,0 +1,438 @@
+//! Archive shape metrics (`--stats`) and the directory creation strategy they pick
+//!
+//! `--stats` also sums up what the archive holds, from its central directory
+//! alone: files and bytes per extension, and the directories with the most bytes
+//! and the most files beneath them ([`ContentStats`]). That is enough to decide
+//! what to `-x` before extracting a dump of hundreds of gigabytes.
+//!
+//! How entries spread over directories decides how directories are best created.
+//! An archive with 100k files in a single directory checks that directory for
+//! existence 100k times when every file creates its own parent; one bulk pass that
+//! creates each directory once up front replaces all of those checks. A deep tree
+//! whose directories hold a file or two gains nothing from such a pass, so there
+//! parents are still created as files need them.
+//!
+//! # Examples
+//!
+//! ```
+//! use unzip::shape::{ArchiveShape, DirStrategy};
+//!
+//! let names = (0..100).map(|i| format!("icons/{}.png", i));
+//! let shape = ArchiveShape::from_names(names.chain(["README".to_string()]));
+//! assert_eq!(shape.max_depth(), 1);
+//! assert_eq!(shape.top_fan_out(1)[0].1, 100);
+//! assert_eq!(shape.dir_strategy(), DirStrategy::Bulk);
+//! ```
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::io::{self, BufWriter, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+use crate::args::Args;
+use crate::error::Result;
+use crate::list::stopped_reading;
+use crate::utils::format_size;
+
+/// Average number of files per directory from which a bulk pass pays off
+const BULK_MIN_FILES_PER_DIR: f64 = 16.0;
+
+/// Number of directories `--stats` lists by file count
+const TOP_FAN_OUT: usize = 5;
+
+/// Number of extensions, and of directories, `--stats` lists by size
+const TOP_CONTENT: usize = 10;
+
+/// How extraction creates the parent directories of files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirStrategy {
+    /// Check for and create the parent of each file as it is extracted
+    PerFile,
+    /// Create every directory once before any file is extracted
+    Bulk,
+}
+
+impl fmt::Display for DirStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PerFile => write!(f, "per-file"),
+            Self::Bulk => write!(f, "bulk"),
+        }
+    }
+}
+
+/// Depth and fan-out of the paths in an archive
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveShape {
+    files: usize,
+    directories: usize,
+    max_depth: usize,
+    /// Number of files directly in each directory; the root is the empty path
+    fan_out: HashMap<PathBuf, usize>,
+}
+
+impl ArchiveShape {
+    /// Shape of entry names, where names ending in `/` are directories
+    pub fn from_names<I, S>(names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut shape = Self::default();
+        for name in names {
+            let name = name.as_ref();
+            match name.strip_suffix('/') {
+                Some(dir) => shape.add_directory(Path::new(dir)),
+                None => shape.add_file(Path::new(name)),
+            }
+        }
+        shape
+    }
+
+    /// Count a file at `path`, relative to the archive or output root
+    pub fn add_file(&mut self, path: &Path) {
+        let parent = path.parent().unwrap_or(Path::new(""));
+        self.files += 1;
+        self.max_depth = self.max_depth.max(parent.components().count());
+        *self.fan_out.entry(parent.to_path_buf()).or_default() += 1;
+    }
+
+    /// Count a directory entry at `path`
+    pub fn add_directory(&mut self, path: &Path) {
+        self.directories += 1;
+        self.max_depth = self.max_depth.max(path.components().count());
+    }
+
+    /// Number of files
+    pub fn files(&self) -> usize {
+        self.files
+    }
+
+    /// Number of directory entries
+    pub fn directories(&self) -> usize {
+        self.directories
+    }
+
+    /// Deepest directory nesting; files at the root are at depth 0
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Average number of files in the directories that hold any
+    pub fn files_per_directory(&self) -> f64 {
+        if self.fan_out.is_empty() {
+            return 0.0;
+        }
+        self.files as f64 / self.fan_out.len() as f64
+    }
+
+    /// The `n` directories holding the most files, most first
+    pub fn top_fan_out(&self, n: usize) -> Vec<(&Path, usize)> {
+        let mut dirs: Vec<_> =
+            self.fan_out.iter().map(|(dir, &count)| (dir.as_path(), count)).collect();
+        dirs.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        dirs.truncate(n);
+        dirs
+    }
+
+    /// How extraction should create parent directories for this shape
+    pub fn dir_strategy(&self) -> DirStrategy {
+        if self.files_per_directory() >= BULK_MIN_FILES_PER_DIR {
+            DirStrategy::Bulk
+        } else {
+            DirStrategy::PerFile
+        }
+    }
+}
+
+/// Files and uncompressed bytes of one extension or under one directory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Usage {
+    /// Number of files
+    pub files: usize,
+    /// Their uncompressed size
+    pub bytes: u64,
+}
+
+impl Usage {
+    fn add(&mut self, other: Usage) {
+        self.files += other.files;
+        self.bytes += other.bytes;
+    }
+}
+
+/// Files and bytes of an archive by extension and by directory
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use unzip::shape::{ContentStats, Usage};
+///
+/// let mut stats = ContentStats::default();
+/// stats.add_file(Path::new("data/raw/a.CSV"), 700);
+/// stats.add_file(Path::new("data/b.csv"), 200);
+/// stats.add_file(Path::new("README"), 10);
+/// assert_eq!(stats.top_extensions(1), [("csv", Usage { files: 2, bytes: 900 })]);
+/// assert_eq!(stats.top_directories_by_size(1), [(Path::new("data"), Usage { files: 2, bytes: 900 })]);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ContentStats {
+    /// Usage per lowercased extension; files without one are under ""
+    extensions: HashMap<String, Usage>,
+    /// Usage of everything beneath each directory, at any depth
+    directories: HashMap<PathBuf, Usage>,
+}
+
+impl ContentStats {
+    /// Stats of the files in `archive`, from its central directory
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the archive's entry table cannot be read
+    pub fn from_archive<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<Self> {
+        let mut stats = Self::default();
+        for i in 0..archive.len() {
+            let file = archive.by_index_raw(i)?;
+            if !file.is_dir() {
+                stats.add_file(Path::new(file.name()), file.size());
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Count a file of `size` bytes at `path`
+    pub fn add_file(&mut self, path: &Path, size: u64) {
+        let usage = Usage { files: 1, bytes: size };
+        let extension = path.extension().map(|ext| ext.to_string_lossy().to_lowercase());
+        self.extensions.entry(extension.unwrap_or_default()).or_default().add(usage);
+        for dir in path.ancestors().skip(1).filter(|dir| !dir.as_os_str().is_empty()) {
+            self.directories.entry(dir.to_path_buf()).or_default().add(usage);
+        }
+    }
+
+    /// The `n` extensions with the most bytes, most first; `""` stands for
+    /// files without one
+    pub fn top_extensions(&self, n: usize) -> Vec<(&str, Usage)> {
+        let extensions = self.extensions.iter().map(|(ext, &usage)| (ext.as_str(), usage));
+        top(extensions, n, |usage| (usage.bytes, usage.files as u64))
+    }
+
+    /// Usage of the extensions [`ContentStats::top_extensions`] leaves out of its
+    /// `n`, and how many there are
+    pub fn other_extensions(&self, n: usize) -> (usize, Usage) {
+        let mut other = Usage::default();
+        let top = self.top_extensions(n);
+        for (ext, usage) in &self.extensions {
+            if !top.iter().any(|(top, _)| top == ext) {
+                other.add(*usage);
+            }
+        }
+        (self.extensions.len() - top.len(), other)
+    }
+
+    /// The `n` directories with the most bytes beneath them, most first
+    pub fn top_directories_by_size(&self, n: usize) -> Vec<(&Path, Usage)> {
+        let dirs = self.directories.iter().map(|(dir, &usage)| (dir.as_path(), usage));
+        top(dirs, n, |usage| (usage.bytes, usage.files as u64))
+    }
+
+    /// The `n` directories with the most files beneath them, most first
+    pub fn top_directories_by_files(&self, n: usize) -> Vec<(&Path, Usage)> {
+        let dirs = self.directories.iter().map(|(dir, &usage)| (dir.as_path(), usage));
+        top(dirs, n, |usage| (usage.files as u64, usage.bytes))
+    }
+}
+
+/// The `n` items with the largest `key`, largest first and then by name
+fn top<'a, K: Ord + ?Sized>(
+    items: impl Iterator<Item = (&'a K, Usage)>,
+    n: usize,
+    key: impl Fn(&Usage) -> (u64, u64),
+) -> Vec<(&'a K, Usage)> {
+    let mut items: Vec<_> = items.collect();
+    items.sort_by(|a, b| key(&b.1).cmp(&key(&a.1)).then_with(|| a.0.cmp(b.0)));
+    items.truncate(n);
+    items
+}
+
+/// Directories to create for a bulk pass: each of `dirs` that is not an ancestor
+/// of another, since creating a directory creates its ancestors too
+pub fn leaf_directories(dirs: impl IntoIterator<Item = PathBuf>) -> Vec<PathBuf> {
+    let dirs: BTreeSet<PathBuf> = dirs.into_iter().collect();
+    let mut leaves: Vec<PathBuf> = Vec::new();
+    // Sorted by components, so descendants directly follow their ancestors
+    for dir in dirs.into_iter().rev() {
+        if leaves.last().is_none_or(|last| !last.starts_with(&dir)) {
+            leaves.push(dir);
+        }
+    }
+    leaves.reverse();
+    leaves
+}
+
+/// Print the shape of the archive's entry names and what it holds (`--stats`)
+///
+/// A reader that stops early, like `head`, ends the report without an error.
+///
+/// # Arguments
+///
+/// * `archive` - The ZIP archive to describe
+/// * `args` - Command line arguments; `-q` drops the header line
+///
+/// # Errors
+///
+/// Returns an error if the archive's entry table cannot be read
+pub fn display_stats<R: Read + Seek>(archive: &mut ZipArchive<R>, args: &Args) -> Result<()> {
+    let shape = ArchiveShape::from_names(archive.file_names());
+    let content = ContentStats::from_archive(archive)?;
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let written = write_stats(&mut out, &shape, &content, args).and_then(|()| out.flush());
+    stopped_reading(written.map_err(Into::into))
+}
+
+/// Write the `--stats` report of `shape` and `content` to `out`
+fn write_stats(
+    out: &mut impl Write,
+    shape: &ArchiveShape,
+    content: &ContentStats,
+    args: &Args,
+) -> io::Result<()> {
+    if args.quiet == 0 {
+        writeln!(out, "Archive:  {}", args.zipfile.display())?;
+    }
+    writeln!(out, "  files:                {}", shape.files())?;
+    writeln!(out, "  directory entries:    {}", shape.directories())?;
+    writeln!(out, "  max depth:            {}", shape.max_depth())?;
+    writeln!(out, "  files per directory:  {:.1}", shape.files_per_directory())?;
+    writeln!(out, "  directory creation:   {}", shape.dir_strategy())?;
+    let top = shape.top_fan_out(TOP_FAN_OUT);
+    if !top.is_empty() {
+        writeln!(out, "  largest directories:")?;
+        for (dir, count) in top {
+            let dir = if dir.as_os_str().is_empty() {
+                "(root)".to_string()
+            } else {
+                format!("{}/", dir.display())
+            };
+            writeln!(out, "    {:>8}  {}", count, dir)?;
+        }
+    }
+
+    let extensions = content.top_extensions(TOP_CONTENT);
+    if !extensions.is_empty() {
+        writeln!(out, "  extensions by size:")?;
+        writeln!(out, "    {:>8}  {:>8}  extension", "size", "files")?;
+        for (ext, usage) in extensions {
+            let ext = if ext.is_empty() {
+                "(none)".to_string()
+            } else {
+                format!(".{}", ext)
+            };
+            writeln!(out, "    {:>8}  {:>8}  {}", format_size(usage.bytes), usage.files, ext)?;
+        }
+        let (count, other) = content.other_extensions(TOP_CONTENT);
+        if count > 0 {
+            let (size, files) = (format_size(other.bytes), other.files);
+            writeln!(out, "    {:>8}  {:>8}  ({} other extensions)", size, files, count)?;
+        }
+    }
+    let by_size = content.top_directories_by_size(TOP_CONTENT);
+    if !by_size.is_empty() {
+        writeln!(out, "  directories by size:")?;
+        writeln!(out, "    {:>8}  {:>8}  directory", "size", "files")?;
+        for (dir, usage) in by_size {
+            writeln!(
+                out,
+                "    {:>8}  {:>8}  {}/",
+                format_size(usage.bytes),
+                usage.files,
+                dir.display()
+            )?;
+        }
+        writeln!(out, "  directories by files:")?;
+        writeln!(out, "    {:>8}  {:>8}  directory", "files", "size")?;
+        for (dir, usage) in content.top_directories_by_files(TOP_CONTENT) {
+            writeln!(
+                out,
+                "    {:>8}  {:>8}  {}/",
+                usage.files,
+                format_size(usage.bytes),
+                dir.display()
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deep_sparse_tree_creates_per_file() {
+        let shape = ArchiveShape::from_names(["a/", "a/b/", "a/b/c/d/e.txt", "a/x.txt", "top.txt"]);
+        assert_eq!((shape.files(), shape.directories()), (3, 2));
+        assert_eq!(shape.max_depth(), 4);
+        assert_eq!(shape.files_per_directory(), 1.0);
+        assert_eq!(shape.dir_strategy(), DirStrategy::PerFile);
+    }
+
+    #[test]
+    fn test_wide_directory_creates_in_bulk() {
+        let mut names: Vec<String> = (0..1000).map(|i| format!("data/{}.bin", i)).collect();
+        names.push("data/sub/one.bin".to_string());
+        let shape = ArchiveShape::from_names(&names);
+        assert_eq!(shape.top_fan_out(3), [(Path::new("data"), 1000), (Path::new("data/sub"), 1)]);
+        assert_eq!(shape.dir_strategy(), DirStrategy::Bulk);
+    }
+
+    #[test]
+    fn test_empty_archive() {
+        let shape = ArchiveShape::from_names(Vec::<String>::new());
+        assert_eq!(shape.files_per_directory(), 0.0);
+        assert!(shape.top_fan_out(5).is_empty());
+        assert_eq!(shape.dir_strategy(), DirStrategy::PerFile);
+    }
+
+    #[test]
+    fn test_content_stats() {
+        let mut stats = ContentStats::default();
+        for i in 0..12 {
+            stats.add_file(Path::new(&format!("dump/part-{}.ext{}", i, i)), 100 + i);
+        }
+        for i in 0..3 {
+            stats.add_file(Path::new(&format!("dump/raw/{}.BIN", i)), 1000);
+        }
+        stats.add_file(Path::new("logs/a"), 1);
+        stats.add_file(Path::new("logs/b"), 1);
+        stats.add_file(Path::new("logs/c"), 1);
+        stats.add_file(Path::new("logs/d"), 1);
+
+        let top = stats.top_extensions(3);
+        assert_eq!(top[0], ("bin", Usage { files: 3, bytes: 3000 }));
+        assert_eq!(top[1], ("ext11", Usage { files: 1, bytes: 111 }));
+        assert_eq!(stats.top_extensions(100).last().unwrap().0, "");
+        assert_eq!(
+            stats.other_extensions(3),
+            (11, Usage { files: 14, bytes: 1266 - 111 - 110 + 4 })
+        );
+
+        let by_size = stats.top_directories_by_size(2);
+        assert_eq!(by_size[0], (Path::new("dump"), Usage { files: 15, bytes: 4266 }));
+        assert_eq!(by_size[1], (Path::new("dump/raw"), Usage { files: 3, bytes: 3000 }));
+        let by_files = stats.top_directories_by_files(3);
+        assert_eq!(
+            by_files.iter().map(|(dir, _)| *dir).collect::<Vec<_>>(),
+            [Path::new("dump"), Path::new("logs"), Path::new("dump/raw")]
+        );
+    }
+
+    #[test]
+    fn test_leaf_directories() {
+        let dirs = ["out/a", "out/a/b", "out/c", "out/a/b/d", "out/ab", "out/a"].map(PathBuf::from);
+        assert_eq!(leaf_directories(dirs), ["out/a/b/d", "out/ab", "out/c"].map(PathBuf::from));
+    }
+}
@@ -0,0 +1,208 @@
+//! Extraction into a directory other processes extract into too (`--shared-dest`)
+//!
+//! Cluster nodes unpacking overlapping archives into one scratch directory race
+//! on the same files: two processes truncating and writing a file interleave
+//! their data, and a reader can see a half-written one. With `--shared-dest`,
+//! each file is written under a temporary name of its own, `.NAME.unzip-PID-N`,
+//! created exclusively (`O_EXCL`) next to its destination, and renamed into place
+//! once complete, with its times and permissions already set. Other processes
+//! see either no file or a whole one.
+//!
+//! An existing file that holds the entry's size and CRC32 counts as done: the
+//! entry is skipped as [`SkipReason::Unchanged`](crate::events::SkipReason::Unchanged),
+//! whether the file was there before the run or another process placed it while
+//! this one was writing. Otherwise the overwrite mode decides: with `-o`, `-u`,
+//! `-f` or `--if-changed` the rename replaces the file atomically; without, it
+//! does not replace a file placed meanwhile (`RENAME_NOREPLACE`, or a hard link
+//! where that is not available) and the entry is skipped as existing.
+//!
+//! No locks are taken, so a process that dies cannot block the others; it may
+//! leave a temporary file behind.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::path::Path;
+//! use unzip::shared::temp_path;
+//!
+//! let temp = temp_path(Path::new("out/data.bin"));
+//! assert!(temp.file_name().unwrap().to_string_lossy().starts_with(".data.bin.unzip-"));
+//! ```
+
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::events::SkipReason;
+use crate::unchanged::is_unchanged;
+
+/// Marks temporary files, between the file name and the process ID
+const TEMP_INFIX: &str = ".unzip-";
+
+/// Hidden name next to `path`, unique to this process and call, to write a file
+/// under before it is put in place
+pub fn temp_path(path: &Path) -> PathBuf {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(format!(
+        "{}{}-{}",
+        TEMP_INFIX,
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    path.with_file_name(name)
+}
+
+/// Where a file written under a temporary name ended up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Placed {
+    /// The file was put in place
+    Written,
+    /// Another process placed a file with the same content first
+    Identical,
+    /// Another process placed a different file first, which was kept
+    Exists,
+}
+
+impl Placed {
+    /// Why the entry counts as skipped, `None` if its file was placed
+    pub(crate) fn skip_reason(self) -> Option<SkipReason> {
+        match self {
+            Self::Written => None,
+            Self::Identical => Some(SkipReason::Unchanged),
+            Self::Exists => Some(SkipReason::Exists),
+        }
+    }
+}
+
+/// A file being written under a temporary name, removed unless it is placed
+#[derive(Debug)]
+pub(crate) struct PendingFile {
+    temp: PathBuf,
+    placed: bool,
+}
+
+impl PendingFile {
+    /// Pending file for the destination `path`, written at a [`temp_path`]
+    pub(crate) fn new(path: &Path) -> Self {
+        Self { temp: temp_path(path), placed: false }
+    }
+
+    /// The temporary name to write to
+    pub(crate) fn path(&self) -> &Path {
+        &self.temp
+    }
+
+    /// Move the file into place at `path`, replacing an existing file only if
+    /// `replace`; a file kept there is compared with `size` and `crc32`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be moved
+    pub(crate) fn place(
+        mut self,
+        path: &Path,
+        replace: bool,
+        size: u64,
+        crc32: u32,
+    ) -> io::Result<Placed> {
+        let moved = if replace {
+            fs::rename(&self.temp, path)
+        } else {
+            rename_noreplace(&self.temp, path)
+        };
+        match moved {
+            Ok(()) => {
+                self.placed = true;
+                Ok(Placed::Written)
+            },
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
+                if is_unchanged(path, size, crc32).unwrap_or(false) {
+                    Ok(Placed::Identical)
+                } else {
+                    Ok(Placed::Exists)
+                }
+            },
+            Err(error) => Err(error),
+        }
+    }
+}
+
+impl Drop for PendingFile {
+    fn drop(&mut self) {
+        if !self.placed {
+            let _ = fs::remove_file(&self.temp);
+        }
+    }
+}
+
+/// Rename `from` to `to` unless something exists at `to`, atomically
+#[cfg(target_os = "linux")]
+fn rename_noreplace(from: &Path, to: &Path) -> io::Result<()> {
+    use rustix::fs::{CWD, RenameFlags, renameat_with};
+    use rustix::io::Errno;
+
+    match renameat_with(CWD, from, CWD, to, RenameFlags::NOREPLACE) {
+        // Filesystems without RENAME_NOREPLACE
+        Err(Errno::INVAL) | Err(Errno::NOSYS) => link_noreplace(from, to),
+        result => Ok(result?),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn rename_noreplace(from: &Path, to: &Path) -> io::Result<()> {
+    link_noreplace(from, to)
+}
+
+/// Link `from` as `to`, which fails if `to` exists, then remove `from`
+fn link_noreplace(from: &Path, to: &Path) -> io::Result<()> {
+    fs::hard_link(from, to)?;
+    fs::remove_file(from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+
+    fn pending(path: &Path, data: &[u8]) -> PendingFile {
+        let file = PendingFile::new(path);
+        File::create_new(file.path()).unwrap().write_all(data).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_temp_paths_are_unique() {
+        let path = Path::new("out/a.txt");
+        let (first, second) = (temp_path(path), temp_path(path));
+        assert_ne!(first, second);
+        assert_eq!(first.parent(), Some(Path::new("out")));
+    }
+
+    #[test]
+    fn test_place() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("a.txt");
+        let crc32 = crc32fast::hash(b"new");
+
+        assert_eq!(pending(&path, b"new").place(&path, false, 3, crc32).unwrap(), Placed::Written);
+        assert_eq!(
+            pending(&path, b"new").place(&path, false, 3, crc32).unwrap(),
+            Placed::Identical
+        );
+        fs::write(&path, b"other").unwrap();
+        assert_eq!(pending(&path, b"new").place(&path, false, 3, crc32).unwrap(), Placed::Exists);
+        assert_eq!(fs::read(&path).unwrap(), b"other");
+        assert_eq!(pending(&path, b"new").place(&path, true, 3, crc32).unwrap(), Placed::Written);
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+
+        drop(pending(&path, b"abandoned"));
+        let names: Vec<_> =
+            fs::read_dir(temp_dir.path()).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(names, ["a.txt"]);
+    }
+}
@@ -0,0 +1,277 @@
+//! Pluggable extraction targets
+//!
+//! [`extract_to_sink`](crate::extract_to_sink) selects, names and
+//! reads entries as [`extract_archive`](crate::extract_archive) does, with the
+//! same patterns, naming rules, password and extraction limits, but hands every
+//! entry to an [`EntrySink`] instead of the filesystem. A sink creates
+//! directories, opens a writer for each file's data and applies the metadata
+//! once the data is written, so a program can extract into memory, a tar
+//! stream, an object store or a test harness without touching disk.
+//!
+//! Two sinks come with the crate: [`DirSink`], the filesystem, confined to its
+//! root like every extraction, and [`MemorySink`], which
+//! [`extract_to_vec`](crate::extract_to_vec) uses. Overwrite prompts, the
+//! threaded writers and the other disk-only features stay with
+//! [`extract_archive`](crate::extract_archive).
+//!
+//! ```
+//! use std::io::{self, Cursor, Write};
+//! use zip::{ZipArchive, ZipWriter, write::SimpleFileOptions};
+//! use unzip::{ExtractOptions, extract_to_sink};
+//! use unzip::sink::{EntrySink, SinkEntry};
+//!
+//! /// Counts the bytes of every file instead of storing them
+//! #[derive(Default)]
+//! struct Sizes(Vec<(String, u64)>);
+//!
+//! impl EntrySink for Sizes {
+//!     fn create_file(&mut self, entry: &SinkEntry) -> io::Result<Box<dyn Write + '_>> {
+//!         self.0.push((entry.name.clone(), 0));
+//!         Ok(Box::new(Counter(&mut self.0.last_mut().unwrap().1)))
+//!     }
+//! }
+//!
+//! struct Counter<'a>(&'a mut u64);
+//!
+//! impl Write for Counter<'_> {
+//!     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+//!         *self.0 += buf.len() as u64;
+//!         Ok(buf.len())
+//!     }
+//!     fn flush(&mut self) -> io::Result<()> {
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+//! zip.start_file("docs/readme.txt", SimpleFileOptions::default())?;
+//! zip.write_all(b"hello")?;
+//! let mut archive = ZipArchive::new(zip.finish()?)?;
+//!
+//! let mut sizes = Sizes::default();
+//! extract_to_sink(&mut archive, &ExtractOptions::new(), &mut sizes)?;
+//! assert_eq!(sizes.0, [("docs/readme.txt".to_string(), 5)]);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::destination::Destination;
+use crate::extract::finalize_extracted_file;
+use crate::timestamps::EntryTimes;
+
+/// An entry as it is handed to an [`EntrySink`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SinkEntry {
+    /// Relative output path, after sanitizing and the naming options
+    pub path: PathBuf,
+    /// Name, decoded with the character set of the options
+    pub name: String,
+    /// Uncompressed size in bytes, as the archive records it
+    pub size: u64,
+    /// Times to restore, empty when the options skip timestamps
+    pub times: EntryTimes,
+    /// Unix mode from the archive, if it records one
+    pub mode: Option<u32>,
+    /// Whether the entry is a directory
+    pub is_dir: bool,
+}
+
+/// Where extracted entries go
+///
+/// Directories are created before the files in them, each file's data is
+/// written to the writer [`EntrySink::create_file`] returns, which is flushed
+/// and dropped before [`EntrySink::set_metadata`] is called for the file, and
+/// directory metadata is set last, deepest first.
+pub trait EntrySink {
+    /// Create the directory `entry`; nothing by default
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be created, which stops the
+    /// extraction
+    fn create_dir(&mut self, entry: &SinkEntry) -> io::Result<()> {
+        let _ = entry;
+        Ok(())
+    }
+
+    /// Start the file `entry`, returning the writer its data goes to
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be created, which stops the
+    /// extraction
+    fn create_file(&mut self, entry: &SinkEntry) -> io::Result<Box<dyn Write + '_>>;
+
+    /// Apply the times and mode of `entry` once it is complete; nothing by
+    /// default
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the metadata cannot be applied, which stops the
+    /// extraction
+    fn set_metadata(&mut self, entry: &SinkEntry) -> io::Result<()> {
+        let _ = entry;
+        Ok(())
+    }
+}
+
+/// Files kept in memory, keyed by output path with `/` separators
+///
+/// Directories are left out, and a later file with the same path replaces an
+/// earlier one.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemorySink {
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+impl MemorySink {
+    /// Create an empty sink
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The files extracted so far
+    pub fn files(&self) -> &BTreeMap<String, Vec<u8>> {
+        &self.files
+    }
+
+    /// The files extracted, ending the sink
+    pub fn into_files(self) -> BTreeMap<String, Vec<u8>> {
+        self.files
+    }
+}
+
+impl EntrySink for MemorySink {
+    fn create_file(&mut self, entry: &SinkEntry) -> io::Result<Box<dyn Write + '_>> {
+        let key = entry
+            .path
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let data = self.files.entry(key).or_default();
+        data.clear();
+        Ok(Box::new(data))
+    }
+}
+
+/// Entries written below a directory on disk, with their times and modes
+///
+/// Paths are confined to the directory as in every extraction: an entry
+/// reached through a symlink leaving it is refused.
+#[derive(Debug)]
+pub struct DirSink {
+    root: PathBuf,
+    destination: Destination,
+}
+
+impl DirSink {
+    /// Write below `root`, creating it if it is missing
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `root` cannot be created or opened
+    pub fn new(root: impl AsRef<Path>) -> io::Result<Self> {
+        let root = root.as_ref();
+        fs::create_dir_all(root)?;
+        Ok(Self { root: root.to_path_buf(), destination: Destination::open(root, false)? })
+    }
+
+    /// The directory entries are written below
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+}
+
+impl EntrySink for DirSink {
+    fn create_dir(&mut self, entry: &SinkEntry) -> io::Result<()> {
+        self.destination.create_dir_all(&self.root.join(&entry.path))
+    }
+
+    fn create_file(&mut self, entry: &SinkEntry) -> io::Result<Box<dyn Write + '_>> {
+        let path = self.root.join(&entry.path);
+        if let Some(parent) = path.parent() {
+            self.destination.create_dir_all(parent)?;
+        }
+        Ok(Box::new(BufWriter::new(self.destination.create_file(&path)?)))
+    }
+
+    fn set_metadata(&mut self, entry: &SinkEntry) -> io::Result<()> {
+        finalize_extracted_file(&self.root.join(&entry.path), entry.times, entry.mode, false);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extract::extract_to_sink;
+    use crate::options::ExtractOptions;
+    use std::io::Cursor;
+    use zip::write::SimpleFileOptions;
+    use zip::{ZipArchive, ZipWriter};
+
+    fn archive() -> ZipArchive<Cursor<Vec<u8>>> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.add_directory("docs/", SimpleFileOptions::default()).unwrap();
+        let private = SimpleFileOptions::default().unix_permissions(0o600);
+        zip.start_file("docs/secret.txt", private).unwrap();
+        zip.write_all(b"hidden").unwrap();
+        zip.finish_into_readable().unwrap()
+    }
+
+    /// Records the calls it receives
+    #[derive(Default)]
+    struct Calls(Vec<String>);
+
+    impl EntrySink for Calls {
+        fn create_dir(&mut self, entry: &SinkEntry) -> io::Result<()> {
+            self.0.push(format!("dir {}", entry.name));
+            Ok(())
+        }
+
+        fn create_file(&mut self, entry: &SinkEntry) -> io::Result<Box<dyn Write + '_>> {
+            self.0.push(format!("file {}", entry.name));
+            Ok(Box::new(io::sink()))
+        }
+
+        fn set_metadata(&mut self, entry: &SinkEntry) -> io::Result<()> {
+            self.0.push(format!("metadata {}", entry.name));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_sink_receives_directories_files_then_directory_metadata() {
+        let mut calls = Calls::default();
+        extract_to_sink(&mut archive(), &ExtractOptions::new(), &mut calls).unwrap();
+        assert_eq!(
+            calls.0,
+            [
+                "dir docs/",
+                "file docs/secret.txt",
+                "metadata docs/secret.txt",
+                "metadata docs/"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dir_sink_writes_data_and_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("out");
+        let mut sink = DirSink::new(&root).unwrap();
+        extract_to_sink(&mut archive(), &ExtractOptions::new(), &mut sink).unwrap();
+        assert_eq!(fs::read(root.join("docs/secret.txt")).unwrap(), b"hidden");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(root.join("docs/secret.txt")).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+}
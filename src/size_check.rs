@@ -0,0 +1,476 @@
+//! Reconciling the size fields of each entry (`--size-check`)
+//!
+//! An entry's sizes are written up to five times: in the 32-bit fields of its
+//! central record and of its local header, in the Zip64 extra field of either,
+//! and in the data descriptor after its data. Some writers do not agree with
+//! themselves: one vendor SDK writes 32-bit sizes next to Zip64 values that
+//! differ from them, and which of the two the zip crate goes by then depends on
+//! the length of the extra field. `--size-check=MODE` reads every one of them
+//! before anything is extracted or tested:
+//!
+//! - `strict` fails the run with [`SizeCheckError::Disagreement`] if the values
+//!   of any entry differ, listing each difference
+//! - `lenient` warns about each difference and settles on the most plausible
+//!   value. For the compressed size, that is the one at whose end the next
+//!   header or the central directory starts. Otherwise, and for the size, it is
+//!   the value the most fields hold, with a 32-bit field counting for the 64-bit
+//!   value it is the low half of, and the central directory breaking ties
+//!
+//! Either way, each entry must then decompress to exactly the size settled on
+//! (see [`ExpectedSizes`]), or it fails with [`SizeCheckError::Decompressed`].
+//! The data itself is still read as the zip crate finds it.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::io::{Cursor, Write};
+//! use unzip::size_check::{SizeCheck, check_sizes, parse_size_check};
+//! use zip::ZipWriter;
+//! use zip::write::SimpleFileOptions;
+//!
+//! let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+//! zip.start_file("hello.txt", SimpleFileOptions::default())?;
+//! zip.write_all(b"hello")?;
+//! let data = zip.finish()?.into_inner();
+//!
+//! assert_eq!(parse_size_check("strict"), Ok(SizeCheck::Strict));
+//! let sizes = check_sizes(Cursor::new(&data), SizeCheck::Strict, |_| {})?;
+//! assert_eq!(sizes.expected(0, 5), 5);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom};
+
+use crate::central::{
+    CentralDirectory, CentralRecord, HeaderSizes, read_descriptor, read_local_header,
+};
+use crate::error::Result;
+use crate::records::{CENTRAL_SIGNATURE, LOCAL_SIGNATURE};
+
+/// Lengths a data descriptor can have: with or without its signature, with
+/// 32-bit or 64-bit sizes
+const DESCRIPTOR_LENS: [u64; 4] = [12, 16, 20, 24];
+
+/// Central records read ahead of looking at their local headers
+const BATCH: usize = 4096;
+
+/// What `--size-check` does when the size fields of an entry disagree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeCheck {
+    /// Refuse the archive
+    Strict,
+    /// Warn, and go by the most plausible value
+    Lenient,
+}
+
+impl fmt::Display for SizeCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Strict => write!(f, "strict"),
+            Self::Lenient => write!(f, "lenient"),
+        }
+    }
+}
+
+/// Parse a `--size-check` mode: `strict` or `lenient`
+///
+/// # Errors
+///
+/// Returns a message suitable for clap if the mode is unknown
+pub fn parse_size_check(value: &str) -> std::result::Result<SizeCheck, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "strict" => Ok(SizeCheck::Strict),
+        "lenient" => Ok(SizeCheck::Lenient),
+        _ => Err(format!("invalid size check: {} (expected strict or lenient)", value)),
+    }
+}
+
+/// A failed `--size-check`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SizeCheckError {
+    /// With `strict`, the size fields that disagree, one line per entry and size
+    Disagreement(Vec<String>),
+    /// An entry decompressed to another size than the one settled on
+    Decompressed {
+        /// Entry name
+        name: String,
+        /// The size settled on
+        expected: u64,
+        /// The size of the data decompressed
+        actual: u64,
+    },
+}
+
+impl fmt::Display for SizeCheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Disagreement(problems) => {
+                write!(f, "size fields disagree (--size-check=strict):")?;
+                for problem in problems {
+                    write!(f, "\n  {}", problem)?;
+                }
+                Ok(())
+            },
+            Self::Decompressed { name, expected, actual } => write!(
+                f,
+                "{}: decompressed to {} bytes, but its size is {} (--size-check)",
+                name, actual, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SizeCheckError {}
+
+/// The size each entry must decompress to after [`check_sizes`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExpectedSizes {
+    /// Sizes settled on for entries whose fields disagree, by the offset of
+    /// their local header; the others hold one size throughout
+    settled: HashMap<u64, u64>,
+}
+
+impl ExpectedSizes {
+    /// The size the entry whose local header is at `header_start` must
+    /// decompress to, given the size the zip crate `declared` for it
+    pub fn expected(&self, header_start: u64, declared: u64) -> u64 {
+        self.settled.get(&header_start).copied().unwrap_or(declared)
+    }
+}
+
+/// Read every size field of every entry of the archive in `reader` and
+/// reconcile them as `mode` says, calling `warn` with each difference under
+/// `lenient`
+///
+/// # Errors
+///
+/// Returns [`SizeCheckError::Disagreement`] under `strict` if any fields
+/// disagree, after reading all of them, or an error if the central directory
+/// cannot be read
+pub fn check_sizes<R: Read + Seek>(
+    reader: R,
+    mode: SizeCheck,
+    mut warn: impl FnMut(&str),
+) -> Result<ExpectedSizes> {
+    let mut directory = CentralDirectory::open(reader)?;
+    let cd_start = directory.layout().start;
+    let mut sizes = ExpectedSizes::default();
+    let mut problems = Vec::new();
+    loop {
+        let batch = std::iter::from_fn(|| directory.next_with_sizes())
+            .take(BATCH)
+            .collect::<Result<Vec<_>>>()?;
+        if batch.is_empty() {
+            break;
+        }
+        directory.with_reader(|reader| {
+            for (record, fields) in &batch {
+                let settled = settle_entry(reader, record, fields, cd_start);
+                if settled.differences.is_empty() {
+                    continue;
+                }
+                let name = String::from_utf8_lossy(&record.name);
+                for difference in &settled.differences {
+                    match mode {
+                        SizeCheck::Strict => problems.push(format!("{}: {}", name, difference)),
+                        SizeCheck::Lenient => warn(&format!(
+                            "{}: {}; using compressed size {} and size {}",
+                            name, difference, settled.compressed_size, settled.size
+                        )),
+                    }
+                }
+                sizes.settled.insert(record.header_offset, settled.size);
+            }
+            Ok(())
+        })?;
+    }
+    if !problems.is_empty() {
+        return Err(SizeCheckError::Disagreement(problems).into());
+    }
+    Ok(sizes)
+}
+
+/// Where a size was read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Source {
+    CentralZip64,
+    Central,
+    Descriptor,
+    LocalZip64,
+    Local,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::CentralZip64 => "central Zip64 field",
+            Self::Central => "central directory",
+            Self::Descriptor => "data descriptor",
+            Self::LocalZip64 => "local Zip64 field",
+            Self::Local => "local header",
+        })
+    }
+}
+
+/// The values each size of an entry was read as
+#[derive(Debug, Default)]
+struct Readings {
+    compressed: Vec<(Source, u64)>,
+    size: Vec<(Source, u64)>,
+}
+
+impl Readings {
+    /// Add the sizes of a header, its 32-bit fields as `narrow` and its Zip64
+    /// field as `wide`, leaving out zeros when `deferred` says the sizes follow
+    /// the data
+    ///
+    /// The Zip64 field holds the sizes whose 32-bit fields are 0xFFFFFFFF, the
+    /// size first, unless it has room for both sizes ahead of any offset: then
+    /// it holds both, as local headers must and some writers do regardless.
+    fn add(&mut self, fields: &HeaderSizes, narrow: Source, wide: Source, deferred: bool) {
+        let both = fields.zip64.len() >= 2 + usize::from(fields.offset_in_zip64);
+        let mut values = fields.zip64.iter().copied();
+        let size = (both || fields.size == u32::MAX).then(|| values.next()).flatten();
+        let compressed = (both || fields.compressed == u32::MAX).then(|| values.next()).flatten();
+        let narrow_compressed = (fields.compressed != u32::MAX).then_some(fields.compressed);
+        let narrow_size = (fields.size != u32::MAX).then_some(fields.size);
+        let kept = |value: &u64| !deferred || *value != 0;
+        let compressed = [(narrow, narrow_compressed.map(u64::from)), (wide, compressed)];
+        let size = [(narrow, narrow_size.map(u64::from)), (wide, size)];
+        for (source, value) in compressed {
+            self.compressed.extend(value.filter(kept).map(|value| (source, value)));
+        }
+        for (source, value) in size {
+            self.size.extend(value.filter(kept).map(|value| (source, value)));
+        }
+    }
+}
+
+/// The sizes settled on for one entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Settled {
+    compressed_size: u64,
+    size: u64,
+    /// One line for each size whose fields disagree
+    differences: Vec<String>,
+}
+
+/// Read every size field of the entry `record` describes, and settle on a
+/// compressed size and a size
+///
+/// Fields that cannot be read, like those of a missing local header, are left
+/// out: extracting or testing the entry reports the damage.
+fn settle_entry<R: Read + Seek>(
+    reader: &mut R,
+    record: &CentralRecord,
+    central: &HeaderSizes,
+    cd_start: u64,
+) -> Settled {
+    let mut readings = Readings::default();
+    readings.add(central, Source::Central, Source::CentralZip64, false);
+    let local = read_local_header(reader, record.header_offset).ok().flatten();
+    if let Some(local) = &local {
+        let deferred = local.record.flags & (1 << 3) != 0;
+        readings.add(&local.sizes, Source::Local, Source::LocalZip64, deferred);
+    }
+    let data_start = local.as_ref().map(|local| local.data_start);
+    let descriptor = record.flags & (1 << 3) != 0;
+
+    let compressed_size = settle(&readings.compressed, |value| {
+        data_start.is_some_and(|start| {
+            ends_at_header(reader, start.saturating_add(value), cd_start, descriptor)
+        })
+    })
+    .unwrap_or(record.compressed_size);
+    if let Some(start) = data_start.filter(|_| descriptor) {
+        let wide = !central.zip64.is_empty()
+            || local.as_ref().is_some_and(|local| !local.sizes.zip64.is_empty());
+        let descriptor = read_descriptor(reader, start.saturating_add(compressed_size));
+        if let Some((compressed, size)) = descriptor.ok().and_then(|fields| {
+            if wide {
+                fields.wide
+            } else {
+                Some(fields.narrow)
+            }
+        }) {
+            readings.compressed.push((Source::Descriptor, compressed));
+            readings.size.push((Source::Descriptor, size));
+        }
+    }
+    // A Stored entry is as long as its data, unless encryption adds a header
+    let stored = record.method == 0 && !record.is_encrypted();
+    let size =
+        settle(&readings.size, |value| !stored || value == compressed_size).unwrap_or(record.size);
+
+    let mut differences = Vec::new();
+    for (field, values) in [("compressed size", &readings.compressed), ("size", &readings.size)] {
+        if values.iter().any(|(_, value)| *value != values[0].1) {
+            let listed: Vec<_> =
+                values.iter().map(|(source, value)| format!("{} {}", source, value)).collect();
+            differences.push(format!("{} disagrees: {}", field, listed.join(", ")));
+        }
+    }
+    Settled { compressed_size, size, differences }
+}
+
+/// The most plausible of `readings`: among those `plausible` accepts, or all if
+/// it accepts none, the value the most readings hold, a 32-bit reading holding
+/// the 64-bit value it is the low half of; ties go to the earlier [`Source`]
+fn settle(readings: &[(Source, u64)], mut plausible: impl FnMut(u64) -> bool) -> Option<u64> {
+    let votes = |value: u64| {
+        readings
+            .iter()
+            .filter(|(_, other)| {
+                *other == value || value > u64::from(u32::MAX) && *other == value & 0xFFFF_FFFF
+            })
+            .count()
+    };
+    if let [(_, first), rest @ ..] = readings
+        && rest.iter().all(|(_, value)| value == first)
+    {
+        return Some(*first);
+    }
+    let accepted: Vec<_> = readings.iter().filter(|(_, value)| plausible(*value)).collect();
+    let candidates = if accepted.is_empty() {
+        readings.iter().collect()
+    } else {
+        accepted
+    };
+    candidates
+        .into_iter()
+        .min_by_key(|(source, value)| (Reverse(votes(*value)), *source))
+        .map(|(_, value)| *value)
+}
+
+/// Whether data ending at `end` is followed by a data descriptor if
+/// `descriptor`, and then by a local header or the central directory
+fn ends_at_header<R: Read + Seek>(
+    reader: &mut R,
+    end: u64,
+    cd_start: u64,
+    descriptor: bool,
+) -> bool {
+    let mut header_at = |at: u64| {
+        at == cd_start
+            || signature_at(reader, at).is_ok_and(|signature| {
+                signature == LOCAL_SIGNATURE || signature == CENTRAL_SIGNATURE
+            })
+    };
+    if !descriptor {
+        return header_at(end);
+    }
+    DESCRIPTOR_LENS.iter().any(|len| header_at(end.saturating_add(*len)))
+}
+
+/// The four bytes at `offset`, as a little-endian signature
+fn signature_at<R: Read + Seek>(reader: &mut R, offset: u64) -> io::Result<u32> {
+    let mut signature = [0u8; 4];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut signature)?;
+    Ok(u32::from_le_bytes(signature))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::ZipWriter;
+    use zip::write::SimpleFileOptions;
+
+    fn archive() -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("a.txt", SimpleFileOptions::default().large_file(true)).unwrap();
+        zip.write_all(&b"size check\n".repeat(40)).unwrap();
+        let stored = SimpleFileOptions::default().compression_method(zip::CompressionMethod::STORE);
+        zip.start_file("b.bin", stored).unwrap();
+        zip.write_all(b"stored").unwrap();
+        zip.finish().unwrap().into_inner()
+    }
+
+    /// Offset of the central record of `name` in `data`
+    fn central(data: &[u8], name: &[u8]) -> usize {
+        let record = data.windows(name.len()).rposition(|window| window == name).unwrap();
+        record - 46
+    }
+
+    #[test]
+    fn test_check_sizes_passes_consistent_archive() {
+        let data = archive();
+        let sizes = check_sizes(Cursor::new(&data), SizeCheck::Strict, |_| {}).unwrap();
+        assert_eq!(sizes, ExpectedSizes::default());
+    }
+
+    #[test]
+    fn test_check_sizes_strict_and_lenient() {
+        let mut data = archive();
+        let at = central(&data, b"b.bin");
+        data[at + 20] = 9; // compressed size, 6 in the local header
+
+        let err = check_sizes(Cursor::new(&data), SizeCheck::Strict, |_| {}).unwrap_err();
+        let crate::error::UnzipError::SizeCheck(SizeCheckError::Disagreement(problems)) = err
+        else {
+            panic!("{}", err)
+        };
+        assert_eq!(
+            problems,
+            ["b.bin: compressed size disagrees: central directory 9, local header 6"]
+        );
+
+        // The data ends where the central directory starts after 6 bytes
+        let mut warnings = Vec::new();
+        let sizes = check_sizes(Cursor::new(&data), SizeCheck::Lenient, |warning| {
+            warnings.push(warning.to_string())
+        })
+        .unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].ends_with("using compressed size 6 and size 6"), "{}", warnings[0]);
+        let header = u64::from(u32::from_le_bytes(data[at + 42..at + 46].try_into().unwrap()));
+        assert_eq!(sizes.expected(header, 6), 6);
+    }
+
+    #[test]
+    fn test_readings_of_zip64_fields() {
+        let mut readings = Readings::default();
+        // 32-bit sizes, and a Zip64 field with both sizes regardless
+        let fields =
+            HeaderSizes { compressed: 10, size: 20, offset_in_zip64: false, zip64: vec![30, 10] };
+        readings.add(&fields, Source::Central, Source::CentralZip64, false);
+        assert_eq!(readings.size, [(Source::Central, 20), (Source::CentralZip64, 30)]);
+        assert_eq!(readings.compressed, [(Source::Central, 10), (Source::CentralZip64, 10)]);
+
+        // Only the size and the offset are too large
+        let mut readings = Readings::default();
+        let fields = HeaderSizes {
+            compressed: 10,
+            size: u32::MAX,
+            offset_in_zip64: true,
+            zip64: vec![99, 7],
+        };
+        readings.add(&fields, Source::Central, Source::CentralZip64, false);
+        assert_eq!(readings.size, [(Source::CentralZip64, 99)]);
+        assert_eq!(readings.compressed, [(Source::Central, 10)]);
+
+        // Sizes in a data descriptor: the local zeros do not count
+        let mut readings = Readings::default();
+        let fields =
+            HeaderSizes { compressed: 0, size: 0, offset_in_zip64: false, zip64: vec![0, 0] };
+        readings.add(&fields, Source::Local, Source::LocalZip64, true);
+        assert!(readings.size.is_empty() && readings.compressed.is_empty());
+    }
+
+    #[test]
+    fn test_settle() {
+        let wide = (1 << 32) + 5;
+        let readings = [(Source::CentralZip64, wide), (Source::Central, 5), (Source::Local, 5)];
+        // The 32-bit fields are the low half of the Zip64 value
+        assert_eq!(settle(&readings, |_| true), Some(wide));
+
+        let readings = [(Source::Central, 7), (Source::Local, 9)];
+        assert_eq!(settle(&readings, |value| value == 9), Some(9));
+        assert_eq!(settle(&readings, |_| false), Some(7));
+        assert_eq!(settle(&[], |_| true), None);
+    }
+}
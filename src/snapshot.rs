@@ -0,0 +1,110 @@
+//! Destination snapshot for freshen/update runs
+//!
+//! With `-f` or `-u` most entries of a repeated run are usually skipped, yet each one
+//! used to cost several filesystem calls (existence check, metadata, parent directory
+//! creation). [`DestinationSnapshot`] reads every destination directory once, with
+//! the size and modification time of each file in it, and answers from memory, so
+//! skipped entries can be decided in bulk before extraction starts without touching
+//! the files themselves.
+
+use filetime::FileTime;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs::{self, DirEntry};
+use std::path::{Path, PathBuf};
+
+/// A file found in a destination directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiskFile {
+    /// Size in bytes
+    pub size: u64,
+    /// Last modification time, if the platform reports one
+    pub modified: Option<FileTime>,
+}
+
+/// Cached directory listings of the extraction destination
+#[derive(Debug, Default)]
+pub struct DestinationSnapshot {
+    listings: HashMap<PathBuf, Option<HashMap<OsString, DiskFile>>>,
+}
+
+impl DestinationSnapshot {
+    /// Create an empty snapshot; directories are read on first use
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The file at `path`, if there is one, reading its parent directory at most
+    /// once
+    ///
+    /// A symlink is described by what it points to, as opening it for writing
+    /// would find it.
+    pub fn file(&mut self, path: &Path) -> Option<DiskFile> {
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return fs::metadata(path).ok().map(|meta| DiskFile::of(&meta));
+        };
+        self.listings
+            .entry(parent.to_path_buf())
+            .or_insert_with(|| read_listing(parent))
+            .as_ref()?
+            .get(name)
+            .copied()
+    }
+
+    /// Whether `path` exists, reading its parent directory at most once
+    pub fn exists(&mut self, path: &Path) -> bool {
+        self.file(path).is_some()
+    }
+
+    /// Number of directories read so far
+    pub fn directories_read(&self) -> usize {
+        self.listings.len()
+    }
+}
+
+impl DiskFile {
+    fn of(meta: &fs::Metadata) -> Self {
+        Self { size: meta.len(), modified: meta.modified().ok().map(FileTime::from_system_time) }
+    }
+}
+
+/// Files in `dir` by name, or `None` if it cannot be read (usually because it is
+/// missing)
+fn read_listing(dir: &Path) -> Option<HashMap<OsString, DiskFile>> {
+    let entries = fs::read_dir(dir).ok()?;
+    Some(
+        entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| (entry.file_name(), disk_file(&entry)))
+            .collect(),
+    )
+}
+
+/// Describe `entry`, following it if it is a symlink; one that cannot be read,
+/// like a dangling symlink, is listed with no size or time
+fn disk_file(entry: &DirEntry) -> DiskFile {
+    let meta = match entry.metadata() {
+        Ok(meta) if meta.file_type().is_symlink() => fs::metadata(entry.path()),
+        meta => meta,
+    };
+    meta.map_or(DiskFile { size: 0, modified: None }, |meta| DiskFile::of(&meta))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_reads_each_directory_once() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        fs::write(temp_dir.path().join("a.txt"), b"a").unwrap();
+        fs::write(temp_dir.path().join("b.txt"), b"b").unwrap();
+
+        let mut snapshot = DestinationSnapshot::new();
+        assert!(snapshot.exists(&temp_dir.path().join("a.txt")));
+        assert_eq!(snapshot.file(&temp_dir.path().join("b.txt")).map(|file| file.size), Some(1));
+        assert!(!snapshot.exists(&temp_dir.path().join("c.txt")));
+        assert!(!snapshot.exists(&temp_dir.path().join("missing/d.txt")));
+        assert_eq!(snapshot.directories_read(), 2);
+    }
+}
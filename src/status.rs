@@ -0,0 +1,249 @@
+//! Status snapshots of a running extraction (SIGUSR1)
+//!
+//! Multi-hour extractions often run without a terminal, so there is no progress
+//! bar to look at. Sending the process SIGUSR1 (`kill -USR1 PID`) prints a
+//! snapshot to stderr and lets the extraction carry on:
+//!
+//! ```text
+//! unzip: status after 1h 02m 03s
+//!   output:      /srv/data
+//!   entries:     1523 of 40000 done
+//!   written:     12.3G (3.4M/s)
+//!   writing:     data/big.bin (1.2G of 4.0G)
+//!   writing:     data/index.db (0B of 96.0M)
+//! ```
+//!
+//! Each `writing:` line is the entry one worker thread is writing; a worker
+//! without one is between entries or done. Entries count as done once written,
+//! skipped or created as directories, and the bytes are those written out,
+//! after decompression.
+//!
+//! Library users can keep a [`StatusBoard`] of their own, set it with
+//! [`ExtractOptions::status_board`](crate::ExtractOptions::status_board) and
+//! render [`StatusBoard::snapshot`] whenever they like. The totals across
+//! archives are also what `--metrics-file` exports (see [`crate::metrics`]).
+//!
+//! # Examples
+//!
+//! ```
+//! use std::path::PathBuf;
+//! use unzip::status::StatusBoard;
+//! use unzip::{ExtractEvent, ExtractObserver};
+//!
+//! let mut board = StatusBoard::new();
+//! board.on_event(&ExtractEvent::Started { total_entries: 2, output_dir: PathBuf::from("out") });
+//! board.on_event(&ExtractEvent::EntryStarted { name: "big.bin".to_string(), size: 4096 });
+//! board.on_event(&ExtractEvent::BytesWritten { name: "big.bin".to_string(), bytes: 1024 });
+//! let snapshot = board.snapshot();
+//! assert_eq!((snapshot.done, snapshot.total_entries, snapshot.bytes), (0, 2, 1024));
+//! assert!(snapshot.to_string().contains("writing:     big.bin (1.0K of 4.0K)"));
+//! ```
+
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::events::{ExtractEvent, ExtractObserver};
+use crate::utils::format_size;
+
+/// Live counters of an extraction, updated from its events and shared by clones
+#[derive(Debug, Clone)]
+pub struct StatusBoard {
+    since: Instant,
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    output_dir: Option<PathBuf>,
+    total_entries: usize,
+    done: usize,
+    bytes: u64,
+    files: u64,
+    skipped: u64,
+    warnings: u64,
+    writing: Vec<Writing>,
+}
+
+/// An entry being written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Writing {
+    /// Entry name
+    pub name: String,
+    /// Uncompressed size
+    pub size: u64,
+    /// Bytes written so far
+    pub written: u64,
+}
+
+/// Where an extraction stood when [`StatusBoard::snapshot`] was taken
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusSnapshot {
+    /// Time since the board was created
+    pub elapsed: Duration,
+    /// Output directory of the archive being extracted, once known
+    pub output_dir: Option<PathBuf>,
+    /// Entries of the archive being extracted
+    pub total_entries: usize,
+    /// Entries written, skipped or created so far
+    pub done: usize,
+    /// Bytes written since the board was created, across archives
+    pub bytes: u64,
+    /// Files written since the board was created, across archives
+    pub files: u64,
+    /// Entries skipped since the board was created, across archives
+    pub skipped: u64,
+    /// Warnings since the board was created, across archives
+    pub warnings: u64,
+    /// Entries being written, one per busy worker
+    pub writing: Vec<Writing>,
+}
+
+impl Default for StatusBoard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StatusBoard {
+    /// Create a board with nothing done, timing from now
+    pub fn new() -> Self {
+        Self { since: Instant::now(), state: Arc::default() }
+    }
+
+    /// The counters as they are now
+    pub fn snapshot(&self) -> StatusSnapshot {
+        let state = self.lock();
+        StatusSnapshot {
+            elapsed: self.since.elapsed(),
+            output_dir: state.output_dir.clone(),
+            total_entries: state.total_entries,
+            done: state.done,
+            bytes: state.bytes,
+            files: state.files,
+            skipped: state.skipped,
+            warnings: state.warnings,
+            writing: state.writing.clone(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Count the entry named `name` as done, and as no longer being written
+    fn entry_done(&self, name: &str) {
+        let mut state = self.lock();
+        state.done += 1;
+        if let Some(i) = state.writing.iter().position(|writing| writing.name == name) {
+            state.writing.remove(i);
+        }
+    }
+}
+
+impl ExtractObserver for StatusBoard {
+    fn on_event(&mut self, event: &ExtractEvent) {
+        match event {
+            ExtractEvent::Started { total_entries, output_dir } => {
+                let mut state = self.lock();
+                state.output_dir = Some(output_dir.clone());
+                state.total_entries = *total_entries;
+                state.done = 0;
+                state.writing.clear();
+            },
+            ExtractEvent::DirCreated { .. } => self.lock().done += 1,
+            ExtractEvent::EntryStarted { name, size } => {
+                let writing = Writing { name: name.clone(), size: *size, written: 0 };
+                self.lock().writing.push(writing);
+            },
+            ExtractEvent::BytesWritten { name, bytes } => {
+                let mut state = self.lock();
+                state.bytes += bytes;
+                if let Some(writing) = state.writing.iter_mut().find(|w| w.name == *name) {
+                    writing.written += bytes;
+                }
+            },
+            ExtractEvent::FileDone { name, .. } => {
+                self.lock().files += 1;
+                self.entry_done(name);
+            },
+            ExtractEvent::Skipped { name, .. } => {
+                self.lock().skipped += 1;
+                self.entry_done(name);
+            },
+            ExtractEvent::Planned { name, .. } => self.entry_done(name),
+            ExtractEvent::Warning { .. } => self.lock().warnings += 1,
+            ExtractEvent::Finished { .. } => self.lock().writing.clear(),
+        }
+    }
+}
+
+impl fmt::Display for StatusSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.elapsed.as_secs();
+        let elapsed = match (secs / 3600, secs / 60 % 60, secs % 60) {
+            (0, 0, s) => format!("{}s", s),
+            (0, m, s) => format!("{}m {:02}s", m, s),
+            (h, m, s) => format!("{}h {:02}m {:02}s", h, m, s),
+        };
+        writeln!(f, "unzip: status after {}", elapsed)?;
+        if let Some(output_dir) = &self.output_dir {
+            writeln!(f, "  output:      {}", output_dir.display())?;
+        }
+        writeln!(f, "  entries:     {} of {} done", self.done, self.total_entries)?;
+        let rate = self.bytes as f64 / self.elapsed.as_secs_f64().max(0.001);
+        writeln!(f, "  written:     {} ({}/s)", format_size(self.bytes), format_size(rate as u64))?;
+        for writing in &self.writing {
+            writeln!(
+                f,
+                "  writing:     {} ({} of {})",
+                writing.name,
+                format_size(writing.written),
+                format_size(writing.size)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::SkipReason;
+
+    #[test]
+    fn test_status_board_follows_events() {
+        let mut board = StatusBoard::new();
+        let events = [
+            ExtractEvent::Started { total_entries: 4, output_dir: PathBuf::from("out") },
+            ExtractEvent::DirCreated { path: PathBuf::from("out/dir") },
+            ExtractEvent::EntryStarted { name: "dir/a".to_string(), size: 10 },
+            ExtractEvent::EntryStarted { name: "dir/b".to_string(), size: 2048 },
+            ExtractEvent::BytesWritten { name: "dir/b".to_string(), bytes: 1024 },
+            ExtractEvent::BytesWritten { name: "dir/a".to_string(), bytes: 10 },
+            ExtractEvent::FileDone {
+                name: "dir/a".to_string(),
+                path: PathBuf::from("out/dir/a"),
+                bytes: 10,
+            },
+            ExtractEvent::Skipped { name: "c".to_string(), reason: SkipReason::Exists },
+        ];
+        for event in &events {
+            board.on_event(event);
+        }
+
+        let snapshot = board.clone().snapshot();
+        assert_eq!(snapshot.output_dir, Some(PathBuf::from("out")));
+        assert_eq!((snapshot.done, snapshot.total_entries, snapshot.bytes), (3, 4, 1034));
+        assert_eq!((snapshot.files, snapshot.skipped, snapshot.warnings), (1, 1, 0));
+        assert_eq!(
+            snapshot.writing,
+            [Writing { name: "dir/b".to_string(), size: 2048, written: 1024 }]
+        );
+        let text = snapshot.to_string();
+        assert!(text.starts_with("unzip: status after 0s\n  output:      out\n"));
+        assert!(text.contains("  entries:     3 of 4 done\n"));
+        assert!(text.ends_with("  writing:     dir/b (1.0K of 2.0K)\n"));
+    }
+}
@@ -0,0 +1,371 @@
+//! Zip to tar conversion on the fly (`--to-tar`)
+//!
+//! `--to-tar FILE` extracts into an uncompressed POSIX tar stream instead of
+//! the filesystem, `-` writing it to stdout, so an archive is repacked without
+//! an intermediate directory:
+//!
+//! ```text
+//! $ unzip --to-tar - site.zip | zstd > site.tar.zst
+//! ```
+//!
+//! Entries are selected and named as extraction would select and name them,
+//! with the same patterns, `-j`, `--strip-components` and `--transform`, and
+//! keep their modes and modification times; symlinks become tar symlinks.
+//! Names longer than the ustar fields, link targets longer than 100 bytes and
+//! sizes or times the octal fields cannot hold go in PAX extended headers.
+//! Owners are root, and entries without a Unix mode get `0644`, or `0755` for
+//! directories. Nothing is printed, as with `-p`.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::io::{Cursor, Write};
+//! use zip::{ZipArchive, ZipWriter, write::SimpleFileOptions};
+//! use unzip::ExtractOptions;
+//! use unzip::tar::extract_to_tar;
+//!
+//! let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+//! zip.start_file("docs/readme.txt", SimpleFileOptions::default())?;
+//! zip.write_all(b"hello")?;
+//! let mut archive = ZipArchive::new(zip.finish()?)?;
+//!
+//! let tar = extract_to_tar(&mut archive, &ExtractOptions::new(), Vec::new())?;
+//! assert_eq!(&tar[..15], b"docs/readme.txt");
+//! assert_eq!(&tar[512..517], b"hello");
+//! // Header, one data block and the two zero blocks that end the archive
+//! assert_eq!(tar.len(), 4 * 512);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::io::{self, Read, Seek, Write};
+use std::path::Path;
+use zip::ZipArchive;
+
+use crate::error::Result;
+use crate::extract::extract_to_sink;
+use crate::options::ExtractOptions;
+use crate::sink::{EntrySink, SinkEntry};
+
+/// Size of a tar header and of the units data is padded to
+const BLOCK: usize = 512;
+const S_IFMT: u32 = 0o170000;
+const S_IFLNK: u32 = 0o120000;
+/// Largest value of the 11 octal digits of the size and mtime fields
+const MAX_OCTAL_11: u64 = 0o77777777777;
+
+const REGULAR: u8 = b'0';
+const SYMLINK: u8 = b'2';
+const DIRECTORY: u8 = b'5';
+const PAX_HEADER: u8 = b'x';
+
+/// Convert the entries `options` select to a tar stream written to `out`,
+/// returning `out` once the archive is ended
+///
+/// # Errors
+///
+/// Returns an error if an entry cannot be read or decrypted, an extraction
+/// limit is crossed, an entry's data does not match its recorded size, or
+/// `out` cannot be written
+pub fn extract_to_tar<R: Read + Seek, W: Write>(
+    archive: &mut ZipArchive<R>,
+    options: &ExtractOptions,
+    out: W,
+) -> Result<W> {
+    let mut sink = TarSink::new(out);
+    extract_to_sink(archive, options, &mut sink)?;
+    Ok(sink.finish()?)
+}
+
+/// An [`EntrySink`] writing entries as a tar stream
+///
+/// File headers record the size the archive gives, and data that does not
+/// match it fails the entry, since the stream cannot go back to fix a header.
+#[derive(Debug)]
+pub struct TarSink<W: Write> {
+    out: W,
+    /// Data bytes the file being written still has to receive
+    remaining: u64,
+    /// Data bytes written for the file being written
+    written: u64,
+    /// Target of the symlink being written, which its header holds
+    link: Option<Vec<u8>>,
+}
+
+impl<W: Write> TarSink<W> {
+    /// Write a tar stream to `out`
+    pub fn new(out: W) -> Self {
+        Self { out, remaining: 0, written: 0, link: None }
+    }
+
+    /// End the archive with two zero blocks and flush it, returning `out`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `out` cannot be written
+    pub fn finish(mut self) -> io::Result<W> {
+        self.out.write_all(&[0; 2 * BLOCK])?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    /// Write the header of `entry`, preceded by a PAX header for the values
+    /// the ustar fields cannot hold
+    fn write_header(
+        &mut self,
+        entry: &SinkEntry,
+        kind: u8,
+        size: u64,
+        link: &[u8],
+    ) -> io::Result<()> {
+        let mut name = path_bytes(&entry.path);
+        if kind == DIRECTORY {
+            name.push(b'/');
+        }
+        let mtime = entry.times.modified.map_or(0, |time| time.unix_seconds());
+        let default_mode = if kind == DIRECTORY { 0o755 } else { 0o644 };
+        let mode = entry.mode.map_or(default_mode, |mode| mode & 0o7777);
+
+        let mut pax = Vec::new();
+        let split = split_name(&name);
+        if split.is_none() {
+            pax_record(&mut pax, "path", &name);
+        }
+        if link.len() > 100 {
+            pax_record(&mut pax, "linkpath", link);
+        }
+        if size > MAX_OCTAL_11 {
+            pax_record(&mut pax, "size", size.to_string().as_bytes());
+        }
+        if !(0..=MAX_OCTAL_11 as i64).contains(&mtime) {
+            pax_record(&mut pax, "mtime", mtime.to_string().as_bytes());
+        }
+        if !pax.is_empty() {
+            let fields = Fields {
+                prefix: b"",
+                name: b"././@PaxHeader",
+                mode: 0o644,
+                size: pax.len() as u64,
+                mtime: 0,
+                kind: PAX_HEADER,
+                link: b"",
+            };
+            self.out.write_all(&fields.header())?;
+            self.out.write_all(&pax)?;
+            self.out.write_all(&[0; BLOCK][..padding(pax.len() as u64)])?;
+        }
+
+        let (prefix, short) = split.unwrap_or((b"", &name[name.len().saturating_sub(100)..]));
+        let fields = Fields {
+            prefix,
+            name: short,
+            mode,
+            size,
+            mtime: mtime.clamp(0, MAX_OCTAL_11 as i64) as u64,
+            kind,
+            link: &link[..link.len().min(100)],
+        };
+        self.out.write_all(&fields.header())
+    }
+}
+
+impl<W: Write> EntrySink for TarSink<W> {
+    fn create_dir(&mut self, entry: &SinkEntry) -> io::Result<()> {
+        self.write_header(entry, DIRECTORY, 0, b"")
+    }
+
+    fn create_file(&mut self, entry: &SinkEntry) -> io::Result<Box<dyn Write + '_>> {
+        if entry.mode.is_some_and(|mode| mode & S_IFMT == S_IFLNK) {
+            return Ok(Box::new(self.link.insert(Vec::new())));
+        }
+        self.write_header(entry, REGULAR, entry.size, b"")?;
+        self.remaining = entry.size;
+        self.written = 0;
+        Ok(Box::new(TarData { sink: self }))
+    }
+
+    fn set_metadata(&mut self, entry: &SinkEntry) -> io::Result<()> {
+        if entry.is_dir {
+            return Ok(());
+        }
+        if let Some(link) = self.link.take() {
+            return self.write_header(entry, SYMLINK, 0, &link);
+        }
+        if self.remaining > 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: data is shorter than its recorded size", entry.name),
+            ));
+        }
+        self.out.write_all(&[0; BLOCK][..padding(self.written)])
+    }
+}
+
+/// Writer of one file's data, which must fill the size its header records
+struct TarData<'a, W: Write> {
+    sink: &'a mut TarSink<W>,
+}
+
+impl<W: Write> Write for TarData<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.len() as u64 > self.sink.remaining {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "data is longer than its recorded size",
+            ));
+        }
+        let written = self.sink.out.write(buf)?;
+        self.sink.remaining -= written as u64;
+        self.sink.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.sink.out.flush()
+    }
+}
+
+/// Values of a ustar header, already cut to fit
+struct Fields<'a> {
+    prefix: &'a [u8],
+    name: &'a [u8],
+    mode: u32,
+    size: u64,
+    mtime: u64,
+    kind: u8,
+    link: &'a [u8],
+}
+
+impl Fields<'_> {
+    fn header(&self) -> [u8; BLOCK] {
+        let mut header = [0; BLOCK];
+        header[..self.name.len()].copy_from_slice(self.name);
+        octal(&mut header[100..108], u64::from(self.mode));
+        octal(&mut header[108..116], 0);
+        octal(&mut header[116..124], 0);
+        octal(&mut header[124..136], self.size);
+        octal(&mut header[136..148], self.mtime);
+        header[156] = self.kind;
+        header[157..157 + self.link.len()].copy_from_slice(self.link);
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        header[345..345 + self.prefix.len()].copy_from_slice(self.prefix);
+
+        header[148..156].fill(b' ');
+        let sum: u32 = header.iter().map(|&byte| u32::from(byte)).sum();
+        header[148..156].copy_from_slice(format!("{:06o}\0 ", sum).as_bytes());
+        header
+    }
+}
+
+/// Write `value` as zero-padded octal digits and a NUL, capped at the largest
+/// value the field holds
+fn octal(field: &mut [u8], value: u64) {
+    let digits = field.len() - 1;
+    let max = (1u64 << (3 * digits)) - 1;
+    let text = format!("{:0width$o}", value.min(max), width = digits);
+    field[..digits].copy_from_slice(text.as_bytes());
+    field[digits] = 0;
+}
+
+/// Zero bytes that pad `len` bytes of data to a whole block
+fn padding(len: u64) -> usize {
+    (BLOCK - (len % BLOCK as u64) as usize) % BLOCK
+}
+
+/// The ustar prefix and name fields for `name`, if it fits them
+fn split_name(name: &[u8]) -> Option<(&[u8], &[u8])> {
+    if name.len() <= 100 {
+        return Some((b"", name));
+    }
+    // The prefix ends at a `/`, which neither field keeps
+    let body = name.strip_suffix(b"/").unwrap_or(name);
+    (1..body.len())
+        .filter(|&i| body[i] == b'/' && i <= 155 && name.len() - i - 1 <= 100)
+        .map(|i| (&name[..i], &name[i + 1..]))
+        .next()
+}
+
+/// Append the PAX record `key=value` to `records`
+///
+/// The record starts with its own length in bytes, counting the digits of that
+/// length.
+fn pax_record(records: &mut Vec<u8>, key: &str, value: &[u8]) {
+    let base = key.len() + value.len() + 3;
+    let mut len = base + base.to_string().len();
+    while len != base + len.to_string().len() {
+        len = base + len.to_string().len();
+    }
+    records.extend_from_slice(format!("{} {}=", len, key).as_bytes());
+    records.extend_from_slice(value);
+    records.push(b'\n');
+}
+
+/// The bytes of the relative path `path`, with `/` separators
+fn path_bytes(path: &Path) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for component in path.components() {
+        if !bytes.is_empty() {
+            bytes.push(b'/');
+        }
+        #[cfg(unix)]
+        bytes.extend_from_slice(std::os::unix::ffi::OsStrExt::as_bytes(component.as_os_str()));
+        #[cfg(not(unix))]
+        bytes.extend_from_slice(component.as_os_str().to_string_lossy().as_bytes());
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use zip::ZipWriter;
+    use zip::write::SimpleFileOptions;
+
+    fn field(header: &[u8], range: std::ops::Range<usize>) -> &[u8] {
+        let field = &header[range];
+        &field[..field.iter().position(|&byte| byte == 0).unwrap_or(field.len())]
+    }
+
+    #[test]
+    fn test_tar_keeps_directories_modes_and_symlinks() {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.add_directory("bin/", SimpleFileOptions::default().unix_permissions(0o750))
+            .unwrap();
+        let executable = SimpleFileOptions::default().unix_permissions(0o755);
+        zip.start_file("bin/run.sh", executable).unwrap();
+        zip.write_all(b"#!/bin/sh\n").unwrap();
+        zip.add_symlink("bin/latest", "run.sh", SimpleFileOptions::default()).unwrap();
+        let mut archive = zip.finish_into_readable().unwrap();
+
+        let tar = extract_to_tar(&mut archive, &ExtractOptions::new(), Vec::new()).unwrap();
+        let headers: Vec<&[u8]> =
+            vec![&tar[..BLOCK], &tar[BLOCK..][..BLOCK], &tar[3 * BLOCK..][..BLOCK]];
+        assert_eq!(field(headers[0], 0..100), b"bin/");
+        assert_eq!((headers[0][156], field(headers[0], 100..108)), (DIRECTORY, &b"0000750"[..]));
+        assert_eq!(field(headers[1], 0..100), b"bin/run.sh");
+        assert_eq!((headers[1][156], field(headers[1], 124..136)), (REGULAR, &b"00000000012"[..]));
+        assert_eq!(&tar[2 * BLOCK..][..10], b"#!/bin/sh\n");
+        assert_eq!(field(headers[2], 0..100), b"bin/latest");
+        assert_eq!((headers[2][156], field(headers[2], 157..257)), (SYMLINK, &b"run.sh"[..]));
+        assert_eq!(tar.len(), 6 * BLOCK);
+    }
+
+    #[test]
+    fn test_long_names_use_prefix_or_pax_header() {
+        let deep = format!("{}/{}", "d".repeat(120), "f".repeat(90));
+        assert_eq!(
+            split_name(deep.as_bytes()),
+            Some((&deep.as_bytes()[..120], &deep.as_bytes()[121..]))
+        );
+        assert_eq!(split_name("n".repeat(101).as_bytes()), None);
+
+        let mut records = Vec::new();
+        pax_record(&mut records, "path", b"abcdef");
+        assert_eq!(records, b"15 path=abcdef\n");
+        records.clear();
+        pax_record(&mut records, "path", "x".repeat(92).as_bytes());
+        assert_eq!(records.len(), 102);
+        assert!(records.starts_with(b"102 path="));
+    }
+}
@@ -0,0 +1,226 @@
+//! Output file names built from entry metadata (`--name-template`)
+//!
+//! `--name-template TEMPLATE` names every extracted file after TEMPLATE, with
+//! placeholders filled in from the entry, so flattening with `-j` cannot make
+//! two entries collide and files carry their provenance without a rename pass:
+//!
+//! ```text
+//! unzip -j --name-template '{stem}-{crc}{ext}' bundle.zip
+//! docs/readme.txt  ->  readme-3610a686.txt
+//! ```
+//!
+//! | Placeholder | Value |
+//! |-------------|-------|
+//! | `{path}` | The whole output path: `docs/readme.txt` |
+//! | `{dir}` | Its directory, empty at the top level: `docs` |
+//! | `{name}` | Its file name: `readme.txt` |
+//! | `{stem}` | The file name without its extension: `readme` |
+//! | `{ext}` | The extension with its dot, or nothing: `.txt` |
+//! | `{crc}` | The entry's CRC-32, as 8 lowercase hex digits |
+//! | `{index}` | The entry's position in the central directory, from 0 (as with `--member-index`) |
+//! | `{date}` | The modification date, `YYYY-MM-DD` |
+//! | `{mtime}` | The modification date and time, `YYYYMMDD-HHMMSS` |
+//!
+//! `{{` and `}}` stand for literal braces. The path placeholders see the path
+//! after `--strip-components`, `-j`, `-L` and `--transform`. The result may add
+//! directories (`{ext}/{name}`); it is sanitized like an entry name, so a template
+//! cannot leave the output directory, and an empty `{dir}` leaves no stray `/`.
+//! Directory entries keep their paths.
+//!
+//! # Examples
+//!
+//! ```
+//! use unzip::template::{EntryFields, parse_name_template};
+//!
+//! let template = parse_name_template("{dir}/{stem}-{crc}{ext}")?;
+//! let fields = EntryFields { index: 4, crc32: 0x3610a686, modified: None };
+//! assert_eq!(template.render("docs/readme.txt", &fields), "docs/readme-3610a686.txt");
+//! assert_eq!(template.render("notes", &fields), "/notes-3610a686");
+//! assert!(parse_name_template("{size}").is_err());
+//! # Ok::<(), String>(())
+//! ```
+
+use std::fmt;
+
+/// One piece of a template
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    /// Text copied as is, with `{{` and `}}` unescaped
+    Literal(String),
+    Path,
+    Dir,
+    Name,
+    Stem,
+    Ext,
+    Crc,
+    Index,
+    Date,
+    Mtime,
+}
+
+/// A parsed `--name-template`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplate {
+    text: String,
+    parts: Vec<Part>,
+}
+
+/// What the metadata placeholders of a [`NameTemplate`] take from an entry
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EntryFields {
+    /// Position in the central directory (`{index}`)
+    pub index: usize,
+    /// CRC-32 of the data (`{crc}`)
+    pub crc32: u32,
+    /// DOS modification time (`{date}`, `{mtime}`); 1980-01-01 00:00 without one
+    pub modified: Option<zip::DateTime>,
+}
+
+impl EntryFields {
+    /// The fields of the entry at `index` of the central directory
+    pub fn of(index: usize, file: &zip::read::ZipFile<'_>) -> Self {
+        Self { index, crc32: file.crc32(), modified: file.last_modified() }
+    }
+}
+
+impl NameTemplate {
+    /// The file name for the entry whose output path is `path` (with `/`
+    /// separators), before sanitizing
+    pub fn render(&self, path: &str, fields: &EntryFields) -> String {
+        let (dir, name) = path.rsplit_once('/').unwrap_or(("", path));
+        // A leading dot starts a hidden file's name, not its extension
+        let (stem, ext) = match name.rfind('.') {
+            Some(dot) if dot > 0 => name.split_at(dot),
+            _ => (name, ""),
+        };
+        let modified = fields.modified.unwrap_or_default();
+        let mut out = String::with_capacity(self.text.len() + path.len());
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => out.push_str(text),
+                Part::Path => out.push_str(path),
+                Part::Dir => out.push_str(dir),
+                Part::Name => out.push_str(name),
+                Part::Stem => out.push_str(stem),
+                Part::Ext => out.push_str(ext),
+                Part::Crc => out.push_str(&format!("{:08x}", fields.crc32)),
+                Part::Index => out.push_str(&fields.index.to_string()),
+                Part::Date => out.push_str(&format!(
+                    "{:04}-{:02}-{:02}",
+                    modified.year(),
+                    modified.month(),
+                    modified.day()
+                )),
+                Part::Mtime => out.push_str(&format!(
+                    "{:04}{:02}{:02}-{:02}{:02}{:02}",
+                    modified.year(),
+                    modified.month(),
+                    modified.day(),
+                    modified.hour(),
+                    modified.minute(),
+                    modified.second()
+                )),
+            }
+        }
+        out
+    }
+}
+
+impl fmt::Display for NameTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// Parse a `--name-template`, such as `{dir}/{stem}-{crc}{ext}`
+///
+/// # Errors
+///
+/// Returns a message suitable for clap for an unknown placeholder or an
+/// unmatched brace
+pub fn parse_name_template(value: &str) -> std::result::Result<NameTemplate, String> {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let bytes = value.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'{' if bytes.get(i + 1) == Some(&b'{') => {
+                literal.push('{');
+                i += 2;
+            },
+            b'}' if bytes.get(i + 1) == Some(&b'}') => {
+                literal.push('}');
+                i += 2;
+            },
+            b'{' => {
+                let end = value[i..]
+                    .find('}')
+                    .ok_or_else(|| format!("unclosed {{ in name template: {}", value))?;
+                let placeholder = &value[i + 1..i + end];
+                let part = match placeholder {
+                    "path" => Part::Path,
+                    "dir" => Part::Dir,
+                    "name" => Part::Name,
+                    "stem" => Part::Stem,
+                    "ext" => Part::Ext,
+                    "crc" => Part::Crc,
+                    "index" => Part::Index,
+                    "date" => Part::Date,
+                    "mtime" => Part::Mtime,
+                    _ => {
+                        return Err(format!(
+                            "unknown placeholder {{{}}} in name template (expected path, dir, \
+                             name, stem, ext, crc, index, date or mtime)",
+                            placeholder
+                        ));
+                    },
+                };
+                if !literal.is_empty() {
+                    parts.push(Part::Literal(std::mem::take(&mut literal)));
+                }
+                parts.push(part);
+                i += end + 1;
+            },
+            b'}' => return Err(format!("unmatched }} in name template: {}", value)),
+            _ => {
+                let c = value[i..].chars().next().unwrap_or_default();
+                literal.push(c);
+                i += c.len_utf8();
+            },
+        }
+    }
+    if !literal.is_empty() {
+        parts.push(Part::Literal(literal));
+    }
+    if parts.iter().all(|part| matches!(part, Part::Literal(_))) {
+        return Err(format!("name template has no placeholder: {}", value));
+    }
+    Ok(NameTemplate { text: value.to_string(), parts })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_name_template() {
+        let modified = zip::DateTime::from_date_and_time(2024, 3, 5, 7, 8, 10).ok();
+        let fields = EntryFields { index: 12, crc32: 0xab, modified };
+        let render =
+            |template: &str, path| parse_name_template(template).unwrap().render(path, &fields);
+        assert_eq!(render("{index}_{name}", "a/b/c.tar.gz"), "12_c.tar.gz");
+        assert_eq!(render("{dir}/{stem}.{crc}{ext}", "a/b/c.tar.gz"), "a/b/c.tar.000000ab.gz");
+        assert_eq!(render("{date}/{stem}{ext}", ".bashrc"), "2024-03-05/.bashrc");
+        assert_eq!(render("{{{mtime}}}-{path}", "x"), "{20240305-070810}-x");
+        assert_eq!(parse_name_template("{{{path}}}").unwrap().to_string(), "{{{path}}}");
+    }
+
+    #[test]
+    fn test_parse_name_template_errors() {
+        assert!(parse_name_template("{stem").unwrap_err().contains("unclosed"));
+        assert!(parse_name_template("a}b{name}").unwrap_err().contains("unmatched"));
+        assert!(parse_name_template("{size}").unwrap_err().contains("unknown placeholder"));
+        assert!(parse_name_template("fixed.txt").unwrap_err().contains("no placeholder"));
+    }
+}
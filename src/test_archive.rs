@@ -6,34 +6,134 @@
 //!
 //! # Features
 //!
-//! - CRC32 verification for all files
+//! - CRC32 verification for all files, streamed through a fixed buffer
+//!   ([`CrcChecker`](crate::verify::CrcChecker)) whatever their size
 //! - Pattern-based file filtering
-//! - Progress reporting during testing
+//! - Progress reporting during testing, or to an [`ExtractObserver`] via
+//!   [`test_archive_with`]
 //! - Detailed error reporting for corrupted files
+//! - Recovery of entries with a mislabelled Stored/Deflate method
+//!   (`--heuristic-decode`), reported as warnings
+//! - Extraction limits (`--max-output-size`, `--max-ratio`, `--max-entries`)
+//! - TAP or JSON output for test harnesses and pipelines (`--report`, see
+//!   [`crate::report`])
 //!
 //! # Examples
 //!
 //! ```no_run
 //! use std::fs::File;
 //! use zip::ZipArchive;
-//! use unzip::{Args, test_archive};
+//! use unzip::{Args, CancelToken, test_archive};
 //! use clap::Parser;
 //!
 //! let file = File::open("archive.zip")?;
 //! let mut archive = ZipArchive::new(file)?;
 //! let args = Args::parse();
-//! test_archive(&mut archive, &args)?;
+//! test_archive(&mut archive, &args, &CancelToken::new())?;
 //! # Ok::<(), Box<dyn std::error::Error>>(())
 //! ```
 
-use anyhow::{Result, bail};
 use indicatif::{ProgressBar, ProgressStyle};
-use std::io::{Read, Seek};
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::io::{self, Read, Seek};
+use std::path::Path;
 use zip::ZipArchive;
+use zip::read::ZipFile;
+use zip::result::ZipError;
 
 use crate::args::Args;
+use crate::cancel::CancelToken;
+use crate::charset::NameDecoder;
+use crate::compat::Compat;
+use crate::error::{Result, UnzipError};
+use crate::events::{ExtractObserver, SkipReason, TestFailure};
+use crate::invalid_names::check_names;
+use crate::legacy::{LegacyEntry, decodes};
+use crate::limits::{ExtractionLimits, LimitExceeded, OutputBudget};
+use crate::options::ExtractOptions;
+use crate::recovery::{decode_relabelled, is_corrupt_data};
+use crate::report::{JsonReporter, Report, ReportFormat, TapReporter};
+use crate::size_check::ExpectedSizes;
 use crate::utils::PatternMatcher;
+use crate::verify::CrcChecker;
+
+/// Outcome of [`test_archive_with`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TestReport {
+    /// Entries whose data was read and checked
+    pub tested: usize,
+    /// Entries that failed to read or whose CRC32 did not match
+    pub errors: usize,
+}
+
+/// Console output of `unzip -t`: a progress bar plus one line per entry, or with
+/// `--compat=infozip` only the lines, on stdout
+struct TestReporter {
+    progress_bar: Option<ProgressBar>,
+    quiet: u8,
+    compat: Compat,
+}
+
+impl TestReporter {
+    fn new(quiet: u8, compat: Compat) -> Self {
+        Self { progress_bar: None, quiet, compat }
+    }
+
+    fn tick(&self) {
+        if let Some(ref pb) = self.progress_bar {
+            pb.inc(1);
+        }
+    }
+}
+
+impl ExtractObserver for TestReporter {
+    fn started(&mut self, total_entries: usize) {
+        if self.quiet == 0 && self.compat == Compat::Native {
+            let pb = ProgressBar::new(total_entries as u64);
+            if let Ok(style) = ProgressStyle::default_bar()
+                .template("{spinner:.green} Testing [{bar:40.cyan/blue}] {pos}/{len} ({eta})")
+            {
+                pb.set_style(style.progress_chars("#>-"));
+            }
+            self.progress_bar = Some(pb);
+        }
+    }
+
+    fn entry_finished(&mut self, name: &str, _path: Option<&Path>, _bytes: u64) {
+        if let Some(ref pb) = self.progress_bar {
+            pb.println(format!("    testing: {}  OK", name));
+        } else if self.quiet == 0 && self.compat == Compat::InfoZip {
+            println!("    testing: {:<22}   OK", name);
+        }
+        self.tick();
+    }
+
+    fn entry_skipped(&mut self, _name: &str, _reason: SkipReason) {
+        self.tick();
+    }
+
+    fn entry_failed(&mut self, name: &str, error: &str) {
+        if self.quiet < 2 {
+            eprintln!("error: {} - {}", name, error);
+        }
+        self.tick();
+    }
+
+    fn warning(&mut self, message: &str) {
+        match self.progress_bar {
+            Some(ref pb) => pb.println(message),
+            None if self.quiet < 2 => eprintln!("{}", message.trim_start()),
+            None => {},
+        }
+    }
+}
+
+impl Drop for TestReporter {
+    fn drop(&mut self) {
+        if let Some(ref pb) = self.progress_bar {
+            pb.finish_and_clear();
+        }
+    }
+}
 
 /// Test ZIP archive integrity by verifying CRC32 checksums for all files.
 ///
@@ -47,137 +147,342 @@ use crate::utils::PatternMatcher;
 /// * `args` - Command-line arguments controlling:
 ///   - Pattern filters (test only matching files)
 ///   - Quiet mode (suppress progress output)
+///   - Extraction limits (abort on suspected decompression bombs)
+/// * `cancel` - Stops testing with [`UnzipError::Cancelled`] once cancelled
 ///
 /// # Errors
 ///
 /// Returns an error if:
-/// - Any file's CRC32 checksum doesn't match (indicates corruption)
-/// - A file cannot be read from the archive
-/// - The number of errors exceeds zero (after testing all files)
+/// - Any file fails to read or its CRC32 checksum doesn't match
+///   ([`UnzipError::TestFailed`], after testing all files)
+/// - The archive's entry table cannot be read
+/// - The archive crosses an extraction limit ([`UnzipError::Limit`], testing stops
+///   immediately)
+/// - `cancel` is cancelled ([`UnzipError::Cancelled`])
 ///
 /// # Examples
 ///
 /// ```no_run
 /// use std::fs::File;
 /// use zip::ZipArchive;
-/// use unzip::{Args, test_archive};
+/// use unzip::{Args, CancelToken, test_archive};
 /// use clap::Parser;
 ///
 /// let file = File::open("archive.zip")?;
 /// let mut archive = ZipArchive::new(file)?;
 /// let args = Args::parse();
-/// test_archive(&mut archive, &args)?;  // Returns Ok if all files valid
+/// test_archive(&mut archive, &args, &CancelToken::new())?;  // Returns Ok if all files valid
 /// # Ok::<(), Box<dyn std::error::Error>>(())
 /// ```
-pub fn test_archive<R: Read + Seek>(archive: &mut ZipArchive<R>, args: &Args) -> Result<()> {
-    let total_files = archive.len();
-    let errors = AtomicUsize::new(0);
-    let tested = AtomicUsize::new(0);
-    let matcher = PatternMatcher::new(&args.patterns, &args.exclude, args.case_insensitive);
-    let mut buffer = vec![0u8; 256 * 1024];
-
-    let progress_bar = if args.quiet == 0 {
-        let pb = ProgressBar::new(total_files as u64);
-        pb.set_style(
-            ProgressStyle::default_bar()
-                .template("{spinner:.green} Testing [{bar:40.cyan/blue}] {pos}/{len} ({eta})")?
-                .progress_chars("#>-"),
-        );
-        Some(pb)
-    } else {
-        None
+pub fn test_archive<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    args: &Args,
+    cancel: &CancelToken,
+) -> Result<()> {
+    if let Some(format) = args.report {
+        return test_archive_report(archive, args, format, cancel);
+    }
+    let report = {
+        let mut reporter = TestReporter::new(args.quiet, args.compat);
+        test_archive_with(archive, args, &mut reporter, cancel)?
+    };
+
+    if args.quiet < 2 && args.compat == Compat::InfoZip {
+        if report.errors == 0 {
+            println!("No errors detected in compressed data of {}.", args.zipfile.display());
+        } else {
+            println!("At least one error was detected in {}.", args.zipfile.display());
+        }
+    } else if args.quiet < 2 {
+        if report.errors == 0 {
+            println!(
+                "No errors detected in compressed data of {}.  {} files tested.",
+                args.zipfile.display(),
+                report.tested
+            );
+        } else {
+            println!(
+                "{} error(s) detected in {}.  {} files tested.",
+                report.errors,
+                args.zipfile.display(),
+                report.tested
+            );
+        }
+    }
+
+    if report.errors > 0 {
+        return Err(UnzipError::TestFailed { errors: report.errors });
+    }
+
+    Ok(())
+}
+
+/// [`test_archive`] with `--report`: the report on stdout instead of the console
+/// output
+fn test_archive_report<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    args: &Args,
+    format: ReportFormat,
+    cancel: &CancelToken,
+) -> Result<()> {
+    let mut reporter: Box<dyn Report> = match format {
+        ReportFormat::Tap => Box::new(TapReporter::new(io::stdout())),
+        ReportFormat::Json => {
+            let name = args.zipfile.display().to_string();
+            Box::new(JsonReporter::new(io::stdout(), &name))
+        },
+    };
+    let report = match test_archive_with(archive, args, reporter.as_mut(), cancel) {
+        Ok(report) => report,
+        Err(error) => {
+            reporter.end(Some(&error.to_string()))?;
+            return Err(error);
+        },
     };
+    reporter.end(None)?;
+    if report.errors > 0 {
+        return Err(UnzipError::TestFailed { errors: report.errors });
+    }
+    Ok(())
+}
+
+/// Test archive integrity, reporting progress to `observer` instead of the console
+///
+/// Every entry produces `entry_started`, one `bytes_written` per block checked, and
+/// then `entry_finished` (with no path) or `entry_failed`. Entries excluded by the
+/// patterns are reported through `entry_skipped`. Unlike [`test_archive`], failed
+/// entries do not make this function return an error; check [`TestReport::errors`].
+///
+/// # Errors
+///
+/// Returns an error if the archive cannot be read, the entry names cannot be
+/// decoded with the requested charset, an extraction limit is crossed, or `cancel`
+/// is cancelled
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use zip::ZipArchive;
+/// use unzip::{Args, CancelToken, ExtractObserver, test_archive_with};
+/// use clap::Parser;
+///
+/// struct Failures(Vec<String>);
+///
+/// impl ExtractObserver for Failures {
+///     fn entry_failed(&mut self, name: &str, _error: &str) {
+///         self.0.push(name.to_string());
+///     }
+/// }
+///
+/// let mut archive = ZipArchive::new(File::open("archive.zip")?)?;
+/// let mut failures = Failures(Vec::new());
+/// let report = test_archive_with(&mut archive, &Args::parse(), &mut failures, &CancelToken::new())?;
+/// println!("{} tested, corrupt: {:?}", report.tested, failures.0);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn test_archive_with<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    args: &Args,
+    observer: &mut dyn ExtractObserver,
+    cancel: &CancelToken,
+) -> Result<TestReport> {
+    let settings = TestSettings {
+        limits: ExtractionLimits::from_args(args),
+        matcher: args.matcher(),
+        names: NameDecoder::from_args(args)?,
+        password: args.password.as_deref(),
+        expected_sizes: args.expected_sizes.as_deref(),
+        heuristic_decode: args.heuristic_decode,
+    };
+    test_entries(archive, &settings, observer, cancel)
+}
+
+/// What testing takes from the arguments, or from [`ExtractOptions`] for
+/// [`crate::session::UnzipSession::test`]
+pub(crate) struct TestSettings<'a> {
+    pub(crate) limits: ExtractionLimits,
+    /// The entries tested: those the member patterns and `-x` select
+    pub(crate) matcher: PatternMatcher<'a>,
+    pub(crate) names: NameDecoder,
+    pub(crate) password: Option<&'a str>,
+    /// Sizes settled by `--size-check`
+    pub(crate) expected_sizes: Option<&'a ExpectedSizes>,
+    pub(crate) heuristic_decode: bool,
+}
+
+impl<'a> TestSettings<'a> {
+    /// The settings of extraction `options`, to test what they would extract
+    pub(crate) fn from_options(options: &'a ExtractOptions) -> Self {
+        Self {
+            limits: options.limits,
+            matcher: options.matcher(),
+            names: options.names.clone(),
+            password: options.password.as_deref(),
+            expected_sizes: options.expected_sizes.as_deref(),
+            heuristic_decode: options.heuristic_decode,
+        }
+    }
+}
+
+/// [`test_archive_with`] by `settings`
+pub(crate) fn test_entries<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    settings: &TestSettings<'_>,
+    observer: &mut dyn ExtractObserver,
+    cancel: &CancelToken,
+) -> Result<TestReport> {
+    let TestSettings { limits, ref matcher, ref names, password, .. } = *settings;
+    limits.check_archive(archive)?;
+    let budget = OutputBudget::new(limits);
+    let total_files = archive.len();
+    let mut report = TestReport::default();
+    check_names(archive, names)?;
+    let mut checker = CrcChecker::new();
+
+    observer.started(total_files);
 
     for i in 0..total_files {
-        let mut file = archive.by_index(i)?;
-        let name = file.name().to_string();
+        cancel.check()?;
+        let name = names.decode(&archive.by_index_raw(i)?).into_owned();
 
         if !matcher.should_extract(&name) {
-            if let Some(ref pb) = progress_bar {
-                pb.inc(1);
-            }
+            observer.entry_skipped(&name, SkipReason::Filtered);
             continue;
         }
 
-        let mut hasher = crc32fast::Hasher::new();
-        let mut read_error: Option<anyhow::Error> = None;
+        let mut file = match open_entry(archive, i, password)? {
+            Ok(file) => file,
+            Err(failure) => {
+                observer.entry_test_failed(&name, &failure);
+                report.errors += 1;
+                report.tested += 1;
+                continue;
+            },
+        };
+        observer.entry_started(&name, file.size());
+        let mut read_error: Option<std::io::Error> = None;
+        let compressed_size = file.compressed_size();
+        let mut legacy_entry = None;
+        let data: &mut dyn Read = if decodes(&file) {
+            legacy_entry.insert(LegacyEntry::new(&mut file))
+        } else {
+            &mut file
+        };
+        let mut reader = budget.guard(data, &name, compressed_size);
         loop {
-            match file.read(&mut buffer) {
-                Ok(0) => break,
-                Ok(n) => hasher.update(&buffer[..n]),
+            cancel.check()?;
+            match checker.next_chunk(&mut reader) {
+                Ok([]) => break,
+                Ok(chunk) => observer.bytes_written(&name, chunk.len() as u64),
+                // A crossed limit ends the whole test rather than one entry
+                Err(e) if e.get_ref().is_some_and(|inner| inner.is::<LimitExceeded>()) => {
+                    return Err(e.into());
+                },
                 Err(e) => {
-                    read_error = Some(e.into());
+                    read_error = Some(e);
                     break;
                 },
             }
         }
-
-        if let Some(e) = read_error {
-            if args.quiet < 2 {
-                eprintln!("error: {} - {}", name, e);
-            }
-            errors.fetch_add(1, Ordering::Relaxed);
-        } else {
-            let computed_crc = hasher.finalize();
-            let stored_crc = file.crc32();
-
-            if computed_crc != stored_crc {
-                if args.quiet < 2 {
-                    eprintln!(
-                        "error: {} - CRC mismatch (stored: {:08x}, computed: {:08x})",
-                        name, stored_crc, computed_crc
-                    );
+        drop(legacy_entry);
+
+        let ((computed, bytes), stored) = (checker.finish(), file.crc32());
+        let failure = match read_error {
+            // The zip crate checks the CRC32 itself once the data ends
+            Some(e) if is_checksum_error(&e) => {
+                Some((TestFailure::CrcMismatch { stored, computed }, true))
+            },
+            Some(e) => Some((TestFailure::Corrupt(e.to_string()), is_corrupt_data(&e))),
+            None if computed != stored => {
+                Some((TestFailure::CrcMismatch { stored, computed }, true))
+            },
+            None => settings
+                .expected_sizes
+                .map(|sizes| sizes.expected(file.header_start(), file.size()))
+                .filter(|expected| *expected != bytes)
+                .map(|expected| (TestFailure::SizeMismatch { expected, actual: bytes }, false)),
+        };
+        let size = file.size();
+        drop(file);
+
+        match failure {
+            None => observer.entry_finished(&name, None, bytes),
+            Some((failure, corrupt)) => {
+                let relabel = if settings.heuristic_decode && corrupt {
+                    decode_relabelled(archive.by_index_raw(i)?, &mut io::sink())?
+                } else {
+                    None
+                };
+                if let Some(relabel) = relabel {
+                    observer.warning(&format!("   recovered: {} ({})", name, relabel));
+                    observer.entry_finished(&name, None, size);
+                } else {
+                    observer.entry_test_failed(&name, &failure);
+                    report.errors += 1;
                 }
-                errors.fetch_add(1, Ordering::Relaxed);
-            } else if args.quiet == 0
-                && let Some(ref pb) = progress_bar
-            {
-                pb.println(format!("    testing: {}  OK", name));
-            }
-        }
-
-        tested.fetch_add(1, Ordering::Relaxed);
-        if let Some(ref pb) = progress_bar {
-            pb.inc(1);
+            },
         }
-    }
 
-    if let Some(pb) = progress_bar {
-        pb.finish_and_clear();
+        report.tested += 1;
     }
 
-    let error_count = errors.load(Ordering::Relaxed);
-    let test_count = tested.load(Ordering::Relaxed);
+    Ok(report)
+}
 
-    if args.quiet < 2 {
-        if error_count == 0 {
-            println!(
-                "No errors detected in compressed data of {}.  {} files tested.",
-                args.zipfile.display(),
-                test_count
-            );
-        } else {
-            println!(
-                "{} error(s) detected in {}.  {} files tested.",
-                error_count,
-                args.zipfile.display(),
-                test_count
-            );
-        }
+/// Open entry `i` for reading, decrypting it with `password` if given, or raw if
+/// it has a legacy method the zip crate cannot decode
+///
+/// # Errors
+///
+/// Returns the entry's [`TestFailure`] inside `Ok` if its method is not
+/// supported or it cannot be decrypted, and other errors reading the archive as
+/// they are
+fn open_entry<'a, R: Read + Seek>(
+    archive: &'a mut ZipArchive<R>,
+    i: usize,
+    password: Option<&str>,
+) -> Result<std::result::Result<ZipFile<'a>, TestFailure>> {
+    let (encrypted, legacy) = {
+        let raw = archive.by_index_raw(i)?;
+        (raw.encrypted(), decodes(&raw))
+    };
+    if legacy {
+        return Ok(Ok(archive.by_index_raw(i)?));
     }
-
-    if error_count > 0 {
-        bail!("Archive test failed with {} errors", error_count);
+    let opened = match password {
+        Some(password) => archive.by_index_decrypt(i, password.as_bytes()),
+        None => archive.by_index(i),
+    };
+    match opened {
+        Ok(file) => Ok(Ok(file)),
+        Err(error @ (ZipError::UnsupportedArchive(_) | ZipError::InvalidPassword)) if encrypted => {
+            Ok(Err(TestFailure::DecryptFailed(error.to_string())))
+        },
+        Err(error @ ZipError::UnsupportedArchive(_)) => {
+            Ok(Err(TestFailure::UnsupportedMethod(error.to_string())))
+        },
+        Err(error) => Err(error.into()),
     }
+}
 
-    Ok(())
+/// Whether `error` is the zip crate's report of data not matching its CRC32
+fn is_checksum_error(error: &io::Error) -> bool {
+    error.kind() == io::ErrorKind::InvalidData && error.to_string() == "Invalid checksum"
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::duplicates::DuplicatePolicy;
+    use crate::implied_dirs::ImpliedDirTimes;
+    use crate::invalid_names::InvalidNames;
+    use crate::linux::CachePolicy;
+    use crate::list::ListFormat;
+    use crate::size_check::{ExpectedSizes, SizeCheck, check_sizes};
     use std::io::{Cursor, Write};
     use std::path::PathBuf;
+    use std::sync::Arc;
     use zip::ZipWriter;
     use zip::write::SimpleFileOptions;
 
@@ -207,23 +512,102 @@ mod tests {
             output_dir: None,
             list_only: false,
             verbose: false,
+            list_format: ListFormat::InfoZip,
+            totals_only: None,
+            sort: None,
+            reverse: false,
+            tree: false,
+            print0: false,
+            du: false,
+            depth: None,
+            min_size: None,
+            max_size: None,
+            newer: None,
+            older: None,
             test: true,
+            report: None,
+            wait_complete: None,
+            size_check: None,
+            expected_sizes: None,
+            no_create_dir: false,
+            raw_names: false,
+            to_tar: None,
+            test_quick: false,
+            lint: false,
+            diff: None,
+            stats: false,
             pipe: false,
             comment_only: false,
+            comment_json: None,
             zipinfo: None,
+            extended_times: false,
             overwrite: false,
             never_overwrite: false,
+            auto_rename: false,
+            allow_self_overwrite: false,
+            if_changed: false,
             freshen: false,
             update: false,
             junk_paths: false,
+            strip_components: 0,
+            transform: Vec::new(),
+            name_template: None,
             case_insensitive: false,
+            wild_stop_at_dir: false,
             lowercase: false,
-            no_timestamps: false,
+            merge_case_dirs: false,
+            no_timestamps: 0,
             quiet: 2, // Suppress output in tests
+            oem_charset: None,
+            iso_charset: None,
+            auto_encoding: false,
+            invalid_names: InvalidNames::Lossy,
+            text_mode: 0,
+            verbose_io: false,
+            cache: CachePolicy::Auto,
+            compat: Compat::Native,
+            recurse_archives: None,
+            subdir_per_archive: false,
+            archives: false,
+            smart_dir: false,
+            stamp_output_dir: None,
+            implied_dir_times: ImpliedDirTimes::Now,
+            flatten_long_paths: false,
+            follow_symlinks: false,
+            heuristic_decode: false,
+            salvage: false,
+            dry_run: false,
+            dirs_only: false,
+            transactional: false,
+            shared_dest: false,
+            patch_in_place: false,
+            force_readonly: false,
+            chown: None,
+            priority: Vec::new(),
+            crt: false,
+            filter: false,
+            checkpoint_every: None,
+            resume: false,
+            metrics_file: None,
+            verify: false,
+            compress_output: None,
+            scan_socket: None,
+            quarantine: None,
+            max_output_size: None,
+            max_ratio: None,
+            max_entries: None,
+            expect_files: None,
+            expect_bytes: None,
+            opendoc_check: false,
             threads: None,
             password: None,
             patterns: vec![],
             exclude: vec![],
+            include_from: Vec::new(),
+            exclude_from: Vec::new(),
+            members_from: None,
+            member_index: None,
+            duplicates: DuplicatePolicy::Last,
         }
     }
 
@@ -240,7 +624,7 @@ mod tests {
         let args = default_args();
 
         // Should pass with no errors for valid archive
-        let result = test_archive(&mut archive, &args);
+        let result = test_archive(&mut archive, &args, &CancelToken::new());
         assert!(result.is_ok());
     }
 
@@ -253,7 +637,7 @@ mod tests {
         let args = default_args();
 
         // Should handle empty archives gracefully
-        let result = test_archive(&mut archive, &args);
+        let result = test_archive(&mut archive, &args, &CancelToken::new());
         assert!(result.is_ok());
     }
 
@@ -268,7 +652,7 @@ mod tests {
         let args = default_args();
 
         // Should handle large files correctly
-        let result = test_archive(&mut archive, &args);
+        let result = test_archive(&mut archive, &args, &CancelToken::new());
         assert!(result.is_ok());
     }
 
@@ -287,7 +671,7 @@ mod tests {
         args.patterns = vec!["*.txt".to_string()];
 
         // Should test only matching files
-        let result = test_archive(&mut archive, &args);
+        let result = test_archive(&mut archive, &args, &CancelToken::new());
         assert!(result.is_ok());
     }
 
@@ -306,7 +690,7 @@ mod tests {
         args.exclude = vec!["*.log".to_string()];
 
         // Should test files except excluded ones
-        let result = test_archive(&mut archive, &args);
+        let result = test_archive(&mut archive, &args, &CancelToken::new());
         assert!(result.is_ok());
     }
 
@@ -325,7 +709,7 @@ mod tests {
         let args = default_args();
 
         // Should handle directories correctly
-        let result = test_archive(&mut archive, &args);
+        let result = test_archive(&mut archive, &args, &CancelToken::new());
         assert!(result.is_ok());
     }
 
@@ -342,10 +726,26 @@ mod tests {
         let args = default_args();
 
         // Should handle zero-byte files correctly
-        let result = test_archive(&mut archive, &args);
+        let result = test_archive(&mut archive, &args, &CancelToken::new());
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_archive_enforces_limits() {
+        let zip_data = create_test_zip(&[("zeros.bin", &[0u8; 100_000])]);
+        let mut args = default_args();
+
+        let mut archive = ZipArchive::new(Cursor::new(zip_data.clone())).unwrap();
+        args.max_output_size = Some(50_000);
+        let err = test_archive(&mut archive, &args, &CancelToken::new()).unwrap_err();
+        assert!(err.to_string().contains("--max-output-size"), "{}", err);
+
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+        args.max_output_size = Some(100_000);
+        args.max_entries = Some(1);
+        assert!(test_archive(&mut archive, &args, &CancelToken::new()).is_ok());
+    }
+
     #[test]
     fn test_archive_binary_content() {
         let binary_data: Vec<u8> = (0..256).map(|i| i as u8).collect();
@@ -356,7 +756,149 @@ mod tests {
         let args = default_args();
 
         // Should handle binary content correctly
-        let result = test_archive(&mut archive, &args);
+        let result = test_archive(&mut archive, &args, &CancelToken::new());
         assert!(result.is_ok());
     }
+
+    #[derive(Default)]
+    struct Counter {
+        checked: u64,
+        ok: Vec<String>,
+        skipped: Vec<String>,
+    }
+
+    impl ExtractObserver for Counter {
+        fn bytes_written(&mut self, _name: &str, bytes: u64) {
+            self.checked += bytes;
+        }
+
+        fn entry_finished(&mut self, name: &str, path: Option<&Path>, _bytes: u64) {
+            assert!(path.is_none());
+            self.ok.push(name.to_string());
+        }
+
+        fn entry_skipped(&mut self, name: &str, _reason: SkipReason) {
+            self.skipped.push(name.to_string());
+        }
+    }
+
+    #[test]
+    fn test_archive_with_observer() {
+        let zip_data = create_test_zip(&[("a.txt", b"hello"), ("b.log", b"skip me")]);
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+        let mut args = default_args();
+        args.exclude = vec!["*.log".to_string()];
+
+        let mut counter = Counter::default();
+        let report =
+            test_archive_with(&mut archive, &args, &mut counter, &CancelToken::new()).unwrap();
+
+        assert_eq!(report, TestReport { tested: 1, errors: 0 });
+        assert_eq!(counter.checked, 5);
+        assert_eq!(counter.ok, ["a.txt"]);
+        assert_eq!(counter.skipped, ["b.log"]);
+    }
+
+    #[test]
+    fn test_archive_reports_crc_mismatch() {
+        let mut zip_data = create_test_zip(&[("a.txt", b"hello world")]);
+        let pos = zip_data.windows(11).position(|w| w == b"hello world").unwrap();
+        zip_data[pos] = b'j';
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+
+        let err = test_archive(&mut archive, &default_args(), &CancelToken::new()).unwrap_err();
+        assert!(matches!(err, UnzipError::TestFailed { errors: 1 }), "{}", err);
+    }
+
+    #[test]
+    fn test_archive_decodes_shrunk_entry() {
+        let shrunk = [0x61, 0xc4, 0x04, 0x1c, 0x23, 0xb0, 0x60, 0x98, 0x83, 0x08, 0xc3, 0x00];
+        let expected = b"ababcbababaaaaaaa";
+        let shrunk_zip = |crc32: u32| {
+            // A stored entry holding the shrunk data, relabelled as shrunk
+            let mut zip_data = create_test_zip(&[("a.txt", &shrunk)]);
+            let central = zip_data.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+            for (method, crc, size) in [(8, 14, 22), (central + 10, central + 16, central + 24)] {
+                zip_data[method..method + 2].copy_from_slice(&1u16.to_le_bytes());
+                zip_data[crc..crc + 4].copy_from_slice(&crc32.to_le_bytes());
+                zip_data[size..size + 4].copy_from_slice(&(expected.len() as u32).to_le_bytes());
+            }
+            ZipArchive::new(Cursor::new(zip_data)).unwrap()
+        };
+
+        let mut archive = shrunk_zip(crc32fast::hash(expected));
+        test_archive(&mut archive, &default_args(), &CancelToken::new()).unwrap();
+
+        let mut archive = shrunk_zip(crc32fast::hash(b"something else"));
+        let err = test_archive(&mut archive, &default_args(), &CancelToken::new()).unwrap_err();
+        assert!(matches!(err, UnzipError::TestFailed { errors: 1 }), "{}", err);
+    }
+
+    #[test]
+    fn test_archive_checks_settled_sizes() {
+        let mut zip_data = create_test_zip(&[("a.txt", b"hello world")]);
+        let central = zip_data.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+        zip_data[central + 24] = 12; // size, 11 in the local header and as stored
+        let check = |mode| check_sizes(Cursor::new(&zip_data), mode, |_| {});
+        assert!(check(SizeCheck::Strict).is_err());
+
+        // Lenient goes by the stored data; the central directory alone is wrong
+        let mut args = default_args();
+        args.expected_sizes = Some(Arc::new(check(SizeCheck::Lenient).unwrap()));
+        let mut archive = ZipArchive::new(Cursor::new(zip_data.clone())).unwrap();
+        test_archive(&mut archive, &args, &CancelToken::new()).unwrap();
+
+        args.expected_sizes = Some(Arc::new(ExpectedSizes::default()));
+        let err = test_archive(&mut archive, &args, &CancelToken::new()).unwrap_err();
+        assert!(matches!(err, UnzipError::TestFailed { errors: 1 }), "{}", err);
+    }
+
+    #[test]
+    fn test_archive_tap_report() {
+        let mut zip_data = create_test_zip(&[("a.txt", b"hello world"), ("b.txt", b"fine")]);
+        let pos = zip_data.windows(11).position(|w| w == b"hello world").unwrap();
+        zip_data[pos] = b'j';
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+
+        let mut tap = TapReporter::new(Vec::new());
+        let report =
+            test_archive_with(&mut archive, &default_args(), &mut tap, &CancelToken::new())
+                .unwrap();
+        let out = String::from_utf8(tap.finish().unwrap()).unwrap();
+        assert_eq!(report, TestReport { tested: 2, errors: 1 });
+        assert!(out.starts_with("TAP version 13\nnot ok 1 - a.txt\n  ---\n  message: '"));
+        assert!(out.ends_with("  ...\nok 2 - b.txt\n1..2\n"), "{}", out);
+    }
+
+    #[test]
+    fn test_archive_json_report() {
+        let mut zip_data = create_test_zip(&[("a.txt", b"hello world"), ("b.txt", b"fine")]);
+        let pos = zip_data.windows(11).position(|w| w == b"hello world").unwrap();
+        zip_data[pos] = b'j';
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+
+        let mut json = JsonReporter::new(Vec::new(), "test.zip");
+        test_archive_with(&mut archive, &default_args(), &mut json, &CancelToken::new()).unwrap();
+        let out = String::from_utf8(json.finish().unwrap()).unwrap();
+        let crc = format!("{:08x}", crc32fast::hash(b"hello world"));
+        assert!(out.starts_with("{\"archive\":\"test.zip\",\"entries\":[\n"), "{}", out);
+        assert!(out.contains("\"status\":\"crc-mismatch\""), "{}", out);
+        assert!(out.contains(&format!("\"stored_crc32\":\"{}\"", crc)), "{}", out);
+        assert!(out.contains("\n{\"name\":\"b.txt\",\"status\":\"ok\",\"size\":4}\n"));
+        assert!(out.ends_with(
+            "\"summary\":{\"tested\":2,\"ok\":1,\"failed\":1,\"skipped\":0,\
+             \"failures\":{\"crc-mismatch\":1}}}\n"
+        ));
+    }
+
+    #[test]
+    fn test_archive_cancelled() {
+        let zip_data = create_test_zip(&[("a.txt", b"hello")]);
+        let mut archive = ZipArchive::new(Cursor::new(zip_data)).unwrap();
+        let cancel = CancelToken::new();
+        cancel.cancel();
+
+        let err = test_archive(&mut archive, &default_args(), &cancel).unwrap_err();
+        assert!(matches!(err, UnzipError::Cancelled));
+    }
 }
@@ -0,0 +1,192 @@
+//! Text mode line ending conversion (`-a` / `-aa`)
+//!
+//! With `-a`, entries flagged as text in their internal file attributes (bit 0 of the
+//! central directory field) have their line endings converted to the local convention
+//! while being extracted: CRLF becomes LF on Unix, and bare LF becomes CRLF on
+//! Windows. `-aa` converts every file regardless of the flag.
+//!
+//! The zip crate does not expose internal attributes, so they are read straight from
+//! the central directory when the archive source is available. When it is not (for
+//! example an in-memory `ZipArchive` handed to [`crate::extract_archive`]), `-a` falls
+//! back to the heuristic archivers use to set the flag: no NUL bytes in the first block.
+//!
+//! # Examples
+//!
+//! ```
+//! use unzip::textmode::TextConverter;
+//!
+//! let mut converter = TextConverter::new();
+//! let mut out = Vec::new();
+//! converter.convert(b"one\r", &mut out);
+//! converter.convert(b"\ntwo\r\n", &mut out);
+//! converter.finish(&mut out);
+//! # #[cfg(not(windows))]
+//! assert_eq!(out, b"one\ntwo\n");
+//! ```
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// Offset of the internal file attributes within a central directory header
+const INTERNAL_ATTRIBUTES_OFFSET: u64 = 36;
+
+/// Internal attribute bit marking an entry as text
+const TEXT_FLAG: u16 = 0x0001;
+
+/// How entries should be treated for line ending conversion
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextMode {
+    /// Write entries byte for byte (default)
+    Binary,
+    /// Convert entries flagged as text (`-a`)
+    Auto,
+    /// Convert every file entry (`-aa`)
+    All,
+}
+
+impl TextMode {
+    /// Map the number of `-a` flags to a mode
+    pub fn from_count(count: u8) -> Self {
+        match count {
+            0 => Self::Binary,
+            1 => Self::Auto,
+            _ => Self::All,
+        }
+    }
+
+    /// Whether to convert an entry whose text flag is `text_flag`, or `None` to
+    /// guess from its content
+    pub fn converts(self, text_flag: Option<bool>) -> Option<bool> {
+        match self {
+            Self::Binary => Some(false),
+            Self::Auto => text_flag,
+            Self::All => Some(true),
+        }
+    }
+}
+
+/// Read the internal file attributes of an entry from its central directory header
+///
+/// # Arguments
+///
+/// * `reader` - Reader over the whole archive
+/// * `central_header_start` - Offset of the entry's central directory header
+///
+/// # Errors
+///
+/// Returns an error if the header cannot be read
+pub fn read_internal_attributes<R: Read + Seek>(
+    reader: &mut R,
+    central_header_start: u64,
+) -> io::Result<u16> {
+    reader.seek(SeekFrom::Start(central_header_start + INTERNAL_ATTRIBUTES_OFFSET))?;
+    let mut field = [0u8; 2];
+    reader.read_exact(&mut field)?;
+    Ok(u16::from_le_bytes(field))
+}
+
+/// Whether internal file attributes mark an entry as text
+pub fn is_text(internal_attributes: u16) -> bool {
+    internal_attributes & TEXT_FLAG != 0
+}
+
+/// Guess whether a block of data is text (no NUL bytes)
+pub fn looks_like_text(sample: &[u8]) -> bool {
+    !sample.contains(&0)
+}
+
+/// Streaming converter from any line ending convention to the local one
+///
+/// Input may be fed in arbitrary chunks; a CR at the end of one chunk is held back
+/// until the next chunk shows whether it starts a CRLF pair.
+#[derive(Debug, Default)]
+pub struct TextConverter {
+    pending_cr: bool,
+}
+
+impl TextConverter {
+    /// Create a converter with no pending state
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Convert one chunk, appending the result to `out`
+    pub fn convert(&mut self, input: &[u8], out: &mut Vec<u8>) {
+        out.reserve(input.len());
+        for &byte in input {
+            if self.pending_cr {
+                self.pending_cr = false;
+                if byte == b'\n' {
+                    out.extend_from_slice(LINE_ENDING);
+                    continue;
+                }
+                out.push(b'\r');
+            }
+            match byte {
+                b'\r' => self.pending_cr = true,
+                b'\n' => out.extend_from_slice(LINE_ENDING),
+                other => out.push(other),
+            }
+        }
+    }
+
+    /// Flush a trailing CR held back by [`TextConverter::convert`]
+    pub fn finish(&mut self, out: &mut Vec<u8>) {
+        if self.pending_cr {
+            self.pending_cr = false;
+            out.push(b'\r');
+        }
+    }
+}
+
+#[cfg(windows)]
+const LINE_ENDING: &[u8] = b"\r\n";
+#[cfg(not(windows))]
+const LINE_ENDING: &[u8] = b"\n";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn convert_chunks(chunks: &[&[u8]]) -> Vec<u8> {
+        let mut converter = TextConverter::new();
+        let mut out = Vec::new();
+        for chunk in chunks {
+            converter.convert(chunk, &mut out);
+        }
+        converter.finish(&mut out);
+        out
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_converter_crlf_to_lf() {
+        assert_eq!(convert_chunks(&[b"a\r\nb\nc\r\n"]), b"a\nb\nc\n");
+        assert_eq!(convert_chunks(&[b"a\r", b"\nb\r"]), b"a\nb\r");
+        assert_eq!(convert_chunks(&[b"lone\rcr"]), b"lone\rcr");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_converter_lf_to_crlf() {
+        assert_eq!(convert_chunks(&[b"a\nb\r\n"]), b"a\r\nb\r\n");
+        assert_eq!(convert_chunks(&[b"a\r", b"\n"]), b"a\r\n");
+    }
+
+    #[test]
+    fn test_text_mode_from_count() {
+        assert_eq!(TextMode::from_count(0), TextMode::Binary);
+        assert_eq!(TextMode::from_count(1), TextMode::Auto);
+        assert_eq!(TextMode::from_count(2), TextMode::All);
+    }
+
+    #[test]
+    fn test_read_internal_attributes() {
+        let mut header = vec![0u8; 46];
+        header[36] = 0x01;
+        let mut reader = Cursor::new(header);
+        let attributes = read_internal_attributes(&mut reader, 0).unwrap();
+        assert!(is_text(attributes));
+        assert!(!looks_like_text(b"bin\0ary"));
+    }
+}
@@ -0,0 +1,319 @@
+//! High-precision entry timestamps from extra fields
+//!
+//! The DOS date and time in every ZIP header has two-second resolution and no time
+//! zone. Archivers that care about timestamps add an extra field next to it:
+//!
+//! - Extended timestamp (`0x5455`, "UT"): Unix seconds in UTC; the central header
+//!   carries the modification time, the local header may add access and creation
+//! - NTFS (`0x000a`): modification, access and creation times in 100 ns ticks since
+//!   1601-01-01 UTC
+//!
+//! [`EntryTimes::from_entry`] prefers NTFS, then UT, and falls back to the DOS time
+//! (interpreted as UTC, like the rest of this crate) when neither is present.
+//!
+//! # Examples
+//!
+//! ```
+//! use filetime::FileTime;
+//! use unzip::timestamps::{format_utc, ntfs_to_filetime};
+//!
+//! // 2020-01-02 03:04:05.5 UTC
+//! let time = ntfs_to_filetime(132_224_078_455_000_000);
+//! assert_eq!(time, FileTime::from_unix_time(1_577_934_245, 500_000_000));
+//! assert_eq!(format_utc(time), "2020-01-02 03:04:05.5000000 UTC");
+//! ```
+
+use filetime::FileTime;
+use zip::extra_fields::ExtraField;
+use zip::read::ZipFile;
+
+use crate::utils::datetime_to_filetime;
+
+/// Seconds between 1601-01-01 (NTFS epoch) and 1970-01-01 (Unix epoch)
+const NTFS_EPOCH_OFFSET: i64 = 11_644_473_600;
+
+/// NTFS ticks (100 ns) per second
+const NTFS_TICKS_PER_SECOND: u64 = 10_000_000;
+
+/// Resolution of the DOS header time in seconds; smaller differences are rounding
+pub const DOS_TIME_RESOLUTION: i64 = 2;
+
+/// Timestamps found in an entry's extra fields
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExtraTimes {
+    /// Last modification time
+    pub modified: Option<FileTime>,
+    /// Last access time
+    pub accessed: Option<FileTime>,
+    /// Creation time
+    pub created: Option<FileTime>,
+}
+
+impl ExtraTimes {
+    /// Whether no timestamp was found
+    pub fn is_empty(&self) -> bool {
+        self.modified.is_none() && self.accessed.is_none() && self.created.is_none()
+    }
+}
+
+/// Extended timestamp and NTFS times of one entry, as stored
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExtraTimestamps {
+    /// Times from the extended timestamp field (`0x5455`)
+    pub unix: Option<ExtraTimes>,
+    /// Times from the NTFS field (`0x000a`)
+    pub ntfs: Option<ExtraTimes>,
+}
+
+impl ExtraTimestamps {
+    /// Collect the timestamp extra fields of an entry
+    pub fn from_entry(file: &ZipFile) -> Self {
+        let mut found = Self::default();
+        for field in file.extra_data_fields() {
+            match field {
+                ExtraField::ExtendedTimestamp(ut) => {
+                    let times = ExtraTimes {
+                        modified: ut.mod_time().map(unix_to_filetime),
+                        accessed: ut.ac_time().map(unix_to_filetime),
+                        created: ut.cr_time().map(unix_to_filetime),
+                    };
+                    if !times.is_empty() {
+                        found.unix = Some(times);
+                    }
+                },
+                ExtraField::Ntfs(ntfs) => {
+                    found.ntfs = Some(ExtraTimes {
+                        modified: nonzero_ntfs(ntfs.mtime()),
+                        accessed: nonzero_ntfs(ntfs.atime()),
+                        created: nonzero_ntfs(ntfs.ctime()),
+                    });
+                },
+            }
+        }
+        found
+    }
+
+    /// The most precise set of times available (NTFS before UT)
+    pub fn best(&self) -> Option<ExtraTimes> {
+        self.ntfs.filter(|times| times.modified.is_some()).or(self.unix)
+    }
+}
+
+/// Times to restore on an extracted entry
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EntryTimes {
+    /// Modification time, from an extra field or the DOS header time
+    pub modified: Option<FileTime>,
+    /// Access time, only when an extra field records one
+    pub accessed: Option<FileTime>,
+}
+
+impl EntryTimes {
+    /// Pick the best available times for an entry
+    pub fn from_entry(file: &ZipFile) -> Self {
+        let extra = ExtraTimestamps::from_entry(file).best().unwrap_or_default();
+        Self {
+            modified: extra.modified.or_else(|| file.last_modified().map(datetime_to_filetime)),
+            accessed: extra.accessed,
+        }
+    }
+
+    /// Pick the times Info-ZIP's UnZip restores on Unix (`--compat=infozip`)
+    ///
+    /// NTFS times are ignored, the extended timestamp field is preferred over the
+    /// DOS time, and the access time falls back to the modification time.
+    pub fn info_zip(file: &ZipFile) -> Self {
+        let unix = ExtraTimestamps::from_entry(file).unix.unwrap_or_default();
+        let modified = unix.modified.or_else(|| file.last_modified().map(datetime_to_filetime));
+        Self { modified, accessed: unix.accessed.or(modified) }
+    }
+}
+
+/// Seconds by which the extended timestamp modification time is ahead of the DOS time
+///
+/// Returns `None` unless the entry has both. Differences beyond
+/// [`DOS_TIME_RESOLUTION`] usually mean the archiver stored local time in the DOS
+/// field, so tools that read only the DOS time make other `-f`/`-u` decisions.
+pub fn dos_time_skew(file: &ZipFile) -> Option<i64> {
+    let ut = ExtraTimestamps::from_entry(file).unix?.modified?;
+    let dos = datetime_to_filetime(file.last_modified()?);
+    Some(ut.unix_seconds() - dos.unix_seconds())
+}
+
+/// Convert an extended timestamp value (Unix seconds, UTC) to a `FileTime`
+pub fn unix_to_filetime(secs: u32) -> FileTime {
+    FileTime::from_unix_time(i64::from(secs), 0)
+}
+
+/// Convert an NTFS time (100 ns ticks since 1601-01-01 UTC) to a `FileTime`
+pub fn ntfs_to_filetime(ticks: u64) -> FileTime {
+    let secs = (ticks / NTFS_TICKS_PER_SECOND) as i64 - NTFS_EPOCH_OFFSET;
+    let nanos = (ticks % NTFS_TICKS_PER_SECOND) as u32 * 100;
+    FileTime::from_unix_time(secs, nanos)
+}
+
+/// NTFS fields written by some tools leave unknown times as zero
+fn nonzero_ntfs(ticks: u64) -> Option<FileTime> {
+    (ticks != 0).then(|| ntfs_to_filetime(ticks))
+}
+
+/// Format a time as `YYYY-MM-DD HH:MM:SS[.fffffff] UTC`
+///
+/// The fraction is printed with NTFS (100 ns) resolution when the time has one.
+pub fn format_utc(time: FileTime) -> String {
+    let secs = time.unix_seconds();
+    let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+    let of_day = secs.rem_euclid(86400);
+    let mut formatted = format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        of_day / 3600,
+        of_day % 3600 / 60,
+        of_day % 60
+    );
+    if time.nanoseconds() != 0 {
+        formatted.push_str(&format!(".{:07}", time.nanoseconds() / 100));
+    }
+    formatted.push_str(" UTC");
+    formatted
+}
+
+/// Convert days since 1970-01-01 to a civil date (Howard Hinnant's algorithm)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::{ExtendedFileOptions, FileOptions};
+    use zip::{ZipArchive, ZipWriter};
+
+    fn archive_with_extra(id: u16, data: &[u8]) -> ZipArchive<Cursor<Vec<u8>>> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let mut options = FileOptions::<ExtendedFileOptions>::default()
+            .compression_method(zip::CompressionMethod::Stored)
+            .last_modified_time(zip::DateTime::from_date_and_time(2020, 1, 2, 3, 4, 6).unwrap());
+        options.add_extra_data(id, data.to_vec().into_boxed_slice(), true).unwrap();
+        zip.start_file("file.txt", options).unwrap();
+        zip.write_all(b"content").unwrap();
+        ZipArchive::new(zip.finish().unwrap()).unwrap()
+    }
+
+    /// The writer refuses reserved IDs such as NTFS, so write a placeholder and patch it
+    fn archive_with_ntfs(data: &[u8]) -> ZipArchive<Cursor<Vec<u8>>> {
+        let mut bytes = archive_with_extra(0x6e74, data).into_inner().into_inner();
+        let marker = [0x74, 0x6e, data.len() as u8, 0];
+        let at = bytes.windows(4).position(|window| window == marker).unwrap();
+        bytes[at..at + 2].copy_from_slice(&0x000au16.to_le_bytes());
+        ZipArchive::new(Cursor::new(bytes)).unwrap()
+    }
+
+    #[test]
+    fn test_ntfs_conversion() {
+        assert_eq!(ntfs_to_filetime(116_444_736_000_000_000), FileTime::from_unix_time(0, 0));
+        assert_eq!(ntfs_to_filetime(116_444_736_000_000_001), FileTime::from_unix_time(0, 100));
+    }
+
+    #[test]
+    fn test_format_utc() {
+        assert_eq!(format_utc(FileTime::from_unix_time(0, 0)), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_utc(FileTime::from_unix_time(951_782_400, 0)), "2000-02-29 00:00:00 UTC");
+        assert_eq!(format_utc(FileTime::from_unix_time(-1, 0)), "1969-12-31 23:59:59 UTC");
+    }
+
+    #[test]
+    fn test_entry_times_from_extended_timestamp() {
+        let mut data = vec![0x03];
+        data.extend_from_slice(&1_577_934_245u32.to_le_bytes());
+        data.extend_from_slice(&1_577_934_300u32.to_le_bytes());
+        let mut archive = archive_with_extra(0x5455, &data);
+        let file = archive.by_index(0).unwrap();
+
+        let times = EntryTimes::from_entry(&file);
+        assert_eq!(times.modified, Some(FileTime::from_unix_time(1_577_934_245, 0)));
+        assert_eq!(times.accessed, Some(FileTime::from_unix_time(1_577_934_300, 0)));
+        assert!(ExtraTimestamps::from_entry(&file).ntfs.is_none());
+    }
+
+    #[test]
+    fn test_entry_times_prefer_ntfs() {
+        let mut data = vec![0u8; 4];
+        data.extend_from_slice(&0x0001u16.to_le_bytes());
+        data.extend_from_slice(&24u16.to_le_bytes());
+        data.extend_from_slice(&132_224_078_455_000_000u64.to_le_bytes());
+        data.extend_from_slice(&132_224_078_460_000_000u64.to_le_bytes());
+        data.extend_from_slice(&0u64.to_le_bytes());
+        let mut archive = archive_with_ntfs(&data);
+        let file = archive.by_index(0).unwrap();
+
+        let extra = ExtraTimestamps::from_entry(&file).best().unwrap();
+        assert_eq!(extra.modified, Some(FileTime::from_unix_time(1_577_934_245, 500_000_000)));
+        assert_eq!(extra.accessed, Some(FileTime::from_unix_time(1_577_934_246, 0)));
+        assert_eq!(extra.created, None);
+    }
+
+    #[test]
+    fn test_entry_times_info_zip_ignores_ntfs() {
+        let mut data = vec![0u8; 4];
+        data.extend_from_slice(&0x0001u16.to_le_bytes());
+        data.extend_from_slice(&24u16.to_le_bytes());
+        data.extend_from_slice(&132_224_078_455_000_000u64.to_le_bytes());
+        data.extend_from_slice(&[0; 16]);
+        let mut archive = archive_with_ntfs(&data);
+        let file = archive.by_index(0).unwrap();
+
+        let dos = datetime_to_filetime(file.last_modified().unwrap());
+        let times = EntryTimes::info_zip(&file);
+        assert_eq!(times, EntryTimes { modified: Some(dos), accessed: Some(dos) });
+
+        let mut data = vec![0x01];
+        data.extend_from_slice(&1_577_934_245u32.to_le_bytes());
+        let mut archive = archive_with_extra(0x5455, &data);
+        let modified = Some(FileTime::from_unix_time(1_577_934_245, 0));
+        let times = EntryTimes::info_zip(&archive.by_index(0).unwrap());
+        assert_eq!(times, EntryTimes { modified, accessed: modified });
+    }
+
+    #[test]
+    fn test_dos_time_skew() {
+        // DOS time is 2020-01-02 03:04:06; UT one second earlier, then an hour later
+        let mut data = vec![0x01];
+        data.extend_from_slice(&1_577_934_245u32.to_le_bytes());
+        let mut archive = archive_with_extra(0x5455, &data);
+        assert_eq!(dos_time_skew(&archive.by_index(0).unwrap()), Some(-1));
+
+        data[1..].copy_from_slice(&(1_577_934_246u32 + 3600).to_le_bytes());
+        let mut archive = archive_with_extra(0x5455, &data);
+        assert_eq!(dos_time_skew(&archive.by_index(0).unwrap()), Some(3600));
+
+        let mut archive = archive_with_extra(0x6e74, b"none");
+        assert_eq!(dos_time_skew(&archive.by_index(0).unwrap()), None);
+    }
+
+    #[test]
+    fn test_entry_times_fall_back_to_dos() {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let dt = zip::DateTime::from_date_and_time(2020, 1, 2, 3, 4, 6).unwrap();
+        let options = zip::write::SimpleFileOptions::default().last_modified_time(dt);
+        zip.start_file("file.txt", options).unwrap();
+        let mut archive = ZipArchive::new(zip.finish().unwrap()).unwrap();
+        let file = archive.by_index(0).unwrap();
+
+        let times = EntryTimes::from_entry(&file);
+        assert_eq!(times.modified, Some(datetime_to_filetime(dt)));
+        assert_eq!(times.accessed, None);
+    }
+}
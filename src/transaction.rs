@@ -0,0 +1,399 @@
+//! All-or-nothing extraction (`--transactional`)
+//!
+//! Installers must not leave half-applied trees behind. With `--transactional`,
+//! entries are extracted into a hidden [`Staging`] directory inside the output
+//! directory, and only moved into place once every entry extracted. If extraction
+//! fails, through an error or Ctrl-C, the staging directory is removed and the
+//! destination was never touched.
+//!
+//! Moving into place can fail too, for instance when a file stands where the
+//! archive has a directory. A [`Journal`] therefore records every directory and
+//! file the move creates, and moves each file it replaces aside to a hidden backup
+//! next to it. When the run succeeds the backups are deleted; when it fails, the
+//! created files and (empty) directories are removed and the backups moved back,
+//! so the destination looks as it did before.
+//!
+//! Metadata of directories that already existed, such as their timestamps, is not
+//! restored.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::fs;
+//! use std::path::Path;
+//! use unzip::transaction::Journal;
+//!
+//! let journal = Journal::new(true);
+//! let result = (|| {
+//!     journal.record_dirs(Path::new("out/docs"));
+//!     fs::create_dir_all("out/docs")?;
+//!     journal.prepare_file(Path::new("out/docs/readme.txt"))?;
+//!     fs::write("out/docs/readme.txt", b"hello")?;
+//!     Err(std::io::Error::other("disk full").into())
+//! })();
+//! // Removes out/docs/readme.txt and the directories that did not exist before
+//! let _: unzip::error::Result<()> = journal.finish(result);
+//! ```
+
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+use crate::destination::{escape_error, missing_dirs};
+use crate::error::{Result, UnzipError};
+
+/// Suffix of the hidden copy an overwritten file is moved to until the run ends
+const BACKUP_SUFFIX: &str = ".unzip-backup";
+
+/// Name of the staging directory, followed by the process ID
+const STAGING_PREFIX: &str = ".unzip-staging-";
+
+/// Paths created and files replaced by one extraction run
+#[derive(Debug, Default)]
+struct Changes {
+    /// Directories and files in creation order
+    created: Vec<PathBuf>,
+    /// Overwritten files and where their original is kept
+    backups: Vec<(PathBuf, PathBuf)>,
+}
+
+/// Record of an extraction run's changes to the destination, for rollback
+///
+/// A disabled journal records nothing, so extraction code can call it
+/// unconditionally.
+#[derive(Debug, Default)]
+pub struct Journal {
+    enabled: bool,
+    changes: Mutex<Changes>,
+}
+
+impl Journal {
+    /// Create a journal; when `enabled` is false every method does nothing
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled, changes: Mutex::default() }
+    }
+
+    /// Record the directories `fs::create_dir_all(path)` is about to create
+    pub fn record_dirs(&self, path: &Path) {
+        if !self.enabled {
+            return;
+        }
+        self.lock().created.extend(missing_dirs(path));
+    }
+
+    /// Record a file about to be created, moving an existing file there aside
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the existing file cannot be moved to its backup
+    pub fn prepare_file(&self, path: &Path) -> io::Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let mut changes = self.lock();
+        if fs::symlink_metadata(path).is_ok() {
+            let backup = backup_path(path);
+            fs::rename(path, &backup)?;
+            changes.backups.push((path.to_path_buf(), backup));
+        }
+        changes.created.push(path.to_path_buf());
+        Ok(())
+    }
+
+    /// End the run: keep the changes if `result` is `Ok`, undo them otherwise
+    ///
+    /// Undoing is best effort: directories that gained files from elsewhere in the
+    /// meantime are left in place.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error in `result`
+    pub fn finish<T>(&self, result: Result<T>) -> Result<T> {
+        if !self.enabled {
+            return result;
+        }
+        let changes = std::mem::take(&mut *self.lock());
+        if result.is_ok() {
+            for (_, backup) in &changes.backups {
+                let _ = fs::remove_file(backup);
+            }
+            return result;
+        }
+
+        for path in changes.created.iter().rev() {
+            match fs::symlink_metadata(path) {
+                Ok(meta) if meta.is_dir() => {
+                    let _ = fs::remove_dir(path);
+                },
+                Ok(_) => {
+                    let _ = fs::remove_file(path);
+                },
+                Err(_) => {},
+            }
+        }
+        for (path, backup) in changes.backups.iter().rev() {
+            let _ = fs::rename(backup, path);
+        }
+        result
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Changes> {
+        self.changes.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Hidden directory inside the output directory that a transactional run
+/// extracts into
+///
+/// The directory and everything in it is removed when the `Staging` is dropped
+/// without being committed, so an early return leaves nothing behind.
+#[derive(Debug)]
+pub struct Staging {
+    output_dir: PathBuf,
+    dir: PathBuf,
+}
+
+impl Staging {
+    /// Create a fresh staging directory in the existing `output_dir`
+    ///
+    /// Being on the same filesystem as the destination, staged files can be moved
+    /// into place with a rename.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be created
+    pub fn create(output_dir: &Path) -> Result<Self> {
+        let dir = output_dir.join(format!("{}{}", STAGING_PREFIX, std::process::id()));
+        fs::create_dir(&dir).map_err(UnzipError::file("create staging directory", &dir))?;
+        Ok(Self { output_dir: output_dir.to_path_buf(), dir })
+    }
+
+    /// The staging directory
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Where to stage the output path `path`; paths outside the output directory
+    /// are returned as they are
+    pub fn path_for(&self, path: &Path) -> PathBuf {
+        match path.strip_prefix(&self.output_dir) {
+            Ok(relative) => self.dir.join(relative),
+            Err(_) => path.to_path_buf(),
+        }
+    }
+
+    /// Move everything staged into the output directory, recording the changes
+    /// in `journal` so that a failed move can be undone
+    ///
+    /// Existing files are replaced; symlinks in their place are replaced rather
+    /// than written through unless `follow_symlinks` is set. Symlinked directories
+    /// are entered only if they stay inside the output directory, or with
+    /// `follow_symlinks`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a file stands where a directory was staged or the other
+    /// way round, a directory symlink leaves the output directory, or a move fails
+    pub fn commit(self, journal: &Journal, follow_symlinks: bool) -> Result<()> {
+        let root = fs::canonicalize(&self.output_dir)
+            .map_err(UnzipError::file("open output directory", &self.output_dir))?;
+        let mover = Mover { root, journal, follow_symlinks };
+        mover.move_dir(&self.dir, &self.output_dir)
+        // Dropping `self` removes the staging directory, empty by now
+    }
+}
+
+impl Drop for Staging {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// State of one [`Staging::commit`]
+struct Mover<'a> {
+    /// Canonical output directory
+    root: PathBuf,
+    journal: &'a Journal,
+    follow_symlinks: bool,
+}
+
+impl Mover<'_> {
+    /// Move the contents of the staged directory `from` into `to`
+    fn move_dir(&self, from: &Path, to: &Path) -> Result<()> {
+        let entries = fs::read_dir(from).map_err(UnzipError::file("read directory", from))?;
+        for entry in entries {
+            let entry = entry.map_err(UnzipError::file("read directory", from))?;
+            let staged = entry.path();
+            let target = to.join(entry.file_name());
+            let file_type = entry.file_type().map_err(UnzipError::file("read directory", from))?;
+            if file_type.is_dir() {
+                let target = self.enter_dir(&target)?;
+                self.move_dir(&staged, &target)?;
+            } else {
+                self.move_file(&staged, &target)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Make sure `target` is a directory to move into, returning its path
+    fn enter_dir(&self, target: &Path) -> Result<PathBuf> {
+        let in_the_way = || UnzipError::file("move into place", target)(collision_error());
+        match fs::symlink_metadata(target) {
+            Err(_) => {
+                self.journal.record_dirs(target);
+                fs::create_dir(target).map_err(UnzipError::file("create directory", target))?;
+                Ok(target.to_path_buf())
+            },
+            Ok(meta) if meta.is_dir() => Ok(target.to_path_buf()),
+            Ok(meta) if meta.is_symlink() => {
+                let resolved = fs::canonicalize(target).map_err(|_| in_the_way())?;
+                if !resolved.is_dir() {
+                    return Err(in_the_way());
+                }
+                if !self.follow_symlinks && !resolved.starts_with(&self.root) {
+                    return Err(UnzipError::file("move into place", target)(escape_error()));
+                }
+                Ok(resolved)
+            },
+            Ok(_) => Err(in_the_way()),
+        }
+    }
+
+    /// Move the staged file `staged` to `target`, replacing what is there
+    fn move_file(&self, staged: &Path, target: &Path) -> Result<()> {
+        let target = match fs::symlink_metadata(target) {
+            Ok(meta) if meta.is_dir() => {
+                return Err(UnzipError::file("move into place", target)(collision_error()));
+            },
+            Ok(meta) if meta.is_symlink() && self.follow_symlinks => {
+                fs::canonicalize(target).unwrap_or_else(|_| target.to_path_buf())
+            },
+            _ => target.to_path_buf(),
+        };
+        self.journal
+            .prepare_file(&target)
+            .map_err(UnzipError::file("back up file", &target))?;
+        fs::rename(staged, &target).map_err(UnzipError::file("move into place", &target))
+    }
+}
+
+fn collision_error() -> io::Error {
+    io::Error::new(io::ErrorKind::AlreadyExists, "a file and a directory have the same path")
+}
+
+/// Hidden name next to `path` that keeps its original while the run lasts
+fn backup_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(BACKUP_SUFFIX);
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rollback_restores_destination() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("keep.txt"), b"old").unwrap();
+
+        let journal = Journal::new(true);
+        journal.record_dirs(&root.join("a/b"));
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        for path in [root.join("a/b/new.txt"), root.join("keep.txt")] {
+            journal.prepare_file(&path).unwrap();
+            fs::write(&path, b"new").unwrap();
+        }
+
+        let result: Result<()> = journal.finish(Err(UnzipError::Cancelled));
+        assert!(matches!(result, Err(UnzipError::Cancelled)));
+        assert!(!root.join("a").exists());
+        assert_eq!(fs::read(root.join("keep.txt")).unwrap(), b"old");
+        assert_eq!(fs::read_dir(root).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_commit_drops_backups() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("keep.txt");
+        fs::write(&path, b"old").unwrap();
+
+        let journal = Journal::new(true);
+        journal.prepare_file(&path).unwrap();
+        fs::write(&path, b"new").unwrap();
+        journal.finish(Ok(())).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_staging_commit_moves_into_place() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir(root.join("docs")).unwrap();
+        fs::write(root.join("docs/old.txt"), b"old").unwrap();
+
+        let staging = Staging::create(root).unwrap();
+        let staged = staging.path_for(&root.join("docs/old.txt"));
+        fs::create_dir_all(staged.parent().unwrap()).unwrap();
+        fs::write(&staged, b"new").unwrap();
+        fs::create_dir_all(staging.path_for(&root.join("a/b"))).unwrap();
+        fs::write(staging.path_for(&root.join("a/b/c.txt")), b"c").unwrap();
+
+        let journal = Journal::new(true);
+        staging.commit(&journal, false).unwrap();
+        journal.finish(Ok(())).unwrap();
+
+        assert_eq!(fs::read(root.join("docs/old.txt")).unwrap(), b"new");
+        assert_eq!(fs::read(root.join("a/b/c.txt")).unwrap(), b"c");
+        assert_eq!(fs::read_dir(root).unwrap().count(), 2);
+        assert_eq!(fs::read_dir(root.join("docs")).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_staging_collision_rolls_back() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("a.txt"), b"old").unwrap();
+        fs::write(root.join("z"), b"a file").unwrap();
+
+        let staging = Staging::create(root).unwrap();
+        fs::write(staging.path_for(&root.join("a.txt")), b"new").unwrap();
+        fs::create_dir(staging.path_for(&root.join("z"))).unwrap();
+        fs::write(staging.path_for(&root.join("z/inner.txt")), b"inner").unwrap();
+
+        let journal = Journal::new(true);
+        let result = journal.finish(staging.commit(&journal, false));
+        assert!(result.unwrap_err().to_string().contains("move into place"));
+        assert_eq!(fs::read(root.join("a.txt")).unwrap(), b"old");
+        assert_eq!(fs::read(root.join("z")).unwrap(), b"a file");
+        assert_eq!(fs::read_dir(root).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_dropped_staging_is_removed() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let staging = Staging::create(temp_dir.path()).unwrap();
+        fs::write(staging.dir().join("partial.bin"), b"data").unwrap();
+        drop(staging);
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_disabled_journal_records_nothing() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("a.txt");
+        fs::write(&path, b"old").unwrap();
+
+        let journal = Journal::new(false);
+        journal.prepare_file(&path).unwrap();
+        let _ = journal.finish::<()>(Err(UnzipError::Cancelled));
+        assert_eq!(fs::read(&path).unwrap(), b"old");
+    }
+}
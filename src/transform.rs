@@ -0,0 +1,194 @@
+//! Path rewriting during extraction (`--transform`)
+//!
+//! `--transform 's/REGEX/REPLACEMENT/FLAGS'` rewrites the output path of every
+//! entry, the way GNU tar's option of the same name does, so files can be
+//! relocated or renamed without a post-processing script. The option can be
+//! repeated; the expressions are applied in order, each to the result of the
+//! previous one.
+//!
+//! Expressions use sed syntax: any character may replace `/` as the delimiter, and
+//! an escaped delimiter stands for itself. In REPLACEMENT, `&` and `\0` are the
+//! whole match and `\1` to `\9` its groups; `\&` is a literal `&`. FLAGS are `g`
+//! to replace every match instead of the first and `i` to ignore case. REGEX uses
+//! the syntax of the [`regex`] crate.
+//!
+//! Transforms see the path after `--strip-components`, `-j` and `-L`, with `/`
+//! separators and a trailing `/` on directories. The result is sanitized again,
+//! so a transform cannot make an entry leave the output directory; an entry whose
+//! path becomes empty is skipped.
+//!
+//! # Examples
+//!
+//! ```
+//! use unzip::transform::{parse_transform, transform_path};
+//!
+//! let transforms = [parse_transform(r"s/^docs\//manual\//")?, parse_transform("s,\\.TXT$,.txt,i")?];
+//! assert_eq!(transform_path(&transforms, "docs/intro.TXT"), "manual/intro.txt");
+//! # Ok::<(), String>(())
+//! ```
+
+use regex::{Regex, RegexBuilder};
+use std::fmt;
+
+/// One `s/REGEX/REPLACEMENT/FLAGS` expression
+#[derive(Debug, Clone)]
+pub struct Transform {
+    expr: String,
+    regex: Regex,
+    /// REPLACEMENT in the syntax of [`Regex::replace`]
+    replacement: String,
+    global: bool,
+}
+
+impl Transform {
+    /// Rewrite `path`, replacing the first match or, with the `g` flag, all of them
+    pub fn apply(&self, path: &str) -> String {
+        if self.global {
+            self.regex.replace_all(path, self.replacement.as_str()).into_owned()
+        } else {
+            self.regex.replace(path, self.replacement.as_str()).into_owned()
+        }
+    }
+}
+
+impl fmt::Display for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expr)
+    }
+}
+
+/// Parse a `--transform` expression, `s/REGEX/REPLACEMENT/FLAGS`
+///
+/// # Errors
+///
+/// Returns a message suitable for clap if the expression is malformed, has an
+/// unknown flag or an invalid regex
+pub fn parse_transform(expr: &str) -> Result<Transform, String> {
+    let malformed = || format!("invalid transform: {} (expected s/REGEX/REPLACEMENT/FLAGS)", expr);
+    let rest = expr.strip_prefix('s').ok_or_else(malformed)?;
+    let delimiter = rest
+        .chars()
+        .next()
+        .filter(|c| !c.is_alphanumeric() && *c != '\\' && *c != '\n')
+        .ok_or_else(malformed)?;
+    let rest = &rest[delimiter.len_utf8()..];
+    let (pattern, rest) = split_part(rest, delimiter).ok_or_else(malformed)?;
+    let (replacement, flags) = split_part(rest, delimiter).ok_or_else(malformed)?;
+
+    let mut global = false;
+    let mut case_insensitive = false;
+    for flag in flags.chars() {
+        match flag {
+            'g' => global = true,
+            'i' => case_insensitive = true,
+            _ => {
+                return Err(format!(
+                    "invalid transform flag '{}' in {} (expected g or i)",
+                    flag, expr
+                ));
+            },
+        }
+    }
+    let regex = RegexBuilder::new(&pattern)
+        .case_insensitive(case_insensitive)
+        .build()
+        .map_err(|error| format!("invalid transform regex in {}: {}", expr, error))?;
+    Ok(Transform {
+        expr: expr.to_string(),
+        regex,
+        replacement: expand_replacement(&replacement),
+        global,
+    })
+}
+
+/// Apply `transforms` to `path` in order
+pub fn transform_path(transforms: &[Transform], path: &str) -> String {
+    transforms
+        .iter()
+        .fold(path.to_string(), |path, transform| transform.apply(&path))
+}
+
+/// Split `text` at the first unescaped `delimiter`, unescaping `\delimiter` in the
+/// part before it; `None` if there is no such delimiter
+fn split_part(text: &str, delimiter: char) -> Option<(String, &str)> {
+    let mut part = String::new();
+    let mut chars = text.char_indices();
+    while let Some((at, c)) = chars.next() {
+        if c == delimiter {
+            return Some((part, &text[at + c.len_utf8()..]));
+        }
+        if c == '\\' {
+            match chars.next() {
+                Some((_, escaped)) if escaped == delimiter => part.push(escaped),
+                Some((_, escaped)) => {
+                    part.push('\\');
+                    part.push(escaped);
+                },
+                None => part.push('\\'),
+            }
+        } else {
+            part.push(c);
+        }
+    }
+    None
+}
+
+/// Turn a sed replacement into the syntax of [`Regex::replace`]
+fn expand_replacement(sed: &str) -> String {
+    let mut replacement = String::new();
+    let mut chars = sed.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(group @ '0'..='9') => {
+                    replacement.push_str("${");
+                    replacement.push(group);
+                    replacement.push('}');
+                },
+                Some('$') => replacement.push_str("$$"),
+                Some(escaped) => replacement.push(escaped),
+                None => replacement.push('\\'),
+            },
+            '&' => replacement.push_str("${0}"),
+            '$' => replacement.push_str("$$"),
+            c => replacement.push(c),
+        }
+    }
+    replacement
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(expr: &str, path: &str) -> String {
+        parse_transform(expr).unwrap().apply(path)
+    }
+
+    #[test]
+    fn test_replacement_syntax() {
+        assert_eq!(apply(r"s/\(v[0-9]\)//", "a/(v1)/b"), "a//b");
+        assert_eq!(apply(r"s,^([^/]*)/(.*)$,\2/\1,", "top/rest/file"), "rest/file/top");
+        assert_eq!(apply("s/o/[&]/g", "foo"), "f[o][o]");
+        assert_eq!(apply(r"s/o/\&$1/", "foo"), "f&$1o");
+        assert_eq!(apply("s|/|_|g", "a/b/c"), "a_b_c");
+        assert_eq!(apply("s/README/readme.md/i", "Readme"), "readme.md");
+        assert_eq!(apply("s/x/y/", "abc"), "abc");
+    }
+
+    #[test]
+    fn test_malformed_expressions() {
+        for expr in ["", "s", "s/a/b", "y/a/b/", "sabac", "s/a/b/q", "s/(/x/"] {
+            assert!(parse_transform(expr).is_err(), "{}", expr);
+        }
+        assert_eq!(parse_transform("s/a/b/g").unwrap().to_string(), "s/a/b/g");
+    }
+
+    #[test]
+    fn test_transforms_apply_in_order() {
+        let transforms =
+            [parse_transform("s/^src/lib/").unwrap(), parse_transform("s/lib/pkg/").unwrap()];
+        assert_eq!(transform_path(&transforms, "src/lib.rs"), "pkg/lib.rs");
+        assert_eq!(transform_path(&[], "src/lib.rs"), "src/lib.rs");
+    }
+}
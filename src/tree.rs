@@ -0,0 +1,228 @@
+//! Tree view of a listing (`--tree`)
+//!
+//! `--tree` draws the entries a listing selects the way the `tree` command does,
+//! each directory with the uncompressed size of everything below it, to see at a
+//! glance where the space of a deep archive goes:
+//!
+//! ```text
+//! [ 14.2M]  site.zip
+//! ├── [ 14.0M]  assets
+//! │   ├── [ 12.0K]  logo.png
+//! │   └── [ 14.0M]  video.mp4
+//! ├── [196.0K]  docs
+//! │   └── [196.0K]  manual.pdf
+//! └── [  3.2K]  index.html
+//!
+//! 2 directories, 3 files
+//! ```
+//!
+//! Directories count whether the archive has entries for them or they are only
+//! implied by the paths below. Entries are in name order; with `--sort` they are
+//! ordered within each directory, directories by their cumulative size and ratio
+//! and by their newest entry. Member patterns, `-x` and the size and date bounds
+//! of [`crate::list_filter`] leave entries out of the tree and of the sizes.
+//!
+//! `--du` summarizes the same tree the way `du` does, to decide which subtrees
+//! are worth extracting: one line per directory down to `--depth N` (1, the
+//! top-level directories, by default), each after the directories below it,
+//! with its uncompressed and compressed sizes, then the archive's total:
+//!
+//! ```text
+//!     Size  Compressed  Directory
+//!    14.0M       13.9M  assets/
+//!   196.0K      150.0K  docs/
+//!    14.2M       14.1M  total
+//! ```
+//!
+//! # Examples
+//!
+//! ```
+//! use unzip::tree::Tree;
+//!
+//! let mut tree = Tree::new();
+//! tree.add("docs/a.txt", 1024, 512, None);
+//! tree.add("README", 10, 10, None);
+//! let mut out = Vec::new();
+//! tree.write(&mut out, "site.zip", None)?;
+//! let out = String::from_utf8(out).unwrap();
+//! assert!(out.starts_with("[  1.0K]  site.zip\n├── [   10B]  README\n"));
+//! assert!(out.ends_with("\n1 directories, 2 files\n"));
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use crate::list_filter::{ListDate, SortKey};
+use crate::utils::format_size;
+
+/// Entries arranged by directory, with cumulative sizes
+#[derive(Debug, Clone, Default)]
+pub struct Tree {
+    root: Node,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Node {
+    children: BTreeMap<String, Node>,
+    is_dir: bool,
+    size: u64,
+    compressed: u64,
+    newest: Option<ListDate>,
+}
+
+impl Node {
+    fn count(&mut self, size: u64, compressed: u64, modified: Option<ListDate>) {
+        self.size += size;
+        self.compressed += compressed;
+        self.newest = self.newest.max(modified);
+    }
+
+    /// Space saved by compression in percent, rounded as the listing's ratio
+    fn ratio(&self) -> u64 {
+        if self.size == 0 || self.compressed >= self.size {
+            return 0;
+        }
+        ((self.size - self.compressed) * 100 + self.size / 2) / self.size
+    }
+}
+
+impl Tree {
+    /// Create an empty tree
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the entry `name`, a directory if it ends in `/`, counting its sizes
+    /// in every directory above it
+    pub fn add(&mut self, name: &str, size: u64, compressed: u64, modified: Option<zip::DateTime>) {
+        let modified = modified.map(ListDate::from_datetime);
+        let mut components: Vec<&str> =
+            name.split('/').filter(|c| !c.is_empty() && *c != ".").collect();
+        let Some(last) = components.pop() else {
+            return;
+        };
+        let mut node = &mut self.root;
+        node.count(size, compressed, modified);
+        for component in components {
+            node = node.children.entry(component.to_string()).or_default();
+            node.is_dir = true;
+            node.count(size, compressed, modified);
+        }
+        let leaf = node.children.entry(last.to_string()).or_default();
+        leaf.is_dir |= name.ends_with('/');
+        leaf.count(size, compressed, modified);
+    }
+
+    /// Summarize the directories down to `depth` like `du`, each after those
+    /// below it, ordering each directory by `sort` (and reversed) if given, and
+    /// end with the total
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to `out` fails
+    pub fn write_du(
+        &self,
+        out: &mut impl Write,
+        depth: usize,
+        sort: Option<(SortKey, bool)>,
+    ) -> io::Result<()> {
+        writeln!(out, "{:>8}  {:>10}  Directory", "Size", "Compressed")?;
+        write_du_dirs(out, &self.root, "", depth, sort)?;
+        let root = &self.root;
+        writeln!(
+            out,
+            "{:>8}  {:>10}  total",
+            format_size(root.size),
+            format_size(root.compressed)
+        )
+    }
+
+    /// Draw the tree under a line for `title`, followed by the directory and
+    /// file counts, ordering each directory by `sort` (and reversed) if given
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to `out` fails
+    pub fn write(
+        &self,
+        out: &mut impl Write,
+        title: &str,
+        sort: Option<(SortKey, bool)>,
+    ) -> io::Result<()> {
+        writeln!(out, "[{:>6}]  {}", format_size(self.root.size), title)?;
+        let (mut dirs, mut files) = (0, 0);
+        write_children(out, &self.root, "", sort, &mut dirs, &mut files)?;
+        writeln!(out)?;
+        writeln!(out, "{} directories, {} files", dirs, files)
+    }
+}
+
+/// Draw the children of `node`, each line after `prefix`
+fn write_children(
+    out: &mut impl Write,
+    node: &Node,
+    prefix: &str,
+    sort: Option<(SortKey, bool)>,
+    dirs: &mut usize,
+    files: &mut usize,
+) -> io::Result<()> {
+    let children = sorted_children(node, sort);
+    let last = children.len().saturating_sub(1);
+    for (i, (name, child)) in children.into_iter().enumerate() {
+        let (branch, indent) = if i == last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        writeln!(out, "{}{}[{:>6}]  {}", prefix, branch, format_size(child.size), name)?;
+        if child.is_dir {
+            *dirs += 1;
+            write_children(out, child, &format!("{}{}", prefix, indent), sort, dirs, files)?;
+        } else {
+            *files += 1;
+        }
+    }
+    Ok(())
+}
+
+/// Write a `--du` line for each directory below `node` at `path`, down to
+/// `depth` more levels, subdirectories first
+fn write_du_dirs(
+    out: &mut impl Write,
+    node: &Node,
+    path: &str,
+    depth: usize,
+    sort: Option<(SortKey, bool)>,
+) -> io::Result<()> {
+    if depth == 0 {
+        return Ok(());
+    }
+    for (name, child) in sorted_children(node, sort) {
+        if !child.is_dir {
+            continue;
+        }
+        let path = format!("{}{}/", path, name);
+        write_du_dirs(out, child, &path, depth - 1, sort)?;
+        let (size, compressed) = (format_size(child.size), format_size(child.compressed));
+        writeln!(out, "{:>8}  {:>10}  {}", size, compressed, path)?;
+    }
+    Ok(())
+}
+
+/// The children of `node` in name order, or ordered by `sort` (and reversed)
+fn sorted_children(node: &Node, sort: Option<(SortKey, bool)>) -> Vec<(&String, &Node)> {
+    let mut children: Vec<(&String, &Node)> = node.children.iter().collect();
+    if let Some((key, reverse)) = sort {
+        children.sort_by(|(a_name, a), (b_name, b)| {
+            let order = match key {
+                SortKey::Name => a_name.cmp(b_name),
+                SortKey::Size => a.size.cmp(&b.size),
+                SortKey::Ratio => a.ratio().cmp(&b.ratio()),
+                SortKey::Mtime => a.newest.cmp(&b.newest),
+            };
+            if reverse { order.reverse() } else { order }
+        });
+    }
+    children
+}
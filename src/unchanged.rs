@@ -0,0 +1,61 @@
+//! Content-based updates that leave identical files alone (`--if-changed`)
+//!
+//! `-f` and `-u` decide by modification time, which says little once an archive
+//! has been built on another machine, checked out from version control or copied
+//! without preserving times. With `--if-changed`, an existing file is compared
+//! with the entry by content instead: if its size and CRC32 match those the
+//! archive records, the file is left untouched and the entry is skipped as
+//! [`SkipReason::Unchanged`](crate::events::SkipReason::Unchanged); otherwise it
+//! is replaced. Missing files are created.
+//!
+//! Re-deploying a mostly unchanged payload then costs a read of each existing
+//! file, and writes only what differs; file times and permissions of skipped
+//! files are not touched either. A size mismatch is decided from metadata alone.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use unzip::unchanged::is_unchanged;
+//!
+//! std::fs::write("config.toml", b"port = 80\n")?;
+//! assert!(is_unchanged("config.toml".as_ref(), 10, crc32fast::hash(b"port = 80\n"))?);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use crate::verify::crc32_of;
+
+/// Whether the file at `path` holds `size` bytes with CRC32 `crc32`
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be opened or read
+pub fn is_unchanged(path: &Path, size: u64, crc32: u32) -> io::Result<bool> {
+    let mut file = File::open(path)?;
+    if file.metadata()?.len() != size {
+        return Ok(false);
+    }
+    Ok(crc32_of(&mut file)? == crc32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_is_unchanged() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("data.bin");
+        fs::write(&path, b"payload").unwrap();
+        let crc32 = crc32fast::hash(b"payload");
+
+        assert!(is_unchanged(&path, 7, crc32).unwrap());
+        assert!(!is_unchanged(&path, 8, crc32).unwrap());
+        assert!(!is_unchanged(&path, 7, crc32fast::hash(b"PAYLOAD")).unwrap());
+        assert!(is_unchanged(&temp_dir.path().join("missing"), 7, crc32).is_err());
+    }
+}
@@ -0,0 +1,347 @@
+//! `find -ls` and `ls -lR` listings (`--format find`, `--format ls-lR`)
+//!
+//! Mirror indexers, `ls-lR` search tools and scripts written for `find . -ls`
+//! read these listings as if the archive were a directory unpacked in `.`:
+//!
+//! ```text
+//! $ unzip -l --format find site.zip
+//!         1      0 drwxr-xr-x   2 0        0               0 Mar 15  2024 ./docs
+//!         2      1 -rw-r--r--   1 0        0             900 Mar 15  2024 ./docs/readme.txt
+//! $ unzip -l --format ls-lR site.zip
+//! .:
+//! total 0
+//! drwxr-xr-x 2 0 0 0 Mar 15  2024 docs
+//!
+//! ./docs:
+//! total 1
+//! -rw-r--r-- 1 0 0 900 Mar 15  2024 readme.txt
+//! ```
+//!
+//! An archive has no inodes, owners or link counts: `find` lines number the
+//! entries in the order they are listed, owners and groups are 0, and
+//! directories have 2 links, everything else 1. Sizes are the uncompressed
+//! sizes the archive records, and blocks count them in 1 KiB units. Dates are
+//! shown as `ls` shows them, with the time if they are within six months before
+//! now and with the year otherwise. Symbolic links are marked as such by their
+//! mode, without the target, which is in the entry's data.
+//!
+//! `ls -lR` lists each directory in name order, the directories the archive
+//! only implies by the paths below them with mode `drwxr-xr-x` and the DOS
+//! epoch as their date; `find` lists the entries in archive order, or as
+//! `--sort` orders them.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::time::SystemTime;
+//! use unzip::unix_listing::LsTree;
+//!
+//! let mut tree = LsTree::new();
+//! tree.add("docs/a.txt", 0o100644, 1500, None);
+//! let mut out = Vec::new();
+//! tree.write(&mut out, SystemTime::now())?;
+//! let out = String::from_utf8(out).unwrap();
+//! assert!(out.starts_with(".:\ntotal 0\ndrwxr-xr-x 2 0 0 0 Jan  1  1980 docs\n"));
+//! assert!(out.ends_with("./docs:\ntotal 2\n-rw-r--r-- 1 0 0 1500 Jan  1  1980 a.txt\n"));
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::time::{Duration, SystemTime};
+
+use crate::utils::datetime_to_system_time;
+
+/// File type bits of a Unix mode
+const S_IFMT: u32 = 0o170000;
+const S_IFSOCK: u32 = 0o140000;
+const S_IFLNK: u32 = 0o120000;
+const S_IFBLK: u32 = 0o060000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFCHR: u32 = 0o020000;
+const S_IFIFO: u32 = 0o010000;
+
+/// Mode of directories that have no entry of their own
+const IMPLIED_DIR_MODE: u32 = S_IFDIR | 0o755;
+
+/// How far back `ls` shows the time of day instead of the year: half of an
+/// average Gregorian year
+const RECENT: Duration = Duration::from_secs(31_556_952 / 2);
+
+/// `mode` the way `ls -l` prints it, such as `drwxr-xr-x` or `-rwsr-xr-x`
+///
+/// # Examples
+///
+/// ```
+/// use unzip::unix_listing::mode_string;
+///
+/// assert_eq!(mode_string(0o100644), "-rw-r--r--");
+/// assert_eq!(mode_string(0o041777), "drwxrwxrwt");
+/// assert_eq!(mode_string(0o120777), "lrwxrwxrwx");
+/// ```
+pub fn mode_string(mode: u32) -> String {
+    let mut text = String::with_capacity(10);
+    text.push(match mode & S_IFMT {
+        S_IFDIR => 'd',
+        S_IFLNK => 'l',
+        S_IFIFO => 'p',
+        S_IFCHR => 'c',
+        S_IFBLK => 'b',
+        S_IFSOCK => 's',
+        _ => '-',
+    });
+    for (shift, special, marker) in [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')] {
+        let bits = mode >> shift;
+        text.push(if bits & 4 != 0 { 'r' } else { '-' });
+        text.push(if bits & 2 != 0 { 'w' } else { '-' });
+        text.push(match (mode & special != 0, bits & 1 != 0) {
+            (true, true) => marker,
+            (true, false) => marker.to_ascii_uppercase(),
+            (false, true) => 'x',
+            (false, false) => '-',
+        });
+    }
+    text
+}
+
+/// `modified` the way `ls -l` prints it relative to `now`: `Mar 15 12:34`
+/// within six months before `now`, `Mar 15  2024` otherwise, and the DOS epoch
+/// if the entry has no date
+pub fn ls_time(modified: Option<zip::DateTime>, now: SystemTime) -> String {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let dt = modified.unwrap_or_default();
+    let month = MONTHS[usize::from(dt.month().clamp(1, 12)) - 1];
+    let recent = now.duration_since(datetime_to_system_time(dt)).is_ok_and(|age| age < RECENT);
+    if recent {
+        format!("{} {:>2} {:02}:{:02}", month, dt.day(), dt.hour(), dt.minute())
+    } else {
+        format!("{} {:>2}  {}", month, dt.day(), dt.year())
+    }
+}
+
+/// Write the `find -ls` line of the entry `name`, numbered `inode`
+///
+/// # Errors
+///
+/// Returns an error if writing to `out` fails
+pub fn write_find_line(
+    out: &mut impl Write,
+    inode: u64,
+    name: &str,
+    mode: u32,
+    size: u64,
+    modified: Option<zip::DateTime>,
+    now: SystemTime,
+) -> io::Result<()> {
+    writeln!(
+        out,
+        "{:>9} {:>6} {} {:>3} {:<8} {:<8} {:>8} {} ./{}",
+        inode,
+        blocks(size),
+        mode_string(mode),
+        links(mode),
+        0,
+        0,
+        size,
+        ls_time(modified, now),
+        name.trim_matches('/')
+    )
+}
+
+/// 1 KiB blocks of `size` bytes, as `ls` and `find` count them
+fn blocks(size: u64) -> u64 {
+    size.div_ceil(1024)
+}
+
+/// Link count shown for an entry: 2 for directories, 1 for anything else
+fn links(mode: u32) -> u32 {
+    if mode & S_IFMT == S_IFDIR { 2 } else { 1 }
+}
+
+/// Entries arranged by directory, for an `ls -lR` listing
+#[derive(Debug, Clone, Default)]
+pub struct LsTree {
+    /// Entries of each directory by name, the top level under `""`
+    dirs: BTreeMap<String, BTreeMap<String, LsEntry>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct LsEntry {
+    mode: u32,
+    size: u64,
+    modified: Option<zip::DateTime>,
+}
+
+impl LsTree {
+    /// Create an empty tree
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the entry `name`, a directory if it ends in `/` or `mode` says so,
+    /// along with the directories above it that have no entry yet
+    pub fn add(&mut self, name: &str, mode: u32, size: u64, modified: Option<zip::DateTime>) {
+        let mut components: Vec<&str> =
+            name.split('/').filter(|c| !c.is_empty() && *c != ".").collect();
+        let Some(last) = components.pop() else {
+            return;
+        };
+        let mut dir = String::new();
+        for component in components {
+            let implied = LsEntry { mode: IMPLIED_DIR_MODE, size: 0, modified: None };
+            self.dirs
+                .entry(dir.clone())
+                .or_default()
+                .entry(component.to_string())
+                .or_insert(implied);
+            dir = join(&dir, component);
+        }
+        let mode = if name.ends_with('/') && mode & S_IFMT != S_IFDIR {
+            S_IFDIR | (mode & 0o7777)
+        } else {
+            mode
+        };
+        self.dirs
+            .entry(dir.clone())
+            .or_default()
+            .insert(last.to_string(), LsEntry { mode, size, modified });
+        if mode & S_IFMT == S_IFDIR {
+            self.dirs.entry(join(&dir, last)).or_default();
+        }
+    }
+
+    /// Write the listing the way `ls -lR` in the unpacked archive would, with
+    /// dates relative to `now`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to `out` fails
+    pub fn write(&self, out: &mut impl Write, now: SystemTime) -> io::Result<()> {
+        self.write_dir(out, "", now)
+    }
+
+    fn write_dir(&self, out: &mut impl Write, dir: &str, now: SystemTime) -> io::Result<()> {
+        let empty = BTreeMap::new();
+        let entries = self.dirs.get(dir).unwrap_or(&empty);
+        if dir.is_empty() {
+            writeln!(out, ".:")?;
+        } else {
+            writeln!(out, "\n./{}:", dir)?;
+        }
+        writeln!(out, "total {}", entries.values().map(|e| blocks(e.size)).sum::<u64>())?;
+        let width = entries.values().map(|e| e.size.to_string().len()).max().unwrap_or(0);
+        for (name, entry) in entries {
+            writeln!(
+                out,
+                "{} {} 0 0 {:>width$} {} {}",
+                mode_string(entry.mode),
+                links(entry.mode),
+                entry.size,
+                ls_time(entry.modified, now),
+                name
+            )?;
+        }
+        for name in entries.keys() {
+            let path = join(dir, name);
+            if self.dirs.contains_key(&path) {
+                self.write_dir(out, &path, now)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Path of `name` inside `dir`, `""` being the top level
+fn join(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", dir, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: u16, month: u8, day: u8, hour: u8, minute: u8) -> zip::DateTime {
+        zip::DateTime::from_date_and_time(year, month, day, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_mode_string() {
+        assert_eq!(mode_string(0o040755), "drwxr-xr-x");
+        assert_eq!(mode_string(0o104755), "-rwsr-xr-x");
+        assert_eq!(mode_string(0o102644), "-rw-r-Sr--");
+        assert_eq!(mode_string(0o041776), "drwxrwxrwT");
+        assert_eq!(mode_string(0o010600), "prw-------");
+    }
+
+    #[test]
+    fn test_ls_time_shows_year_after_six_months() {
+        let now = datetime_to_system_time(date(2024, 9, 1, 12, 0));
+        assert_eq!(ls_time(Some(date(2024, 8, 5, 9, 7)), now), "Aug  5 09:07");
+        assert_eq!(ls_time(Some(date(2024, 2, 15, 9, 7)), now), "Feb 15  2024");
+        // Dates after now are shown with their year, as ls does
+        assert_eq!(ls_time(Some(date(2024, 9, 2, 9, 7)), now), "Sep  2  2024");
+        assert_eq!(ls_time(None, now), "Jan  1  1980");
+    }
+
+    #[test]
+    fn test_find_line() {
+        let now = datetime_to_system_time(date(2030, 1, 1, 0, 0));
+        let mut out = Vec::new();
+        write_find_line(&mut out, 3, "docs/", 0o040755, 0, Some(date(2024, 3, 15, 12, 34)), now)
+            .unwrap();
+        write_find_line(&mut out, 4, "docs/a b.txt", 0o100600, 1025, None, now).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "        3      0 drwxr-xr-x   2 0        0               0 Mar 15  2024 ./docs\n\
+             \x20       4      2 -rw-------   1 0        0            1025 Jan  1  1980 ./docs/a b.txt\n"
+        );
+    }
+
+    #[test]
+    fn test_ls_tree_lists_directories_depth_first() {
+        let now = datetime_to_system_time(date(2030, 1, 1, 0, 0));
+        let modified = Some(date(2024, 3, 15, 12, 34));
+        let mut tree = LsTree::new();
+        tree.add("b.txt", 0o100644, 2048, modified);
+        tree.add("a/", 0o040700, 0, modified);
+        tree.add("a/z/deep.txt", 0o100644, 10, modified);
+        tree.add("a/y.txt", 0o100755, 100, modified);
+        tree.add("a.b/c", 0o120777, 5, modified);
+        let mut out = Vec::new();
+        tree.write(&mut out, now).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            ".:\n\
+             total 2\n\
+             drwx------ 2 0 0    0 Mar 15  2024 a\n\
+             drwxr-xr-x 2 0 0    0 Jan  1  1980 a.b\n\
+             -rw-r--r-- 1 0 0 2048 Mar 15  2024 b.txt\n\
+             \n\
+             ./a:\n\
+             total 1\n\
+             -rwxr-xr-x 1 0 0 100 Mar 15  2024 y.txt\n\
+             drwxr-xr-x 2 0 0   0 Jan  1  1980 z\n\
+             \n\
+             ./a/z:\n\
+             total 1\n\
+             -rw-r--r-- 1 0 0 10 Mar 15  2024 deep.txt\n\
+             \n\
+             ./a.b:\n\
+             total 1\n\
+             lrwxrwxrwx 1 0 0 5 Mar 15  2024 c\n"
+        );
+    }
+
+    #[test]
+    fn test_ls_tree_empty() {
+        let mut out = Vec::new();
+        LsTree::new().write(&mut out, SystemTime::now()).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), ".:\ntotal 0\n");
+    }
+}
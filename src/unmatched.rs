@@ -0,0 +1,168 @@
+//! Member patterns that select nothing (exit code 11)
+//!
+//! Automation tells "the archive had nothing for me" from success by Info-ZIP's
+//! exit code 11. Before extracting, listing (`-l`, `-v`) or testing (`-t`) with
+//! member patterns or `-x`, the entry names are read from the central directory
+//! and each pattern is checked for an entry it matches, excluded or not:
+//!
+//! - If no entry is selected at all, nothing is done: the run prints
+//!   `caution: filename not matched:  PATTERN` for each pattern and fails with
+//!   "no files matched"
+//! - Otherwise the command runs on the selected entries, and then the same
+//!   caution follows for each pattern that matched nothing before the run fails
+//!
+//! Either way the exit code is 11, also without `--compat=infozip`. Runs with
+//! `--members-from` or `--member-index` are left to those checks (see
+//! [`crate::members`]).
+//!
+//! # Examples
+//!
+//! ```
+//! use unzip::glob::Wildcards;
+//! use unzip::unmatched::find_unmatched;
+//!
+//! let patterns = vec!["*.txt".to_string(), "*.md".to_string()];
+//! let stop = Wildcards::StopAtDir;
+//! let unmatched = find_unmatched(["a.txt", "b.rs"], &patterns, &[], false, stop).unwrap();
+//! assert_eq!(unmatched.patterns, ["*.md"]);
+//! assert_eq!(unmatched.selected, 1);
+//! assert!(find_unmatched(["a.txt", "b.md"], &patterns, &[], false, stop).is_none());
+//! ```
+
+use std::fmt;
+use std::io::{Read, Seek};
+
+use crate::args::Args;
+use crate::central::CentralDirectory;
+use crate::charset::NameDecoder;
+use crate::error::Result;
+use crate::glob::Wildcards;
+use crate::utils::PatternMatcher;
+
+/// Member patterns that matched no entry, or a selection left empty by `-x`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unmatched {
+    /// The patterns that matched no entry, in command line order
+    pub patterns: Vec<String>,
+    /// Number of entries selected by the patterns and `-x` together
+    pub selected: usize,
+}
+
+impl Unmatched {
+    /// Info-ZIP's caution lines, one per pattern
+    pub fn cautions(&self) -> impl Iterator<Item = String> + '_ {
+        self.patterns
+            .iter()
+            .map(|pattern| format!("caution: filename not matched:  {}", pattern))
+    }
+}
+
+impl fmt::Display for Unmatched {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.selected == 0 {
+            write!(f, "no files matched")
+        } else {
+            write!(f, "{} of the member patterns matched no files", self.patterns.len())
+        }
+    }
+}
+
+impl std::error::Error for Unmatched {}
+
+/// The patterns that match none of `names`, or `None` when each matches one and
+/// the selection is not empty
+///
+/// A selection left empty by `exclude` counts as unmatched too, with no
+/// patterns to name.
+pub fn find_unmatched<'n>(
+    names: impl IntoIterator<Item = &'n str>,
+    patterns: &[String],
+    exclude: &[String],
+    case_insensitive: bool,
+    wildcards: Wildcards,
+) -> Option<Unmatched> {
+    let matcher =
+        PatternMatcher::new(patterns, exclude, case_insensitive).with_wildcards(wildcards);
+    let mut matched = vec![false; patterns.len()];
+    let mut selected = 0;
+    for name in names {
+        if matcher.selects(name) {
+            selected += 1;
+        }
+        matcher.mark_matched(name, &mut matched);
+    }
+    let unmatched: Vec<String> = (patterns.iter().zip(matched))
+        .filter(|(_, matched)| !matched)
+        .map(|(pattern, _)| pattern.clone())
+        .collect();
+    (!unmatched.is_empty() || selected == 0).then_some(Unmatched { patterns: unmatched, selected })
+}
+
+/// [`find_unmatched`] for the patterns of `args` and the entries of the archive
+/// `reader` holds, read from its central directory
+///
+/// Returns `None` without patterns or `-x`, and with `--members-from` or
+/// `--member-index`.
+///
+/// # Errors
+///
+/// Returns an error if the central directory cannot be read or the names cannot
+/// be decoded with the requested charset
+pub fn unmatched_patterns<R: Read + Seek>(reader: R, args: &Args) -> Result<Option<Unmatched>> {
+    let filtered = !args.patterns.is_empty() || !args.exclude.is_empty();
+    if !filtered || args.members_from.is_some() || args.member_index.is_some() {
+        return Ok(None);
+    }
+    let decoder = NameDecoder::from_args(args)?;
+    let mut names = Vec::new();
+    for record in CentralDirectory::open(reader)? {
+        let record = record?;
+        names.push(decoder.decode_raw(&record.name, record.is_utf8, record.host()).into_owned());
+    }
+    let names = names.iter().map(String::as_str);
+    let wildcards = args.wildcards();
+    Ok(find_unmatched(
+        names,
+        &args.patterns,
+        &args.exclude,
+        args.case_insensitive,
+        wildcards,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_find_unmatched() {
+        let names = ["docs/", "docs/a.txt", "docs/b.log", "src/main.rs"];
+        let find = |patterns: &[&str], exclude: &[&str], case_insensitive| {
+            let (patterns, exclude) = (strings(patterns), strings(exclude));
+            find_unmatched(names, &patterns, &exclude, case_insensitive, Wildcards::StopAtDir)
+        };
+
+        let unmatched = find(&["docs/*", "*.md", "SRC/*"], &[], false).unwrap();
+        assert_eq!(unmatched, Unmatched { patterns: strings(&["*.md", "SRC/*"]), selected: 3 });
+        let cautions: Vec<String> = unmatched.cautions().collect();
+        assert_eq!(cautions[0], "caution: filename not matched:  *.md");
+        assert_eq!(unmatched.to_string(), "2 of the member patterns matched no files");
+
+        // Excluded entries still count as matched by their pattern
+        let unmatched = find(&["docs/*"], &["docs/*"], false);
+        assert_eq!(unmatched.unwrap(), Unmatched { patterns: Vec::new(), selected: 0 });
+
+        let unmatched = find(&["*.MD", "DOCS/*.TXT"], &[], true).unwrap();
+        assert_eq!(unmatched.to_string(), "1 of the member patterns matched no files");
+        assert!(find(&["src/*.rs", "docs"], &[], false).is_none());
+        assert!(find(&[], &["*.md"], false).is_none());
+
+        // Without -W under --compat=infozip, `*` reaches into directories
+        let patterns = strings(&["*.txt"]);
+        assert!(find_unmatched(names, &patterns, &[], false, Wildcards::MatchDir).is_none());
+    }
+}
@@ -25,8 +25,10 @@
 //! assert!(should_extract("file.txt", &includes, &excludes, false));
 //! ```
 
-use crate::glob::glob_match;
+use crate::glob::{Wildcards, glob_match_with};
+use crate::members::is_member;
 use filetime::FileTime;
+use std::borrow::Cow;
 use std::time::SystemTime;
 
 /// Format a byte size as a human-readable string with appropriate units.
@@ -115,86 +117,177 @@ pub fn should_extract(
 }
 
 pub(crate) struct PatternMatcher<'a> {
-    patterns: &'a [String],
-    exclude: &'a [String],
-    patterns_ci: Option<Vec<String>>,
-    exclude_ci: Option<Vec<String>>,
+    /// Inclusion and exclusion patterns, lowercased with `-C`
+    patterns: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    /// Exact entry names, sorted (`--members-from`)
+    members: &'a [String],
     case_insensitive: bool,
+    wildcards: Wildcards,
 }
 
 impl<'a> PatternMatcher<'a> {
-    pub(crate) fn new(
-        patterns: &'a [String],
-        exclude: &'a [String],
-        case_insensitive: bool,
-    ) -> Self {
-        let patterns_ci = if case_insensitive {
-            Some(patterns.iter().map(|p| p.to_lowercase()).collect())
-        } else {
-            None
-        };
-        let exclude_ci = if case_insensitive {
-            Some(exclude.iter().map(|p| p.to_lowercase()).collect())
-        } else {
-            None
+    pub(crate) fn new(patterns: &[String], exclude: &[String], case_insensitive: bool) -> Self {
+        // Patterns are prepared once here, names normalized once per check
+        let compile = |patterns: &[String]| {
+            (patterns.iter())
+                .map(|pattern| Pattern::new(pattern, case_insensitive))
+                .collect()
         };
         Self {
-            patterns,
-            exclude,
-            patterns_ci,
-            exclude_ci,
+            patterns: compile(patterns),
+            exclude: compile(exclude),
+            members: &[],
             case_insensitive,
+            wildcards: Wildcards::default(),
         }
     }
 
+    /// Also select the entries named exactly as one of the sorted `members`,
+    /// regardless of case-insensitive matching
+    pub(crate) fn with_members(mut self, members: &'a [String]) -> Self {
+        self.members = members;
+        self
+    }
+
+    /// Let `*` and `?` match `/` or not, as `wildcards` says (`-W`)
+    pub(crate) fn with_wildcards(mut self, wildcards: Wildcards) -> Self {
+        self.wildcards = wildcards;
+        self
+    }
+
     pub(crate) fn should_extract(&self, name: &str) -> bool {
-        if self.patterns.is_empty() && self.exclude.is_empty() {
+        if self.selects_all() {
             return true;
         }
+        let normalized = self.normalize(name);
+        !self.excluded(&normalized) && (is_member(self.members, name) || self.included(&normalized))
+    }
 
-        if self.case_insensitive {
-            let name_cmp = name.to_lowercase();
-            let exclude = self.exclude_ci.as_deref().unwrap_or(&[]);
-            for pattern in exclude {
-                if glob_match(pattern, &name_cmp) {
-                    return false;
-                }
-            }
+    /// Whether a directory entry is selected
+    ///
+    /// The name is matched both with and without its trailing `/`, since `*` does
+    /// not match `/`: `docs/*` selects `docs/empty/`, and `-x cache` leaves out
+    /// `cache/`.
+    pub(crate) fn should_extract_dir(&self, name: &str) -> bool {
+        if self.selects_all() {
+            return true;
+        }
+        let member = is_member(self.members, name);
+        let name = self.normalize(name);
+        let bare = name.trim_end_matches('/');
+        !self.excluded(&name)
+            && !self.excluded(bare)
+            && (member || self.included(&name) || self.included(bare))
+    }
 
-            let patterns = self.patterns_ci.as_deref().unwrap_or(&[]);
-            if patterns.is_empty() {
-                return true;
-            }
+    /// Whether the entry named `name` is selected, as a directory if the name
+    /// ends with `/`
+    pub(crate) fn selects(&self, name: &str) -> bool {
+        if name.ends_with('/') {
+            self.should_extract_dir(name)
+        } else {
+            self.should_extract(name)
+        }
+    }
 
-            for pattern in patterns {
-                if glob_match(pattern, &name_cmp) {
-                    return true;
-                }
-            }
+    /// Set `matched[i]` for each pattern `i` that matches the entry named `name`,
+    /// as a directory if the name ends with `/`, whether excluded or not
+    pub(crate) fn mark_matched(&self, name: &str, matched: &mut [bool]) {
+        let name = self.normalize(name);
+        let bare = name.strip_suffix('/');
+        for (pattern, matched) in self.patterns.iter().zip(matched) {
+            *matched = *matched
+                || pattern.matches(&name, self.wildcards)
+                || bare.is_some_and(|bare| pattern.matches(bare, self.wildcards));
+        }
+    }
+
+    fn selects_all(&self) -> bool {
+        self.patterns.is_empty() && self.exclude.is_empty() && self.members.is_empty()
+    }
 
-            false
+    fn normalize<'n>(&self, name: &'n str) -> Cow<'n, str> {
+        if self.case_insensitive {
+            lowercase(name)
         } else {
-            for pattern in self.exclude {
-                if glob_match(pattern, name) {
-                    return false;
-                }
-            }
+            Cow::Borrowed(name)
+        }
+    }
 
-            if self.patterns.is_empty() {
-                return true;
-            }
+    fn excluded(&self, name: &str) -> bool {
+        self.exclude.iter().any(|pattern| pattern.matches(name, self.wildcards))
+    }
 
-            for pattern in self.patterns {
-                if glob_match(pattern, name) {
-                    return true;
-                }
-            }
+    fn included(&self, name: &str) -> bool {
+        (self.patterns.is_empty() && self.members.is_empty())
+            || self.patterns.iter().any(|pattern| pattern.matches(name, self.wildcards))
+    }
+}
+
+/// A member or `-x` pattern, with what a name must look like to match it worked
+/// out once: most patterns are then ruled out by a byte comparison
+struct Pattern {
+    text: String,
+    shape: Shape,
+}
+
+enum Shape {
+    /// No wildcards: the name must be the pattern
+    Exact,
+    /// A `*` followed by no wildcards, such as `*.txt`: the name must end with
+    /// the rest
+    Suffix,
+    /// Anything else: the name must start with the first `literal` bytes, the
+    /// text before the first wildcard
+    Glob { literal: usize },
+}
 
-            false
+impl Pattern {
+    fn new(pattern: &str, case_insensitive: bool) -> Self {
+        let text = if case_insensitive {
+            lowercase(pattern).into_owned()
+        } else {
+            pattern.to_string()
+        };
+        let literal = text.find(['*', '?']);
+        let shape = match literal {
+            None => Shape::Exact,
+            Some(0) if text.starts_with('*') && !text[1..].contains(['*', '?']) => Shape::Suffix,
+            Some(literal) => Shape::Glob { literal },
+        };
+        Self { text, shape }
+    }
+
+    /// Whether the normalized `name` matches
+    fn matches(&self, name: &str, wildcards: Wildcards) -> bool {
+        match self.shape {
+            Shape::Exact => name == self.text,
+            Shape::Suffix => name
+                .strip_suffix(&self.text[1..])
+                .is_some_and(|star| wildcards == Wildcards::MatchDir || !star.contains('/')),
+            Shape::Glob { literal } => {
+                name.as_bytes().starts_with(&self.text.as_bytes()[..literal])
+                    && glob_match_with(&self.text, name, wildcards)
+            },
         }
     }
 }
 
+/// `text` in lowercase, borrowed when it already is
+///
+/// Entry names and patterns are mostly lowercase ASCII, which `-C` and `-L` then
+/// need neither to copy nor to run through Unicode case mapping.
+pub(crate) fn lowercase(text: &str) -> Cow<'_, str> {
+    if !text.is_ascii() {
+        Cow::Owned(text.to_lowercase())
+    } else if text.bytes().any(|b| b.is_ascii_uppercase()) {
+        Cow::Owned(text.to_ascii_lowercase())
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
 /// Convert ZIP DateTime format to Rust SystemTime.
 ///
 /// Converts the date/time format used in ZIP archives (year, month, day, hour,
@@ -384,4 +477,52 @@ mod tests {
         assert!(should_extract("file.txt", &patterns, &exclude, false));
         assert!(!should_extract("secret.txt", &patterns, &exclude, false));
     }
+
+    #[test]
+    fn test_should_extract_dir_ignores_trailing_slash() {
+        let patterns = vec!["docs/*".to_string()];
+        let exclude = vec!["docs/cache".to_string()];
+        let matcher = PatternMatcher::new(&patterns, &exclude, false);
+        assert!(matcher.should_extract_dir("docs/empty/"));
+        assert!(!matcher.should_extract_dir("docs/cache/"));
+        assert!(!matcher.should_extract_dir("src/"));
+
+        let exclude = vec!["BUILD/".to_string()];
+        let matcher = PatternMatcher::new(&[], &exclude, true);
+        assert!(!matcher.should_extract_dir("build/"));
+        assert!(matcher.should_extract_dir("src/"));
+    }
+
+    #[test]
+    fn test_mark_matched_with_literal_prefixes() {
+        let patterns: Vec<String> = ["Docs/*.TXT", "docs/a?c", "src", "**/*.rs"]
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        let matcher = PatternMatcher::new(&patterns, &[], true);
+        let mut matched = [false; 4];
+        for name in ["DOCS/readme.txt", "docs/abd", "SRC/", "lib/x.rs"] {
+            matcher.mark_matched(name, &mut matched);
+        }
+        assert_eq!(matched, [true, false, true, true]);
+        assert!(matcher.should_extract("docs/ABC"));
+        assert!(!matcher.should_extract("doc/abc"));
+        assert_eq!(lowercase("docs/a.txt"), Cow::Borrowed("docs/a.txt"));
+        assert_eq!(lowercase("Ä/B.TXT"), "ä/b.txt");
+    }
+
+    #[test]
+    fn test_pattern_shapes_match_as_globs() {
+        let patterns = ["a.txt", "*.txt", "*/a.txt", "*", "*a*", "docs/*", "**/*.rs", "?.txt"];
+        let names = ["a.txt", "b/a.txt", "c/b/a.txt", ".txt", "a.txt.bak", "docs/x", "x.rs", ""];
+        for wildcards in [Wildcards::StopAtDir, Wildcards::MatchDir] {
+            for pattern in patterns {
+                let compiled = Pattern::new(pattern, false);
+                for name in names {
+                    let expected = glob_match_with(pattern, name, wildcards);
+                    assert_eq!(compiled.matches(name, wildcards), expected, "{pattern} {name}");
+                }
+            }
+        }
+    }
 }
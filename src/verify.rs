@@ -0,0 +1,184 @@
+//! Read-back verification of extracted files (`--verify`)
+//!
+//! The CRC32 check while decompressing proves the archive data is intact, not that
+//! it reached the disk intact. With `--verify`, each extracted file is flushed to
+//! the storage device, dropped from the page cache and read back, and the CRC32 of
+//! what comes back is compared with that of the data written. Silent corruption on
+//! the way to the disk, as seen on failing drives or controllers during large
+//! migrations, then fails the extraction instead of going unnoticed.
+//!
+//! Files are read back through a [`CrcChecker`], which hashes data as it streams
+//! through one fixed buffer; `-t` checks entries with it too, so neither holds
+//! more than the buffer of a file in memory, however large it is.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use unzip::verify::verify_file;
+//!
+//! std::fs::write("out.txt", b"hello")?;
+//! verify_file("out.txt".as_ref(), crc32fast::hash(b"hello"))?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::error::{Result, UnzipError};
+use crate::linux::fadvise_dontneed;
+
+/// Buffer size for reading files back
+const BUFFER_SIZE: usize = 256 * 1024;
+
+/// Streaming CRC32 check: data is read through one fixed buffer and hashed as
+/// it goes, so any amount of it takes the same memory
+///
+/// One checker can check many files or entries in turn; [`CrcChecker::finish`]
+/// ends one and starts the next.
+///
+/// # Examples
+///
+/// ```
+/// use unzip::verify::CrcChecker;
+///
+/// let data = vec![7u8; 1 << 20];
+/// let mut checker = CrcChecker::new();
+/// let mut reader = &data[..];
+/// while !checker.next_chunk(&mut reader)?.is_empty() {}
+/// assert_eq!(checker.finish(), (crc32fast::hash(&data), data.len() as u64));
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct CrcChecker {
+    buffer: Vec<u8>,
+    hasher: crc32fast::Hasher,
+    bytes: u64,
+}
+
+impl Default for CrcChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CrcChecker {
+    /// A checker with its buffer allocated and nothing read yet
+    pub fn new() -> Self {
+        Self { buffer: vec![0; BUFFER_SIZE], hasher: crc32fast::Hasher::new(), bytes: 0 }
+    }
+
+    /// Read the next chunk of `reader` and hash it, returning the chunk; it is
+    /// empty once `reader` is at its end
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails; what was read before still counts
+    pub fn next_chunk(&mut self, reader: &mut impl Read) -> io::Result<&[u8]> {
+        let n = loop {
+            match reader.read(&mut self.buffer) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {},
+                result => break result?,
+            }
+        };
+        self.hasher.update(&self.buffer[..n]);
+        self.bytes += n as u64;
+        Ok(&self.buffer[..n])
+    }
+
+    /// CRC32 and size of the data read since the last call, starting over
+    pub fn finish(&mut self) -> (u32, u64) {
+        let hasher = std::mem::take(&mut self.hasher);
+        (hasher.finalize(), std::mem::take(&mut self.bytes))
+    }
+
+    /// CRC32 of everything `reader` yields
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails
+    pub fn crc32_of(&mut self, reader: &mut impl Read) -> io::Result<u32> {
+        self.finish();
+        while !self.next_chunk(reader)?.is_empty() {}
+        Ok(self.finish().0)
+    }
+}
+
+/// CRC32 of a file as stored on disk
+///
+/// The file's data is flushed and dropped from the page cache before reading, so
+/// on Linux it comes from the storage device rather than from memory. Elsewhere
+/// it may still be served from the cache.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be opened, synced or read
+pub fn read_back_crc32(path: &Path) -> io::Result<u32> {
+    let mut file = File::open(path)?;
+    file.sync_data()?;
+    fadvise_dontneed(&file, 0, 0);
+
+    crc32_of(&mut file)
+}
+
+/// CRC32 of everything `reader` yields
+///
+/// # Errors
+///
+/// Returns an error if reading fails
+pub(crate) fn crc32_of(reader: &mut impl Read) -> io::Result<u32> {
+    CrcChecker::new().crc32_of(reader)
+}
+
+/// Check that the file at `path` reads back as data with CRC32 `written`
+///
+/// # Errors
+///
+/// Returns [`UnzipError::VerifyFailed`] if the file differs, or an error if it
+/// cannot be read back
+pub fn verify_file(path: &Path, written: u32) -> Result<()> {
+    let read = read_back_crc32(path).map_err(UnzipError::file("verify file", path))?;
+    if read != written {
+        return Err(UnzipError::VerifyFailed { path: path.to_path_buf(), written, read });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_verify_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("data.bin");
+        fs::write(&path, b"written data").unwrap();
+
+        verify_file(&path, crc32fast::hash(b"written data")).unwrap();
+        let error = verify_file(&path, crc32fast::hash(b"other data")).unwrap_err();
+        assert!(matches!(error, UnzipError::VerifyFailed { .. }), "{:?}", error);
+        assert!(error.to_string().contains("verification failed"));
+
+        let missing = temp_dir.path().join("missing.bin");
+        assert!(matches!(verify_file(&missing, 0), Err(UnzipError::File { .. })));
+    }
+
+    #[test]
+    fn test_crc_checker_streams_and_restarts() {
+        let data: Vec<u8> = (0..3 * BUFFER_SIZE + 5).map(|i| (i % 253) as u8).collect();
+        let mut checker = CrcChecker::new();
+        let mut reader = &data[..];
+        let mut chunks = 0;
+        while !checker.next_chunk(&mut reader).unwrap().is_empty() {
+            chunks += 1;
+        }
+        assert_eq!(chunks, 4);
+        assert_eq!(checker.finish(), (crc32fast::hash(&data), data.len() as u64));
+
+        // The next check starts from nothing, even after a partial read
+        checker.next_chunk(&mut &b"partial"[..]).unwrap();
+        assert_eq!(checker.crc32_of(&mut &b"hello"[..]).unwrap(), crc32fast::hash(b"hello"));
+        assert_eq!(checker.finish(), (crc32fast::hash(b""), 0));
+    }
+}
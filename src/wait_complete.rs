@@ -0,0 +1,232 @@
+//! Waiting for archives that are still being written (`--wait-complete`)
+//!
+//! Ingest directories receive uploads through slow transfers, and a job started
+//! as soon as a file appears races the upload: it finds no end of central
+//! directory record yet, or reads a file that is still growing. With
+//! `--wait-complete[=TIMEOUT]` the archive is polled once a second until it is
+//! complete, which takes all of:
+//!
+//! - the file exists and its size did not change since the last poll, or it was
+//!   last modified more than a poll ago, so finished archives are not delayed;
+//! - the file ends with an archive, as [`locate_archive`] finds it: an end of
+//!   central directory record and its whole comment, with the central directory
+//!   the record counts back to, or a Zip64 locator, right before it.
+//!
+//! If TIMEOUT (10 minutes by default) passes first, the run fails with
+//! [`IncompleteArchive`], Info-ZIP's exit code 9 for an archive without a
+//! zipfile directory.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::time::Duration;
+//! use unzip::cancel::CancelToken;
+//! use unzip::wait_complete::{parse_wait_timeout, wait_complete};
+//!
+//! let dir = tempfile::tempdir()?;
+//! let path = dir.path().join("upload.zip");
+//! std::fs::write(&path, b"PK\x03\x04 half an upload")?;
+//! assert!(wait_complete(&path, Duration::ZERO, &CancelToken::new()).is_err());
+//! assert_eq!(parse_wait_timeout("90").unwrap(), Duration::from_secs(90));
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::cancel::CancelToken;
+use crate::checkpoint::parse_duration;
+use crate::error::{Result, UnzipError};
+use crate::sfx::locate_archive;
+
+/// How often the archive is looked at again
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Parse a `--wait-complete` timeout: seconds (`90`), or a time with a unit
+/// (`30s`, `5min`, `1h`)
+///
+/// # Errors
+///
+/// Returns a message suitable for clap if the value is not a time
+pub fn parse_wait_timeout(value: &str) -> std::result::Result<Duration, String> {
+    let value = value.trim();
+    value
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+        .or_else(|| parse_duration(value))
+        .ok_or_else(|| format!("invalid timeout: {} (expected seconds, or 30s, 5min, 1h)", value))
+}
+
+/// What was wrong with an archive the last time it was looked at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Incomplete {
+    /// The file does not exist
+    Missing,
+    /// Its size changed since the poll before
+    Growing,
+    /// No end of central directory record, or no central directory where it
+    /// says
+    NoDirectory,
+}
+
+impl fmt::Display for Incomplete {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Missing => "it does not exist",
+            Self::Growing => "it is still growing",
+            Self::NoDirectory => "it has no central directory yet",
+        })
+    }
+}
+
+/// The archive was not complete within the `--wait-complete` timeout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncompleteArchive {
+    /// The archive waited for
+    pub path: PathBuf,
+    /// How long it was waited for
+    pub timeout: Duration,
+    /// Why it was still incomplete at the end
+    pub state: Incomplete,
+}
+
+impl fmt::Display for IncompleteArchive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is not complete after waiting {}s: {}",
+            self.path.display(),
+            self.timeout.as_secs(),
+            self.state
+        )
+    }
+}
+
+impl std::error::Error for IncompleteArchive {}
+
+/// Wait until the archive at `path` is complete (see the module documentation),
+/// for at most `timeout`
+///
+/// # Errors
+///
+/// Returns [`IncompleteArchive`] once `timeout` passed, [`UnzipError::Cancelled`]
+/// if `cancel` fires, and an error if the file cannot be read
+pub fn wait_complete(path: &Path, timeout: Duration, cancel: &CancelToken) -> Result<()> {
+    let start = Instant::now();
+    let mut last_len = None;
+    loop {
+        cancel.check()?;
+        let state = match fs::metadata(path) {
+            Ok(metadata) => {
+                let len = metadata.len();
+                let settled = last_len == Some(len)
+                    || metadata
+                        .modified()
+                        .ok()
+                        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                        .is_some_and(|age| age > POLL_INTERVAL);
+                last_len = Some(len);
+                if !settled {
+                    Incomplete::Growing
+                } else if has_directory(path, len)
+                    .map_err(UnzipError::file("read archive", path))?
+                {
+                    return Ok(());
+                } else {
+                    Incomplete::NoDirectory
+                }
+            },
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Incomplete::Missing,
+            Err(error) => return Err(UnzipError::file("read archive", path)(error)),
+        };
+        let waited = start.elapsed();
+        if waited >= timeout {
+            return Err(IncompleteArchive { path: path.to_path_buf(), timeout, state }.into());
+        }
+        thread::sleep(POLL_INTERVAL.min(timeout - waited));
+    }
+}
+
+/// Whether the `len` bytes of the archive at `path` end with an end of central
+/// directory record whose central directory is in place
+///
+/// An archive that ends earlier, such as a ZIP stored inside a partial upload, or
+/// whose comment is cut short does not count.
+fn has_directory(path: &Path, len: u64) -> io::Result<bool> {
+    let found = locate_archive(&mut File::open(path)?)?;
+    Ok(found.is_some_and(|archive| !archive.comment_truncated && archive.end() == len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::records::CENTRAL_SIGNATURE;
+    use std::io::{Cursor, Write};
+    use zip::ZipWriter;
+    use zip::write::SimpleFileOptions;
+
+    fn archive() -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("a.txt", SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"hello").unwrap();
+        zip.set_comment("built by CI");
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_has_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.zip");
+        let data = archive();
+        let check = |data: &[u8]| {
+            fs::write(&path, data).unwrap();
+            has_directory(&path, data.len() as u64).unwrap()
+        };
+        assert!(check(&data));
+        // Cut in the central directory, or before it
+        let central = data.windows(4).position(|w| w == CENTRAL_SIGNATURE.to_le_bytes()).unwrap();
+        assert!(!check(&data[..central + 10]));
+        assert!(!check(&data[..central]));
+        // The end record of an earlier, shorter archive left at the end
+        let mut rewritten = data[..central].to_vec();
+        rewritten.extend_from_slice(&data[data.len() - 33..]);
+        assert!(!check(&rewritten));
+        // A complete archive with the rest of the upload still to come
+        let mut growing = data.clone();
+        growing.extend_from_slice(b"PK\x03\x04 next member");
+        assert!(!check(&growing));
+    }
+
+    #[test]
+    fn test_wait_complete() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("upload.zip");
+        let cancel = CancelToken::new();
+
+        let err = wait_complete(&path, Duration::ZERO, &cancel).unwrap_err();
+        let UnzipError::Incomplete(incomplete) = err else {
+            panic!("{}", err)
+        };
+        assert_eq!(incomplete.state, Incomplete::Missing);
+
+        // Written just now: seen twice at the same size before it counts
+        fs::write(&path, archive()).unwrap();
+        wait_complete(&path, Duration::from_secs(5), &cancel).unwrap();
+
+        cancel.cancel();
+        let err = wait_complete(&path, Duration::from_secs(5), &cancel).unwrap_err();
+        assert!(matches!(err, UnzipError::Cancelled), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_wait_timeout() {
+        assert_eq!(parse_wait_timeout("30").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_wait_timeout("5min").unwrap(), Duration::from_secs(300));
+        assert!(parse_wait_timeout("soon").is_err());
+    }
+}
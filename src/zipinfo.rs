@@ -28,11 +28,18 @@
 //! # Ok::<(), Box<dyn std::error::Error>>(())
 //! ```
 
-use anyhow::Result;
 use std::io::{Read, Seek, Write};
-use zip::ZipArchive;
+use zip::{HasZipMetadata, ZipArchive};
 
 use crate::args::Args;
+use crate::charset::NameDecoder;
+use crate::encryption::entry_scheme;
+use crate::error::Result;
+use crate::invalid_names::check_names;
+use crate::permissions::display_mode;
+use crate::timestamps::{
+    DOS_TIME_RESOLUTION, ExtraTimes, ExtraTimestamps, dos_time_skew, format_utc,
+};
 use crate::utils::PatternMatcher;
 
 struct DateTimeCache {
@@ -150,6 +157,11 @@ impl ZipinfoMode {
     }
 }
 
+/// Whether `word` names a zipinfo mode, as in `-Z l` or `-Z -v`
+pub(crate) fn is_zipinfo_mode(word: &str) -> bool {
+    ZipinfoMode::from_str(word).is_some()
+}
+
 /// Display zipinfo output for the archive
 ///
 /// Shows detailed technical information about files in the ZIP archive in
@@ -181,7 +193,9 @@ impl ZipinfoMode {
 pub fn display_zipinfo<R: Read + Seek>(archive: &mut ZipArchive<R>, args: &Args) -> Result<()> {
     let stdout = std::io::stdout();
     let mut out = std::io::BufWriter::new(stdout.lock());
-    let matcher = PatternMatcher::new(&args.patterns, &args.exclude, args.case_insensitive);
+    let matcher = args.matcher();
+    let names = NameDecoder::from_args(args)?;
+    check_names(archive, &names)?;
     let use_filters = !(args.patterns.is_empty() && args.exclude.is_empty());
     let mut datetime_cache = DateTimeCache::new();
     // Determine mode from zipinfo argument
@@ -193,13 +207,14 @@ pub fn display_zipinfo<R: Read + Seek>(archive: &mut ZipArchive<R>, args: &Args)
 
     // Print header (except for FilenamesOnly mode)
     if mode != ZipinfoMode::FilenamesOnly && args.quiet == 0 {
-        print_header(&mut out, archive, args, &matcher, use_filters)?;
+        print_header(&mut out, archive, args, &matcher, &names, use_filters)?;
     }
 
     // Print file entries
     for i in 0..archive.len() {
         let file = archive.by_index_raw(i)?;
-        let name = file.name();
+        let name = names.decode(&file);
+        let name = name.as_ref();
 
         if use_filters && !matcher.should_extract(name) {
             continue;
@@ -216,10 +231,19 @@ pub fn display_zipinfo<R: Read + Seek>(archive: &mut ZipArchive<R>, args: &Args)
                 print_medium_format(&mut out, &file, name, &mut datetime_cache)?;
             },
             ZipinfoMode::Long => {
-                print_long_format(&mut out, &file, name, &mut datetime_cache)?;
+                print_long_format(&mut out, &file, name, args.extended_times, &mut datetime_cache)?;
             },
             ZipinfoMode::Verbose => {
-                print_verbose_format(&mut out, &file, name, &mut datetime_cache)?;
+                let meta = file.get_metadata();
+                let invalid = names.is_invalid(file.name_raw(), meta.is_utf8, meta.system as u8);
+                print_verbose_format(
+                    &mut out,
+                    &file,
+                    name,
+                    invalid.then(|| file.name_raw()),
+                    args.extended_times,
+                    &mut datetime_cache,
+                )?;
             },
         }
     }
@@ -238,14 +262,15 @@ fn print_header<R: Read + Seek>(
     archive: &mut ZipArchive<R>,
     args: &Args,
     matcher: &PatternMatcher,
+    names: &NameDecoder,
     use_filters: bool,
 ) -> Result<()> {
     let mut total_size: u64 = 0;
     let mut file_count: usize = 0;
     for i in 0..archive.len() {
         if let Ok(f) = archive.by_index_raw(i) {
-            let name = f.name();
-            if !use_filters || matcher.should_extract(name) {
+            let name = names.decode(&f);
+            if !use_filters || matcher.should_extract(&name) {
                 total_size += f.size();
                 file_count += 1;
             }
@@ -370,10 +395,13 @@ fn print_medium_format(
 
 /// Print file entry in long format (with compressed size)
 /// Format: -rw-rws---  1.5 unx    2802 t-     538 defX 11-Aug-91 13:48 perms.2660
+///
+/// With `extended_times`, the UT time follows the DOS time (see [`write_ut_column`]).
 fn print_long_format(
     out: &mut dyn Write,
     file: &zip::read::ZipFile,
     name: &str,
+    extended_times: bool,
     datetime_cache: &mut DateTimeCache,
 ) -> Result<()> {
     let perms = format_permissions(file);
@@ -407,6 +435,9 @@ fn print_long_format(
     out.write_all(b" ")?;
     out.write_all(datetime.as_bytes())?;
     out.write_all(b" ")?;
+    if extended_times {
+        write_ut_column(out, file)?;
+    }
     out.write_all(name.as_bytes())?;
     out.write_all(b"\n")?;
 
@@ -414,16 +445,24 @@ fn print_long_format(
 }
 
 /// Print file entry in verbose format (detailed multi-line)
+///
+/// `raw_name` is given for names that do not decode, and printed as hex bytes.
 fn print_verbose_format(
     out: &mut dyn Write,
     file: &zip::read::ZipFile,
     name: &str,
+    raw_name: Option<&[u8]>,
+    extended_times: bool,
     datetime_cache: &mut DateTimeCache,
 ) -> Result<()> {
     let mut num_buf = [0u8; 32];
     out.write_all(b"File: ")?;
     out.write_all(name.as_bytes())?;
     out.write_all(b"\n")?;
+    if let Some(raw_name) = raw_name {
+        let hex: Vec<String> = raw_name.iter().map(|byte| format!("{:02x}", byte)).collect();
+        writeln!(out, "  Raw name:          {} (does not decode)", hex.join(" "))?;
+    }
 
     let comp = file.compressed_size();
     let comp_len = write_u64(&mut num_buf, comp);
@@ -449,7 +488,7 @@ fn print_verbose_format(
     out.write_all(b"%\n")?;
 
     out.write_all(b"  Compression method: ")?;
-    out.write_all(format_method(file).as_bytes())?;
+    out.write_all(format_method(file).trim_end().as_bytes())?;
     out.write_all(b"\n")?;
 
     let crc = file.crc32();
@@ -471,6 +510,20 @@ fn print_verbose_format(
     out.write_all(datetime_cache.as_str(file.last_modified()).as_bytes())?;
     out.write_all(b"\n")?;
 
+    let extra_times = ExtraTimestamps::from_entry(file);
+    if let Some(times) = extra_times.unix {
+        write_extra_times(out, "UT", &times)?;
+    }
+    if let Some(times) = extra_times.ntfs {
+        write_extra_times(out, "NTFS", &times)?;
+    }
+    if extended_times
+        && let Some(skew) = dos_time_skew(file)
+        && skew.abs() > DOS_TIME_RESOLUTION
+    {
+        writeln!(out, "  Time mismatch:     UT is {:+} s from the DOS time", skew)?;
+    }
+
     out.write_all(b"  OS:                ")?;
     out.write_all(format_os(file).as_bytes())?;
     out.write_all(b"\n")?;
@@ -478,32 +531,51 @@ fn print_verbose_format(
     out.write_all(b"  Version made by:   ")?;
     out.write_all(format_version(file).as_bytes())?;
     out.write_all(b"\n")?;
-    if file.encrypted() {
-        out.write_all(b"  Encrypted:         Yes\n")?;
+    let scheme = entry_scheme(file);
+    if scheme.is_encrypted() {
+        writeln!(out, "  Encrypted:         {}", scheme)?;
     }
     out.write_all(b"\n")?;
 
     Ok(())
 }
 
-/// Format file permissions in Unix style
-fn format_permissions(file: &zip::read::ZipFile) -> String {
-    #[cfg(unix)]
-    {
-        if let Some(mode) = file.unix_mode() {
-            return format_unix_mode(mode);
-        }
+/// Print the UT modification time as an extra long-format column
+///
+/// The column is `-` without a UT field, and the time ends in `!` when it is more
+/// than [`DOS_TIME_RESOLUTION`] away from the DOS time.
+fn write_ut_column(out: &mut dyn Write, file: &zip::read::ZipFile) -> Result<()> {
+    let ut = ExtraTimestamps::from_entry(file).unix.and_then(|times| times.modified);
+    match ut {
+        Some(time) => {
+            let mismatch = dos_time_skew(file).is_some_and(|skew| skew.abs() > DOS_TIME_RESOLUTION);
+            write!(out, "{}{} ", format_utc(time), if mismatch { '!' } else { ' ' })?;
+        },
+        None => write!(out, "{:<25}", "-")?,
     }
+    Ok(())
+}
 
-    // Default permissions for non-Unix or when not available
-    if file.is_dir() {
-        "drwxr-xr-x".to_string()
-    } else {
-        "-rw-r--r--".to_string()
+/// Print the times from one timestamp extra field, aligned with the other fields
+fn write_extra_times(out: &mut dyn Write, source: &str, times: &ExtraTimes) -> Result<()> {
+    for (kind, time) in [
+        ("Modified", times.modified),
+        ("Accessed", times.accessed),
+        ("Created", times.created),
+    ] {
+        if let Some(time) = time {
+            let label = format!("{} ({}):", kind, source);
+            writeln!(out, "  {:<19}{}", label, format_utc(time))?;
+        }
     }
+    Ok(())
+}
+
+/// Format file permissions in Unix style, interpreted for the host that made the entry
+fn format_permissions(file: &zip::read::ZipFile) -> String {
+    format_unix_mode(display_mode(file))
 }
 
-#[cfg(unix)]
 fn format_unix_mode(mode: u32) -> String {
     let file_type = if mode & 0o040000 != 0 { 'd' } else { '-' };
 
@@ -560,7 +632,21 @@ fn format_method(file: &zip::read::ZipFile) -> &'static str {
         zip::CompressionMethod::Stored => "stor",
         zip::CompressionMethod::Deflated => "defN", // Default to normal
         zip::CompressionMethod::Bzip2 => "bzp2",
+        zip::CompressionMethod::Lzma => "lzma",
+        // Padded to the four columns of the other names
+        zip::CompressionMethod::Xz => "xz  ",
         zip::CompressionMethod::Zstd => "zstd",
+        #[allow(deprecated)]
+        zip::CompressionMethod::Unsupported(code) => match code {
+            1 => "shrk",
+            2 => "re:1",
+            3 => "re:2",
+            4 => "re:3",
+            5 => "re:4",
+            6 => "impl",
+            98 => "ppmd",
+            _ => "unkn",
+        },
         _ => "unkn",
     }
 }
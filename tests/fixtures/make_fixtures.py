@@ -0,0 +1,130 @@
+#!/usr/bin/env python3
+"""Regenerate the fixture archives of the listing snapshot tests.
+
+The archives are committed, so the snapshots do not depend on this script or on
+the tools it runs; it documents how they were made. Run it from this directory,
+then review the snapshot changes with `cargo insta review` (or rerun the tests
+with INSTA_UPDATE=always) before committing both.
+
+encrypted.zip needs Info-ZIP's `zip`, since Python cannot write ZipCrypto; its
+12-byte encryption headers are random, so regenerating it changes its bytes but
+not its listings.
+"""
+
+import os
+import struct
+import subprocess
+import tempfile
+import zipfile
+import zlib
+
+TIME = (2024, 3, 15, 12, 34, 56)
+UNIX = 3
+
+
+def entry(name, mode, comment=b"", method=zipfile.ZIP_DEFLATED):
+    info = zipfile.ZipInfo(name, date_time=TIME)
+    info.create_system = UNIX
+    info.external_attr = mode << 16
+    if name.endswith("/"):
+        info.external_attr |= 0x10
+    info.compress_type = method
+    info.comment = comment
+    return info
+
+
+def basic():
+    with zipfile.ZipFile("basic.zip", "w") as zf:
+        zf.comment = b"Release 1.0\nbuilt for the listing tests"
+        zf.writestr(entry("docs/", 0o040755, method=zipfile.ZIP_STORED), b"")
+        zf.writestr(
+            entry("docs/readme.txt", 0o100644, comment=b"read me first"),
+            b"The quick brown fox jumps over the lazy dog.\n" * 20,
+        )
+        zf.writestr(entry("bin/run.sh", 0o100755), b"#!/bin/sh\nexec ./app \"$@\"\n")
+        zf.writestr(entry("data.bin", 0o100600, method=zipfile.ZIP_STORED), bytes(range(256)))
+        zf.writestr(entry("empty.txt", 0o100644, method=zipfile.ZIP_STORED), b"")
+
+
+def zip64():
+    # Python only writes Zip64 fields an archive needs, so these Stored entries
+    # are written by hand with every size and offset in Zip64 extra fields and
+    # the end records, as streaming archivers do
+    dos_time = (TIME[3] << 11) | (TIME[4] << 5) | (TIME[5] // 2)
+    dos_date = ((TIME[0] - 1980) << 9) | (TIME[1] << 5) | TIME[2]
+    out = bytearray()
+    central = bytearray()
+    entries = [(b"big.log", b"line\n" * 1000), (b"small.txt", b"tiny\n")]
+    for name, data in entries:
+        offset = len(out)
+        crc = zlib.crc32(data)
+        local_extra = struct.pack("<HHQQ", 1, 16, len(data), len(data))
+        out += struct.pack(
+            "<IHHHHHIIIHH", 0x04034B50, 45, 0, 0, dos_time, dos_date, crc,
+            0xFFFFFFFF, 0xFFFFFFFF, len(name), len(local_extra),
+        )
+        out += name + local_extra + data
+        extra = struct.pack("<HHQQQ", 1, 24, len(data), len(data), offset)
+        central += struct.pack(
+            "<IHHHHHHIIIHHHHHII", 0x02014B50, (UNIX << 8) | 45, 45, 0, 0, dos_time,
+            dos_date, crc, 0xFFFFFFFF, 0xFFFFFFFF, len(name), len(extra), 0, 0, 0,
+            0o100644 << 16, 0xFFFFFFFF,
+        )
+        central += name + extra
+    cd_offset = len(out)
+    out += central
+    eocd64_offset = len(out)
+    count = len(entries)
+    out += struct.pack(
+        "<IQHHIIQQQQ", 0x06064B50, 44, (UNIX << 8) | 45, 45, 0, 0, count, count,
+        len(central), cd_offset,
+    )
+    out += struct.pack("<IIQI", 0x07064B50, 0, eocd64_offset, 1)
+    out += struct.pack("<IHHHHIIH", 0x06054B50, 0, 0, 0xFFFF, 0xFFFF, 0xFFFFFFFF, 0xFFFFFFFF, 0)
+    with open("zip64.zip", "wb") as f:
+        f.write(out)
+
+
+def symlink():
+    with zipfile.ZipFile("symlink.zip", "w") as zf:
+        zf.writestr(entry("target.txt", 0o100644), b"pointed at\n")
+        zf.writestr(entry("link", 0o120777, method=zipfile.ZIP_STORED), b"target.txt")
+        zf.writestr(entry("dir/", 0o040755, method=zipfile.ZIP_STORED), b"")
+        zf.writestr(entry("dir/up", 0o120777, method=zipfile.ZIP_STORED), b"../target.txt")
+
+
+def names():
+    # Python flags every non-ASCII name as UTF-8, so the CP437 name is written
+    # with a placeholder byte and patched into both headers afterwards
+    with zipfile.ZipFile("names.zip", "w") as zf:
+        zf.writestr(entry("caf#.txt", 0o100644), b"cp437 name\n")
+        zf.writestr(entry("日本語/ファイル.txt", 0o100644), b"utf-8 name\n")
+        zf.writestr(entry("spaces in name.txt", 0o100644), b"spaces\n")
+    with open("names.zip", "rb") as f:
+        data = f.read()
+    assert data.count(b"caf#.txt") == 2
+    with open("names.zip", "wb") as f:
+        f.write(data.replace(b"caf#.txt", b"caf\x82.txt"))
+
+
+def encrypted():
+    with tempfile.TemporaryDirectory() as tmp:
+        for name, data in [("secret.txt", b"attack at dawn\n" * 4), ("plain.txt", b"hello\n")]:
+            path = os.path.join(tmp, name)
+            with open(path, "wb") as f:
+                f.write(data)
+            os.utime(path, (1710506096, 1710506096))
+        out = os.path.abspath("encrypted.zip")
+        if os.path.exists(out):
+            os.remove(out)
+        env = dict(os.environ, TZ="UTC")
+        subprocess.run(["zip", "-qX", "-P", "secret", out, "secret.txt"], cwd=tmp, env=env, check=True)
+        subprocess.run(["zip", "-qX", out, "plain.txt"], cwd=tmp, env=env, check=True)
+
+
+if __name__ == "__main__":
+    basic()
+    zip64()
+    symlink()
+    names()
+    encrypted()
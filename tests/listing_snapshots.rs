@@ -0,0 +1,79 @@
+//! Golden-output tests for every listing format
+//!
+//! Scripts parse `-l`, `-v`, `-z` and zipinfo output, so a change to it breaks
+//! them as surely as an API change breaks callers. Each format is run over the
+//! fixture archives in `tests/fixtures` (see `make_fixtures.py` there) and its
+//! stdout compared byte for byte with the snapshots in `tests/snapshots`.
+//!
+//! After an intended change, review and accept the new output with
+//! `cargo insta review`, or rerun with `INSTA_UPDATE=always` and check the diff.
+
+use std::path::Path;
+use std::process::Command;
+
+/// Fixture archives: plain entries with comments, Zip64 fields, ZipCrypto
+/// encryption, symlinks, and CP437/UTF-8 names
+const FIXTURES: &[&str] = &["basic", "zip64", "encrypted", "symlink", "names"];
+
+/// Run the binary in the fixture directory and return its stdout
+fn run(args: &[&str]) -> String {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let output = Command::new(env!("CARGO_BIN_EXE_unzip"))
+        .args(args)
+        .current_dir(fixtures)
+        .env("TZ", "UTC")
+        .output()
+        .expect("run unzip");
+    assert!(
+        output.status.success(),
+        "unzip {} failed: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).expect("listing is UTF-8")
+}
+
+/// Snapshot `flags` over every fixture, as `<fixture>_<label>`
+fn snapshot_all(label: &str, flags: &[&str]) {
+    for fixture in FIXTURES {
+        let archive = format!("{}.zip", fixture);
+        let mut args = flags.to_vec();
+        args.push(&archive);
+        insta::assert_snapshot!(format!("{}_{}", fixture, label), run(&args));
+    }
+}
+
+#[test]
+fn test_list_short_matches_snapshots() {
+    snapshot_all("l_infozip", &["-l"]);
+    snapshot_all("l_infozip", &["--compat=infozip", "-l"]);
+}
+
+#[test]
+fn test_list_verbose_matches_snapshots() {
+    snapshot_all("v_infozip", &["-v"]);
+    snapshot_all("v_infozip", &["--compat=infozip", "-v"]);
+}
+
+#[test]
+fn test_list_pretty_matches_snapshots() {
+    snapshot_all("l_pretty", &["--format", "pretty", "-l"]);
+    snapshot_all("v_pretty", &["--format", "pretty", "-v"]);
+}
+
+#[test]
+fn test_comment_matches_snapshots() {
+    snapshot_all("z", &["-z"]);
+}
+
+#[test]
+fn test_zipinfo_modes_match_snapshots() {
+    for mode in ["1", "2", "s", "m", "l", "v"] {
+        snapshot_all(&format!("zipinfo_{}", mode), &["-Z", mode]);
+    }
+}
+
+#[test]
+fn test_zipinfo_extended_times_matches_snapshots() {
+    snapshot_all("zipinfo_l_extended", &["-Z", "l", "--extended-times"]);
+}